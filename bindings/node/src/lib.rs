@@ -0,0 +1,147 @@
+//! Node.js bindings: `convert(buffer, options)` and `metadata(buffer)` for
+//! PDFs held in memory, so applications can convert without shipping the
+//! `pdf2md` binary for every platform.
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use pdf2md::config::Config;
+
+/// Options of `convert`; each defaults to the command-line default
+#[napi(object)]
+#[derive(Default)]
+pub struct ConvertOptions {
+    /// Emit text set in math fonts as LaTeX
+    pub math: Option<bool>,
+    /// Detect footnotes and write them as "inline", "reference" or "notes"
+    pub footnotes: Option<String>,
+    /// Write term/definition layouts as definition lists
+    pub definition_lists: Option<bool>,
+    /// Write horizontal lines drawn across the page as `---`
+    pub horizontal_rules: Option<bool>,
+    /// Mark up underlined text as "emphasis", "bold" or "html"
+    pub underline: Option<String>,
+    /// Write struck-through text as `~~text~~`
+    pub strikethrough: Option<bool>,
+    /// Wrap coloured text in `<span style="color:...">`
+    pub text_colors: Option<bool>,
+    /// Make the heading levels consistent
+    pub normalize_headings: Option<bool>,
+    /// Number the headings (1, 1.1, ...)
+    pub number_headings: Option<bool>,
+    /// Remove numbers from the headings
+    pub strip_heading_numbers: Option<bool>,
+    /// Split the output into chat-sized sections
+    pub chat: Option<bool>,
+    /// Fail on the first page that cannot be extracted
+    pub strict: Option<bool>,
+}
+
+impl ConvertOptions {
+    fn config(self) -> Result<Config> {
+        Ok(Config {
+            math: self.math.unwrap_or(false),
+            footnotes: self.footnotes.as_deref().map(parse).transpose()?,
+            definition_lists: self.definition_lists.unwrap_or(false),
+            horizontal_rules: self.horizontal_rules.unwrap_or(false),
+            underline: self.underline.as_deref().map(parse).transpose()?,
+            strikethrough: self.strikethrough.unwrap_or(false),
+            text_colors: self.text_colors.unwrap_or(false),
+            normalize_headings: self.normalize_headings.unwrap_or(false),
+            number_headings: self.number_headings.unwrap_or(false),
+            strip_heading_numbers: self.strip_heading_numbers.unwrap_or(false),
+            chat: self.chat.unwrap_or(false),
+            strict: self.strict.unwrap_or(false),
+            // The command line's default section size
+            chat_section_limit: 1900,
+            ..Default::default()
+        })
+    }
+}
+
+fn parse<T: std::str::FromStr<Err = String>>(value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|e: String| Error::new(Status::InvalidArg, e))
+}
+
+fn error(e: impl std::fmt::Display) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// Background conversion, so the event loop keeps running
+pub struct Convert {
+    bytes: Vec<u8>,
+    config: Config,
+}
+
+impl Task for Convert {
+    type Output = String;
+    type JsValue = String;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        pdf2md::convert_bytes(&self.bytes, &self.config).map_err(error)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+/// Convert a PDF to Markdown on a worker thread; resolves to the Markdown
+#[napi(ts_return_type = "Promise<string>")]
+pub fn convert(buffer: Buffer, options: Option<ConvertOptions>) -> Result<AsyncTask<Convert>> {
+    Ok(AsyncTask::new(Convert {
+        bytes: buffer.to_vec(),
+        config: options.unwrap_or_default().config()?,
+    }))
+}
+
+/// Convert a PDF to Markdown, blocking until it is done
+#[napi]
+pub fn convert_sync(buffer: Buffer, options: Option<ConvertOptions>) -> Result<String> {
+    let config = options.unwrap_or_default().config()?;
+    pdf2md::convert_bytes(&buffer, &config).map_err(error)
+}
+
+/// Document information of a PDF
+#[napi(object)]
+pub struct Metadata {
+    pub page_count: u32,
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// Application the document was created in
+    pub creator: Option<String>,
+    /// Application that wrote the PDF
+    pub producer: Option<String>,
+    /// Creation date, as RFC 3339
+    pub creation_date: Option<String>,
+    /// Modification date, as RFC 3339
+    pub modification_date: Option<String>,
+    /// Whether the pages have extractable text, rather than only scans
+    pub has_text: bool,
+    /// PDF version from the file header (e.g. "1.7")
+    pub version: String,
+}
+
+/// Read the document information of a PDF without converting it
+#[napi]
+pub fn metadata(buffer: Buffer) -> Result<Metadata> {
+    let doc = pdf_extract::PdfDocument::from_bytes(&buffer).map_err(error)?;
+    let info = doc.extract_metadata().map_err(error)?;
+    let compatibility = doc.compatibility().map_err(error)?;
+    Ok(Metadata {
+        page_count: info.page_count as u32,
+        title: info.title,
+        author: info.author,
+        subject: info.subject,
+        keywords: info.keywords,
+        creator: info.creator,
+        producer: info.producer,
+        creation_date: info.creation_date,
+        modification_date: info.modification_date,
+        has_text: info.has_text,
+        version: compatibility.version,
+    })
+}
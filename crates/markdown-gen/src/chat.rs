@@ -0,0 +1,152 @@
+use crate::headings::heading;
+
+/// Rewrite Markdown for chat apps (Slack, Discord).
+///
+/// Headings become bold lines, tables become one line per row and every
+/// section (a heading and the text under it) is cut to `max_section_chars`
+/// characters so a snippet fits in a single message. A limit of 0 keeps
+/// sections whole.
+pub fn to_chat(markdown: &str, max_section_chars: usize) -> String {
+    let mut sections: Vec<String> = vec![String::new()];
+    let mut table: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if !in_fence && is_table_row(trimmed) {
+            table.push(trimmed);
+            continue;
+        }
+        let section = sections.last_mut().expect("at least one section");
+        flush_table(&mut table, section);
+
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && let Some((_, heading)) = heading(trimmed) {
+            sections.push(format!("**{}**\n", heading));
+            continue;
+        }
+        let section = sections.last_mut().expect("at least one section");
+        section.push_str(line);
+        section.push('\n');
+    }
+    flush_table(
+        &mut table,
+        sections.last_mut().expect("at least one section"),
+    );
+
+    sections
+        .iter()
+        .map(|section| truncate_section(section.trim(), max_section_chars))
+        .filter(|section| !section.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn is_table_row(line: &str) -> bool {
+    line.len() > 1 && line.starts_with('|') && line.ends_with('|')
+}
+
+/// Write buffered table rows as `a · b` lines, the header row in bold
+fn flush_table(rows: &mut Vec<&str>, out: &mut String) {
+    let has_header = rows.get(1).is_some_and(|row| is_separator_row(row));
+    for (i, row) in rows.iter().enumerate() {
+        if is_separator_row(row) {
+            continue;
+        }
+        let cells: Vec<&str> = row
+            .trim_matches('|')
+            .split('|')
+            .map(str::trim)
+            .filter(|cell| !cell.is_empty())
+            .collect();
+        if cells.is_empty() {
+            continue;
+        }
+        let line = cells.join(" · ");
+        if i == 0 && has_header {
+            out.push_str(&format!("**{}**\n", line));
+        } else {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    rows.clear();
+}
+
+/// `|---|:--:|` line between a table's header and body
+fn is_separator_row(row: &str) -> bool {
+    row.contains('-') && row.chars().all(|c| matches!(c, '|' | '-' | ':' | ' '))
+}
+
+/// Cut a section to `max_chars` characters at a word boundary, closing any open code fence
+fn truncate_section(section: &str, max_chars: usize) -> String {
+    if max_chars == 0 || section.chars().count() <= max_chars {
+        return section.to_string();
+    }
+
+    // Leave room for the ellipsis and a closing fence
+    let budget = max_chars.saturating_sub(5).max(1);
+    let end = section
+        .char_indices()
+        .nth(budget)
+        .map_or(section.len(), |(i, _)| i);
+    let cut = match section[..end].rfind(char::is_whitespace) {
+        Some(space) if space > 0 => &section[..space],
+        _ => &section[..end],
+    };
+
+    let mut out = cut.trim_end().to_string();
+    let fences = out
+        .lines()
+        .filter(|line| line.trim_start().starts_with("```"))
+        .count();
+    out.push('…');
+    if fences % 2 == 1 {
+        out.push_str("\n```");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headings_become_bold_lines() {
+        let markdown = "## Results\n\nSales grew.\n\n```\n## not a heading\n```";
+        assert_eq!(
+            to_chat(markdown, 0),
+            "**Results**\n\nSales grew.\n\n```\n## not a heading\n```"
+        );
+    }
+
+    #[test]
+    fn test_tables_become_rows() {
+        let markdown = "| Region | Sales |\n|--------|------:|\n| North | 10 |\n| South | 12 |";
+        assert_eq!(
+            to_chat(markdown, 0),
+            "**Region · Sales**\nNorth · 10\nSouth · 12"
+        );
+    }
+
+    #[test]
+    fn test_sections_are_length_capped() {
+        let body = "word ".repeat(100);
+        let markdown = format!("## First\n\n{}\n\n## Second\n\nShort.", body);
+        let chat = to_chat(&markdown, 60);
+        let sections: Vec<&str> = chat.split("\n\n**").collect();
+        assert!(sections[0].starts_with("**First**"));
+        assert!(sections[0].ends_with('…'));
+        assert!(sections[0].chars().count() <= 60);
+        assert_eq!(sections[1], "Second**\n\nShort.");
+    }
+
+    #[test]
+    fn test_truncation_closes_code_fence() {
+        let section = format!("```\n{}\n```", "x = 1\n".repeat(50));
+        let cut = truncate_section(&section, 40);
+        assert!(cut.ends_with("…\n```"));
+        assert!(cut.chars().count() <= 40);
+    }
+}
@@ -1,7 +1,30 @@
 use log::debug;
 
+/// Thresholds of the heading detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FormatOptions {
+    /// Shortest line, in characters, taken for an all-caps heading
+    pub min_heading_chars: usize,
+    /// Longest line, in characters, taken for an all-caps heading
+    pub max_heading_chars: usize,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        Self {
+            min_heading_chars: 1,
+            max_heading_chars: 59,
+        }
+    }
+}
+
 /// Format text content as Markdown
 pub fn format_content(text: &str) -> String {
+    format_content_with(text, &FormatOptions::default())
+}
+
+/// Format text content as Markdown with the given heading thresholds
+pub fn format_content_with(text: &str, options: &FormatOptions) -> String {
     debug!("Formatting content as Markdown");
 
     // Split into paragraphs (separated by blank lines)
@@ -14,15 +37,84 @@ pub fn format_content(text: &str) -> String {
     // Format each paragraph
     let formatted_paragraphs: Vec<String> = paragraphs
         .iter()
-        .map(|para| format_paragraph(para))
+        .map(|para| format_paragraph(para, options))
         .collect();
 
     // Join paragraphs with double newlines
     formatted_paragraphs.join("\n\n")
 }
 
+/// Formats text as it arrives, a paragraph at a time, into the Markdown
+/// `format_content_with` gives for the whole text
+#[derive(Debug)]
+pub struct StreamingFormatter {
+    options: FormatOptions,
+    /// Text of the paragraph still being received
+    pending: String,
+    /// Whether a paragraph was written, so the next one is separated from it
+    started: bool,
+}
+
+impl StreamingFormatter {
+    pub fn new(options: FormatOptions) -> Self {
+        Self {
+            options,
+            pending: String::new(),
+            started: false,
+        }
+    }
+
+    /// Add the next piece of text; returns the Markdown of the paragraphs it
+    /// completed
+    pub fn push(&mut self, text: &str) -> String {
+        // A paragraph break may straddle the previous piece and this one
+        let from = self.pending.len().saturating_sub(1);
+        self.pending.push_str(text);
+        let Some(end) = self.pending.as_bytes()[from..]
+            .windows(2)
+            .rposition(|pair| pair == b"\n\n")
+            .map(|i| from + i)
+        else {
+            return String::new();
+        };
+        let markdown = format_paragraphs(
+            &self.pending[..end],
+            &self.options,
+            &mut self.started,
+        );
+        self.pending.drain(..end + 2);
+        markdown
+    }
+
+    /// The Markdown of the last paragraph
+    pub fn finish(mut self) -> String {
+        format_paragraphs(&self.pending, &self.options, &mut self.started)
+    }
+}
+
+/// Markdown of complete paragraphs, separated from any written before
+fn format_paragraphs(text: &str, options: &FormatOptions, started: &mut bool) -> String {
+    let mut markdown = String::with_capacity(text.len() + 8);
+    for para in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if *started {
+            markdown.push_str("\n\n");
+        }
+        *started = true;
+        markdown.push_str(&format_paragraph(para, options));
+    }
+    markdown
+}
+
 /// Format a single paragraph
-fn format_paragraph(para: &str) -> String {
+fn format_paragraph(para: &str, options: &FormatOptions) -> String {
+    // Definition list items ("Term\n: definition") keep their line break
+    if let Some((term, definition)) = para.split_once("\n: ")
+        && !term.contains('\n')
+    {
+        let collapse = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+        return format!("{}\n: {}", collapse(term), collapse(definition));
+    }
+
     // Replace single newlines within a paragraph with spaces
     // (PDFs often break mid-sentence)
     let single_line = para.replace('\n', " ");
@@ -39,7 +131,7 @@ fn format_paragraph(para: &str) -> String {
         format!("## {}\n\n{}", header, content)
     }
     // Detect potential headers (all caps, short lines, etc.)
-    else if is_potential_header(&cleaned) {
+    else if is_potential_header(&cleaned, options) {
         format!("## {}", cleaned)
     } else {
         cleaned
@@ -68,14 +160,13 @@ fn split_concatenated_header(text: &str) -> Option<(String, String)> {
     ];
 
     for header in &potential_headers {
-        if text.starts_with(header) {
-            let rest = &text[header.len()..];
+        if let Some(rest) = text.strip_prefix(header) {
             // Check if the next character is uppercase (not space)
-            if let Some(first_char) = rest.chars().next() {
-                if first_char.is_uppercase() {
-                    // Found a concatenated header
-                    return Some((header.to_string(), rest.to_string()));
-                }
+            if let Some(first_char) = rest.chars().next()
+                && first_char.is_uppercase()
+            {
+                // Found a concatenated header
+                return Some((header.to_string(), rest.to_string()));
             }
         }
     }
@@ -84,13 +175,21 @@ fn split_concatenated_header(text: &str) -> Option<(String, String)> {
 }
 
 /// Heuristic to detect if a line might be a header
-fn is_potential_header(text: &str) -> bool {
+fn is_potential_header(text: &str, options: &FormatOptions) -> bool {
+    // Display math like "$$E = MC^2$$" is never a header
+    if text.starts_with('$') {
+        return false;
+    }
+
     // Short lines that are all caps might be headers
-    if text.len() < 60 && text.chars().all(|c| !c.is_lowercase() || !c.is_alphabetic()) {
+    let length = text.chars().count();
+    if (options.min_heading_chars..=options.max_heading_chars).contains(&length)
+        && text.chars().all(|c| !c.is_lowercase() || !c.is_alphabetic())
+    {
         // Check if mostly uppercase letters
         let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
         let uppercase_count = letters.iter().filter(|c| c.is_uppercase()).count();
-        letters.len() > 0 && (uppercase_count as f32 / letters.len() as f32) > 0.7
+        !letters.is_empty() && (uppercase_count as f32 / letters.len() as f32) > 0.7
     } else {
         false
     }
@@ -127,6 +226,13 @@ mod tests {
         assert_eq!(markdown, "This is a sentence that was broken across lines.");
     }
 
+    #[test]
+    fn test_format_content_keeps_definition_lists() {
+        let text = "Latency\n: Time taken by\none request.\n\nPlain text.";
+        let markdown = format_content(text);
+        assert_eq!(markdown, "Latency\n: Time taken by one request.\n\nPlain text.");
+    }
+
     #[test]
     fn test_format_content_detects_headers() {
         let text = "INTRODUCTION\n\nThis is the content.";
@@ -134,11 +240,46 @@ mod tests {
         assert_eq!(markdown, "## INTRODUCTION\n\nThis is the content.");
     }
 
+    #[test]
+    fn test_streaming_formatter_matches_whole_text() {
+        let text = "INTRODUCTION\n\nThis is a sentence\nthat was broken.\n\n\nLatency\n: Time taken\n\n\n\nEnd.";
+        let whole = format_content(text);
+        for size in 1..6 {
+            let mut formatter = StreamingFormatter::new(FormatOptions::default());
+            let mut markdown = String::new();
+            let chars: Vec<char> = text.chars().collect();
+            for piece in chars.chunks(size) {
+                markdown.push_str(&formatter.push(&piece.iter().collect::<String>()));
+            }
+            markdown.push_str(&formatter.finish());
+            assert_eq!(markdown, whole, "pieces of {}", size);
+        }
+    }
+
     #[test]
     fn test_is_potential_header() {
-        assert!(is_potential_header("INTRODUCTION"));
-        assert!(is_potential_header("CHAPTER 1"));
-        assert!(!is_potential_header("This is a regular sentence."));
-        assert!(!is_potential_header("This is a very long line that should not be considered a header even if it has some CAPS"));
+        let options = FormatOptions::default();
+        assert!(is_potential_header("INTRODUCTION", &options));
+        assert!(is_potential_header("CHAPTER 1", &options));
+        assert!(!is_potential_header("This is a regular sentence.", &options));
+        assert!(!is_potential_header("$$E = MC$$", &options));
+        assert!(!is_potential_header("This is a very long line that should not be considered a header even if it has some CAPS", &options));
+    }
+
+    #[test]
+    fn test_heading_length_thresholds() {
+        let text = "Q3\n\nSECOND QUARTER IN REVIEW\n\nBody.";
+        assert_eq!(
+            format_content(text),
+            "## Q3\n\n## SECOND QUARTER IN REVIEW\n\nBody."
+        );
+        let options = FormatOptions {
+            min_heading_chars: 3,
+            max_heading_chars: 20,
+        };
+        assert_eq!(
+            format_content_with(text, &options),
+            "Q3\n\nSECOND QUARTER IN REVIEW\n\nBody."
+        );
     }
 }
@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+
+/// Level and text of an ATX heading (`# Title` through `###### Title`)
+pub(crate) fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    if !(1..=6).contains(&level) {
+        return None;
+    }
+    let text = line[level..]
+        .strip_prefix(' ')?
+        .trim()
+        .trim_end_matches('#')
+        .trim();
+    (!text.is_empty()).then_some((level, text))
+}
+
+/// Rewrite each heading outside code fences with `rewrite(level, text)`,
+/// borrowing the Markdown when no heading changes
+fn map_headings(markdown: &str, mut rewrite: impl FnMut(usize, &str) -> String) -> Cow<'_, str> {
+    let mut in_fence = false;
+    let mut changed = false;
+    let lines: Vec<Cow<str>> = markdown
+        .split('\n')
+        .map(|line| {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            match heading(line) {
+                Some((level, text)) if !in_fence => {
+                    let rewritten = rewrite(level, text);
+                    if rewritten == line {
+                        return Cow::Borrowed(line);
+                    }
+                    changed = true;
+                    Cow::Owned(rewritten)
+                }
+                _ => Cow::Borrowed(line),
+            }
+        })
+        .collect();
+    if changed {
+        Cow::Owned(lines.join("\n"))
+    } else {
+        Cow::Borrowed(markdown)
+    }
+}
+
+/// Fix the heading hierarchy: exactly one H1, and no skipped levels below it.
+///
+/// A document whose first heading is also its only shallowest one keeps that
+/// heading as the H1; otherwise `# title` is added at the top. Every other
+/// heading becomes one level deeper than its parent, so an H4 directly under
+/// the H1 becomes an H2.
+pub fn normalize_headings<'a>(markdown: &'a str, title: &str) -> Cow<'a, str> {
+    let title_level = document_title_level(markdown);
+    // (original level, new level) of the open sections
+    let mut parents: Vec<(usize, usize)> = Vec::new();
+    let mut seen_title = false;
+    let body = map_headings(markdown, |level, text| {
+        if !seen_title && title_level == Some(level) {
+            seen_title = true;
+            parents.push((level, 1));
+            return format!("# {}", text);
+        }
+        while parents
+            .last()
+            .is_some_and(|&(parent, new)| parent >= level && new > 1)
+        {
+            parents.pop();
+        }
+        let new_level = (parents.last().map_or(1, |&(_, new)| new) + 1).min(6);
+        parents.push((level, new_level));
+        format!("{} {}", "#".repeat(new_level), text)
+    });
+
+    if title_level.is_some() {
+        body
+    } else if body.is_empty() {
+        Cow::Owned(format!("# {}", title))
+    } else {
+        Cow::Owned(format!("# {}\n\n{}", title, body))
+    }
+}
+
+/// Level of the heading that titles the document: the first heading, when no
+/// other heading is at its level or shallower
+fn document_title_level(markdown: &str) -> Option<usize> {
+    let mut all = headings(markdown);
+    let (first, _) = all.next()?;
+    all.all(|(level, _)| level > first).then_some(first)
+}
+
+/// Prefix headings with hierarchical numbers (`1`, `1.1`, `1.1.2`).
+///
+/// The shallowest heading level in the document is numbered `1, 2, ...`; a
+/// document title (a lone first heading above all others) is not numbered.
+/// Numbers already present in the PDF are replaced rather than doubled.
+pub fn number_headings(markdown: &str) -> Cow<'_, str> {
+    let title_level = document_title_level(markdown);
+    let Some(top) = headings(markdown)
+        .map(|(level, _)| level)
+        .filter(|&level| Some(level) != title_level)
+        .min()
+    else {
+        return Cow::Borrowed(markdown);
+    };
+    let mut counters = [0usize; 6];
+    map_headings(markdown, |level, text| {
+        if Some(level) == title_level {
+            return format!("{} {}", "#".repeat(level), text);
+        }
+        let depth = level - top;
+        counters[depth] += 1;
+        counters[depth + 1..].fill(0);
+        // A heading that skips a level still gets a number for it
+        for counter in &mut counters[..depth] {
+            *counter = (*counter).max(1);
+        }
+        let number: Vec<String> = counters[..=depth].iter().map(usize::to_string).collect();
+        format!(
+            "{} {} {}",
+            "#".repeat(level),
+            number.join("."),
+            strip_number(text)
+        )
+    })
+}
+
+/// Remove section numbers (`1.`, `2.3`, `IV.`, `A.1`) from the start of headings
+pub fn strip_heading_numbers(markdown: &str) -> Cow<'_, str> {
+    map_headings(markdown, |level, text| {
+        format!("{} {}", "#".repeat(level), strip_number(text))
+    })
+}
+
+/// Level and text of each heading, in document order
+pub fn outline(markdown: &str) -> Vec<(usize, String)> {
+    headings(markdown)
+        .map(|(level, text)| (level, text.to_string()))
+        .collect()
+}
+
+/// Headings outside code fences
+fn headings(markdown: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut in_fence = false;
+    markdown.lines().filter_map(move |line| {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if in_fence { None } else { heading(line) }
+    })
+}
+
+/// Heading text without a leading section number
+fn strip_number(text: &str) -> &str {
+    match text.split_once(char::is_whitespace) {
+        Some((token, rest)) if is_section_number(token) && !rest.trim().is_empty() => rest.trim(),
+        _ => text,
+    }
+}
+
+/// `1`, `1.`, `2.3`, `2.3)`, `IV.` or `A.1`; numbers of four or more digits
+/// are left alone since they are usually years
+fn is_section_number(token: &str) -> bool {
+    let body = token.trim_end_matches(['.', ')']);
+    if body.is_empty() {
+        return false;
+    }
+    let numeric = |part: &str| {
+        !part.is_empty() && part.len() <= 3 && part.chars().all(|c| c.is_ascii_digit())
+    };
+    let mut parts = body.split('.');
+    let first = parts.next().unwrap_or_default();
+    let rest_numeric = parts.all(numeric);
+    if numeric(first) {
+        return rest_numeric;
+    }
+    let roman = first.chars().all(|c| "IVXLC".contains(c)) && token.ends_with('.');
+    let appendix =
+        first.len() == 1 && first.chars().all(|c| c.is_ascii_uppercase()) && body.contains('.');
+    rest_numeric && (roman || appendix)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_number_headings() {
+        let markdown = "# Guide\n\nIntro\n\n## Setup\n\n### 3.1 Install\n\n```\n## not a heading\n```\n\n## Usage\n\n#### Deep";
+        assert_eq!(
+            number_headings(markdown),
+            "# Guide\n\nIntro\n\n## 1 Setup\n\n### 1.1 Install\n\n```\n## not a heading\n```\n\n## 2 Usage\n\n#### 2.1.1 Deep"
+        );
+    }
+
+    #[test]
+    fn test_normalize_headings_fixes_skipped_levels() {
+        let markdown = "# Guide\n\n#### Setup\n\n#### Usage\n\n###### Flags\n\n### Limits";
+        assert_eq!(
+            normalize_headings(markdown, "Ignored"),
+            "# Guide\n\n## Setup\n\n## Usage\n\n### Flags\n\n## Limits"
+        );
+    }
+
+    #[test]
+    fn test_normalize_headings_adds_single_h1() {
+        let markdown = "# Part One\n\nText\n\n# Part Two\n\n### Detail";
+        assert_eq!(
+            normalize_headings(markdown, "Handbook"),
+            "# Handbook\n\n## Part One\n\nText\n\n## Part Two\n\n### Detail"
+        );
+        assert_eq!(normalize_headings("", "Handbook"), "# Handbook");
+        assert_eq!(
+            normalize_headings("## INTRODUCTION\n\nText", "Report"),
+            "# INTRODUCTION\n\nText"
+        );
+    }
+
+    #[test]
+    fn test_outline_skips_code_fences() {
+        let markdown = "# Guide\n\n```\n# comment\n```\n\n## Setup ##";
+        assert_eq!(
+            outline(markdown),
+            vec![(1, "Guide".to_string()), (2, "Setup".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_strip_heading_numbers() {
+        let markdown = "## 1. INTRODUCTION\n\n## 2.3 Methods\n\n## IV. RESULTS\n\n## A.1 Tables\n\n## 2024 BUDGET\n\n## 7\n\n1. List item";
+        assert_eq!(
+            strip_heading_numbers(markdown),
+            "## INTRODUCTION\n\n## Methods\n\n## RESULTS\n\n## Tables\n\n## 2024 BUDGET\n\n## 7\n\n1. List item"
+        );
+    }
+
+    #[test]
+    fn test_unchanged_headings_are_borrowed() {
+        let markdown = "# Guide\n\n## Setup\n\nText\n\n### Install";
+        assert!(matches!(strip_heading_numbers(markdown), Cow::Borrowed(_)));
+        assert!(matches!(
+            normalize_headings(markdown, "Ignored"),
+            Cow::Borrowed(_)
+        ));
+        assert!(matches!(number_headings("No headings"), Cow::Borrowed(_)));
+        assert!(matches!(number_headings(markdown), Cow::Owned(_)));
+    }
+}
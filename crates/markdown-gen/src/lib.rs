@@ -1,8 +1,14 @@
+mod chat;
 mod format;
+mod headings;
 mod writer;
+mod zip;
 
-pub use format::format_content;
-pub use writer::{create_parent_dirs, write_to_file};
+pub use chat::to_chat;
+pub use format::{FormatOptions, StreamingFormatter, format_content, format_content_with};
+pub use headings::{normalize_headings, number_headings, outline, strip_heading_numbers};
+pub use writer::{create_parent_dirs, write_binary_file, write_to_file};
+pub use zip::write_zip;
 
 // Re-export error type for convenience
 pub type Result<T> = std::result::Result<T, MarkdownError>;
@@ -19,6 +19,15 @@ pub fn write_to_file(markdown: &str, path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Write binary content (e.g. an extracted image) to file
+pub fn write_binary_file(data: &[u8], path: &Path) -> Result<()> {
+    debug!("Writing {} bytes to: {}", data.len(), path.display());
+
+    create_parent_dirs(path)?;
+    fs::write(path, data)?;
+    Ok(())
+}
+
 /// Create parent directories for a file path
 pub fn create_parent_dirs(path: &Path) -> Result<()> {
     if let Some(parent) = path.parent()
@@ -67,6 +76,16 @@ mod tests {
         assert_eq!(written_content, new_content);
     }
 
+    #[test]
+    fn test_write_binary_file_creates_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let image_path = temp_dir.path().join("images").join("page-1-image-1.png");
+
+        let result = write_binary_file(&[0x89, b'P', b'N', b'G'], &image_path);
+        assert!(result.is_ok());
+        assert_eq!(fs::read(&image_path).unwrap(), vec![0x89, b'P', b'N', b'G']);
+    }
+
     #[test]
     fn test_create_parent_dirs_creates_directories() {
         let temp_dir = TempDir::new().unwrap();
@@ -0,0 +1,130 @@
+use crate::Result;
+use crate::writer::write_binary_file;
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use std::io::Write;
+use std::path::Path;
+
+/// Deflate compression method
+const DEFLATE: u16 = 8;
+/// Language encoding flag: entry names are UTF-8
+const UTF8_NAMES: u16 = 1 << 11;
+/// MS-DOS date of 1980-01-01, so archives are reproducible
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Write a zip archive holding `(name, data)` entries, deflate-compressed
+pub fn write_zip(entries: &[(String, &[u8])], path: &Path) -> Result<()> {
+    write_binary_file(&zip_archive(entries)?, path)
+}
+
+fn zip_archive(entries: &[(String, &[u8])]) -> Result<Vec<u8>> {
+    let mut archive = Vec::new();
+    let mut directory = Vec::new();
+
+    for (name, data) in entries {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let crc = crc32fast::hash(data);
+        let offset = archive.len() as u32;
+
+        // Local file header
+        put_u32(&mut archive, 0x0403_4b50);
+        put_entry_fields(&mut archive, name, crc, compressed.len(), data.len());
+        put_u16(&mut archive, 0);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&compressed);
+
+        // Central directory record
+        put_u32(&mut directory, 0x0201_4b50);
+        put_u16(&mut directory, 20);
+        put_entry_fields(&mut directory, name, crc, compressed.len(), data.len());
+        put_u16(&mut directory, 0); // extra field length
+        put_u16(&mut directory, 0); // comment length
+        put_u16(&mut directory, 0); // disk number
+        put_u16(&mut directory, 0); // internal attributes
+        put_u32(&mut directory, 0); // external attributes
+        put_u32(&mut directory, offset);
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+
+    // End of central directory
+    put_u32(&mut archive, 0x0605_4b50);
+    put_u16(&mut archive, 0);
+    put_u16(&mut archive, 0);
+    put_u16(&mut archive, entries.len() as u16);
+    put_u16(&mut archive, entries.len() as u16);
+    put_u32(&mut archive, directory.len() as u32);
+    put_u32(&mut archive, directory_offset);
+    put_u16(&mut archive, 0);
+    Ok(archive)
+}
+
+/// Fields shared by the local header and the central directory, up to the name length
+fn put_entry_fields(out: &mut Vec<u8>, name: &str, crc: u32, compressed: usize, size: usize) {
+    put_u16(out, 20); // version needed to extract
+    put_u16(out, UTF8_NAMES);
+    put_u16(out, DEFLATE);
+    put_u16(out, 0); // modification time
+    put_u16(out, DOS_DATE);
+    put_u32(out, crc);
+    put_u32(out, compressed as u32);
+    put_u32(out, size as u32);
+    put_u16(out, name.len() as u16);
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::DeflateDecoder;
+    use std::io::Read;
+
+    fn u16_at(data: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([data[at], data[at + 1]]) as usize
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> usize {
+        u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]]) as usize
+    }
+
+    #[test]
+    fn test_zip_archive_layout() {
+        let text = b"# Notes\n\nHello, hello, hello.".as_slice();
+        let entries = vec![
+            ("notes.md".to_string(), text),
+            ("images/a.png".to_string(), b"\x89PNG".as_slice()),
+        ];
+        let archive = zip_archive(&entries).unwrap();
+
+        // End of central directory points at two records
+        let end = archive.len() - 22;
+        assert_eq!(u32_at(&archive, end), 0x0605_4b50);
+        assert_eq!(u16_at(&archive, end + 10), 2);
+        let directory = u32_at(&archive, end + 16);
+        assert_eq!(u32_at(&archive, directory), 0x0201_4b50);
+
+        // First entry decompresses to the original text
+        assert_eq!(u32_at(&archive, 0), 0x0403_4b50);
+        assert_eq!(u32_at(&archive, 14), crc32fast::hash(text) as usize);
+        let compressed = u32_at(&archive, 18);
+        let name_len = u16_at(&archive, 26);
+        assert_eq!(&archive[30..30 + name_len], b"notes.md");
+        let start = 30 + name_len;
+        let mut decoded = Vec::new();
+        DeflateDecoder::new(&archive[start..start + compressed])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, text);
+    }
+}
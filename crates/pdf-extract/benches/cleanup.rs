@@ -0,0 +1,75 @@
+//! Throughput of the extracted-text clean-up: `cargo bench -p pdf-extract`
+//!
+//! Uses no benchmark framework; each case runs for about a second and the
+//! mean time per call is printed.
+
+use pdf_extract::{CleanupThresholds, clean_extracted_text_with};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Text as lopdf tends to return it: ragged spacing, CRLF line ends, runs of
+/// blank lines, no-break spaces and soft hyphens
+fn structured_text(bytes: usize) -> String {
+    let lines = [
+        "  The results  of the   second\u{00A0}experiment are shown in\r",
+        "Table 3, where\u{00A0}each value is the mean of five inter\u{00AD}",
+        "  national measurements.   \r",
+        "",
+        "",
+        "",
+        "\u{FEFF}RESULTS",
+        "Temperatures rose by 2\u{202F}% over the period.",
+    ];
+    let mut text = String::with_capacity(bytes + 100);
+    while text.len() < bytes {
+        for line in lines {
+            text.push_str(line);
+            text.push('\n');
+        }
+    }
+    text
+}
+
+/// Text that needs no cleaning, which is returned without copying
+fn clean_text(bytes: usize) -> String {
+    let line = "Each line of this report is already trimmed and single-spaced.\n";
+    line.repeat(bytes / line.len() + 1)
+}
+
+/// Text with no line breaks at all, which also goes through the
+/// paragraph-break pass
+fn unstructured_text(bytes: usize) -> String {
+    let sentence = "The committee reviewed the annual budget in detail. Members agreed on the \
+                    changes.  Spending on  maintenance is up by a tenth. ";
+    sentence.repeat(bytes / sentence.len() + 1)
+}
+
+fn bench(name: &str, text: &str) {
+    let thresholds = CleanupThresholds::default();
+    let mut runs = 0u32;
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(1) {
+        black_box(clean_extracted_text_with(black_box(text), &thresholds));
+        runs += 1;
+    }
+    let per_run = start.elapsed() / runs;
+    let mb_per_s = text.len() as f64 / per_run.as_secs_f64() / 1_000_000.0;
+    println!(
+        "{:<24} {:>8} bytes  {:>10.3?}/call  {:>8.1} MB/s",
+        name,
+        text.len(),
+        per_run,
+        mb_per_s
+    );
+}
+
+fn main() {
+    for bytes in [4_000, 1_000_000] {
+        bench(&format!("structured/{}", bytes), &structured_text(bytes));
+        bench(
+            &format!("unstructured/{}", bytes),
+            &unstructured_text(bytes),
+        );
+        bench(&format!("clean/{}", bytes), &clean_text(bytes));
+    }
+}
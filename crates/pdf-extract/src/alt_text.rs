@@ -0,0 +1,11 @@
+use super::types::ExtractedImage;
+use std::fmt::Debug;
+
+/// Extension point supplying alt text for extracted images.
+///
+/// Implementations might call a vision model or look descriptions up in a
+/// catalogue. Returning `None` falls back to the detected caption, if any.
+pub trait AltTextProvider: Debug + Send + Sync {
+    /// Describe an image for readers who cannot see it
+    fn alt_text(&self, image: &ExtractedImage) -> Option<String>;
+}
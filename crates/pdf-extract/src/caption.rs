@@ -0,0 +1,125 @@
+use super::geometry::Rect;
+use super::layout::TextRun;
+
+/// Words that introduce a figure caption ("Figure 3: ...", "Fig. 2.1 ...")
+const CAPTION_LABELS: [&str; 7] = [
+    "figure",
+    "fig.",
+    "fig",
+    "chart",
+    "exhibit",
+    "illustration",
+    "plate",
+];
+
+/// Maximum gap between an image edge and its caption baseline, in font sizes
+const MAX_CAPTION_GAP: f32 = 3.0;
+
+/// Check whether a line of text looks like a figure caption
+pub fn is_caption(text: &str) -> bool {
+    let mut words = text.split_whitespace();
+    let (Some(label), Some(number)) = (words.next(), words.next()) else {
+        return false;
+    };
+    let label = label.to_lowercase();
+    CAPTION_LABELS.contains(&label.as_str())
+        && number
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+}
+
+/// Plain text of a line of runs
+pub fn line_text(line: &[&TextRun]) -> String {
+    let text: String = line.iter().map(|run| run.text.as_str()).collect();
+    text.trim().to_string()
+}
+
+/// Find the caption belonging to a figure: the closest caption line just
+/// below it, or failing that just above it
+pub fn find_caption(lines: &[Vec<&TextRun>], image: &Rect) -> Option<usize> {
+    let gap = |index: usize, below: bool| {
+        let line = &lines[index];
+        let run = line[0];
+        let distance = if below {
+            image.y - run.y
+        } else {
+            run.y - image.top()
+        };
+        let limit = run.size.max(1.0) * MAX_CAPTION_GAP;
+        (distance >= 0.0 && distance <= limit && is_caption(&line_text(line))).then_some(distance)
+    };
+
+    let closest = |below: bool| {
+        (0..lines.len())
+            .filter_map(|i| gap(i, below).map(|d| (i, d)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+    };
+
+    closest(true).or_else(|| closest(false))
+}
+
+/// Escape a caption for use as Markdown image alt text
+pub fn alt_text(caption: &str) -> String {
+    caption.replace('[', "\\[").replace(']', "\\]")
+}
+
+/// Render a caption as an italic line below the image
+pub fn caption_line(caption: &str) -> String {
+    format!("*{}*", caption.replace('*', "\\*"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Color;
+
+    fn run(text: &str, y: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: "Helvetica".to_string(),
+            size: 10.0,
+            x: 50.0,
+            y,
+            width: 6.0 * text.len() as f32,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    #[test]
+    fn test_is_caption() {
+        assert!(is_caption("Figure 1: System architecture"));
+        assert!(is_caption("Fig. 2.3 Results"));
+        assert!(is_caption("FIGURE A. Overview"));
+        assert!(!is_caption("Figure out what went wrong"));
+        assert!(!is_caption("The figure shows"));
+    }
+
+    #[test]
+    fn test_find_caption_prefers_line_below_image() {
+        let runs = [
+            run("Figure 1: Above", 665.0),
+            run("Figure 2: Below", 540.0),
+            run("Body text", 400.0),
+        ];
+        let lines: Vec<Vec<&TextRun>> = runs.iter().map(|r| vec![r]).collect();
+        let image = Rect::from_corners(50.0, 550.0, 250.0, 650.0);
+        assert_eq!(find_caption(&lines, &image), Some(1));
+    }
+
+    #[test]
+    fn test_find_caption_ignores_distant_lines() {
+        let runs = [run("Figure 9: Far away", 100.0)];
+        let lines: Vec<Vec<&TextRun>> = runs.iter().map(|r| vec![r]).collect();
+        let image = Rect::from_corners(50.0, 550.0, 250.0, 650.0);
+        assert_eq!(find_caption(&lines, &image), None);
+    }
+
+    #[test]
+    fn test_alt_text_and_caption_line_escape_markdown() {
+        assert_eq!(alt_text("Figure [1]"), "Figure \\[1\\]");
+        assert_eq!(caption_line("Figure 1: a*b"), "*Figure 1: a\\*b*");
+    }
+}
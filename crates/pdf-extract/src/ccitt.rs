@@ -0,0 +1,369 @@
+//! CCITT Group 3 and Group 4 fax decoding (`/CCITTFaxDecode`), used by most
+//! scanned bilevel images.
+
+use super::limits::MAX_STREAM_SIZE;
+
+/// Parameters from the image's `/DecodeParms` dictionary
+#[derive(Debug, Clone, Copy)]
+pub struct FaxParams {
+    /// `< 0` Group 4, `0` Group 3 one-dimensional, `> 0` Group 3 mixed
+    pub k: i64,
+    pub columns: usize,
+    pub rows: usize,
+    pub encoded_byte_align: bool,
+    pub black_is_1: bool,
+}
+
+/// White run-length codes (terminating 0–63, then make-up codes), as `(bits, run)`
+#[rustfmt::skip]
+const WHITE_CODES: &[(&str, u16)] = &[
+    ("00110101", 0), ("000111", 1), ("0111", 2), ("1000", 3), ("1011", 4),
+    ("1100", 5), ("1110", 6), ("1111", 7), ("10011", 8), ("10100", 9),
+    ("00111", 10), ("01000", 11), ("001000", 12), ("000011", 13), ("110100", 14),
+    ("110101", 15), ("101010", 16), ("101011", 17), ("0100111", 18), ("0001100", 19),
+    ("0001000", 20), ("0010111", 21), ("0000011", 22), ("0000100", 23), ("0101000", 24),
+    ("0101011", 25), ("0010011", 26), ("0100100", 27), ("0011000", 28), ("00000010", 29),
+    ("00000011", 30), ("00011010", 31), ("00011011", 32), ("00010010", 33), ("00010011", 34),
+    ("00010100", 35), ("00010101", 36), ("00010110", 37), ("00010111", 38), ("00101000", 39),
+    ("00101001", 40), ("00101010", 41), ("00101011", 42), ("00101100", 43), ("00101101", 44),
+    ("00000100", 45), ("00000101", 46), ("00001010", 47), ("00001011", 48), ("01010010", 49),
+    ("01010011", 50), ("01010100", 51), ("01010101", 52), ("00100100", 53), ("00100101", 54),
+    ("01011000", 55), ("01011001", 56), ("01011010", 57), ("01011011", 58), ("01001010", 59),
+    ("01001011", 60), ("00110010", 61), ("00110011", 62), ("00110100", 63),
+    ("11011", 64), ("10010", 128), ("010111", 192), ("0110111", 256), ("00110110", 320),
+    ("00110111", 384), ("01100100", 448), ("01100101", 512), ("01101000", 576),
+    ("01100111", 640), ("011001100", 704), ("011001101", 768), ("011010010", 832),
+    ("011010011", 896), ("011010100", 960), ("011010101", 1024), ("011010110", 1088),
+    ("011010111", 1152), ("011011000", 1216), ("011011001", 1280), ("011011010", 1344),
+    ("011011011", 1408), ("010011000", 1472), ("010011001", 1536), ("010011010", 1600),
+    ("011000", 1664), ("010011011", 1728),
+];
+
+/// Black run-length codes (terminating 0–63, then make-up codes), as `(bits, run)`
+#[rustfmt::skip]
+const BLACK_CODES: &[(&str, u16)] = &[
+    ("0000110111", 0), ("010", 1), ("11", 2), ("10", 3), ("011", 4),
+    ("0011", 5), ("0010", 6), ("00011", 7), ("000101", 8), ("000100", 9),
+    ("0000100", 10), ("0000101", 11), ("0000111", 12), ("00000100", 13), ("00000111", 14),
+    ("000011000", 15), ("0000010111", 16), ("0000011000", 17), ("0000001000", 18),
+    ("00001100111", 19), ("00001101000", 20), ("00001101100", 21), ("00000110111", 22),
+    ("00000101000", 23), ("00000010111", 24), ("00000011000", 25), ("000011001010", 26),
+    ("000011001011", 27), ("000011001100", 28), ("000011001101", 29), ("000001101000", 30),
+    ("000001101001", 31), ("000001101010", 32), ("000001101011", 33), ("000011010010", 34),
+    ("000011010011", 35), ("000011010100", 36), ("000011010101", 37), ("000011010110", 38),
+    ("000011010111", 39), ("000001101100", 40), ("000001101101", 41), ("000011011010", 42),
+    ("000011011011", 43), ("000001010100", 44), ("000001010101", 45), ("000001010110", 46),
+    ("000001010111", 47), ("000001100100", 48), ("000001100101", 49), ("000001010010", 50),
+    ("000001010011", 51), ("000000100100", 52), ("000000110111", 53), ("000000111000", 54),
+    ("000000100111", 55), ("000000101000", 56), ("000001011000", 57), ("000001011001", 58),
+    ("000000101011", 59), ("000000101100", 60), ("000001011010", 61), ("000001100110", 62),
+    ("000001100111", 63),
+    ("0000001111", 64), ("000011001000", 128), ("000011001001", 192), ("000001011011", 256),
+    ("000000110011", 320), ("000000110100", 384), ("000000110101", 448),
+    ("0000001101100", 512), ("0000001101101", 576), ("0000001001010", 640),
+    ("0000001001011", 704), ("0000001001100", 768), ("0000001001101", 832),
+    ("0000001110010", 896), ("0000001110011", 960), ("0000001110100", 1024),
+    ("0000001110101", 1088), ("0000001110110", 1152), ("0000001110111", 1216),
+    ("0000001010010", 1280), ("0000001010011", 1344), ("0000001010100", 1408),
+    ("0000001010101", 1472), ("0000001011010", 1536), ("0000001011011", 1600),
+    ("0000001100100", 1664), ("0000001100101", 1728),
+];
+
+/// Extended make-up codes shared by both colours
+#[rustfmt::skip]
+const EXTENDED_CODES: &[(&str, u16)] = &[
+    ("00000001000", 1792), ("00000001100", 1856), ("00000001101", 1920),
+    ("000000010010", 1984), ("000000010011", 2048), ("000000010100", 2112),
+    ("000000010101", 2176), ("000000010110", 2240), ("000000010111", 2304),
+    ("000000011100", 2368), ("000000011101", 2432), ("000000011110", 2496),
+    ("000000011111", 2560),
+];
+
+/// Two-dimensional coding modes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Pass,
+    Horizontal,
+    Vertical(isize),
+}
+
+const MODE_CODES: &[(&str, Mode)] = &[
+    ("1", Mode::Vertical(0)),
+    ("011", Mode::Vertical(1)),
+    ("010", Mode::Vertical(-1)),
+    ("001", Mode::Horizontal),
+    ("0001", Mode::Pass),
+    ("000011", Mode::Vertical(2)),
+    ("000010", Mode::Vertical(-2)),
+    ("0000011", Mode::Vertical(3)),
+    ("0000010", Mode::Vertical(-3)),
+];
+
+/// End-of-line marker: eleven zeros followed by a one
+const EOL_ZEROS: u32 = 11;
+
+/// MSB-first bit reader
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Bits<'_> {
+    fn peek(&self, offset: usize) -> Option<bool> {
+        let pos = self.pos + offset;
+        let byte = *self.data.get(pos / 8)?;
+        Some(byte & (0x80 >> (pos % 8)) != 0)
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.data.len() * 8
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.div_ceil(8) * 8;
+    }
+
+    /// Consume the first code in `table` matching the upcoming bits
+    fn read_code<T: Copy>(&mut self, table: &[(&str, T)]) -> Option<T> {
+        let (code, value) = table.iter().find(|(code, _)| {
+            code.bytes()
+                .enumerate()
+                .all(|(i, bit)| self.peek(i) == Some(bit == b'1'))
+        })?;
+        self.pos += code.len();
+        Some(*value)
+    }
+
+    /// Skip an end-of-line marker (with any fill zeros) if one is next
+    fn skip_eol(&mut self) -> bool {
+        let mut zeros = 0;
+        while self.peek(zeros as usize) == Some(false) {
+            zeros += 1;
+        }
+        if zeros >= EOL_ZEROS && self.peek(zeros as usize) == Some(true) {
+            self.pos += zeros as usize + 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Read one run length: make-up codes followed by a terminating code
+    fn read_run(&mut self, white: bool) -> Option<usize> {
+        let table = if white { WHITE_CODES } else { BLACK_CODES };
+        let mut total = 0;
+        loop {
+            let run = self
+                .read_code(table)
+                .or_else(|| self.read_code(EXTENDED_CODES))? as usize;
+            total += run;
+            if run < 64 {
+                return Some(total);
+            }
+        }
+    }
+}
+
+/// Decode fax data into 8-bit gray samples (0 black, 255 white), one byte per pixel.
+///
+/// Rows that cannot be decoded are left white, so truncated scans still yield
+/// an image. Returns `None` when not even the first row decodes, or when
+/// `Columns` by `Rows` comes to more than `MAX_STREAM_SIZE` samples.
+pub fn decode(data: &[u8], params: &FaxParams) -> Option<Vec<u8>> {
+    let columns = params.columns;
+    let size = columns.checked_mul(params.rows)?;
+    if size as u64 > MAX_STREAM_SIZE {
+        return None;
+    }
+    let mut bits = Bits { data, pos: 0 };
+    // Changing elements of the reference line; an all-white line has none
+    let mut reference: Vec<usize> = Vec::new();
+    let mut pixels = vec![255u8; size];
+
+    for row in 0..params.rows {
+        if params.encoded_byte_align {
+            bits.align();
+        }
+        bits.skip_eol();
+        if bits.at_end() {
+            break;
+        }
+        let two_dimensional = match params.k {
+            k if k < 0 => true,
+            0 => false,
+            _ => {
+                // Mixed Group 3: a tag bit after each EOL selects the coding
+                let tag = bits.peek(0)?;
+                bits.pos += 1;
+                !tag
+            }
+        };
+        let line = if two_dimensional {
+            decode_2d_line(&mut bits, &reference, columns)
+        } else {
+            decode_1d_line(&mut bits, columns)
+        };
+        let Some(line) = line else {
+            if row == 0 {
+                return None;
+            }
+            break;
+        };
+
+        let out = &mut pixels[row * columns..(row + 1) * columns];
+        let mut white = true;
+        let mut start = 0;
+        for &change in line.iter().chain(std::iter::once(&columns)) {
+            if !white {
+                out[start..change].fill(0);
+            }
+            start = change;
+            white = !white;
+        }
+        reference = line;
+    }
+
+    if params.black_is_1 {
+        // Black pixels carry sample value 1, which DeviceGray shows as white
+        for pixel in &mut pixels {
+            *pixel = 255 - *pixel;
+        }
+    }
+    Some(pixels)
+}
+
+/// Decode a Modified Huffman line into its changing elements
+fn decode_1d_line(bits: &mut Bits, columns: usize) -> Option<Vec<usize>> {
+    let mut changes = Vec::new();
+    let mut position = 0;
+    let mut white = true;
+    while position < columns {
+        position = (position + bits.read_run(white)?).min(columns);
+        changes.push(position);
+        white = !white;
+    }
+    trim_changes(&mut changes, columns);
+    Some(changes)
+}
+
+/// Decode a two-dimensionally coded line against the reference line
+fn decode_2d_line(bits: &mut Bits, reference: &[usize], columns: usize) -> Option<Vec<usize>> {
+    let mut changes: Vec<usize> = Vec::new();
+    let mut a0: isize = -1;
+    let mut white = true;
+
+    while a0 < columns as isize {
+        // b1: first changing element above and right of a0 whose colour
+        // change is the opposite of the current colour; b2 the next one
+        let parity = if white { 0 } else { 1 };
+        let b1_index = (0..reference.len())
+            .find(|&i| i % 2 == parity && reference[i] as isize > a0)
+            .unwrap_or(reference.len());
+        let b1 = reference.get(b1_index).copied().unwrap_or(columns);
+        let b2 = reference.get(b1_index + 1).copied().unwrap_or(columns);
+
+        match bits.read_code(MODE_CODES)? {
+            Mode::Pass => a0 = b2 as isize,
+            Mode::Horizontal => {
+                let start = a0.max(0) as usize;
+                let a1 = (start + bits.read_run(white)?).min(columns);
+                let a2 = (a1 + bits.read_run(!white)?).min(columns);
+                changes.extend([a1, a2]);
+                a0 = a2 as isize;
+            }
+            Mode::Vertical(delta) => {
+                let a1 = (b1 as isize + delta).clamp(0, columns as isize);
+                changes.push(a1 as usize);
+                a0 = a1;
+                white = !white;
+            }
+        }
+    }
+    trim_changes(&mut changes, columns);
+    Some(changes)
+}
+
+/// Drop changing elements at the right edge, which carry no pixels
+fn trim_changes(changes: &mut Vec<usize>, columns: usize) {
+    while changes.last().is_some_and(|&c| c >= columns) {
+        changes.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pack a string of '0'/'1' characters into bytes, padding with zeros
+    fn pack(bits: &str) -> Vec<u8> {
+        let bits: Vec<u8> = bits.bytes().filter(|b| *b != b' ').collect();
+        bits.chunks(8)
+            .map(|chunk| {
+                chunk.iter().enumerate().fold(0u8, |byte, (i, &bit)| {
+                    byte | (((bit == b'1') as u8) << (7 - i))
+                })
+            })
+            .collect()
+    }
+
+    fn params(k: i64, columns: usize, rows: usize) -> FaxParams {
+        FaxParams {
+            k,
+            columns,
+            rows,
+            encoded_byte_align: false,
+            black_is_1: false,
+        }
+    }
+
+    #[test]
+    fn test_code_tables_are_prefix_free() {
+        for table in [WHITE_CODES, BLACK_CODES] {
+            let codes: Vec<&str> = table
+                .iter()
+                .chain(EXTENDED_CODES)
+                .map(|(code, _)| *code)
+                .collect();
+            for (i, a) in codes.iter().enumerate() {
+                for b in &codes[i + 1..] {
+                    assert!(!a.starts_with(b) && !b.starts_with(a), "{a} / {b}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_group3_one_dimensional() {
+        // 8 columns: 2 white (0111), 3 black (10), 3 white (1000)
+        let data = pack("0111 10 1000");
+        let pixels = decode(&data, &params(0, 8, 1)).unwrap();
+        assert_eq!(pixels, [255, 255, 0, 0, 0, 255, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_group4_vertical_modes() {
+        // Row 1 (horizontal): 2 white, 3 black, rest white via V0 on an empty reference.
+        // Row 2: V0, V0, V0 copies row 1.
+        let data = pack("001 0111 10 1  1 1 1");
+        let pixels = decode(&data, &params(-1, 8, 2)).unwrap();
+        let row = [255, 255, 0, 0, 0, 255, 255, 255];
+        assert_eq!(&pixels[..8], row);
+        assert_eq!(&pixels[8..], row);
+    }
+
+    #[test]
+    fn test_decode_refuses_huge_images() {
+        let data = pack("0111 10 1000");
+        assert!(decode(&data, &params(0, 65536, 65536)).is_none());
+        assert!(decode(&data, &params(0, usize::MAX, 2)).is_none());
+    }
+
+    #[test]
+    fn test_decode_black_is_1_inverts() {
+        let data = pack("0111 10 1000");
+        let mut p = params(0, 8, 1);
+        p.black_is_1 = true;
+        let pixels = decode(&data, &p).unwrap();
+        assert_eq!(pixels, [0, 0, 255, 255, 255, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,262 @@
+use super::limits;
+use super::types::PdfCompatibility;
+use lopdf::xref::XrefEntry;
+use lopdf::{Dictionary, Document, Object};
+
+/// Newest PDF version whose features extraction is written against
+const NEWEST_KNOWN_VERSION: f32 = 2.0;
+
+/// Detect the PDF version and the features in use that affect extraction.
+///
+/// `bytes` is the raw file, used to count incremental updates.
+pub fn check(document: &Document, bytes: &[u8]) -> PdfCompatibility {
+    let catalog = document.catalog().ok();
+    let acro_form = catalog
+        .and_then(|catalog| catalog.get(b"AcroForm").ok())
+        .and_then(|form| resolve_dict(document, form));
+
+    PdfCompatibility {
+        version: document.version.clone(),
+        encryption: encryption(document),
+        decrypted: false,
+        with_password: false,
+        xfa: acro_form.is_some_and(|form| form.has(b"XFA")),
+        dynamic_xfa: catalog
+            .and_then(|catalog| catalog.get(b"NeedsRendering").ok())
+            .and_then(|value| value.as_bool().ok())
+            .unwrap_or(false),
+        object_streams: document
+            .reference_table
+            .entries
+            .values()
+            .any(|entry| matches!(entry, XrefEntry::Compressed { .. })),
+        incremental_updates: incremental_updates(bytes),
+        recovered_objects: None,
+    }
+}
+
+impl PdfCompatibility {
+    /// Supported features worth knowing about, for the dry-run report
+    pub fn features(&self) -> Vec<String> {
+        let mut features = Vec::new();
+        match &self.encryption {
+            Some(method) if self.with_password => features.push(format!(
+                "encryption ({}, opened with the password given)",
+                method
+            )),
+            Some(method) if self.decrypted => features.push(format!(
+                "encryption ({}, opened with the empty user password)",
+                method
+            )),
+            Some(method) => features.push(format!("encryption ({})", method)),
+            None => {}
+        }
+        if self.dynamic_xfa {
+            features.push("dynamic XFA form".to_string());
+        } else if self.xfa {
+            features.push("XFA form".to_string());
+        }
+        if self.object_streams {
+            features.push("object streams".to_string());
+        }
+        match self.incremental_updates {
+            0 => {}
+            1 => features.push("1 incremental update".to_string()),
+            n => features.push(format!("{} incremental updates", n)),
+        }
+        features
+    }
+
+    /// Features that will make the converted text incomplete or wrong, and why
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if let Some(objects) = self.recovered_objects {
+            warnings.push(format!(
+                "cross-reference table is damaged or missing; rebuilt it from {} objects found in the file, so some content may be missing",
+                objects
+            ));
+        }
+        if self
+            .version
+            .parse::<f32>()
+            .map_or(true, |version| version > NEWEST_KNOWN_VERSION)
+        {
+            warnings.push(format!(
+                "PDF version {} is not recognised; features newer than PDF 2.0 are ignored",
+                self.version
+            ));
+        }
+        if let Some(method) = &self.encryption
+            && !self.decrypted
+        {
+            warnings.push(format!(
+                "document is encrypted ({}) and could not be decrypted without a password; text may be garbled",
+                method
+            ));
+        }
+        if self.dynamic_xfa {
+            warnings.push(
+                "document is a dynamic XFA form; its pages are drawn by the viewer and have no text to extract"
+                    .to_string(),
+            );
+        } else if self.xfa {
+            warnings.push(
+                "document contains an XFA form; form data kept only in the XFA packet is not extracted"
+                    .to_string(),
+            );
+        }
+        warnings
+    }
+}
+
+fn resolve_dict<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    limits::resolve(document, object)?.as_dict().ok()
+}
+
+/// Encryption method of an encrypted document
+pub fn encryption(document: &Document) -> Option<String> {
+    document
+        .get_encrypted()
+        .ok()
+        .map(|dict| encryption_method(document, dict))
+}
+
+/// Human-readable encryption method from the encryption dictionary
+fn encryption_method(document: &Document, dict: &Dictionary) -> String {
+    let name = |dict: &Dictionary, key: &[u8]| {
+        dict.get(key)
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok()
+    };
+    let filter = name(dict, b"Filter").unwrap_or_default();
+    if filter != "Standard" {
+        return format!("{} security handler", filter);
+    }
+
+    let integer = |key: &[u8]| dict.get(key).and_then(Object::as_i64).ok();
+    match integer(b"V").unwrap_or(0) {
+        1 => "RC4 40-bit".to_string(),
+        2 | 3 => format!("RC4 {}-bit", integer(b"Length").unwrap_or(40)),
+        4 => {
+            // The method of the default stream filter
+            let method = name(dict, b"StmF").and_then(|filter| {
+                let filters = dict
+                    .get(b"CF")
+                    .ok()
+                    .and_then(|cf| resolve_dict(document, cf))?;
+                let filter = filters.get(filter.as_bytes()).ok()?;
+                name(resolve_dict(document, filter)?, b"CFM")
+            });
+            match method.as_deref() {
+                Some("AESV2") => "AES-128".to_string(),
+                Some("V2") => "RC4 128-bit".to_string(),
+                _ => "crypt filters".to_string(),
+            }
+        }
+        5 => "AES-256".to_string(),
+        v => format!("unknown method V{}", v),
+    }
+}
+
+/// Number of revisions appended to the original file.
+///
+/// Every revision ends with `%%EOF`; a linearized file has an extra one after
+/// its first-page section without being updated.
+pub fn incremental_updates(bytes: &[u8]) -> usize {
+    let ends = bytes.windows(5).filter(|window| window == b"%%EOF").count();
+    ends.saturating_sub(if is_linearized(bytes) { 2 } else { 1 })
+}
+
+/// Whether a file starting with `head` is linearized ("fast web view"): its
+/// first page's objects come first, and the objects of each page together
+pub fn is_linearized(head: &[u8]) -> bool {
+    head[..head.len().min(1024)]
+        .windows(11)
+        .any(|window| window == b"/Linearized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    fn document(version: &str) -> Document {
+        let mut doc = Document::with_version(version);
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog" });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    #[test]
+    fn test_plain_document_has_no_warnings() {
+        let report = check(&document("1.7"), b"%PDF-1.7\n...%%EOF\n");
+        assert_eq!(report.version, "1.7");
+        assert!(report.features().is_empty());
+        assert!(report.warnings().is_empty());
+    }
+
+    #[test]
+    fn test_encryption_method() {
+        let mut doc = document("1.6");
+        let crypt_filter = dictionary! { "CFM" => "AESV2" };
+        let encrypt_id = doc.add_object(dictionary! {
+            "Filter" => "Standard",
+            "V" => 4,
+            "CF" => dictionary! { "StdCF" => crypt_filter },
+            "StmF" => "StdCF",
+        });
+        doc.trailer.set("Encrypt", encrypt_id);
+
+        let report = check(&doc, b"");
+        assert_eq!(report.encryption.as_deref(), Some("AES-128"));
+        assert!(report.warnings()[0].contains("encrypted (AES-128)"));
+
+        let decrypted = PdfCompatibility {
+            decrypted: true,
+            ..report
+        };
+        assert!(decrypted.warnings().is_empty());
+        assert_eq!(
+            decrypted.features(),
+            vec!["encryption (AES-128, opened with the empty user password)"]
+        );
+        let unlocked = PdfCompatibility {
+            with_password: true,
+            ..decrypted
+        };
+        assert_eq!(
+            unlocked.features(),
+            vec!["encryption (AES-128, opened with the password given)"]
+        );
+    }
+
+    #[test]
+    fn test_xfa_and_unknown_version() {
+        let mut doc = document("3.0");
+        let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+        let catalog = doc.get_dictionary_mut(catalog_id).unwrap();
+        catalog.set("AcroForm", dictionary! { "XFA" => Object::Array(vec![]) });
+        catalog.set("NeedsRendering", true);
+
+        let report = check(&doc, b"");
+        assert!(report.xfa && report.dynamic_xfa);
+        let warnings = report.warnings();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings[0].contains("version 3.0"));
+        assert!(warnings[1].contains("dynamic XFA"));
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        assert_eq!(incremental_updates(b"%PDF-1.4 ... %%EOF"), 0);
+        assert_eq!(
+            incremental_updates(b"%PDF-1.4 ... %%EOF ... %%EOF ... %%EOF"),
+            2
+        );
+        assert_eq!(
+            incremental_updates(b"%PDF-1.4 << /Linearized 1 >> ... %%EOF ... %%EOF"),
+            0
+        );
+    }
+}
@@ -0,0 +1,286 @@
+use super::geometry::Rect;
+use super::layout::{MarkupKind, PageLayout, TextRun};
+use super::types::UnderlineStyle;
+use super::vector;
+
+/// An underline sits at most this many font sizes below the baseline
+const UNDERLINE_DEPTH: f32 = 0.35;
+/// ... or this many font sizes above it, for lines drawn through descenders
+const UNDERLINE_RISE: f32 = 0.05;
+/// A strikethrough line sits between these heights above the baseline, in font sizes
+const STRIKE_LOW: f32 = 0.15;
+const STRIKE_HIGH: f32 = 0.5;
+/// A drawn line covers at least this share of the run it underlines
+const DRAWN_COVERAGE: f32 = 0.8;
+/// An annotation covers at least this share of the run it marks
+const ANNOTATION_COVERAGE: f32 = 0.5;
+
+/// Whether a line drawn at `bounds` underlines a text run
+pub fn underlines(bounds: &Rect, run: &TextRun) -> bool {
+    let y = bounds.y + bounds.height / 2.0;
+    let size = run.size.max(1.0);
+    y >= run.y - size * UNDERLINE_DEPTH
+        && y <= run.y + size * UNDERLINE_RISE
+        && coverage(bounds, run) >= DRAWN_COVERAGE
+}
+
+/// Whether a line drawn at `bounds` strikes through a text run
+pub fn strikes(bounds: &Rect, run: &TextRun) -> bool {
+    let y = bounds.y + bounds.height / 2.0;
+    let size = run.size.max(1.0);
+    y >= run.y + size * STRIKE_LOW
+        && y <= run.y + size * STRIKE_HIGH
+        && coverage(bounds, run) >= DRAWN_COVERAGE
+}
+
+/// Whether a line drawn at `bounds` underlines or strikes through any run on the page
+pub fn decorates_text(bounds: &Rect, layout: &PageLayout) -> bool {
+    layout
+        .runs
+        .iter()
+        .any(|run| underlines(bounds, run) || strikes(bounds, run))
+}
+
+/// Whether a run is underlined by a drawn line or an underline annotation
+pub fn is_underlined(run: &TextRun, layout: &PageLayout) -> bool {
+    is_marked(run, layout, MarkupKind::Underline, underlines)
+}
+
+/// Whether a run is struck through by a drawn line or a strikeout annotation
+pub fn is_struck(run: &TextRun, layout: &PageLayout) -> bool {
+    is_marked(run, layout, MarkupKind::StrikeOut, strikes)
+}
+
+fn is_marked(
+    run: &TextRun,
+    layout: &PageLayout,
+    kind: MarkupKind,
+    drawn: fn(&Rect, &TextRun) -> bool,
+) -> bool {
+    if run.text.trim().is_empty() || run.width <= 0.0 {
+        return false;
+    }
+    layout
+        .paths
+        .iter()
+        .any(|path| vector::is_horizontal_line(path) && drawn(&path.bounds, run))
+        || layout
+            .markup
+            .iter()
+            .filter(|markup| markup.kind == kind)
+            .flat_map(|markup| &markup.rects)
+            .any(|rect| {
+                run.y >= rect.y - 1.0
+                    && run.y <= rect.top()
+                    && coverage(rect, run) >= ANNOTATION_COVERAGE
+            })
+}
+
+/// Share of a run's width lying within the horizontal extent of `bounds`
+fn coverage(bounds: &Rect, run: &TextRun) -> f32 {
+    let overlap = bounds.right().min(run.x + run.width) - bounds.x.max(run.x);
+    overlap.max(0.0) / run.width
+}
+
+/// Opening and closing markup for underlined text
+fn markers(style: UnderlineStyle) -> (&'static str, &'static str) {
+    match style {
+        UnderlineStyle::Emphasis => ("*", "*"),
+        UnderlineStyle::Bold => ("**", "**"),
+        UnderlineStyle::Html => ("<u>", "</u>"),
+    }
+}
+
+/// Wrap each stretch of underlined runs in the style's markup
+pub fn underline(runs: &mut [TextRun], underlined: &[bool], style: UnderlineStyle) {
+    let (open, close) = markers(style);
+    wrap(runs, underlined, open, close);
+}
+
+/// Wrap each stretch of struck-through runs in `~~`
+pub fn strike(runs: &mut [TextRun], struck: &[bool]) {
+    wrap(runs, struck, "~~", "~~");
+}
+
+/// Wrap runs painted in a colour in `<span style="color:#rrggbb">`.
+///
+/// Black and grays are body text styling and are left alone; consecutive runs
+/// of the same colour share one span.
+pub fn color_spans(runs: &mut [TextRun]) {
+    let mut start = 0;
+    while start < runs.len() {
+        let color = runs[start].color;
+        let end = start
+            + runs[start..]
+                .iter()
+                .take_while(|run| run.color == color || run.text.trim().is_empty())
+                .count();
+        if !color.is_gray() {
+            let open = format!("<span style=\"color:{}\">", color.hex());
+            let flagged = vec![true; end - start];
+            wrap(&mut runs[start..end], &flagged, &open, "</span>");
+        }
+        start = end;
+    }
+}
+
+/// Whether a run is painted in a colour other than black or a gray
+pub fn is_colored(run: &TextRun) -> bool {
+    !run.color.is_gray() && !run.text.trim().is_empty()
+}
+
+/// Wrap each stretch of flagged runs in `open` and `close`.
+///
+/// Consecutive runs share one pair of markers, placed inside any surrounding
+/// whitespace so Markdown emphasis stays valid.
+fn wrap(runs: &mut [TextRun], flagged: &[bool], open: &str, close: &str) {
+    let mut i = 0;
+    while i < runs.len() {
+        if !flagged[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i + 1 < runs.len() && flagged[i + 1] {
+            i += 1;
+        }
+        let first = &mut runs[start].text;
+        let indent = first.len() - first.trim_start().len();
+        first.insert_str(indent, open);
+        let last = &mut runs[i].text;
+        let end = last.trim_end().len();
+        last.insert_str(end, close);
+        i += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Color, PathShape, Segment};
+    use crate::layout::Markup;
+
+    fn run(text: &str, x: f32, width: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: "Helvetica".to_string(),
+            size: 12.0,
+            x,
+            y: 700.0,
+            width,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    fn stroke(x0: f32, x1: f32, y: f32) -> PathShape {
+        PathShape {
+            segments: vec![Segment::MoveTo(x0, y), Segment::LineTo(x1, y)],
+            stroke: Some(Color::BLACK),
+            fill: None,
+            line_width: 0.5,
+            bounds: Rect::from_corners(x0, y, x1, y),
+            runs_before: 0,
+        }
+    }
+
+    #[test]
+    fn test_drawn_underline() {
+        let layout = PageLayout {
+            paths: vec![stroke(72.0, 130.0, 698.0), stroke(72.0, 540.0, 650.0)],
+            ..Default::default()
+        };
+        assert!(is_underlined(&run("Important", 72.0, 56.0), &layout));
+        // A line under only part of the run, or far below it, is not an underline
+        assert!(!is_underlined(&run(" and more text", 128.0, 80.0), &layout));
+        assert!(!is_underlined(
+            &run("Body", 72.0, 30.0),
+            &PageLayout {
+                paths: vec![stroke(72.0, 540.0, 680.0)],
+                ..Default::default()
+            }
+        ));
+    }
+
+    #[test]
+    fn test_annotation_underline() {
+        let layout = PageLayout {
+            markup: vec![Markup {
+                kind: MarkupKind::Underline,
+                rects: vec![Rect::from_corners(70.0, 696.0, 132.0, 712.0)],
+            }],
+            ..Default::default()
+        };
+        assert!(is_underlined(&run("Important", 72.0, 56.0), &layout));
+        assert!(!is_underlined(&run("Other", 200.0, 30.0), &layout));
+    }
+
+    #[test]
+    fn test_underline_styles() {
+        let mut runs = vec![
+            run("See ", 0.0, 24.0),
+            run("the ", 24.0, 20.0),
+            run("manual ", 44.0, 40.0),
+            run("now.", 84.0, 24.0),
+        ];
+        underline(
+            &mut runs,
+            &[false, true, true, false],
+            UnderlineStyle::Emphasis,
+        );
+        let text: String = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(text, "See *the manual* now.");
+
+        let mut runs = vec![run(" term", 0.0, 30.0)];
+        underline(&mut runs, &[true], UnderlineStyle::Html);
+        assert_eq!(runs[0].text, " <u>term</u>");
+        underline(&mut runs, &[true], UnderlineStyle::Bold);
+        assert_eq!(runs[0].text, " **<u>term</u>**");
+    }
+
+    #[test]
+    fn test_color_spans() {
+        let red = Color(204, 0, 0);
+        let mut runs = vec![
+            run("The buyer ", 0.0, 60.0),
+            run("must", 60.0, 24.0),
+            run(" ", 84.0, 6.0),
+            run("sign ", 90.0, 30.0),
+            run("the form.", 120.0, 54.0),
+        ];
+        for run in &mut runs[1..4] {
+            run.color = red;
+        }
+        // Dark gray from a CMYK black is not a colour
+        runs[4].color = Color(35, 31, 32);
+        color_spans(&mut runs);
+        let text: String = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(
+            text,
+            "The buyer <span style=\"color:#cc0000\">must sign</span> the form."
+        );
+        assert!(is_colored(&runs[1]) && !is_colored(&runs[4]));
+    }
+
+    #[test]
+    fn test_strikethrough() {
+        let layout = PageLayout {
+            paths: vec![stroke(72.0, 130.0, 704.0)],
+            markup: vec![Markup {
+                kind: MarkupKind::StrikeOut,
+                rects: vec![Rect::from_corners(200.0, 696.0, 240.0, 712.0)],
+            }],
+            ..Default::default()
+        };
+        let deleted = run("old terms", 72.0, 56.0);
+        assert!(is_struck(&deleted, &layout));
+        assert!(!is_underlined(&deleted, &layout));
+        assert!(is_struck(&run("gone", 200.0, 24.0), &layout));
+        assert!(!is_struck(&run("kept", 140.0, 24.0), &layout));
+
+        let mut runs = vec![run("Pay ", 0.0, 24.0), deleted, run(" now.", 0.0, 30.0)];
+        strike(&mut runs, &[false, true, false]);
+        let text: String = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(text, "Pay ~~old terms~~ now.");
+    }
+}
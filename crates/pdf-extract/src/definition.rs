@@ -0,0 +1,229 @@
+use super::caption::line_text;
+use super::layout::TextRun;
+use std::ops::Range;
+
+/// A definition starts at least this many font sizes right of its term
+const MIN_INDENT: f32 = 1.0;
+/// Longest term, in characters
+const MAX_TERM_LEN: usize = 60;
+/// Headings that introduce a glossary of `Term: definition` lines
+const GLOSSARY_HEADINGS: [&str; 5] = [
+    "glossary",
+    "definitions",
+    "terms and definitions",
+    "key terms",
+    "terminology",
+];
+/// Separators between a term and its definition on a glossary line
+const SEPARATORS: [&str; 4] = [": ", " — ", " – ", " - "];
+
+/// A term and its definition, spanning `lines` of the page
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub lines: Range<usize>,
+    pub term: String,
+    pub definition: String,
+}
+
+impl Entry {
+    /// Markdown definition list item (`Term\n: definition`)
+    pub fn to_markdown(&self) -> String {
+        format!("{}\n: {}", self.term, self.definition)
+    }
+}
+
+/// Check whether a base font name is a bold face
+pub fn is_bold(font: &str) -> bool {
+    ["Bold", "Black", "Heavy", "Semibold", "Demi"]
+        .iter()
+        .any(|weight| font.contains(weight))
+        || font.ends_with(",B")
+}
+
+/// Find term/definition layouts: a bold term line followed by indented
+/// explanation lines, or `Term: definition` lines under a glossary heading
+pub fn find(lines: &[Vec<&TextRun>]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut in_glossary = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let text = line_text(&lines[i]);
+        if is_glossary_heading(&text) {
+            in_glossary = true;
+            i += 1;
+            continue;
+        }
+
+        if let Some(entry) = bold_term_entry(lines, i) {
+            i = entry.lines.end;
+            entries.push(entry);
+            continue;
+        }
+
+        if in_glossary {
+            match glossary_entry(lines, i) {
+                Some(entry) => {
+                    i = entry.lines.end;
+                    entries.push(entry);
+                    continue;
+                }
+                None => in_glossary = false,
+            }
+        }
+        i += 1;
+    }
+    entries
+}
+
+fn is_glossary_heading(text: &str) -> bool {
+    let text = text.trim_end_matches(':').trim().to_lowercase();
+    GLOSSARY_HEADINGS.contains(&text.as_str())
+}
+
+/// A short all-bold line followed by lines indented further
+fn bold_term_entry(lines: &[Vec<&TextRun>], index: usize) -> Option<Entry> {
+    let line = &lines[index];
+    let term = line_text(line);
+    let all_bold = line
+        .iter()
+        .filter(|run| !run.text.trim().is_empty())
+        .all(|run| is_bold(&run.font));
+    if !all_bold || term.is_empty() || term.chars().count() > MAX_TERM_LEN {
+        return None;
+    }
+
+    let indent = line[0].x + line[0].size.max(1.0) * MIN_INDENT;
+    let definition_x = lines.get(index + 1)?[0].x;
+    let end = (index + 1..lines.len())
+        .find(|&j| {
+            let first = lines[j][0];
+            is_bold(&first.font) || first.x < indent || (first.x - definition_x).abs() > 1.0
+        })
+        .unwrap_or(lines.len());
+    if end == index + 1 {
+        return None;
+    }
+
+    Some(Entry {
+        lines: index..end,
+        term: term.trim_end_matches(':').trim().to_string(),
+        definition: joined_text(&lines[index + 1..end]),
+    })
+}
+
+/// A `Term: definition` line in a glossary, with any indented continuation lines
+fn glossary_entry(lines: &[Vec<&TextRun>], index: usize) -> Option<Entry> {
+    let text = line_text(&lines[index]);
+    let (term, definition) = SEPARATORS
+        .iter()
+        .filter_map(|separator| text.split_once(separator))
+        .min_by_key(|(term, _)| term.len())?;
+    let term = term.trim();
+    if term.is_empty() || term.chars().count() > MAX_TERM_LEN || definition.trim().is_empty() {
+        return None;
+    }
+
+    let indent = lines[index][0].x + lines[index][0].size.max(1.0) * MIN_INDENT;
+    let end = (index + 1..lines.len())
+        .find(|&j| lines[j][0].x < indent)
+        .unwrap_or(lines.len());
+    let mut definition = definition.trim().to_string();
+    if end > index + 1 {
+        definition.push(' ');
+        definition.push_str(&joined_text(&lines[index + 1..end]));
+    }
+
+    Some(Entry {
+        lines: index..end,
+        term: term.to_string(),
+        definition,
+    })
+}
+
+fn joined_text(lines: &[Vec<&TextRun>]) -> String {
+    lines
+        .iter()
+        .map(|line| line_text(line))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Color;
+
+    fn run(text: &str, font: &str, x: f32, y: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: font.to_string(),
+            size: 12.0,
+            x,
+            y,
+            width: 6.0 * text.len() as f32,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    #[test]
+    fn test_bold_term_with_indented_definition() {
+        let runs = [
+            run("Latency", "Helvetica-Bold", 72.0, 700.0),
+            run("Time taken by", "Helvetica", 100.0, 686.0),
+            run("one request.", "Helvetica", 100.0, 672.0),
+            run("Throughput", "Helvetica-Bold", 72.0, 650.0),
+            run("Requests per second.", "Helvetica", 100.0, 636.0),
+            run("Back to body text.", "Helvetica", 72.0, 610.0),
+        ];
+        let lines: Vec<Vec<&TextRun>> = runs.iter().map(|run| vec![run]).collect();
+
+        let entries = find(&lines);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].lines, 0..3);
+        assert_eq!(
+            entries[0].to_markdown(),
+            "Latency\n: Time taken by one request."
+        );
+        assert_eq!(entries[1].lines, 3..5);
+    }
+
+    #[test]
+    fn test_glossary_section() {
+        let runs = [
+            run("Glossary", "Helvetica-Bold", 72.0, 700.0),
+            run("API: Application programming", "Helvetica", 72.0, 680.0),
+            run("interface.", "Helvetica", 90.0, 666.0),
+            run("SDK — Software development kit.", "Helvetica", 72.0, 650.0),
+            run("The glossary ends here.", "Helvetica", 72.0, 620.0),
+        ];
+        let lines: Vec<Vec<&TextRun>> = runs.iter().map(|run| vec![run]).collect();
+
+        let entries = find(&lines);
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    lines: 1..3,
+                    term: "API".to_string(),
+                    definition: "Application programming interface.".to_string(),
+                },
+                Entry {
+                    lines: 3..4,
+                    term: "SDK".to_string(),
+                    definition: "Software development kit.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bold_heading_without_indent_is_not_a_term() {
+        let runs = [
+            run("Introduction", "Helvetica-Bold", 72.0, 700.0),
+            run("Body text at the margin.", "Helvetica", 72.0, 686.0),
+        ];
+        let lines: Vec<Vec<&TextRun>> = runs.iter().map(|run| vec![run]).collect();
+        assert!(find(&lines).is_empty());
+    }
+}
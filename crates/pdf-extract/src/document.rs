@@ -1,53 +1,364 @@
-use super::{metadata, text, types::{ExtractedContent, PdfMetadata}};
+use super::{
+    compatibility, images, inventory, layout, limits, metadata, outline, page_tree, portfolio,
+    ranged::{self, RangeSource},
+    recovery, signature, stream_cache, text,
+    types::{
+        AnnotationCounts, EmbeddedDocument, ExtractOptions, ExtractedContent, ExtractedImage,
+        FontInfo, ImageInfo, OutlineEntry, PageSelection, PdfCompatibility, PdfMetadata,
+    },
+    validation::{check_header, validate_pdf},
+};
 use crate::{PdfError, Result};
-use log::info;
-use lopdf::Document;
-use std::path::{Path, PathBuf};
+use log::{info, warn};
+use lopdf::encryption::DecryptionError;
+use lopdf::{Document, Object, ObjectId, Reader};
+use std::ops::ControlFlow;
+use std::path::Path;
 
 /// PDF document wrapper
 #[derive(Debug)]
 pub struct PdfDocument {
-    #[allow(dead_code)]
-    path: PathBuf,
     document: Document,
+    /// Revisions appended to the original file, counted when it was read
+    incremental_updates: usize,
+    /// Encryption method, when the document was decrypted on open
+    decrypted_from: Option<String>,
+    /// It was decrypted with a password given to [`decrypt`](Self::decrypt)
+    /// rather than the empty one
+    with_password: bool,
+    /// Objects found by scanning the file, when its xref table had to be rebuilt
+    recovered_objects: Option<usize>,
+    /// Bytes of the file up to its trailing whitespace, where the last
+    /// signature made ends
+    length: u64,
 }
 
 impl PdfDocument {
-    /// Open and validate a PDF file
+    /// Open and validate a PDF file.
+    ///
+    /// The file is judged by its content (the `%PDF-` header and a successful
+    /// parse), so temp files and content-addressed blobs without a `.pdf`
+    /// extension open too; a missing extension is only logged.
     pub fn open(path: &Path) -> Result<Self> {
+        Self::load(path, false)
+    }
+
+    /// Open a PDF file like [`open`](Self::open), without keeping the data of
+    /// its images.
+    ///
+    /// A scanned document is mostly image data, which text extraction never
+    /// reads; leaving it out while parsing keeps the document in memory to a
+    /// small part of the file's size. Images cannot be extracted from the
+    /// document returned, and the image inventory lists them with no bytes.
+    pub fn open_without_images(path: &Path) -> Result<Self> {
+        Self::load(path, true)
+    }
+
+    fn load(path: &Path, without_images: bool) -> Result<Self> {
         info!("Opening PDF file: {}", path.display());
 
-        // Validate file extension
-        if let Some(ext) = path.extension() {
-            if ext.to_str() != Some("pdf") {
-                return Err(PdfError::InvalidInput(
-                    "File must have .pdf extension".to_string(),
-                ));
-            }
-        } else {
-            return Err(PdfError::InvalidInput(
-                "File must have .pdf extension".to_string(),
-            ));
+        if !path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+        {
+            warn!(
+                "{} has no .pdf extension; checking its content instead",
+                path.display()
+            );
         }
+        validate_pdf(path)?;
+
+        let bytes = std::fs::read(path)?;
+        Self::parse(&bytes, without_images)
+    }
+
+    /// Open a PDF held in memory, such as an upload or a file read by the
+    /// caller; it is judged by its content, like [`open`](Self::open)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        info!("Opening PDF from {} bytes", bytes.len());
+        check_header(bytes)?;
+        Self::parse(bytes, false)
+    }
+
+    /// Load only the pages `selection` picks, and the objects they need, of a
+    /// file read a range of bytes at a time, such as a large file on a web
+    /// server; the pages are numbered from 1 in the document returned.
+    ///
+    /// A linearized file keeps the objects of each page together, so they
+    /// take a few requests; any other may take one for each object.
+    pub fn from_ranges(source: &mut dyn RangeSource, selection: &PageSelection) -> Result<Self> {
+        info!("Opening PDF of {} bytes a range at a time", source.size());
+        let (mut document, incremental_updates) = ranged::load(source, selection)?;
+        let decrypted_from = decrypt_with_empty_password(&mut document);
+        Ok(Self {
+            document,
+            incremental_updates,
+            decrypted_from,
+            with_password: false,
+            recovered_objects: None,
+            length: source.size(),
+        })
+    }
 
-        // Load the PDF document
-        let document = Document::load(path)
-            .map_err(|e| PdfError::Processing(format!("Failed to load PDF: {}", e)))?;
+    /// Open only the pages `selection` picks of a PDF file, reading the
+    /// objects they need and nothing else, as
+    /// [`from_ranges`](Self::from_ranges) does.
+    ///
+    /// The interactive form, structure tree and name tree are not read, so
+    /// signatures, XFA forms, tagging and portfolios go unreported.
+    pub fn open_pages(path: &Path, selection: &PageSelection) -> Result<Self> {
+        info!(
+            "Opening pages {} of PDF file: {}",
+            selection,
+            path.display()
+        );
+        let mut source = ranged::FileSource::open(path)?;
+        Self::from_ranges(&mut source, selection)
+    }
+
+    fn parse(bytes: &[u8], without_images: bool) -> Result<Self> {
+        // Refuse nesting deep enough to overflow the parser's stack
+        limits::check_nesting(bytes)?;
+
+        // Load the PDF document, rebuilding a damaged cross-reference table
+        let reader = Reader {
+            buffer: bytes,
+            document: Document::new(),
+        };
+        let filter = without_images.then_some(drop_image_data as _);
+        let (mut document, recovered_objects) = match reader.read(filter) {
+            Ok(document) => (document, None),
+            Err(e) => {
+                let (mut document, objects) = recovery::rebuild(bytes)
+                    .ok_or_else(|| PdfError::Processing(format!("Failed to load PDF: {}", e)))?;
+                warn!(
+                    "Failed to load PDF ({}); rebuilt it from {} objects",
+                    e, objects
+                );
+                if without_images {
+                    for object in document.objects.values_mut() {
+                        if let Object::Stream(stream) = object {
+                            empty_image(stream);
+                        }
+                    }
+                }
+                (document, Some(objects))
+            }
+        };
+        let decrypted_from = decrypt_with_empty_password(&mut document);
 
         Ok(Self {
-            path: path.to_path_buf(),
             document,
+            incremental_updates: compatibility::incremental_updates(bytes),
+            decrypted_from,
+            with_password: false,
+            recovered_objects,
+            length: bytes.trim_ascii_end().len() as u64,
         })
     }
 
+    /// Keep up to `budget` bytes of the content streams and ToUnicode maps
+    /// that pages read decompressed, so each is inflated once rather than on
+    /// every read; those shared by the most pages go first. Returns the
+    /// number of streams decompressed.
+    pub fn cache_streams(&mut self, budget: usize) -> usize {
+        stream_cache::cache_streams(&mut self.document, budget).0
+    }
+
+    /// Whether the document is encrypted and the empty user password did
+    /// not open it, so its text stays garbled without [`decrypt`](Self::decrypt)
+    pub fn needs_password(&self) -> bool {
+        self.decrypted_from.is_none() && compatibility::encryption(&self.document).is_some()
+    }
+
+    /// Decrypt the document with its user password. A wrong password is an
+    /// [`InvalidInput`](PdfError::InvalidInput) error, after which another
+    /// may be tried; an encryption lopdf cannot undo is a processing error.
+    pub fn decrypt(&mut self, password: &str) -> Result<()> {
+        let Some(method) = compatibility::encryption(&self.document) else {
+            return Err(PdfError::InvalidInput(
+                "the document is not encrypted".to_string(),
+            ));
+        };
+        match self.document.decrypt(password) {
+            Ok(()) => {
+                info!("Decrypted {} document with the password given", method);
+                self.decrypted_from = Some(method);
+                self.with_password = true;
+                Ok(())
+            }
+            Err(lopdf::Error::Decryption(DecryptionError::IncorrectPassword)) => Err(
+                PdfError::InvalidInput("the password is incorrect".to_string()),
+            ),
+            Err(e) => Err(PdfError::Processing(format!(
+                "Could not decrypt {} document: {}",
+                method, e
+            ))),
+        }
+    }
+
+    /// Number of pages in the document
+    pub fn page_count(&self) -> usize {
+        self.document.get_pages().len()
+    }
+
+    /// Keep only the pages `selection` picks, numbered from 1 again in the
+    /// order of the document; fails when it picks none
+    pub fn select_pages(&mut self, selection: &PageSelection) -> Result<()> {
+        let pages = self.document.get_pages();
+        let numbers = selection.pages(pages.len() as u32);
+        if numbers.is_empty() {
+            return Err(PdfError::InvalidInput(format!(
+                "pages {} are not in the document, which has {}",
+                selection,
+                pages.len()
+            )));
+        }
+        let kept: Vec<ObjectId> = numbers.iter().map(|number| pages[number]).collect();
+        page_tree::keep(&mut self.document, &kept);
+        Ok(())
+    }
+
     /// Extract text content from PDF
     pub fn extract_text(&self) -> Result<ExtractedContent> {
         text::extract_text(&self.document)
     }
 
+    /// Extract text content from PDF with the given options
+    pub fn extract_text_with(&self, options: &ExtractOptions) -> Result<ExtractedContent> {
+        text::extract_text_with(&self.document, options)
+    }
+
+    /// Extract text page by page, handing the cleaned text to `emit` as soon
+    /// as it is ready and the images of each page to `emit_images`. Joined,
+    /// the pieces are the text `extract_text_with` returns (except that
+    /// glyphs are replaced within each page); returning `ControlFlow::Break`
+    /// stops the extraction.
+    pub fn extract_text_streaming(
+        &self,
+        options: &ExtractOptions,
+        emit: impl FnMut(String) -> ControlFlow<()>,
+        emit_images: impl FnMut(Vec<ExtractedImage>) -> ControlFlow<()>,
+    ) -> Result<ExtractedContent> {
+        text::extract_text_streaming(&self.document, options, emit, emit_images)
+    }
+
+    /// Extract only the images, with the image options of `options`
+    pub fn extract_images(&self, options: &ExtractOptions) -> Vec<ExtractedImage> {
+        images::extract_images(&self.document, options)
+    }
+
+    /// Extract the plain text of one page, numbered from 1
+    pub fn extract_page_text(&self, page_num: u32) -> Result<String> {
+        text::extract_page(&self.document, page_num)
+    }
+
+    /// Count the glyphs the content stream of one page shows, numbered from
+    /// 1, to compare with what extraction finds
+    pub fn page_glyph_count(&self, page_num: u32) -> Result<usize> {
+        let page_id =
+            *self.document.get_pages().get(&page_num).ok_or_else(|| {
+                PdfError::InvalidInput(format!("Page {} does not exist", page_num))
+            })?;
+        layout::glyph_count(&self.document, page_id)
+    }
+
     /// Extract metadata and structure for preview (dry-run mode)
     pub fn extract_metadata(&self) -> Result<PdfMetadata> {
-        metadata::extract_metadata(&self.document)
+        Ok(PdfMetadata {
+            signatures: signature::signatures(&self.document, self.length),
+            ..metadata::extract_metadata(&self.document)?
+        })
+    }
+
+    /// List the images drawn on each page
+    pub fn image_inventory(&self) -> Vec<ImageInfo> {
+        inventory::images(&self.document)
+    }
+
+    /// List the fonts used by the pages
+    pub fn font_inventory(&self) -> Vec<FontInfo> {
+        inventory::fonts(&self.document)
+    }
+
+    /// Count the links and other annotations on all pages
+    pub fn annotation_counts(&self) -> AnnotationCounts {
+        inventory::annotations(&self.document)
+    }
+
+    /// Read the bookmarks of the document outline, if it has one
+    pub fn outline(&self) -> Vec<OutlineEntry> {
+        outline::bookmarks(&self.document)
+    }
+
+    /// The PDFs of a portfolio, whose single page is usually only a cover
+    /// sheet; empty for any other document
+    pub fn portfolio_documents(&self) -> Vec<EmbeddedDocument> {
+        portfolio::documents(&self.document)
+    }
+
+    /// Report the PDF version and features that may degrade extraction
+    pub fn compatibility(&self) -> Result<PdfCompatibility> {
+        let mut report = PdfCompatibility {
+            incremental_updates: self.incremental_updates,
+            ..compatibility::check(&self.document, &[])
+        };
+        if let Some(method) = &self.decrypted_from {
+            report.encryption = Some(method.clone());
+            report.decrypted = true;
+            report.with_password = self.with_password;
+        }
+        report.recovered_objects = self.recovered_objects;
+        Ok(report)
+    }
+}
+
+/// Parser filter emptying image XObjects as they are read.
+///
+/// lopdf keeps the object it passed in, except for the members of object
+/// streams, where it keeps the object returned; those are never streams, so
+/// only they are copied.
+fn drop_image_data(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+    if let Object::Stream(stream) = object {
+        empty_image(stream);
+        return Some((id, Object::Null));
+    }
+    Some((id, object.clone()))
+}
+
+/// Drop the data of an image XObject
+fn empty_image(stream: &mut lopdf::Stream) {
+    if stream
+        .dict
+        .get(b"Subtype")
+        .and_then(Object::as_name)
+        .is_ok_and(|subtype| subtype == b"Image")
+    {
+        stream.content = Vec::new();
+        // lopdf reads empty streams again from the file otherwise
+        stream.start_position = None;
+    }
+}
+
+/// Decrypt a document whose user password is empty.
+///
+/// Many "protected" PDFs open in any viewer and only restrict printing or
+/// copying through the owner password; their text is decrypted so it can be
+/// read. Returns the encryption method when the document was decrypted.
+fn decrypt_with_empty_password(document: &mut Document) -> Option<String> {
+    let method = compatibility::encryption(document)?;
+    match document.decrypt("") {
+        Ok(()) => {
+            info!(
+                "Decrypted {} document with the empty user password; owner-password restrictions were bypassed for text extraction",
+                method
+            );
+            Some(method)
+        }
+        Err(e) => {
+            warn!("Could not decrypt {} document: {}", method, e);
+            None
+        }
     }
 }
 
@@ -55,6 +366,7 @@ impl PdfDocument {
 mod tests {
     use super::*;
     use crate::test_utils::create_valid_test_pdf;
+    use lopdf::{Stream, dictionary};
     use std::fs;
     use tempfile::TempDir;
 
@@ -70,7 +382,7 @@ mod tests {
     }
 
     #[test]
-    fn test_pdf_document_open_with_invalid_extension() {
+    fn test_pdf_document_open_with_invalid_content() {
         let temp_dir = TempDir::new().unwrap();
         let txt_path = temp_dir.path().join("test.txt");
         fs::write(&txt_path, "content").unwrap();
@@ -78,10 +390,20 @@ mod tests {
         let result = PdfDocument::open(&txt_path);
         assert!(result.is_err());
         match result.unwrap_err() {
-            PdfError::InvalidInput(msg) => {
-                assert!(msg.contains(".pdf extension"));
+            PdfError::Processing(msg) => {
+                assert!(msg.contains("not a valid PDF"));
             }
-            _ => panic!("Expected InvalidInput error"),
+            _ => panic!("Expected Processing error"),
+        }
+    }
+
+    #[test]
+    fn test_pdf_document_open_ignores_extension() {
+        let temp_dir = TempDir::new().unwrap();
+        for name in ["blob", "upload.tmp", "REPORT.PDF"] {
+            let path = temp_dir.path().join(name);
+            create_valid_test_pdf(&path).unwrap();
+            assert!(PdfDocument::open(&path).is_ok(), "{}", name);
         }
     }
 
@@ -129,4 +451,95 @@ mod tests {
         let metadata = result.unwrap();
         assert!(metadata.page_count > 0);
     }
+
+    #[test]
+    fn test_pdf_document_from_bytes() {
+        let temp_dir = TempDir::new().unwrap();
+        let pdf_path = temp_dir.path().join("test.pdf");
+        create_valid_test_pdf(&pdf_path).unwrap();
+        let bytes = fs::read(&pdf_path).unwrap();
+
+        let from_file = PdfDocument::open(&pdf_path).unwrap();
+        let from_bytes = PdfDocument::from_bytes(&bytes).unwrap();
+        assert_eq!(
+            from_bytes.extract_text().unwrap().text,
+            from_file.extract_text().unwrap().text
+        );
+        assert_eq!(
+            from_bytes.compatibility().unwrap(),
+            from_file.compatibility().unwrap()
+        );
+        assert!(PdfDocument::from_bytes(b"content").is_err());
+    }
+
+    #[test]
+    fn test_open_without_images() {
+        let temp_dir = TempDir::new().unwrap();
+        let pdf_path = temp_dir.path().join("scan.pdf");
+        create_valid_test_pdf(&pdf_path).unwrap();
+        let mut doc = Document::load(&pdf_path).unwrap();
+        let image = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => 2,
+                "Height" => 2,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 8,
+            },
+            vec![0, 255, 255, 0],
+        );
+        let image_id = doc.add_object(image);
+        doc.save(&pdf_path).unwrap();
+
+        let image_data = |doc: &PdfDocument| {
+            doc.document
+                .get_object(image_id)
+                .and_then(Object::as_stream)
+                .unwrap()
+                .content
+                .len()
+        };
+        let full = PdfDocument::open(&pdf_path).unwrap();
+        let lean = PdfDocument::open_without_images(&pdf_path).unwrap();
+        assert_eq!(image_data(&full), 4);
+        assert_eq!(image_data(&lean), 0);
+        assert_eq!(
+            lean.extract_text().unwrap().text,
+            full.extract_text().unwrap().text
+        );
+    }
+
+    #[test]
+    fn test_open_pages() {
+        let temp_dir = TempDir::new().unwrap();
+        let pdf_path = temp_dir.path().join("test.pdf");
+        create_valid_test_pdf(&pdf_path).unwrap();
+
+        let first: PageSelection = "1".parse().unwrap();
+        let doc = PdfDocument::open_pages(&pdf_path, &first).unwrap();
+        assert_eq!(doc.page_count(), 1);
+        assert_eq!(
+            doc.extract_text().unwrap().text,
+            PdfDocument::open(&pdf_path)
+                .unwrap()
+                .extract_text()
+                .unwrap()
+                .text
+        );
+        let missing: PageSelection = "2".parse().unwrap();
+        assert!(PdfDocument::open_pages(&pdf_path, &missing).is_err());
+    }
+
+    #[test]
+    fn test_compatibility_without_reading_the_file_again() {
+        let temp_dir = TempDir::new().unwrap();
+        let pdf_path = temp_dir.path().join("test.pdf");
+        create_valid_test_pdf(&pdf_path).unwrap();
+
+        let doc = PdfDocument::open(&pdf_path).unwrap();
+        fs::remove_file(&pdf_path).unwrap();
+        let report = doc.compatibility().unwrap();
+        assert_eq!(report.incremental_updates, 0);
+    }
 }
@@ -0,0 +1,52 @@
+//! Drop caps: the oversized first letter of a chapter or section, drawn
+//! apart from the rest of its word, which extraction leaves on a line of
+//! its own
+
+/// The page text with each drop cap joined to the word it starts: a line
+/// holding one capital letter, followed by a line that starts in lowercase
+pub fn merge(text: String) -> String {
+    if !text.lines().any(is_drop_cap) {
+        return text;
+    }
+
+    let mut lines = text.split_inclusive('\n').peekable();
+    let mut merged = String::with_capacity(text.len());
+    while let Some(line) = lines.next() {
+        let continues = lines.peek().is_some_and(|next| {
+            next.trim_start()
+                .chars()
+                .next()
+                .is_some_and(char::is_lowercase)
+        });
+        if continues && is_drop_cap(line) {
+            merged.push_str(line.trim());
+            let next = lines.next().expect("peeked");
+            merged.push_str(next.trim_start());
+        } else {
+            merged.push_str(line);
+        }
+    }
+    merged
+}
+
+fn is_drop_cap(line: &str) -> bool {
+    let mut chars = line.trim().chars();
+    chars.next().is_some_and(char::is_uppercase) && chars.next().is_none()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        assert_eq!(
+            merge("Chapter One\nT\nhe night was\ndark.\n".to_string()),
+            "Chapter One\nThe night was\ndark.\n"
+        );
+        assert_eq!(merge("\u{dc} \n  ber Land".to_string()), "\u{dc}ber Land");
+        // Labels and initials followed by a capitalized line stay apart
+        let labels = "A\nB\nFigure 2: the circuit\n";
+        assert_eq!(merge(labels.to_string()), labels);
+    }
+}
@@ -0,0 +1,304 @@
+use super::caption::line_text;
+use super::layout::TextRun;
+use super::text::SOFT_HYPHEN;
+use super::types::FootnoteStyle;
+use std::collections::HashMap;
+
+/// Markers and footnote text are set at most this fraction of the body font size
+const SMALL_SCALE: f32 = 0.9;
+/// A marker's baseline sits at least this many body sizes above the line's
+const MARKER_RISE: f32 = 0.15;
+/// Maximum baseline distance between lines of one note, in font sizes
+const NOTE_LINE_SPACING: f32 = 1.6;
+/// Symbols used as footnote labels when notes are not numbered
+const SYMBOLS: [char; 4] = ['*', '†', '‡', '§'];
+
+/// A footnote found at the bottom of a page
+#[derive(Debug, PartialEq)]
+pub struct PageNote {
+    pub label: String,
+    pub text: String,
+}
+
+/// Footnotes on one page: the notes, the lines they occupy and the markers referring to them
+#[derive(Debug, Default)]
+pub struct PageFootnotes {
+    pub notes: Vec<PageNote>,
+    /// Line indices holding footnote text
+    note_lines: Vec<usize>,
+    /// (line index, run index) of each marker -> index into `notes`
+    markers: HashMap<(usize, usize), usize>,
+}
+
+impl PageFootnotes {
+    /// Whether a line is footnote text rather than running text
+    pub fn is_note_line(&self, line: usize) -> bool {
+        self.note_lines.contains(&line)
+    }
+
+    /// Note referred to by the marker at a run, if the run is one
+    pub fn marker(&self, line: usize, run: usize) -> Option<usize> {
+        self.markers.get(&(line, run)).copied()
+    }
+}
+
+/// Find footnotes on a page.
+///
+/// A footnote is a small-print line below the running text that starts with a
+/// label (`1`, `*`, ...) matching a small, raised marker in the running text.
+/// Labels without both halves are left alone, so page numbers and stray
+/// superscripts are not mistaken for footnotes.
+pub fn find(lines: &[Vec<&TextRun>]) -> PageFootnotes {
+    let Some(body) = body_size(lines) else {
+        return PageFootnotes::default();
+    };
+    let small = body * SMALL_SCALE;
+
+    // Raised small labels inside lines of running text
+    let mut markers: Vec<(usize, usize, String)> = Vec::new();
+    let mut lowest_body_line = f32::MAX;
+    for (i, line) in lines.iter().enumerate() {
+        let Some(base) = line
+            .iter()
+            .filter(|run| run.size > small)
+            .map(|run| run.y)
+            .reduce(f32::min)
+        else {
+            continue;
+        };
+        lowest_body_line = lowest_body_line.min(base);
+        for (j, run) in line.iter().enumerate() {
+            if run.size <= small
+                && run.y >= base + body * MARKER_RISE
+                && let Some(label) = label(run.text.trim())
+                && label.len() == run.text.trim().len()
+            {
+                markers.push((i, j, label.to_string()));
+            }
+        }
+    }
+
+    // Small-print lines below the running text, grouped into labelled notes
+    let mut candidates: Vec<(PageNote, Vec<usize>)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if line.iter().any(|run| run.size > small) || line[0].y >= lowest_body_line {
+            continue;
+        }
+        let text = line_text(line);
+        let first = line[0].text.trim();
+        match note_start(&text, first) {
+            Some((label, rest)) if markers.iter().any(|(_, _, marker)| marker == label) => {
+                let note = PageNote {
+                    label: label.to_string(),
+                    text: rest.to_string(),
+                };
+                candidates.push((note, vec![i]));
+            }
+            _ => {
+                // Continuation of the previous note when it directly follows it
+                if let Some((note, note_lines)) = candidates.last_mut()
+                    && let Some(&last) = note_lines.last()
+                    && last + 1 == i
+                    && lines[last][0].y - line[0].y <= line[0].size * NOTE_LINE_SPACING
+                {
+                    // A word broken over the lines at a soft hyphen is joined up again
+                    match note.text.trim_end().strip_suffix(SOFT_HYPHEN) {
+                        Some(head) => note.text.truncate(head.len()),
+                        None => note.text.push(' '),
+                    }
+                    note.text.push_str(&text);
+                    note_lines.push(i);
+                }
+            }
+        }
+    }
+
+    let mut footnotes = PageFootnotes::default();
+    for (note, note_lines) in candidates {
+        if footnotes.notes.iter().any(|seen| seen.label == note.label) {
+            continue;
+        }
+        if let Some(&(line, run, _)) = markers.iter().find(|(_, _, label)| *label == note.label) {
+            footnotes.markers.insert((line, run), footnotes.notes.len());
+            footnotes.note_lines.extend(note_lines);
+            footnotes.notes.push(PageNote {
+                text: note.text.replace(SOFT_HYPHEN, ""),
+                ..note
+            });
+        }
+    }
+    footnotes
+}
+
+/// Most common font size on the page, weighted by the number of characters
+fn body_size(lines: &[Vec<&TextRun>]) -> Option<f32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for run in lines.iter().flatten() {
+        let key = (run.size * 2.0).round() as u32;
+        *counts.entry(key).or_default() += run.text.trim().chars().count();
+    }
+    counts
+        .into_iter()
+        .filter(|&(key, count)| key > 0 && count > 0)
+        .max_by_key(|&(key, count)| (count, key))
+        .map(|(key, _)| key as f32 / 2.0)
+}
+
+/// Leading footnote label of `text`: up to three digits, or up to three symbols
+fn label(text: &str) -> Option<&str> {
+    let digits = text.chars().take_while(char::is_ascii_digit).count();
+    let len = if digits > 0 {
+        digits
+    } else {
+        text.chars()
+            .take_while(|c| SYMBOLS.contains(c))
+            .map(char::len_utf8)
+            .sum()
+    };
+    (len > 0 && text[..len].chars().count() <= 3).then(|| &text[..len])
+}
+
+/// Split a footnote line into its label and text.
+///
+/// The label is either its own run (a superscript) or separated from the
+/// text by a space, `.` or `)`, which rules out lines starting with a number.
+fn note_start<'a>(text: &'a str, first_run: &str) -> Option<(&'a str, &'a str)> {
+    let label = label(text)?;
+    let rest = &text[label.len()..];
+    let separated = first_run == label || rest.starts_with([' ', '.', ')']);
+    let rest = rest.trim_start_matches(['.', ')']).trim();
+    (separated && !rest.is_empty()).then_some((label, rest))
+}
+
+/// Text that replaces a marker for note number `number`
+pub fn marker(style: FootnoteStyle, number: usize, text: &str) -> String {
+    match style {
+        FootnoteStyle::Inline => format!("^[{}]", text.replace(']', "\\]")),
+        FootnoteStyle::Reference => format!("[^{}]", number),
+        FootnoteStyle::Notes => format!("<sup>{}</sup>", number),
+    }
+}
+
+/// Footnote definitions or "Notes" section to append after the document.
+///
+/// Inline footnotes carry their text in the body, so nothing is appended for them.
+pub fn render_footnotes(style: FootnoteStyle, notes: &[String]) -> String {
+    if notes.is_empty() {
+        return String::new();
+    }
+    match style {
+        FootnoteStyle::Inline => String::new(),
+        FootnoteStyle::Reference => notes
+            .iter()
+            .enumerate()
+            .map(|(i, note)| format!("\n\n[^{}]: {}", i + 1, note))
+            .collect(),
+        FootnoteStyle::Notes => {
+            let mut section = String::from("\n\n## Notes\n");
+            for (i, note) in notes.iter().enumerate() {
+                section.push_str(&format!("\n{}. {}", i + 1, note));
+            }
+            section
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Color;
+
+    fn run(text: &str, size: f32, x: f32, y: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: "Helvetica".to_string(),
+            size,
+            x,
+            y,
+            width: size * 0.5 * text.len() as f32,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    #[test]
+    fn test_find_matches_markers_to_notes() {
+        let runs = [
+            run("The method is fast", 12.0, 72.0, 700.0),
+            run("1", 7.0, 171.0, 705.0),
+            run(" in practice.", 12.0, 175.0, 700.0),
+            run("Page 2 of 9", 12.0, 72.0, 680.0),
+            run("1 Measured on a", 9.0, 72.0, 80.0),
+            run("laptop.", 9.0, 72.0, 70.0),
+            run("12", 9.0, 300.0, 40.0),
+        ];
+        let lines: Vec<Vec<&TextRun>> = vec![
+            vec![&runs[0], &runs[1], &runs[2]],
+            vec![&runs[3]],
+            vec![&runs[4]],
+            vec![&runs[5]],
+            vec![&runs[6]],
+        ];
+
+        let footnotes = find(&lines);
+        assert_eq!(
+            footnotes.notes,
+            vec![PageNote {
+                label: "1".to_string(),
+                text: "Measured on a laptop.".to_string(),
+            }]
+        );
+        assert_eq!(footnotes.marker(0, 1), Some(0));
+        assert!(footnotes.is_note_line(2) && footnotes.is_note_line(3));
+        // The page number has no marker and stays in the text
+        assert!(!footnotes.is_note_line(4));
+    }
+
+    #[test]
+    fn test_soft_hyphens_are_removed_from_notes() {
+        let runs = [
+            run("The standard is widely used in practice", 12.0, 72.0, 700.0),
+            run("1", 7.0, 306.0, 705.0),
+            run("1 An inter\u{00AD}", 9.0, 72.0, 80.0),
+            run("national stan\u{00AD}dard.", 9.0, 72.0, 70.0),
+        ];
+        let lines = vec![vec![&runs[0], &runs[1]], vec![&runs[2]], vec![&runs[3]]];
+        assert_eq!(find(&lines).notes[0].text, "An international standard.");
+    }
+
+    #[test]
+    fn test_unmatched_labels_are_not_footnotes() {
+        let runs = [
+            run("Body text", 12.0, 72.0, 700.0),
+            run("2 Small print without a marker", 9.0, 72.0, 80.0),
+        ];
+        let lines = vec![vec![&runs[0]], vec![&runs[1]]];
+        assert!(find(&lines).notes.is_empty());
+    }
+
+    #[test]
+    fn test_note_start() {
+        assert_eq!(
+            note_start("1. See above", "1. See"),
+            Some(("1", "See above"))
+        );
+        assert_eq!(note_start("†Symbol note", "†"), Some(("†", "Symbol note")));
+        assert_eq!(note_start("2024 was a year", "2024 was"), None);
+    }
+
+    #[test]
+    fn test_render_styles() {
+        let notes = vec!["First.".to_string(), "Second.".to_string()];
+        assert_eq!(marker(FootnoteStyle::Inline, 1, "See [3]"), "^[See [3\\]]");
+        assert_eq!(marker(FootnoteStyle::Reference, 2, ""), "[^2]");
+        assert_eq!(
+            render_footnotes(FootnoteStyle::Reference, &notes),
+            "\n\n[^1]: First.\n\n[^2]: Second."
+        );
+        assert_eq!(
+            render_footnotes(FootnoteStyle::Notes, &notes),
+            "\n\n## Notes\n\n1. First.\n2. Second."
+        );
+        assert!(render_footnotes(FootnoteStyle::Inline, &notes).is_empty());
+    }
+}
@@ -0,0 +1,271 @@
+//! Formulas of tagged PDFs: the `Formula` structure elements with content on
+//! a page, and the MathML associated with them (PDF 2.0 `AF` entries)
+
+use super::limits;
+use super::mathml;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// A formula on a page, as the structure tree tags it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    /// Marked-content identifiers of its content on the page
+    pub mcids: Vec<i64>,
+    /// LaTeX of its MathML, and whether the MathML displays it
+    pub mathml: Option<(String, bool)>,
+}
+
+/// The formulas with content on a page, in the order of their first
+/// marked content, found through the structure tree's parent tree. A page
+/// of a document that is not tagged has none.
+pub fn on_page(document: &Document, page_id: ObjectId) -> Vec<Formula> {
+    let Some(key) = document
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"StructParents").ok()?.as_i64().ok())
+    else {
+        return Vec::new();
+    };
+    let Some(root) = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| resolve_dict(document, catalog.get(b"StructTreeRoot").ok()?))
+    else {
+        return Vec::new();
+    };
+    let role_map = root
+        .get(b"RoleMap")
+        .ok()
+        .and_then(|map| resolve_dict(document, map));
+    let Some(Object::Array(parents)) = root
+        .get(b"ParentTree")
+        .ok()
+        .and_then(|tree| resolve_dict(document, tree))
+        .and_then(|tree| number_tree_value(document, tree, key, 0))
+        .and_then(|parents| limits::resolve(document, parents))
+    else {
+        return Vec::new();
+    };
+
+    let mut formulas: Vec<(ObjectId, Formula)> = Vec::new();
+    for (mcid, parent) in parents.iter().enumerate() {
+        let Some(id) = parent
+            .as_reference()
+            .ok()
+            .and_then(|id| formula_of(document, id, role_map))
+        else {
+            continue;
+        };
+        match formulas
+            .iter_mut()
+            .find(|(formula_id, _)| *formula_id == id)
+        {
+            Some((_, formula)) => formula.mcids.push(mcid as i64),
+            None => formulas.push((
+                id,
+                Formula {
+                    mcids: vec![mcid as i64],
+                    mathml: mathml(document, id),
+                },
+            )),
+        }
+    }
+    formulas.into_iter().map(|(_, formula)| formula).collect()
+}
+
+/// The value of `key` in a number tree
+fn number_tree_value<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    key: i64,
+    depth: usize,
+) -> Option<&'a Object> {
+    if depth > limits::MAX_REFERENCE_DEPTH {
+        return None;
+    }
+    if let Some(Object::Array(nums)) = node
+        .get(b"Nums")
+        .ok()
+        .and_then(|nums| limits::resolve(document, nums))
+    {
+        for pair in nums.chunks(2) {
+            if let [Object::Integer(number), value] = pair
+                && *number == key
+            {
+                return Some(value);
+            }
+        }
+    }
+    let Some(Object::Array(kids)) = node
+        .get(b"Kids")
+        .ok()
+        .and_then(|kids| limits::resolve(document, kids))
+    else {
+        return None;
+    };
+    kids.iter()
+        .filter_map(|kid| resolve_dict(document, kid))
+        .filter(|kid| {
+            let limits = kid.get(b"Limits").and_then(Object::as_array);
+            match limits.map(|limits| limits.as_slice()) {
+                Ok([Object::Integer(low), Object::Integer(high)]) => (*low..=*high).contains(&key),
+                _ => true,
+            }
+        })
+        .find_map(|kid| number_tree_value(document, kid, key, depth + 1))
+}
+
+/// The `Formula` element that is `id` or one of its ancestors
+fn formula_of(
+    document: &Document,
+    id: ObjectId,
+    role_map: Option<&Dictionary>,
+) -> Option<ObjectId> {
+    let mut id = id;
+    for _ in 0..limits::MAX_REFERENCE_DEPTH {
+        let element = document.get_dictionary(id).ok()?;
+        let kind = element.get(b"S").and_then(Object::as_name).ok()?;
+        if is_formula(kind, role_map) {
+            return Some(id);
+        }
+        id = element.get(b"P").and_then(Object::as_reference).ok()?;
+    }
+    None
+}
+
+/// Whether a structure type is `Formula`, or mapped to it by the role map
+fn is_formula(kind: &[u8], role_map: Option<&Dictionary>) -> bool {
+    let mut kind = kind;
+    for _ in 0..limits::MAX_REFERENCE_DEPTH {
+        if kind == b"Formula" {
+            return true;
+        }
+        match role_map.and_then(|map| map.get(kind).and_then(Object::as_name).ok()) {
+            Some(mapped) if mapped != kind => kind = mapped,
+            _ => return false,
+        }
+    }
+    false
+}
+
+/// LaTeX of the MathML file associated with a structure element
+fn mathml(document: &Document, id: ObjectId) -> Option<(String, bool)> {
+    let element = document.get_dictionary(id).ok()?;
+    let files = match limits::resolve(document, element.get(b"AF").ok()?)? {
+        Object::Array(files) => files.iter().collect(),
+        file => vec![file],
+    };
+    files.into_iter().find_map(|spec| {
+        let spec = resolve_dict(document, spec)?;
+        let streams = resolve_dict(document, spec.get(b"EF").ok()?)?;
+        let stream = streams
+            .get(b"UF")
+            .or_else(|_| streams.get(b"F"))
+            .ok()
+            .and_then(|stream| limits::resolve(document, stream))?
+            .as_stream()
+            .ok()?;
+        let mime_type = stream.dict.get(b"Subtype").and_then(Object::as_name).ok();
+        let name = spec
+            .get(b"UF")
+            .or_else(|_| spec.get(b"F"))
+            .ok()
+            .and_then(|name| limits::resolve(document, name))
+            .and_then(|name| lopdf::decode_text_string(name).ok())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if mime_type != Some(b"application/mathml+xml") && !name.ends_with(".mml") {
+            return None;
+        }
+        let data = limits::decompressed_content(stream)?;
+        mathml::to_latex(&String::from_utf8_lossy(&data))
+    })
+}
+
+fn resolve_dict<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    limits::resolve(document, object)?.as_dict().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Stream, dictionary};
+
+    /// A one-page document whose MCIDs 0 and 2 are a formula with MathML,
+    /// MCID 1 a paragraph, and MCID 3 a span in an `Equation` mapped to
+    /// `Formula`; the parent tree has an intermediate node
+    fn tagged() -> (Document, ObjectId) {
+        let mut document = Document::with_version("2.0");
+        let page = document.add_object(dictionary! { "Type" => "Page", "StructParents" => 0 });
+        let root = document.new_object_id();
+        let mathml = document.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile", "Subtype" => "application/mathml+xml" },
+            b"<math><msup><mi>x</mi><mn>2</mn></msup></math>".to_vec(),
+        ));
+        let formula = document.add_object(dictionary! {
+            "S" => "Formula",
+            "P" => root,
+            "AF" => vec![dictionary! {
+                "Type" => "Filespec",
+                "F" => Object::string_literal("formula1.xml"),
+                "EF" => dictionary! { "F" => mathml },
+            }
+            .into()],
+        });
+        let paragraph = document.add_object(dictionary! { "S" => "P", "P" => root });
+        let equation = document.add_object(dictionary! { "S" => "Equation", "P" => root });
+        let span = document.add_object(dictionary! { "S" => "Span", "P" => equation });
+        let parents = document.add_object(vec![
+            formula.into(),
+            paragraph.into(),
+            formula.into(),
+            span.into(),
+        ]);
+        let leaf = document.add_object(dictionary! {
+            "Limits" => vec![0.into(), 0.into()],
+            "Nums" => vec![0.into(), parents.into()],
+        });
+        document.objects.insert(
+            root,
+            Object::Dictionary(dictionary! {
+                "Type" => "StructTreeRoot",
+                "RoleMap" => dictionary! { "Equation" => "Formula" },
+                "ParentTree" => dictionary! { "Kids" => vec![leaf.into()] },
+            }),
+        );
+        let catalog = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "StructTreeRoot" => root,
+        });
+        document.trailer.set("Root", catalog);
+        (document, page)
+    }
+
+    #[test]
+    fn test_on_page() {
+        let (document, page) = tagged();
+        assert_eq!(
+            on_page(&document, page),
+            [
+                Formula {
+                    mcids: vec![0, 2],
+                    mathml: Some(("x^{2}".to_string(), false)),
+                },
+                Formula {
+                    mcids: vec![3],
+                    mathml: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_page_of_untagged_page() {
+        let (mut document, page) = tagged();
+        document
+            .get_object_mut(page)
+            .and_then(Object::as_dict_mut)
+            .unwrap()
+            .remove(b"StructParents");
+        assert!(on_page(&document, page).is_empty());
+    }
+}
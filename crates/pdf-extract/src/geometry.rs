@@ -0,0 +1,154 @@
+/// Axis-aligned rectangle in PDF user space (origin bottom-left, y up)
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Rect {
+    /// Rectangle spanning two corner points in any order
+    pub fn from_corners(x0: f32, y0: f32, x1: f32, y1: f32) -> Self {
+        Rect {
+            x: x0.min(x1),
+            y: y0.min(y1),
+            width: (x1 - x0).abs(),
+            height: (y1 - y0).abs(),
+        }
+    }
+
+    /// Top edge
+    pub fn top(&self) -> f32 {
+        self.y + self.height
+    }
+
+    /// Right edge
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn area(&self) -> f32 {
+        self.width * self.height
+    }
+
+    /// Smallest rectangle containing both
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::from_corners(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.right().max(other.right()),
+            self.top().max(other.top()),
+        )
+    }
+
+    /// Grow by `margin` on every side
+    pub fn expand(&self, margin: f32) -> Rect {
+        Rect {
+            x: self.x - margin,
+            y: self.y - margin,
+            width: self.width + 2.0 * margin,
+            height: self.height + 2.0 * margin,
+        }
+    }
+
+    /// Whether a point lies inside or on the edge
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.x <= x && x <= self.right() && self.y <= y && y <= self.top()
+    }
+
+    /// Whether the rectangles touch or overlap
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x <= other.right()
+            && other.x <= self.right()
+            && self.y <= other.top()
+            && other.y <= self.top()
+    }
+}
+
+/// Opaque RGB colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    pub const BLACK: Color = Color(0, 0, 0);
+
+    /// Build a colour from 1 (gray), 3 (RGB) or 4 (CMYK) components in `0.0..=1.0`
+    pub fn from_components(components: &[f32]) -> Option<Color> {
+        let channel = |v: f32| (v.clamp(0.0, 1.0) * 255.0).round() as u8;
+        match *components {
+            [g] => Some(Color(channel(g), channel(g), channel(g))),
+            [r, g, b] => Some(Color(channel(r), channel(g), channel(b))),
+            [c, m, y, k] => Some(Color(
+                channel((1.0 - c) * (1.0 - k)),
+                channel((1.0 - m) * (1.0 - k)),
+                channel((1.0 - y) * (1.0 - k)),
+            )),
+            _ => None,
+        }
+    }
+
+    /// CSS hex notation (`#rrggbb`)
+    pub fn hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+
+    pub fn is_white(&self) -> bool {
+        *self == Color(255, 255, 255)
+    }
+
+    /// Black, white or a shade of gray, allowing for rounding in CMYK conversion
+    pub fn is_gray(&self) -> bool {
+        let Color(r, g, b) = *self;
+        r.max(g).max(b) - r.min(g).min(b) <= 16
+    }
+}
+
+/// One step of a vector path, already transformed into user space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Segment {
+    MoveTo(f32, f32),
+    LineTo(f32, f32),
+    CurveTo(f32, f32, f32, f32, f32, f32),
+    Close,
+}
+
+/// A painted vector path
+#[derive(Debug, Clone)]
+pub struct PathShape {
+    pub segments: Vec<Segment>,
+    pub stroke: Option<Color>,
+    pub fill: Option<Color>,
+    pub line_width: f32,
+    pub bounds: Rect,
+    /// Text runs drawn before the path, which a fill paints over
+    pub runs_before: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_union_and_intersects() {
+        let a = Rect::from_corners(0.0, 0.0, 10.0, 10.0);
+        let b = Rect::from_corners(20.0, 20.0, 30.0, 30.0);
+        assert!(!a.intersects(&b));
+        assert!(a.expand(5.0).intersects(&b.expand(5.0)));
+        assert_eq!(a.union(&b), Rect::from_corners(0.0, 0.0, 30.0, 30.0));
+    }
+
+    #[test]
+    fn test_color_from_components() {
+        assert_eq!(Color::from_components(&[0.0]), Some(Color::BLACK));
+        assert_eq!(
+            Color::from_components(&[1.0, 0.0, 0.0]).unwrap().hex(),
+            "#ff0000"
+        );
+        assert_eq!(
+            Color::from_components(&[0.0, 0.0, 0.0, 1.0]),
+            Some(Color::BLACK)
+        );
+        assert_eq!(Color::from_components(&[0.5, 0.5]), None);
+    }
+}
@@ -0,0 +1,480 @@
+use super::ccitt::{self, FaxParams};
+use super::geometry::Rect;
+use super::jpeg;
+use super::layout::{self, ImagePlacement};
+use super::limits;
+use super::png;
+use super::raster::{self, ColorSpace};
+use super::types::{ExtractOptions, ExtractedImage, ImageFormat};
+use super::vector::{self, Drawing};
+use log::{debug, warn};
+use lopdf::{Dictionary, Document, Object, ObjectId, Stream};
+use std::collections::{HashMap, HashSet};
+
+/// Extract the images on every page without extracting the text.
+///
+/// Files are named as in full extraction (`page-3-image-1.png`); vector
+/// drawings are included with `vector_graphics`.
+pub fn extract_images(document: &Document, options: &ExtractOptions) -> Vec<ExtractedImage> {
+    let mut collector = ImageCollector::default();
+    for (page_num, page_id) in document.get_pages() {
+        if let Err(e) = limits::check_page_content(document, page_id) {
+            warn!("Skipping images on page {}: {}", page_num, e);
+            continue;
+        }
+        match layout::page_layout(document, page_id) {
+            Ok(page) => {
+                collector.collect(document, page_num, &page.images, options);
+                if options.vector_graphics {
+                    collector.collect_drawings(page_num, &vector::find_drawings(&page), options);
+                }
+            }
+            Err(e) => warn!("Failed to analyze layout of page {}: {}", page_num, e),
+        }
+    }
+    collector.images
+}
+
+/// Collects embedded images across pages, writing each XObject only once
+#[derive(Debug, Default)]
+pub struct ImageCollector {
+    names: HashMap<ObjectId, String>,
+    /// Images left out by the size and type filters
+    skipped: HashSet<ObjectId>,
+    /// Page, bounds and file name of each vector drawing converted to SVG
+    drawings: Vec<(u32, Rect, String)>,
+    pub images: Vec<ExtractedImage>,
+}
+
+impl ImageCollector {
+    /// Encode the images placed on a page that have not been seen before
+    pub fn collect(
+        &mut self,
+        document: &Document,
+        page_num: u32,
+        placements: &[ImagePlacement],
+        options: &ExtractOptions,
+    ) {
+        for placement in placements {
+            if self.names.contains_key(&placement.id) || self.skipped.contains(&placement.id) {
+                continue;
+            }
+            let Ok(stream) = document
+                .get_object(placement.id)
+                .and_then(Object::as_stream)
+            else {
+                continue;
+            };
+            match encode_image(document, stream, options.image_format) {
+                Some((_, extension, width, height))
+                    if !options.keeps_image(extension, width, height) =>
+                {
+                    debug!(
+                        "Skipping {}x{} {} image on page {}",
+                        width, height, extension, page_num
+                    );
+                    self.skipped.insert(placement.id);
+                }
+                Some((data, extension, width, height)) => {
+                    let index = self.images.iter().filter(|i| i.page == page_num).count() + 1;
+                    let file_name = format!("page-{}-image-{}.{}", page_num, index, extension);
+                    debug!("Extracted {} ({} bytes)", file_name, data.len());
+                    self.names.insert(placement.id, file_name.clone());
+                    self.push(file_name, page_num, width, height, data, options);
+                }
+                None => warn!("Skipping unsupported image on page {}", page_num),
+            }
+        }
+    }
+
+    /// Convert the vector drawings on a page to SVG files
+    pub fn collect_drawings(
+        &mut self,
+        page_num: u32,
+        drawings: &[Drawing],
+        options: &ExtractOptions,
+    ) {
+        let sized = drawings.iter().map(|drawing| {
+            let (width, height) = (
+                drawing.bounds.width.round() as u32,
+                drawing.bounds.height.round() as u32,
+            );
+            (drawing, width, height)
+        });
+        let kept = sized.filter(|&(_, width, height)| options.keeps_image("svg", width, height));
+        for (index, (drawing, width, height)) in kept.enumerate() {
+            let file_name = format!("page-{}-figure-{}.svg", page_num, index + 1);
+            let svg = vector::to_svg(drawing);
+            debug!("Extracted {} ({} paths)", file_name, drawing.paths.len());
+            self.drawings
+                .push((page_num, drawing.bounds, file_name.clone()));
+            self.push(
+                file_name,
+                page_num,
+                width,
+                height,
+                svg.into_bytes(),
+                options,
+            );
+        }
+    }
+
+    /// Record an extracted file, asking the alt text provider to describe it
+    fn push(
+        &mut self,
+        file_name: String,
+        page: u32,
+        width: u32,
+        height: u32,
+        data: Vec<u8>,
+        options: &ExtractOptions,
+    ) {
+        let mut image = ExtractedImage {
+            file_name,
+            page,
+            width,
+            height,
+            data,
+            alt_text: None,
+        };
+        if let Some(provider) = &options.alt_text {
+            image.alt_text = provider.alt_text(&image);
+        }
+        self.images.push(image);
+    }
+
+    /// File name assigned to an image XObject, if it was extracted
+    pub fn name(&self, id: ObjectId) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Bounds and file names of the drawings extracted from a page
+    pub fn drawings(&self, page_num: u32) -> impl Iterator<Item = (Rect, &str)> {
+        self.drawings
+            .iter()
+            .filter(move |(page, _, _)| *page == page_num)
+            .map(|(_, bounds, name)| (*bounds, name.as_str()))
+    }
+
+    /// Alt text supplied for an extracted file by the configured provider
+    pub fn alt_text(&self, file_name: &str) -> Option<&str> {
+        self.images
+            .iter()
+            .find(|image| image.file_name == file_name)
+            .and_then(|image| image.alt_text.as_deref())
+    }
+}
+
+/// Convert an image XObject into a file format viewers understand.
+///
+/// JPEG and JPEG 2000 data is kept as is unless PNG output is requested or
+/// the JPEG is CMYK, which browsers cannot display. Everything else that can
+/// be decoded becomes an 8-bit gray or RGB PNG.
+///
+/// Returns the encoded bytes, file extension, width and height.
+fn encode_image(
+    document: &Document,
+    stream: &Stream,
+    format: ImageFormat,
+) -> Option<(Vec<u8>, &'static str, u32, u32)> {
+    let dict = &stream.dict;
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()? as u32;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()? as u32;
+    let filters = stream.filters().unwrap_or_default();
+    let mask = dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or(false);
+
+    let mut data = stream.content.clone();
+    let mut bits = dict
+        .get(b"BitsPerComponent")
+        .and_then(Object::as_i64)
+        .unwrap_or(if mask { 1 } else { 8 }) as u32;
+    let mut space = ColorSpace::of_image(document, dict);
+
+    for (index, filter) in filters.iter().enumerate() {
+        let params = decode_parms(document, dict, index);
+        match filter.as_str() {
+            "FlateDecode" | "Fl" => data = inflate(&data, params)?,
+            "DCTDecode" | "DCT" => {
+                let cmyk = space.as_ref().is_some_and(|s| s.components() == 4);
+                if format != ImageFormat::Png && !cmyk {
+                    return Some((data, "jpg", width, height));
+                }
+                match jpeg::decode(&data, (width, height)) {
+                    Ok(image) => {
+                        space = ColorSpace::with_components(image.components);
+                        data = image.pixels;
+                        bits = 8;
+                    }
+                    Err(e) => {
+                        warn!("Keeping JPEG image as is: {}", e);
+                        return Some((data, "jpg", width, height));
+                    }
+                }
+            }
+            "JPXDecode" => {
+                if format == ImageFormat::Png {
+                    warn!("JPEG 2000 images cannot be converted; keeping original");
+                }
+                return Some((data, "jp2", width, height));
+            }
+            "CCITTFaxDecode" | "CCF" => {
+                let params = fax_params(params, width as usize, height as usize);
+                data = ccitt::decode(&data, &params)?;
+                space = Some(ColorSpace::Gray);
+                bits = 8;
+            }
+            "JBIG2Decode" => {
+                warn!("JBIG2 images cannot be decoded; skipping");
+                return None;
+            }
+            other => {
+                debug!("Unsupported image filter {}", other);
+                return None;
+            }
+        }
+    }
+
+    let decode: Vec<f32> = dict
+        .get(b"Decode")
+        .and_then(Object::as_array)
+        .map(|values| values.iter().filter_map(layout::number).collect())
+        .unwrap_or_default();
+    let (color, pixels) = raster::to_pixels(
+        &data,
+        width as usize,
+        height as usize,
+        bits,
+        &space?,
+        &decode,
+    )?;
+    let png = png::encode(width, height, color, &pixels).ok()?;
+    Some((png, "png", width, height))
+}
+
+/// Decode parameters for the filter at `index` in the image's filter chain
+fn decode_parms<'a>(
+    document: &'a Document,
+    dict: &'a Dictionary,
+    index: usize,
+) -> Option<&'a Dictionary> {
+    match limits::resolve(document, dict.get(b"DecodeParms").ok()?)? {
+        Object::Dictionary(parms) => Some(parms),
+        Object::Array(items) => limits::resolve(document, items.get(index)?)?.as_dict().ok(),
+        _ => None,
+    }
+}
+
+/// Inflate Flate-compressed data and undo any predictor
+fn inflate(data: &[u8], params: Option<&Dictionary>) -> Option<Vec<u8>> {
+    let inflated = limits::inflate(data)?;
+
+    let param = |key: &[u8], default: i64| {
+        params
+            .and_then(|p| p.get(key).and_then(Object::as_i64).ok())
+            .unwrap_or(default)
+            .max(0) as usize
+    };
+    let predictor = param(b"Predictor", 1);
+    let colors = param(b"Colors", 1).max(1);
+    let bits = param(b"BitsPerComponent", 8).max(1);
+    let columns = param(b"Columns", 1).max(1);
+    unpredict(inflated, predictor, colors, bits, columns)
+}
+
+/// Reverse a TIFF (2) or PNG (10-15) predictor
+fn unpredict(
+    data: Vec<u8>,
+    predictor: usize,
+    colors: usize,
+    bits: usize,
+    columns: usize,
+) -> Option<Vec<u8>> {
+    let pixel_len = (colors * bits).div_ceil(8);
+    let row_len = (columns * colors * bits).div_ceil(8);
+    match predictor {
+        1 => Some(data),
+        2 if bits == 8 => {
+            let mut data = data;
+            for row in data.chunks_mut(row_len) {
+                for i in pixel_len..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - pixel_len]);
+                }
+            }
+            Some(data)
+        }
+        10..=15 => {
+            let mut out: Vec<u8> = Vec::with_capacity(data.len());
+            let mut previous = vec![0u8; row_len];
+            for chunk in data.chunks(row_len + 1) {
+                let (&filter, encoded) = chunk.split_first()?;
+                let mut row = encoded.to_vec();
+                row.resize(row_len, 0);
+                for i in 0..row_len {
+                    let left = if i >= pixel_len {
+                        row[i - pixel_len]
+                    } else {
+                        0
+                    };
+                    let up = previous[i];
+                    let up_left = if i >= pixel_len {
+                        previous[i - pixel_len]
+                    } else {
+                        0
+                    };
+                    let prediction = match filter {
+                        0 => 0,
+                        1 => left,
+                        2 => up,
+                        3 => ((left as u16 + up as u16) / 2) as u8,
+                        4 => paeth(left, up, up_left),
+                        _ => return None,
+                    };
+                    row[i] = row[i].wrapping_add(prediction);
+                }
+                out.extend_from_slice(&row);
+                previous = row;
+            }
+            Some(out)
+        }
+        _ => None,
+    }
+}
+
+/// PNG Paeth predictor
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let p = left as i16 + up as i16 - up_left as i16;
+    let (pa, pb, pc) = (
+        (p - left as i16).abs(),
+        (p - up as i16).abs(),
+        (p - up_left as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        left
+    } else if pb <= pc {
+        up
+    } else {
+        up_left
+    }
+}
+
+/// CCITT parameters, with the PDF defaults for missing entries
+fn fax_params(params: Option<&Dictionary>, width: usize, height: usize) -> FaxParams {
+    let int = |key: &[u8]| params.and_then(|p| p.get(key).and_then(Object::as_i64).ok());
+    let flag = |key: &[u8]| {
+        params
+            .and_then(|p| p.get(key).and_then(Object::as_bool).ok())
+            .unwrap_or(false)
+    };
+    FaxParams {
+        k: int(b"K").unwrap_or(0),
+        columns: int(b"Columns").map_or(width, |c| c.max(1) as usize),
+        rows: int(b"Rows").map_or(height, |r| r.max(1) as usize),
+        encoded_byte_align: flag(b"EncodedByteAlign"),
+        black_is_1: flag(b"BlackIs1"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_encode_image_passes_jpeg_through() {
+        let stream = Stream::new(
+            dictionary! {
+                "Subtype" => "Image",
+                "Width" => 4,
+                "Height" => 2,
+                "Filter" => "DCTDecode",
+            },
+            b"\xFF\xD8jpeg".to_vec(),
+        );
+        let (data, extension, width, height) =
+            encode_image(&Document::new(), &stream, ImageFormat::Original).unwrap();
+        assert_eq!(extension, "jpg");
+        assert_eq!(data, b"\xFF\xD8jpeg");
+        assert_eq!((width, height), (4, 2));
+    }
+
+    #[test]
+    fn test_encode_image_converts_raw_rgb_to_png() {
+        let stream = Stream::new(
+            dictionary! {
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceRGB",
+                "BitsPerComponent" => 8,
+            },
+            vec![255, 0, 0],
+        );
+        let (data, extension, _, _) =
+            encode_image(&Document::new(), &stream, ImageFormat::Original).unwrap();
+        assert_eq!(extension, "png");
+        assert!(data.starts_with(b"\x89PNG"));
+    }
+
+    #[test]
+    fn test_encode_image_converts_cmyk_to_png() {
+        let stream = Stream::new(
+            dictionary! {
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceCMYK",
+                "BitsPerComponent" => 8,
+            },
+            vec![0, 0, 0, 0],
+        );
+        let (data, extension, _, _) =
+            encode_image(&Document::new(), &stream, ImageFormat::Original).unwrap();
+        assert_eq!(extension, "png");
+        // IHDR colour type byte: RGB
+        assert_eq!(data[25], 2);
+    }
+
+    #[test]
+    fn test_encode_image_keeps_undecodable_jpeg_in_png_mode() {
+        let stream = Stream::new(
+            dictionary! {
+                "Subtype" => "Image",
+                "Width" => 4,
+                "Height" => 2,
+                "ColorSpace" => "DeviceRGB",
+                "Filter" => "DCTDecode",
+            },
+            b"\xFF\xD8jpeg".to_vec(),
+        );
+        let (_, extension, _, _) =
+            encode_image(&Document::new(), &stream, ImageFormat::Png).unwrap();
+        assert_eq!(extension, "jpg");
+    }
+
+    #[test]
+    fn test_encode_image_skips_jbig2() {
+        let stream = Stream::new(
+            dictionary! {
+                "Subtype" => "Image",
+                "Width" => 1,
+                "Height" => 1,
+                "ColorSpace" => "DeviceGray",
+                "BitsPerComponent" => 1,
+                "Filter" => "JBIG2Decode",
+            },
+            vec![0],
+        );
+        assert!(encode_image(&Document::new(), &stream, ImageFormat::Original).is_none());
+    }
+
+    #[test]
+    fn test_unpredict_png_rows() {
+        // Two 2-byte rows: Sub filter, then Up filter
+        let data = vec![1, 10, 5, 2, 1, 1];
+        let rows = unpredict(data, 12, 1, 8, 2).unwrap();
+        assert_eq!(rows, [10, 15, 11, 16]);
+    }
+}
@@ -0,0 +1,280 @@
+use super::layout;
+use super::limits;
+use super::types::{AnnotationCounts, FontInfo, ImageInfo};
+use lopdf::{Dictionary, Document, Object, Stream};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Images drawn on each page, in page order.
+///
+/// An image drawn twice on a page is listed once; pages whose content cannot
+/// be read are left out.
+pub fn images(document: &Document) -> Vec<ImageInfo> {
+    let mut images = Vec::new();
+    for (&page, &page_id) in &document.get_pages() {
+        if limits::check_page_content(document, page_id).is_err() {
+            continue;
+        }
+        let Ok(page_layout) = layout::page_layout(document, page_id) else {
+            continue;
+        };
+        let mut seen = BTreeSet::new();
+        for placement in &page_layout.images {
+            if !seen.insert(placement.id) {
+                continue;
+            }
+            let Ok(stream) = document
+                .get_object(placement.id)
+                .and_then(Object::as_stream)
+            else {
+                continue;
+            };
+            let dimension = |key: &[u8]| {
+                stream
+                    .dict
+                    .get(key)
+                    .and_then(Object::as_i64)
+                    .map_or(0, |value| value.max(0) as u32)
+            };
+            images.push(ImageInfo {
+                page,
+                format: image_encoding(stream).to_string(),
+                width: dimension(b"Width"),
+                height: dimension(b"Height"),
+                bytes: stream.content.len(),
+            });
+        }
+    }
+    images
+}
+
+/// Fonts used by the document's pages, each listed once, with the pages using it
+pub fn fonts(document: &Document) -> Vec<FontInfo> {
+    let mut fonts: BTreeMap<(String, String), FontInfo> = BTreeMap::new();
+    for (&page, &page_id) in &document.get_pages() {
+        let Ok(page_fonts) = document.get_page_fonts(page_id) else {
+            continue;
+        };
+        for font in page_fonts.values() {
+            let info = font_info(document, font);
+            let entry = fonts
+                .entry((info.name.clone(), info.subtype.clone()))
+                .or_insert(info);
+            if entry.pages.last() != Some(&page) {
+                entry.pages.push(page);
+            }
+        }
+    }
+    fonts.into_values().collect()
+}
+
+fn font_info(document: &Document, font: &Dictionary) -> FontInfo {
+    let name = |dict: &Dictionary, key: &[u8]| {
+        dict.get(key)
+            .and_then(Object::as_name)
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok()
+    };
+    let subtype = name(font, b"Subtype").unwrap_or_default();
+    let base_font = name(font, b"BaseFont").unwrap_or_default();
+
+    // A composite font's glyphs and descriptor are in its descendant font
+    let descendant = font
+        .get(b"DescendantFonts")
+        .ok()
+        .and_then(|fonts| limits::resolve(document, fonts))
+        .and_then(|fonts| fonts.as_array().ok())
+        .and_then(|fonts| limits::resolve(document, fonts.first()?))
+        .and_then(|font| font.as_dict().ok());
+    let descriptor = descendant
+        .unwrap_or(font)
+        .get(b"FontDescriptor")
+        .ok()
+        .and_then(|descriptor| limits::resolve(document, descriptor))
+        .and_then(|descriptor| descriptor.as_dict().ok());
+    let embedded = subtype == "Type3"
+        || descriptor.is_some_and(|descriptor| {
+            [b"FontFile".as_slice(), b"FontFile2", b"FontFile3"]
+                .iter()
+                .any(|key| descriptor.has(key))
+        });
+    let encoding = match font
+        .get(b"Encoding")
+        .ok()
+        .map(|e| limits::resolve(document, e))
+    {
+        Some(Some(Object::Name(encoding))) => Some(String::from_utf8_lossy(encoding).into_owned()),
+        Some(Some(Object::Dictionary(encoding))) => {
+            Some(name(encoding, b"BaseEncoding").unwrap_or_else(|| "custom".to_string()))
+        }
+        _ => None,
+    };
+
+    FontInfo {
+        subset: layout::strip_subset_prefix(&base_font) != base_font,
+        name: base_font,
+        subtype,
+        embedded,
+        to_unicode: font.has(b"ToUnicode"),
+        encoding,
+        pages: Vec::new(),
+    }
+}
+
+/// Links and other annotations on all pages.
+///
+/// Form field widgets and pop-up windows belong to other annotations and are
+/// not counted.
+pub fn annotations(document: &Document) -> AnnotationCounts {
+    let mut counts = AnnotationCounts::default();
+    for &page_id in document.get_pages().values() {
+        let annotations = document
+            .get_dictionary(page_id)
+            .ok()
+            .and_then(|page| page.get(b"Annots").ok())
+            .and_then(|annots| limits::resolve(document, annots))
+            .and_then(|annots| annots.as_array().ok());
+        for annotation in annotations.into_iter().flatten() {
+            let Some(dict) = limits::resolve(document, annotation).and_then(|a| a.as_dict().ok())
+            else {
+                continue;
+            };
+            let subtype = dict
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .unwrap_or(b"");
+            match subtype {
+                b"Link" if is_external_link(document, dict) => counts.external_links += 1,
+                b"Link" => counts.internal_links += 1,
+                b"Widget" | b"Popup" => {}
+                other => {
+                    *counts
+                        .other
+                        .entry(String::from_utf8_lossy(other).into_owned())
+                        .or_default() += 1
+                }
+            }
+        }
+    }
+    counts
+}
+
+/// Whether a link annotation opens a URI or another file rather than a place in this document
+fn is_external_link(document: &Document, link: &Dictionary) -> bool {
+    link.get(b"A")
+        .ok()
+        .and_then(|action| limits::resolve(document, action))
+        .and_then(|action| action.as_dict().ok())
+        .and_then(|action| action.get(b"S").and_then(Object::as_name).ok())
+        .is_some_and(|action| matches!(action, b"URI" | b"GoToR" | b"Launch"))
+}
+
+/// Name of the codec an image is stored with: the last filter in its chain
+fn image_encoding(stream: &Stream) -> &'static str {
+    let filters = stream.filters().unwrap_or_default();
+    match filters.last().map(String::as_str) {
+        Some("DCTDecode" | "DCT") => "JPEG",
+        Some("JPXDecode") => "JPEG 2000",
+        Some("CCITTFaxDecode" | "CCF") => "CCITT",
+        Some("JBIG2Decode") => "JBIG2",
+        Some("FlateDecode" | "Fl") => "Flate",
+        Some("LZWDecode" | "LZW") => "LZW",
+        Some("RunLengthDecode" | "RL") => "RunLength",
+        Some(_) => "other",
+        None => "uncompressed",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_font_info() {
+        let mut document = Document::with_version("1.5");
+        let descriptor = document.add_object(dictionary! {
+            "Type" => "FontDescriptor",
+            "FontFile2" => Object::Reference((99, 0)),
+        });
+        let cid_font = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "CIDFontType2",
+            "FontDescriptor" => descriptor,
+        };
+        let font = dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type0",
+            "BaseFont" => "ABCDEF+Calibri",
+            "Encoding" => "Identity-H",
+            "DescendantFonts" => vec![Object::Dictionary(cid_font)],
+        };
+        let info = font_info(&document, &font);
+        assert_eq!(info.name, "ABCDEF+Calibri");
+        assert!(info.embedded && info.subset && !info.to_unicode);
+        assert_eq!(info.encoding.as_deref(), Some("Identity-H"));
+        assert!(info.may_garble());
+
+        let helvetica = dictionary! {
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+            "Encoding" => "WinAnsiEncoding",
+        };
+        let info = font_info(&document, &helvetica);
+        assert!(!info.embedded && !info.subset && !info.may_garble());
+    }
+
+    #[test]
+    fn test_annotation_counts() {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let annots = vec![
+            Object::Dictionary(dictionary! {
+                "Subtype" => "Link",
+                "A" => dictionary! { "S" => "URI", "URI" => Object::string_literal("https://example.com") },
+            }),
+            Object::Dictionary(dictionary! { "Subtype" => "Link", "Dest" => "chapter2" }),
+            Object::Dictionary(dictionary! { "Subtype" => "Highlight" }),
+            Object::Dictionary(dictionary! { "Subtype" => "Widget" }),
+        ];
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Annots" => annots,
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => 1,
+                "Kids" => vec![page_id.into()],
+            }),
+        );
+        let catalog_id =
+            document.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        document.trailer.set("Root", catalog_id);
+
+        let counts = annotations(&document);
+        assert_eq!((counts.external_links, counts.internal_links), (1, 1));
+        assert_eq!(
+            counts.other.into_iter().collect::<Vec<_>>(),
+            vec![("Highlight".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn test_image_encoding() {
+        let stream = |filter: Object| Stream::new(dictionary! { "Filter" => filter }, vec![]);
+        assert_eq!(image_encoding(&stream("DCTDecode".into())), "JPEG");
+        assert_eq!(
+            image_encoding(&stream(Object::Array(vec![
+                "ASCII85Decode".into(),
+                "JPXDecode".into()
+            ]))),
+            "JPEG 2000"
+        );
+        assert_eq!(
+            image_encoding(&Stream::new(dictionary! {}, vec![])),
+            "uncompressed"
+        );
+    }
+}
@@ -0,0 +1,651 @@
+//! Baseline JPEG decoding (`/DCTDecode`), so JPEG images can be re-encoded
+//! as PNG and CMYK JPEGs converted to RGB.
+//!
+//! Only sequential Huffman-coded 8-bit images are supported; progressive
+//! and arithmetic-coded files are reported as errors.
+
+use super::limits::MAX_STREAM_SIZE;
+use std::f32::consts::{FRAC_1_SQRT_2, PI};
+use std::io;
+
+/// Decoded image with interleaved 8-bit samples: 1 (gray), 3 (RGB) or 4 (CMYK) per pixel
+#[derive(Debug)]
+pub struct Image {
+    pub components: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// Natural (row-major) index of each zig-zag coefficient
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+fn error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("JPEG: {}", message))
+}
+
+/// Canonical Huffman table
+#[derive(Debug, Clone, Default)]
+struct Huffman {
+    /// (code length, code, symbol), ordered by length then code
+    codes: Vec<(u8, u16, u8)>,
+}
+
+impl Huffman {
+    fn new(counts: &[u8], symbols: &[u8]) -> Self {
+        let mut codes = Vec::with_capacity(symbols.len());
+        let mut code: u16 = 0;
+        let mut symbols = symbols.iter();
+        for (length, &count) in (1..=16u8).zip(counts) {
+            for _ in 0..count {
+                if let Some(&symbol) = symbols.next() {
+                    codes.push((length, code, symbol));
+                }
+                code = code.wrapping_add(1);
+            }
+            code <<= 1;
+        }
+        Huffman { codes }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant: usize,
+    dc_table: usize,
+    ac_table: usize,
+    /// Decoded samples, padded to whole MCUs
+    plane: Vec<u8>,
+    plane_width: usize,
+    dc_pred: i32,
+}
+
+/// Entropy-coded segment reader that removes byte stuffing and stops at markers
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        BitReader {
+            data,
+            pos,
+            buffer: 0,
+            count: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.count <= 24 {
+            let mut byte = 0;
+            if self.pos < self.data.len() && self.data[self.pos] != 0xFF {
+                byte = self.data[self.pos];
+                self.pos += 1;
+            } else if self.pos + 1 < self.data.len() && self.data[self.pos + 1] == 0x00 {
+                byte = 0xFF;
+                self.pos += 2;
+            }
+            // At a marker, pad with zeros and leave the marker for the caller
+            self.buffer |= (byte as u32) << (24 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn bit(&mut self) -> u32 {
+        self.bits(1)
+    }
+
+    fn bits(&mut self, n: u32) -> u32 {
+        if n == 0 {
+            return 0;
+        }
+        self.fill();
+        let value = self.buffer >> (32 - n);
+        self.buffer <<= n;
+        self.count -= n;
+        value
+    }
+
+    /// Read `n` bits as a signed coefficient (JPEG "EXTEND")
+    fn receive(&mut self, n: u32) -> i32 {
+        if n == 0 {
+            return 0;
+        }
+        let value = self.bits(n) as i32;
+        if value < 1 << (n - 1) {
+            value - (1 << n) + 1
+        } else {
+            value
+        }
+    }
+
+    fn decode(&mut self, table: &Huffman) -> io::Result<u8> {
+        let mut code: u16 = 0;
+        let mut length = 0u8;
+        let mut index = 0;
+        while length < 16 {
+            code = (code << 1) | self.bit() as u16;
+            length += 1;
+            while index < table.codes.len() && table.codes[index].0 == length {
+                let (_, candidate, symbol) = table.codes[index];
+                if candidate == code {
+                    return Ok(symbol);
+                }
+                index += 1;
+            }
+        }
+        Err(error("bad Huffman code"))
+    }
+
+    /// Drop buffered bits and skip a restart marker
+    fn restart(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+        while self.pos + 1 < self.data.len() {
+            if self.data[self.pos] == 0xFF && (0xD0..=0xD7).contains(&self.data[self.pos + 1]) {
+                self.pos += 2;
+                return;
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Position of the next marker after the entropy-coded data
+    fn next_marker(&self) -> usize {
+        let mut pos = self.pos;
+        while pos + 1 < self.data.len() {
+            let next = self.data[pos + 1];
+            if self.data[pos] == 0xFF && next != 0x00 && !(0xD0..=0xD7).contains(&next) {
+                return pos;
+            }
+            pos += 1;
+        }
+        self.data.len()
+    }
+}
+
+/// Largest DC difference category and AC coefficient size of 8-bit data
+const MAX_DC_CATEGORY: u8 = 11;
+const MAX_AC_SIZE: u8 = 10;
+
+/// Decode a baseline JPEG file.
+///
+/// The frame must be `size` pixels, the size the image dictionary gives, and
+/// its samples may take at most `MAX_STREAM_SIZE` bytes; both are checked
+/// before anything is allocated.
+pub fn decode(data: &[u8], size: (u32, u32)) -> io::Result<Image> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(error("missing SOI marker"));
+    }
+
+    let mut quant = [[0u16; 64]; 4];
+    let mut dc_tables = vec![Huffman::default(); 4];
+    let mut ac_tables = vec![Huffman::default(); 4];
+    let mut components: Vec<Component> = Vec::new();
+    let (mut width, mut height) = (0usize, 0usize);
+    let mut restart_interval = 0usize;
+    let mut adobe_transform: Option<u8> = None;
+    let mut pos = 2;
+
+    loop {
+        while pos < data.len() && data[pos] != 0xFF {
+            pos += 1;
+        }
+        while pos < data.len() && data[pos] == 0xFF {
+            pos += 1;
+        }
+        let Some(&marker) = data.get(pos) else {
+            break;
+        };
+        pos += 1;
+        if marker == 0xD9 {
+            break;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            continue;
+        }
+        let length = read_u16(data, pos)? as usize;
+        let segment = data
+            .get(pos + 2..pos + length)
+            .ok_or_else(|| error("truncated segment"))?;
+
+        match marker {
+            0xC0 | 0xC1 => {
+                if !components.is_empty() {
+                    return Err(error("more than one frame header"));
+                }
+                if segment.first() != Some(&8) {
+                    return Err(error("only 8-bit precision is supported"));
+                }
+                height = read_u16(segment, 1)? as usize;
+                width = read_u16(segment, 3)? as usize;
+                if (width as u32, height as u32) != size {
+                    return Err(error(&format!(
+                        "frame is {}x{} but the image dictionary says {}x{}",
+                        width, height, size.0, size.1
+                    )));
+                }
+                let count = *segment.get(5).ok_or_else(|| error("bad SOF"))? as usize;
+                if !(1..=4).contains(&count) {
+                    return Err(error("bad SOF"));
+                }
+                for i in 0..count {
+                    let c = segment
+                        .get(6 + i * 3..9 + i * 3)
+                        .ok_or_else(|| error("bad SOF"))?;
+                    let (h, v) = ((c[1] >> 4).max(1), (c[1] & 15).max(1));
+                    if h > 4 || v > 4 {
+                        return Err(error("bad sampling factors"));
+                    }
+                    components.push(Component {
+                        id: c[0],
+                        h: h as usize,
+                        v: v as usize,
+                        quant: (c[2] & 3) as usize,
+                        dc_table: 0,
+                        ac_table: 0,
+                        plane: Vec::new(),
+                        plane_width: 0,
+                        dc_pred: 0,
+                    });
+                }
+                if plane_size(&components, width, height) > MAX_STREAM_SIZE {
+                    return Err(error("image too large"));
+                }
+            }
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err(error(
+                    "progressive, lossless and arithmetic coding are not supported",
+                ));
+            }
+            0xC4 => {
+                let mut rest = segment;
+                while rest.len() >= 17 {
+                    let (class, id) = (rest[0] >> 4, (rest[0] & 3) as usize);
+                    let counts = &rest[1..17];
+                    let total: usize = counts.iter().map(|&c| c as usize).sum();
+                    let symbols = rest
+                        .get(17..17 + total)
+                        .ok_or_else(|| error("truncated DHT"))?;
+                    let table = Huffman::new(counts, symbols);
+                    if class == 0 {
+                        dc_tables[id] = table;
+                    } else {
+                        ac_tables[id] = table;
+                    }
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xDB => {
+                let mut rest = segment;
+                while !rest.is_empty() {
+                    let (precision, id) = (rest[0] >> 4, (rest[0] & 3) as usize);
+                    let size = if precision == 0 { 64 } else { 128 };
+                    let values = rest
+                        .get(1..1 + size)
+                        .ok_or_else(|| error("truncated DQT"))?;
+                    for (k, slot) in quant[id].iter_mut().enumerate() {
+                        *slot = if precision == 0 {
+                            values[k] as u16
+                        } else {
+                            u16::from_be_bytes([values[2 * k], values[2 * k + 1]])
+                        };
+                    }
+                    rest = &rest[1 + size..];
+                }
+            }
+            0xDD => restart_interval = read_u16(segment, 0)? as usize,
+            0xEE if segment.starts_with(b"Adobe") && segment.len() >= 12 => {
+                adobe_transform = Some(segment[11]);
+            }
+            0xDA => {
+                if components.is_empty() || width == 0 || height == 0 {
+                    return Err(error("scan before frame header"));
+                }
+                let count = *segment.first().ok_or_else(|| error("bad SOS"))? as usize;
+                let mut scan = Vec::with_capacity(count);
+                for i in 0..count {
+                    let s = segment
+                        .get(1 + i * 2..3 + i * 2)
+                        .ok_or_else(|| error("bad SOS"))?;
+                    let index = components
+                        .iter()
+                        .position(|c| c.id == s[0])
+                        .ok_or_else(|| error("unknown scan component"))?;
+                    components[index].dc_table = ((s[1] >> 4) & 3) as usize;
+                    components[index].ac_table = (s[1] & 3) as usize;
+                    scan.push(index);
+                }
+                let mut reader = BitReader::new(data, pos + length);
+                decode_scan(
+                    &mut reader,
+                    &mut components,
+                    &scan,
+                    (width, height),
+                    restart_interval,
+                    (&dc_tables, &ac_tables, &quant),
+                )?;
+                pos = reader.next_marker();
+                continue;
+            }
+            _ => {}
+        }
+        pos += length;
+    }
+
+    if components.is_empty() || components[0].plane.is_empty() {
+        return Err(error("no image data"));
+    }
+    Ok(Image {
+        components: components.len(),
+        pixels: assemble(&components, width, height, adobe_transform),
+    })
+}
+
+fn read_u16(data: &[u8], pos: usize) -> io::Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or_else(|| error("unexpected end of data"))
+}
+
+/// Bytes the component planes of a frame take, padded to whole MCUs
+fn plane_size(components: &[Component], width: usize, height: usize) -> u64 {
+    let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+    let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+    let mcus_x = width.div_ceil(8 * h_max) as u64;
+    let mcus_y = height.div_ceil(8 * v_max) as u64;
+    components
+        .iter()
+        .map(|c| mcus_x * c.h as u64 * 8 * mcus_y * c.v as u64 * 8)
+        .sum()
+}
+
+/// Decode every MCU of one scan into the component planes
+fn decode_scan(
+    reader: &mut BitReader,
+    components: &mut [Component],
+    scan: &[usize],
+    (width, height): (usize, usize),
+    restart_interval: usize,
+    (dc_tables, ac_tables, quant): (&[Huffman], &[Huffman], &[[u16; 64]; 4]),
+) -> io::Result<()> {
+    let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+    let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+    let mcus_x = width.div_ceil(8 * h_max);
+    let mcus_y = height.div_ceil(8 * v_max);
+    for component in components.iter_mut() {
+        if component.plane.is_empty() {
+            component.plane_width = mcus_x * component.h * 8;
+            component.plane = vec![0; component.plane_width * mcus_y * component.v * 8];
+        }
+        component.dc_pred = 0;
+    }
+
+    // A single-component scan is not interleaved: its MCU is one block and
+    // only blocks covering the component's own extent are coded
+    let (mcus_x, mcus_y) = if scan.len() == 1 {
+        let c = &components[scan[0]];
+        (
+            (width * c.h).div_ceil(h_max).div_ceil(8),
+            (height * c.v).div_ceil(v_max).div_ceil(8),
+        )
+    } else {
+        (mcus_x, mcus_y)
+    };
+
+    let cos = cos_table();
+    let mut block = [0f32; 64];
+    for mcu in 0..mcus_x * mcus_y {
+        if restart_interval > 0 && mcu > 0 && mcu % restart_interval == 0 {
+            reader.restart();
+            for &index in scan {
+                components[index].dc_pred = 0;
+            }
+        }
+        let (mx, my) = (mcu % mcus_x, mcu / mcus_x);
+        for &index in scan {
+            let component = &mut components[index];
+            let (bh, bv) = if scan.len() == 1 {
+                (1, 1)
+            } else {
+                (component.h, component.v)
+            };
+            for by in 0..bv {
+                for bx in 0..bh {
+                    let q = &quant[component.quant];
+                    let mut coefficients = [0i32; 64];
+                    let t = reader.decode(&dc_tables[component.dc_table])?;
+                    if t > MAX_DC_CATEGORY {
+                        return Err(error("bad Huffman symbol"));
+                    }
+                    component.dc_pred = component.dc_pred.wrapping_add(reader.receive(t as u32));
+                    coefficients[0] = component.dc_pred.wrapping_mul(q[0] as i32);
+                    let mut k = 1;
+                    while k < 64 {
+                        let rs = reader.decode(&ac_tables[component.ac_table])?;
+                        let (run, size) = ((rs >> 4) as usize, rs & 15);
+                        if size > MAX_AC_SIZE {
+                            return Err(error("bad Huffman symbol"));
+                        }
+                        if size == 0 {
+                            if run == 15 {
+                                k += 16;
+                                continue;
+                            }
+                            break;
+                        }
+                        k += run;
+                        if k > 63 {
+                            break;
+                        }
+                        coefficients[ZIGZAG[k]] =
+                            reader.receive(size as u32).wrapping_mul(q[k] as i32);
+                        k += 1;
+                    }
+
+                    idct(&cos, &coefficients, &mut block);
+                    let x0 = (mx * bh + bx) * 8;
+                    let y0 = (my * bv + by) * 8;
+                    for y in 0..8 {
+                        let row = (y0 + y) * component.plane_width + x0;
+                        for x in 0..8 {
+                            component.plane[row + x] =
+                                (block[y * 8 + x] + 128.0).round().clamp(0.0, 255.0) as u8;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// IDCT basis: `C(u) * cos((2x + 1) * u * pi / 16)` indexed `[x][u]`
+fn cos_table() -> [[f32; 8]; 8] {
+    let mut cos = [[0f32; 8]; 8];
+    for (x, row) in cos.iter_mut().enumerate() {
+        for (u, value) in row.iter_mut().enumerate() {
+            let scale = if u == 0 { FRAC_1_SQRT_2 } else { 1.0 };
+            *value = scale * (((2 * x + 1) * u) as f32 * PI / 16.0).cos();
+        }
+    }
+    cos
+}
+
+/// Separable floating-point inverse DCT of one 8x8 block
+fn idct(cos: &[[f32; 8]; 8], coefficients: &[i32; 64], out: &mut [f32; 64]) {
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8)
+                .map(|u| cos[x][u] * coefficients[v * 8 + u] as f32)
+                .sum::<f32>()
+                / 2.0;
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            out[y * 8 + x] = (0..8).map(|v| cos[y][v] * rows[v * 8 + x]).sum::<f32>() / 2.0;
+        }
+    }
+}
+
+/// Upsample the component planes and apply the colour transform
+fn assemble(components: &[Component], width: usize, height: usize, adobe: Option<u8>) -> Vec<u8> {
+    let n = components.len();
+    let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+    let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+    // YCbCr unless an Adobe marker says otherwise; YCCK for 4 components only when flagged
+    let transform = match (n, adobe) {
+        (3, Some(0)) => false,
+        (3, _) => true,
+        (4, Some(2)) => true,
+        _ => false,
+    };
+
+    let mut pixels = Vec::with_capacity(width * height * n);
+    let mut sample = [0u8; 4];
+    for y in 0..height {
+        for x in 0..width {
+            for (slot, c) in sample.iter_mut().zip(components) {
+                let (sx, sy) = (x * c.h / h_max, y * c.v / v_max);
+                *slot = c.plane[sy * c.plane_width + sx];
+            }
+            if transform {
+                let (r, g, b) = ycc_to_rgb(sample[0], sample[1], sample[2]);
+                if n == 4 {
+                    // YCCK: the transform yields inverted CMY
+                    sample[..3].copy_from_slice(&[255 - r, 255 - g, 255 - b]);
+                } else {
+                    sample[..3].copy_from_slice(&[r, g, b]);
+                }
+            }
+            pixels.extend_from_slice(&sample[..n]);
+        }
+    }
+    pixels
+}
+
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> (u8, u8, u8) {
+    let (y, cb, cr) = (y as f32, cb as f32 - 128.0, cr as f32 - 128.0);
+    let channel = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    (
+        channel(y + 1.402 * cr),
+        channel(y - 0.344_136 * cb - 0.714_136 * cr),
+        channel(y + 1.772 * cb),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 8x8 grayscale baseline JPEG of a uniform mid-gray (value 128) block,
+    /// with minimal single-symbol Huffman tables
+    fn gray_jpeg(dc_category: u8, dc_bits: &[bool]) -> Vec<u8> {
+        let mut jpeg = vec![0xFF, 0xD8];
+        // DQT: all ones
+        jpeg.extend_from_slice(&[0xFF, 0xDB, 0x00, 0x43, 0x00]);
+        jpeg.extend_from_slice(&[1; 64]);
+        // SOF0: 8x8, one component
+        jpeg.extend_from_slice(&[0xFF, 0xC0, 0x00, 0x0B, 8, 0, 8, 0, 8, 1, 1, 0x11, 0]);
+        // DHT: DC table with one 1-bit code for `dc_category`, AC table with one 1-bit EOB
+        let mut counts = [0u8; 16];
+        counts[0] = 1;
+        jpeg.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x14, 0x00]);
+        jpeg.extend_from_slice(&counts);
+        jpeg.push(dc_category);
+        jpeg.extend_from_slice(&[0xFF, 0xC4, 0x00, 0x14, 0x10]);
+        jpeg.extend_from_slice(&counts);
+        jpeg.push(0x00);
+        // SOS
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x08, 1, 1, 0x00, 0, 63, 0]);
+        // DC code "0", DC bits, AC EOB "0", padded with ones
+        let mut bits = vec![false];
+        bits.extend_from_slice(dc_bits);
+        bits.push(false);
+        let mut byte = 0xFFu8;
+        for (i, &bit) in bits.iter().enumerate() {
+            if !bit {
+                byte &= !(0x80 >> i);
+            }
+        }
+        jpeg.push(byte);
+        jpeg.extend_from_slice(&[0xFF, 0xD9]);
+        jpeg
+    }
+
+    #[test]
+    fn test_decode_uniform_gray_block() {
+        let image = decode(&gray_jpeg(0, &[]), (8, 8)).unwrap();
+        assert_eq!(image.components, 1);
+        assert_eq!(image.pixels.len(), 64);
+        assert!(image.pixels.iter().all(|&p| p == 128));
+    }
+
+    #[test]
+    fn test_decode_applies_dc_coefficient() {
+        // DC category 4, value 0b1000 = +8; a DC of 8 shifts every sample by 8 / 8 = 1
+        let image = decode(&gray_jpeg(4, &[true, false, false, false]), (8, 8)).unwrap();
+        assert!(image.pixels.iter().all(|&p| p == 129));
+    }
+
+    #[test]
+    fn test_decode_rejects_progressive() {
+        let mut jpeg = gray_jpeg(0, &[]);
+        // Turn SOF0 into SOF2
+        let sof = jpeg.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        jpeg[sof + 1] = 0xC2;
+        assert!(decode(&jpeg, (8, 8)).is_err());
+    }
+
+    #[test]
+    fn test_decode_checks_frame_before_allocating() {
+        let jpeg = gray_jpeg(0, &[]);
+        assert!(decode(&jpeg, (16, 8)).is_err());
+
+        // A 65535x65535 frame with 15x15 sampling, as its dictionary says
+        let mut huge = jpeg.clone();
+        let sof = huge.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        huge[sof + 5..sof + 9].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        huge[sof + 11] = 0xFF;
+        let message = decode(&huge, (65535, 65535)).unwrap_err().to_string();
+        assert!(message.contains("sampling"), "{message}");
+        huge[sof + 11] = 0x11;
+        let message = decode(&huge, (65535, 65535)).unwrap_err().to_string();
+        assert!(message.contains("too large"), "{message}");
+    }
+
+    #[test]
+    fn test_decode_rejects_bad_huffman_symbols() {
+        // A DC table whose one code is for category 200
+        let message = decode(&gray_jpeg(200, &[]), (8, 8))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("bad Huffman symbol"), "{message}");
+
+        // An AC table whose one code is for size 15
+        let mut jpeg = gray_jpeg(0, &[]);
+        let ac = jpeg
+            .windows(5)
+            .position(|w| w == [0xFF, 0xC4, 0x00, 0x14, 0x10])
+            .unwrap();
+        jpeg[ac + 21] = 0x0F;
+        let message = decode(&jpeg, (8, 8)).unwrap_err().to_string();
+        assert!(message.contains("bad Huffman symbol"), "{message}");
+    }
+
+    #[test]
+    fn test_ycc_to_rgb() {
+        assert_eq!(ycc_to_rgb(128, 128, 128), (128, 128, 128));
+        assert_eq!(ycc_to_rgb(255, 128, 128), (255, 255, 255));
+    }
+}
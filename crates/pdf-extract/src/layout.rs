@@ -0,0 +1,823 @@
+use super::formula::Formula;
+use super::geometry::{Color, PathShape, Rect, Segment};
+use super::symbol::SymbolFont;
+use crate::{PdfError, Result};
+use log::{debug, warn};
+use lopdf::content::{Content, Operation};
+use lopdf::{Document, Encoding, Object, ObjectId};
+use std::collections::BTreeMap;
+
+/// A run of text drawn with a single font at a single position
+#[derive(Debug, Clone)]
+pub struct TextRun {
+    pub text: String,
+    /// Base font name with any subset prefix (e.g. `ABCDEF+`) removed
+    pub font: String,
+    pub size: f32,
+    pub x: f32,
+    pub y: f32,
+    /// Advance width, from the font's `/Widths` or estimated at half an em per glyph
+    pub width: f32,
+    /// Fill colour the text is painted in
+    pub color: Color,
+    /// Identifier of the innermost marked content with one that the text is in
+    pub mcid: Option<i64>,
+}
+
+/// Kind of a text markup annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkupKind {
+    Underline,
+    StrikeOut,
+    /// Marked for redaction, which has not been applied yet
+    Redact,
+}
+
+/// A text markup annotation, with one rectangle per marked line
+#[derive(Debug, Clone)]
+pub struct Markup {
+    pub kind: MarkupKind,
+    pub rects: Vec<Rect>,
+}
+
+/// An image XObject drawn on the page, with its bounding box in user space
+#[derive(Debug, Clone)]
+pub struct ImagePlacement {
+    pub id: ObjectId,
+    pub bounds: Rect,
+}
+
+/// Positioned content of a single page, in content-stream order
+#[derive(Debug, Clone, Default)]
+pub struct PageLayout {
+    /// The page's MediaBox
+    pub page_box: Rect,
+    pub runs: Vec<TextRun>,
+    pub images: Vec<ImagePlacement>,
+    /// Painted (stroked or filled) vector paths
+    pub paths: Vec<PathShape>,
+    /// Underline, strikeout and redact annotations on the page
+    pub markup: Vec<Markup>,
+    /// Formulas the structure tree tags on the page; only looked up when
+    /// math is converted
+    pub formulas: Vec<Formula>,
+}
+
+impl PageLayout {
+    /// Group runs into visual lines, top to bottom, preserving stream order within a line
+    pub fn lines(&self) -> Vec<Vec<&TextRun>> {
+        let mut lines: Vec<Vec<&TextRun>> = Vec::new();
+        for run in &self.runs {
+            match lines.last_mut() {
+                Some(line) if same_line(line[0], run) => line.push(run),
+                _ => lines.push(vec![run]),
+            }
+        }
+        lines
+    }
+}
+
+/// Two runs share a line when their baselines are within half a font size
+fn same_line(a: &TextRun, b: &TextRun) -> bool {
+    let tolerance = a.size.max(b.size).max(1.0) * 0.5;
+    (a.y - b.y).abs() <= tolerance
+}
+
+/// 2D affine transform in PDF order `[a b c d e f]`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix(pub [f32; 6]);
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    /// Build a matrix from six numeric operands
+    pub fn from_operands(operands: &[Object]) -> Option<Self> {
+        if operands.len() < 6 {
+            return None;
+        }
+        let mut m = [0.0; 6];
+        for (slot, operand) in m.iter_mut().zip(operands) {
+            *slot = number(operand)?;
+        }
+        Some(Matrix(m))
+    }
+
+    pub fn translate(tx: f32, ty: f32) -> Self {
+        Matrix([1.0, 0.0, 0.0, 1.0, tx, ty])
+    }
+
+    /// Compose `self` followed by `other` (PDF `self × other`)
+    pub fn then(&self, other: &Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+
+    /// Apply the transform to a point
+    pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    /// Vertical scale factor, used to turn a font size into user space
+    pub fn scale_y(&self) -> f32 {
+        let [_, _, c, d, _, _] = self.0;
+        (c * c + d * d).sqrt()
+    }
+}
+
+/// Read a numeric operand as `f32`
+pub fn number(object: &Object) -> Option<f32> {
+    match object {
+        Object::Integer(i) => Some(*i as f32),
+        Object::Real(r) => Some(*r),
+        _ => None,
+    }
+}
+
+/// Font resources of a page, keyed by resource name
+struct PageFont<'a> {
+    name: String,
+    encoding: Option<Encoding<'a>>,
+    /// `/FirstChar` and `/Widths` of a simple font, in thousandths of an em
+    widths: Option<(u32, Vec<f32>)>,
+    /// Type0 fonts use two-byte character codes
+    two_byte: bool,
+    /// Symbol fonts are decoded with their own tables
+    symbols: Option<SymbolFont>,
+}
+
+impl PageFont<'_> {
+    /// Text of a string operand set in the font
+    fn decode(&self, bytes: &[u8]) -> Option<String> {
+        match (self.symbols, &self.encoding) {
+            (Some(symbols), _) if !self.two_byte => Some(symbols.decode(bytes)),
+            (symbols, Some(encoding)) => {
+                let text = Document::decode_text(encoding, bytes).ok()?;
+                Some(match symbols {
+                    Some(symbols) => symbols.remap(&text),
+                    None => text,
+                })
+            }
+            (_, None) => None,
+        }
+    }
+}
+
+/// Graphics state saved and restored by `q` / `Q`
+#[derive(Debug, Clone, Copy)]
+struct GraphicsState {
+    ctm: Matrix,
+    stroke: Color,
+    fill: Color,
+    line_width: f32,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        Self {
+            ctm: Matrix::IDENTITY,
+            stroke: Color::BLACK,
+            fill: Color::BLACK,
+            line_width: 1.0,
+        }
+    }
+}
+
+/// Text and graphics state tracked while walking a content stream
+struct State<'a> {
+    fonts: BTreeMap<Vec<u8>, PageFont<'a>>,
+    images: BTreeMap<Vec<u8>, ObjectId>,
+    font: Option<Vec<u8>>,
+    font_size: f32,
+    leading: f32,
+    gs: GraphicsState,
+    gs_stack: Vec<GraphicsState>,
+    text_matrix: Matrix,
+    line_matrix: Matrix,
+    /// Path under construction, in user space
+    path: Vec<Segment>,
+    /// Current point in user space
+    point: (f32, f32),
+    /// Open marked-content sequences, with their MCIDs
+    marked: Vec<Option<i64>>,
+}
+
+/// Walk a page's content stream and collect positioned text runs
+pub fn page_layout(document: &Document, page_id: ObjectId) -> Result<PageLayout> {
+    let data = document
+        .get_page_content(page_id)
+        .map_err(|e| PdfError::Processing(format!("Failed to read page content: {}", e)))?;
+    let content = Content::decode(&data)
+        .map_err(|e| PdfError::Processing(format!("Failed to decode page content: {}", e)))?;
+
+    let mut state = State {
+        fonts: page_fonts(document, page_id),
+        images: page_images(document, page_id),
+        font: None,
+        font_size: 0.0,
+        leading: 0.0,
+        gs: GraphicsState::default(),
+        gs_stack: Vec::new(),
+        text_matrix: Matrix::IDENTITY,
+        line_matrix: Matrix::IDENTITY,
+        path: Vec::new(),
+        point: (0.0, 0.0),
+        marked: Vec::new(),
+    };
+
+    let mut layout = PageLayout {
+        page_box: page_box(document, page_id),
+        markup: page_markup(document, page_id),
+        ..Default::default()
+    };
+    for operation in &content.operations {
+        apply_operation(&mut state, operation, &mut layout);
+    }
+
+    debug!("Collected {} text runs", layout.runs.len());
+    Ok(layout)
+}
+
+/// Count the glyphs a page's content stream shows, leaving out the spaces of
+/// one-byte fonts: the text there is for extraction to find
+pub fn glyph_count(document: &Document, page_id: ObjectId) -> Result<usize> {
+    let data = document
+        .get_page_content(page_id)
+        .map_err(|e| PdfError::Processing(format!("Failed to read page content: {}", e)))?;
+    let content = Content::decode(&data)
+        .map_err(|e| PdfError::Processing(format!("Failed to decode page content: {}", e)))?;
+
+    let fonts = page_fonts(document, page_id);
+    let mut two_byte = false;
+    let mut count = 0;
+    for operation in &content.operations {
+        let operands = &operation.operands;
+        match operation.operator.as_str() {
+            "Tf" => {
+                two_byte = operands
+                    .first()
+                    .and_then(|o| o.as_name().ok())
+                    .and_then(|name| fonts.get(name))
+                    .is_some_and(|font| font.two_byte);
+            }
+            "Tj" | "TJ" | "'" | "\"" => count += glyphs(two_byte, operands),
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+/// Glyphs shown by the string operands of a text-showing operator
+fn glyphs(two_byte: bool, operands: &[Object]) -> usize {
+    operands
+        .iter()
+        .map(|operand| match operand {
+            Object::String(bytes, _) if two_byte => bytes.len() / 2,
+            Object::String(bytes, _) => bytes.iter().filter(|&&code| code != b' ').count(),
+            Object::Array(items) => glyphs(two_byte, items),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// The page's MediaBox, inherited from ancestors when absent (US Letter if missing)
+fn page_box(document: &Document, page_id: ObjectId) -> Rect {
+    let mut node = document.get_dictionary(page_id).ok();
+    // Bounded walk up the page tree guards against Parent cycles
+    for _ in 0..32 {
+        let Some(dict) = node else {
+            break;
+        };
+        if let Ok(values) = dict.get(b"MediaBox").and_then(Object::as_array) {
+            let n: Vec<f32> = values.iter().filter_map(number).collect();
+            if let [x0, y0, x1, y1] = n[..] {
+                return Rect::from_corners(x0, y0, x1, y1);
+            }
+        }
+        node = dict
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .and_then(|id| document.get_dictionary(id))
+            .ok();
+    }
+    Rect::from_corners(0.0, 0.0, 612.0, 792.0)
+}
+
+/// Resolve the fonts used on a page with their base names and encodings
+fn page_fonts(document: &Document, page_id: ObjectId) -> BTreeMap<Vec<u8>, PageFont<'_>> {
+    let fonts = match document.get_page_fonts(page_id) {
+        Ok(fonts) => fonts,
+        Err(e) => {
+            warn!("Failed to read page fonts: {}", e);
+            return BTreeMap::new();
+        }
+    };
+
+    fonts
+        .into_iter()
+        .map(|(key, font)| {
+            let name = font
+                .get(b"BaseFont")
+                .and_then(Object::as_name)
+                .map(|n| strip_subset_prefix(&String::from_utf8_lossy(n)).to_string())
+                .unwrap_or_default();
+            let encoding = font.get_font_encoding(document).ok();
+            let first_char = font.get(b"FirstChar").ok().and_then(number);
+            let widths = font
+                .get(b"Widths")
+                .ok()
+                .and_then(|widths| match widths {
+                    Object::Reference(id) => document.get_object(*id).ok(),
+                    other => Some(other),
+                })
+                .and_then(|widths| widths.as_array().ok())
+                .map(|widths| widths.iter().filter_map(number).collect::<Vec<f32>>());
+            let two_byte = font
+                .get(b"Subtype")
+                .and_then(Object::as_name)
+                .is_ok_and(|subtype| subtype == b"Type0");
+            let symbols = SymbolFont::from_name(&name);
+            (
+                key,
+                PageFont {
+                    name,
+                    encoding,
+                    widths: first_char.zip(widths).map(|(first, w)| (first as u32, w)),
+                    two_byte,
+                    symbols,
+                },
+            )
+        })
+        .collect()
+}
+
+/// Read the page's underline and strikeout annotations
+fn page_markup(document: &Document, page_id: ObjectId) -> Vec<Markup> {
+    let resolve = |object: &Object| match object {
+        Object::Reference(id) => document.get_object(*id).ok().cloned(),
+        other => Some(other.clone()),
+    };
+    let Some(annotations) = document
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(resolve)
+        .and_then(|annots| annots.as_array().ok().cloned())
+    else {
+        return Vec::new();
+    };
+
+    annotations
+        .iter()
+        .filter_map(resolve)
+        .filter_map(|annotation| {
+            let dict = annotation.as_dict().ok()?;
+            let kind = match dict.get(b"Subtype").and_then(Object::as_name).ok()? {
+                b"Underline" => MarkupKind::Underline,
+                b"StrikeOut" => MarkupKind::StrikeOut,
+                b"Redact" => MarkupKind::Redact,
+                _ => return None,
+            };
+            let numbers = |key: &[u8]| -> Vec<f32> {
+                dict.get(key)
+                    .ok()
+                    .and_then(resolve)
+                    .and_then(|values| values.as_array().ok().cloned())
+                    .map(|values| values.iter().filter_map(number).collect())
+                    .unwrap_or_default()
+            };
+            // QuadPoints hold four corners per marked line; Rect is the fallback
+            let quads = numbers(b"QuadPoints");
+            let mut rects: Vec<Rect> = quads
+                .chunks_exact(8)
+                .map(|q| {
+                    Rect::from_corners(
+                        q[0].min(q[2]).min(q[4]).min(q[6]),
+                        q[1].min(q[3]).min(q[5]).min(q[7]),
+                        q[0].max(q[2]).max(q[4]).max(q[6]),
+                        q[1].max(q[3]).max(q[5]).max(q[7]),
+                    )
+                })
+                .collect();
+            if rects.is_empty()
+                && let [x0, y0, x1, y1] = numbers(b"Rect")[..]
+            {
+                rects.push(Rect::from_corners(x0, y0, x1, y1));
+            }
+            (!rects.is_empty()).then_some(Markup { kind, rects })
+        })
+        .collect()
+}
+
+/// Resolve the image XObjects available to a page, keyed by resource name
+fn page_images(document: &Document, page_id: ObjectId) -> BTreeMap<Vec<u8>, ObjectId> {
+    let mut images = BTreeMap::new();
+    let Ok((inline, inherited)) = document.get_page_resources(page_id) else {
+        return images;
+    };
+
+    let dictionaries = inline.into_iter().chain(
+        inherited
+            .iter()
+            .filter_map(|&id| document.get_dictionary(id).ok()),
+    );
+    for resources in dictionaries {
+        let xobjects = match resources.get(b"XObject") {
+            Ok(Object::Reference(id)) => document.get_dictionary(*id).ok(),
+            Ok(Object::Dictionary(dict)) => Some(dict),
+            _ => None,
+        };
+        for (name, value) in xobjects.into_iter().flat_map(|dict| dict.iter()) {
+            let Ok(id) = value.as_reference() else {
+                continue;
+            };
+            let is_image = document
+                .get_object(id)
+                .and_then(Object::as_stream)
+                .and_then(|s| s.dict.get(b"Subtype").and_then(Object::as_name))
+                .is_ok_and(|subtype| subtype == b"Image");
+            if is_image {
+                images.entry(name.clone()).or_insert(id);
+            }
+        }
+    }
+    images
+}
+
+/// Remove the six-letter subset tag from an embedded font name (`ABCDEF+CMMI10` -> `CMMI10`)
+pub fn strip_subset_prefix(name: &str) -> &str {
+    match name.split_once('+') {
+        Some((tag, rest)) if tag.len() == 6 && tag.chars().all(|c| c.is_ascii_uppercase()) => rest,
+        _ => name,
+    }
+}
+
+/// Update state for one operator, emitting a run for text-showing operators
+fn apply_operation(state: &mut State, operation: &Operation, layout: &mut PageLayout) {
+    let operands = &operation.operands;
+    match operation.operator.as_str() {
+        "q" => state.gs_stack.push(state.gs),
+        "Q" => state.gs = state.gs_stack.pop().unwrap_or_default(),
+        "cm" => {
+            if let Some(m) = Matrix::from_operands(operands) {
+                state.gs.ctm = m.then(&state.gs.ctm);
+            }
+        }
+        "w" => {
+            let width = operands.first().and_then(number).unwrap_or(1.0);
+            state.gs.line_width = width * state.gs.ctm.scale_y();
+        }
+        "G" | "RG" | "K" | "SC" | "SCN" => {
+            if let Some(color) = color_operands(operands) {
+                state.gs.stroke = color;
+            }
+        }
+        "g" | "rg" | "k" | "sc" | "scn" => {
+            if let Some(color) = color_operands(operands) {
+                state.gs.fill = color;
+            }
+        }
+        "m" | "l" | "c" | "v" | "y" | "h" | "re" => build_path(state, operation),
+        "S" | "s" | "f" | "F" | "f*" | "B" | "B*" | "b" | "b*" | "n" => {
+            paint_path(state, &operation.operator, layout)
+        }
+        "BT" => {
+            state.text_matrix = Matrix::IDENTITY;
+            state.line_matrix = Matrix::IDENTITY;
+        }
+        "Tf" => {
+            state.font = operands
+                .first()
+                .and_then(|o| o.as_name().ok())
+                .map(<[u8]>::to_vec);
+            state.font_size = operands.get(1).and_then(number).unwrap_or(state.font_size);
+        }
+        "TL" => state.leading = operands.first().and_then(number).unwrap_or(state.leading),
+        "Td" | "TD" => {
+            let tx = operands.first().and_then(number).unwrap_or(0.0);
+            let ty = operands.get(1).and_then(number).unwrap_or(0.0);
+            if operation.operator == "TD" {
+                state.leading = -ty;
+            }
+            move_line(state, tx, ty);
+        }
+        "Tm" => {
+            if let Some(m) = Matrix::from_operands(operands) {
+                state.text_matrix = m;
+                state.line_matrix = m;
+            }
+        }
+        "T*" => move_line(state, 0.0, -state.leading),
+        "Tj" | "TJ" => show_text(state, operands, layout),
+        "Do" => place_image(state, operands, layout),
+        "'" | "\"" => {
+            move_line(state, 0.0, -state.leading);
+            show_text(state, operands, layout);
+        }
+        "BMC" => state.marked.push(None),
+        "BDC" => state.marked.push(
+            operands
+                .get(1)
+                .and_then(|properties| properties.as_dict().ok())
+                .and_then(|properties| properties.get(b"MCID").ok()?.as_i64().ok()),
+        ),
+        "EMC" => {
+            state.marked.pop();
+        }
+        _ => {}
+    }
+}
+
+/// Start a new text line offset from the current line start
+fn move_line(state: &mut State, tx: f32, ty: f32) {
+    state.line_matrix = Matrix::translate(tx, ty).then(&state.line_matrix);
+    state.text_matrix = state.line_matrix;
+}
+
+/// Decode the string operands of a text-showing operator into a run
+fn show_text(state: &mut State, operands: &[Object], layout: &mut PageLayout) {
+    let Some(font) = state.font.as_ref().and_then(|key| state.fonts.get(key)) else {
+        return;
+    };
+    let mut text = String::new();
+    collect_text(&mut text, font, operands);
+    if text.is_empty() {
+        return;
+    }
+
+    let transform = state.text_matrix.then(&state.gs.ctm);
+    let (x, y) = transform.apply(0.0, 0.0);
+    let advance = text_advance(font, operands) / 1000.0 * state.font_size;
+    let (end_x, end_y) = transform.apply(advance, 0.0);
+    layout.runs.push(TextRun {
+        text,
+        font: font.name.clone(),
+        size: state.font_size * transform.scale_y(),
+        x,
+        y,
+        width: ((end_x - x).powi(2) + (end_y - y).powi(2)).sqrt(),
+        color: state.gs.fill,
+        mcid: state.marked.iter().rev().find_map(|&mcid| mcid),
+    });
+    // The next string on this line starts where this one ends
+    state.text_matrix = Matrix::translate(advance, 0.0).then(&state.text_matrix);
+}
+
+/// Horizontal advance of text-showing operands, in thousandths of the font size.
+///
+/// Character and word spacing are not applied.
+fn text_advance(font: &PageFont, operands: &[Object]) -> f32 {
+    const DEFAULT_WIDTH: f32 = 500.0;
+    let mut advance = 0.0;
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) if font.two_byte => {
+                advance += (bytes.len() / 2) as f32 * DEFAULT_WIDTH;
+            }
+            Object::String(bytes, _) => {
+                for &code in bytes {
+                    let width = font.widths.as_ref().and_then(|(first, widths)| {
+                        let index = (code as u32).checked_sub(*first)?;
+                        widths.get(index as usize).copied()
+                    });
+                    advance += width.unwrap_or(DEFAULT_WIDTH);
+                }
+            }
+            Object::Array(items) => {
+                for item in items {
+                    match item {
+                        Object::String(..) => {
+                            advance += text_advance(font, std::slice::from_ref(item))
+                        }
+                        // Kerning adjustments move the next glyph left
+                        other => advance -= number(other).unwrap_or(0.0),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    advance
+}
+
+/// Record an image XObject drawn into the unit square mapped by the CTM
+fn place_image(state: &State, operands: &[Object], layout: &mut PageLayout) {
+    let Some(&id) = operands
+        .first()
+        .and_then(|o| o.as_name().ok())
+        .and_then(|name| state.images.get(name))
+    else {
+        return;
+    };
+
+    let (x0, y0) = state.gs.ctm.apply(0.0, 0.0);
+    let (x1, y1) = state.gs.ctm.apply(1.0, 1.0);
+    layout.images.push(ImagePlacement {
+        id,
+        bounds: Rect::from_corners(x0, y0, x1, y1),
+    });
+}
+
+/// Parse colour operands, ignoring pattern names and unsupported component counts
+fn color_operands(operands: &[Object]) -> Option<Color> {
+    let components: Vec<f32> = operands.iter().filter_map(number).collect();
+    Color::from_components(&components)
+}
+
+/// Append a path construction operator to the current path
+fn build_path(state: &mut State, operation: &Operation) {
+    let n: Vec<f32> = operation.operands.iter().filter_map(number).collect();
+    let ctm = state.gs.ctm;
+    let point = |i: usize| ctm.apply(n[i], n[i + 1]);
+    let segment = match (operation.operator.as_str(), n.len()) {
+        ("m", 2) => {
+            let (x, y) = point(0);
+            Segment::MoveTo(x, y)
+        }
+        ("l", 2) => {
+            let (x, y) = point(0);
+            Segment::LineTo(x, y)
+        }
+        ("c", 6) => {
+            let ((x1, y1), (x2, y2), (x3, y3)) = (point(0), point(2), point(4));
+            Segment::CurveTo(x1, y1, x2, y2, x3, y3)
+        }
+        ("v", 4) => {
+            let (x1, y1) = state.point;
+            let ((x2, y2), (x3, y3)) = (point(0), point(2));
+            Segment::CurveTo(x1, y1, x2, y2, x3, y3)
+        }
+        ("y", 4) => {
+            let ((x1, y1), (x3, y3)) = (point(0), point(2));
+            Segment::CurveTo(x1, y1, x3, y3, x3, y3)
+        }
+        ("h", _) => Segment::Close,
+        ("re", 4) => {
+            let (x, y, w, h) = (n[0], n[1], n[2], n[3]);
+            for (px, py) in [(x, y), (x + w, y), (x + w, y + h), (x, y + h)] {
+                let (tx, ty) = ctm.apply(px, py);
+                state.path.push(if px == x && py == y {
+                    Segment::MoveTo(tx, ty)
+                } else {
+                    Segment::LineTo(tx, ty)
+                });
+            }
+            state.path.push(Segment::Close);
+            state.point = ctm.apply(x, y);
+            return;
+        }
+        _ => return,
+    };
+    match segment {
+        Segment::MoveTo(x, y) | Segment::LineTo(x, y) | Segment::CurveTo(_, _, _, _, x, y) => {
+            state.point = (x, y)
+        }
+        Segment::Close => {}
+    }
+    state.path.push(segment);
+}
+
+/// Finish the current path with a painting operator
+fn paint_path(state: &mut State, operator: &str, layout: &mut PageLayout) {
+    let segments = std::mem::take(&mut state.path);
+    let stroke = matches!(operator, "S" | "s" | "B" | "B*" | "b" | "b*");
+    let fill = matches!(operator, "f" | "F" | "f*" | "B" | "B*" | "b" | "b*");
+    if segments.is_empty() || !(stroke || fill) {
+        return;
+    }
+
+    let Some(bounds) = path_bounds(&segments) else {
+        return;
+    };
+    layout.paths.push(PathShape {
+        segments,
+        stroke: stroke.then_some(state.gs.stroke),
+        fill: fill.then_some(state.gs.fill),
+        line_width: state.gs.line_width,
+        bounds,
+        runs_before: layout.runs.len(),
+    });
+}
+
+/// Bounding box of every point in a path, control points included
+fn path_bounds(segments: &[Segment]) -> Option<Rect> {
+    let points = segments.iter().flat_map(|segment| match *segment {
+        Segment::MoveTo(x, y) | Segment::LineTo(x, y) => vec![(x, y)],
+        Segment::CurveTo(x1, y1, x2, y2, x3, y3) => vec![(x1, y1), (x2, y2), (x3, y3)],
+        Segment::Close => vec![],
+    });
+    points
+        .map(|(x, y)| Rect::from_corners(x, y, x, y))
+        .reduce(|a, b| a.union(&b))
+}
+
+/// Decode strings and kerning adjustments the same way lopdf's `extract_text`
+/// does, symbol fonts aside
+fn collect_text(text: &mut String, font: &PageFont, operands: &[Object]) {
+    for operand in operands {
+        match operand {
+            Object::String(bytes, _) => {
+                if let Some(decoded) = font.decode(bytes) {
+                    text.push_str(&decoded);
+                }
+            }
+            Object::Array(items) => collect_text(text, font, items),
+            Object::Integer(i) if *i < -100 => text.push(' '),
+            Object::Real(r) if *r < -100.0 => text.push(' '),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_subset_prefix() {
+        assert_eq!(strip_subset_prefix("ABCDEF+CMMI10"), "CMMI10");
+        assert_eq!(strip_subset_prefix("Helvetica"), "Helvetica");
+        assert_eq!(strip_subset_prefix("Foo+Bar"), "Foo+Bar");
+    }
+
+    #[test]
+    fn test_matrix_composition() {
+        let m = Matrix::translate(10.0, 20.0).then(&Matrix([2.0, 0.0, 0.0, 2.0, 0.0, 0.0]));
+        assert_eq!(m.apply(0.0, 0.0), (20.0, 40.0));
+        assert_eq!(m.scale_y(), 2.0);
+    }
+
+    #[test]
+    fn test_lines_group_runs_by_baseline() {
+        let run = |text: &str, y: f32| TextRun {
+            text: text.to_string(),
+            font: "Helvetica".to_string(),
+            size: 12.0,
+            x: 0.0,
+            y,
+            width: 6.0,
+            color: Color::BLACK,
+            mcid: None,
+        };
+        let layout = PageLayout {
+            runs: vec![run("a", 700.0), run("b", 701.0), run("c", 680.0)],
+            ..Default::default()
+        };
+        let lines = layout.lines();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].len(), 2);
+    }
+
+    #[test]
+    fn test_text_advance_uses_font_widths() {
+        let font = PageFont {
+            name: "Helvetica".to_string(),
+            encoding: None,
+            widths: Some((65, vec![600.0, 700.0])),
+            two_byte: false,
+            symbols: None,
+        };
+        let text = |bytes: &[u8]| Object::String(bytes.to_vec(), lopdf::StringFormat::Literal);
+        assert_eq!(text_advance(&font, &[text(b"AB")]), 1300.0);
+        // Codes outside the table fall back to half an em; kerning moves left
+        let kerned = Object::Array(vec![text(b"A"), Object::Integer(100), text(b"z")]);
+        assert_eq!(text_advance(&font, &[kerned]), 1000.0);
+    }
+
+    #[test]
+    fn test_glyphs() {
+        let text = |bytes: &[u8]| Object::String(bytes.to_vec(), lopdf::StringFormat::Literal);
+        let kerned = Object::Array(vec![text(b"Hello "), Object::Integer(-250), text(b"world")]);
+        assert_eq!(glyphs(false, &[kerned]), 10);
+        // The word and character spacing operands of `"` are no glyphs
+        let spaced = [
+            Object::Integer(2),
+            Object::Integer(0),
+            text(b"\0\x41\0\x20"),
+        ];
+        assert_eq!(glyphs(true, &spaced), 2);
+    }
+
+    #[test]
+    fn test_path_bounds_covers_all_points() {
+        let segments = [
+            Segment::MoveTo(10.0, 10.0),
+            Segment::LineTo(50.0, 20.0),
+            Segment::CurveTo(60.0, 5.0, 70.0, 30.0, 80.0, 10.0),
+            Segment::Close,
+        ];
+        assert_eq!(
+            path_bounds(&segments),
+            Some(Rect::from_corners(10.0, 5.0, 80.0, 30.0))
+        );
+        assert_eq!(path_bounds(&[Segment::Close]), None);
+    }
+}
@@ -1,14 +1,56 @@
+mod alt_text;
+mod caption;
+mod ccitt;
+mod compatibility;
+mod decoration;
+mod definition;
 mod document;
+mod drop_cap;
+mod footnote;
+mod formula;
+mod geometry;
+mod images;
+mod inventory;
+mod jpeg;
+mod layout;
+mod limits;
+mod math;
+mod mathml;
 mod metadata;
+mod outline;
+mod page_cache;
+mod page_tree;
+mod png;
+mod portfolio;
+mod ranged;
+mod raster;
+mod recovery;
+mod redaction;
+mod render;
+mod signature;
+mod stream_cache;
+mod symbol;
 mod text;
 mod types;
 mod validation;
+mod vector;
 
 #[cfg(test)]
 mod test_utils;
 
+pub use alt_text::AltTextProvider;
+pub use compatibility::is_linearized;
 pub use document::PdfDocument;
-pub use types::{ExtractedContent, PdfMetadata};
+pub use footnote::render_footnotes;
+pub use page_cache::PageCache;
+pub use ranged::RangeSource;
+pub use text::{TextCleaner, clean_extracted_text, clean_extracted_text_with};
+pub use types::{
+    AnnotationCounts, CleanupThresholds, EmbeddedDocument, ExtractOptions, ExtractTimings,
+    ExtractedContent, ExtractedImage, FontInfo, FootnoteStyle, ImageFormat, ImageInfo,
+    OutlineEntry, PageSelection, PdfCompatibility, PdfMetadata, Redactions, SignatureInfo,
+    UnderlineStyle,
+};
 pub use validation::validate_pdf;
 
 // Re-export error type for convenience
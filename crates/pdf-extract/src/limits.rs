@@ -0,0 +1,225 @@
+use crate::{PdfError, Result};
+use flate2::read::ZlibDecoder;
+use lopdf::{Document, Object, ObjectId, Stream};
+use std::io::{self, Read};
+
+/// Largest decompressed stream accepted, in bytes
+pub const MAX_STREAM_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Longest chain of indirect references followed to resolve one value
+pub const MAX_REFERENCE_DEPTH: usize = 32;
+
+/// Deepest nesting of arrays and dictionaries accepted in the file
+pub const MAX_NESTING: usize = 256;
+
+/// Follow indirect references to the object they point at.
+///
+/// Gives up after `MAX_REFERENCE_DEPTH` hops, so reference cycles end.
+pub fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+    let mut object = object;
+    for _ in 0..MAX_REFERENCE_DEPTH {
+        match object {
+            Object::Reference(id) => object = document.get_object(*id).ok()?,
+            other => return Some(other),
+        }
+    }
+    None
+}
+
+/// Inflate Flate-compressed data, refusing output larger than `MAX_STREAM_SIZE`
+pub fn inflate(data: &[u8]) -> Option<Vec<u8>> {
+    inflate_within(data, MAX_STREAM_SIZE)
+}
+
+fn inflate_within(data: &[u8], limit: u64) -> Option<Vec<u8>> {
+    let mut inflated = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit + 1)
+        .read_to_end(&mut inflated)
+        .ok()?;
+    (inflated.len() as u64 <= limit).then_some(inflated)
+}
+
+/// Check that a page's content streams decompress to at most `MAX_STREAM_SIZE`.
+///
+/// Content streams are decompressed by lopdf without a limit, so a crafted
+/// stream (a "zip bomb") is caught here first. The error says which stream
+/// is too large.
+pub fn check_page_content(
+    document: &Document,
+    page_id: ObjectId,
+) -> std::result::Result<(), String> {
+    for id in document.get_page_contents(page_id) {
+        let Ok(stream) = document.get_object(id).and_then(Object::as_stream) else {
+            continue;
+        };
+        if is_flate(stream) && exceeds(&stream.content, MAX_STREAM_SIZE) {
+            return Err(format!(
+                "content stream {} {} R decompresses to more than {} MB",
+                id.0,
+                id.1,
+                MAX_STREAM_SIZE / (1024 * 1024)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Decompressed data of a stream, or `None` when it exceeds `MAX_STREAM_SIZE`.
+///
+/// Streams in another encoding are decoded by lopdf, or kept as stored.
+pub fn decompressed_content(stream: &Stream) -> Option<Vec<u8>> {
+    if is_flate(stream) && !stream.dict.has(b"DecodeParms") {
+        return inflate(&stream.content);
+    }
+    if is_flate(stream) && exceeds(&stream.content, MAX_STREAM_SIZE) {
+        return None;
+    }
+    Some(
+        stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone()),
+    )
+}
+
+fn is_flate(stream: &Stream) -> bool {
+    match stream.dict.get(b"Filter") {
+        Ok(Object::Name(name)) => name == b"FlateDecode",
+        Ok(Object::Array(filters)) => filters
+            .first()
+            .and_then(|filter| filter.as_name().ok())
+            .is_some_and(|name| name == b"FlateDecode"),
+        _ => false,
+    }
+}
+
+/// Whether Flate data inflates to more than `limit` bytes, without keeping the output
+fn exceeds(data: &[u8], limit: u64) -> bool {
+    let mut decoder = ZlibDecoder::new(data).take(limit + 1);
+    io::copy(&mut decoder, &mut io::sink()).is_ok_and(|size| size > limit)
+}
+
+/// Reject files nesting arrays and dictionaries deeper than `MAX_NESTING`.
+///
+/// lopdf parses nested objects recursively, so a file of a few kilobytes of
+/// `[[[[...` would otherwise overflow the stack. Stream data, strings and
+/// comments are skipped; objects inside compressed object streams are not
+/// visible to this scan.
+pub fn check_nesting(bytes: &[u8]) -> Result<()> {
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                while i < bytes.len() && !matches!(bytes[i], b'\r' | b'\n') {
+                    i += 1;
+                }
+            }
+            b'(' => i = skip_literal_string(bytes, i),
+            b'<' if bytes.get(i + 1) == Some(&b'<') => {
+                depth += 1;
+                i += 1;
+            }
+            b'<' => {
+                // Hex string
+                while i < bytes.len() && bytes[i] != b'>' {
+                    i += 1;
+                }
+            }
+            b'[' => depth += 1,
+            b'>' if bytes.get(i + 1) == Some(&b'>') => {
+                depth = depth.saturating_sub(1);
+                i += 1;
+            }
+            b']' => depth = depth.saturating_sub(1),
+            b's' if bytes[i..].starts_with(b"stream")
+                && (i == 0 || !bytes[i - 1].is_ascii_alphabetic()) =>
+            {
+                i = find(&bytes[i..], b"endstream").map_or(bytes.len(), |end| i + end + 8);
+            }
+            _ => {}
+        }
+        if depth > MAX_NESTING {
+            return Err(PdfError::Processing(format!(
+                "arrays and dictionaries are nested more than {} levels deep",
+                MAX_NESTING
+            )));
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+/// Index of the `)` closing the literal string opened at `start`
+fn skip_literal_string(bytes: &[u8], start: usize) -> usize {
+    let mut open = 0usize;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 1,
+            b'(' => open += 1,
+            b')' => {
+                open -= 1;
+                if open == 0 {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    i
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn test_resolve_stops_at_reference_cycles() {
+        let mut document = Document::with_version("1.5");
+        document.objects.insert((1, 0), Object::Reference((2, 0)));
+        document.objects.insert((2, 0), Object::Reference((1, 0)));
+        document.objects.insert((3, 0), Object::Integer(7));
+        assert_eq!(resolve(&document, &Object::Reference((1, 0))), None);
+        assert_eq!(
+            resolve(&document, &Object::Reference((3, 0))),
+            Some(&Object::Integer(7))
+        );
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0u8; 4096]).unwrap();
+        let bomb = encoder.finish().unwrap();
+        assert_eq!(
+            inflate_within(&bomb, 4096).map(|data| data.len()),
+            Some(4096)
+        );
+        assert_eq!(inflate_within(&bomb, 1024), None);
+        assert!(exceeds(&bomb, 1024) && !exceeds(&bomb, 4096));
+    }
+
+    #[test]
+    fn test_check_nesting() {
+        let deep = format!("1 0 obj {}{} endobj", "[".repeat(300), "]".repeat(300));
+        assert!(check_nesting(deep.as_bytes()).is_err());
+
+        let shallow = b"1 0 obj << /A [1 (a [[[ string) <5B5B>] >> endobj % [[[[";
+        assert!(check_nesting(shallow).is_ok());
+        let mut streamed = b"2 0 obj << /Length 400 >> stream\n".to_vec();
+        streamed.extend_from_slice(&[b'['; 400]);
+        streamed.extend_from_slice(b"\nendstream endobj");
+        assert!(check_nesting(&streamed).is_ok());
+    }
+}
@@ -0,0 +1,396 @@
+use super::formula::Formula;
+use super::layout::{PageLayout, TextRun};
+use super::render::standalone;
+use std::collections::HashMap;
+
+/// Font name fragments used by TeX and OpenType math fonts
+const MATH_FONT_PREFIXES: [&str; 9] = [
+    "CMMI", "CMSY", "CMEX", "CMBSY", "MSAM", "MSBM", "EUFM", "EUSM", "RSFS",
+];
+
+/// Check whether a base font name belongs to a math font
+pub fn is_math_font(font: &str) -> bool {
+    MATH_FONT_PREFIXES
+        .iter()
+        .any(|prefix| font.starts_with(prefix))
+        || font.contains("Math")
+}
+
+/// Check whether a page uses any math fonts or has formulas tagged
+pub fn has_math(layout: &PageLayout) -> bool {
+    layout.runs.iter().any(|run| is_math_font(&run.font)) || !layout.formulas.is_empty()
+}
+
+/// Markdown of the formulas a tagged PDF marks on a page, written in place
+/// of the first of their runs
+#[derive(Debug, Default)]
+pub struct TaggedMath {
+    /// MCID -> index of the formula its content belongs to
+    formulas: HashMap<i64, usize>,
+    markdown: Vec<Option<String>>,
+}
+
+impl TaggedMath {
+    /// Write each formula from the LaTeX of its MathML, or else from the
+    /// text of its runs. A formula is displayed when its MathML says so or
+    /// when nothing else shares its lines.
+    pub fn new(formulas: &[Formula], lines: &[Vec<&TextRun>], mark_lost: bool) -> Self {
+        let formula_of: HashMap<i64, usize> = formulas
+            .iter()
+            .enumerate()
+            .flat_map(|(i, formula)| formula.mcids.iter().map(move |&mcid| (mcid, i)))
+            .collect();
+        let of = |run: &TextRun| run.mcid.and_then(|mcid| formula_of.get(&mcid).copied());
+
+        let mut text = vec![String::new(); formulas.len()];
+        let mut shares_lines = vec![false; formulas.len()];
+        for line in lines {
+            for run in line {
+                if let Some(i) = of(run) {
+                    text[i].push_str(&run.text);
+                    shares_lines[i] |= line.iter().any(|other| of(other) != Some(i));
+                }
+            }
+        }
+
+        let markdown = formulas
+            .iter()
+            .zip(text.iter().zip(shares_lines))
+            .map(|(formula, (text, shares_lines))| {
+                let body = text.trim();
+                if body.is_empty() {
+                    return None;
+                }
+                let (latex, display) = match &formula.mathml {
+                    Some((latex, display)) => (latex.clone(), *display || !shares_lines),
+                    None if !shares_lines && mark_lost && is_unrecoverable(body) => {
+                        return Some(standalone("<!-- equation could not be extracted -->"));
+                    }
+                    None => (to_latex(body), !shares_lines),
+                };
+                Some(if display {
+                    standalone(&format!("$${}$$", latex))
+                } else {
+                    let before = if text.starts_with(char::is_whitespace) {
+                        " "
+                    } else {
+                        ""
+                    };
+                    let after = if text.ends_with(char::is_whitespace) {
+                        " "
+                    } else {
+                        ""
+                    };
+                    format!("{}${}${}", before, latex, after)
+                })
+            })
+            .collect();
+        Self {
+            formulas: formula_of,
+            markdown,
+        }
+    }
+
+    /// Markdown of the formula a run belongs to: all of it for the first of
+    /// its runs, nothing for the rest
+    fn take(&mut self, run: &TextRun) -> Option<String> {
+        let formula = *self.formulas.get(&run.mcid?)?;
+        Some(self.markdown[formula].take().unwrap_or_default())
+    }
+}
+
+/// Render a single visual line, writing tagged formulas as `tagged` has
+/// them, wrapping other math-font runs as inline `$...$` and lines made up
+/// entirely of math as display `$$...$$` blocks.
+///
+/// With `mark_lost`, a display equation whose glyphs could not be decoded is
+/// replaced by a comment saying so, rather than written as garbled text.
+pub fn render_line(line: &[&TextRun], tagged: &mut TaggedMath, mark_lost: bool) -> String {
+    let has_formula = line.iter().any(|run| {
+        run.mcid
+            .is_some_and(|mcid| tagged.formulas.contains_key(&mcid))
+    });
+    if !has_formula && is_display_math(line) {
+        let body: String = line.iter().map(|run| run.text.as_str()).collect();
+        if mark_lost && is_unrecoverable(&body) {
+            return standalone("<!-- equation could not be extracted -->");
+        }
+        return standalone(&format!("$${}$$", to_latex(body.trim())));
+    }
+
+    let mut out = String::new();
+    let mut math = String::new();
+    for run in line {
+        if let Some(formula) = tagged.take(run) {
+            flush_inline_math(&mut out, &mut math);
+            out.push_str(&formula);
+        } else if is_math_font(&run.font) {
+            math.push_str(&run.text);
+        } else {
+            flush_inline_math(&mut out, &mut math);
+            out.push_str(&run.text);
+        }
+    }
+    flush_inline_math(&mut out, &mut math);
+    out
+}
+
+/// Text containing replacement, control or private-use characters came from
+/// glyphs without a usable Unicode mapping
+fn is_unrecoverable(text: &str) -> bool {
+    text.chars().any(|c| {
+        c == '\u{FFFD}'
+            || (c.is_control() && !c.is_whitespace())
+            || ('\u{E000}'..='\u{F8FF}').contains(&c)
+    })
+}
+
+/// A line is display math when every run is in a math font and it has some substance
+fn is_display_math(line: &[&TextRun]) -> bool {
+    let all_math = line.iter().all(|run| is_math_font(&run.font));
+    let glyphs: usize = line
+        .iter()
+        .map(|run| run.text.chars().filter(|c| !c.is_whitespace()).count())
+        .sum();
+    all_math && glyphs >= 3
+}
+
+/// Append pending math as `$...$`, keeping surrounding whitespace outside the delimiters
+fn flush_inline_math(out: &mut String, math: &mut String) {
+    let trimmed = math.trim();
+    if !trimmed.is_empty() {
+        if math.starts_with(char::is_whitespace) {
+            out.push(' ');
+        }
+        out.push('$');
+        out.push_str(&to_latex(trimmed));
+        out.push('$');
+        if math.ends_with(char::is_whitespace) {
+            out.push(' ');
+        }
+    }
+    math.clear();
+}
+
+/// Translate Unicode math symbols to LaTeX commands
+pub fn to_latex(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut after_command = false;
+    for ch in text.chars() {
+        if let Some(command) = latex_command(ch) {
+            out.push_str(command);
+            after_command = command.starts_with('\\') && !command.ends_with('}');
+            continue;
+        }
+        if after_command && ch.is_ascii_alphabetic() {
+            out.push(' ');
+        }
+        after_command = false;
+        match ch {
+            '$' => out.push_str("\\$"),
+            _ => out.push(ch),
+        }
+    }
+    out
+}
+
+/// LaTeX spelling of a single Unicode math character
+fn latex_command(ch: char) -> Option<&'static str> {
+    let command = match ch {
+        'α' => "\\alpha",
+        'β' => "\\beta",
+        'γ' => "\\gamma",
+        'δ' => "\\delta",
+        'ε' | 'ϵ' => "\\epsilon",
+        'ζ' => "\\zeta",
+        'η' => "\\eta",
+        'θ' => "\\theta",
+        'ι' => "\\iota",
+        'κ' => "\\kappa",
+        'λ' => "\\lambda",
+        'μ' => "\\mu",
+        'ν' => "\\nu",
+        'ξ' => "\\xi",
+        'π' => "\\pi",
+        'ρ' => "\\rho",
+        'σ' => "\\sigma",
+        'τ' => "\\tau",
+        'υ' => "\\upsilon",
+        'φ' | 'ϕ' => "\\phi",
+        'χ' => "\\chi",
+        'ψ' => "\\psi",
+        'ω' => "\\omega",
+        'Γ' => "\\Gamma",
+        'Δ' => "\\Delta",
+        'Θ' => "\\Theta",
+        'Λ' => "\\Lambda",
+        'Ξ' => "\\Xi",
+        'Π' => "\\Pi",
+        'Σ' => "\\Sigma",
+        'Φ' => "\\Phi",
+        'Ψ' => "\\Psi",
+        'Ω' => "\\Omega",
+        '≤' => "\\le",
+        '≥' => "\\ge",
+        '≠' => "\\neq",
+        '≈' => "\\approx",
+        '≡' => "\\equiv",
+        '∼' => "\\sim",
+        '±' => "\\pm",
+        '∓' => "\\mp",
+        '×' => "\\times",
+        '÷' => "\\div",
+        '·' | '⋅' => "\\cdot",
+        '∞' => "\\infty",
+        '∑' => "\\sum",
+        '∏' => "\\prod",
+        '∫' => "\\int",
+        '∮' => "\\oint",
+        '√' => "\\sqrt",
+        '∂' => "\\partial",
+        '∇' => "\\nabla",
+        '→' => "\\to",
+        '←' => "\\leftarrow",
+        '↔' => "\\leftrightarrow",
+        '⇒' => "\\Rightarrow",
+        '⇐' => "\\Leftarrow",
+        '⇔' => "\\Leftrightarrow",
+        '↦' => "\\mapsto",
+        '∈' => "\\in",
+        '∉' => "\\notin",
+        '∋' => "\\ni",
+        '⊂' => "\\subset",
+        '⊆' => "\\subseteq",
+        '⊃' => "\\supset",
+        '⊇' => "\\supseteq",
+        '∪' => "\\cup",
+        '∩' => "\\cap",
+        '∅' => "\\emptyset",
+        '∀' => "\\forall",
+        '∃' => "\\exists",
+        '¬' => "\\neg",
+        '∧' => "\\wedge",
+        '∨' => "\\vee",
+        '⊕' => "\\oplus",
+        '⊗' => "\\otimes",
+        '∘' => "\\circ",
+        '⟨' => "\\langle",
+        '⟩' => "\\rangle",
+        '′' => "'",
+        '−' => "-",
+        '∗' => "*",
+        'ℝ' => "\\mathbb{R}",
+        'ℕ' => "\\mathbb{N}",
+        'ℤ' => "\\mathbb{Z}",
+        'ℚ' => "\\mathbb{Q}",
+        'ℂ' => "\\mathbb{C}",
+        _ => return None,
+    };
+    Some(command)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Color;
+
+    fn run(text: &str, font: &str, x: f32, y: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: font.to_string(),
+            size: 10.0,
+            x,
+            y,
+            width: 5.0 * text.len() as f32,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    #[test]
+    fn test_is_math_font() {
+        assert!(is_math_font("CMMI10"));
+        assert!(is_math_font("CMSY7"));
+        assert!(is_math_font("CambriaMath"));
+        assert!(!is_math_font("CMR10"));
+        assert!(!is_math_font("Helvetica"));
+    }
+
+    #[test]
+    fn test_to_latex_maps_symbols() {
+        assert_eq!(to_latex("α ≤ β"), "\\alpha \\le \\beta");
+        assert_eq!(to_latex("πr"), "\\pi r");
+        assert_eq!(to_latex("x ∈ ℝ"), "x \\in \\mathbb{R}");
+        assert_eq!(to_latex("$5"), "\\$5");
+    }
+
+    #[test]
+    fn test_render_line_inline_and_display() {
+        let inline = [
+            run("Let ", "CMR10", 0.0, 700.0),
+            run("x", "CMMI10", 20.0, 700.0),
+            run(" be real.", "CMR10", 25.0, 700.0),
+        ];
+        let refs: Vec<&TextRun> = inline.iter().collect();
+        assert_eq!(
+            render_line(&refs, &mut TaggedMath::default(), false),
+            "Let $x$ be real."
+        );
+
+        let display = [run("E = mc", "CMMI10", 0.0, 680.0)];
+        let refs: Vec<&TextRun> = display.iter().collect();
+        assert_eq!(
+            render_line(&refs, &mut TaggedMath::default(), false).trim(),
+            "$$E = mc$$"
+        );
+    }
+
+    #[test]
+    fn test_render_line_tagged_formulas() {
+        let formulas = [
+            Formula {
+                mcids: vec![1],
+                mathml: Some(("\\pi r^{2}".to_string(), false)),
+            },
+            Formula {
+                mcids: vec![3, 4],
+                mathml: None,
+            },
+        ];
+        let tagged = |text: &str, mcid: i64, y: f32| TextRun {
+            mcid: Some(mcid),
+            ..run(text, "Helvetica", 0.0, y)
+        };
+        let page = [
+            tagged("Area ", 0, 700.0),
+            tagged("πr2", 1, 700.0),
+            tagged(" here.", 2, 700.0),
+            tagged("E = ", 3, 680.0),
+            tagged("mc2", 4, 680.0),
+        ];
+        let lines = [
+            page[..3].iter().collect::<Vec<_>>(),
+            page[3..].iter().collect(),
+        ];
+        let mut math = TaggedMath::new(&formulas, &lines, false);
+        assert_eq!(
+            render_line(&lines[0], &mut math, false),
+            "Area $\\pi r^{2}$ here."
+        );
+        assert_eq!(
+            render_line(&lines[1], &mut math, false).trim(),
+            "$$E = mc2$$"
+        );
+    }
+
+    #[test]
+    fn test_render_line_marks_lost_equation() {
+        let display = [run("\u{F0B1}\u{F0B2}\u{F0B3}", "CMEX10", 0.0, 680.0)];
+        let refs: Vec<&TextRun> = display.iter().collect();
+        assert!(
+            render_line(&refs, &mut TaggedMath::default(), true)
+                .contains("equation could not be extracted")
+        );
+        assert!(render_line(&refs, &mut TaggedMath::default(), false).contains("$$\u{F0B1}"));
+    }
+}
@@ -0,0 +1,472 @@
+//! MathML to LaTeX, for the MathML a tagged PDF associates with its
+//! formulas.
+//!
+//! Presentation MathML is translated element by element; a `semantics`
+//! element with a TeX annotation gives that annotation as is. Content
+//! MathML has no layout of its own and comes out as the text it holds.
+
+use super::limits::MAX_NESTING;
+use super::math;
+
+/// A node of a MathML tree
+#[derive(Debug)]
+enum Node {
+    Element(Element),
+    Text(String),
+}
+
+/// An element, named without its namespace prefix
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    /// The text of the element and its descendants, trimmed
+    fn text(&self) -> String {
+        fn collect(element: &Element, out: &mut String) {
+            for node in &element.children {
+                match node {
+                    Node::Element(child) => collect(child, out),
+                    Node::Text(text) => out.push_str(text),
+                }
+            }
+        }
+        let mut text = String::new();
+        collect(self, &mut text);
+        text.trim().to_string()
+    }
+
+    /// The first `math` element of the tree, searched breadth first
+    fn find_math(&self) -> Option<&Element> {
+        let mut level = vec![self];
+        while !level.is_empty() {
+            if let Some(math) = level.iter().find(|element| element.name == "math") {
+                return Some(math);
+            }
+            level = level
+                .iter()
+                .flat_map(|element| element.elements())
+                .collect();
+        }
+        None
+    }
+}
+
+/// The LaTeX of the first `math` element of a MathML document, and whether
+/// it is displayed (`display="block"`) rather than inline
+pub fn to_latex(mathml: &str) -> Option<(String, bool)> {
+    let document = parse(mathml)?;
+    let math = document.find_math()?;
+    let display =
+        math.attribute("display") == Some("block") || math.attribute("mode") == Some("display");
+    let latex = latex(math).trim().to_string();
+    (!latex.is_empty()).then_some((latex, display))
+}
+
+/// Parse XML into a tree under an unnamed root, skipping the prolog,
+/// comments and processing instructions. Unclosed elements are closed at
+/// the end; `None` when elements nest deeper than `MAX_NESTING`.
+fn parse(xml: &str) -> Option<Element> {
+    let mut stack = vec![Element::default()];
+    let mut rest = xml;
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.split_once("-->").map_or("", |(_, rest)| rest);
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let (text, after) = after.split_once("]]>").unwrap_or((after, ""));
+            stack
+                .last_mut()?
+                .children
+                .push(Node::Text(text.to_string()));
+            rest = after;
+        } else if rest.starts_with("<?") || rest.starts_with("<!") {
+            rest = rest.split_once('>').map_or("", |(_, rest)| rest);
+        } else if let Some(after) = rest.strip_prefix("</") {
+            rest = after.split_once('>').map_or("", |(_, rest)| rest);
+            if stack.len() > 1 {
+                let element = stack.pop()?;
+                stack.last_mut()?.children.push(Node::Element(element));
+            }
+        } else if let Some(after) = rest.strip_prefix('<') {
+            let end = tag_end(after)?;
+            let tag = &after[..end];
+            rest = &after[end + 1..];
+            match tag.strip_suffix('/') {
+                Some(tag) => stack
+                    .last_mut()?
+                    .children
+                    .push(Node::Element(start_tag(tag))),
+                None if stack.len() > MAX_NESTING => return None,
+                None => stack.push(start_tag(tag)),
+            }
+        } else {
+            let end = rest.find('<').unwrap_or(rest.len());
+            stack
+                .last_mut()?
+                .children
+                .push(Node::Text(unescape(&rest[..end])));
+            rest = &rest[end..];
+        }
+    }
+    while stack.len() > 1 {
+        let element = stack.pop()?;
+        stack.last_mut()?.children.push(Node::Element(element));
+    }
+    stack.pop()
+}
+
+/// Position of the `>` closing a tag, outside quoted attribute values
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '>') => return Some(i),
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The element a start tag opens, with its attributes
+fn start_tag(tag: &str) -> Element {
+    let local = |name: &str| name.rsplit(':').next().unwrap_or(name).to_string();
+    let (name, mut rest) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+    let mut attributes = Vec::new();
+    while let Some((key, after)) = rest.split_once('=') {
+        let after = after.trim_start();
+        let Some(quote) = after.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            break;
+        };
+        let Some((value, after)) = after[1..].split_once(quote) else {
+            break;
+        };
+        attributes.push((local(key.trim()), unescape(value)));
+        rest = after;
+    }
+    Element {
+        name: local(name.trim()),
+        attributes,
+        children: Vec::new(),
+    }
+}
+
+/// Replace character and entity references; unknown entities are kept
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let decoded = after.split_once(';').and_then(|(name, after)| {
+            let c = match name {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                "nbsp" => ' ',
+                "InvisibleTimes" | "it" => '\u{2062}',
+                "ApplyFunction" | "af" => '\u{2061}',
+                _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    Some(hex) => char::from_u32(u32::from_str_radix(hex, 16).ok()?)?,
+                    None => char::from_u32(name.strip_prefix('#')?.parse().ok()?)?,
+                },
+            };
+            Some((c, after))
+        });
+        match decoded {
+            Some((c, after)) => {
+                out.push(c);
+                rest = after;
+            }
+            None => {
+                out.push('&');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Function names LaTeX has commands for
+const FUNCTIONS: [&str; 20] = [
+    "sin", "cos", "tan", "cot", "sec", "csc", "sinh", "cosh", "tanh", "arcsin", "arccos", "arctan",
+    "log", "ln", "exp", "lim", "max", "min", "det", "gcd",
+];
+
+/// Operators whose limits go below and above them
+const LARGE_OPERATORS: [&str; 8] = [
+    "\\sum", "\\prod", "\\int", "\\oint", "\\lim", "\\max", "\\min", "\\bigcup",
+];
+
+/// LaTeX of one element
+fn latex(element: &Element) -> String {
+    let children: Vec<&Element> = element.elements().collect();
+    let arg = |i: usize| {
+        children
+            .get(i)
+            .map(|child| latex(child))
+            .unwrap_or_default()
+    };
+    match element.name.as_str() {
+        "mi" => identifier(&element.text()),
+        "mn" => element.text(),
+        "mo" => operator(&element.text()),
+        "mtext" | "ms" => {
+            let text = element.text();
+            if text.is_empty() {
+                " ".to_string()
+            } else {
+                format!("\\text{{{}}}", escape_text(&text))
+            }
+        }
+        "mspace" => " ".to_string(),
+        "msup" => format!("{}^{}", base(arg(0)), group(&arg(1))),
+        "msub" => format!("{}_{}", base(arg(0)), group(&arg(1))),
+        "msubsup" => format!("{}_{}^{}", base(arg(0)), group(&arg(1)), group(&arg(2))),
+        "mfrac" => format!("\\frac{{{}}}{{{}}}", arg(0), arg(1)),
+        "msqrt" => format!("\\sqrt{{{}}}", row(&children)),
+        "mroot" => format!("\\sqrt[{}]{{{}}}", arg(1), arg(0)),
+        "mover" => over(arg(0), &arg(1)),
+        "munder" => under(arg(0), &arg(1)),
+        "munderover" => {
+            let base = arg(0);
+            if LARGE_OPERATORS.contains(&base.as_str()) {
+                format!("{}_{}^{}", base, group(&arg(1)), group(&arg(2)))
+            } else {
+                format!(
+                    "\\overset{{{}}}{{\\underset{{{}}}{{{}}}}}",
+                    arg(2),
+                    arg(1),
+                    base
+                )
+            }
+        }
+        "mfenced" => {
+            let open = element.attribute("open").unwrap_or("(");
+            let close = element.attribute("close").unwrap_or(")");
+            let separator = element.attribute("separators").unwrap_or(",").trim();
+            let separator = separator
+                .chars()
+                .next()
+                .map(String::from)
+                .unwrap_or_default();
+            let items: Vec<String> = children.iter().map(|child| latex(child)).collect();
+            format!(
+                "{}{}{}",
+                operator(open),
+                items.join(&separator),
+                operator(close)
+            )
+        }
+        "mtable" => {
+            let rows: Vec<String> = children.iter().map(|row| latex(row)).collect();
+            format!("\\begin{{matrix}}{}\\end{{matrix}}", rows.join(" \\\\ "))
+        }
+        "mtr" | "mlabeledtr" => {
+            // The first cell of a labeled row is its equation number
+            let skip = usize::from(element.name == "mlabeledtr");
+            let cells: Vec<String> = children.iter().skip(skip).map(|c| latex(c)).collect();
+            cells.join(" & ")
+        }
+        "semantics" => children
+            .iter()
+            .find(|child| {
+                child.name == "annotation"
+                    && child.attribute("encoding").is_some_and(|encoding| {
+                        matches!(
+                            encoding,
+                            "application/x-tex" | "application/x-latex" | "TeX" | "LaTeX"
+                        )
+                    })
+            })
+            .map(|tex| tex.text())
+            .unwrap_or_else(|| arg(0)),
+        "annotation" | "annotation-xml" | "none" | "mprescripts" | "mphantom" => String::new(),
+        _ => row(&children),
+    }
+}
+
+/// LaTeX of elements written one after the other
+fn row(children: &[&Element]) -> String {
+    let mut out = String::new();
+    for child in children {
+        let piece = latex(child);
+        // A command name would run into a letter after it
+        if ends_with_command(&out) && piece.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            out.push(' ');
+        }
+        out.push_str(&piece);
+    }
+    out
+}
+
+fn ends_with_command(latex: &str) -> bool {
+    let rest = latex.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    rest.len() < latex.len() && rest.ends_with('\\')
+}
+
+fn identifier(text: &str) -> String {
+    if text.chars().count() == 1 {
+        math::to_latex(text)
+    } else if FUNCTIONS.contains(&text) {
+        format!("\\{}", text)
+    } else {
+        format!("\\mathrm{{{}}}", text)
+    }
+}
+
+fn operator(text: &str) -> String {
+    match text {
+        "\u{2061}" | "\u{2062}" | "\u{2063}" | "\u{2064}" => String::new(),
+        "{" => "\\{".to_string(),
+        "}" => "\\}".to_string(),
+        "&" => "\\&".to_string(),
+        "%" => "\\%".to_string(),
+        "#" => "\\#".to_string(),
+        _ if FUNCTIONS.contains(&text) => format!("\\{}", text),
+        _ => math::to_latex(text),
+    }
+}
+
+/// A base of a script; an empty one still takes the script
+fn base(latex: String) -> String {
+    if latex.is_empty() {
+        "{}".to_string()
+    } else {
+        latex
+    }
+}
+
+/// A script, braced so that it stays one argument
+fn group(latex: &str) -> String {
+    format!("{{{}}}", latex)
+}
+
+fn over(base: String, over: &str) -> String {
+    let accent = match over {
+        "^" | "\u{2c6}" => "\\hat",
+        "\u{af}" | "\u{203e}" | "\u{332}" | "-" => "\\overline",
+        "\\to" | "\u{20d7}" => "\\vec",
+        "~" | "\u{2dc}" | "\\sim" => "\\tilde",
+        "\u{2d9}" | "." => "\\dot",
+        "\u{a8}" | ".." => "\\ddot",
+        "\u{23de}" => "\\overbrace",
+        _ if LARGE_OPERATORS.contains(&base.as_str()) => {
+            return format!("{}^{}", base, group(over));
+        }
+        _ => return format!("\\overset{{{}}}{{{}}}", over, base),
+    };
+    format!("{}{{{}}}", accent, base)
+}
+
+fn under(base: String, under: &str) -> String {
+    let accent = match under {
+        "_" | "\u{332}" => "\\underline",
+        "\u{23df}" => "\\underbrace",
+        _ if LARGE_OPERATORS.contains(&base.as_str()) => {
+            return format!("{}_{}", base, group(under));
+        }
+        _ => return format!("\\underset{{{}}}{{{}}}", under, base),
+    };
+    format!("{}{{{}}}", accent, base)
+}
+
+/// Escape the characters `\text{...}` gives a meaning to
+fn escape_text(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => out.push_str("\\textbackslash{}"),
+            '{' | '}' | '$' | '&' | '#' | '%' | '_' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn latex_of(body: &str) -> String {
+        to_latex(&format!(
+            "<math xmlns=\"http://www.w3.org/1998/Math/MathML\">{}</math>",
+            body
+        ))
+        .unwrap()
+        .0
+    }
+
+    #[test]
+    fn test_to_latex_presentation_markup() {
+        assert_eq!(
+            latex_of("<mi>A</mi><mo>=</mo><mi>&#x3C0;</mi><msup><mi>r</mi><mn>2</mn></msup>"),
+            "A=\\pi r^{2}"
+        );
+        assert_eq!(
+            latex_of(
+                "<mfrac><mrow><mo>-</mo><mi>b</mi><mo>&#xB1;</mo>\
+                 <msqrt><msup><mi>b</mi><mn>2</mn></msup><mo>-</mo><mn>4</mn><mi>a</mi><mi>c</mi></msqrt>\
+                 </mrow><mrow><mn>2</mn><mi>a</mi></mrow></mfrac>"
+            ),
+            "\\frac{-b\\pm\\sqrt{b^{2}-4ac}}{2a}"
+        );
+        assert_eq!(
+            latex_of(
+                "<munderover><mo>&#x2211;</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover>\
+                 <msub><mi>x</mi><mi>i</mi></msub>"
+            ),
+            "\\sum_{i=1}^{n}x_{i}"
+        );
+        assert_eq!(
+            latex_of("<mi>sin</mi><mo>&ApplyFunction;</mo><mi>x</mi>"),
+            "\\sin x"
+        );
+        assert_eq!(
+            latex_of("<mover><mi>v</mi><mo>&#x2192;</mo></mover><mtext>if $x</mtext>"),
+            "\\vec{v}\\text{if \\$x}"
+        );
+    }
+
+    #[test]
+    fn test_to_latex_prefers_tex_annotation() {
+        let mathml = "<?xml version=\"1.0\"?><!-- formula 1 -->\
+            <m:math display=\"block\" xmlns:m=\"http://www.w3.org/1998/Math/MathML\">\
+            <m:semantics><m:mi>x</m:mi>\
+            <m:annotation encoding=\"application/x-tex\">x^{2} + 1</m:annotation>\
+            </m:semantics></m:math>";
+        assert_eq!(to_latex(mathml), Some(("x^{2} + 1".to_string(), true)));
+    }
+
+    #[test]
+    fn test_to_latex_rejects_what_is_not_mathml() {
+        assert_eq!(to_latex("plain text"), None);
+        assert_eq!(to_latex("<math></math>"), None);
+        let deep = "<mrow>".repeat(MAX_NESTING + 1);
+        assert_eq!(to_latex(&format!("<math>{}</math>", deep)), None);
+    }
+}
@@ -1,7 +1,8 @@
 use super::types::PdfMetadata;
+use super::{limits, redaction, text};
 use crate::Result;
 use log::info;
-use lopdf::Document;
+use lopdf::{Dictionary, Document};
 
 /// Extract metadata and structure for preview (dry-run mode)
 pub fn extract_metadata(document: &Document) -> Result<PdfMetadata> {
@@ -9,50 +10,122 @@ pub fn extract_metadata(document: &Document) -> Result<PdfMetadata> {
 
     let page_count = document.get_pages().len();
 
-    // Try to extract metadata from document info dictionary
-    let mut title = None;
-    let mut author = None;
-
-    if let Ok(info) = document.trailer.get(b"Info")
-        && let Ok(info_dict) = info.as_dict()
-    {
-        // Try to get title
-        if let Ok(title_obj) = info_dict.get(b"Title")
-            && let Ok(title_str) = title_obj.as_str()
-        {
-            title = Some(String::from_utf8_lossy(title_str).to_string());
-        }
-
-        // Try to get author
-        if let Ok(author_obj) = info_dict.get(b"Author")
-            && let Ok(author_str) = author_obj.as_str()
-        {
-            author = Some(String::from_utf8_lossy(author_str).to_string());
-        }
-    }
+    // The Info entry is normally an indirect reference
+    let info = document
+        .trailer
+        .get(b"Info")
+        .ok()
+        .and_then(|info| limits::resolve(document, info))
+        .and_then(|info| info.as_dict().ok());
+    let field = |key: &[u8]| info.and_then(|info| info_string(document, info, key));
 
     // Check if document has extractable text
-    let has_text = matches!(document.extract_text(&[1]), Ok(text) if !text.is_empty());
+    let has_text = page_text(document, 1).is_some_and(|text| !text.is_empty());
 
     // Try to detect sections by looking for large text or headings
     let sections = detect_sections(document);
 
+    let catalog = document.catalog().ok();
+    let xmp = catalog.and_then(|catalog| xmp(document, catalog));
+    let property = |name: &str| xmp.as_deref().and_then(|xmp| xmp_property(xmp, name));
+
     Ok(PdfMetadata {
         page_count,
-        title,
-        author,
+        title: field(b"Title"),
+        author: field(b"Author"),
+        subject: field(b"Subject"),
+        keywords: field(b"Keywords"),
+        creator: field(b"Creator"),
+        producer: field(b"Producer"),
+        creation_date: field(b"CreationDate").and_then(|date| parse_pdf_date(&date)),
+        modification_date: field(b"ModDate").and_then(|date| parse_pdf_date(&date)),
         has_text,
         sections,
+        pdfa: property("pdfaid:part").map(|part| {
+            let level = property("pdfaid:conformance").unwrap_or_default();
+            format!("PDF/A-{}{}", part, level.to_lowercase())
+        }),
+        pdfua: property("pdfuaid:part").map(|part| format!("PDF/UA-{}", part)),
+        tagged: catalog.is_some_and(|catalog| is_tagged(document, catalog)),
+        signatures: Vec::new(),
     })
 }
 
+/// The XMP packet of the catalog's `Metadata` stream
+fn xmp(document: &Document, catalog: &Dictionary) -> Option<String> {
+    let stream = limits::resolve(document, catalog.get(b"Metadata").ok()?)?
+        .as_stream()
+        .ok()?;
+    let data = limits::decompressed_content(stream)?;
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// A simple property of an XMP packet, written as an attribute
+/// (`pdfaid:part="2"`) or an element (`<pdfaid:part>2</pdfaid:part>`).
+/// The prefixes the PDF/A and PDF/UA standards use are looked for; files
+/// binding their namespaces to others are rare enough to go unreported.
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let value = xmp.match_indices(name).find_map(|(start, _)| {
+        let before = xmp[..start].chars().next_back()?;
+        let after = &xmp[start + name.len()..];
+        if before == '<' {
+            let value = after.strip_prefix('>')?;
+            return Some(&value[..value.find('<')?]);
+        }
+        if !before.is_whitespace() {
+            return None;
+        }
+        let after = after.trim_start().strip_prefix('=')?.trim_start();
+        let quote = after.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &after[1..];
+        Some(&value[..value.find(quote)?])
+    })?;
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// Whether the catalog marks the document as tagged and has a structure tree
+fn is_tagged(document: &Document, catalog: &Dictionary) -> bool {
+    let marked = catalog
+        .get(b"MarkInfo")
+        .ok()
+        .and_then(|info| limits::resolve(document, info))
+        .and_then(|info| info.as_dict().ok())
+        .and_then(|info| info.get(b"Marked").ok())
+        .and_then(|marked| marked.as_bool().ok())
+        .unwrap_or(false);
+    marked && catalog.has(b"StructTreeRoot")
+}
+
+/// A text string of the document info dictionary, decoded from PDFDocEncoding
+/// or UTF-16; empty values count as missing
+fn info_string(document: &Document, info: &Dictionary, key: &[u8]) -> Option<String> {
+    let value = limits::resolve(document, info.get(key).ok()?)?;
+    let text = lopdf::decode_text_string(value)
+        .ok()
+        .or_else(|| Some(String::from_utf8_lossy(value.as_str().ok()?).to_string()))?;
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Plain text of a page, skipping pages whose content streams exceed the size
+/// limit, and without the text a redaction hides
+fn page_text(document: &Document, page_num: u32) -> Option<String> {
+    let page_id = *document.get_pages().get(&page_num)?;
+    limits::check_page_content(document, page_id).ok()?;
+    if redaction::may_hide(document, page_id) {
+        return text::extract_page(document, page_num).ok();
+    }
+    document.extract_text(&[page_num]).ok()
+}
+
 /// Detect sections in PDF by analyzing text content
 pub fn detect_sections(document: &Document) -> Vec<String> {
     let mut sections = Vec::new();
 
     // Try to extract text from first few pages to detect headings
     for page_num in 1..=3.min(document.get_pages().len() as u32) {
-        if let Ok(text) = document.extract_text(&[page_num]) {
+        if let Some(text) = page_text(document, page_num) {
             // Look for lines that might be headings (short lines, potentially capitalized)
             for line in text.lines() {
                 let trimmed = line.trim();
@@ -75,3 +148,144 @@ pub fn detect_sections(document: &Document) -> Vec<String> {
 
     sections
 }
+
+/// Convert a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`) to RFC 3339.
+///
+/// Every field after the year is optional; missing ones default to the
+/// start of the period and a missing offset to UTC.
+pub fn parse_pdf_date(date: &str) -> Option<String> {
+    let date = date.trim();
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let digits: String = date.chars().take_while(char::is_ascii_digit).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let field = |start: usize, default: u32| {
+        digits
+            .get(start..start + 2)
+            .and_then(|d| d.parse::<u32>().ok())
+            .unwrap_or(default)
+    };
+    let year: u32 = digits[..4].parse().ok()?;
+    let (month, day) = (field(4, 1), field(6, 1));
+    let (hour, minute, second) = (field(8, 0), field(10, 0), field(12, 0));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    let rest = &date[digits.len()..];
+    let offset = match rest.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let numbers: Vec<u32> = rest[1..]
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|part| !part.is_empty())
+                .filter_map(|part| part.parse().ok())
+                .collect();
+            let hours = numbers.first().copied().unwrap_or(0);
+            let minutes = numbers.get(1).copied().unwrap_or(0);
+            format!("{}{:02}:{:02}", sign, hours, minutes)
+        }
+        _ => "+00:00".to_string(),
+    };
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second.min(59),
+        offset
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pdf_date() {
+        assert_eq!(
+            parse_pdf_date("D:20240501103045+02'00'").as_deref(),
+            Some("2024-05-01T10:30:45+02:00")
+        );
+        assert_eq!(
+            parse_pdf_date("D:20231224").as_deref(),
+            Some("2023-12-24T00:00:00+00:00")
+        );
+        assert_eq!(
+            parse_pdf_date("20200101120000Z").as_deref(),
+            Some("2020-01-01T12:00:00+00:00")
+        );
+        assert_eq!(
+            parse_pdf_date("D:2024").as_deref(),
+            Some("2024-01-01T00:00:00+00:00")
+        );
+        assert_eq!(parse_pdf_date("yesterday"), None);
+        assert_eq!(parse_pdf_date("D:20241301"), None);
+    }
+
+    #[test]
+    fn test_info_string() {
+        use lopdf::{Object, StringFormat, dictionary};
+
+        let info = dictionary! {
+            "Title" => Object::String(b"\xFE\xFF\x00\xC9\x00t\x00\xE9".to_vec(), StringFormat::Hexadecimal),
+            "Producer" => Object::string_literal("pdfTeX-1.40 "),
+            "Keywords" => Object::string_literal(""),
+        };
+        let document = Document::with_version("1.5");
+        assert_eq!(
+            info_string(&document, &info, b"Title").as_deref(),
+            Some("\u{c9}t\u{e9}")
+        );
+        assert_eq!(
+            info_string(&document, &info, b"Producer").as_deref(),
+            Some("pdfTeX-1.40")
+        );
+        assert_eq!(info_string(&document, &info, b"Keywords"), None);
+        assert_eq!(info_string(&document, &info, b"Author"), None);
+    }
+
+    #[test]
+    fn test_conformance() {
+        use lopdf::{Object, Stream, dictionary};
+
+        let xmp = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+            <rdf:Description rdf:about="" pdfaid:part="2" pdfaid:conformance="B"/>
+            <rdf:Description rdf:about=""><pdfuaid:part>1</pdfuaid:part></rdf:Description>
+            </rdf:RDF></x:xmpmeta>"#;
+        assert_eq!(xmp_property(xmp, "pdfaid:part").as_deref(), Some("2"));
+        assert_eq!(
+            xmp_property(xmp, "pdfaid:conformance").as_deref(),
+            Some("B")
+        );
+        assert_eq!(xmp_property(xmp, "pdfuaid:part").as_deref(), Some("1"));
+        assert_eq!(xmp_property(xmp, "pdfaid:amd"), None);
+
+        let mut document = Document::with_version("1.7");
+        let metadata = document.add_object(Stream::new(
+            dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+            xmp.as_bytes().to_vec(),
+        ));
+        let tree = document.add_object(dictionary! { "Type" => "StructTreeRoot" });
+        let catalog = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Metadata" => metadata,
+            "MarkInfo" => dictionary! { "Marked" => Object::Boolean(true) },
+            "StructTreeRoot" => tree,
+        });
+        document.trailer.set("Root", catalog);
+        let metadata = extract_metadata(&document).unwrap();
+        assert_eq!(metadata.pdfa.as_deref(), Some("PDF/A-2b"));
+        assert_eq!(metadata.pdfua.as_deref(), Some("PDF/UA-1"));
+        assert!(metadata.tagged);
+
+        document.catalog_mut().unwrap().set(
+            "MarkInfo",
+            dictionary! { "Marked" => Object::Boolean(false) },
+        );
+        assert!(!extract_metadata(&document).unwrap().tagged);
+    }
+}
@@ -0,0 +1,259 @@
+use super::limits::{self, MAX_NESTING};
+use super::types::OutlineEntry;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{BTreeMap, HashSet};
+
+/// Bookmarks of the document outline in reading order, with their depth from 1.
+///
+/// Items linking to a missing page or elsewhere than this document keep no
+/// page number. Loops in the outline tree are followed once.
+pub fn bookmarks(document: &Document) -> Vec<OutlineEntry> {
+    let Some(first) = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| catalog.get(b"Outlines").ok())
+        .and_then(|outlines| limits::resolve(document, outlines))
+        .and_then(|outlines| outlines.as_dict().ok())
+        .and_then(|outlines| outlines.get(b"First").ok())
+    else {
+        return Vec::new();
+    };
+
+    let walker = Walker {
+        document,
+        pages: document
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| (id, number))
+            .collect(),
+    };
+    let mut entries = Vec::new();
+    walker.walk(first, 1, &mut HashSet::new(), &mut entries);
+    entries
+}
+
+struct Walker<'a> {
+    document: &'a Document,
+    /// Page number of each page object
+    pages: BTreeMap<ObjectId, u32>,
+}
+
+impl<'a> Walker<'a> {
+    /// Add the item `first` and its siblings, each followed by its children
+    fn walk(
+        &self,
+        first: &'a Object,
+        level: usize,
+        visited: &mut HashSet<ObjectId>,
+        entries: &mut Vec<OutlineEntry>,
+    ) {
+        if level > MAX_NESTING {
+            return;
+        }
+        let mut next = Some(first);
+        while let Some(Object::Reference(id)) = next {
+            if !visited.insert(*id) {
+                break;
+            }
+            let Ok(item) = self.document.get_dictionary(*id) else {
+                break;
+            };
+            if let Ok(title) = item.get(b"Title") {
+                entries.push(OutlineEntry {
+                    level,
+                    title: limits::resolve(self.document, title)
+                        .and_then(|title| lopdf::decode_text_string(title).ok())
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                    page: self.page(item),
+                });
+            }
+            if let Ok(child) = item.get(b"First") {
+                self.walk(child, level + 1, visited, entries);
+            }
+            next = item.get(b"Next").ok();
+        }
+    }
+
+    /// Page number an outline item's destination or GoTo action points to
+    fn page(&self, item: &'a Dictionary) -> Option<u32> {
+        let destination = match item.get(b"Dest") {
+            Ok(destination) => destination,
+            Err(_) => {
+                let action = self.resolve_dict(item.get(b"A").ok()?)?;
+                if action.get(b"S").and_then(Object::as_name).ok()? != b"GoTo" {
+                    return None;
+                }
+                action.get(b"D").ok()?
+            }
+        };
+        self.destination_page(destination, 0)
+    }
+
+    fn destination_page(&self, destination: &'a Object, depth: usize) -> Option<u32> {
+        if depth > limits::MAX_REFERENCE_DEPTH {
+            return None;
+        }
+        match limits::resolve(self.document, destination)? {
+            Object::Array(parts) => match parts.first()? {
+                Object::Reference(page) => self.pages.get(page).copied(),
+                _ => None,
+            },
+            // A named destination's value may wrap the array in a /D entry
+            Object::Dictionary(dict) => self.destination_page(dict.get(b"D").ok()?, depth + 1),
+            Object::Name(name) | Object::String(name, _) => {
+                self.destination_page(self.named(name)?, depth + 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// A named destination, from the catalog's `Dests` dictionary or the `Dests` name tree
+    fn named(&self, name: &[u8]) -> Option<&'a Object> {
+        let catalog = self.document.catalog().ok()?;
+        if let Some(dests) = catalog
+            .get(b"Dests")
+            .ok()
+            .and_then(|d| self.resolve_dict(d))
+            && let Ok(destination) = dests.get(name)
+        {
+            return Some(destination);
+        }
+        let names = self.resolve_dict(catalog.get(b"Names").ok()?)?;
+        self.name_tree_lookup(self.resolve_dict(names.get(b"Dests").ok()?)?, name, 0)
+    }
+
+    fn name_tree_lookup(
+        &self,
+        node: &'a Dictionary,
+        name: &[u8],
+        depth: usize,
+    ) -> Option<&'a Object> {
+        if depth > limits::MAX_REFERENCE_DEPTH {
+            return None;
+        }
+        if let Some(Object::Array(pairs)) = node
+            .get(b"Names")
+            .ok()
+            .and_then(|n| limits::resolve(self.document, n))
+        {
+            for pair in pairs.chunks(2) {
+                if let [Object::String(key, _), value] = pair
+                    && key == name
+                {
+                    return Some(value);
+                }
+            }
+        }
+        let kids = limits::resolve(self.document, node.get(b"Kids").ok()?)?
+            .as_array()
+            .ok()?;
+        kids.iter()
+            .filter_map(|kid| self.resolve_dict(kid))
+            .find_map(|kid| self.name_tree_lookup(kid, name, depth + 1))
+    }
+
+    fn resolve_dict(&self, object: &'a Object) -> Option<&'a Dictionary> {
+        limits::resolve(self.document, object)?.as_dict().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{StringFormat, dictionary};
+
+    /// A two-page document whose outline is built by `items`
+    fn document(items: impl FnOnce(&mut Document, [ObjectId; 2]) -> ObjectId) -> Document {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let page_ids = [0, 1]
+            .map(|_| document.add_object(dictionary! { "Type" => "Page", "Parent" => pages_id }));
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => 2,
+                "Kids" => page_ids.iter().map(|&id| id.into()).collect::<Vec<Object>>(),
+            }),
+        );
+        let first = items(&mut document, page_ids);
+        let outlines = document.add_object(dictionary! { "Type" => "Outlines", "First" => first });
+        let catalog = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines,
+        });
+        document.trailer.set("Root", catalog);
+        document
+    }
+
+    #[test]
+    fn test_nested_bookmarks() {
+        let document = document(|document, [page1, page2]| {
+            let section = document.add_object(dictionary! {
+                "Title" => Object::String(b"\xFE\xFF\x00S\x00e\x00t\x00u\x00p".to_vec(), StringFormat::Hexadecimal),
+                "A" => dictionary! { "S" => "GoTo", "D" => vec![page2.into(), "Fit".into()] },
+            });
+            let chapter2 = document.add_object(dictionary! {
+                "Title" => Object::string_literal("Chapter 2"),
+                "First" => section,
+            });
+            document.add_object(dictionary! {
+                "Title" => Object::string_literal("Chapter 1"),
+                "Dest" => vec![page1.into(), "XYZ".into()],
+                "Next" => chapter2,
+            })
+        });
+
+        let entries = bookmarks(&document);
+        let titles: Vec<(usize, &str, Option<u32>)> = entries
+            .iter()
+            .map(|entry| (entry.level, entry.title.as_str(), entry.page))
+            .collect();
+        assert_eq!(
+            titles,
+            vec![
+                (1, "Chapter 1", Some(1)),
+                (1, "Chapter 2", None),
+                (2, "Setup", Some(2))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_named_destination_and_loop() {
+        let mut dests = None;
+        let mut document = document(|document, [_, page2]| {
+            dests = Some(document.add_object(dictionary! {
+                "results" => dictionary! { "D" => vec![page2.into(), "Fit".into()] },
+            }));
+            let item = document.new_object_id();
+            document.objects.insert(
+                item,
+                Object::Dictionary(dictionary! {
+                    "Title" => Object::string_literal("Results"),
+                    "Dest" => "results",
+                    // A damaged outline pointing back at itself
+                    "Next" => item,
+                }),
+            );
+            item
+        });
+        let catalog = document
+            .trailer
+            .get(b"Root")
+            .unwrap()
+            .as_reference()
+            .unwrap();
+        document
+            .get_dictionary_mut(catalog)
+            .unwrap()
+            .set("Dests", dests.unwrap());
+
+        let entries = bookmarks(&document);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].page, Some(2));
+    }
+}
@@ -0,0 +1,253 @@
+use super::types::ExtractOptions;
+use lopdf::{Document, Object, ObjectId};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt::Debug;
+
+/// Extension point keeping the extracted text of pages between runs.
+///
+/// Keys are digests of everything a page's text is extracted from, so a
+/// page whose key is found may use the stored text instead of being
+/// extracted again. Pages that produced images or footnotes are never
+/// stored, since a stored page gives only its text.
+pub trait PageCache: Debug + Send + Sync {
+    /// The text stored under `key`, if any
+    fn get(&self, key: &str) -> Option<String>;
+    /// Store the text of a page
+    fn put(&self, key: &str, text: &str);
+}
+
+/// Cache key of a page: a hex SHA-256 digest of the page number, the
+/// options that change how pages are extracted, and the objects the page
+/// draws from.
+///
+/// Everything the page dictionary references is included except its parent,
+/// whose inheritable entries are hashed instead; another page it links to
+/// counts by its object number only. Content changes anywhere else, such as
+/// pages appended by an incremental update, leave the key alone.
+pub fn page_key(
+    document: &Document,
+    page_num: u32,
+    page_id: ObjectId,
+    options: &ExtractOptions,
+) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+    hasher.update(page_num.to_le_bytes());
+    hasher.update(options_fingerprint(options).as_bytes());
+
+    let mut hashing = Hashing {
+        document,
+        hasher,
+        page_id,
+        seen: HashSet::from([page_id]),
+    };
+    if let Ok(page) = document.get_dictionary(page_id) {
+        hashing.dictionary(page);
+        // Entries a page inherits from the page tree
+        let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+        while let Some(id) = parent.filter(|&id| hashing.seen.insert(id)) {
+            let Ok(node) = document.get_dictionary(id) else {
+                break;
+            };
+            for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+                if let Ok(value) = node.get(key) {
+                    hashing.hasher.update(key);
+                    hashing.object(value);
+                }
+            }
+            parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+        }
+    }
+    let digest = hashing.hasher.finalize();
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// The options a page's extracted text depends on; clean-up happens after
+/// pages are joined, so its settings are left out
+fn options_fingerprint(options: &ExtractOptions) -> String {
+    format!(
+        "{:?} {:?}",
+        (
+            options.math,
+            options.mark_lost_equations,
+            options.images,
+            &options.image_prefix,
+            options.alt_text.is_some(),
+            options.vector_graphics,
+            options.image_format,
+            options.min_image_size,
+            &options.skip_image_types,
+        ),
+        (
+            options.footnotes,
+            options.definition_lists,
+            options.horizontal_rules,
+            options.underline,
+            options.strikethrough,
+            options.text_colors,
+        ),
+    )
+}
+
+/// Feeds objects to the hasher, following references once each
+struct Hashing<'a> {
+    document: &'a Document,
+    hasher: Sha256,
+    page_id: ObjectId,
+    seen: HashSet<ObjectId>,
+}
+
+impl Hashing<'_> {
+    fn object(&mut self, object: &Object) {
+        match object {
+            Object::Reference(id) => {
+                self.hasher.update(b"R");
+                self.hasher.update(id.0.to_le_bytes());
+                self.hasher.update(id.1.to_le_bytes());
+                if !self.seen.insert(*id) {
+                    return;
+                }
+                if let Ok(target) = self.document.get_object(*id) {
+                    // A link to another page depends on its number, not its content
+                    let other_page = target
+                        .as_dict()
+                        .is_ok_and(|dict| dict.type_is(b"Page") && *id != self.page_id);
+                    if !other_page {
+                        self.object(target);
+                    }
+                }
+            }
+            Object::Dictionary(dict) => self.dictionary(dict),
+            Object::Stream(stream) => {
+                self.dictionary(&stream.dict);
+                self.hasher.update(b"S");
+                self.hasher
+                    .update((stream.content.len() as u64).to_le_bytes());
+                self.hasher.update(&stream.content);
+            }
+            Object::Array(items) => {
+                self.hasher.update(b"[");
+                for item in items {
+                    self.object(item);
+                }
+                self.hasher.update(b"]");
+            }
+            // Scalars hash by their debug form, tagged by it too
+            scalar => self.hasher.update(format!("{:?};", scalar).as_bytes()),
+        }
+    }
+
+    fn dictionary(&mut self, dict: &lopdf::Dictionary) {
+        self.hasher.update(b"<<");
+        for (key, value) in dict.iter() {
+            // Back references to the page tree and the annotated page
+            if key == b"Parent" || key == b"P" {
+                continue;
+            }
+            self.hasher.update((key.len() as u64).to_le_bytes());
+            self.hasher.update(key);
+            self.object(value);
+        }
+        self.hasher.update(b">>");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_test_font, add_test_page};
+    use lopdf::{Stream, dictionary};
+
+    /// A document with one page per text, each with its own content stream
+    fn document(texts: &[&str]) -> Document {
+        let mut doc = Document::with_version("1.4");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.new_object_id();
+        add_test_font(&mut doc, font_id);
+        let mut kids = Vec::new();
+        for text in texts {
+            let content = format!("BT\n/F1 12 Tf\n50 700 Td\n({}) Tj\nET\n", text);
+            let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+            let page_id = doc.new_object_id();
+            add_test_page(&mut doc, page_id, pages_id, content_id, font_id);
+            kids.push(page_id.into());
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => texts.len() as i64,
+                "Kids" => kids,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        doc
+    }
+
+    fn keys(doc: &Document, options: &ExtractOptions) -> Vec<String> {
+        doc.get_pages()
+            .into_iter()
+            .map(|(num, id)| page_key(doc, num, id, options))
+            .collect()
+    }
+
+    #[test]
+    fn test_page_key() {
+        let options = ExtractOptions::default();
+        let before = keys(&document(&["One", "Two"]), &options);
+        assert_eq!(before[0].len(), 64);
+        assert_ne!(before[0], before[1]);
+        // The same content keeps its keys when a page is added...
+        let appended = keys(&document(&["One", "Two", "Three"]), &options);
+        assert_eq!(appended[..2], before[..]);
+        // ...but not when it changes, or is extracted differently
+        assert_ne!(keys(&document(&["One", "2"]), &options)[1], before[1]);
+        let math = ExtractOptions {
+            math: true,
+            ..Default::default()
+        };
+        assert_ne!(keys(&document(&["One", "Two"]), &math), before);
+    }
+
+    /// Pages stored in memory
+    #[derive(Debug, Default)]
+    struct MemoryCache(std::sync::Mutex<std::collections::HashMap<String, String>>);
+
+    impl PageCache for MemoryCache {
+        fn get(&self, key: &str) -> Option<String> {
+            self.0.lock().unwrap().get(key).cloned()
+        }
+
+        fn put(&self, key: &str, text: &str) {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), text.to_string());
+        }
+    }
+
+    #[test]
+    fn test_extraction_reuses_cached_pages() {
+        let cache = std::sync::Arc::new(MemoryCache::default());
+        let options = ExtractOptions {
+            page_cache: Some(cache.clone()),
+            ..Default::default()
+        };
+        let doc = document(&["One", "Two"]);
+        let fresh = crate::text::extract_text_with(&doc, &options).unwrap();
+        assert_eq!(cache.0.lock().unwrap().len(), 2);
+
+        // A stored page is used as is
+        let key = keys(&doc, &options)[0].clone();
+        let stored = cache.get(&key).unwrap();
+        cache.put(&key, &stored.replace("One", "Cached"));
+        let cached = crate::text::extract_text_with(&doc, &options).unwrap();
+        assert!(cached.text.starts_with("Cached"));
+        assert_eq!(cached.text.replace("Cached", "One"), fresh.text);
+    }
+}
@@ -0,0 +1,117 @@
+//! Narrowing a document to some of its pages
+
+use super::limits;
+use lopdf::{Dictionary, Document, Object, ObjectId};
+
+/// Page attributes a page takes from its ancestors when it has none itself
+const INHERITED: [&[u8]; 4] = [b"Resources", b"MediaBox", b"CropBox", b"Rotate"];
+
+/// Replace the page tree with one holding only `pages`, in that order.
+///
+/// The pages keep what they inherited from the nodes above them. The objects
+/// of the other pages stay in the document, but no longer belong to a page.
+pub fn keep(document: &mut Document, pages: &[ObjectId]) {
+    let root = document.new_object_id();
+    for &page in pages {
+        let inherited = inherited(document, page);
+        if let Ok(Object::Dictionary(dict)) = document.get_object_mut(page) {
+            for (key, value) in inherited {
+                dict.set(key, value);
+            }
+            dict.set("Parent", Object::Reference(root));
+        }
+    }
+    let mut tree = Dictionary::new();
+    tree.set("Type", Object::Name(b"Pages".to_vec()));
+    tree.set(
+        "Kids",
+        pages
+            .iter()
+            .map(|&page| Object::Reference(page))
+            .collect::<Vec<_>>(),
+    );
+    tree.set("Count", pages.len() as i64);
+    document.objects.insert(root, Object::Dictionary(tree));
+    if let Ok(catalog) = document.catalog_mut() {
+        catalog.set("Pages", Object::Reference(root));
+    }
+}
+
+/// The inherited attributes `page` lacks, from the nearest ancestor with each
+fn inherited(document: &Document, page: ObjectId) -> Vec<(&'static [u8], Object)> {
+    let Ok(dict) = document.get_dictionary(page) else {
+        return Vec::new();
+    };
+    let mut found: Vec<(&'static [u8], Object)> = Vec::new();
+    let mut node = dict;
+    for _ in 0..limits::MAX_REFERENCE_DEPTH {
+        let Some(parent) = node
+            .get(b"Parent")
+            .ok()
+            .and_then(|parent| limits::resolve(document, parent))
+            .and_then(|parent| parent.as_dict().ok())
+        else {
+            break;
+        };
+        for key in INHERITED {
+            if !dict.has(key)
+                && !found.iter().any(|(k, _)| *k == key)
+                && let Ok(value) = parent.get(key)
+            {
+                found.push((key, value.clone()));
+            }
+        }
+        node = parent;
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::dictionary;
+
+    #[test]
+    fn test_keep() {
+        let mut document = Document::with_version("1.7");
+        let root = document.new_object_id();
+        let inner = document.new_object_id();
+        let pages: Vec<ObjectId> = (0..3)
+            .map(|i| {
+                document.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => if i == 0 { root } else { inner },
+                })
+            })
+            .collect();
+        document.objects.insert(
+            inner,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Parent" => root,
+                "Kids" => vec![pages[1].into(), pages[2].into()],
+                "Count" => 2,
+                "Rotate" => 90,
+            }),
+        );
+        document.objects.insert(
+            root,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![pages[0].into(), inner.into()],
+                "Count" => 3,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "Pages" => root });
+        document.trailer.set("Root", catalog);
+
+        keep(&mut document, &[pages[2], pages[0]]);
+        let kept: Vec<ObjectId> = document.get_pages().into_values().collect();
+        assert_eq!(kept, [pages[2], pages[0]]);
+        let page = document.get_dictionary(pages[2]).unwrap();
+        assert_eq!(page.get(b"Rotate").unwrap().as_i64().unwrap(), 90);
+        assert!(page.has(b"MediaBox"));
+        assert!(!document.get_dictionary(pages[0]).unwrap().has(b"Rotate"));
+    }
+}
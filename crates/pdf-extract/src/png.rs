@@ -0,0 +1,85 @@
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use std::io::Write;
+
+/// PNG colour types supported by the encoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Gray = 0,
+    Rgb = 2,
+}
+
+impl ColorType {
+    /// Number of samples per pixel
+    pub fn channels(self) -> usize {
+        match self {
+            Self::Gray => 1,
+            Self::Rgb => 3,
+        }
+    }
+}
+
+/// Encode 8-bit samples as a PNG file
+pub fn encode(
+    width: u32,
+    height: u32,
+    color: ColorType,
+    samples: &[u8],
+) -> std::io::Result<Vec<u8>> {
+    let row_len = width as usize * color.channels();
+    let expected = row_len * height as usize;
+    if samples.len() < expected {
+        return Err(std::io::Error::other(format!(
+            "image data too short: {} bytes, expected {}",
+            samples.len(),
+            expected
+        )));
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::with_capacity(expected / 2), Compression::default());
+    for row in samples[..expected].chunks(row_len) {
+        encoder.write_all(&[0])?;
+        encoder.write_all(row)?;
+    }
+    let idat = encoder.finish()?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, color as u8, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &idat);
+    write_chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+/// Append a length-prefixed, CRC-terminated chunk
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+    png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_writes_png_structure() {
+        let png = encode(2, 2, ColorType::Gray, &[0, 255, 255, 0]).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(&png[12..16], b"IHDR");
+        assert!(png.ends_with(&[0xAE, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn test_encode_rejects_short_data() {
+        assert!(encode(4, 4, ColorType::Rgb, &[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+//! Portfolios (PDF collections): a catalog with a `Collection` dictionary,
+//! whose viewers list the files embedded in the document instead of showing
+//! its pages
+
+use super::limits;
+use super::types::EmbeddedDocument;
+use super::validation::check_header;
+use lopdf::{Dictionary, Document, Object};
+
+/// The PDFs embedded in a portfolio, in the order of the `EmbeddedFiles`
+/// name tree; other attachments are left out.
+///
+/// A document without a `Collection` is no portfolio, even when files are
+/// attached to it, and gives none.
+pub fn documents(document: &Document) -> Vec<EmbeddedDocument> {
+    let Some(tree) = document
+        .catalog()
+        .ok()
+        .filter(|catalog| catalog.has(b"Collection"))
+        .and_then(|catalog| resolve_dict(document, catalog.get(b"Names").ok()?))
+        .and_then(|names| resolve_dict(document, names.get(b"EmbeddedFiles").ok()?))
+    else {
+        return Vec::new();
+    };
+    let mut files = Vec::new();
+    leaves(document, tree, 0, &mut files);
+    files
+        .into_iter()
+        .filter_map(|(key, spec)| embedded_pdf(document, key, spec))
+        .collect()
+}
+
+/// The keys and values of a name tree, in order
+fn leaves<'a>(
+    document: &'a Document,
+    node: &'a Dictionary,
+    depth: usize,
+    found: &mut Vec<(&'a [u8], &'a Object)>,
+) {
+    if depth > limits::MAX_REFERENCE_DEPTH {
+        return;
+    }
+    if let Some(Object::Array(pairs)) = node
+        .get(b"Names")
+        .ok()
+        .and_then(|names| limits::resolve(document, names))
+    {
+        for pair in pairs.chunks(2) {
+            if let [Object::String(key, _), value] = pair {
+                found.push((key, value));
+            }
+        }
+    }
+    if let Some(Object::Array(kids)) = node
+        .get(b"Kids")
+        .ok()
+        .and_then(|kids| limits::resolve(document, kids))
+    {
+        for kid in kids {
+            if let Some(kid) = resolve_dict(document, kid) {
+                leaves(document, kid, depth + 1, found);
+            }
+        }
+    }
+}
+
+/// The file a file specification embeds, if it is a PDF, named by the
+/// specification's Unicode or plain file name, or else its key in the tree
+fn embedded_pdf(document: &Document, key: &[u8], spec: &Object) -> Option<EmbeddedDocument> {
+    let spec = resolve_dict(document, spec)?;
+    let streams = resolve_dict(document, spec.get(b"EF").ok()?)?;
+    let stream = streams
+        .get(b"UF")
+        .or_else(|_| streams.get(b"F"))
+        .ok()
+        .and_then(|stream| limits::resolve(document, stream))?
+        .as_stream()
+        .ok()?;
+    let data = limits::decompressed_content(stream)?;
+    check_header(&data).ok()?;
+
+    let name = spec
+        .get(b"UF")
+        .or_else(|_| spec.get(b"F"))
+        .ok()
+        .and_then(|name| limits::resolve(document, name))
+        .and_then(|name| lopdf::decode_text_string(name).ok())
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| String::from_utf8_lossy(key).into_owned());
+    Some(EmbeddedDocument { name, data })
+}
+
+fn resolve_dict<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    limits::resolve(document, object)?.as_dict().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{Stream, dictionary};
+
+    /// A document with `files` (key, name, data) attached, a portfolio when
+    /// `collection` is set
+    fn document(collection: bool, files: &[(&str, &str, &[u8])]) -> Document {
+        let mut document = Document::with_version("1.7");
+        let mut names = Vec::new();
+        for (key, name, data) in files {
+            let stream = document.add_object(Stream::new(dictionary! {}, data.to_vec()));
+            let spec = document.add_object(dictionary! {
+                "Type" => "Filespec",
+                "F" => Object::string_literal(*name),
+                "EF" => dictionary! { "F" => stream },
+            });
+            names.push(Object::string_literal(*key));
+            names.push(spec.into());
+        }
+        // The leaves sit under an intermediate node
+        let leaf = document.add_object(dictionary! { "Names" => names });
+        let mut catalog = dictionary! {
+            "Type" => "Catalog",
+            "Names" => dictionary! {
+                "EmbeddedFiles" => dictionary! { "Kids" => vec![leaf.into()] },
+            },
+        };
+        if collection {
+            catalog.set("Collection", dictionary! { "View" => "D" });
+        }
+        let catalog = document.add_object(catalog);
+        document.trailer.set("Root", catalog);
+        document
+    }
+
+    #[test]
+    fn test_documents() {
+        let files: [(&str, &str, &[u8]); 3] = [
+            ("001", "Budget.pdf", b"%PDF-1.4 budget"),
+            ("002", "notes.txt", b"not a PDF"),
+            ("003", "", b"%PDF-1.7 minutes"),
+        ];
+        assert_eq!(
+            documents(&document(true, &files)),
+            [
+                EmbeddedDocument {
+                    name: "Budget.pdf".to_string(),
+                    data: b"%PDF-1.4 budget".to_vec(),
+                },
+                EmbeddedDocument {
+                    name: "003".to_string(),
+                    data: b"%PDF-1.7 minutes".to_vec(),
+                },
+            ]
+        );
+        // Attachments alone do not make a portfolio
+        assert!(documents(&document(false, &files)).is_empty());
+    }
+}
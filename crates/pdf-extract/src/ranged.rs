@@ -0,0 +1,933 @@
+//! Reading some pages of a document a range of bytes at a time, for files
+//! on a server too large to download: the cross-reference sections are read
+//! from the end of the file, then only the objects those pages reach
+
+use super::compatibility;
+use super::limits;
+use super::page_tree;
+use super::types::PageSelection;
+use super::validation::check_header;
+use crate::{PdfError, Result};
+use log::{debug, info};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::Path;
+
+/// Bytes read from the end of the file to find its last cross-reference section
+const TAIL: u64 = 1024;
+
+/// Bytes first read of a cross-reference section, four times more each time
+/// it turns out to be longer
+const SECTION: u64 = 16 * 1024;
+
+/// Longest cross-reference section read
+const MAX_SECTION: u64 = 64 * 1024 * 1024;
+
+/// Most cross-reference sections followed through `Prev`
+const MAX_SECTIONS: usize = 256;
+
+/// Objects at most this far apart are read in one request
+const GAP: u64 = 16 * 1024;
+
+/// Most rounds of reading the objects found missing
+const MAX_ROUNDS: usize = 64;
+
+/// Catalog entries not followed: they can be large and lead to every page
+const SKIPPED: [&[u8]; 4] = [b"Pages", b"Names", b"StructTreeRoot", b"AcroForm"];
+
+/// A file read a range of bytes at a time, such as one on an HTTP server
+/// answering range requests
+pub trait RangeSource {
+    /// Length of the file in bytes
+    fn size(&self) -> u64;
+
+    /// The bytes of `range`, which lies within the file
+    fn read(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>>;
+}
+
+/// A local file, read at the offsets asked for
+pub(crate) struct FileSource {
+    file: File,
+    size: u64,
+}
+
+impl FileSource {
+    pub(crate) fn open(path: &Path) -> std::io::Result<Self> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { file, size })
+    }
+}
+
+impl RangeSource for FileSource {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+        let mut bytes = vec![0; (range.end - range.start) as usize];
+        self.file.seek(SeekFrom::Start(range.start))?;
+        self.file.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Where the cross-reference sections put an object
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Entry {
+    Free,
+    Object {
+        offset: u64,
+        generation: u16,
+    },
+    /// In the object stream numbered `container`
+    Compressed {
+        container: u32,
+    },
+}
+
+/// A source counting what it is asked for
+struct Counted<'a> {
+    source: &'a mut dyn RangeSource,
+    requests: usize,
+    bytes: u64,
+}
+
+impl Counted<'_> {
+    fn read(&mut self, range: Range<u64>) -> Result<Vec<u8>> {
+        let bytes = self.source.read(range.clone())?;
+        if bytes.len() as u64 != range.end - range.start {
+            return Err(PdfError::Processing(format!(
+                "asked for bytes {}-{}, received {}",
+                range.start,
+                range.end - 1,
+                bytes.len()
+            )));
+        }
+        self.requests += 1;
+        self.bytes += bytes.len() as u64;
+        Ok(bytes)
+    }
+}
+
+/// Load the pages `selection` picks and the objects they need, reading
+/// nothing else; the page tree holds those pages only, numbered from 1.
+///
+/// Returns the document, still encrypted if the file is, and the number of
+/// revisions appended to the original file.
+pub fn load(source: &mut dyn RangeSource, selection: &PageSelection) -> Result<(Document, usize)> {
+    let size = source.size();
+    let mut source = Counted {
+        source,
+        requests: 0,
+        bytes: 0,
+    };
+    let head = source.read(0..size.min(TAIL))?;
+    check_header(&head)?;
+    let version = version(&head);
+    let xref = CrossReference::read(&mut source, size, &version)?;
+    let extents = xref.extents(size);
+
+    let mut objects: BTreeMap<ObjectId, Vec<u8>> = BTreeMap::new();
+    let mut tried = HashSet::new();
+    let mut document = Document::new();
+    for _ in 0..MAX_ROUNDS {
+        let mut walk = Walk::new(&document);
+        walk.run(&xref.trailer, selection)?;
+        let wanted: BTreeSet<u32> = walk
+            .missing
+            .iter()
+            .filter_map(|number| match xref.entries.get(number) {
+                Some(Entry::Compressed { container }) => Some(*container),
+                Some(Entry::Object { .. }) => Some(*number),
+                _ => None,
+            })
+            .filter(|&number| tried.insert(number))
+            .collect();
+        if wanted.is_empty() {
+            let Some(pages) = walk.pages else {
+                return Err(PdfError::Processing(
+                    "the pages chosen cannot be found in the page tree".to_string(),
+                ));
+            };
+            info!(
+                "Read {} of {} bytes in {} requests",
+                source.bytes, size, source.requests
+            );
+            document.trailer = xref.trailer();
+            document.version = version;
+            document.max_id = xref.entries.keys().max().copied().unwrap_or(0);
+            page_tree::keep(&mut document, &pages);
+            let linearized = compatibility::is_linearized(&head);
+            let updates = xref
+                .starts
+                .len()
+                .saturating_sub(if linearized { 2 } else { 1 });
+            return Ok((document, updates));
+        }
+
+        let ranges: Vec<Extent> = wanted
+            .iter()
+            .filter_map(|number| match xref.entries.get(number) {
+                Some(Entry::Object { generation, .. }) => {
+                    Some(((*number, *generation), extents.get(number)?.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        debug!("Reading {} objects", ranges.len());
+        for (range, members) in coalesce(ranges) {
+            let bytes = source.read(range.clone())?;
+            for (id, member) in members {
+                let at = (member.start - range.start) as usize;
+                let end = (member.end - range.start) as usize;
+                objects.insert(id, bytes[at..end].to_vec());
+            }
+        }
+        document = assemble(
+            &version,
+            objects
+                .iter()
+                .map(|(&id, bytes)| (id, direct_length(bytes))),
+        )
+        .ok_or_else(|| PdfError::Processing("failed to load the objects read".to_string()))?;
+    }
+    Err(PdfError::Processing(format!(
+        "the pages chosen still need objects after {} rounds of reading",
+        MAX_ROUNDS
+    )))
+}
+
+/// The version in the `%PDF-1.7` header
+fn version(head: &[u8]) -> String {
+    head[5..]
+        .iter()
+        .take_while(|b| b.is_ascii_digit() || **b == b'.')
+        .map(|&b| b as char)
+        .collect()
+}
+
+/// The bytes of an object in the file
+type Extent = (ObjectId, Range<u64>);
+
+/// Object ranges close enough together merged into one range each, with the
+/// objects it holds
+fn coalesce(mut ranges: Vec<Extent>) -> Vec<(Range<u64>, Vec<Extent>)> {
+    ranges.sort_by_key(|(_, range)| range.start);
+    let mut merged: Vec<(Range<u64>, Vec<Extent>)> = Vec::new();
+    for (id, range) in ranges {
+        match merged.last_mut() {
+            Some((all, members)) if range.start <= all.end + GAP => {
+                all.end = all.end.max(range.end);
+                members.push((id, range));
+            }
+            _ => merged.push((range.clone(), vec![(id, range)])),
+        }
+    }
+    merged
+}
+
+/// The cross-reference sections of the file, newest first
+struct CrossReference {
+    /// Each object as the newest section has it
+    entries: BTreeMap<u32, Entry>,
+    /// The newest trailer
+    trailer: Dictionary,
+    /// Where each section starts
+    starts: Vec<u64>,
+}
+
+impl CrossReference {
+    fn read(source: &mut Counted, size: u64, version: &str) -> Result<Self> {
+        let tail = source.read(size.saturating_sub(TAIL)..size)?;
+        let start = rfind(&tail, b"startxref")
+            .and_then(|at| Tokens::new(&tail[at + 9..]).number::<u64>())
+            .ok_or_else(|| {
+                PdfError::Processing("no startxref at the end of the file".to_string())
+            })?;
+
+        let mut xref = Self {
+            entries: BTreeMap::new(),
+            trailer: Dictionary::new(),
+            starts: Vec::new(),
+        };
+        let mut pending = vec![start];
+        let mut seen = HashSet::new();
+        while let Some(start) = pending.pop() {
+            if !seen.insert(start) || seen.len() > MAX_SECTIONS {
+                continue;
+            }
+            if start >= size {
+                return Err(PdfError::Processing(format!(
+                    "cross-reference section at {} is past the end of the file",
+                    start
+                )));
+            }
+            let (entries, trailer) = read_section(source, start, size, version)?;
+            for (number, entry) in entries {
+                xref.entries.entry(number).or_insert(entry);
+            }
+            // A hybrid file's stream section belongs to the same revision,
+            // so it comes before the previous one
+            for key in [b"Prev".as_slice(), b"XRefStm"] {
+                if let Ok(offset) = trailer.get(key).and_then(Object::as_i64) {
+                    pending.push(offset.max(0) as u64);
+                }
+            }
+            if xref.starts.is_empty() {
+                xref.trailer = trailer;
+            }
+            xref.starts.push(start);
+        }
+        debug!(
+            "Read {} cross-reference sections with {} objects",
+            xref.starts.len(),
+            xref.entries.len()
+        );
+        Ok(xref)
+    }
+
+    /// Bytes of each object: from its offset to where the next object or
+    /// section starts
+    fn extents(&self, size: u64) -> BTreeMap<u32, Range<u64>> {
+        let mut starts: Vec<u64> = self
+            .entries
+            .values()
+            .filter_map(|entry| match entry {
+                Entry::Object { offset, .. } => Some(*offset),
+                _ => None,
+            })
+            .chain(self.starts.iter().copied())
+            .chain([size])
+            .collect();
+        starts.sort_unstable();
+        starts.dedup();
+        self.entries
+            .iter()
+            .filter_map(|(&number, entry)| match *entry {
+                Entry::Object { offset, .. } if offset < size => {
+                    let next = starts[starts.partition_point(|&start| start <= offset)..]
+                        .first()
+                        .copied()
+                        .unwrap_or(size);
+                    Some((number, offset..next))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The trailer the document keeps: which objects are the catalog and
+    /// the document information, and how the file is encrypted
+    fn trailer(&self) -> Dictionary {
+        let mut trailer = Dictionary::new();
+        for key in [b"Root".as_slice(), b"Info", b"Encrypt", b"ID"] {
+            if let Ok(value) = self.trailer.get(key) {
+                trailer.set(key, value.clone());
+            }
+        }
+        trailer.set(
+            "Size",
+            self.entries.keys().max().map_or(0, |&max| max as i64 + 1),
+        );
+        trailer
+    }
+}
+
+type Section = (Vec<(u32, Entry)>, Dictionary);
+
+/// A cross-reference section and its trailer, read in larger pieces until
+/// the whole section is in
+fn read_section(source: &mut Counted, start: u64, size: u64, version: &str) -> Result<Section> {
+    let mut length = SECTION;
+    loop {
+        let end = start.saturating_add(length).min(size);
+        let bytes = source.read(start..end)?;
+        let section = if Tokens::new(&bytes).next() == Some(b"xref".as_slice()) {
+            table_section(&bytes, version)
+        } else {
+            stream_section(&bytes, version)
+        };
+        if let Some(section) = section {
+            return Ok(section);
+        }
+        if end == size || length >= MAX_SECTION {
+            return Err(PdfError::Processing(format!(
+                "cannot read the cross-reference section at {}",
+                start
+            )));
+        }
+        length *= 4;
+    }
+}
+
+/// An `xref` table and the trailer after it; `None` until both are complete
+fn table_section(bytes: &[u8], version: &str) -> Option<Section> {
+    let mut tokens = Tokens::new(bytes);
+    tokens.next()?;
+    let mut entries = Vec::new();
+    let trailer = loop {
+        let at = tokens.at;
+        let token = tokens.next()?;
+        if token.starts_with(b"trailer") {
+            break at + tokens.skipped + b"trailer".len();
+        }
+        let first: u32 = parse(token)?;
+        let count: u32 = tokens.number()?;
+        for number in first..first.checked_add(count)? {
+            let offset: u64 = tokens.number()?;
+            let generation: u16 = tokens.number()?;
+            let entry = match tokens.next()? {
+                b"n" => Entry::Object { offset, generation },
+                b"f" => Entry::Free,
+                _ => return None,
+            };
+            entries.push((number, entry));
+        }
+    };
+    let end = trailer + find(&bytes[trailer..], b"startxref")?;
+    let wrapped = [b"1 0 obj\n", &bytes[trailer..end], b"\nendobj"].concat();
+    let document = assemble(version, [((1, 0), Cow::Owned(wrapped))].into_iter())?;
+    let dict = document.get_dictionary((1, 0)).ok()?.clone();
+    Some((entries, dict))
+}
+
+/// A cross-reference stream, whose dictionary is the trailer; `None` until
+/// the whole object is in
+fn stream_section(bytes: &[u8], version: &str) -> Option<Section> {
+    let end = find(bytes, b"endobj")? + b"endobj".len();
+    let mut tokens = Tokens::new(bytes);
+    let id = (tokens.number()?, tokens.number()?);
+    let document = assemble(version, [(id, direct_length(&bytes[..end]))].into_iter())?;
+    let stream = document.get_object(id).ok()?.as_stream().ok()?;
+    if !stream.dict.type_is(b"XRef") {
+        return None;
+    }
+    let data = limits::decompressed_content(stream)?;
+    let integers = |key: &[u8]| -> Option<Vec<u64>> {
+        stream
+            .dict
+            .get(key)
+            .ok()?
+            .as_array()
+            .ok()?
+            .iter()
+            .map(|n| n.as_i64().ok().and_then(|n| u64::try_from(n).ok()))
+            .collect()
+    };
+    let widths: Vec<usize> = integers(b"W")?
+        .into_iter()
+        .map(|width| width.min(8) as usize)
+        .collect();
+    let [kind_width, second_width, third_width] = widths[..] else {
+        return None;
+    };
+    let index = match integers(b"Index") {
+        Some(index) => index,
+        None => vec![0, stream.dict.get(b"Size").ok()?.as_i64().ok()? as u64],
+    };
+    let field = |bytes: &[u8]| bytes.iter().fold(0u64, |n, &b| n << 8 | b as u64);
+    let mut rows = data.chunks_exact(kind_width + second_width + third_width);
+    let mut entries = Vec::new();
+    for pair in index.chunks(2) {
+        let [first, count] = *pair else {
+            return None;
+        };
+        for number in first..first.checked_add(count)? {
+            let row = rows.next()?;
+            let (kind, rest) = row.split_at(kind_width);
+            let (second, third) = rest.split_at(second_width);
+            // Without a type field every entry is an object
+            let kind = if kind_width == 0 { 1 } else { field(kind) };
+            let entry = match kind {
+                0 => Entry::Free,
+                1 => Entry::Object {
+                    offset: field(second),
+                    generation: field(third) as u16,
+                },
+                2 => Entry::Compressed {
+                    container: field(second) as u32,
+                },
+                _ => continue,
+            };
+            entries.push((u32::try_from(number).ok()?, entry));
+        }
+    }
+    Some((entries, stream.dict.clone()))
+}
+
+/// Load complete `N G obj ... endobj` objects, giving them a fresh
+/// cross-reference table as [`rebuild`](super::recovery::rebuild) does for
+/// a damaged file
+fn assemble<'a>(
+    version: &str,
+    objects: impl Iterator<Item = (ObjectId, Cow<'a, [u8]>)>,
+) -> Option<Document> {
+    let mut buffer = format!("%PDF-{}\n", version).into_bytes();
+    let mut offsets = Vec::new();
+    for (id, bytes) in objects {
+        offsets.push((id, buffer.len()));
+        buffer.extend_from_slice(&bytes);
+        buffer.push(b'\n');
+    }
+    let xref_offset = buffer.len();
+    let mut xref = String::from("xref\n0 1\n0000000000 65535 f\r\n");
+    for ((number, generation), offset) in &offsets {
+        let _ = write!(xref, "{} 1\n{:010} {:05} n\r\n", number, offset, generation);
+    }
+    let size = offsets.iter().map(|((number, _), _)| number + 1).max()?;
+    let _ = write!(
+        xref,
+        "trailer\n<< /Size {} >>\nstartxref\n{}\n%%EOF\n",
+        size, xref_offset
+    );
+    buffer.extend_from_slice(xref.as_bytes());
+    Document::load_mem(&buffer).ok()
+}
+
+/// A stream object whose `Length` is a reference, with the length written in
+/// its place: the object holding it may not have been read yet
+fn direct_length(object: &[u8]) -> Cow<'_, [u8]> {
+    let Some(keyword) = (0..object.len()).find(|&i| {
+        object[i..].starts_with(b"stream")
+            && matches!(object.get(i + 6), Some(b'\r' | b'\n'))
+            && !object[..i].ends_with(b"end")
+    }) else {
+        return Cow::Borrowed(object);
+    };
+    // `/Length 12 0 R`, and not `/Length1`, which font files have too
+    let Some((value, reference_end)) = (0..keyword)
+        .filter(|&i| object[i..keyword].starts_with(b"/Length"))
+        .find_map(|key| {
+            let value = key + b"/Length".len();
+            let mut tokens = Tokens::new(&object[value..keyword]);
+            let is_reference = tokens.skipped_whitespace()
+                && tokens.number::<u32>().is_some()
+                && tokens.number::<u16>().is_some()
+                && tokens.peek() == Some(b'R');
+            is_reference.then_some((value, value + tokens.at + 1))
+        })
+    else {
+        return Cow::Borrowed(object);
+    };
+
+    let mut start = keyword + b"stream".len();
+    if object[start..].starts_with(b"\r\n") {
+        start += 2;
+    } else {
+        start += 1;
+    }
+    let Some(mut end) = rfind(object, b"endstream").filter(|&end| end >= start) else {
+        return Cow::Borrowed(object);
+    };
+    // The end of line before `endstream` is not part of the data
+    if object[start..end].ends_with(b"\r\n") {
+        end -= 2;
+    } else if object[start..end].ends_with(b"\n") || object[start..end].ends_with(b"\r") {
+        end -= 1;
+    }
+    let mut direct = object[..value].to_vec();
+    direct.extend_from_slice(format!(" {}", end - start).as_bytes());
+    direct.extend_from_slice(&object[reference_end..]);
+    Cow::Owned(direct)
+}
+
+/// Whitespace-separated words of PDF syntax
+struct Tokens<'a> {
+    bytes: &'a [u8],
+    at: usize,
+    /// Whitespace skipped before the last word
+    skipped: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            at: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Skip whitespace and comments, returning whether there was any
+    fn skipped_whitespace(&mut self) -> bool {
+        let start = self.at;
+        while let Some(&b) = self.bytes.get(self.at) {
+            if b == b'%' {
+                while self
+                    .bytes
+                    .get(self.at)
+                    .is_some_and(|&b| b != b'\n' && b != b'\r')
+                {
+                    self.at += 1;
+                }
+            } else if b.is_ascii_whitespace() || b == 0 {
+                self.at += 1;
+            } else {
+                break;
+            }
+        }
+        self.at > start
+    }
+
+    /// The byte starting the next word
+    fn peek(&mut self) -> Option<u8> {
+        self.skipped_whitespace();
+        self.bytes.get(self.at).copied()
+    }
+
+    /// The next word; `None` at the end, or when the word may go on past
+    /// the bytes there are
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let before = self.at;
+        self.skipped_whitespace();
+        self.skipped = self.at - before;
+        let start = self.at;
+        while self
+            .bytes
+            .get(self.at)
+            .is_some_and(|&b| !b.is_ascii_whitespace())
+        {
+            self.at += 1;
+        }
+        if start == self.at || self.at == self.bytes.len() {
+            return None;
+        }
+        Some(&self.bytes[start..self.at])
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Option<T> {
+        parse(self.next()?)
+    }
+}
+
+fn parse<T: std::str::FromStr>(token: &[u8]) -> Option<T> {
+    std::str::from_utf8(token).ok()?.parse().ok()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// One pass over the objects read so far, finding those still needed
+struct Walk<'a> {
+    document: &'a Document,
+    /// Numbers of objects referred to but not read
+    missing: BTreeSet<u32>,
+    seen: HashSet<ObjectId>,
+    /// The pages chosen, once the page tree above them is read
+    pages: Option<Vec<ObjectId>>,
+}
+
+impl<'a> Walk<'a> {
+    fn new(document: &'a Document) -> Self {
+        Self {
+            document,
+            missing: BTreeSet::new(),
+            seen: HashSet::new(),
+            pages: None,
+        }
+    }
+
+    fn run(&mut self, trailer: &'a Dictionary, selection: &PageSelection) -> Result<()> {
+        for key in [b"Info".as_slice(), b"Encrypt"] {
+            if let Ok(value) = trailer.get(key) {
+                self.follow(value);
+            }
+        }
+        let Some(catalog) = trailer
+            .get(b"Root")
+            .ok()
+            .and_then(|root| self.resolve(root))
+            .and_then(|root| root.as_dict().ok())
+        else {
+            return Ok(());
+        };
+        for (key, value) in catalog.iter() {
+            if !SKIPPED.contains(&key.as_slice()) {
+                self.follow(value);
+            }
+        }
+        let Some(root) = catalog
+            .get(b"Pages")
+            .ok()
+            .and_then(|pages| self.resolve(pages))
+            .and_then(|pages| pages.as_dict().ok())
+        else {
+            return Ok(());
+        };
+        let count = root.get(b"Count").and_then(Object::as_i64).unwrap_or(0);
+        let numbers = selection.pages(count.clamp(0, u32::MAX as i64) as u32);
+        if numbers.is_empty() {
+            return Err(PdfError::InvalidInput(format!(
+                "pages {} are not in the document, which has {}",
+                selection, count
+            )));
+        }
+        let pages: Option<Vec<ObjectId>> = numbers
+            .iter()
+            .map(|&number| self.page(root, number - 1))
+            .collect();
+        self.pages = pages;
+        Ok(())
+    }
+
+    /// The page at `index` under `root`, from 0, reading only the tree
+    /// nodes on the way there and the objects the page and those nodes need
+    fn page(&mut self, root: &'a Dictionary, mut index: u32) -> Option<ObjectId> {
+        let mut node = root;
+        for _ in 0..limits::MAX_REFERENCE_DEPTH {
+            // Inherited resources and the like
+            for (key, value) in node.iter() {
+                if key != b"Kids" {
+                    self.follow(value);
+                }
+            }
+            let kids = node
+                .get(b"Kids")
+                .ok()
+                .and_then(|kids| self.resolve(kids))?
+                .as_array()
+                .ok()?;
+            let count = node.get(b"Count").and_then(Object::as_i64).ok();
+
+            // When a node counts as many pages as it has kids, they are all
+            // pages, and the others need not be read
+            if count == Some(kids.len() as i64)
+                && let Some(&Object::Reference(id)) = kids.get(index as usize)
+            {
+                let kid = self.get(id)?.as_dict().ok()?;
+                if !kid.type_is(b"Pages") {
+                    return Some(self.leaf(id, kid));
+                }
+            }
+
+            let kids: Vec<(ObjectId, Option<&'a Dictionary>)> = kids
+                .iter()
+                .filter_map(|kid| kid.as_reference().ok())
+                .map(|id| (id, self.get(id).and_then(|kid| kid.as_dict().ok())))
+                .collect();
+            let mut next = None;
+            for (id, kid) in kids {
+                let kid = kid?;
+                let pages = if kid.type_is(b"Pages") {
+                    kid.get(b"Count").and_then(Object::as_i64).unwrap_or(0) as u32
+                } else {
+                    1
+                };
+                if index < pages {
+                    next = Some((id, kid));
+                    break;
+                }
+                index -= pages;
+            }
+            let (id, kid) = next?;
+            if !kid.type_is(b"Pages") {
+                return Some(self.leaf(id, kid));
+            }
+            node = kid;
+        }
+        None
+    }
+
+    fn leaf(&mut self, id: ObjectId, page: &'a Dictionary) -> ObjectId {
+        self.seen.insert(id);
+        for (_, value) in page.iter() {
+            self.follow(value);
+        }
+        id
+    }
+
+    /// The object `id`, noting it as missing when it has not been read
+    fn get(&mut self, id: ObjectId) -> Option<&'a Object> {
+        let object = self.document.objects.get(&id);
+        if object.is_none() {
+            self.missing.insert(id.0);
+        }
+        object
+    }
+
+    fn resolve(&mut self, object: &'a Object) -> Option<&'a Object> {
+        let mut object = object;
+        for _ in 0..limits::MAX_REFERENCE_DEPTH {
+            match object {
+                Object::Reference(id) => object = self.get(*id)?,
+                other => return Some(other),
+            }
+        }
+        None
+    }
+
+    /// Everything `object` refers to, except other pages: page tree nodes
+    /// are not gone into, nor are the pages destinations point at
+    fn follow(&mut self, object: &'a Object) {
+        let mut stack = vec![object];
+        while let Some(object) = stack.pop() {
+            match object {
+                Object::Reference(id) => {
+                    if self.seen.insert(*id)
+                        && let Some(target) = self.get(*id)
+                        && !is_page_tree(target)
+                    {
+                        stack.push(target);
+                    }
+                }
+                Object::Array(items) => {
+                    // `[page /XYZ left top zoom]` and other destinations
+                    let destination = matches!(
+                        items.as_slice(),
+                        [Object::Reference(_), Object::Name(_), ..]
+                    );
+                    stack.extend(items.iter().skip(usize::from(destination)));
+                }
+                Object::Dictionary(dict) => stack.extend(dict.iter().map(|(_, value)| value)),
+                Object::Stream(stream) => stack.extend(stream.dict.iter().map(|(_, value)| value)),
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_page_tree(object: &Object) -> bool {
+    object
+        .as_dict()
+        .is_ok_and(|dict| dict.type_is(b"Page") || dict.type_is(b"Pages"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::add_test_font;
+    use lopdf::{Stream, dictionary};
+
+    /// A file in memory, counting the bytes read
+    struct Memory {
+        bytes: Vec<u8>,
+        read: u64,
+    }
+
+    impl RangeSource for Memory {
+        fn size(&self) -> u64 {
+            self.bytes.len() as u64
+        }
+
+        fn read(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+            self.read += range.end - range.start;
+            Ok(self.bytes[range.start as usize..range.end as usize].to_vec())
+        }
+    }
+
+    /// Three pages, each with a long content stream
+    fn document() -> Vec<u8> {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.new_object_id();
+        add_test_font(&mut doc, font_id);
+        let kids: Vec<Object> = ["one", "two", "three"]
+            .iter()
+            .map(|name| {
+                let mut content = format!("BT /F1 12 Tf 50 700 Td (Page {}) Tj ET\n", name);
+                // Drawing that takes up room without adding text
+                for i in 0..4000 {
+                    content.push_str(&format!("{} {} m {} 0 l S\n", i, i, i));
+                }
+                let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+                doc.add_object(dictionary! {
+                    "Type" => "Page",
+                    "Parent" => pages_id,
+                    "Contents" => content_id,
+                })
+                .into()
+            })
+            .collect();
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => 3,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! { "Type" => "Catalog", "Pages" => pages_id });
+        doc.trailer.set("Root", catalog_id);
+        let mut bytes = Vec::new();
+        doc.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_load_reads_only_the_pages_chosen() {
+        let bytes = document();
+        let size = bytes.len() as u64;
+        let mut source = Memory { bytes, read: 0 };
+        let (document, updates) = load(&mut source, &"2".parse().unwrap()).unwrap();
+        assert_eq!(updates, 0);
+        assert_eq!(document.get_pages().len(), 1);
+        let text = crate::text::extract_page(&document, 1).unwrap();
+        assert!(text.contains("Page two"), "{}", text);
+        assert!(source.read < size / 2, "read {} of {}", source.read, size);
+
+        assert!(load(&mut source, &"4-".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_stream_section() {
+        let rows = [0u8, 0, 0, 255, 1, 0, 15, 0, 2, 0, 4, 1];
+        let mut bytes =
+            b"5 0 obj\n<< /Type /XRef /W [1 2 1] /Size 3 /Root 1 0 R /Length 12 >>\nstream\n"
+                .to_vec();
+        bytes.extend_from_slice(&rows);
+        bytes.extend_from_slice(b"\nendstream\nendobj\n");
+        let (entries, trailer) = stream_section(&bytes, "1.5").unwrap();
+        assert_eq!(
+            entries,
+            [
+                (0, Entry::Free),
+                (
+                    1,
+                    Entry::Object {
+                        offset: 15,
+                        generation: 0
+                    }
+                ),
+                (2, Entry::Compressed { container: 4 }),
+            ]
+        );
+        assert!(trailer.has(b"Root"));
+        // Cut short, the section is read again with more bytes
+        assert_eq!(stream_section(&bytes[..60], "1.5"), None);
+    }
+
+    #[test]
+    fn test_direct_length() {
+        let object =
+            b"4 0 obj\n<< /Length 9 0 R /Filter /FlateDecode >>\nstream\r\nabc\nendstream\nendobj";
+        assert_eq!(
+            direct_length(object).as_ref(),
+            b"4 0 obj\n<< /Length 3 /Filter /FlateDecode >>\nstream\r\nabc\nendstream\nendobj"
+        );
+        // Direct lengths and other keys are left alone
+        let direct = b"4 0 obj\n<< /Length1 9 0 R /Length 3 >>\nstream\nabc\nendstream\nendobj";
+        assert_eq!(direct_length(direct).as_ref(), direct.as_slice());
+        let font = b"4 0 obj\n<< /Length1 9 0 R /Length 8 0 R >>\nstream\nabc\nendstream\nendobj";
+        assert_eq!(
+            direct_length(font).as_ref(),
+            b"4 0 obj\n<< /Length1 9 0 R /Length 3 >>\nstream\nabc\nendstream\nendobj"
+        );
+    }
+}
@@ -0,0 +1,277 @@
+use super::limits::{self, MAX_REFERENCE_DEPTH};
+use super::png::ColorType;
+use lopdf::{Dictionary, Document, Object};
+
+/// Colour space of image samples, limited to what can be converted to PNG
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    /// Palette lookup into `base`, with `base.components()` bytes per entry
+    Indexed {
+        base: Box<ColorSpace>,
+        palette: Vec<u8>,
+    },
+}
+
+impl ColorSpace {
+    /// Colour space of an image XObject; stencil masks are treated as gray
+    pub fn of_image(document: &Document, dict: &Dictionary) -> Option<Self> {
+        if dict
+            .get(b"ImageMask")
+            .and_then(Object::as_bool)
+            .unwrap_or(false)
+        {
+            return Some(ColorSpace::Gray);
+        }
+        Self::parse(document, dict.get(b"ColorSpace").ok()?, 0)
+    }
+
+    /// `depth` counts the colour spaces and references already followed, so a
+    /// colour space that refers back to itself ends instead of recursing forever
+    fn parse(document: &Document, object: &Object, depth: usize) -> Option<Self> {
+        if depth > MAX_REFERENCE_DEPTH {
+            return None;
+        }
+        match object {
+            Object::Reference(id) => {
+                Self::parse(document, document.get_object(*id).ok()?, depth + 1)
+            }
+            Object::Name(name) => Self::from_family(name),
+            Object::Array(items) => {
+                let family = items.first()?.as_name().ok()?;
+                match family {
+                    b"ICCBased" => {
+                        let id = items.get(1)?.as_reference().ok()?;
+                        let stream = document.get_object(id).and_then(Object::as_stream).ok()?;
+                        match stream.dict.get(b"N").and_then(Object::as_i64) {
+                            Ok(1) => Some(ColorSpace::Gray),
+                            Ok(3) => Some(ColorSpace::Rgb),
+                            Ok(4) => Some(ColorSpace::Cmyk),
+                            _ => Self::parse(
+                                document,
+                                stream.dict.get(b"Alternate").ok()?,
+                                depth + 1,
+                            ),
+                        }
+                    }
+                    b"Indexed" | b"I" => {
+                        let base = Self::parse(document, items.get(1)?, depth + 1)?;
+                        let palette = match items.get(3)? {
+                            Object::String(bytes, _) => bytes.clone(),
+                            Object::Reference(id) => {
+                                let stream =
+                                    document.get_object(*id).and_then(Object::as_stream).ok()?;
+                                limits::decompressed_content(stream)?
+                            }
+                            _ => return None,
+                        };
+                        Some(ColorSpace::Indexed {
+                            base: Box::new(base),
+                            palette,
+                        })
+                    }
+                    _ => Self::from_family(family),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn from_family(name: &[u8]) -> Option<Self> {
+        match name {
+            b"DeviceGray" | b"CalGray" | b"G" => Some(ColorSpace::Gray),
+            b"DeviceRGB" | b"CalRGB" | b"RGB" => Some(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => Some(ColorSpace::Cmyk),
+            _ => None,
+        }
+    }
+
+    /// Colour space with the given number of components per pixel
+    pub fn with_components(components: usize) -> Option<Self> {
+        match components {
+            1 => Some(ColorSpace::Gray),
+            3 => Some(ColorSpace::Rgb),
+            4 => Some(ColorSpace::Cmyk),
+            _ => None,
+        }
+    }
+
+    /// Samples per pixel in the image data
+    pub fn components(&self) -> usize {
+        match self {
+            ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+        }
+    }
+
+    /// PNG colour type the samples are converted to
+    pub fn output(&self) -> ColorType {
+        match self {
+            ColorSpace::Gray => ColorType::Gray,
+            ColorSpace::Rgb | ColorSpace::Cmyk => ColorType::Rgb,
+            ColorSpace::Indexed { base, .. } => base.output(),
+        }
+    }
+
+    /// Append one pixel, given as 8-bit components (or a palette index), in the output colour type
+    fn push_pixel(&self, out: &mut Vec<u8>, pixel: &[u8]) {
+        match self {
+            ColorSpace::Gray | ColorSpace::Rgb => out.extend_from_slice(pixel),
+            ColorSpace::Cmyk => {
+                let k = 255 - pixel[3] as u32;
+                for &c in &pixel[..3] {
+                    out.push(((255 - c as u32) * k / 255) as u8);
+                }
+            }
+            ColorSpace::Indexed { base, palette } => {
+                let n = base.components();
+                let start = pixel[0] as usize * n;
+                match palette.get(start..start + n) {
+                    Some(entry) => base.push_pixel(out, entry),
+                    None => out.extend(std::iter::repeat_n(0, base.output().channels())),
+                }
+            }
+        }
+    }
+}
+
+/// Convert packed image samples to 8-bit gray or RGB pixels.
+///
+/// `decode` is the image's `/Decode` array (empty for the default mapping);
+/// it is ignored for indexed images.
+pub fn to_pixels(
+    samples: &[u8],
+    width: usize,
+    height: usize,
+    bits: u32,
+    space: &ColorSpace,
+    decode: &[f32],
+) -> Option<(ColorType, Vec<u8>)> {
+    if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return None;
+    }
+    let components = space.components();
+    let row_len = (width * components * bits as usize).div_ceil(8);
+    if samples.len() < row_len * height {
+        return None;
+    }
+
+    let indexed = matches!(space, ColorSpace::Indexed { .. });
+    let max = ((1u32 << bits) - 1) as f32;
+    let ranges: Vec<(f32, f32)> = (0..components)
+        .map(|c| match decode.get(2 * c..2 * c + 2) {
+            Some(&[low, high]) => (low, high),
+            _ => (0.0, 1.0),
+        })
+        .collect();
+
+    let output = space.output();
+    let mut out = Vec::with_capacity(width * height * output.channels());
+    let mut pixel = [0u8; 4];
+    for row in samples.chunks(row_len).take(height) {
+        for x in 0..width {
+            for (c, slot) in pixel.iter_mut().enumerate().take(components) {
+                let value = sample(row, x * components + c, bits);
+                *slot = if indexed {
+                    value as u8
+                } else {
+                    let (low, high) = ranges[c];
+                    let level = low + value as f32 / max * (high - low);
+                    (level.clamp(0.0, 1.0) * 255.0).round() as u8
+                };
+            }
+            space.push_pixel(&mut out, &pixel[..components]);
+        }
+    }
+    Some((output, out))
+}
+
+/// The `index`-th sample of a row packed at `bits` per sample
+fn sample(row: &[u8], index: usize, bits: u32) -> u32 {
+    match bits {
+        8 => row[index] as u32,
+        16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]) as u32,
+        _ => {
+            let bit = index * bits as usize;
+            let shift = 8 - bits as usize - bit % 8;
+            (row[bit / 8] as u32 >> shift) & ((1 << bits) - 1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cmyk_converts_to_rgb() {
+        let (color, pixels) = to_pixels(
+            &[0, 0, 0, 255, 255, 0, 0, 0],
+            2,
+            1,
+            8,
+            &ColorSpace::Cmyk,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(color, ColorType::Rgb);
+        assert_eq!(pixels, [0, 0, 0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_one_bit_gray_expands_and_honours_decode() {
+        let (_, pixels) = to_pixels(&[0b1010_0000], 4, 1, 1, &ColorSpace::Gray, &[]).unwrap();
+        assert_eq!(pixels, [255, 0, 255, 0]);
+        let (_, inverted) =
+            to_pixels(&[0b1010_0000], 4, 1, 1, &ColorSpace::Gray, &[1.0, 0.0]).unwrap();
+        assert_eq!(inverted, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_indexed_looks_up_palette() {
+        let space = ColorSpace::Indexed {
+            base: Box::new(ColorSpace::Rgb),
+            palette: vec![255, 0, 0, 0, 0, 255],
+        };
+        let (color, pixels) = to_pixels(&[0b0001_0000], 2, 1, 2, &space, &[]).unwrap();
+        assert_eq!(color, ColorType::Rgb);
+        assert_eq!(pixels, [255, 0, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_self_referencing_color_space_ends() {
+        use lopdf::{Stream, dictionary};
+
+        // An ICC profile whose alternate is the ICC colour space itself
+        let mut document = Document::with_version("1.5");
+        let profile = document.new_object_id();
+        let space = document.add_object(Object::Array(vec![
+            Object::Name(b"ICCBased".to_vec()),
+            Object::Reference(profile),
+        ]));
+        document.objects.insert(
+            profile,
+            Object::Stream(Stream::new(
+                dictionary! { "N" => 5, "Alternate" => space },
+                vec![],
+            )),
+        );
+        let image = dictionary! { "ColorSpace" => space };
+        assert_eq!(ColorSpace::of_image(&document, &image), None);
+    }
+
+    #[test]
+    fn test_sixteen_bit_samples_keep_high_byte() {
+        let (_, pixels) =
+            to_pixels(&[0xFF, 0xFF, 0x00, 0x00], 2, 1, 16, &ColorSpace::Gray, &[]).unwrap();
+        assert_eq!(pixels, [255, 0]);
+    }
+
+    #[test]
+    fn test_short_data_is_rejected() {
+        assert!(to_pixels(&[0; 5], 2, 1, 8, &ColorSpace::Rgb, &[]).is_none());
+    }
+}
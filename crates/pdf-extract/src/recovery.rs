@@ -0,0 +1,185 @@
+use log::debug;
+use lopdf::{Document, Object};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// A complete `N G obj ... endobj` found in the file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Found {
+    generation: u16,
+    offset: usize,
+    end: usize,
+}
+
+/// Rebuild a document whose cross-reference table is missing or damaged.
+///
+/// The file is scanned for `N G obj ... endobj` markers, everything after the
+/// last complete object (a truncated object, a broken xref table) is cut off,
+/// and a fresh xref table and trailer are written for what remains. Returns
+/// the document and the number of objects recovered.
+pub fn rebuild(bytes: &[u8]) -> Option<(Document, usize)> {
+    let objects = find_objects(bytes);
+    let end = objects.values().map(|found| found.end).max()?;
+    let size = objects.keys().max()? + 1;
+
+    let mut rebuilt = bytes[..end].to_vec();
+    rebuilt.push(b'\n');
+    let xref_offset = rebuilt.len();
+    let mut xref = format!("xref\n0 {}\n0000000000 65535 f\r\n", size);
+    for id in 1..size {
+        match objects.get(&id) {
+            Some(found) => {
+                let _ = write!(xref, "{:010} {:05} n\r\n", found.offset, found.generation);
+            }
+            None => xref.push_str("0000000000 00000 f\r\n"),
+        }
+    }
+    let _ = write!(
+        xref,
+        "trailer\n<< /Size {} >>\nstartxref\n{}\n%%EOF\n",
+        size, xref_offset
+    );
+    rebuilt.extend_from_slice(xref.as_bytes());
+
+    let mut document = Document::load_mem(&rebuilt).ok()?;
+    // The catalog may sit in an object stream, so it is looked for after loading
+    let catalog = document
+        .objects
+        .iter()
+        .find(|(_, object)| object.as_dict().is_ok_and(|dict| dict.type_is(b"Catalog")))
+        .map(|(&id, _)| id)?;
+    document.trailer.set("Root", Object::Reference(catalog));
+    if let Some(info) = document
+        .objects
+        .iter()
+        .find(|(_, object)| {
+            object.as_dict().is_ok_and(|dict| {
+                !dict.has(b"Type") && (dict.has(b"Producer") || dict.has(b"Title"))
+            })
+        })
+        .map(|(&id, _)| id)
+    {
+        document.trailer.set("Info", Object::Reference(info));
+    }
+    debug!(
+        "Rebuilt cross-reference table from {} objects",
+        objects.len()
+    );
+    Some((document, objects.len()))
+}
+
+/// Complete objects in the file by number; a later definition of an object
+/// (from an incremental update) replaces an earlier one
+fn find_objects(bytes: &[u8]) -> BTreeMap<u32, Found> {
+    let headers: Vec<(u32, u16, usize, usize)> = (0..bytes.len())
+        .filter(|&i| bytes[i..].starts_with(b"obj"))
+        .filter_map(|i| {
+            object_header(bytes, i).map(|(id, generation, start)| (id, generation, start, i + 3))
+        })
+        .collect();
+
+    let mut objects = BTreeMap::new();
+    for (k, &(id, generation, offset, body)) in headers.iter().enumerate() {
+        // An object must end before the next one starts
+        let limit = headers.get(k + 1).map_or(bytes.len(), |next| next.2);
+        if let Some(end) = find(&bytes[body..limit], b"endobj") {
+            objects.insert(
+                id,
+                Found {
+                    generation,
+                    offset,
+                    end: body + end + b"endobj".len(),
+                },
+            );
+        }
+    }
+    objects
+}
+
+/// Object number, generation and start offset of an `N G obj` header whose
+/// `obj` keyword is at `keyword`
+fn object_header(bytes: &[u8], keyword: usize) -> Option<(u32, u16, usize)> {
+    let delimiter = |b: u8| b.is_ascii_whitespace() || b"<[/(%".contains(&b);
+    if bytes.get(keyword + 3).is_some_and(|&b| !delimiter(b)) {
+        return None;
+    }
+
+    let mut i = keyword;
+    let number = |i: &mut usize| -> Option<u32> {
+        let end = *i;
+        while *i > 0 && bytes[*i - 1].is_ascii_whitespace() {
+            *i -= 1;
+        }
+        let digits_end = *i;
+        while *i > 0 && bytes[*i - 1].is_ascii_digit() {
+            *i -= 1;
+        }
+        if digits_end == end || *i == digits_end || digits_end - *i > 10 {
+            return None;
+        }
+        std::str::from_utf8(&bytes[*i..digits_end])
+            .ok()?
+            .parse()
+            .ok()
+    };
+    let generation = number(&mut i)?;
+    let id = number(&mut i)?;
+    let line_start = i == 0 || bytes[i - 1].is_ascii_whitespace();
+    (line_start && id > 0).then_some((id, u16::try_from(generation).ok()?, i))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::create_valid_test_pdf;
+    use tempfile::TempDir;
+
+    fn valid_pdf() -> Vec<u8> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("valid.pdf");
+        create_valid_test_pdf(&path).unwrap();
+        std::fs::read(&path).unwrap()
+    }
+
+    #[test]
+    fn test_rebuild_truncated_file() {
+        // Cut off in the middle of the cross-reference stream
+        let bytes = valid_pdf();
+        let xref = find(&bytes, b"/Type/XRef").unwrap();
+        let truncated = &bytes[..xref];
+        assert!(Document::load_mem(truncated).is_err());
+
+        let (document, recovered) = rebuild(truncated).unwrap();
+        assert_eq!(recovered, 5);
+        assert_eq!(document.get_pages().len(), 1);
+        assert!(
+            document
+                .extract_text(&[1])
+                .unwrap()
+                .contains("Sample Document")
+        );
+    }
+
+    #[test]
+    fn test_truncated_object_is_dropped() {
+        let bytes = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n2 0 obj\n<< /Length 99 >>\nstream\nBT (cut";
+        let objects = find_objects(bytes);
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[&1].offset, 9);
+    }
+
+    #[test]
+    fn test_object_header() {
+        let bytes = b"x\n12 0 obj<<>>endobj 3 0 objection";
+        let keyword = find(bytes, b"obj").unwrap();
+        assert_eq!(object_header(bytes, keyword), Some((12, 0, 2)));
+        let later = find(&bytes[25..], b"obj").unwrap() + 25;
+        assert_eq!(object_header(bytes, later), None);
+    }
+}
@@ -0,0 +1,281 @@
+//! Redactions: text under a Redact annotation, or painted over with a dark
+//! box, is left out of the text extracted. Neither removes the text from the
+//! file, which is what applying a redaction does; an applied redaction
+//! leaves a dark box over nothing.
+
+use super::geometry::{Color, Rect};
+use super::layout::{MarkupKind, PageLayout, TextRun};
+use super::types::Redactions;
+use lopdf::{Document, Object, ObjectId};
+
+/// Brightest channel a fill may have to count as a redaction box
+const DARK: u8 = 64;
+
+/// Heights, in points, of the boxes over no text counted as applied
+/// redactions; smaller and larger ones are rules and artwork
+const APPLIED_HEIGHT: std::ops::RangeInclusive<f32> = 4.0..=72.0;
+
+/// What the redactions of one page hide
+#[derive(Debug, Default, PartialEq)]
+pub struct Hidden {
+    /// Runs under Redact annotations
+    pub unapplied: usize,
+    /// Runs painted over with a dark box
+    pub covered: usize,
+    /// Dark boxes over no text
+    pub applied: usize,
+}
+
+impl Hidden {
+    /// Whether any text was left out
+    pub fn any(&self) -> bool {
+        self.unapplied + self.covered > 0
+    }
+}
+
+impl Redactions {
+    /// Add what the redactions of `page` hid
+    pub(crate) fn add(&mut self, page: u32, hidden: &Hidden) {
+        if hidden.unapplied > 0 {
+            self.unapplied.push(page);
+        }
+        if hidden.covered > 0 {
+            self.covered.push(page);
+        }
+        self.applied += hidden.applied;
+    }
+}
+
+/// Whether a page may hide text: it has Redact annotations, or its content
+/// fills a path, which may be a box of any shape. Pages that do are
+/// extracted from their layout.
+pub fn may_hide(document: &Document, page_id: ObjectId) -> bool {
+    has_redact_annotations(document, page_id)
+        || document
+            .get_page_content(page_id)
+            .is_ok_and(|content| fills_path(&content))
+}
+
+fn has_redact_annotations(document: &Document, page_id: ObjectId) -> bool {
+    fn resolve<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Object> {
+        match object {
+            Object::Reference(id) => document.get_object(*id).ok(),
+            other => Some(other),
+        }
+    }
+    document
+        .get_dictionary(page_id)
+        .ok()
+        .and_then(|page| page.get(b"Annots").ok())
+        .and_then(|annots| resolve(document, annots))
+        .and_then(|annots| annots.as_array().ok())
+        .is_some_and(|annots| {
+            annots
+                .iter()
+                .filter_map(|annot| resolve(document, annot))
+                .any(|annotation| {
+                    annotation
+                        .as_dict()
+                        .and_then(|dict| dict.get(b"Subtype"))
+                        .and_then(Object::as_name)
+                        .is_ok_and(|subtype| subtype == b"Redact")
+                })
+        })
+}
+
+/// Whether a content stream has a fill operator
+fn fills_path(content: &[u8]) -> bool {
+    content
+        .split(|byte| byte.is_ascii_whitespace())
+        .any(|token| matches!(token, b"f" | b"F" | b"f*" | b"B" | b"B*" | b"b" | b"b*"))
+}
+
+/// Remove the runs a redaction hides from `page`: those under Redact
+/// annotations and those a dark box is painted over. A run partly hidden
+/// goes whole, so the part that shows is lost rather than the part hidden
+/// kept.
+pub fn hide(page: &mut PageLayout) -> Hidden {
+    let redact: Vec<Rect> = page
+        .markup
+        .iter()
+        .filter(|markup| markup.kind == MarkupKind::Redact)
+        .flat_map(|markup| markup.rects.iter().copied())
+        .collect();
+    let boxes: Vec<(Rect, usize)> = page
+        .paths
+        .iter()
+        .filter(|path| path.fill.is_some_and(is_dark))
+        .map(|path| (path.bounds, path.runs_before))
+        .collect();
+
+    let mut hidden = Hidden::default();
+    let mut under_box = vec![false; boxes.len()];
+    let mut keep = Vec::with_capacity(page.runs.len());
+    for (i, run) in page.runs.iter().enumerate() {
+        if redact.iter().any(|area| hides(area, run)) {
+            hidden.unapplied += 1;
+            keep.push(false);
+            continue;
+        }
+        let mut covered = false;
+        for (j, (area, runs_before)) in boxes.iter().enumerate() {
+            if hides(area, run) {
+                under_box[j] = true;
+                covered |= i < *runs_before;
+            }
+        }
+        if covered {
+            hidden.covered += 1;
+        }
+        keep.push(!covered);
+    }
+    // Boxes text is drawn on are backgrounds, not redactions
+    hidden.applied = boxes
+        .iter()
+        .zip(&under_box)
+        .filter(|((area, _), under)| {
+            !**under && APPLIED_HEIGHT.contains(&area.height) && area.width >= area.height
+        })
+        .count();
+    let mut keep = keep.into_iter();
+    page.runs.retain(|_| keep.next().unwrap_or(true));
+    hidden
+}
+
+fn is_dark(color: Color) -> bool {
+    color.0.max(color.1).max(color.2) <= DARK
+}
+
+/// Whether `area` spans at least half the height of `run` where they meet
+fn hides(area: &Rect, run: &TextRun) -> bool {
+    let size = run.size.max(1.0);
+    let bottom = run.y - size * 0.2;
+    let top = bottom + size;
+    let overlap = top.min(area.top()) - bottom.max(area.y);
+    let across = (run.x + run.width).min(area.right()) - run.x.max(area.x);
+    overlap >= size * 0.5 && across > 0.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::PathShape;
+    use crate::layout::Markup;
+
+    fn run(text: &str, x: f32, y: f32) -> TextRun {
+        TextRun {
+            text: text.to_string(),
+            font: "Helvetica".to_string(),
+            size: 12.0,
+            x,
+            y,
+            width: 6.0 * text.len() as f32,
+            color: Color::BLACK,
+            mcid: None,
+        }
+    }
+
+    fn filled(rect: Rect, color: Color, runs_before: usize) -> PathShape {
+        PathShape {
+            segments: Vec::new(),
+            stroke: None,
+            fill: Some(color),
+            line_width: 1.0,
+            bounds: rect,
+            runs_before,
+        }
+    }
+
+    #[test]
+    fn test_hide() {
+        let mut page = PageLayout {
+            runs: vec![
+                run("Patient:", 72.0, 700.0),
+                run("John Smith", 130.0, 700.0),
+                run("SSN 123-45-6789", 72.0, 680.0),
+                run("Header", 72.0, 600.0),
+                run("Visible", 72.0, 580.0),
+            ],
+            paths: vec![
+                // Painted over the name after it was drawn
+                filled(
+                    Rect::from_corners(128.0, 696.0, 200.0, 712.0),
+                    Color::BLACK,
+                    2,
+                ),
+                // A dark header background, drawn before its text
+                filled(
+                    Rect::from_corners(70.0, 596.0, 200.0, 612.0),
+                    Color(20, 20, 40),
+                    3,
+                ),
+                // An applied redaction: nothing left beneath
+                filled(
+                    Rect::from_corners(72.0, 500.0, 200.0, 515.0),
+                    Color::BLACK,
+                    5,
+                ),
+                // A light box over text is a highlight
+                filled(
+                    Rect::from_corners(70.0, 576.0, 200.0, 592.0),
+                    Color(255, 255, 0),
+                    5,
+                ),
+            ],
+            markup: vec![Markup {
+                kind: MarkupKind::Redact,
+                rects: vec![Rect::from_corners(70.0, 676.0, 180.0, 692.0)],
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            hide(&mut page),
+            Hidden {
+                unapplied: 1,
+                covered: 1,
+                applied: 1,
+            }
+        );
+        let kept: Vec<&str> = page.runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(kept, ["Patient:", "Header", "Visible"]);
+    }
+
+    #[test]
+    fn test_fills_path() {
+        assert!(fills_path(b"0 g 100 700 80 14 re\nf\nBT ET"));
+        assert!(fills_path(b"0 g 55 96 m 155 96 l 155 112 l 55 112 l h f"));
+        assert!(fills_path(b"0 g 55 96 m 155 96 l 155 112 l h b*"));
+        assert!(!fills_path(b"0 0 612 792 re W n BT (x) Tj ET"));
+        assert!(!fills_path(b"100 700 m 200 700 l S"));
+    }
+
+    #[test]
+    fn test_add() {
+        let mut redactions = Redactions::default();
+        redactions.add(
+            2,
+            &Hidden {
+                unapplied: 3,
+                ..Default::default()
+            },
+        );
+        redactions.add(
+            4,
+            &Hidden {
+                covered: 1,
+                applied: 2,
+                ..Default::default()
+            },
+        );
+        redactions.add(
+            5,
+            &Hidden {
+                covered: 1,
+                ..Default::default()
+            },
+        );
+        assert_eq!(redactions.unapplied, [2]);
+        assert_eq!(redactions.covered, [4, 5]);
+        assert_eq!(redactions.applied, 2);
+    }
+}
@@ -0,0 +1,226 @@
+use super::caption;
+use super::decoration;
+use super::definition;
+use super::footnote;
+use super::geometry::Rect;
+use super::images::ImageCollector;
+use super::layout::{PageLayout, TextRun};
+use super::math::{self, TaggedMath};
+use super::types::ExtractOptions;
+use super::vector;
+use std::collections::HashMap;
+
+/// An extracted image or drawing to reference from the page text
+struct Figure<'a> {
+    bounds: Rect,
+    file_name: &'a str,
+}
+
+/// Render a page layout as text, interleaving image references by vertical position.
+///
+/// Caption lines next to an image become its alt text and are emitted as an
+/// italic line below it instead of in the running text. Labels inside an
+/// extracted drawing are part of its SVG and are dropped from the text.
+///
+/// Footnote text is taken out of the page, appended to `notes` and replaced
+/// by a marker in the configured style. Term/definition layouts become
+/// definition list items, wide horizontal lines `---`, underlined text
+/// emphasis, bold or `<u>`, struck-through text `~~text~~` and coloured text
+/// HTML spans when enabled.
+pub fn render_layout(
+    layout: &PageLayout,
+    page_num: u32,
+    options: &ExtractOptions,
+    images: &ImageCollector,
+    notes: &mut Vec<String>,
+) -> String {
+    let mut figures: Vec<Figure> = layout
+        .images
+        .iter()
+        .filter_map(|image| {
+            images.name(image.id).map(|file_name| Figure {
+                bounds: image.bounds,
+                file_name,
+            })
+        })
+        .chain(
+            images
+                .drawings(page_num)
+                .map(|(bounds, file_name)| Figure { bounds, file_name }),
+        )
+        .collect();
+    figures.sort_by(|a, b| b.bounds.top().total_cmp(&a.bounds.top()));
+
+    let in_drawing = |run: &TextRun| {
+        images
+            .drawings(page_num)
+            .any(|(bounds, _)| bounds.contains(run.x, run.y))
+    };
+    let lines: Vec<Vec<&TextRun>> = layout
+        .lines()
+        .into_iter()
+        .map(|line| line.into_iter().filter(|run| !in_drawing(run)).collect())
+        .filter(|line: &Vec<&TextRun>| !line.is_empty())
+        .collect();
+
+    // Caption line index -> index of the figure it belongs to
+    let captions: HashMap<usize, usize> = figures
+        .iter()
+        .enumerate()
+        .filter_map(|(i, figure)| {
+            caption::find_caption(&lines, &figure.bounds).map(|line| (line, i))
+        })
+        .collect();
+    let caption_for = |figure: usize| {
+        captions
+            .iter()
+            .find(|&(_, &owner)| owner == figure)
+            .map(|(&line, _)| caption::line_text(&lines[line]))
+    };
+
+    let page_notes = match options.footnotes {
+        Some(_) => footnote::find(&lines),
+        None => footnote::PageFootnotes::default(),
+    };
+    let first_note = notes.len();
+    notes.extend(page_notes.notes.iter().map(|note| note.text.clone()));
+
+    let definitions = if options.definition_lists {
+        definition::find(&lines)
+    } else {
+        Vec::new()
+    };
+
+    // Rules inside an extracted drawing belong to its SVG
+    let rules: Vec<f32> = if options.horizontal_rules {
+        vector::find_rules(layout)
+            .into_iter()
+            .filter(|&y| {
+                !images
+                    .drawings(page_num)
+                    .any(|(bounds, _)| y >= bounds.y - 1.0 && y <= bounds.top() + 1.0)
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let mut pending_rules = rules.iter().peekable();
+
+    let mut tagged = TaggedMath::new(&layout.formulas, &lines, options.mark_lost_equations);
+
+    let mut pending = figures.iter().enumerate().peekable();
+    let mut out: Vec<String> = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let y = line[0].y;
+        while let Some((i, figure)) = pending.next_if(|(_, figure)| figure.bounds.top() >= y) {
+            out.push(image_reference(figure, caption_for(i), options, images));
+        }
+        // A rule needs text above it to separate anything
+        if pending_rules.next_if(|&&rule| rule > y).is_some() {
+            while pending_rules.next_if(|&&rule| rule > y).is_some() {}
+            if !out.is_empty() {
+                out.push(standalone("---"));
+            }
+        }
+        if captions.contains_key(&index) || page_notes.is_note_line(index) {
+            continue;
+        }
+        if let Some(entry) = definitions
+            .iter()
+            .find(|entry| entry.lines.contains(&index))
+        {
+            if entry.lines.start == index {
+                out.push(standalone(&entry.to_markdown()));
+            }
+            continue;
+        }
+        let has_marker = (0..line.len()).any(|i| page_notes.marker(index, i).is_some());
+        let underlined: Vec<bool> = match options.underline {
+            Some(_) => line
+                .iter()
+                .map(|run| decoration::is_underlined(run, layout))
+                .collect(),
+            None => Vec::new(),
+        };
+        let struck: Vec<bool> = if options.strikethrough {
+            line.iter()
+                .map(|run| decoration::is_struck(run, layout))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let colored = options.text_colors && line.iter().any(|run| decoration::is_colored(run));
+        if !has_marker && !underlined.contains(&true) && !struck.contains(&true) && !colored {
+            out.push(render_line(line, &mut tagged, options));
+            continue;
+        }
+
+        let mut runs: Vec<TextRun> = line.iter().map(|&run| run.clone()).collect();
+        if let Some(style) = options.footnotes {
+            for (i, run) in runs.iter_mut().enumerate() {
+                if let Some(note) = page_notes.marker(index, i) {
+                    run.text = footnote::marker(
+                        style,
+                        first_note + note + 1,
+                        &page_notes.notes[note].text,
+                    );
+                }
+            }
+        }
+        if let Some(style) = options.underline {
+            decoration::underline(&mut runs, &underlined, style);
+        }
+        if options.strikethrough {
+            decoration::strike(&mut runs, &struck);
+        }
+        if colored {
+            decoration::color_spans(&mut runs);
+        }
+        out.push(render_line(
+            &runs.iter().collect::<Vec<_>>(),
+            &mut tagged,
+            options,
+        ));
+    }
+    for (i, figure) in pending {
+        out.push(image_reference(figure, caption_for(i), options, images));
+    }
+
+    out.join("\n")
+}
+
+/// Render one line of text runs
+fn render_line(line: &[&TextRun], tagged: &mut TaggedMath, options: &ExtractOptions) -> String {
+    if options.math {
+        math::render_line(line, tagged, options.mark_lost_equations)
+    } else {
+        line.iter().map(|run| run.text.as_str()).collect()
+    }
+}
+
+/// Markdown image reference on its own paragraph, followed by its caption.
+///
+/// Alt text from the configured provider wins over the caption.
+fn image_reference(
+    figure: &Figure,
+    caption: Option<String>,
+    options: &ExtractOptions,
+    images: &ImageCollector,
+) -> String {
+    let alt = images
+        .alt_text(figure.file_name)
+        .or(caption.as_deref())
+        .map(caption::alt_text)
+        .unwrap_or_default();
+    let mut block = format!("![{}]({}{})", alt, options.image_prefix, figure.file_name);
+    if let Some(caption) = &caption {
+        block.push_str("\n\n\n");
+        block.push_str(&caption::caption_line(caption));
+    }
+    standalone(&block)
+}
+
+/// Surround a block with enough blank lines to survive text cleanup as its own paragraph
+pub fn standalone(block: &str) -> String {
+    format!("\n\n{}\n\n", block)
+}
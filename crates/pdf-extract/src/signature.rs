@@ -0,0 +1,389 @@
+//! Digital signatures: the signature fields of the form, who signed them
+//! and when, and whether the file changed after signing
+
+use super::limits;
+use super::metadata::parse_pdf_date;
+use super::types::SignatureInfo;
+use lopdf::{Dictionary, Document, Object};
+
+/// DER tags of the parts of a CMS signature read here
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const OBJECT_ID: u8 = 0x06;
+/// `[0]`: the content of a `ContentInfo`, the certificates of a `SignedData`
+/// and the version of a certificate
+const CONTEXT_0: u8 = 0xA0;
+
+/// Object identifier of the common name attribute (2.5.4.3)
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// The signatures of the document's signature fields; unsigned fields are
+/// left out. `length` is the size of the file without trailing whitespace,
+/// which the byte range of a signature reaches unless the file was changed
+/// after it was signed.
+pub fn signatures(document: &Document, length: u64) -> Vec<SignatureInfo> {
+    let Some(fields) = document
+        .catalog()
+        .ok()
+        .and_then(|catalog| resolve_dict(document, catalog.get(b"AcroForm").ok()?))
+        .and_then(|form| limits::resolve(document, form.get(b"Fields").ok()?))
+        .and_then(|fields| fields.as_array().ok())
+    else {
+        return Vec::new();
+    };
+    let mut found = Vec::new();
+    for field in fields {
+        visit(document, field, "", false, 0, length, &mut found);
+    }
+    found
+}
+
+/// Add the signature of `field`, or those of the fields below it
+fn visit(
+    document: &Document,
+    field: &Object,
+    parent_name: &str,
+    parent_is_signature: bool,
+    depth: usize,
+    length: u64,
+    found: &mut Vec<SignatureInfo>,
+) {
+    if depth > limits::MAX_REFERENCE_DEPTH {
+        return;
+    }
+    let Some(dict) = resolve_dict(document, field) else {
+        return;
+    };
+    let name = match dict
+        .get(b"T")
+        .ok()
+        .and_then(|t| lopdf::decode_text_string(t).ok())
+    {
+        Some(partial) if parent_name.is_empty() => partial,
+        Some(partial) => format!("{}.{}", parent_name, partial),
+        // A widget of the field above
+        None => parent_name.to_string(),
+    };
+    // The field type is inherited
+    let is_signature = dict
+        .get(b"FT")
+        .and_then(Object::as_name)
+        .map_or(parent_is_signature, |kind| kind == b"Sig");
+    if is_signature
+        && let Some(value) = dict
+            .get(b"V")
+            .ok()
+            .and_then(|value| resolve_dict(document, value))
+    {
+        found.push(signature(document, name.clone(), value, length));
+        return;
+    }
+    if let Some(kids) = dict
+        .get(b"Kids")
+        .ok()
+        .and_then(|kids| limits::resolve(document, kids))
+        .and_then(|kids| kids.as_array().ok())
+    {
+        for kid in kids {
+            visit(document, kid, &name, is_signature, depth + 1, length, found);
+        }
+    }
+}
+
+/// What the signature dictionary `value` of `field` tells
+fn signature(document: &Document, field: String, value: &Dictionary, length: u64) -> SignatureInfo {
+    let text = |key: &[u8]| {
+        limits::resolve(document, value.get(key).ok()?)
+            .and_then(|text| lopdf::decode_text_string(text).ok())
+            .map(|text| text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    };
+    let contents = value
+        .get(b"Contents")
+        .ok()
+        .and_then(|contents| limits::resolve(document, contents))
+        .and_then(|contents| contents.as_str().ok());
+    let covered = value
+        .get(b"ByteRange")
+        .ok()
+        .and_then(|range| limits::resolve(document, range))
+        .and_then(|range| range.as_array().ok())
+        .and_then(|range| {
+            let numbers: Vec<i64> = range.iter().filter_map(|n| n.as_i64().ok()).collect();
+            // Pairs of offset and length; the last one ends the signed bytes
+            numbers
+                .chunks_exact(2)
+                .map(|pair| pair[0].saturating_add(pair[1]))
+                .max()
+        });
+    SignatureInfo {
+        field,
+        signer: text(b"Name").or_else(|| contents.and_then(signer_common_name)),
+        signing_time: text(b"M").and_then(|date| parse_pdf_date(&date)),
+        modified_after_signing: covered.is_some_and(|end| u64::try_from(end).unwrap_or(0) < length),
+    }
+}
+
+fn resolve_dict<'a>(document: &'a Document, object: &'a Object) -> Option<&'a Dictionary> {
+    limits::resolve(document, object)?.as_dict().ok()
+}
+
+/// A DER element: its tag and its content
+#[derive(Debug, Clone, Copy)]
+struct Der<'a> {
+    tag: u8,
+    body: &'a [u8],
+}
+
+impl<'a> Der<'a> {
+    /// The element `data` starts with, and the bytes after it
+    fn read(data: &'a [u8]) -> Option<(Self, &'a [u8])> {
+        let (&tag, rest) = data.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (length, rest) = if first < 0x80 {
+            (first as usize, rest)
+        } else {
+            let bytes = (first & 0x7F) as usize;
+            if bytes == 0 || bytes > 4 || rest.len() < bytes {
+                return None;
+            }
+            let length = rest[..bytes]
+                .iter()
+                .fold(0usize, |length, &byte| length << 8 | byte as usize);
+            (length, &rest[bytes..])
+        };
+        (rest.len() >= length).then(|| {
+            (
+                Self {
+                    tag,
+                    body: &rest[..length],
+                },
+                &rest[length..],
+            )
+        })
+    }
+
+    /// The elements inside a constructed element
+    fn children(self) -> impl Iterator<Item = Der<'a>> {
+        let mut rest = self.body;
+        std::iter::from_fn(move || {
+            let (child, after) = Self::read(rest)?;
+            rest = after;
+            Some(child)
+        })
+    }
+
+    fn child(self, tag: u8) -> Option<Der<'a>> {
+        self.children().find(|child| child.tag == tag)
+    }
+}
+
+/// The common name of the certificate that made a CMS signature (the
+/// `Contents` of a `adbe.pkcs7.detached` or `ETSI.CAdES.detached`
+/// signature): the one whose serial number the first signer gives, or else
+/// the first certificate
+fn signer_common_name(contents: &[u8]) -> Option<String> {
+    // The signature is padded with zeros to the space reserved for it
+    let (content_info, _) = Der::read(contents)?;
+    let signed_data = content_info.child(CONTEXT_0)?.child(SEQUENCE)?;
+    let certificates: Vec<Der> = signed_data.child(CONTEXT_0)?.children().collect();
+    let serial = signed_data
+        .children()
+        .filter(|child| child.tag == SET)
+        .last()
+        .and_then(|signer_infos| signer_infos.child(SEQUENCE))
+        .and_then(|signer_info| signer_info.child(SEQUENCE))
+        .and_then(|issuer_and_serial| issuer_and_serial.child(INTEGER))
+        .map(|serial| serial.body);
+    let subjects: Vec<(&[u8], Der)> = certificates
+        .iter()
+        .filter_map(|certificate| {
+            let tbs = certificate.child(SEQUENCE)?;
+            let mut fields = tbs.children().skip_while(|field| field.tag == CONTEXT_0);
+            let serial = fields.next().filter(|field| field.tag == INTEGER)?;
+            // The signature algorithm, the issuer and the validity come first
+            let subject = fields.nth(3)?;
+            Some((serial.body, subject))
+        })
+        .collect();
+    let subject = subjects
+        .iter()
+        .find(|(number, _)| Some(*number) == serial)
+        .or_else(|| subjects.first())?
+        .1;
+    subject
+        .children()
+        .flat_map(|set| set.children())
+        .find_map(|attribute| {
+            let mut parts = attribute.children();
+            let kind = parts.next().filter(|kind| kind.tag == OBJECT_ID)?;
+            (kind.body == COMMON_NAME).then(|| parts.next()).flatten()
+        })
+        .and_then(directory_string)
+}
+
+/// The text of a DER string: UTF-8, printable, IA5 and teletex strings as
+/// UTF-8 (teletex is Latin-1 in practice), BMP strings as UTF-16
+fn directory_string(value: Der) -> Option<String> {
+    let text = match value.tag {
+        0x0C | 0x13 | 0x16 => String::from_utf8_lossy(value.body).into_owned(),
+        0x14 => value.body.iter().map(|&byte| byte as char).collect(),
+        0x1E => {
+            let units: Vec<u16> = value
+                .body
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => return None,
+    };
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{StringFormat, dictionary};
+
+    /// A DER element of `tag` holding `parts`
+    fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let body = parts.concat();
+        let mut element = vec![tag];
+        if body.len() < 0x80 {
+            element.push(body.len() as u8);
+        } else {
+            element.push(0x82);
+            element.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        }
+        element.extend(body);
+        element
+    }
+
+    fn name(common_name: &str) -> Vec<u8> {
+        let attribute = der(
+            SEQUENCE,
+            &[
+                &der(OBJECT_ID, &[COMMON_NAME]),
+                &der(0x0C, &[common_name.as_bytes()]),
+            ],
+        );
+        der(SEQUENCE, &[&der(SET, &[&attribute])])
+    }
+
+    fn certificate(serial: u8, subject: &str) -> Vec<u8> {
+        let tbs = der(
+            SEQUENCE,
+            &[
+                &der(CONTEXT_0, &[&der(INTEGER, &[&[2]])]),
+                &der(INTEGER, &[&[serial]]),
+                &der(SEQUENCE, &[]),
+                &name("Example CA"),
+                &der(SEQUENCE, &[]),
+                &name(subject),
+            ],
+        );
+        der(SEQUENCE, &[&tbs, &der(SEQUENCE, &[]), &der(0x03, &[&[0]])])
+    }
+
+    /// A CMS signature by the certificate with serial number 7, the second
+    /// of the two it carries
+    fn cms() -> Vec<u8> {
+        let signer_info = der(
+            SEQUENCE,
+            &[
+                &der(INTEGER, &[&[1]]),
+                &der(SEQUENCE, &[&name("Example CA"), &der(INTEGER, &[&[7]])]),
+            ],
+        );
+        let signed_data = der(
+            SEQUENCE,
+            &[
+                &der(INTEGER, &[&[1]]),
+                &der(SET, &[]),
+                &der(SEQUENCE, &[]),
+                &der(
+                    CONTEXT_0,
+                    &[&certificate(3, "Example CA"), &certificate(7, "Jane Doe")],
+                ),
+                &der(SET, &[&signer_info]),
+            ],
+        );
+        let mut cms = der(
+            SEQUENCE,
+            &[
+                &der(
+                    OBJECT_ID,
+                    &[&[0x2A, 0x86, 0x48, 0x86, 0xF7, 0x0D, 0x01, 0x07, 0x02]],
+                ),
+                &der(CONTEXT_0, &[&signed_data]),
+            ],
+        );
+        cms.resize(cms.len() + 64, 0);
+        cms
+    }
+
+    #[test]
+    fn test_signer_common_name() {
+        assert_eq!(signer_common_name(&cms()).as_deref(), Some("Jane Doe"));
+        assert_eq!(signer_common_name(b"\x30\x05\x00"), None);
+    }
+
+    #[test]
+    fn test_signatures() {
+        let mut document = Document::with_version("1.7");
+        let signed = document.add_object(dictionary! {
+            "Type" => "Sig",
+            "Filter" => "Adobe.PPKLite",
+            "SubFilter" => "adbe.pkcs7.detached",
+            "ByteRange" => vec![0.into(), 100.into(), 400.into(), 600.into()],
+            "Contents" => Object::String(cms(), StringFormat::Hexadecimal),
+            "M" => Object::string_literal("D:20240501103045+02'00'"),
+        });
+        let fields = vec![
+            dictionary! {
+                "T" => Object::string_literal("Approval"),
+                "Kids" => vec![dictionary! {
+                    "T" => Object::string_literal("Manager"),
+                    "FT" => "Sig",
+                    "V" => signed,
+                }.into()],
+            }
+            .into(),
+            // Not signed yet
+            dictionary! { "T" => Object::string_literal("Witness"), "FT" => "Sig" }.into(),
+            dictionary! {
+                "T" => Object::string_literal("Counter"),
+                "FT" => "Sig",
+                "V" => dictionary! {
+                    "Name" => Object::string_literal("John Roe"),
+                    "ByteRange" => vec![0.into(), 100.into(), 400.into(), 900.into()],
+                },
+            }
+            .into(),
+        ];
+        let form = document.add_object(dictionary! { "Fields" => fields });
+        let catalog = document.add_object(dictionary! { "Type" => "Catalog", "AcroForm" => form });
+        document.trailer.set("Root", catalog);
+
+        assert_eq!(
+            signatures(&document, 1300),
+            [
+                SignatureInfo {
+                    field: "Approval.Manager".to_string(),
+                    signer: Some("Jane Doe".to_string()),
+                    signing_time: Some("2024-05-01T10:30:45+02:00".to_string()),
+                    modified_after_signing: true,
+                },
+                SignatureInfo {
+                    field: "Counter".to_string(),
+                    signer: Some("John Roe".to_string()),
+                    signing_time: None,
+                    modified_after_signing: false,
+                },
+            ]
+        );
+    }
+}
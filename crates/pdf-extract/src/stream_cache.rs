@@ -0,0 +1,163 @@
+use super::limits;
+use log::{debug, info};
+use lopdf::{Document, Object, ObjectId};
+use std::collections::BTreeMap;
+
+/// Decompress, in place, the content streams and ToUnicode maps that pages
+/// read, up to `budget` bytes of decompressed data.
+///
+/// Every reader of a Flate stream inflates it again: the size check, the
+/// layout pass and lopdf's text extraction each do, and a stream shared by
+/// many pages (a page template, the ToUnicode map of a common font) is
+/// inflated on every one of them. Storing the inflated data once makes those
+/// reads free. Streams used by the most pages go first; a stream too large
+/// for the remaining budget, or over `MAX_STREAM_SIZE`, is left compressed.
+///
+/// Returns the number of streams and bytes decompressed.
+pub fn cache_streams(document: &mut Document, budget: usize) -> (usize, usize) {
+    if budget == 0 {
+        return (0, 0);
+    }
+
+    // Pages reading each stream, in order of first use
+    let mut uses: BTreeMap<ObjectId, (usize, usize)> = BTreeMap::new();
+    let mut order = 0;
+    let mut count = |id: ObjectId| {
+        let entry = uses.entry(id).or_insert((0, order));
+        entry.0 += 1;
+        order += 1;
+    };
+    for page_id in document.get_pages().into_values() {
+        for id in document.get_page_contents(page_id) {
+            count(id);
+        }
+        if let Ok(fonts) = document.get_page_fonts(page_id) {
+            for font in fonts.values() {
+                if let Ok(id) = font.get(b"ToUnicode").and_then(Object::as_reference) {
+                    count(id);
+                }
+            }
+        }
+    }
+    let mut candidates: Vec<(ObjectId, usize, usize)> = uses
+        .into_iter()
+        .map(|(id, (pages, first))| (id, pages, first))
+        .collect();
+    candidates.sort_by_key(|&(_, pages, first)| (std::cmp::Reverse(pages), first));
+
+    let (mut streams, mut bytes) = (0, 0);
+    for (id, pages, _) in candidates {
+        let Ok(stream) = document.get_object_mut(id).and_then(Object::as_stream_mut) else {
+            continue;
+        };
+        if !is_plain_flate(stream) {
+            continue;
+        }
+        let Some(data) = limits::inflate(&stream.content) else {
+            continue;
+        };
+        if bytes + data.len() > budget {
+            continue;
+        }
+        debug!(
+            "Caching stream {} {} R ({} bytes, {} pages)",
+            id.0,
+            id.1,
+            data.len(),
+            pages
+        );
+        bytes += data.len();
+        streams += 1;
+        stream.set_plain_content(data);
+    }
+    info!("Cached {} decompressed streams ({} bytes)", streams, bytes);
+    (streams, bytes)
+}
+
+/// Whether the stream is Flate-compressed without a predictor, so inflating
+/// it gives its data exactly
+fn is_plain_flate(stream: &lopdf::Stream) -> bool {
+    let flate = match stream.dict.get(b"Filter") {
+        Ok(Object::Name(name)) => name == b"FlateDecode",
+        Ok(Object::Array(filters)) => {
+            filters.len() == 1
+                && filters[0]
+                    .as_name()
+                    .is_ok_and(|name| name == b"FlateDecode")
+        }
+        _ => false,
+    };
+    flate && !stream.dict.has(b"DecodeParms")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{add_test_font, add_test_page};
+    use lopdf::{Stream, dictionary};
+
+    /// Two pages drawing the same compressed content stream
+    fn shared_content_document() -> (Document, ObjectId) {
+        let mut doc = Document::with_version("1.4");
+        let pages_id = doc.new_object_id();
+        let font_id = doc.new_object_id();
+        let content_id = doc.new_object_id();
+        add_test_font(&mut doc, font_id);
+        // Long enough that Flate shrinks it, so it is stored compressed
+        let line = "BT\n/F1 12 Tf\n50 700 Td\n(Shared template text) Tj\nET\n";
+        let mut content = Stream::new(dictionary! {}, line.repeat(20).into_bytes());
+        content.compress().unwrap();
+        doc.objects.insert(content_id, Object::Stream(content));
+        let mut kids = Vec::new();
+        for _ in 0..2 {
+            let page_id = doc.new_object_id();
+            add_test_page(&mut doc, page_id, pages_id, content_id, font_id);
+            kids.push(page_id.into());
+        }
+        doc.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => 2,
+                "Kids" => kids,
+            }),
+        );
+        let catalog_id = doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        doc.trailer.set("Root", catalog_id);
+        (doc, content_id)
+    }
+
+    fn is_compressed(doc: &Document, id: ObjectId) -> bool {
+        doc.get_object(id)
+            .unwrap()
+            .as_stream()
+            .unwrap()
+            .dict
+            .has(b"Filter")
+    }
+
+    #[test]
+    fn test_cache_streams() {
+        let (mut doc, content_id) = shared_content_document();
+        let before = doc.extract_text(&[1, 2]).unwrap();
+
+        assert_eq!(cache_streams(&mut doc, 0), (0, 0));
+        assert!(is_compressed(&doc, content_id));
+
+        let (streams, bytes) = cache_streams(&mut doc, 4096);
+        assert_eq!(streams, 1);
+        assert!(bytes > 0);
+        assert!(!is_compressed(&doc, content_id));
+        assert_eq!(doc.extract_text(&[1, 2]).unwrap(), before);
+    }
+
+    #[test]
+    fn test_cache_streams_respects_budget() {
+        let (mut doc, content_id) = shared_content_document();
+        assert_eq!(cache_streams(&mut doc, 10), (0, 0));
+        assert!(is_compressed(&doc, content_id));
+    }
+}
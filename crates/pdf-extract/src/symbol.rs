@@ -0,0 +1,249 @@
+//! Unicode for the glyphs of the non-Unicode symbol fonts Symbol,
+//! ZapfDingbats and Wingdings, whose character codes otherwise come out as
+//! the Latin letters sharing them
+
+use super::layout::strip_subset_prefix;
+use lopdf::{Document, Object, ObjectId};
+
+/// Symbol, codes 0x20 to 0x7E
+const SYMBOL_LOW: &str = " !∀#∃%&∋()∗+,−./0123456789:;<=>?\
+    ≅ΑΒΧΔΕΦΓΗΙϑΚΛΜΝΟΠΘΡΣΤΥςΩΞΨΖ[∴]⊥_\
+    ‾αβχδεφγηιϕκλμνοπθρστυϖωξψζ{|}∼";
+
+/// Symbol, codes 0xA0 to 0xFE (0xF0 is the Apple logo, in the private use area)
+const SYMBOL_HIGH: &str = "€ϒ′≤⁄∞ƒ♣♦♥♠↔←↑→↓°±″≥×∝∂•÷≠≡≈…⏐⎯↵\
+    ℵℑℜ℘⊗⊕∅∩∪⊃⊇⊄⊂⊆∈∉∠∇®©™∏√⋅¬∧∨⇔⇐⇑⇒⇓\
+    ◊⟨®©™∑⎛⎜⎝⎡⎢⎣⎧⎨⎩⎪\u{F8FF}⟩∫⌠⎮⌡⎞⎟⎠⎤⎥⎦⎫⎬⎭";
+
+/// A font whose character codes stand for symbols rather than letters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolFont {
+    Symbol,
+    Dingbats,
+    Wingdings,
+}
+
+impl SymbolFont {
+    /// The symbol font a base font name (without subset prefix) is a style of
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.split([',', '-']).next().unwrap_or(name) {
+            "Symbol" | "SymbolMT" => Some(Self::Symbol),
+            "ZapfDingbats" | "ZapfDingbatsITC" | "Dingbats" => Some(Self::Dingbats),
+            "Wingdings" => Some(Self::Wingdings),
+            _ => None,
+        }
+    }
+
+    /// Text of a string of one-byte character codes
+    pub fn decode(self, bytes: &[u8]) -> String {
+        bytes.iter().filter_map(|&code| self.char(code)).collect()
+    }
+
+    /// Text a `ToUnicode` map decoded to the private use area, as U+F020 to
+    /// U+F0FF hold the codes of symbol fonts, with the symbols instead
+    pub fn remap(self, text: &str) -> String {
+        text.chars()
+            .map(|c| match c as u32 {
+                code @ 0xF020..=0xF0FF => self.char(code as u8).unwrap_or(c),
+                _ => c,
+            })
+            .collect()
+    }
+
+    fn char(self, code: u8) -> Option<char> {
+        match self {
+            Self::Symbol => symbol(code),
+            Self::Dingbats => dingbat(code),
+            Self::Wingdings => wingding(code),
+        }
+    }
+}
+
+/// Whether a page uses a symbol font
+pub fn on_page(document: &Document, page_id: ObjectId) -> bool {
+    document.get_page_fonts(page_id).is_ok_and(|fonts| {
+        fonts.values().any(|font| {
+            font.get(b"BaseFont")
+                .and_then(Object::as_name)
+                .is_ok_and(|name| {
+                    SymbolFont::from_name(strip_subset_prefix(&String::from_utf8_lossy(name)))
+                        .is_some()
+                })
+        })
+    })
+}
+
+fn symbol(code: u8) -> Option<char> {
+    match code {
+        0x20..=0x7E => SYMBOL_LOW.chars().nth(usize::from(code - 0x20)),
+        0xA0..=0xFE => SYMBOL_HIGH.chars().nth(usize::from(code - 0xA0)),
+        _ => None,
+    }
+}
+
+/// ZapfDingbats, the model of the Unicode Dingbats block: most codes sit at
+/// a fixed offset from their character, the rest were given older ones
+fn dingbat(code: u8) -> Option<char> {
+    let code = u32::from(code);
+    let c = match code {
+        0x20 => 0x20,
+        0x25 => 0x260E,
+        0x2A => 0x261B,
+        0x2B => 0x261E,
+        0x48 => 0x2605,
+        0x6C => 0x25CF,
+        0x6E => 0x25A0,
+        0x73 => 0x25B2,
+        0x74 => 0x25BC,
+        0x75 => 0x25C6,
+        0x77 => 0x25D7,
+        0x21..=0x7E => 0x2700 + code - 0x20,
+        0x80..=0x8D => 0x2768 + code - 0x80,
+        0xA8 => 0x2663,
+        0xA9 => 0x2666,
+        0xAA => 0x2665,
+        0xAB => 0x2660,
+        0xAC..=0xB5 => 0x2460 + code - 0xAC,
+        0xD5 => 0x2192,
+        0xD6 => 0x2194,
+        0xD7 => 0x2195,
+        0xA1..=0xEF | 0xF1..=0xFE => 0x2700 + code - 0x40,
+        _ => return None,
+    };
+    char::from_u32(c)
+}
+
+/// Wingdings: the bullets, check boxes, arrows, hands, faces and numbers
+/// documents use; other codes go to the private use area, where
+/// `--glyph-map` can pick them up
+fn wingding(code: u8) -> Option<char> {
+    let code = u32::from(code);
+    let c = match code {
+        0x20 => 0x20,
+        0x22 => 0x2702,
+        0x23 => 0x2701,
+        0x28 => 0x260E,
+        0x29 => 0x2706,
+        0x2A => 0x2709,
+        0x36 => 0x231B,
+        0x37 => 0x2328,
+        0x3F => 0x270D,
+        0x41 => 0x270C,
+        0x42 => 0x1F44C,
+        0x43 => 0x1F44D,
+        0x44 => 0x1F44E,
+        0x45 => 0x261C,
+        0x46 => 0x261E,
+        0x47 => 0x261D,
+        0x48 => 0x261F,
+        0x4A => 0x263A,
+        0x4B => 0x1F610,
+        0x4C => 0x2639,
+        0x4D => 0x1F4A3,
+        0x4E => 0x2620,
+        0x51 => 0x2708,
+        0x52 => 0x263C,
+        0x54 => 0x2744,
+        0x56 => 0x271E,
+        0x58 => 0x2720,
+        0x59 => 0x2721,
+        0x5A => 0x262A,
+        0x5B => 0x262F,
+        0x5C => 0x0950,
+        0x5D => 0x2638,
+        0x5E..=0x69 => 0x2648 + code - 0x5E,
+        0x6C => 0x25CF,
+        0x6D => 0x274D,
+        0x6E => 0x25A0,
+        0x6F => 0x25A1,
+        0x71 => 0x2751,
+        0x72 => 0x2752,
+        0x74 => 0x29EB,
+        0x75 => 0x25C6,
+        0x76 => 0x2756,
+        0x77 => 0x2B25,
+        0x78 => 0x2327,
+        0x7A => 0x2318,
+        0x7B => 0x2740,
+        0x7C => 0x273F,
+        0x7D => 0x275D,
+        0x7E => 0x275E,
+        0x80 => 0x24EA,
+        0x81..=0x8A => 0x2460 + code - 0x81,
+        0x8B => 0x24FF,
+        0x8C..=0x95 => 0x2776 + code - 0x8C,
+        0x9E => 0x00B7,
+        0x9F => 0x2022,
+        0xA1 => 0x25CB,
+        0xA7 => 0x25AA,
+        0xA8 => 0x25FB,
+        0xAB => 0x2605,
+        0xD8 => 0x27A2,
+        0xDF => 0x2190,
+        0xE0 => 0x2192,
+        0xE1 => 0x2191,
+        0xE2 => 0x2193,
+        0xE3 => 0x2196,
+        0xE4 => 0x2197,
+        0xE5 => 0x2199,
+        0xE6 => 0x2198,
+        0xE8 => 0x2794,
+        0xEF => 0x21E6,
+        0xF0 => 0x21E8,
+        0xF1 => 0x21E7,
+        0xF2 => 0x21E9,
+        0xFB => 0x2717,
+        0xFC => 0x2713,
+        0xFD => 0x2612,
+        0xFE => 0x2611,
+        0x21..=0xFF => 0xF000 + code,
+        _ => return None,
+    };
+    char::from_u32(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(SymbolFont::from_name("Symbol"), Some(SymbolFont::Symbol));
+        assert_eq!(
+            SymbolFont::from_name("SymbolMT,Bold"),
+            Some(SymbolFont::Symbol)
+        );
+        assert_eq!(
+            SymbolFont::from_name("Wingdings-Regular"),
+            Some(SymbolFont::Wingdings)
+        );
+        assert_eq!(
+            SymbolFont::from_name("ZapfDingbats"),
+            Some(SymbolFont::Dingbats)
+        );
+        assert_eq!(SymbolFont::from_name("Wingdings3"), None);
+        assert_eq!(SymbolFont::from_name("Helvetica"), None);
+    }
+
+    #[test]
+    fn test_tables() {
+        assert_eq!(SYMBOL_LOW.chars().count(), 0x7F - 0x20);
+        assert_eq!(SYMBOL_HIGH.chars().count(), 0xFF - 0xA0);
+        assert_eq!(
+            SymbolFont::Symbol.decode(b"a \xAE b \xB7 \xA3"),
+            "α → β • ≤"
+        );
+        assert_eq!(SymbolFont::Dingbats.decode(b"34l\xD5\xAC\xD4"), "✓✔●→①➔");
+        assert_eq!(SymbolFont::Wingdings.decode(b"\xFC\xFEl\xE0J"), "✓☑●→☺");
+        assert_eq!(SymbolFont::Wingdings.decode(b"\x24"), "\u{F024}");
+    }
+
+    #[test]
+    fn test_remap() {
+        assert_eq!(
+            SymbolFont::Wingdings.remap("\u{F0A7} item \u{F0FC}"),
+            "▪ item ✓"
+        );
+        assert_eq!(SymbolFont::Symbol.remap("\u{F061}=1"), "α=1");
+    }
+}
@@ -1,140 +1,729 @@
-use super::types::ExtractedContent;
-use crate::Result;
+use super::images::ImageCollector;
+use super::types::{
+    CleanupThresholds, ExtractOptions, ExtractTimings, ExtractedContent, ExtractedImage, Redactions,
+};
+use super::{
+    decoration, definition, drop_cap, footnote, formula, layout, limits, math, page_cache,
+    redaction, render, symbol, vector,
+};
+use crate::{PdfError, Result};
 use log::{debug, info, warn};
-use lopdf::Document;
+use lopdf::{Document, ObjectId};
+use memchr::{memchr, memchr_iter, memchr3, memmem};
+use std::borrow::Cow;
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant};
+
+/// Words of each page kept in `ExtractedContent::page_starts`
+const PAGE_START_WORDS: usize = 8;
 
 /// Extract text content from a PDF document
 pub fn extract_text(document: &Document) -> Result<ExtractedContent> {
-    info!("Extracting text from PDF");
+    extract_text_with(document, &ExtractOptions::default())
+}
 
+/// Extract text content from a PDF document with the given options
+pub fn extract_text_with(
+    document: &Document,
+    options: &ExtractOptions,
+) -> Result<ExtractedContent> {
     let mut all_text = String::new();
-    let page_count = document.get_pages().len();
+    let mut content = extract_pages(
+        document,
+        options,
+        |text| {
+            all_text.push_str(text);
+            ControlFlow::Continue(())
+        },
+        |_| ControlFlow::Continue(()),
+    )?;
+
+    // Clean up the extracted text
+    let started = Instant::now();
+    if !options.glyph_map.is_empty() {
+        update(&mut all_text, |text| {
+            replace_glyphs(text, &options.glyph_map)
+        });
+    }
+    update(&mut all_text, |text| {
+        clean(text, &options.cleanup, options.math)
+    });
+    content.timings.cleanup = started.elapsed();
+
+    content.text = all_text;
+    Ok(content)
+}
+
+/// Extract text page by page like `extract_text_with`, handing the cleaned
+/// text to `emit` as soon as it is ready so it can be written out while later
+/// pages are still being extracted.
+///
+/// Joined, the pieces are the text `extract_text_with` returns (except that
+/// glyphs are replaced within each page), and the content returned carries
+/// everything else but the images, which go to `emit_images` as each page
+/// is done. Returning `ControlFlow::Break` from either stops the extraction.
+pub fn extract_text_streaming(
+    document: &Document,
+    options: &ExtractOptions,
+    mut emit: impl FnMut(String) -> ControlFlow<()>,
+    mut emit_images: impl FnMut(Vec<ExtractedImage>) -> ControlFlow<()>,
+) -> Result<ExtractedContent> {
+    let mut cleaner = TextCleaner::new(options.cleanup).with_math(options.math);
+    let mut stopped = false;
+    let mut cleanup = Duration::ZERO;
+    let mut content = extract_pages(
+        document,
+        options,
+        |text| {
+            let started = Instant::now();
+            let text = if options.glyph_map.is_empty() {
+                Cow::Borrowed(text)
+            } else {
+                replace_glyphs(text, &options.glyph_map)
+            };
+            cleaner.push(&text);
+            let ready = cleaner.take();
+            cleanup += started.elapsed();
+            if ready.is_empty() {
+                return ControlFlow::Continue(());
+            }
+            let flow = emit(ready);
+            stopped = flow.is_break();
+            flow
+        },
+        |images| emit_images(std::mem::take(images)),
+    )?;
+    let started = Instant::now();
+    let rest = cleaner.finish();
+    content.timings.cleanup = cleanup + started.elapsed();
+    if !stopped && !rest.is_empty() {
+        let _ = emit(rest);
+    }
+    Ok(content)
+}
+
+/// The page loop of `extract_text_with` and `extract_text_streaming`: `emit`
+/// receives the raw text of each page in order, page separators included,
+/// and may stop the extraction. `emit_images` is offered the images
+/// collected so far after each page; what it leaves in the list is returned
+/// with the content. The content returned has no text, and its timings leave
+/// out the time spent in `emit` and `emit_images`.
+fn extract_pages(
+    document: &Document,
+    options: &ExtractOptions,
+    mut emit: impl FnMut(&str) -> ControlFlow<()>,
+    mut emit_images: impl FnMut(&mut Vec<ExtractedImage>) -> ControlFlow<()>,
+) -> Result<ExtractedContent> {
+    info!("Extracting text from PDF");
+    let started = Instant::now();
+    let mut emitting = Duration::ZERO;
+    let mut emit = |text: &str| {
+        let started = Instant::now();
+        let flow = emit(text);
+        emitting += started.elapsed();
+        flow
+    };
+    let mut emitting_images = Duration::ZERO;
+
+    let mut images = ImageCollector::default();
+    let mut footnotes = Vec::new();
+    let mut failed_pages = Vec::new();
+    let mut redactions = Redactions::default();
+    let mut page_starts = Vec::new();
+    let mut stopped_at = None;
+    let mut page_times = Vec::new();
+    let pages = document.get_pages();
+    let page_count = pages.len();
 
     info!("Processing {} pages", page_count);
 
     // Extract text from each page
-    for page_num in 1..=page_count as u32 {
+    for (&page_num, &page_id) in &pages {
         debug!("Extracting text from page {}", page_num);
 
-        match document.extract_text(&[page_num]) {
+        let page_started = Instant::now();
+        let result = limits::check_page_content(document, page_id).and_then(|()| {
+            let Some(cache) = &options.page_cache else {
+                return extract_page_text(
+                    document,
+                    page_num,
+                    page_id,
+                    options,
+                    &mut images,
+                    &mut footnotes,
+                    &mut redactions,
+                )
+                .map_err(|e| e.to_string());
+            };
+            let key = page_cache::page_key(document, page_num, page_id, options);
+            if let Some(text) = cache.get(&key) {
+                debug!("Page {} is unchanged, using its cached text", page_num);
+                return Ok(text);
+            }
+            let (image_count, footnote_count) = (images.images.len(), footnotes.len());
+            let redactions_before = redactions.clone();
+            let text = extract_page_text(
+                document,
+                page_num,
+                page_id,
+                options,
+                &mut images,
+                &mut footnotes,
+                &mut redactions,
+            )
+            .map_err(|e| e.to_string())?;
+            // The cache keeps text only, so pages adding images, notes or
+            // redactions are redone
+            if images.images.len() == image_count
+                && footnotes.len() == footnote_count
+                && redactions == redactions_before
+            {
+                cache.put(&key, &text);
+            }
+            Ok(text)
+        });
+        page_times.push((page_num, page_started.elapsed()));
+        match result {
             Ok(text) => {
-                if !text.is_empty() {
-                    // Add page separator if not first page
-                    if page_num > 1 {
-                        all_text.push_str("\n\n");
+                if !text.trim().is_empty() {
+                    let words: Vec<&str> = text.split_whitespace().take(PAGE_START_WORDS).collect();
+                    page_starts.push((page_num, words.join(" ")));
+                }
+                // Add page separator if not first page
+                if !text.is_empty()
+                    && ((page_num > 1 && emit("\n\n").is_break()) || emit(&text).is_break())
+                {
+                    break;
+                }
+                if !images.images.is_empty() {
+                    let started = Instant::now();
+                    let flow = emit_images(&mut images.images);
+                    emitting_images += started.elapsed();
+                    if flow.is_break() {
+                        break;
                     }
-                    all_text.push_str(&text);
                 }
             }
+            Err(e) if options.strict => {
+                let reason = format!("Failed to extract text from page {}: {}", page_num, e);
+                if !options.keep_partial {
+                    return Err(PdfError::Processing(reason));
+                }
+                stopped_at = Some((page_num, reason));
+                break;
+            }
             Err(e) => {
                 warn!("Failed to extract text from page {}: {}", page_num, e);
-                // Continue with other pages even if one fails
+                // Continue with other pages even if one fails, marking the gap
+                failed_pages.push(page_num);
+                if emit(&render::standalone(&failed_page_marker(page_num, options))).is_break() {
+                    break;
+                }
             }
         }
     }
 
-    // Clean up the extracted text
-    all_text = clean_extracted_text(&all_text);
-
     Ok(ExtractedContent {
-        text: all_text,
+        text: String::new(),
         page_count,
+        images: images.images,
+        footnotes,
+        failed_pages,
+        page_starts,
+        stopped_at,
+        redactions,
+        timings: ExtractTimings {
+            extraction: started.elapsed().saturating_sub(emitting + emitting_images),
+            pages: page_times,
+            cleanup: Duration::ZERO,
+        },
     })
 }
 
+/// Plain text of one page, cleaned up as in full extraction
+pub fn extract_page(document: &Document, page_num: u32) -> Result<String> {
+    let page_id = *document
+        .get_pages()
+        .get(&page_num)
+        .ok_or_else(|| PdfError::InvalidInput(format!("Page {} does not exist", page_num)))?;
+    limits::check_page_content(document, page_id).map_err(PdfError::Processing)?;
+    let mut text = extract_page_text(
+        document,
+        page_num,
+        page_id,
+        &ExtractOptions::default(),
+        &mut ImageCollector::default(),
+        &mut Vec::new(),
+        &mut Redactions::default(),
+    )
+    .map_err(|e| PdfError::Processing(e.to_string()))?;
+    update(&mut text, clean_extracted_text);
+    Ok(text)
+}
+
+/// Replace `text` with the result of a clean-up pass, which borrows a prefix
+/// of its input when it only dropped trailing characters
+fn update(text: &mut String, pass: impl for<'a> FnOnce(&'a str) -> Cow<'a, str>) {
+    let kept = match pass(text) {
+        Cow::Borrowed(kept) => kept.len(),
+        Cow::Owned(changed) => {
+            *text = changed;
+            return;
+        }
+    };
+    text.truncate(kept);
+}
+
+/// Blockquote left in place of a page whose text could not be extracted
+fn failed_page_marker(page_num: u32, options: &ExtractOptions) -> String {
+    let note = options
+        .failed_page_note
+        .as_deref()
+        .unwrap_or("Page {page} could not be extracted");
+    format!(
+        "> \u{26a0}\u{fe0f} {}",
+        note.replace("{page}", &page_num.to_string())
+    )
+}
+
+/// Extract the raw text of a single page, from its layout when a feature
+/// needs it, the page uses a symbol font or a redaction hides some of its
+/// text, with drop caps put back on their words
+fn extract_page_text(
+    document: &Document,
+    page_num: u32,
+    page_id: ObjectId,
+    options: &ExtractOptions,
+    images: &mut ImageCollector,
+    footnotes: &mut Vec<String>,
+    redactions: &mut Redactions,
+) -> lopdf::Result<String> {
+    // lopdf reads symbol fonts as if they held letters
+    let symbols = symbol::on_page(document, page_id);
+    let hiding = redaction::may_hide(document, page_id);
+    if symbols
+        || hiding
+        || options.math
+        || options.images
+        || options.vector_graphics
+        || options.footnotes.is_some()
+        || options.definition_lists
+        || options.horizontal_rules
+        || options.underline.is_some()
+        || options.strikethrough
+        || options.text_colors
+    {
+        match layout::page_layout(document, page_id) {
+            Ok(mut page) => {
+                let hidden = if hiding {
+                    redaction::hide(&mut page)
+                } else {
+                    redaction::Hidden::default()
+                };
+                redactions.add(page_num, &hidden);
+                if options.math {
+                    page.formulas = formula::on_page(document, page_id);
+                }
+                if !(symbols || hidden.any() || needs_layout(&page, options)) {
+                    return document.extract_text(&[page_num]).map(drop_cap::merge);
+                }
+                debug!("Rendering page {} from its layout", page_num);
+                if options.images {
+                    images.collect(document, page_num, &page.images, options);
+                }
+                if options.vector_graphics {
+                    let drawings = vector::find_drawings(&page);
+                    images.collect_drawings(page_num, &drawings, options);
+                }
+                let text = render::render_layout(&page, page_num, options, images, footnotes);
+                return Ok(drop_cap::merge(text));
+            }
+            // Extracted the plain way, the text a redaction hides would show
+            Err(e) if hiding => return Err(lopdf::Error::Invalid(e.to_string())),
+            Err(e) => warn!("Failed to analyze layout of page {}: {}", page_num, e),
+        }
+    }
+    document.extract_text(&[page_num]).map(drop_cap::merge)
+}
+
+/// Pages without math, images, drawings, footnotes, definition lists, rules,
+/// underlines, strikethroughs or coloured text are extracted the plain way
+fn needs_layout(page: &layout::PageLayout, options: &ExtractOptions) -> bool {
+    (options.math && math::has_math(page))
+        || (options.images && !page.images.is_empty())
+        || (options.vector_graphics && !page.paths.is_empty())
+        || (options.footnotes.is_some() && !footnote::find(&page.lines()).notes.is_empty())
+        || (options.definition_lists && !definition::find(&page.lines()).is_empty())
+        || (options.horizontal_rules && !vector::find_rules(page).is_empty())
+        || (options.underline.is_some()
+            && page
+                .runs
+                .iter()
+                .any(|run| decoration::is_underlined(run, page)))
+        || (options.strikethrough && page.runs.iter().any(|run| decoration::is_struck(run, page)))
+        || (options.text_colors && page.runs.iter().any(decoration::is_colored))
+}
+
 /// Clean up extracted text by removing extra whitespace and normalizing line breaks
-pub fn clean_extracted_text(text: &str) -> String {
-    // Remove carriage returns
-    let text = text.replace('\r', "");
-
-    // Normalize multiple spaces to single space within each line
-    let text = text
-        .split('\n')
-        .map(|line| {
-            // Remove leading/trailing whitespace from each line
-            let line = line.trim();
-            // Collapse multiple spaces within the line
-            line.split_whitespace().collect::<Vec<_>>().join(" ")
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
-
-    // Remove excessive blank lines (more than 2 consecutive)
-    let mut result = String::new();
-    let mut blank_count = 0;
-
-    for line in text.lines() {
-        if line.is_empty() {
-            blank_count += 1;
-            if blank_count <= 2 {
-                result.push('\n');
+pub fn clean_extracted_text(text: &str) -> Cow<'_, str> {
+    clean_extracted_text_with(text, &CleanupThresholds::default())
+}
+
+/// Clean up extracted text with the given heuristic thresholds.
+///
+/// One pass over the characters writes straight into the output: carriage
+/// returns and invisible characters are dropped, soft hyphens join the words
+/// they split, no-break spaces become spaces, each line is trimmed with its
+/// runs of whitespace collapsed to one space, and runs of blank lines are
+/// capped at `max_blank_lines`. Zero-width (non-)joiners are kept since they
+/// change how some scripts and emoji render. Text that is already clean is
+/// returned borrowed, less any trailing whitespace.
+pub fn clean_extracted_text_with<'a>(
+    text: &'a str,
+    thresholds: &CleanupThresholds,
+) -> Cow<'a, str> {
+    clean(text, thresholds, false)
+}
+
+/// `clean_extracted_text_with`, keeping inline math in one paragraph when
+/// `math` is set
+fn clean<'a>(text: &'a str, thresholds: &CleanupThresholds, math: bool) -> Cow<'a, str> {
+    if let Some(clean) = already_clean(text) {
+        return Cow::Borrowed(clean);
+    }
+
+    let mut cleaner = TextCleaner::new(*thresholds).with_math(math);
+    cleaner.push(text);
+    Cow::Owned(cleaner.finish())
+}
+
+/// Streaming form of [`clean_extracted_text_with`]: text pushed in any number
+/// of pieces comes out as the whole text would
+pub struct TextCleaner {
+    thresholds: CleanupThresholds,
+    /// Whether the text has inline LaTeX math (`$...$`), which paragraph
+    /// breaks are kept out of
+    math: bool,
+    /// Cleaned text not taken yet
+    output: String,
+    /// Line breaks written so far
+    newlines: usize,
+    /// Whether any text was written, so leading blank lines are dropped
+    started: bool,
+    /// Whether the current line has text yet, and the blank lines before it
+    in_line: bool,
+    blank_lines: usize,
+    space_pending: bool,
+    hyphen: Hyphen,
+}
+
+/// Progress in skipping the line break (and any spaces around it) after a
+/// soft hyphen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hyphen {
+    None,
+    BeforeBreak,
+    AfterBreak,
+}
+
+impl TextCleaner {
+    pub fn new(thresholds: CleanupThresholds) -> Self {
+        Self {
+            thresholds,
+            math: false,
+            output: String::new(),
+            newlines: 0,
+            started: false,
+            in_line: false,
+            blank_lines: 0,
+            space_pending: false,
+            hyphen: Hyphen::None,
+        }
+    }
+
+    /// Keep paragraph breaks out of inline LaTeX math, as extraction with
+    /// `ExtractOptions::math` writes it
+    pub fn with_math(mut self, math: bool) -> Self {
+        self.math = math;
+        self
+    }
+
+    /// Clean the next piece of text
+    pub fn push(&mut self, text: &str) {
+        let max_breaks = self.thresholds.max_blank_lines + 1;
+        let output = &mut self.output;
+        output.reserve(text.len());
+        // The state lives in locals while the loop runs, which is faster
+        let mut newlines = self.newlines;
+        let mut started = self.started;
+        let mut in_line = self.in_line;
+        let mut blank_lines = self.blank_lines;
+        let mut space_pending = self.space_pending;
+        let mut hyphen = self.hyphen;
+
+        let bytes = text.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            let start = i;
+            // Printable ASCII comes in runs, which are copied whole; any other
+            // character is looked at on its own
+            let run = plain_run(&bytes[i..]);
+            if run > 0 {
+                i += run;
+                hyphen = Hyphen::None;
+            } else {
+                let ch = match bytes[i] {
+                    b if b.is_ascii() => char::from(b),
+                    _ => text[i..].chars().next().expect("i is on a char boundary"),
+                };
+                i += ch.len_utf8();
+                match hyphen {
+                    Hyphen::BeforeBreak | Hyphen::AfterBreak if matches!(ch, ' ' | '\t' | '\r') => {
+                        continue;
+                    }
+                    Hyphen::BeforeBreak if ch == '\n' => {
+                        hyphen = Hyphen::AfterBreak;
+                        continue;
+                    }
+                    _ => hyphen = Hyphen::None,
+                }
+                match ch {
+                    '\r' => continue,
+                    '\n' => {
+                        if in_line {
+                            in_line = false;
+                            blank_lines = 0;
+                        } else {
+                            blank_lines += 1;
+                        }
+                        space_pending = false;
+                        continue;
+                    }
+                    SOFT_HYPHEN => {
+                        hyphen = Hyphen::BeforeBreak;
+                        continue;
+                    }
+                    c if INVISIBLE_CHARS.contains(&c) => continue,
+                    c if c.is_whitespace() || NO_BREAK_SPACES.contains(&c) => {
+                        space_pending = true;
+                        continue;
+                    }
+                    _ => {}
+                }
             }
-        } else {
-            blank_count = 0;
-            if !result.is_empty() && !result.ends_with('\n') {
-                result.push('\n');
+
+            if in_line {
+                if space_pending {
+                    output.push(' ');
+                }
+            } else {
+                // The first line break ends the previous line, the next ones
+                // are blank lines; leading blank lines are dropped
+                if started {
+                    let breaks = blank_lines.clamp(1, max_breaks);
+                    for _ in 0..breaks {
+                        output.push('\n');
+                    }
+                    newlines += breaks;
+                }
+                started = true;
+                in_line = true;
             }
-            result.push_str(line);
+            space_pending = false;
+            output.push_str(&text[start..i]);
+        }
+
+        self.newlines = newlines;
+        self.started = started;
+        self.in_line = in_line;
+        self.blank_lines = blank_lines;
+        self.space_pending = space_pending;
+        self.hyphen = hyphen;
+    }
+
+    /// The text cleaned since the last call. Nothing is returned until the
+    /// text has three line breaks, since text with fewer gets paragraph
+    /// breaks added once it is complete.
+    pub fn take(&mut self) -> String {
+        if self.newlines < 3 {
+            return String::new();
+        }
+        std::mem::take(&mut self.output)
+    }
+
+    /// The rest of the cleaned text
+    pub fn finish(self) -> String {
+        // Ensure paragraphs are separated by blank lines
+        // If we have very few newlines, add paragraph breaks after sentences
+        if self.newlines < 3 {
+            // PDF didn't have good line break structure, add them ourselves
+            add_paragraph_breaks(&self.output, &self.thresholds, self.math)
+        } else {
+            self.output
         }
     }
+}
 
-    // Trim leading/trailing whitespace but preserve internal structure
-    let trimmed = result.trim().to_string();
+/// Length of the run of printable ASCII characters (`!` to `~`, and DEL) at
+/// the start of `bytes`, found eight bytes at a time
+fn plain_run(bytes: &[u8]) -> usize {
+    const ONES: u64 = u64::from_ne_bytes([0x01; 8]);
+    const HIGH: u64 = u64::from_ne_bytes([0x80; 8]);
+    let mut chunks = bytes.chunks_exact(8);
+    let mut len = 0;
+    for chunk in &mut chunks {
+        let word = u64::from_le_bytes(chunk.try_into().expect("8 bytes"));
+        // The high bit of each byte below `!` or above DEL; a borrow can only
+        // mark bytes after the first one wrongly
+        let special = ((word.wrapping_sub(ONES * 0x21) & !word) | word) & HIGH;
+        if special != 0 {
+            return len + special.trailing_zeros() as usize / 8;
+        }
+        len += 8;
+    }
+    len + chunks
+        .remainder()
+        .iter()
+        .take_while(|&&b| b > b' ' && b < 0x80)
+        .count()
+}
 
-    // Ensure paragraphs are separated by blank lines
-    // If we have very few newlines, add paragraph breaks after sentences
-    if trimmed.matches('\n').count() < 3 {
-        // PDF didn't have good line break structure, add them ourselves
-        add_paragraph_breaks(&trimmed)
-    } else {
-        trimmed
+/// The text less its trailing whitespace, when cleaning would not change it
+/// otherwise: at least three line breaks (so no paragraph breaks are added),
+/// trimmed lines with single spaces, no blank lines and no special characters
+fn already_clean(text: &str) -> Option<&str> {
+    let text = text.trim_end();
+    let bytes = text.as_bytes();
+    // Each is a vectorized search over the whole text
+    let ragged = bytes.first().is_some_and(|&b| b == b' ' || b == b'\n')
+        || ["\n\n", "\n ", " \n", "  "]
+            .iter()
+            .any(|pair| memmem::find(bytes, pair.as_bytes()).is_some())
+        || memchr3(b'\t', b'\r', b'\x0B', bytes).is_some()
+        || memchr(b'\x0C', bytes).is_some();
+    if ragged || memchr_iter(b'\n', bytes).take(3).count() < 3 {
+        return None;
     }
+    let special = |c: char| {
+        !c.is_ascii() && (c.is_whitespace() || c == SOFT_HYPHEN || INVISIBLE_CHARS.contains(&c))
+    };
+    (text.is_ascii() || !text.chars().any(special)).then_some(text)
 }
 
-/// Add paragraph breaks after sentences when PDF lacks structure
-fn add_paragraph_breaks(text: &str) -> String {
-    let mut result = String::new();
+/// Characters that take no space and carry no meaning in extracted text:
+/// zero-width space, word joiner and byte order mark
+const INVISIBLE_CHARS: [char; 3] = ['\u{200B}', '\u{2060}', '\u{FEFF}'];
+
+/// Space characters that only prevent a line break (no-break, narrow no-break
+/// and figure space)
+const NO_BREAK_SPACES: [char; 3] = ['\u{00A0}', '\u{202F}', '\u{2007}'];
+
+/// Invisible break point inside a word, shown as a hyphen only where the
+/// word is broken over two lines
+pub(crate) const SOFT_HYPHEN: char = '\u{00AD}';
+
+/// Replace every occurrence of a key of `glyph_map` in one pass, preferring
+/// the longest key at each position, so replacements are never replaced again
+fn replace_glyphs<'a>(text: &'a str, glyph_map: &[(String, String)]) -> Cow<'a, str> {
+    let mut entries: Vec<&(String, String)> = glyph_map
+        .iter()
+        .filter(|(from, _)| !from.is_empty())
+        .collect();
+    entries.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+
+    if !entries.iter().any(|(from, _)| text.contains(from.as_str())) {
+        return Cow::Borrowed(text);
+    }
+
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        match entries
+            .iter()
+            .find(|(from, _)| rest.starts_with(from.as_str()))
+        {
+            Some((from, to)) => {
+                result.push_str(to);
+                rest = &rest[from.len()..];
+            }
+            None => {
+                result.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    Cow::Owned(result)
+}
+
+/// Add paragraph breaks after sentences when PDF lacks structure, none of
+/// them inside inline math when the text has some (`math`)
+fn add_paragraph_breaks(text: &str, thresholds: &CleanupThresholds, math: bool) -> String {
+    // Room for a break every hundred or so characters before reallocating
+    let mut result = String::with_capacity(text.len() + text.len() / 64);
     let mut chars = text.chars().peekable();
     let mut char_count_since_break = 0;
 
+    // Characters since inline math opened, while it is open
+    let mut in_math: Option<usize> = None;
+    let mut open = OpenMarks::default();
+
     while let Some(ch) = chars.next() {
         result.push(ch);
         char_count_since_break += 1;
 
+        // Never break inside inline LaTeX math; a `$` left open as long as
+        // a stray quotation mark is forgotten the same way
+        if math {
+            in_math = match (ch, in_math) {
+                ('$', None) => Some(0),
+                ('$' | '\n', Some(_)) => None,
+                (_, Some(chars)) if chars < MAX_OPEN_CHARS => Some(chars + 1),
+                _ => None,
+            };
+            if in_math.is_some() {
+                continue;
+            }
+        }
+        // ... or inside quotation marks and brackets
+        open.push(ch);
+        if !open.is_empty() {
+            continue;
+        }
+
         // After a period, check if we should add a paragraph break
-        if ch == '.' && char_count_since_break > 40 {
+        if ch == '.' && char_count_since_break > thresholds.min_paragraph_chars {
             // Look ahead to see if next char is uppercase or space
-            if let Some(&next_ch) = chars.peek() {
-                if next_ch.is_uppercase() || next_ch.is_whitespace() {
-                    // Skip whitespace
-                    while let Some(&next_ch) = chars.peek() {
-                        if next_ch.is_whitespace() && next_ch != '\n' {
-                            chars.next();
-                        } else {
-                            break;
-                        }
+            if let Some(&next_ch) = chars.peek()
+                && (next_ch.is_uppercase() || next_ch.is_whitespace())
+            {
+                // Skip whitespace
+                while let Some(&next_ch) = chars.peek() {
+                    if next_ch.is_whitespace() && next_ch != '\n' {
+                        chars.next();
+                    } else {
+                        break;
                     }
-                    // Add paragraph break if next char is uppercase
-                    if let Some(&next_ch) = chars.peek() {
-                        if next_ch.is_uppercase() {
-                            result.push_str("\n\n");
-                            char_count_since_break = 0;
-                        } else {
-                            result.push(' ');
-                        }
+                }
+                // Add paragraph break if next char is uppercase
+                if let Some(&next_ch) = chars.peek() {
+                    if next_ch.is_uppercase() {
+                        result.push_str("\n\n");
+                        char_count_since_break = 0;
+                    } else {
+                        result.push(' ');
                     }
                 }
             }
         }
         // Detect concatenated words: lowercase followed directly by uppercase
         // This often indicates missing breaks between sections (e.g., "TestingIntroduction")
-        else if ch.is_lowercase() && char_count_since_break > 20 {
-            if let Some(&next_ch) = chars.peek() {
-                if next_ch.is_uppercase() {
-                    // Likely a section boundary, add paragraph break
-                    result.push_str("\n\n");
-                    char_count_since_break = 0;
-                }
+        else if ch.is_lowercase() && char_count_since_break > thresholds.min_section_chars {
+            if let Some(&next_ch) = chars.peek()
+                && next_ch.is_uppercase()
+            {
+                // Likely a section boundary, add paragraph break
+                result.push_str("\n\n");
+                char_count_since_break = 0;
             }
         } else if ch == '\n' {
             char_count_since_break = 0;
@@ -144,6 +733,61 @@ fn add_paragraph_breaks(text: &str) -> String {
     result
 }
 
+/// Characters after which a quotation mark, bracket or `$` still open is
+/// taken for a stray one and forgotten
+const MAX_OPEN_CHARS: usize = 500;
+
+/// The quotation marks and brackets open at a point of the text, as the
+/// marks that close them, innermost last
+#[derive(Default)]
+struct OpenMarks {
+    closers: Vec<char>,
+    /// Characters since the outermost one opened
+    chars: usize,
+}
+
+impl OpenMarks {
+    fn is_empty(&self) -> bool {
+        self.closers.is_empty()
+    }
+
+    fn push(&mut self, ch: char) {
+        if !self.closers.is_empty() {
+            self.chars += 1;
+            if ch == '\n' || self.chars > MAX_OPEN_CHARS {
+                self.closers.clear();
+                return;
+            }
+        }
+        // `"` and German `“` close the mark they match, or open one
+        if self.closers.last() == Some(&ch) {
+            self.closers.pop();
+            return;
+        }
+        let closer = match ch {
+            '(' => ')',
+            '[' => ']',
+            '{' => '}',
+            '"' => '"',
+            '“' => '”',
+            '„' => '“',
+            '«' => '»',
+            '»' | ')' | ']' | '}' | '”' => {
+                // A closer for an outer mark closes the ones inside it too
+                if let Some(at) = self.closers.iter().rposition(|&c| c == ch) {
+                    self.closers.truncate(at);
+                }
+                return;
+            }
+            _ => return,
+        };
+        if self.closers.is_empty() {
+            self.chars = 0;
+        }
+        self.closers.push(closer);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -157,4 +801,199 @@ mod tests {
         assert!(!result.contains("  ")); // No double spaces
         assert!(!result.contains('\r')); // No carriage returns
     }
+
+    #[test]
+    fn test_clean_extracted_text_special_characters() {
+        assert_eq!(
+            clean_extracted_text("\u{FEFF}Zero\u{200B}width\u{2060}chars"),
+            "Zerowidthchars"
+        );
+        assert_eq!(
+            clean_extracted_text("10\u{00A0}kg and 5\u{202F}%"),
+            "10 kg and 5 %"
+        );
+        // Soft hyphens vanish, joining words hyphenated across lines
+        assert_eq!(
+            clean_extracted_text("inter\u{00AD}national exam\u{00AD}\r\n  ple"),
+            "international example"
+        );
+        // Joiners shape some scripts and emoji
+        assert_eq!(clean_extracted_text("\u{200C}\u{200D}"), "\u{200C}\u{200D}");
+    }
+
+    #[test]
+    fn test_clean_extracted_text_line_structure() {
+        assert_eq!(
+            clean_extracted_text(
+                "\r\n \r\n  One \t two  \r\n\r\n\r\nThree\r\n\u{200B}\nFour\nFive \n\n"
+            ),
+            "One two\n\nThree\nFour\nFive"
+        );
+    }
+
+    #[test]
+    fn test_clean_extracted_text_strips_special_whitespace() {
+        let input =
+            "\u{FEFF}Title\u{00A0}\u{00A0}Page\n\nA hyphen\u{00AD}\nated word.\n\nMore\ntext\nhere";
+        assert_eq!(
+            clean_extracted_text(input),
+            "Title Page\nA hyphenated word.\nMore\ntext\nhere"
+        );
+    }
+
+    #[test]
+    fn test_clean_text_is_borrowed() {
+        let clean = "Title\nFirst line\nSecond line\nThird line";
+        assert!(matches!(clean_extracted_text(clean), Cow::Borrowed(text) if text == clean));
+        // Trailing whitespace is trimmed without copying
+        let trailing = format!("{}  \n\n", clean);
+        assert!(matches!(clean_extracted_text(&trailing), Cow::Borrowed(text) if text == clean));
+        assert!(matches!(
+            clean_extracted_text("Title\r\n\nFirst line\nSecond\nThird"),
+            Cow::Owned(_)
+        ));
+        assert!(matches!(
+            replace_glyphs(clean, &[("\u{F0B7}".to_string(), "*".to_string())]),
+            Cow::Borrowed(_)
+        ));
+
+        let mut text = trailing;
+        update(&mut text, clean_extracted_text);
+        assert_eq!(text, clean);
+    }
+
+    #[test]
+    fn test_text_cleaner_matches_whole_text() {
+        let text = "  Title \r\n\n\n\nA hyphen\u{00AD}\n  ated   word\nline two\n\nline\u{00A0}three\nend ";
+        let whole = clean_extracted_text(text);
+        for size in 1..8 {
+            let chars: Vec<char> = text.chars().collect();
+            let mut cleaner = TextCleaner::new(CleanupThresholds::default());
+            let mut pieces = String::new();
+            for piece in chars.chunks(size) {
+                cleaner.push(&piece.iter().collect::<String>());
+                pieces.push_str(&cleaner.take());
+            }
+            pieces.push_str(&cleaner.finish());
+            assert_eq!(pieces, whole, "pieces of {}", size);
+        }
+
+        // Too few line breaks to know yet whether paragraph breaks are added
+        let mut cleaner = TextCleaner::new(CleanupThresholds::default());
+        cleaner.push("One sentence here. Another sentence");
+        assert_eq!(cleaner.take(), "");
+        assert_eq!(
+            cleaner.finish(),
+            clean_extracted_text("One sentence here. Another sentence")
+        );
+    }
+
+    #[test]
+    fn test_plain_run() {
+        assert_eq!(plain_run(b""), 0);
+        assert_eq!(plain_run(b" word"), 0);
+        assert_eq!(plain_run(b"word"), 4);
+        assert_eq!(plain_run(b"Long-enough~words!\tnext"), 18);
+        assert_eq!(plain_run(b"abcdefgh\x01"), 8);
+        assert_eq!(plain_run("abcdefghij\u{00A0}".as_bytes()), 10);
+        // Only the first special byte of a word counts
+        assert_eq!(plain_run(b"ab\n\x00\x00 cd"), 2);
+    }
+
+    #[test]
+    fn test_paragraph_breaks_skip_inline_math() {
+        let thresholds = CleanupThresholds::default();
+        let input = "The relation between energy and mass is $E = mc^2. M = E/c^2$ as shown. \
+                     Another sentence follows.";
+        let result = clean(input, &thresholds, true);
+        assert!(result.contains("$E = mc^2. M = E/c^2$"), "{}", result);
+        assert!(result.contains("as shown.\n\nAnother"), "{}", result);
+
+        // Without math, a price in prose is just a dollar sign
+        let input = "The basic plan costs $5. Upgrades are sold separately at the desk. \
+                     Support is included for the first year of the plan. Renewals are yearly.";
+        let result = clean_extracted_text(input);
+        assert!(result.contains("at the desk.\n\nSupport"), "{}", result);
+        assert!(result.contains("of the plan.\n\nRenewals"), "{}", result);
+        // With math, a `$` never closed stops holding breaks back
+        let stray = format!(
+            "{}{}",
+            input,
+            " A sentence of some length goes on.".repeat(20)
+        );
+        let result = clean(&stray, &thresholds, true);
+        assert!(
+            result.ends_with("goes on.\n\nA sentence of some length goes on."),
+            "{}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_paragraph_breaks_skip_quotes_and_brackets() {
+        let input = "The committee heard from the director at length. \
+                     She said \u{201C}We shipped late. Customers noticed. It hurt.\u{201D} \
+                     The board then asked for a review (see Annex B. Section Two. Costs) \
+                     of the whole programme. Nothing else was discussed that day.";
+        let result = clean_extracted_text(input);
+        assert!(
+            result.contains("\u{201C}We shipped late. Customers noticed. It hurt.\u{201D}"),
+            "{}",
+            result
+        );
+        assert!(
+            result.contains("(see Annex B. Section Two. Costs)"),
+            "{}",
+            result
+        );
+        // Outside them, sentences still break
+        assert!(result.contains("at length.\n\nShe said"), "{}", result);
+        assert!(result.contains("programme.\n\nNothing"), "{}", result);
+
+        // An opening mark never closed stops holding breaks back
+        let stray = format!(
+            "(Stray bracket. {}",
+            "A sentence of some length goes on. ".repeat(20)
+        );
+        assert!(clean_extracted_text(&stray).contains("\n\n"));
+    }
+
+    #[test]
+    fn test_replace_glyphs() {
+        let glyph_map = [
+            ("\u{F0B7}".to_string(), "\u{2022}".to_string()),
+            ("->".to_string(), "\u{2192}".to_string()),
+            ("-".to_string(), "\u{2013}".to_string()),
+            ("\u{2022}".to_string(), "*".to_string()),
+        ];
+        assert_eq!(
+            replace_glyphs("\u{F0B7} a -> b - c", &glyph_map),
+            "\u{2022} a \u{2192} b \u{2013} c"
+        );
+    }
+
+    #[test]
+    fn test_cleanup_thresholds() {
+        let input = "Short news item. Another item. A third one here.";
+        assert_eq!(clean_extracted_text(input), input);
+        let thresholds = CleanupThresholds {
+            min_paragraph_chars: 10,
+            ..Default::default()
+        };
+        assert_eq!(
+            clean_extracted_text_with(input, &thresholds),
+            "Short news item.\n\nAnother item.\n\nA third one here."
+        );
+
+        let spaced = "One\n\n\n\n\nTwo\nThree\nFour";
+        let thresholds = CleanupThresholds {
+            max_blank_lines: 0,
+            ..Default::default()
+        };
+        assert_eq!(clean_extracted_text(spaced), "One\n\nTwo\nThree\nFour");
+        assert_eq!(
+            clean_extracted_text_with(spaced, &thresholds),
+            "One\nTwo\nThree\nFour"
+        );
+    }
 }
@@ -1,16 +1,484 @@
+use crate::{AltTextProvider, PageCache};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
 /// Extracted text content from a PDF document
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ExtractedContent {
     pub text: String,
     pub page_count: usize,
+    pub images: Vec<ExtractedImage>,
+    /// Text of each detected footnote, numbered from 1 in document order
+    pub footnotes: Vec<String>,
+    /// Pages skipped because their text could not be extracted
+    pub failed_pages: Vec<u32>,
+    /// Opening words of each page with text, for finding where the page
+    /// begins in output made from the text
+    pub page_starts: Vec<(u32, String)>,
+    /// Page extraction stopped at, and why, when it ended early with `keep_partial`
+    pub stopped_at: Option<(u32, String)>,
+    /// Text left out because a redaction hides it
+    pub redactions: Redactions,
+    /// Where the extraction spent its time
+    pub timings: ExtractTimings,
 }
 
-/// Metadata extracted from a PDF document
+/// Redactions found while extracting. The text they hide is left out, but
+/// unless a redaction was applied that text is still in the file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Redactions {
+    /// Pages with Redact annotations over text: marked for redaction, but
+    /// never applied
+    pub unapplied: Vec<u32>,
+    /// Pages with text painted over with a dark box
+    pub covered: Vec<u32>,
+    /// Dark boxes over no text, as an applied redaction leaves
+    pub applied: usize,
+}
+
+/// Time spent extracting a document, not counting what was done with the
+/// text of each page as it was handed over
+#[derive(Debug, Clone, Default)]
+pub struct ExtractTimings {
+    /// Reading the pages, from the first to the last
+    pub extraction: Duration,
+    /// Time each page took, in page order
+    pub pages: Vec<(u32, Duration)>,
+    /// Glyph replacement and clean-up of the extracted text
+    pub cleanup: Duration,
+}
+
+/// An embedded image converted to a standalone file
 #[derive(Debug, Clone)]
+pub struct ExtractedImage {
+    /// File name referenced from the extracted text (e.g. `page-3-image-1.png`)
+    pub file_name: String,
+    pub page: u32,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    /// Description supplied by an [`AltTextProvider`]
+    pub alt_text: Option<String>,
+}
+
+/// An image drawn on a page, as listed in the dry-run inventory
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageInfo {
+    pub page: u32,
+    /// Codec the image is stored with (`JPEG`, `JPEG 2000`, `Flate`, ...)
+    pub format: String,
+    pub width: u32,
+    pub height: u32,
+    /// Size of the stored (encoded) image data
+    pub bytes: usize,
+}
+
+/// A font used by the document, as listed in the dry-run inventory
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontInfo {
+    /// `BaseFont` name, with any subset tag (`ABCDEF+Calibri`)
+    pub name: String,
+    /// Font type (`Type1`, `TrueType`, `Type0`, `Type3`, ...)
+    pub subtype: String,
+    /// The font program is embedded in the file
+    pub embedded: bool,
+    /// Only the glyphs used are embedded
+    pub subset: bool,
+    /// The font has a ToUnicode map from glyph codes to text
+    pub to_unicode: bool,
+    /// Encoding name, `custom` for a Differences-only encoding
+    pub encoding: Option<String>,
+    /// Pages using the font
+    pub pages: Vec<u32>,
+}
+
+impl FontInfo {
+    /// Whether text in this font is likely to come out garbled: without a
+    /// ToUnicode map, composite and Type 3 fonts, and embedded fonts relying
+    /// on their built-in encoding, give no reliable way to map glyphs to text
+    pub fn may_garble(&self) -> bool {
+        !self.to_unicode
+            && match self.subtype.as_str() {
+                "Type0" | "Type3" => true,
+                _ => self.embedded && self.encoding.is_none(),
+            }
+    }
+}
+
+/// Numbers of links and annotations in a document, for the dry-run report
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnnotationCounts {
+    /// Links to web addresses or other files
+    pub external_links: usize,
+    /// Links to a place in the document itself
+    pub internal_links: usize,
+    /// Other annotations (comments, highlights, ...) by subtype
+    pub other: BTreeMap<String, usize>,
+}
+
+/// One bookmark of the document outline
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    /// Depth in the outline, from 1 for top-level bookmarks
+    pub level: usize,
+    pub title: String,
+    /// Page the bookmark points to, when it points into this document
+    pub page: Option<u32>,
+}
+
+/// A PDF embedded in a portfolio
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddedDocument {
+    /// File name given by the portfolio
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Metadata extracted from a PDF document
+#[derive(Debug, Clone, Default)]
 pub struct PdfMetadata {
     pub page_count: usize,
     pub title: Option<String>,
     pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// Application the document was created in
+    pub creator: Option<String>,
+    /// Application that wrote the PDF
+    pub producer: Option<String>,
+    /// Creation date from the document info dictionary, as RFC 3339
+    pub creation_date: Option<String>,
+    /// Modification date from the document info dictionary, as RFC 3339
+    pub modification_date: Option<String>,
     pub has_text: bool,
     pub sections: Vec<String>,
+    /// PDF/A conformance the XMP metadata claims (e.g. `PDF/A-2b`)
+    pub pdfa: Option<String>,
+    /// PDF/UA conformance the XMP metadata claims (e.g. `PDF/UA-1`)
+    pub pdfua: Option<String>,
+    /// The document is tagged: marked as such, with a structure tree giving
+    /// its headings, lists and tables
+    pub tagged: bool,
+    /// Signatures of the document's signature fields, in the order of the form
+    pub signatures: Vec<SignatureInfo>,
+}
+
+/// A digital signature held by a signature field
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SignatureInfo {
+    /// Fully qualified name of the form field
+    pub field: String,
+    /// Signer, from the signature dictionary or else the common name of the
+    /// signing certificate
+    pub signer: Option<String>,
+    /// Signing time the signature dictionary gives, as RFC 3339
+    pub signing_time: Option<String>,
+    /// The file goes on past the bytes the signature covers: a revision was
+    /// appended after signing
+    pub modified_after_signing: bool,
+}
+
+/// PDF version and the features in use that affect extraction
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfCompatibility {
+    /// Version from the file header (e.g. `1.7`)
+    pub version: String,
+    /// Encryption method (e.g. `AES-128`) when the document is encrypted
+    pub encryption: Option<String>,
+    /// The document was decrypted, with the empty user password unless
+    /// `with_password`
+    pub decrypted: bool,
+    /// A password given for the document decrypted it
+    pub with_password: bool,
+    /// The document carries an XFA form
+    pub xfa: bool,
+    /// The XFA form is dynamic: pages are laid out by the viewer
+    pub dynamic_xfa: bool,
+    /// Objects are stored in compressed object streams (PDF 1.5+)
+    pub object_streams: bool,
+    /// Revisions appended to the original file
+    pub incremental_updates: usize,
+    /// Objects recovered by scanning the file when its xref table was damaged
+    pub recovered_objects: Option<usize>,
+}
+
+/// Options controlling text extraction
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    /// Emit text set in math fonts, and the formulas a tagged PDF marks, as
+    /// LaTeX (`$...$` inline, `$$...$$` display); a formula's MathML is used
+    /// when it has one
+    pub math: bool,
+    /// With `math`, leave a comment in place of each display equation whose
+    /// glyphs could not be decoded, instead of its garbled text
+    pub mark_lost_equations: bool,
+    /// Extract embedded images and reference them from the text
+    pub images: bool,
+    /// Path prepended to image file names in references (e.g. `images/`)
+    pub image_prefix: String,
+    /// Hook supplying alt text for each extracted image
+    pub alt_text: Option<Arc<dyn AltTextProvider>>,
+    /// Convert vector drawings (charts, diagrams) to SVG files referenced from the text
+    pub vector_graphics: bool,
+    /// File format for extracted raster images
+    pub image_format: ImageFormat,
+    /// Skip images narrower or shorter than this many pixels (points for SVG)
+    pub min_image_size: u32,
+    /// File extensions of images not to extract (e.g. `jpg`, `svg`)
+    pub skip_image_types: Vec<String>,
+    /// Detect footnotes and write them in this style
+    pub footnotes: Option<FootnoteStyle>,
+    /// Write term/definition layouts as definition lists (`Term\n: definition`)
+    pub definition_lists: bool,
+    /// Write horizontal lines drawn across the page as `---` separators
+    pub horizontal_rules: bool,
+    /// Mark up underlined text in this style
+    pub underline: Option<UnderlineStyle>,
+    /// Write struck-through text as `~~text~~`
+    pub strikethrough: bool,
+    /// Wrap coloured text in `<span style="color:...">`
+    pub text_colors: bool,
+    /// Fail on the first page that cannot be extracted instead of skipping it
+    pub strict: bool,
+    /// When extraction stops early, return the pages extracted so far instead of an error
+    pub keep_partial: bool,
+    /// Thresholds of the blank-line and paragraph-break clean-up
+    pub cleanup: CleanupThresholds,
+    /// Replacements for characters or strings of the extracted text, such as
+    /// private-use glyphs of symbol fonts; the longest match wins
+    pub glyph_map: Vec<(String, String)>,
+    /// Text of the notice left in place of a page that could not be
+    /// extracted, with `{page}` for its number; English when `None`
+    pub failed_page_note: Option<String>,
+    /// Store of page text from earlier runs; pages whose content is
+    /// unchanged are read from it instead of being extracted again
+    pub page_cache: Option<Arc<dyn PageCache>>,
+}
+
+/// Thresholds of the heuristics tidying extracted text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CleanupThresholds {
+    /// Blank lines kept in a row; longer runs are shortened
+    pub max_blank_lines: usize,
+    /// In text with almost no line breaks, a paragraph is split after a
+    /// sentence once it is longer than this many characters
+    pub min_paragraph_chars: usize,
+    /// ... and between glued words (`endIntroduction`) once it is longer than this
+    pub min_section_chars: usize,
+}
+
+impl Default for CleanupThresholds {
+    fn default() -> Self {
+        Self {
+            max_blank_lines: 1,
+            min_paragraph_chars: 40,
+            min_section_chars: 20,
+        }
+    }
+}
+
+impl ExtractOptions {
+    /// Whether an extracted image passes the size and type filters
+    pub fn keeps_image(&self, extension: &str, width: u32, height: u32) -> bool {
+        width.min(height) >= self.min_image_size
+            && !self
+                .skip_image_types
+                .iter()
+                .any(|skipped| image_type(skipped) == extension)
+    }
+}
+
+/// Normalise a user-supplied image type to the extension used for extracted files
+fn image_type(name: &str) -> String {
+    let name = name.trim().trim_start_matches('.').to_ascii_lowercase();
+    match name.as_str() {
+        "jpeg" => "jpg".to_string(),
+        "jpx" | "jpeg2000" => "jp2".to_string(),
+        _ => name,
+    }
+}
+
+/// File format for extracted raster images
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ImageFormat {
+    /// Keep JPEG and JPEG 2000 data as is; convert everything else to PNG
+    #[default]
+    Original,
+    /// Convert every decodable image to PNG
+    Png,
+}
+
+impl std::str::FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "original" => Ok(ImageFormat::Original),
+            "png" => Ok(ImageFormat::Png),
+            other => Err(format!(
+                "unknown image format '{}' (expected 'original' or 'png')",
+                other
+            )),
+        }
+    }
+}
+
+/// How detected footnotes are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FootnoteStyle {
+    /// Pandoc inline notes: `^[text]` where the marker was
+    Inline,
+    /// GFM footnotes: `[^1]` markers with `[^1]: text` definitions at the end
+    #[default]
+    Reference,
+    /// `<sup>1</sup>` markers and a numbered "Notes" section at the end
+    Notes,
+}
+
+impl std::str::FromStr for FootnoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "inline" => Ok(FootnoteStyle::Inline),
+            "reference" => Ok(FootnoteStyle::Reference),
+            "notes" => Ok(FootnoteStyle::Notes),
+            other => Err(format!(
+                "unknown footnote style '{}' (expected 'inline', 'reference' or 'notes')",
+                other
+            )),
+        }
+    }
+}
+
+/// How underlined text is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnderlineStyle {
+    /// `*text*`
+    Emphasis,
+    /// `**text**`
+    Bold,
+    /// `<u>text</u>`
+    Html,
+}
+
+impl std::str::FromStr for UnderlineStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "emphasis" => Ok(UnderlineStyle::Emphasis),
+            "bold" => Ok(UnderlineStyle::Bold),
+            "html" => Ok(UnderlineStyle::Html),
+            other => Err(format!(
+                "unknown underline style '{}' (expected 'emphasis', 'bold' or 'html')",
+                other
+            )),
+        }
+    }
+}
+
+/// Pages chosen by number, from 1: `3`, `1-5`, `10-` (to the last page) or
+/// `-4` (from the first), separated by commas
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageSelection(Vec<(u32, Option<u32>)>);
+
+impl PageSelection {
+    /// The pages chosen of a document with `page_count` pages, in order and
+    /// each once
+    pub fn pages(&self, page_count: u32) -> Vec<u32> {
+        let mut pages: Vec<u32> = self
+            .0
+            .iter()
+            .flat_map(|&(first, last)| first..=last.unwrap_or(page_count).min(page_count))
+            .collect();
+        pages.sort_unstable();
+        pages.dedup();
+        pages
+    }
+}
+
+impl std::str::FromStr for PageSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let number = |part: &str| match part.trim().parse::<u32>() {
+            Ok(0) => Err("pages are numbered from 1".to_string()),
+            Ok(n) => Ok(n),
+            Err(_) => Err(format!("'{}' is not a page number", part.trim())),
+        };
+        let mut ranges = Vec::new();
+        for part in s.split(',') {
+            let range = match part.split_once('-') {
+                None => (number(part)?, Some(number(part)?)),
+                Some((first, last)) => {
+                    let first = if first.trim().is_empty() {
+                        1
+                    } else {
+                        number(first)?
+                    };
+                    let last = if last.trim().is_empty() {
+                        None
+                    } else {
+                        Some(number(last)?)
+                    };
+                    if last.is_some_and(|last| last < first) {
+                        return Err(format!("'{}' ends before it starts", part.trim()));
+                    }
+                    (first, last)
+                }
+            };
+            ranges.push(range);
+        }
+        Ok(Self(ranges))
+    }
+}
+
+impl std::fmt::Display for PageSelection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, &(first, last)) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            match last {
+                Some(last) if last == first => write!(f, "{}", first)?,
+                Some(last) => write!(f, "{}-{}", first, last)?,
+                None => write!(f, "{}-", first)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keeps_image_applies_size_and_type_filters() {
+        let options = ExtractOptions {
+            min_image_size: 32,
+            skip_image_types: vec!["JPEG".to_string(), ".svg".to_string()],
+            ..Default::default()
+        };
+        assert!(options.keeps_image("png", 64, 32));
+        assert!(!options.keeps_image("png", 64, 16));
+        assert!(!options.keeps_image("jpg", 100, 100));
+        assert!(!options.keeps_image("svg", 100, 100));
+        assert!(ExtractOptions::default().keeps_image("jpg", 1, 1));
+    }
+
+    #[test]
+    fn test_page_selection() {
+        let selection: PageSelection = "7, 1-3,2,10-".parse().unwrap();
+        assert_eq!(selection.pages(12), [1, 2, 3, 7, 10, 11, 12]);
+        assert_eq!(selection.pages(2), [1, 2]);
+        assert_eq!(selection.to_string(), "7,1-3,2,10-");
+        assert_eq!("-2".parse::<PageSelection>().unwrap().pages(5), [1, 2]);
+        assert!("0".parse::<PageSelection>().is_err());
+        assert!("5-3".parse::<PageSelection>().is_err());
+        assert!("1,,2".parse::<PageSelection>().is_err());
+        assert!("".parse::<PageSelection>().is_err());
+    }
 }
@@ -11,18 +11,25 @@ pub fn validate_pdf(path: &Path) -> Result<()> {
     // Read PDF header
     let mut file = File::open(path)?;
     let mut header = [0u8; 5];
+    let read = file.read(&mut header)?;
+    check_header(&header[..read])?;
 
-    file.read_exact(&mut header).map_err(|_| {
-        PdfError::Processing("Failed to read PDF header (file too small)".to_string())
-    })?;
+    info!("PDF validation successful");
+    Ok(())
+}
 
-    if &header != b"%PDF-" {
+/// Check that PDF data starts with the `%PDF-` header
+pub fn check_header(bytes: &[u8]) -> Result<()> {
+    if bytes.len() < 5 {
+        return Err(PdfError::Processing(
+            "Failed to read PDF header (file too small)".to_string(),
+        ));
+    }
+    if &bytes[..5] != b"%PDF-" {
         return Err(PdfError::Processing(
             "File is not a valid PDF (missing PDF header)".to_string(),
         ));
     }
-
-    info!("PDF validation successful");
     Ok(())
 }
 
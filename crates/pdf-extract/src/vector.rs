@@ -0,0 +1,292 @@
+use super::decoration;
+use super::geometry::{PathShape, Rect, Segment};
+use super::layout::{PageLayout, TextRun};
+use std::fmt::Write;
+
+/// Paths closer than this (in points) belong to the same drawing
+const JOIN_MARGIN: f32 = 6.0;
+
+/// Fewer paths than this is decoration (rules, boxes), not a drawing
+const MIN_PATHS: usize = 3;
+
+/// Smallest drawing worth extracting, in points on each side
+const MIN_SIZE: f32 = 36.0;
+
+/// Paths covering this share of the page are backgrounds or frames
+const BACKGROUND_COVERAGE: f32 = 0.8;
+
+/// Thickest path, in points, still treated as a line rather than a box
+const MAX_LINE_THICKNESS: f32 = 3.0;
+
+/// A horizontal rule spans at least this share of the page width
+const RULE_WIDTH: f32 = 0.5;
+
+/// Rules closer than this (in points) are one double rule
+const DOUBLE_RULE_GAP: f32 = 4.0;
+
+/// A cluster of vector paths forming one chart or diagram, with the text
+/// labels drawn inside it
+#[derive(Debug, Clone)]
+pub struct Drawing {
+    pub bounds: Rect,
+    pub paths: Vec<PathShape>,
+    pub labels: Vec<TextRun>,
+}
+
+impl Drawing {
+    /// Whether a text run's origin lies inside the drawing
+    pub fn contains(&self, run: &TextRun) -> bool {
+        self.bounds.contains(run.x, run.y)
+    }
+}
+
+/// Group the page's vector paths into drawings, top to bottom
+pub fn find_drawings(layout: &PageLayout) -> Vec<Drawing> {
+    let page_area = layout.page_box.area();
+    let mut clusters: Vec<(Rect, Vec<&PathShape>)> = Vec::new();
+
+    for path in &layout.paths {
+        let background = page_area > 0.0 && path.bounds.area() >= page_area * BACKGROUND_COVERAGE;
+        // White fills without an outline only erase the page
+        let blank = path.stroke.is_none() && path.fill.is_some_and(|c| c.is_white());
+        if background || blank {
+            continue;
+        }
+        let mut bounds = path.bounds;
+        let mut members = vec![path];
+        // Merge every cluster this path touches, directly or through the growing bounds
+        loop {
+            let touching = clusters
+                .iter()
+                .position(|(b, _)| b.expand(JOIN_MARGIN).intersects(&bounds));
+            let Some(index) = touching else {
+                break;
+            };
+            let (b, paths) = clusters.swap_remove(index);
+            bounds = bounds.union(&b);
+            members.extend(paths);
+        }
+        clusters.push((bounds, members));
+    }
+
+    let mut drawings: Vec<Drawing> = clusters
+        .into_iter()
+        .filter(|(bounds, paths)| {
+            paths.len() >= MIN_PATHS && bounds.width >= MIN_SIZE && bounds.height >= MIN_SIZE
+        })
+        .map(|(bounds, paths)| {
+            let mut drawing = Drawing {
+                bounds,
+                paths: paths.into_iter().cloned().collect(),
+                labels: Vec::new(),
+            };
+            drawing.labels = layout
+                .runs
+                .iter()
+                .filter(|run| drawing.contains(run))
+                .cloned()
+                .collect();
+            drawing
+        })
+        .collect();
+    drawings.sort_by(|a, b| b.bounds.top().total_cmp(&a.bounds.top()));
+    drawings
+}
+
+/// Whether a path is a visible, thin horizontal line (a stroked line or a filled hairline box)
+pub fn is_horizontal_line(path: &PathShape) -> bool {
+    let visible = path.stroke.or(path.fill).is_some_and(|c| !c.is_white());
+    visible && path.bounds.height <= MAX_LINE_THICKNESS && path.bounds.width > path.bounds.height
+}
+
+/// Vertical positions of horizontal rules spanning most of the page, top to bottom.
+///
+/// Lines underlining or striking through text are not rules.
+pub fn find_rules(layout: &PageLayout) -> Vec<f32> {
+    let min_width = layout.page_box.width * RULE_WIDTH;
+    let mut rules: Vec<f32> = layout
+        .paths
+        .iter()
+        .filter(|path| is_horizontal_line(path) && path.bounds.width >= min_width)
+        .filter(|path| !decoration::decorates_text(&path.bounds, layout))
+        .map(|path| path.bounds.y + path.bounds.height / 2.0)
+        .collect();
+    rules.sort_by(|a, b| b.total_cmp(a));
+    rules.dedup_by(|below, above| *above - *below <= DOUBLE_RULE_GAP);
+    rules
+}
+
+/// Render a drawing as a standalone SVG document.
+///
+/// Coordinates are shifted so the drawing's bounding box starts at the
+/// origin, with the y axis flipped to SVG's top-down convention.
+pub fn to_svg(drawing: &Drawing) -> String {
+    let margin = drawing
+        .paths
+        .iter()
+        .map(|path| path.line_width / 2.0)
+        .fold(1.0, f32::max);
+    let view = drawing.bounds.expand(margin);
+    let point = |x: f32, y: f32| format!("{} {}", num(x - view.x), num(view.top() - y));
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = num(view.width),
+        h = num(view.height)
+    );
+    for path in &drawing.paths {
+        let d: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| match *segment {
+                Segment::MoveTo(x, y) => format!("M{}", point(x, y)),
+                Segment::LineTo(x, y) => format!("L{}", point(x, y)),
+                Segment::CurveTo(x1, y1, x2, y2, x3, y3) => {
+                    format!("C{} {} {}", point(x1, y1), point(x2, y2), point(x3, y3))
+                }
+                Segment::Close => "Z".to_string(),
+            })
+            .collect();
+        let fill = path.fill.map_or("none".to_string(), |c| c.hex());
+        let _ = write!(svg, r#"  <path d="{}" fill="{}""#, d.join(" "), fill);
+        if let Some(stroke) = path.stroke {
+            let _ = write!(
+                svg,
+                r#" stroke="{}" stroke-width="{}""#,
+                stroke.hex(),
+                num(path.line_width)
+            );
+        }
+        svg.push_str("/>\n");
+    }
+    for label in &drawing.labels {
+        let _ = writeln!(
+            svg,
+            r#"  <text x="{}" y="{}" font-size="{}" font-family="{}">{}</text>"#,
+            num(label.x - view.x),
+            num(view.top() - label.y),
+            num(label.size),
+            escape(&label.font),
+            escape(label.text.trim())
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Format a coordinate with at most two decimals
+fn num(value: f32) -> String {
+    let text = format!("{:.2}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0" } else { text }.to_string()
+}
+
+/// Escape text for XML content and attributes
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Color;
+
+    fn line(x0: f32, y0: f32, x1: f32, y1: f32) -> PathShape {
+        PathShape {
+            segments: vec![Segment::MoveTo(x0, y0), Segment::LineTo(x1, y1)],
+            stroke: Some(Color::BLACK),
+            fill: None,
+            line_width: 1.0,
+            bounds: Rect::from_corners(x0, y0, x1, y1),
+            runs_before: 0,
+        }
+    }
+
+    fn chart_layout() -> PageLayout {
+        PageLayout {
+            page_box: Rect::from_corners(0.0, 0.0, 612.0, 792.0),
+            runs: vec![
+                TextRun {
+                    text: "Q1".to_string(),
+                    font: "Helvetica".to_string(),
+                    size: 8.0,
+                    x: 110.0,
+                    y: 410.0,
+                    width: 8.0,
+                    color: Color::BLACK,
+                    mcid: None,
+                },
+                TextRun {
+                    text: "Body text".to_string(),
+                    font: "Helvetica".to_string(),
+                    size: 12.0,
+                    x: 72.0,
+                    y: 700.0,
+                    width: 54.0,
+                    color: Color::BLACK,
+                    mcid: None,
+                },
+            ],
+            images: Vec::new(),
+            paths: vec![
+                // Axes and a data line of a small chart
+                line(100.0, 400.0, 300.0, 400.0),
+                line(100.0, 400.0, 100.0, 550.0),
+                line(100.0, 420.0, 290.0, 540.0),
+                // A lone horizontal rule elsewhere
+                line(72.0, 650.0, 540.0, 650.0),
+                // Full-page frame
+                line(0.0, 0.0, 612.0, 792.0),
+            ],
+            markup: Vec::new(),
+            formulas: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_drawings_clusters_connected_paths() {
+        let drawings = find_drawings(&chart_layout());
+        assert_eq!(drawings.len(), 1);
+        let drawing = &drawings[0];
+        assert_eq!(drawing.paths.len(), 3);
+        assert_eq!(
+            drawing.bounds,
+            Rect::from_corners(100.0, 400.0, 300.0, 550.0)
+        );
+        assert_eq!(drawing.labels.len(), 1);
+        assert_eq!(drawing.labels[0].text, "Q1");
+    }
+
+    #[test]
+    fn test_find_rules_keeps_wide_thin_lines() {
+        let mut layout = chart_layout();
+        // A double rule, and a short underline that is not a rule
+        layout.paths.push(line(72.0, 300.0, 540.0, 300.0));
+        layout.paths.push(line(72.0, 297.0, 540.0, 297.0));
+        layout.paths.push(line(72.0, 200.0, 120.0, 200.0));
+        assert_eq!(find_rules(&layout), vec![650.0, 300.0]);
+    }
+
+    #[test]
+    fn test_to_svg_flips_y_axis() {
+        let drawing = &find_drawings(&chart_layout())[0];
+        let svg = to_svg(drawing);
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"202\""));
+        // (100, 400) is the bottom-left corner: x = 1, y = height - 1
+        assert!(svg.contains(r##"<path d="M1 151 L201 151" fill="none" stroke="#000000""##));
+        assert!(svg.contains(">Q1</text>"));
+        assert!(svg.ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn test_num_trims_trailing_zeros() {
+        assert_eq!(num(1.5), "1.5");
+        assert_eq!(num(2.0), "2");
+        assert_eq!(num(-0.001), "0");
+    }
+}
@@ -0,0 +1,94 @@
+#!/usr/bin/env python3
+"""
+Generate src/normalize/tables.rs, the Unicode data behind --normalize, from
+the unicodedata module of the Python running it.
+
+Usage: python3 scripts/generate_unicode_tables.py > src/normalize/tables.rs
+"""
+
+import sys
+import unicodedata
+
+HANGUL = range(0xAC00, 0xD7A4)
+PER_LINE = 4
+
+
+def literal(text):
+    return "".join(c if " " <= c <= "~" and c not in "\"\\'" else f"\\u{{{ord(c):x}}}" for c in text)
+
+
+def chars():
+    for code in range(0x110000):
+        if 0xD800 <= code <= 0xDFFF or code in HANGUL:
+            continue
+        yield chr(code)
+
+
+def table(name, doc, kind, entries):
+    print()
+    print(f"/// {doc}")
+    print("#[rustfmt::skip]")
+    print(f"pub static {name}: &[{kind}] = &[")
+    for i in range(0, len(entries), PER_LINE):
+        print("    " + " ".join(entry + "," for entry in entries[i : i + PER_LINE]))
+    print("];")
+
+
+def main():
+    classes = []
+    for c in chars():
+        ccc = unicodedata.combining(c)
+        if ccc == 0:
+            continue
+        if classes and classes[-1][2] == ccc and ord(classes[-1][1]) + 1 == ord(c):
+            classes[-1][1] = c
+        else:
+            classes.append([c, c, ccc])
+
+    canonical, compatibility, compositions = [], [], []
+    for c in chars():
+        nfd = unicodedata.normalize("NFD", c)
+        nfkd = unicodedata.normalize("NFKD", c)
+        if nfd != c:
+            canonical.append(f"('{literal(c)}', \"{literal(nfd)}\")")
+        if nfkd != nfd:
+            compatibility.append(f"('{literal(c)}', \"{literal(nfkd)}\")")
+        mapping = unicodedata.decomposition(c)
+        if mapping and not mapping.startswith("<"):
+            parts = [chr(int(code, 16)) for code in mapping.split()]
+            # Primary composites: two-character mappings NFC puts back together
+            if len(parts) == 2 and unicodedata.normalize("NFC", c) == c:
+                compositions.append((parts[0], parts[1], c))
+    compositions.sort()
+
+    print("// @generated by scripts/generate_unicode_tables.py; do not edit")
+    print()
+    print(f"//! Unicode {unicodedata.unidata_version} character data for `--normalize`")
+    table(
+        "COMBINING_CLASSES",
+        "Canonical combining class of the characters whose class is not 0, as ranges",
+        "(char, char, u8)",
+        [f"('{literal(a)}', '{literal(b)}', {ccc})" for a, b, ccc in classes],
+    )
+    table(
+        "CANONICAL",
+        "Full canonical decomposition (NFD) of each character that has one, Hangul syllables aside",
+        "(char, &str)",
+        canonical,
+    )
+    table(
+        "COMPATIBILITY",
+        "Full compatibility decomposition (NFKD) of each character where it differs from the canonical one",
+        "(char, &str)",
+        compatibility,
+    )
+    table(
+        "COMPOSITIONS",
+        "The two characters each primary composite is made of, and the composite, by pair",
+        "(char, char, char)",
+        [f"('{literal(a)}', '{literal(b)}', '{literal(c)}')" for a, b, c in compositions],
+    )
+
+
+if __name__ == "__main__":
+    sys.exit(main())
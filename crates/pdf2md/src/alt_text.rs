@@ -0,0 +1,109 @@
+use log::{debug, warn};
+use pdf_extract::{AltTextProvider, ExtractedImage};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Alt text supplied by an external command.
+///
+/// The image bytes are written to the command's stdin and its trimmed stdout
+/// becomes the alt text. `PDF2MD_IMAGE_NAME`, `PDF2MD_IMAGE_PAGE`,
+/// `PDF2MD_IMAGE_WIDTH` and `PDF2MD_IMAGE_HEIGHT` describe the image.
+#[derive(Debug)]
+pub struct CommandAltText {
+    command: String,
+}
+
+impl CommandAltText {
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+        }
+    }
+
+    fn run(&self, image: &ExtractedImage) -> std::io::Result<String> {
+        let mut child = shell(&self.command)
+            .env("PDF2MD_IMAGE_NAME", &image.file_name)
+            .env("PDF2MD_IMAGE_PAGE", image.page.to_string())
+            .env("PDF2MD_IMAGE_WIDTH", image.width.to_string())
+            .env("PDF2MD_IMAGE_HEIGHT", image.height.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // The command may not read the image at all; a closed pipe is fine
+            let _ = stdin.write_all(&image.data);
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "command exited with {}",
+                output.status
+            )));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+}
+
+impl AltTextProvider for CommandAltText {
+    fn alt_text(&self, image: &ExtractedImage) -> Option<String> {
+        match self.run(image) {
+            Ok(text) if !text.is_empty() => {
+                debug!("Alt text for {}: {}", image.file_name, text);
+                Some(text)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!("Alt text command failed for {}: {}", image.file_name, e);
+                None
+            }
+        }
+    }
+}
+
+/// Build a command that runs through the platform shell
+pub fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> ExtractedImage {
+        ExtractedImage {
+            file_name: "page-2-image-1.png".to_string(),
+            page: 2,
+            width: 10,
+            height: 20,
+            data: vec![1, 2, 3],
+            alt_text: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_alt_text_reads_stdout() {
+        let provider = CommandAltText::new("echo \"Chart on page $PDF2MD_IMAGE_PAGE\"");
+        assert_eq!(
+            provider.alt_text(&image()),
+            Some("Chart on page 2".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command_alt_text_failure_returns_none() {
+        let provider = CommandAltText::new("exit 3");
+        assert_eq!(provider.alt_text(&image()), None);
+    }
+}
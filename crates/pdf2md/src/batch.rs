@@ -0,0 +1,479 @@
+use crate::config::Config;
+use crate::i18n::{Message, tr, trf};
+use crate::json::Value;
+use crate::style::{self, Stream, Style};
+use crate::{Converted, Pdf2MdError, Result, provenance, search};
+use chrono::{SecondsFormat, Utc};
+use log::info;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Written in the output directory after a batch, listing what became of
+/// each input
+const MANIFEST: &str = "manifest.json";
+
+/// Where `--batch-report` writes, and in which format, by the file's extension
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchReport {
+    Csv(PathBuf),
+    Json(PathBuf),
+}
+
+impl std::str::FromStr for BatchReport {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let path = PathBuf::from(s);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Ok(BatchReport::Csv(path)),
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Ok(BatchReport::Json(path)),
+            _ => Err(format!(
+                "unknown report format '{}' (expected a .csv or .json file)",
+                s
+            )),
+        }
+    }
+}
+
+/// Columns of the batch report
+const REPORT_COLUMNS: [&str; 9] = [
+    "input", "output", "status", "pages", "words", "warnings", "seconds", "bytes", "error",
+];
+
+/// Outcome of converting one file of a batch
+struct FileResult {
+    input: PathBuf,
+    output: PathBuf,
+    /// SHA-256 of the input, when it could be read
+    sha256: Option<String>,
+    elapsed: Duration,
+    result: Result<Converted>,
+}
+
+/// Convert every PDF in the input directory into the `-o` directory.
+///
+/// `config.jobs` worker threads each take the next file off a shared queue
+/// as soon as they finish one, so a few large documents cannot leave the
+/// other workers idle. Outputs mirror the input tree: `in/a/b.pdf` becomes
+/// `out/a/b.md`, with its images under `<image-dir>/b/` next to it. Returns
+/// the number of files converted.
+pub fn run_batch(config: &Config) -> Result<usize> {
+    for (set, option) in [(config.dry_run, "--dry-run"), (config.confirm, "--confirm")] {
+        if set {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "{} previews a single PDF; pass a file, not a directory",
+                option
+            )));
+        }
+    }
+    let inputs = find_pdfs(&config.input_path, config.recursive)?;
+    if inputs.is_empty() {
+        return Err(Pdf2MdError::InvalidInput(format!(
+            "no PDF files in {}{}",
+            config.input_path.display(),
+            if config.recursive {
+                ""
+            } else {
+                " (add --recursive to search subdirectories)"
+            }
+        )));
+    }
+    let jobs = match config.jobs {
+        // Each conversion has its own ceiling, so only one runs at a time
+        0 if config.low_memory => 1,
+        0 => std::thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+    .min(inputs.len());
+    info!("Converting {} files with {} jobs", inputs.len(), jobs);
+
+    let started = Instant::now();
+    let started_at = Utc::now();
+    let next = AtomicUsize::new(0);
+    let done = AtomicUsize::new(0);
+    let mut results: Vec<FileResult> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..jobs)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut results = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(input) = inputs.get(i) else {
+                            break;
+                        };
+                        let file_config = file_config(config, input);
+                        let output = file_config.output_path.clone();
+                        let start = Instant::now();
+                        let result = crate::convert(file_config);
+                        let elapsed = start.elapsed();
+                        let sha256 = provenance::hash(input).ok().map(|(_, sha256)| sha256);
+                        let n = done.fetch_add(1, Ordering::Relaxed) + 1;
+                        match &result {
+                            Ok(_) => eprintln!(
+                                "{} {} -> {} ({:.2}s)",
+                                style::paint(
+                                    &format!("[{}/{}]", n, inputs.len()),
+                                    Style::Success,
+                                    Stream::Stderr
+                                ),
+                                input.display(),
+                                output.display(),
+                                elapsed.as_secs_f64()
+                            ),
+                            Err(e) => eprintln!(
+                                "[{}/{}] {}: {}: {}",
+                                n,
+                                inputs.len(),
+                                input.display(),
+                                style::error(tr(Message::Error)),
+                                e
+                            ),
+                        }
+                        results.push(FileResult {
+                            input: input.clone(),
+                            output,
+                            sha256,
+                            elapsed,
+                            result,
+                        });
+                    }
+                    results
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("conversion worker panicked"))
+            .collect()
+    });
+
+    // Timing per file, slowest first
+    results.sort_by_key(|file| std::cmp::Reverse(file.elapsed));
+    for file in &results {
+        info!(
+            "{:.2}s {}",
+            file.elapsed.as_secs_f64(),
+            file.input.display()
+        );
+    }
+    let failed = results.iter().filter(|file| file.result.is_err()).count();
+    eprintln!(
+        "{}",
+        trf(
+            Message::BatchSummary,
+            &[
+                ("ok", &(results.len() - failed)),
+                ("total", &results.len()),
+                (
+                    "seconds",
+                    &format!("{:.1}", started.elapsed().as_secs_f64())
+                ),
+                ("jobs", &jobs),
+            ]
+        )
+    );
+    if let Some(slowest) = results.first() {
+        eprintln!(
+            "{}",
+            trf(
+                Message::Slowest,
+                &[
+                    ("file", &slowest.input.display()),
+                    ("seconds", &format!("{:.2}", slowest.elapsed.as_secs_f64())),
+                ]
+            )
+        );
+    }
+
+    // A check leaves the output directory as it is
+    if !config.check {
+        write_manifest(config, started_at, started.elapsed(), &results)?;
+    }
+    if let Some(report) = &config.batch_report {
+        write_report(report, &results)?;
+    }
+
+    // Index what was converted, even when some files failed
+    if let Some(target) = config.search_index.as_ref().filter(|_| !config.check) {
+        let mut outputs: Vec<PathBuf> = results
+            .iter()
+            .filter(|file| file.result.is_ok())
+            .map(|file| file.output.clone())
+            .collect();
+        outputs.sort();
+        search::write(target, &config.output_path, &outputs)?;
+    }
+
+    if failed > 0 {
+        return Err(Pdf2MdError::Batch {
+            failed,
+            total: results.len(),
+        });
+    }
+    Ok(results.len())
+}
+
+/// Write the manifest of a batch to the output directory: every input in
+/// order, with the files written for it, its checksum, status and timing
+fn write_manifest(
+    config: &Config,
+    started: chrono::DateTime<Utc>,
+    elapsed: Duration,
+    results: &[FileResult],
+) -> Result<()> {
+    let path = |path: &Path| Value::String(path.display().to_string());
+    let seconds =
+        |elapsed: Duration| Value::Number((elapsed.as_secs_f64() * 1000.0).round() / 1000.0);
+    let mut files: Vec<&FileResult> = results.iter().collect();
+    files.sort_by(|a, b| a.input.cmp(&b.input));
+    let files = files
+        .into_iter()
+        .map(|file| {
+            let (status, written, warnings, error) = match &file.result {
+                Ok(converted) if converted.warnings.is_empty() => (
+                    "ok",
+                    converted.files.as_slice(),
+                    &converted.warnings[..],
+                    None,
+                ),
+                Ok(converted) => (
+                    "warnings",
+                    converted.files.as_slice(),
+                    &converted.warnings[..],
+                    None,
+                ),
+                Err(e) => ("failed", &[][..], &[][..], Some(e.to_string())),
+            };
+            Value::Object(vec![
+                ("input".to_string(), path(&file.input)),
+                (
+                    "outputs".to_string(),
+                    Value::Array(written.iter().map(|file| path(file)).collect()),
+                ),
+                (
+                    "sha256".to_string(),
+                    file.sha256.clone().map_or(Value::Null, Value::String),
+                ),
+                ("status".to_string(), Value::String(status.to_string())),
+                ("seconds".to_string(), seconds(file.elapsed)),
+                (
+                    "warnings".to_string(),
+                    Value::Array(warnings.iter().cloned().map(Value::String).collect()),
+                ),
+                (
+                    "error".to_string(),
+                    error.map_or(Value::Null, Value::String),
+                ),
+            ])
+        })
+        .collect();
+
+    let manifest = Value::Object(vec![
+        (
+            "converter".to_string(),
+            Value::String(format!("pdf2md {}", env!("CARGO_PKG_VERSION"))),
+        ),
+        ("input".to_string(), path(&config.input_path)),
+        ("output".to_string(), path(&config.output_path)),
+        (
+            "started".to_string(),
+            Value::String(started.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        ),
+        ("seconds".to_string(), seconds(elapsed)),
+        ("files".to_string(), Value::Array(files)),
+    ]);
+    let path = config.output_path.join(MANIFEST);
+    info!("Writing manifest to: {}", path.display());
+    markdown_gen::write_to_file(&(manifest.to_pretty_json() + "\n"), &path)?;
+    Ok(())
+}
+
+/// Write the `--batch-report` table: a row for every input in order, with
+/// its pages, words, warnings, seconds, output size and status
+fn write_report(report: &BatchReport, results: &[FileResult]) -> Result<()> {
+    let mut files: Vec<&FileResult> = results.iter().collect();
+    files.sort_by(|a, b| a.input.cmp(&b.input));
+    let rows: Vec<[Value; 9]> = files.into_iter().map(report_row).collect();
+    let (text, path) = match report {
+        BatchReport::Csv(path) => {
+            let mut csv = REPORT_COLUMNS.join(",") + "\n";
+            for row in &rows {
+                let fields: Vec<String> = row.iter().map(csv_field).collect();
+                csv.push_str(&fields.join(","));
+                csv.push('\n');
+            }
+            (csv, path)
+        }
+        BatchReport::Json(path) => {
+            let rows = rows
+                .into_iter()
+                .map(|row| {
+                    Value::Object(
+                        REPORT_COLUMNS
+                            .iter()
+                            .map(|column| column.to_string())
+                            .zip(row)
+                            .collect(),
+                    )
+                })
+                .collect();
+            (Value::Array(rows).to_pretty_json() + "\n", path)
+        }
+    };
+    info!("Writing batch report to: {}", path.display());
+    markdown_gen::write_to_file(&text, path)?;
+    Ok(())
+}
+
+/// One file's values, in `REPORT_COLUMNS` order; those a failed conversion
+/// has none of are null
+fn report_row(file: &FileResult) -> [Value; 9] {
+    let number = |n: usize| Value::Number(n as f64);
+    let (status, converted, error) = match &file.result {
+        Ok(converted) if converted.warnings.is_empty() => ("ok", Some(converted), Value::Null),
+        Ok(converted) => ("warnings", Some(converted), Value::Null),
+        Err(e) => ("failed", None, Value::String(e.to_string())),
+    };
+    let bytes = converted
+        .and_then(|_| std::fs::metadata(&file.output).ok())
+        .filter(|metadata| metadata.is_file())
+        .map_or(Value::Null, |metadata| Value::Number(metadata.len() as f64));
+    [
+        Value::String(file.input.display().to_string()),
+        Value::String(file.output.display().to_string()),
+        Value::String(status.to_string()),
+        converted.map_or(Value::Null, |converted| number(converted.pages)),
+        converted.map_or(Value::Null, |converted| number(converted.words)),
+        converted.map_or(Value::Null, |converted| number(converted.warnings.len())),
+        Value::Number((file.elapsed.as_secs_f64() * 1000.0).round() / 1000.0),
+        bytes,
+        error,
+    ]
+}
+
+/// A value as a CSV field: null is empty, and text with commas, quotes or
+/// line breaks is quoted
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => return String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_json(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+/// The PDFs (by extension) in `dir`, and with `recursive` in its
+/// subdirectories, in a stable order
+fn find_pdfs(dir: &Path, recursive: bool) -> Result<Vec<PathBuf>> {
+    let mut pdfs = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            } else if path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"))
+            {
+                pdfs.push(path);
+            }
+        }
+    }
+    pdfs.sort();
+    Ok(pdfs)
+}
+
+/// Settings for converting `input`, one file of the batch
+fn file_config(config: &Config, input: &Path) -> Config {
+    let mut file_config = config.clone();
+    let relative = input.strip_prefix(&config.input_path).unwrap_or(input);
+    let stem = input.file_stem().unwrap_or_default();
+    file_config.input_path = input.to_path_buf();
+    // Site layouts place every document under the one site root
+    if config.site.is_none() {
+        file_config.output_path = config
+            .output_path
+            .join(relative)
+            .with_extension(config.output_format.extension());
+    }
+    // Documents written to the same directory must not share image names
+    file_config.image_dir = config.image_dir.join(stem);
+    file_config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_find_pdfs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        for name in ["b.pdf", "a.PDF", "notes.txt", "sub/c.pdf"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let names = |pdfs: Vec<PathBuf>| -> Vec<String> {
+            pdfs.iter()
+                .map(|path| {
+                    let path = path.strip_prefix(dir.path()).unwrap();
+                    path.to_string_lossy().replace('\\', "/")
+                })
+                .collect()
+        };
+        assert_eq!(
+            names(find_pdfs(dir.path(), false).unwrap()),
+            ["a.PDF", "b.pdf"]
+        );
+        assert_eq!(
+            names(find_pdfs(dir.path(), true).unwrap()),
+            ["a.PDF", "b.pdf", "sub/c.pdf"]
+        );
+    }
+
+    #[test]
+    fn test_file_config_mirrors_the_input_tree() {
+        let config = Config {
+            input_path: PathBuf::from("in"),
+            output_path: PathBuf::from("out"),
+            image_dir: PathBuf::from("images"),
+            ..Default::default()
+        };
+        let file_config = file_config(&config, Path::new("in/2024/report.pdf"));
+        assert_eq!(file_config.output_path, Path::new("out/2024/report.md"));
+        assert_eq!(file_config.image_dir, Path::new("images/report"));
+    }
+
+    #[test]
+    fn test_batch_report_format() {
+        assert_eq!(
+            "out/report.CSV".parse(),
+            Ok(BatchReport::Csv(PathBuf::from("out/report.CSV")))
+        );
+        assert_eq!(
+            "report.json".parse(),
+            Ok(BatchReport::Json(PathBuf::from("report.json")))
+        );
+        assert!("report.txt".parse::<BatchReport>().is_err());
+        assert!("report".parse::<BatchReport>().is_err());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field(&Value::Null), "");
+        assert_eq!(csv_field(&Value::Number(0.25)), "0.25");
+        assert_eq!(csv_field(&Value::String("a.pdf".into())), "a.pdf");
+        assert_eq!(
+            csv_field(&Value::String("bad font \"F1\", page 2".into())),
+            "\"bad font \"\"F1\"\", page 2\""
+        );
+    }
+}
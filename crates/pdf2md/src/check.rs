@@ -0,0 +1,173 @@
+use crate::i18n::{Message, count, tr, trf};
+use crate::{Pdf2MdError, Result};
+use std::path::Path;
+
+/// What extraction recovered of the text one page shows
+#[derive(Debug, Clone, PartialEq)]
+struct PageCoverage {
+    page: u32,
+    /// Glyphs shown by the content stream
+    glyphs: usize,
+    /// Characters extracted, other than whitespace, up to `glyphs`: ligatures
+    /// come out as more characters than glyphs
+    extracted: usize,
+    /// Why the page could not be counted or extracted
+    error: Option<String>,
+}
+
+impl PageCoverage {
+    fn percent(&self) -> f64 {
+        percent(self.extracted, self.glyphs)
+    }
+}
+
+/// Compare the glyphs each page's content stream shows with the characters
+/// extracted from it, printing the pages below `min_coverage` percent and
+/// the coverage of the whole document.
+///
+/// Fails when the document's coverage is below `min_coverage`: text the
+/// conversion would silently lose.
+pub fn run_check(input: &Path, min_coverage: f64) -> Result<()> {
+    if !(0.0..=100.0).contains(&min_coverage) {
+        return Err(Pdf2MdError::InvalidInput(format!(
+            "--min-coverage must be between 0 and 100, got {}",
+            min_coverage
+        )));
+    }
+    pdf_extract::validate_pdf(input)?;
+    let doc = pdf_extract::PdfDocument::open(input)?;
+
+    let pages: Vec<PageCoverage> = (1..=doc.page_count() as u32)
+        .map(|page| {
+            let counted = doc.page_glyph_count(page).and_then(|glyphs| {
+                let text = doc.extract_page_text(page)?;
+                Ok((glyphs, text.chars().filter(|c| !c.is_whitespace()).count()))
+            });
+            match counted {
+                Ok((glyphs, extracted)) => PageCoverage {
+                    page,
+                    glyphs,
+                    extracted: extracted.min(glyphs),
+                    error: None,
+                },
+                Err(e) => PageCoverage {
+                    page,
+                    glyphs: doc.page_glyph_count(page).unwrap_or(0),
+                    extracted: 0,
+                    error: Some(trf(Message::CannotExtract, &[("error", &e)])),
+                },
+            }
+        })
+        .collect();
+
+    print!("{}", report(input, &pages, min_coverage));
+
+    let glyphs: usize = pages.iter().map(|page| page.glyphs).sum();
+    let extracted: usize = pages.iter().map(|page| page.extracted).sum();
+    let coverage = percent(extracted, glyphs);
+    if coverage < min_coverage {
+        return Err(Pdf2MdError::Validation(trf(
+            Message::LowCoverage,
+            &[
+                ("percent", &format!("{:.1}", coverage)),
+                ("min", &min_coverage),
+            ],
+        )));
+    }
+    Ok(())
+}
+
+/// One line per page below `min_coverage` or failing, then the coverage of
+/// the document
+fn report(input: &Path, pages: &[PageCoverage], min_coverage: f64) -> String {
+    let mut report = String::new();
+    for page in pages {
+        let place = trf(Message::Page, &[("page", &page.page)]);
+        if let Some(error) = &page.error {
+            report.push_str(&format!(
+                "{}: {}: {}\n",
+                place,
+                tr(Message::SeverityError),
+                error
+            ));
+        } else if page.percent() < min_coverage {
+            report.push_str(&format!(
+                "{}: {}\n",
+                place,
+                coverage(page.extracted, page.glyphs)
+            ));
+        }
+    }
+    let glyphs: usize = pages.iter().map(|page| page.glyphs).sum();
+    let extracted: usize = pages.iter().map(|page| page.extracted).sum();
+    let verdict = if glyphs == 0 {
+        tr(Message::NoGlyphs).to_string()
+    } else {
+        coverage(extracted, glyphs)
+    };
+    let checked = count(pages.len(), Message::OnePage, Message::Pages);
+    report.push_str(&format!(
+        "{}: {}\n",
+        input.display(),
+        trf(
+            Message::Checked,
+            &[("verdict", &verdict), ("pages", &checked)]
+        )
+    ));
+    report
+}
+
+/// `97.3% of 1204 glyphs extracted`
+fn coverage(extracted: usize, glyphs: usize) -> String {
+    trf(
+        Message::Coverage,
+        &[
+            ("percent", &format!("{:.1}", percent(extracted, glyphs))),
+            ("glyphs", &glyphs),
+        ],
+    )
+}
+
+/// Percentage of the glyphs extracted; all of none
+fn percent(extracted: usize, glyphs: usize) -> f64 {
+    if glyphs == 0 {
+        100.0
+    } else {
+        extracted as f64 * 100.0 / glyphs as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let page = |page, glyphs, extracted| PageCoverage {
+            page,
+            glyphs,
+            extracted,
+            error: None,
+        };
+        let pages = [
+            page(1, 800, 800),
+            page(2, 400, 100),
+            PageCoverage {
+                error: Some("text cannot be extracted: bad font".to_string()),
+                ..page(3, 0, 0)
+            },
+            // An image-only page is not below any threshold
+            page(4, 0, 0),
+        ];
+        assert_eq!(
+            report(Path::new("a.pdf"), &pages, 90.0),
+            "Page 2: 25.0% of 400 glyphs extracted\n\
+             Page 3: error: text cannot be extracted: bad font\n\
+             a.pdf: 75.0% of 1200 glyphs extracted (4 pages checked)\n"
+        );
+        assert_eq!(
+            report(Path::new("a.pdf"), &[page(1, 0, 0)], 90.0),
+            "a.pdf: no text in the content streams (1 page checked)\n"
+        );
+    }
+}
@@ -1,5 +1,16 @@
-use clap::Parser;
-use std::path::PathBuf;
+use crate::batch::BatchReport;
+use clap::error::ErrorKind;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum, ValueHint};
+use crate::config_file;
+use crate::i18n::Language;
+use crate::index::IndexTarget;
+use crate::replace::ReplaceRule;
+use crate::search::SearchIndex;
+use crate::sections::SectionRule;
+use crate::site::{FrontMatterFormat, Site};
+use pdf_extract::{FootnoteStyle, ImageFormat, PageSelection, UnderlineStyle};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 
 const VERSION_INFO: &str = concat!(
     env!("CARGO_PKG_VERSION"), "\n",
@@ -12,7 +23,7 @@ const VERSION_INFO: &str = concat!(
 );
 
 /// PDF to Markdown converter
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(name = "pdf2md")]
 #[command(version = VERSION_INFO)]
 #[command(long_version = VERSION_INFO)]
@@ -23,12 +34,30 @@ EXAMPLES:
     # Basic conversion
     pdf2md -i document.pdf -o output.md
 
+    # Write document.md next to the input
+    pdf2md -i document.pdf
+
+    # Into a directory, named after the input (out/document.md)
+    pdf2md -i document.pdf --output-dir out
+
     # With verbose output
     pdf2md -i document.pdf -o output.md --verbose
 
     # Preview PDF structure without converting
     pdf2md -i document.pdf -o output.md --dry-run
 
+    # Print the bookmarks (or detected sections) only
+    pdf2md toc document.pdf
+
+    # Print the metadata as JSON
+    pdf2md metadata document.pdf --format json
+
+    # Check every page before ingesting a file
+    pdf2md validate document.pdf
+
+    # Extract the figures only
+    pdf2md extract-images document.pdf -o figures
+
 AI CODING AGENT INSTRUCTIONS:
 
 This tool converts PDF documents to Markdown format for use in documentation
@@ -38,6 +67,7 @@ USAGE FOR AI AGENTS:
   1. Extract text from PDF: pdf2md -i input.pdf -o output.md
   2. Preview PDF structure first: pdf2md -i input.pdf -o output.md --dry-run
   3. Use verbose mode for debugging: pdf2md -i input.pdf -o output.md --verbose
+  4. Call it as MCP tools (convert, preview, metadata): pdf2md mcp
 
 TYPICAL WORKFLOWS:
   - Documentation conversion: Extract PDF manuals to markdown for wikis
@@ -50,6 +80,10 @@ ERROR HANDLING:
   - Exit code 2: I/O error (permissions, disk space)
   - Exit code 3: Markdown generation error
   - Exit code 4: PDF processing error (corrupt PDF, extraction failed)
+  - Exit code 5: `pdf2md validate` found pages that cannot be extracted,
+    `pdf2md check` too little text, or --check an out-of-date output
+  - Exit code 6: some files of a directory conversion failed
+  - Exit code 7: every file of a directory conversion failed
 
 LIBRARY USAGE:
   This tool uses the `pdf-extract` and `markdown-gen` libraries which can
@@ -70,14 +104,71 @@ LIBRARY USAGE:
   ```
 
 For more information: https://github.com/softwarewrighter/pdf2md"#)]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// Path to input PDF file
-    #[arg(short, long, value_name = "FILE")]
-    pub input: PathBuf,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Path to input PDF file, a directory of PDFs to convert, or an http:// or https:// URL
+    #[arg(short, long, value_name = "FILE", required_unless_present = "rpc")]
+    pub input: Option<PathBuf>,
 
-    /// Path to output Markdown file
+    /// Path to output Markdown file (the output directory when the input is a directory; default: the input's name with .md, next to it)
     #[arg(short, long, value_name = "FILE")]
-    pub output: PathBuf,
+    pub output: Option<PathBuf>,
+
+    /// Instead of -o: directory to write the output to, named after the input (report.pdf -> DIR/report.md)
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with = "output")]
+    pub output_dir: Option<PathBuf>,
+
+    /// Overwrite an existing file at the default output path, used when neither -o nor --output-dir is given
+    #[arg(long, default_value_t = false)]
+    pub force: bool,
+
+    /// Convert only these pages, e.g. 1-3,7,10- (numbered from 1 again in the output); a linearized PDF at a URL is then read a range at a time instead of downloaded
+    #[arg(long, value_name = "PAGES")]
+    pub pages: Option<PageSelection>,
+
+    /// Password of an encrypted PDF; it shows in the shell history and the process list, so prefer --password-file
+    #[arg(long, value_name = "PASSWORD", conflicts_with = "password_file")]
+    pub password: Option<String>,
+
+    /// Read the password of an encrypted PDF from the first line of FILE (without one, pdf2md asks on the terminal)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub password_file: Option<PathBuf>,
+
+    /// Output format: markdown, or pandoc-json (Pandoc's JSON AST, for `pandoc -f json`)
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        default_value = "markdown",
+        conflicts_with = "site"
+    )]
+    pub format: OutputFormat,
+
+    /// Also write the metadata, statistics, warnings and page map to <output name>.meta.json or .meta.yaml
+    #[arg(long, value_name = "FORMAT")]
+    pub sidecar: Option<SidecarFormat>,
+
+    /// Add each converted document's text, metadata and page offsets to a full-text index: sqlite:<FILE> (an SQLite FTS5 database)
+    #[arg(long, value_name = "TARGET")]
+    pub index: Option<IndexTarget>,
+
+    /// Write a client-side search index over the converted documents: lunr:<FILE> or elasticlunr:<FILE>
+    #[arg(long, value_name = "TARGET")]
+    pub search_index: Option<SearchIndex>,
+
+    /// With a directory input, also convert the PDFs in its subdirectories
+    #[arg(short, long, default_value_t = false)]
+    pub recursive: bool,
+
+    /// With a directory input, files converted at once (default: one per CPU)
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    pub jobs: usize,
+
+    /// With a directory input, also write a table of each file's pages, words, warnings, time, output size and status (.csv or .json)
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub batch_report: Option<BatchReport>,
 
     /// Enable verbose output
     #[arg(short, long, default_value_t = false)]
@@ -86,12 +177,490 @@ pub struct Args {
     /// Preview mode: show PDF structure without converting
     #[arg(short = 'n', long, default_value_t = false)]
     pub dry_run: bool,
+
+    /// Show the --dry-run preview, then ask before converting
+    #[arg(long, default_value_t = false, conflicts_with = "dry_run")]
+    pub confirm: bool,
+
+    /// Convert in memory and fail, showing the first difference, unless the output file already holds the result; nothing is written
+    #[arg(long, default_value_t = false, conflicts_with_all = ["dry_run", "confirm", "site"])]
+    pub check: bool,
+
+    /// With --dry-run, print the first 200 characters of each of the first N pages
+    #[arg(long, value_name = "N", default_value_t = 0, requires = "dry_run")]
+    pub preview_pages: usize,
+
+    /// Emit text set in math fonts and tagged formulas as LaTeX ($...$ and $$...$$)
+    #[arg(long, default_value_t = false)]
+    pub math: bool,
+
+    /// With --math, write a comment in place of display equations whose glyphs cannot be decoded
+    #[arg(long, default_value_t = false, requires = "math")]
+    pub mark_lost_equations: bool,
+
+    /// Extract embedded images and reference them from the Markdown
+    #[arg(long, default_value_t = false)]
+    pub images: bool,
+
+    /// Directory for extracted images, relative to the output file
+    #[arg(long, value_name = "DIR", default_value = "images")]
+    pub image_dir: PathBuf,
+
+    /// Command that prints alt text for an image read from stdin
+    #[arg(long, value_name = "CMD")]
+    pub alt_text_cmd: Option<String>,
+
+    /// Convert vector drawings (charts, diagrams) to SVG files in the image directory
+    #[arg(long, default_value_t = false)]
+    pub vector_graphics: bool,
+
+    /// Image file format: "original" keeps JPEGs as is, "png" converts them too
+    #[arg(long, value_name = "FORMAT", default_value = "original")]
+    pub image_format: ImageFormat,
+
+    /// Skip images narrower or shorter than this many pixels (logos, bullets)
+    #[arg(long, value_name = "PX", default_value_t = 0)]
+    pub min_image_size: u32,
+
+    /// Comma-separated image types not to extract (e.g. jpg,svg)
+    #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+    pub skip_image_types: Vec<String>,
+
+    /// Detect footnotes and write them as inline (`^[...]`), reference (`[^1]`, the default)
+    /// or notes (a "Notes" section at the end)
+    #[arg(long, value_name = "STYLE", num_args = 0..=1, default_missing_value = "reference")]
+    pub footnotes: Option<FootnoteStyle>,
+
+    /// Write term/definition layouts as definition lists (on by default for .qmd/.Rmd output)
+    #[arg(long, default_value_t = false)]
+    pub definition_lists: bool,
+
+    /// Write horizontal lines drawn across the page as `---` separators
+    #[arg(long, default_value_t = false)]
+    pub horizontal_rules: bool,
+
+    /// Write underlined text as emphasis (`*text*`), bold (`**text**`) or html (`<u>text</u>`)
+    #[arg(long, value_name = "STYLE")]
+    pub underline: Option<UnderlineStyle>,
+
+    /// Write struck-through text as `~~text~~`
+    #[arg(long, default_value_t = false)]
+    pub strikethrough: bool,
+
+    /// Wrap coloured text in `<span style="color:...">` (for Markdown renderers allowing inline HTML)
+    #[arg(long, default_value_t = false)]
+    pub text_colors: bool,
+
+    /// Fix the heading hierarchy: a single H1 (the PDF title if needed) and no skipped levels
+    #[arg(long, default_value_t = false)]
+    pub normalize_headings: bool,
+
+    /// Prefix headings with hierarchical numbers (1, 1.1, 1.1.2)
+    #[arg(long, default_value_t = false, conflicts_with = "strip_heading_numbers")]
+    pub number_headings: bool,
+
+    /// Remove section numbers already present in headings
+    #[arg(long, default_value_t = false)]
+    pub strip_heading_numbers: bool,
+
+    /// Write a static site layout with -o as the site root (hugo, mkdocs, obsidian, jekyll, docusaurus, zola, notion)
+    #[arg(long, value_name = "SITE")]
+    pub site: Option<Site>,
+
+    /// With --site mkdocs, also write the nav for mkdocs.yml
+    #[arg(long, default_value_t = false)]
+    pub mkdocs_nav: bool,
+
+    /// Comma-separated tags for the front matter (--site obsidian, zola)
+    #[arg(long, value_name = "TAGS", value_delimiter = ',')]
+    pub tags: Vec<String>,
+
+    /// Jekyll layout named in the front matter (--site jekyll)
+    #[arg(long, value_name = "NAME", default_value = "post")]
+    pub jekyll_layout: String,
+
+    /// Write to the _<NAME> collection instead of _posts (--site jekyll)
+    #[arg(long, value_name = "NAME")]
+    pub jekyll_collection: Option<String>,
+
+    /// Comma-separated categories for the front matter (--site jekyll, zola)
+    #[arg(long, value_name = "CATEGORIES", value_delimiter = ',')]
+    pub categories: Vec<String>,
+
+    /// Front matter syntax: yaml, or toml (+++) for --site hugo and zola
+    #[arg(long, value_name = "FORMAT", default_value = "yaml")]
+    pub front_matter_format: FrontMatterFormat,
+
+    /// Let Quarto run code chunks in .qmd/.Rmd output (they get `#| eval: false` otherwise)
+    #[arg(long, default_value_t = false)]
+    pub execute_chunks: bool,
+
+    /// Compact Markdown for Slack/Discord: bold lines for headings, no tables
+    #[arg(long, default_value_t = false)]
+    pub chat: bool,
+
+    /// With --chat, cut each section to this many characters (0 for no limit)
+    #[arg(long, value_name = "CHARS", default_value_t = 1900)]
+    pub chat_section_limit: usize,
+
+    /// Fail with a non-zero exit status if any page cannot be extracted
+    #[arg(long, default_value_t = false, conflicts_with = "lenient")]
+    pub strict: bool,
+
+    /// Skip pages that cannot be extracted, leaving a marker in their place (the default)
+    #[arg(long, default_value_t = false)]
+    pub lenient: bool,
+
+    /// If conversion aborts partway (e.g. a page failure with --strict), still write the pages converted so far
+    #[arg(long, default_value_t = false)]
+    pub keep_partial: bool,
+
+    /// What to do with control characters and undecodable text in the extracted text: strip, replace (with U+FFFD) or error
+    #[arg(long, value_name = "POLICY", default_value = "strip")]
+    pub sanitize: Sanitize,
+
+    /// What to do with zero-width joiners and non-joiners and bidi controls in the extracted text: keep, warn (keep, and say how many) or strip
+    #[arg(long, value_name = "POLICY", default_value = "warn")]
+    pub invisible_chars: InvisibleChars,
+
+    /// Unicode normalization of the extracted text: none, nfc (composed) or nfkc (composed, compatibility characters folded)
+    #[arg(long, value_name = "FORM", default_value = "none")]
+    pub normalize: Normalization,
+
+    /// Record the source file name, size, SHA-256, tool version and conversion time in the front matter
+    #[arg(long, default_value_t = false)]
+    pub provenance: bool,
+
+    /// Insert an HTML comment naming the source page (`<!-- p.12 -->`) before each block of the Markdown
+    #[arg(long, default_value_t = false)]
+    pub annotate_provenance: bool,
+
+    /// Mark where each source page begins with an anchor with the id `page-N`: html
+    /// (`<a id="page-12"></a>`, the default) or heading (`###### Page 12 {#page-12}`)
+    #[arg(long, value_name = "STYLE", num_args = 0..=1, default_missing_value = "html")]
+    pub page_anchors: Option<PageAnchors>,
+
+    /// Shortest all-caps line taken for a heading, in characters
+    #[arg(long, value_name = "CHARS", default_value_t = 1)]
+    pub min_heading_chars: usize,
+
+    /// Longest all-caps line taken for a heading, in characters
+    #[arg(long, value_name = "CHARS", default_value_t = 59)]
+    pub max_heading_chars: usize,
+
+    /// In text without line breaks, start a new paragraph after a sentence once the paragraph is longer than this
+    #[arg(long, value_name = "CHARS", default_value_t = 40)]
+    pub min_paragraph_chars: usize,
+
+    /// In text without line breaks, split glued words (`endIntroduction`) once the paragraph is longer than this
+    #[arg(long, value_name = "CHARS", default_value_t = 20)]
+    pub min_section_chars: usize,
+
+    /// Blank lines kept in a row in the extracted text
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub max_blank_lines: usize,
+
+    /// Memory, in MB, for keeping shared content streams and font maps decompressed (0 to turn off)
+    #[arg(long, value_name = "MB", default_value_t = 64)]
+    pub stream_cache_mb: usize,
+
+    /// Keep memory use low for very large PDFs: skip image data when not extracting images, write pages and images as they are done, no stream cache
+    #[arg(long, default_value_t = false)]
+    pub low_memory: bool,
+
+    /// Directory keeping the text of each page between runs, so only pages that changed are extracted again
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+    pub page_cache: Option<PathBuf>,
+
+    /// Serve JSON-RPC requests (convert, info, cancel) on stdin and stdout, one per line, instead of converting one file
+    #[arg(long, default_value_t = false, conflicts_with_all = ["input", "output", "dry_run"])]
+    pub rpc: bool,
+
+    /// Print the time spent loading, extracting, cleaning up, formatting and writing, and the slowest pages
+    #[arg(long, default_value_t = false)]
+    pub timings: bool,
+
+    /// Shell command the extracted text is piped through before Markdown is generated
+    #[arg(long, value_name = "CMD")]
+    pub pre_hook: Option<String>,
+
+    /// Shell command the final Markdown is piped through before it is written
+    #[arg(long, value_name = "CMD")]
+    pub post_hook: Option<String>,
+
+    /// When the conversion or directory finishes, POST a JSON summary of how it went to this URL (with curl)
+    #[arg(long, value_name = "URL")]
+    pub notify_url: Option<String>,
+
+    /// When the conversion or directory finishes, run this shell command with a JSON summary of how it went on stdin
+    #[arg(long, value_name = "CMD")]
+    pub notify_cmd: Option<String>,
+
+    /// TOML file of characters ("U+F0B7") or strings and the text to replace them with
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub glyph_map: Option<PathBuf>,
+
+    /// Rhai script whose transform(blocks) rewrites the Markdown's headings and blocks
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    pub script: Option<PathBuf>,
+
+    /// Ignore .pdf2md.toml files
+    #[arg(long, default_value_t = false, conflicts_with = "profile")]
+    pub no_config: bool,
+
+    /// Use the settings of the [profile.<NAME>] section of .pdf2md.toml (e.g. academic, manual, slides)
+    #[arg(long, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Settings file the defaults were read from
+    #[arg(skip)]
+    pub config_file: Option<PathBuf>,
+
+    /// Find/replace rules from the settings file
+    #[arg(skip)]
+    pub replace_rules: Vec<ReplaceRule>,
+
+    /// Language of messages, warnings and reports (en, de, es; default: $PDF2MD_LANG or en)
+    #[arg(long, value_name = "LANG", global = true)]
+    pub lang: Option<Language>,
+
+    /// Print without colours and bold text, also when writing to a terminal (as does a non-empty $NO_COLOR)
+    #[arg(long, global = true, default_value_t = false)]
+    pub no_color: bool,
+
+    /// Chapter file mapping rules from the settings file
+    #[arg(skip)]
+    pub section_rules: Vec<SectionRule>,
+}
+
+/// Tasks other than conversion
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Print the document outline (bookmarks), or the detected sections when it has none
+    Toc {
+        /// Path to input PDF file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Output format
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        format: TocFormat,
+    },
+
+    /// Print the document metadata (title, author, dates, producer, ...) for other tools
+    Metadata {
+        /// Path to input PDF file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Output format
+        #[arg(long, value_name = "FORMAT", default_value = "text")]
+        format: MetadataFormat,
+    },
+
+    /// Check that the PDF parses and every page can be extracted, reporting problems per page
+    Validate {
+        /// Path to input PDF file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Also fail (exit code 5) on warnings, such as pages without a text layer
+        #[arg(long, default_value_t = false)]
+        deny_warnings: bool,
+    },
+
+    /// Compare the glyphs the pages show with the text extracted, reporting the coverage in percent
+    Check {
+        /// Path to input PDF file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Fail (exit code 5) when less than this percentage of the glyphs is extracted
+        #[arg(long, value_name = "PERCENT", default_value_t = 90.0)]
+        min_coverage: f64,
+    },
+
+    /// Extract only the embedded images, named by page, with a manifest.json listing them
+    ExtractImages {
+        /// Path to input PDF file
+        #[arg(value_name = "FILE")]
+        input: PathBuf,
+
+        /// Directory for the images and manifest.json
+        #[arg(short, long, value_name = "DIR")]
+        output_dir: PathBuf,
+
+        /// Image file format: "original" keeps JPEGs as is, "png" converts them too
+        #[arg(long, value_name = "FORMAT", default_value = "original")]
+        image_format: ImageFormat,
+
+        /// Skip images narrower or shorter than this many pixels (logos, bullets)
+        #[arg(long, value_name = "PX", default_value_t = 0)]
+        min_image_size: u32,
+
+        /// Comma-separated image types not to extract (e.g. jpg,svg)
+        #[arg(long, value_name = "TYPES", value_delimiter = ',')]
+        skip_image_types: Vec<String>,
+
+        /// Also convert vector drawings (charts, diagrams) to SVG files
+        #[arg(long, default_value_t = false)]
+        vector_graphics: bool,
+    },
+
+    /// Serve convert, preview and metadata as Model Context Protocol tools on stdin and stdout, for AI agents
+    Mcp,
+}
+
+/// What a conversion writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Markdown text
+    #[default]
+    Markdown,
+    /// Pandoc's JSON AST of the Markdown
+    PandocJson,
+}
+
+impl OutputFormat {
+    /// Extension of the files written for a directory input
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Markdown => "md",
+            OutputFormat::PandocJson => "json",
+        }
+    }
+}
+
+/// What `--sanitize` does with unwanted characters in the extracted text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Sanitize {
+    /// Leave them out
+    #[default]
+    Strip,
+    /// Write U+FFFD in their place
+    Replace,
+    /// Fail the conversion
+    Error,
+}
+
+/// What `--invisible-chars` does with zero-width joiners and non-joiners and
+/// bidi controls, which some generators put between every glyph
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum InvisibleChars {
+    /// Leave them in the text
+    Keep,
+    /// Leave them in, and warn when there are any
+    #[default]
+    Warn,
+    /// Leave them out, also where they join emoji or letters of Arabic and Indic scripts
+    Strip,
+}
+
+/// How `--page-anchors` marks where a source page begins
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum PageAnchors {
+    /// An empty HTML anchor, placed within a paragraph when the page begins there
+    #[default]
+    Html,
+    /// A level-6 heading with the id as a Pandoc attribute, before the next block
+    Heading,
+}
+
+/// Unicode normalization form of the extracted text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Normalization {
+    /// Left as extracted
+    #[default]
+    None,
+    /// Canonical composition: `e` and a combining acute become `é`
+    Nfc,
+    /// Compatibility composition: also folds ligatures, superscripts and full-width forms (`ﬁ` to `fi`)
+    Nfkc,
+}
+
+/// Syntax of the `--sidecar` file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SidecarFormat {
+    /// `<name>.meta.json`
+    Json,
+    /// `<name>.meta.yaml`
+    Yaml,
+}
+
+/// How the `toc` subcommand prints the outline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TocFormat {
+    /// Indented list
+    Text,
+    /// Array of `{"level", "title", "page"}` objects
+    Json,
+}
+
+/// How the `metadata` subcommand prints the metadata
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MetadataFormat {
+    /// `Label: value` lines, leaving out missing values
+    Text,
+    /// One JSON object, with `null` for missing values
+    Json,
+    /// A YAML mapping, with `null` for missing values
+    Yaml,
 }
 
 impl Args {
-    /// Parse arguments from command line
+    /// Parse arguments from command line, taking defaults for the options not
+    /// given from the nearest `.pdf2md.toml` above the input file
     pub fn parse_args() -> Self {
-        Self::parse()
+        let cli: Vec<OsString> = std::env::args_os().collect();
+        let matches = Self::command().get_matches_from(&cli);
+        let args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+        if args.command.is_some() || args.no_config {
+            return args;
+        }
+
+        let start = match args.input.as_ref() {
+            Some(dir) if dir.is_dir() => std::path::absolute(dir),
+            Some(input) => match input.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => std::path::absolute(dir),
+                _ => std::env::current_dir(),
+            },
+            None => std::env::current_dir(),
+        };
+        let Some(path) = start.ok().and_then(|dir| config_file::discover(&dir)) else {
+            if let Some(profile) = &args.profile {
+                let message = format!(
+                    "--profile {} needs a {} defining [profile.{}]",
+                    profile,
+                    config_file::FILE_NAME,
+                    profile
+                );
+                Self::command().error(ErrorKind::InvalidValue, message).exit();
+            }
+            return args;
+        };
+        let (defaults, rules, sections) = config_file::load(&path)
+            .and_then(|table| config_file::select(table, args.profile.as_deref()))
+            .and_then(|mut table| {
+                let rules = config_file::take_rules(&mut table)?;
+                let sections = config_file::take_sections(&mut table)?;
+                let base = path.parent().unwrap_or(Path::new("."));
+                let defaults = config_file::to_args(&table, &Self::command(), &matches, base)?;
+                Ok((defaults, rules, sections))
+            })
+            .unwrap_or_else(|e| {
+                Self::command()
+                    .error(ErrorKind::InvalidValue, format!("{}: {}", path.display(), e))
+                    .exit()
+            });
+
+        let mut merged = cli[..1].to_vec();
+        merged.extend(defaults);
+        merged.extend_from_slice(&cli[1..]);
+        let mut args = Self::parse_from(merged);
+        args.config_file = Some(path);
+        args.replace_rules = rules;
+        args.section_rules = sections;
+        args
     }
 }
 
@@ -103,15 +672,29 @@ mod tests {
     fn test_args_parse_minimal() {
         // Test that Args can be created with required fields
         let args = Args {
-            input: PathBuf::from("input.pdf"),
-            output: PathBuf::from("output.md"),
+            input: Some(PathBuf::from("input.pdf")),
+            output: Some(PathBuf::from("output.md")),
             verbose: false,
             dry_run: false,
+            ..Default::default()
         };
 
-        assert_eq!(args.input, PathBuf::from("input.pdf"));
-        assert_eq!(args.output, PathBuf::from("output.md"));
+        assert_eq!(args.input, Some(PathBuf::from("input.pdf")));
+        assert_eq!(args.output, Some(PathBuf::from("output.md")));
         assert!(!args.verbose);
         assert!(!args.dry_run);
+        assert!(!args.math);
+    }
+
+    #[test]
+    fn test_toc_subcommand_needs_no_output() {
+        let args =
+            Args::try_parse_from(["pdf2md", "toc", "book.pdf", "--format", "json"]).unwrap();
+        assert!(args.input.is_none());
+        assert!(matches!(
+            args.command,
+            Some(Command::Toc { format: TocFormat::Json, .. })
+        ));
+        assert!(Args::try_parse_from(["pdf2md", "-o", "book.md"]).is_err());
     }
 }
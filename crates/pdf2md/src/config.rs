@@ -1,36 +1,359 @@
-use crate::cli::Args;
+use crate::alt_text::CommandAltText;
+use crate::batch::BatchReport;
+use crate::cli::{
+    Args, InvisibleChars, Normalization, OutputFormat, PageAnchors, Sanitize, SidecarFormat,
+};
 use crate::error::{Pdf2MdError, Result};
+use crate::i18n::{Message, tr};
+use crate::index::IndexTarget;
+use crate::page_cache::DirPageCache;
+use crate::replace::ReplaceRule;
+use crate::search::SearchIndex;
+use crate::sections::SectionRule;
+use crate::site::{FrontMatterFormat, Site};
+use clap::Parser;
+use pdf_extract::{
+    AltTextProvider, FootnoteStyle, ImageFormat, PageCache, PageSelection, UnderlineStyle,
+};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Config {
     pub input_path: PathBuf,
     pub output_path: PathBuf,
+    /// Neither -o nor --output-dir was given: the output goes next to the
+    /// input, and an existing file there is only replaced with `force`
+    pub default_output: bool,
+    pub force: bool,
+    /// Pages converted, when not all of them
+    pub pages: Option<PageSelection>,
+    /// Password of an encrypted PDF, from --password
+    pub password: Option<String>,
+    /// File the password of an encrypted PDF is read from
+    pub password_file: Option<PathBuf>,
+    /// Markdown, or Pandoc's JSON AST of it
+    pub output_format: OutputFormat,
+    /// Write the conversion's metadata, statistics and page map beside the output
+    pub sidecar: Option<SidecarFormat>,
+    /// Full-text index each converted document is added to
+    pub index: Option<IndexTarget>,
+    /// Search index written over the converted documents once they are all done
+    pub search_index: Option<SearchIndex>,
+    /// Table of the files of a directory conversion written once they are all done
+    pub batch_report: Option<BatchReport>,
     pub verbose: bool,
+    /// With a directory input, convert its subdirectories too
+    pub recursive: bool,
+    /// Files of a directory input converted at once; 0 for one per CPU
+    pub jobs: usize,
     pub dry_run: bool,
+    /// Ask after the preview whether to convert
+    pub confirm: bool,
+    /// Compare the conversion with the existing output instead of writing it
+    pub check: bool,
+    /// Serve JSON-RPC requests on stdin and stdout
+    pub rpc: bool,
+    pub preview_pages: usize,
+    pub math: bool,
+    pub mark_lost_equations: bool,
+    pub images: bool,
+    pub image_dir: PathBuf,
+    /// URL prefix for image references when it differs from `image_dir`
+    pub image_url: Option<String>,
+    pub alt_text_cmd: Option<String>,
+    pub vector_graphics: bool,
+    pub image_format: ImageFormat,
+    pub min_image_size: u32,
+    pub skip_image_types: Vec<String>,
+    pub footnotes: Option<FootnoteStyle>,
+    pub definition_lists: bool,
+    pub horizontal_rules: bool,
+    pub underline: Option<UnderlineStyle>,
+    pub strikethrough: bool,
+    pub text_colors: bool,
+    pub normalize_headings: bool,
+    pub number_headings: bool,
+    pub strip_heading_numbers: bool,
+    pub site: Option<Site>,
+    pub mkdocs_nav: bool,
+    pub tags: Vec<String>,
+    pub jekyll_layout: String,
+    pub jekyll_collection: Option<String>,
+    pub categories: Vec<String>,
+    pub front_matter_format: FrontMatterFormat,
+    pub execute_chunks: bool,
+    pub chat: bool,
+    pub chat_section_limit: usize,
+    pub strict: bool,
+    pub keep_partial: bool,
+    /// Control characters and undecodable text stripped, replaced, or an error
+    pub sanitize: Sanitize,
+    /// Zero-width joiners and non-joiners and bidi controls kept or stripped
+    pub invisible_chars: InvisibleChars,
+    /// Unicode normalization form the extracted text is put in
+    pub normalize: Normalization,
+    /// Heading detection thresholds for Markdown formatting
+    pub format: markdown_gen::FormatOptions,
+    /// Thresholds of the blank-line and paragraph-break clean-up
+    pub cleanup: pdf_extract::CleanupThresholds,
+    pub provenance: bool,
+    /// Source page comments before each block of the Markdown
+    pub annotate_provenance: bool,
+    /// Anchors where each source page begins
+    pub page_anchors: Option<PageAnchors>,
+    /// Megabytes of shared streams kept decompressed during extraction
+    pub stream_cache_mb: usize,
+    /// Convert in bounded memory: no image data unless extracting images,
+    /// pages and images written as they are done, no stream cache
+    pub low_memory: bool,
+    /// Directory the text of unchanged pages is reused from
+    pub page_cache: Option<PathBuf>,
+    /// Report the time spent in each stage of the conversion
+    pub timings: bool,
+    /// Settings file the defaults were read from
+    pub config_file: Option<PathBuf>,
+    pub pre_hook: Option<String>,
+    pub post_hook: Option<String>,
+    /// Where to report a finished run
+    pub notify_url: Option<String>,
+    pub notify_cmd: Option<String>,
+    /// Replacements for characters of the extracted text, read at conversion
+    pub glyph_map: Option<PathBuf>,
+    /// Rhai script rewriting the Markdown's blocks, compiled at conversion
+    pub script: Option<PathBuf>,
+    /// Find/replace rules applied to the generated Markdown
+    pub replace_rules: Vec<ReplaceRule>,
+    /// Where split site layouts write the chapters whose headings match
+    pub section_rules: Vec<SectionRule>,
 }
 
 impl Config {
     /// Create configuration from CLI arguments
     pub fn from_args(args: Args) -> Self {
+        let output_path = output_path(&args);
         Self {
-            input_path: args.input,
-            output_path: args.output,
+            input_path: args.input.unwrap_or_default(),
+            output_path,
+            default_output: args.output.is_none() && args.output_dir.is_none(),
+            force: args.force,
+            pages: args.pages,
+            password: args.password,
+            password_file: args.password_file,
+            output_format: args.format,
+            sidecar: args.sidecar,
+            index: args.index,
+            search_index: args.search_index,
+            batch_report: args.batch_report,
             verbose: args.verbose,
+            recursive: args.recursive,
+            jobs: args.jobs,
             dry_run: args.dry_run,
+            confirm: args.confirm,
+            check: args.check,
+            rpc: args.rpc,
+            preview_pages: args.preview_pages,
+            math: args.math,
+            mark_lost_equations: args.mark_lost_equations,
+            images: args.images,
+            image_dir: args.image_dir,
+            image_url: None,
+            alt_text_cmd: args.alt_text_cmd,
+            vector_graphics: args.vector_graphics,
+            image_format: args.image_format,
+            min_image_size: args.min_image_size,
+            skip_image_types: args.skip_image_types,
+            footnotes: args.footnotes,
+            definition_lists: args.definition_lists,
+            horizontal_rules: args.horizontal_rules,
+            underline: args.underline,
+            strikethrough: args.strikethrough,
+            text_colors: args.text_colors,
+            normalize_headings: args.normalize_headings,
+            number_headings: args.number_headings,
+            strip_heading_numbers: args.strip_heading_numbers,
+            site: args.site,
+            mkdocs_nav: args.mkdocs_nav,
+            tags: args.tags,
+            jekyll_layout: args.jekyll_layout,
+            jekyll_collection: args.jekyll_collection,
+            categories: args.categories,
+            front_matter_format: args.front_matter_format,
+            execute_chunks: args.execute_chunks,
+            chat: args.chat,
+            chat_section_limit: args.chat_section_limit,
+            strict: args.strict,
+            keep_partial: args.keep_partial,
+            sanitize: args.sanitize,
+            invisible_chars: args.invisible_chars,
+            normalize: args.normalize,
+            format: markdown_gen::FormatOptions {
+                min_heading_chars: args.min_heading_chars,
+                max_heading_chars: args.max_heading_chars,
+            },
+            cleanup: pdf_extract::CleanupThresholds {
+                max_blank_lines: args.max_blank_lines,
+                min_paragraph_chars: args.min_paragraph_chars,
+                min_section_chars: args.min_section_chars,
+            },
+            provenance: args.provenance,
+            annotate_provenance: args.annotate_provenance,
+            page_anchors: args.page_anchors,
+            stream_cache_mb: args.stream_cache_mb,
+            low_memory: args.low_memory,
+            page_cache: args.page_cache,
+            timings: args.timings,
+            config_file: args.config_file,
+            pre_hook: args.pre_hook,
+            post_hook: args.post_hook,
+            notify_url: args.notify_url,
+            notify_cmd: args.notify_cmd,
+            glyph_map: args.glyph_map,
+            script: args.script,
+            replace_rules: args.replace_rules,
+            section_rules: args.section_rules,
+        }
+    }
+
+    /// The configuration of a command line giving no options
+    pub fn command_line_defaults() -> Self {
+        // `--rpc` stands in for the input and output, required otherwise
+        let args = Args::parse_from(["pdf2md", "--rpc"]);
+        Self {
+            rpc: false,
+            ..Self::from_args(args)
+        }
+    }
+
+    /// Options passed to the PDF text extractor
+    pub fn extract_options(&self) -> pdf_extract::ExtractOptions {
+        pdf_extract::ExtractOptions {
+            math: self.math,
+            mark_lost_equations: self.mark_lost_equations,
+            images: self.images,
+            image_prefix: self
+                .image_url
+                .clone()
+                .unwrap_or_else(|| image_prefix(&self.image_dir)),
+            alt_text: self
+                .alt_text_cmd
+                .as_ref()
+                .map(|cmd| Arc::new(CommandAltText::new(cmd.as_str())) as Arc<dyn AltTextProvider>),
+            vector_graphics: self.vector_graphics,
+            image_format: self.image_format,
+            min_image_size: self.min_image_size,
+            skip_image_types: self.skip_image_types.clone(),
+            footnotes: self.footnotes,
+            // Pandoc, and so Quarto, reads definition lists
+            definition_lists: self.definition_lists
+                || (self.site.is_none() && crate::quarto::is_quarto_path(&self.output_path)),
+            horizontal_rules: self.horizontal_rules,
+            underline: self.underline,
+            strikethrough: self.strikethrough,
+            text_colors: self.text_colors,
+            strict: self.strict,
+            keep_partial: self.keep_partial,
+            cleanup: self.cleanup,
+            // Read from the `glyph_map` file when converting
+            glyph_map: Vec::new(),
+            failed_page_note: Some(tr(Message::FailedPageNote).to_string()),
+            page_cache: self
+                .page_cache
+                .as_ref()
+                .map(|dir| Arc::new(DirPageCache::new(dir)) as Arc<dyn PageCache>),
+        }
+    }
+
+    /// Directory extracted images are written to
+    pub fn image_output_dir(&self) -> PathBuf {
+        match self.output_path.parent() {
+            Some(parent) => parent.join(&self.image_dir),
+            None => self.image_dir.clone(),
         }
     }
 
     /// Validate configuration
     pub fn validate(&self) -> Result<()> {
         validate_input_path(&self.input_path)?;
+        if self.front_matter_format == FrontMatterFormat::Toml
+            && !self.site.is_some_and(Site::supports_toml)
+        {
+            return Err(Pdf2MdError::InvalidInput(
+                "--front-matter-format toml requires --site hugo or --site zola".to_string(),
+            ));
+        }
+        // Each reads the one Markdown file written
+        for (set, option) in [
+            (self.sidecar.is_some(), "--sidecar"),
+            (self.index.is_some(), "--index"),
+            (self.search_index.is_some(), "--search-index"),
+        ] {
+            if set && self.site.is_some() {
+                return Err(Pdf2MdError::InvalidInput(format!(
+                    "{} reads one Markdown file and cannot be used with --site",
+                    option
+                )));
+            }
+            if set && self.output_format != OutputFormat::Markdown {
+                return Err(Pdf2MdError::InvalidInput(format!(
+                    "{} reads the Markdown and cannot be used with --format pandoc-json",
+                    option
+                )));
+            }
+        }
+        if self.low_memory
+            && !self.dry_run
+            && let Some(option) = crate::pipeline::whole_document_option(self)
+        {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "--low-memory writes pages as they are extracted, but {} needs the whole document",
+                option
+            )));
+        }
+        if self.default_output && self.site.is_some() {
+            return Err(Pdf2MdError::InvalidInput(
+                "--site needs -o or --output-dir for the site root".to_string(),
+            ));
+        }
+        // A PDF named `notes.md` is its own default output, even with --force
+        if !self.dry_run && !self.check && is_same_file(&self.input_path, &self.output_path) {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "{} is the input file; choose another output with -o or --output-dir",
+                self.output_path.display()
+            )));
+        }
+        if self.default_output
+            && !self.force
+            && !self.dry_run
+            && !self.check
+            && self.output_path.exists()
+        {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "{} already exists; pass --force to overwrite it, or choose the output with -o",
+                self.output_path.display()
+            )));
+        }
         // Note: We don't validate output path because we create parent dirs automatically
         Ok(())
     }
 }
 
+/// Image references use forward slashes so the Markdown renders on every platform
+pub(crate) fn image_prefix(dir: &Path) -> String {
+    let dir = dir.to_string_lossy().replace('\\', "/");
+    if dir.is_empty() || dir.ends_with('/') {
+        dir
+    } else {
+        format!("{}/", dir)
+    }
+}
+
 /// Validate input file exists and is readable
 fn validate_input_path(path: &Path) -> Result<()> {
+    // Read by `remote::run`, before or while converting
+    if crate::remote::is_url(path) {
+        return Ok(());
+    }
     if !path.exists() {
         return Err(Pdf2MdError::InvalidInput(format!(
             "Input file does not exist: {}",
@@ -48,6 +371,42 @@ fn validate_input_path(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Whether two paths name the same existing file
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// `-o`, or the input's name in the `--output-dir` directory or else the
+/// input's own: a directory input or a site layout takes the directory itself
+fn output_path(args: &Args) -> PathBuf {
+    let Some(input) = &args.input else {
+        return args.output.clone().unwrap_or_default();
+    };
+    let dir = match (&args.output, &args.output_dir) {
+        (Some(output), _) => return output.clone(),
+        (None, Some(dir)) => dir.clone(),
+        // A URL's file is named in the current directory, as by `curl -O`
+        (None, None) if crate::remote::is_url(input) => PathBuf::new(),
+        (None, None) if input.is_dir() => input.clone(),
+        (None, None) => input.parent().unwrap_or(Path::new("")).to_path_buf(),
+    };
+    if args.site.is_some() || input.is_dir() {
+        return dir;
+    }
+    let file_name = if crate::remote::is_url(input) {
+        Path::new(crate::remote::file_name(input))
+    } else {
+        input.as_path()
+    };
+    let mut name = file_name.file_stem().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(args.format.extension());
+    dir.join(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -57,10 +416,11 @@ mod tests {
     #[test]
     fn test_config_from_args() {
         let args = Args {
-            input: PathBuf::from("input.pdf"),
-            output: PathBuf::from("output.md"),
+            input: Some(PathBuf::from("input.pdf")),
+            output: Some(PathBuf::from("output.md")),
             verbose: true,
             dry_run: false,
+            ..Default::default()
         };
 
         let config = Config::from_args(args);
@@ -69,6 +429,99 @@ mod tests {
         assert_eq!(config.output_path, PathBuf::from("output.md"));
         assert!(config.verbose);
         assert!(!config.dry_run);
+        assert!(!config.math);
+    }
+
+    #[test]
+    fn test_output_dir_names_output_after_input() {
+        let args = |input: &str| Args {
+            input: Some(PathBuf::from(input)),
+            output_dir: Some(PathBuf::from("out")),
+            ..Default::default()
+        };
+        let output = |args| Config::from_args(args).output_path;
+        assert_eq!(
+            output(args("scans/report.v2.pdf")),
+            PathBuf::from("out/report.v2.md")
+        );
+        assert_eq!(
+            output(Args {
+                format: OutputFormat::PandocJson,
+                ..args("report.pdf")
+            }),
+            PathBuf::from("out/report.json")
+        );
+        // Without either, next to the input
+        assert_eq!(
+            output(Args {
+                output_dir: None,
+                ..args("scans/report.pdf")
+            }),
+            PathBuf::from("scans/report.md")
+        );
+        // A URL's file goes in the current directory
+        assert_eq!(
+            output(Args {
+                output_dir: None,
+                ..args("https://example.com/archive/report.pdf?version=2")
+            }),
+            PathBuf::from("report.md")
+        );
+        // A directory input keeps the directory, as with -o
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            output(args(dir.path().to_str().unwrap())),
+            PathBuf::from("out")
+        );
+    }
+
+    #[test]
+    fn test_image_paths_are_relative_to_output() {
+        let config = Config {
+            output_path: PathBuf::from("docs/guide.md"),
+            image_dir: PathBuf::from("assets"),
+            images: true,
+            ..Default::default()
+        };
+
+        assert_eq!(config.image_output_dir(), PathBuf::from("docs/assets"));
+        assert_eq!(config.extract_options().image_prefix, "assets/");
+    }
+
+    #[test]
+    fn test_toml_front_matter_requires_toml_site() {
+        let temp_dir = TempDir::new().unwrap();
+        let input_path = temp_dir.path().join("test.pdf");
+        File::create(&input_path).unwrap();
+
+        let mut config = Config {
+            input_path,
+            front_matter_format: FrontMatterFormat::Toml,
+            site: Some(Site::Jekyll),
+            ..Default::default()
+        };
+        assert!(config.validate().is_err());
+
+        config.site = Some(Site::Zola);
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_output_is_never_the_input() {
+        let temp_dir = TempDir::new().unwrap();
+        // A PDF judged by its content, named like Markdown
+        let input = temp_dir.path().join("notes.md");
+        File::create(&input).unwrap();
+        let config = Config::from_args(Args {
+            input: Some(input.clone()),
+            force: true,
+            ..Default::default()
+        });
+        assert_eq!(config.output_path, input);
+        match config.validate() {
+            Err(Pdf2MdError::InvalidInput(msg)) => assert!(msg.contains("is the input file")),
+            other => panic!("Expected InvalidInput error, got {:?}", other),
+        }
     }
 
     #[test]
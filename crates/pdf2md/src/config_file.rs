@@ -0,0 +1,301 @@
+use crate::replace::ReplaceRule;
+use crate::sections::SectionRule;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueHint};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Name of the project settings file searched for above the input
+pub const FILE_NAME: &str = ".pdf2md.toml";
+
+/// Options that only make sense on the command line
+const COMMAND_LINE_ONLY: &[&str] = &[
+    "input",
+    "output",
+    "rpc",
+    "no_config",
+    "profile",
+    "help",
+    "version",
+];
+
+/// The nearest `.pdf2md.toml` in `start` or one of its parent directories
+pub fn discover(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+/// Read a settings file
+pub fn load(path: &Path) -> Result<toml::Table, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    text.parse::<toml::Table>().map_err(|e| e.to_string())
+}
+
+/// The settings of a file, with those of `[profile.<name>]` replacing the
+/// top-level ones when a profile is chosen
+pub fn select(mut table: toml::Table, profile: Option<&str>) -> Result<toml::Table, String> {
+    let profiles = table.remove("profile");
+    let Some(name) = profile else {
+        return Ok(table);
+    };
+    let profiles = profiles.as_ref().and_then(toml::Value::as_table);
+    match profiles.and_then(|profiles| profiles.get(name)) {
+        Some(toml::Value::Table(settings)) => {
+            let mut settings = settings.clone();
+            // The profile's replace rules run after the top-level ones
+            if let Some(toml::Value::Array(rules)) = settings.remove("replace") {
+                match table.get_mut("replace") {
+                    Some(toml::Value::Array(top_level)) => top_level.extend(rules),
+                    _ => {
+                        table.insert("replace".to_string(), toml::Value::Array(rules));
+                    }
+                }
+            }
+            // and its section rules are tried before them
+            if let Some(toml::Value::Array(mut rules)) = settings.remove("section") {
+                if let Some(toml::Value::Array(top_level)) = table.remove("section") {
+                    rules.extend(top_level);
+                }
+                table.insert("section".to_string(), toml::Value::Array(rules));
+            }
+            table.extend(settings);
+            Ok(table)
+        }
+        Some(_) => Err(format!("`profile.{}` must be a table", name)),
+        None => {
+            let known: Vec<&str> = profiles
+                .map(|profiles| profiles.keys().map(String::as_str).collect())
+                .unwrap_or_default();
+            if known.is_empty() {
+                Err(format!(
+                    "no [profile.{}] section (the file defines no profiles)",
+                    name
+                ))
+            } else {
+                Err(format!(
+                    "no [profile.{}] section (profiles: {})",
+                    name,
+                    known.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Take the `[[replace]]` rules out of the settings, in file order
+pub fn take_rules(table: &mut toml::Table) -> Result<Vec<ReplaceRule>, String> {
+    match table.remove("replace") {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(rules)) => rules.iter().map(ReplaceRule::from_toml).collect(),
+        Some(_) => Err("`replace` must be a list of tables ([[replace]])".to_string()),
+    }
+}
+
+/// Take the `[[section]]` file mapping rules out of the settings, in file order
+pub fn take_sections(table: &mut toml::Table) -> Result<Vec<SectionRule>, String> {
+    match table.remove("section") {
+        None => Ok(Vec::new()),
+        Some(toml::Value::Array(rules)) => rules.iter().map(SectionRule::from_toml).collect(),
+        Some(_) => Err("`section` must be a list of tables ([[section]])".to_string()),
+    }
+}
+
+/// Command-line arguments equivalent to the settings in `table`.
+///
+/// Settings are named like the long options, with `_` or `-` between words.
+/// Those given on the command line (`matches`), or conflicting with an option
+/// given there, are left out so the command line wins. Relative file paths
+/// are taken from `base`, the directory of the settings file.
+pub fn to_args(
+    table: &toml::Table,
+    command: &Command,
+    matches: &ArgMatches,
+    base: &Path,
+) -> Result<Vec<OsString>, String> {
+    let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+    let mut args = Vec::new();
+    for (key, value) in table {
+        let id = key.replace('-', "_");
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_id() == id.as_str() && arg.get_long().is_some())
+            .filter(|_| !COMMAND_LINE_ONLY.contains(&id.as_str()))
+            .ok_or_else(|| format!("unknown setting `{}`", key))?;
+        if explicit(&id)
+            || command
+                .get_arguments()
+                .filter(|other| explicit(other.get_id().as_str()))
+                .any(|other| conflicts(command, arg, other) || conflicts(command, other, arg))
+        {
+            continue;
+        }
+        let value = match value {
+            toml::Value::String(path)
+                if matches!(
+                    arg.get_value_hint(),
+                    ValueHint::FilePath | ValueHint::DirPath
+                ) =>
+            {
+                toml::Value::String(base.join(path).display().to_string())
+            }
+            value => value.clone(),
+        };
+        if let Some(value) = arg_value(arg, &value).map_err(|e| format!("`{}` {}", key, e))? {
+            args.push(OsString::from(format!(
+                "--{}{}",
+                arg.get_long().unwrap(),
+                value
+            )));
+        }
+    }
+    Ok(args)
+}
+
+/// Whether `arg` declares a conflict with `other`
+fn conflicts(command: &Command, arg: &Arg, other: &Arg) -> bool {
+    command
+        .get_arg_conflicts_with(arg)
+        .iter()
+        .any(|conflict| conflict.get_id() == other.get_id())
+}
+
+/// The `=value` part of an option (empty for a flag), or `None` to leave the
+/// option out
+fn arg_value(arg: &Arg, value: &toml::Value) -> Result<Option<String>, String> {
+    let is_flag = matches!(arg.get_action(), ArgAction::SetTrue);
+    let value_optional = arg
+        .get_num_args()
+        .is_some_and(|range| range.min_values() == 0);
+    match value {
+        toml::Value::Boolean(set) if is_flag || value_optional => Ok(set.then(String::new)),
+        toml::Value::Boolean(_) => Err("needs a value, not true or false".to_string()),
+        _ if is_flag => Err("must be true or false".to_string()),
+        toml::Value::String(text) => Ok(Some(format!("={}", text))),
+        toml::Value::Integer(number) => Ok(Some(format!("={}", number))),
+        toml::Value::Array(items) => {
+            let items = items
+                .iter()
+                .map(|item| item.as_str().ok_or("must be a list of strings"))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((!items.is_empty()).then(|| format!("={}", items.join(","))))
+        }
+        _ => Err("must be a string, number, boolean or list of strings".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::Args;
+    use clap::CommandFactory;
+    use tempfile::TempDir;
+
+    fn settings_args(toml: &str, cli: &[&str]) -> Result<Vec<OsString>, String> {
+        let command = Args::command();
+        let matches = command.clone().try_get_matches_from(cli).unwrap();
+        to_args(
+            &toml.parse().unwrap(),
+            &command,
+            &matches,
+            Path::new("/docs"),
+        )
+    }
+
+    #[test]
+    fn test_to_args() {
+        let args = settings_args(
+            "math = true\nstrikethrough = false\nimage-dir = \"assets\"\nmin_image_size = 32\ntags = [\"a\", \"b\"]\nfootnotes = true\nglyph_map = \"glyphs.toml\"\n",
+            &["pdf2md", "-i", "a.pdf", "-o", "a.md"],
+        )
+        .unwrap();
+        assert_eq!(
+            args,
+            [
+                "--footnotes",
+                "--glyph-map=/docs/glyphs.toml",
+                "--image-dir=assets",
+                "--math",
+                "--min-image-size=32",
+                "--tags=a,b"
+            ]
+            .map(OsString::from)
+        );
+    }
+
+    #[test]
+    fn test_command_line_wins() {
+        let args = settings_args(
+            "image_dir = \"assets\"\nnumber_headings = true\nlenient = true\n",
+            &[
+                "pdf2md",
+                "-i",
+                "a.pdf",
+                "-o",
+                "a.md",
+                "--image-dir",
+                "figs",
+                "--strip-heading-numbers",
+                "--strict",
+            ],
+        )
+        .unwrap();
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn test_bad_settings() {
+        let cli = &["pdf2md", "-i", "a.pdf", "-o", "a.md"];
+        assert_eq!(
+            settings_args("colour = true", cli).unwrap_err(),
+            "unknown setting `colour`"
+        );
+        assert!(settings_args("output = \"x.md\"", cli).is_err());
+        assert!(settings_args("math = \"yes\"", cli).is_err());
+        assert!(settings_args("site = true", cli).is_err());
+    }
+
+    #[test]
+    fn test_select_profile() {
+        let table: toml::Table = "images = true\nfootnotes = \"reference\"\n\n[profile.academic]\nfootnotes = \"notes\"\nmath = true\n\n[profile.slides]\nimages = false\n"
+            .parse()
+            .unwrap();
+
+        let plain = select(table.clone(), None).unwrap();
+        assert_eq!(plain.keys().collect::<Vec<_>>(), ["footnotes", "images"]);
+
+        let academic = select(table.clone(), Some("academic")).unwrap();
+        assert_eq!(academic["footnotes"].as_str(), Some("notes"));
+        assert_eq!(academic["math"].as_bool(), Some(true));
+        assert_eq!(academic["images"].as_bool(), Some(true));
+
+        assert_eq!(
+            select(table, Some("manual")).unwrap_err(),
+            "no [profile.manual] section (profiles: academic, slides)"
+        );
+    }
+
+    #[test]
+    fn test_profile_rules_follow_top_level_rules() {
+        let table: toml::Table = "[[replace]]\npattern = \"a\"\nreplacement = \"b\"\n\n[[profile.x.replace]]\npattern = \"b\"\nreplacement = \"c\"\n"
+            .parse()
+            .unwrap();
+        let mut settings = select(table, Some("x")).unwrap();
+        let rules = take_rules(&mut settings).unwrap();
+        assert_eq!(crate::replace::apply("a", &rules), "c");
+        assert!(settings.is_empty());
+    }
+
+    #[test]
+    fn test_discover() {
+        let temp_dir = TempDir::new().unwrap();
+        let nested = temp_dir.path().join("docs/pdfs");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(discover(&nested), None);
+
+        let settings = temp_dir.path().join(FILE_NAME);
+        std::fs::write(&settings, "math = true\n").unwrap();
+        assert_eq!(discover(&nested), Some(settings));
+    }
+}
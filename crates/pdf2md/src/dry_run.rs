@@ -1,36 +1,517 @@
 use crate::Result;
+use crate::config::Config;
+use crate::i18n::{Message, count, tr, trf};
+use crate::site;
+use crate::style::{Stream, Style};
 use log::info;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::io::{BufRead, Write as _};
+
+/// Characters of each page shown by `--preview-pages`
+const PREVIEW_CHARS: usize = 200;
 
 /// Run in dry-run mode: preview PDF structure without converting
-pub fn run_dry_run(doc: &pdf_extract::PdfDocument) -> Result<()> {
+pub fn run_dry_run(doc: &pdf_extract::PdfDocument, config: &Config) -> Result<()> {
     info!("Running in preview mode (dry-run)");
+    let report = report(doc, config)?;
+    print!("{}", styled(&report, Stream::Stdout.colored()));
+    Ok(())
+}
+
+/// Show the preview, then ask on stderr whether to convert: only an answer
+/// of yes does, and no answer (the end of the input) does not
+pub fn confirm(doc: &pdf_extract::PdfDocument, config: &Config) -> Result<bool> {
+    run_dry_run(doc, config)?;
+    let pages = count(doc.page_count(), Message::OnePage, Message::Pages);
+    eprint!(
+        "{}",
+        trf(
+            Message::ConfirmConvert,
+            &[("pages", &pages), ("output", &config.output_path.display())]
+        )
+    );
+    std::io::stderr().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(is_yes(&answer))
+}
+
+/// Whether an answer is yes, in English or the language of the messages
+fn is_yes(answer: &str) -> bool {
+    let answer = answer.trim().to_lowercase();
+    let yes = tr(Message::Yes).to_lowercase();
+    ["y", "yes"].contains(&answer.as_str()) || (!answer.is_empty() && yes.starts_with(&answer))
+}
+
+/// The report with the head of each section in bold and the fonts likely to
+/// garble flagged in yellow, when `colored`
+fn styled(report: &str, colored: bool) -> String {
+    if !colored {
+        return report.to_string();
+    }
+    let garble = tr(Message::MayGarble);
+    let changed = tr(Message::ChangedAfterSigning);
+    let mut styled = String::with_capacity(report.len() * 2);
+    let mut after_blank = true;
+    for line in report.split_inclusive('\n') {
+        let text = line.trim_end_matches('\n');
+        let head = after_blank && !text.starts_with(' ');
+        after_blank = text.is_empty();
+        if head {
+            styled.push_str(&Style::Bold.apply(text, true));
+        } else {
+            let text = text.replace(garble, &Style::Warning.apply(garble, true));
+            styled.push_str(&text.replace(changed, &Style::Warning.apply(changed, true)));
+        }
+        styled.push_str(&line[text.len()..]);
+    }
+    styled
+}
+
+/// The dry-run report: metadata, text coverage, images, fonts, annotations,
+/// the heading outline and, with `--preview-pages`, the start of each page
+pub(crate) fn report(doc: &pdf_extract::PdfDocument, config: &Config) -> Result<String> {
+    let mut report = String::new();
+    let out = &mut report;
 
     let metadata = doc.extract_metadata()?;
 
-    println!("\n=== PDF Preview ===");
-    println!("Pages: {}", metadata.page_count);
+    let compatibility = doc.compatibility()?;
+
+    // Text of every page, extracted once for the coverage, outline and previews
+    let pages: Vec<pdf_extract::Result<String>> = (1..=metadata.page_count as u32)
+        .map(|page| doc.extract_page_text(page))
+        .collect();
+
+    let _ = writeln!(out, "\n{}", tr(Message::PreviewStart));
+    let _ = writeln!(
+        out,
+        "{}",
+        trf(Message::PdfVersion, &[("version", &compatibility.version)])
+    );
+    let _ = writeln!(
+        out,
+        "{}",
+        trf(Message::PageCount, &[("n", &metadata.page_count)])
+    );
 
     if let Some(title) = &metadata.title {
-        println!("Title: {}", title);
+        let _ = writeln!(out, "{}", trf(Message::Title, &[("title", title)]));
     }
 
     if let Some(author) = &metadata.author {
-        println!("Author: {}", author);
+        let _ = writeln!(out, "{}", trf(Message::Author, &[("author", author)]));
     }
 
-    println!(
-        "Has extractable text: {}",
-        if metadata.has_text { "Yes" } else { "No" }
-    );
+    let answer = tr(if metadata.has_text {
+        Message::Yes
+    } else {
+        Message::No
+    });
+    let _ = writeln!(out, "{}", trf(Message::HasText, &[("answer", &answer)]));
+    let answer = tr(if metadata.tagged {
+        Message::Yes
+    } else {
+        Message::No
+    });
+    let _ = writeln!(out, "{}", trf(Message::Tagged, &[("answer", &answer)]));
+    let claimed: Vec<&str> = [&metadata.pdfa, &metadata.pdfua]
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    if !claimed.is_empty() {
+        let _ = writeln!(
+            out,
+            "{}",
+            trf(Message::Conformance, &[("list", &claimed.join(", "))])
+        );
+    }
+    if !pages.is_empty() {
+        let with_text = pages
+            .iter()
+            .filter(|text| text.as_ref().is_ok_and(|text| !text.trim().is_empty()))
+            .count();
+        let _ = writeln!(
+            out,
+            "{}",
+            trf(
+                Message::TextCoverage,
+                &[
+                    ("percent", &(with_text * 100 / pages.len())),
+                    ("with", &with_text),
+                    ("total", &pages.len()),
+                ]
+            )
+        );
+    }
+
+    let features = compatibility.features();
+    if !features.is_empty() {
+        let _ = writeln!(
+            out,
+            "{}",
+            trf(Message::Features, &[("list", &features.join(", "))])
+        );
+    }
+
+    write_signatures(out, &metadata.signatures);
 
     if !metadata.sections.is_empty() {
-        println!("\nDetected sections:");
+        let _ = writeln!(out, "\n{}", tr(Message::DetectedSections));
         for section in &metadata.sections {
-            println!("  • {}", section);
+            let _ = writeln!(out, "  • {}", section);
         }
     }
 
-    println!("\n=== End Preview ===\n");
+    write_images(out, &doc.image_inventory());
+    write_fonts(out, &doc.font_inventory());
+    write_annotations(out, &doc.annotation_counts());
 
-    Ok(())
+    let outline = heading_outline(&pages, config, &metadata);
+    match outline.iter().map(|(level, _, _)| *level).min() {
+        Some(top) => {
+            let _ = writeln!(out, "\n{}", tr(Message::HeadingOutline));
+            for (level, text, page) in &outline {
+                let indent = "  ".repeat(level - top + 1);
+                let _ = writeln!(
+                    out,
+                    "{}{}",
+                    indent,
+                    trf(Message::OnPage, &[("text", text), ("page", page)])
+                );
+            }
+        }
+        None => {
+            let _ = writeln!(out, "\n{}", tr(Message::NoHeadings));
+        }
+    }
+
+    if config.preview_pages > 0 {
+        let _ = writeln!(out, "\n{}", tr(Message::PagePreviews));
+        for (page, text) in (1..).zip(pages.iter().take(config.preview_pages)) {
+            let text = match text {
+                Ok(text) if text.trim().is_empty() => tr(Message::NoText).to_string(),
+                Ok(text) => preview(text),
+                Err(e) => trf(Message::NotExtracted, &[("error", e)]),
+            };
+            let _ = writeln!(
+                out,
+                "  {}",
+                trf(Message::PageLine, &[("page", &page), ("text", &text)])
+            );
+        }
+    }
+
+    let _ = writeln!(out, "\n{}\n", tr(Message::PreviewEnd));
+
+    Ok(report)
+}
+
+/// Who signed each signature field and when, and whether the document
+/// changed afterwards
+fn write_signatures(out: &mut String, signatures: &[pdf_extract::SignatureInfo]) {
+    if signatures.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "\n{}", tr(Message::Signatures));
+    for signature in signatures {
+        let unknown = tr(Message::UnknownSigner);
+        let signer = signature.signer.as_deref().unwrap_or(unknown);
+        let mut line = trf(
+            Message::SignedBy,
+            &[("field", &signature.field), ("signer", &signer)],
+        );
+        if let Some(time) = &signature.signing_time {
+            line.push_str(&trf(Message::SignedOn, &[("time", time)]));
+        }
+        if signature.modified_after_signing {
+            line = format!("{}; {}", line, tr(Message::ChangedAfterSigning));
+        }
+        let _ = writeln!(out, "  • {}", line);
+    }
+}
+
+/// Image count by format and total size, then the images on each page
+fn write_images(out: &mut String, images: &[pdf_extract::ImageInfo]) {
+    if images.is_empty() {
+        let _ = writeln!(out, "\n{}", tr(Message::NoImages));
+        return;
+    }
+    let mut formats: BTreeMap<&str, usize> = BTreeMap::new();
+    for image in images {
+        *formats.entry(image.format.as_str()).or_default() += 1;
+    }
+    let formats: Vec<String> = formats
+        .iter()
+        .map(|(format, count)| format!("{} {}", count, format))
+        .collect();
+    let total: usize = images.iter().map(|image| image.bytes).sum();
+    let _ = writeln!(
+        out,
+        "\n{}",
+        trf(
+            Message::Images,
+            &[
+                ("n", &images.len()),
+                ("formats", &formats.join(", ")),
+                ("size", &human_size(total)),
+            ]
+        )
+    );
+    for on_page in images.chunk_by(|a, b| a.page == b.page) {
+        let list: Vec<String> = on_page
+            .iter()
+            .map(|image| {
+                format!(
+                    "{} {}×{} ({})",
+                    image.format,
+                    image.width,
+                    image.height,
+                    human_size(image.bytes)
+                )
+            })
+            .collect();
+        let _ = writeln!(
+            out,
+            "  {}",
+            trf(
+                Message::PageLine,
+                &[("page", &on_page[0].page), ("text", &list.join(", "))]
+            )
+        );
+    }
+}
+
+/// Each font with its type, embedding, ToUnicode map and pages, flagging the
+/// ones whose text is likely to come out garbled
+fn write_fonts(out: &mut String, fonts: &[pdf_extract::FontInfo]) {
+    if fonts.is_empty() {
+        let _ = writeln!(out, "\n{}", tr(Message::NoFonts));
+        return;
+    }
+    let _ = writeln!(out, "\n{}", trf(Message::Fonts, &[("n", &fonts.len())]));
+    for font in fonts {
+        let embedding = tr(match (font.embedded, font.subset) {
+            (true, true) => Message::EmbeddedSubset,
+            (true, false) => Message::Embedded,
+            (false, _) => Message::NotEmbedded,
+        });
+        let to_unicode = tr(if font.to_unicode {
+            Message::Yes
+        } else {
+            Message::No
+        })
+        .to_lowercase();
+        let on_pages = if font.pages.len() == 1 {
+            Message::FontOnPage
+        } else {
+            Message::FontOnPages
+        };
+        let _ = writeln!(
+            out,
+            "  {}: {}, {}, ToUnicode: {}, {}{}",
+            font.name,
+            font.subtype,
+            embedding,
+            to_unicode,
+            trf(on_pages, &[("pages", &page_ranges(&font.pages))]),
+            if font.may_garble() {
+                format!(" {}", tr(Message::MayGarble))
+            } else {
+                String::new()
+            }
+        );
+    }
+}
+
+/// Link counts, and other annotations by type
+fn write_annotations(out: &mut String, counts: &pdf_extract::AnnotationCounts) {
+    let _ = writeln!(
+        out,
+        "\n{}",
+        trf(
+            Message::Links,
+            &[
+                ("external", &counts.external_links),
+                ("internal", &counts.internal_links),
+            ]
+        )
+    );
+    let total: usize = counts.other.values().sum();
+    if total == 0 {
+        let _ = writeln!(out, "{}", tr(Message::NoAnnotations));
+        return;
+    }
+    let kinds: Vec<String> = counts
+        .other
+        .iter()
+        .map(|(kind, count)| format!("{} {}", count, kind))
+        .collect();
+    let _ = writeln!(
+        out,
+        "{}",
+        trf(
+            Message::Annotations,
+            &[("n", &total), ("kinds", &kinds.join(", "))]
+        )
+    );
+}
+
+/// Sorted page numbers as ranges (`1-3, 7`)
+fn page_ranges(pages: &[u32]) -> String {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < pages.len() {
+        let start = pages[i];
+        while i + 1 < pages.len() && pages[i + 1] == pages[i] + 1 {
+            i += 1;
+        }
+        ranges.push(match pages[i] {
+            end if end == start => start.to_string(),
+            end => format!("{}-{}", start, end),
+        });
+        i += 1;
+    }
+    ranges.join(", ")
+}
+
+/// Byte count in B, KB or MB
+fn human_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    match bytes as f64 {
+        size if size < KB => format!("{} B", bytes),
+        size if size < KB * KB => format!("{:.1} KB", size / KB),
+        size => format!("{:.1} MB", size / (KB * KB)),
+    }
+}
+
+/// Level, text and page of each heading the conversion would write, after the
+/// heading options (`--normalize-headings`, `--number-headings`,
+/// `--strip-heading-numbers`) are applied
+pub(crate) fn heading_outline(
+    pages: &[pdf_extract::Result<String>],
+    config: &Config,
+    metadata: &pdf_extract::PdfMetadata,
+) -> Vec<(usize, String, u32)> {
+    let mut pages_markdown = Vec::new();
+    let mut heading_pages = Vec::new();
+    for (page, text) in (1..).zip(pages) {
+        let Ok(text) = text else {
+            continue;
+        };
+        let markdown = markdown_gen::format_content_with(text, &config.format);
+        let headings = markdown_gen::outline(&markdown).len();
+        heading_pages.extend(std::iter::repeat_n(page, headings));
+        if !markdown.is_empty() {
+            pages_markdown.push(markdown);
+        }
+    }
+
+    let mut markdown = pages_markdown.join("\n\n");
+    if config.normalize_headings {
+        let title = site::document_title(config, metadata);
+        crate::rewrite(&mut markdown, |markdown| {
+            markdown_gen::normalize_headings(markdown, &title)
+        });
+    }
+    if config.number_headings {
+        crate::rewrite(&mut markdown, markdown_gen::number_headings);
+    } else if config.strip_heading_numbers {
+        crate::rewrite(&mut markdown, markdown_gen::strip_heading_numbers);
+    }
+
+    // Normalising may add a title heading at the top
+    let outline = markdown_gen::outline(&markdown);
+    let added = outline.len().saturating_sub(heading_pages.len());
+    outline
+        .into_iter()
+        .enumerate()
+        .map(|(i, (level, text))| {
+            let page = i.checked_sub(added).map_or(1, |i| heading_pages[i]);
+            (level, text, page)
+        })
+        .collect()
+}
+
+/// The start of a page's text on one line, cut to `PREVIEW_CHARS` characters
+fn preview(text: &str) -> String {
+    let line = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match line.char_indices().nth(PREVIEW_CHARS) {
+        Some((end, _)) => format!("{}…", line[..end].trim_end()),
+        None => line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview() {
+        assert_eq!(preview("Annual\nReport  2024"), "Annual Report 2024");
+        let long = "é".repeat(250);
+        assert_eq!(preview(&long), format!("{}…", "é".repeat(200)));
+    }
+
+    #[test]
+    fn test_page_ranges() {
+        assert_eq!(page_ranges(&[1, 2, 3, 7, 9, 10]), "1-3, 7, 9-10");
+        assert_eq!(page_ranges(&[4]), "4");
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(900), "900 B");
+        assert_eq!(human_size(1536), "1.5 KB");
+        assert_eq!(human_size(3 * 1024 * 1024), "3.0 MB");
+    }
+
+    #[test]
+    fn test_is_yes() {
+        for answer in ["y\n", "Yes\n", " YES "] {
+            assert!(is_yes(answer), "{:?}", answer);
+        }
+        for answer in ["", "\n", "n\n", "no", "yep"] {
+            assert!(!is_yes(answer), "{:?}", answer);
+        }
+    }
+
+    #[test]
+    fn test_write_signatures() {
+        let mut out = String::new();
+        write_signatures(
+            &mut out,
+            &[
+                pdf_extract::SignatureInfo {
+                    field: "Approval".to_string(),
+                    signer: Some("Jane Doe".to_string()),
+                    signing_time: Some("2024-05-01T10:30:45+02:00".to_string()),
+                    modified_after_signing: false,
+                },
+                pdf_extract::SignatureInfo {
+                    field: "Counter".to_string(),
+                    modified_after_signing: true,
+                    ..Default::default()
+                },
+            ],
+        );
+        assert_eq!(
+            out,
+            "\nSignatures:\n  • Approval: signed by Jane Doe on 2024-05-01T10:30:45+02:00\n  \
+             • Counter: signed by an unknown signer; the document was changed after signing\n"
+        );
+    }
+
+    #[test]
+    fn test_styled() {
+        let report = "\n=== PDF Preview ===\nPages: 2\n\nFonts: 1\n  F: Type0 (may extract as garbled text)\n";
+        assert_eq!(styled(report, false), report);
+        assert_eq!(
+            styled(report, true),
+            "\n\x1b[1m=== PDF Preview ===\x1b[0m\nPages: 2\n\n\x1b[1mFonts: 1\x1b[0m\n  \
+             F: Type0 \x1b[1;33m(may extract as garbled text)\x1b[0m\n"
+        );
+    }
 }
@@ -1,3 +1,4 @@
+use crate::i18n::{Message, tr, trf};
 use std::fmt;
 
 pub type Result<T> = std::result::Result<T, Pdf2MdError>;
@@ -13,15 +14,28 @@ pub enum Pdf2MdError {
     MarkdownError(markdown_gen::MarkdownError),
     /// I/O error
     Io(std::io::Error),
+    /// `validate`, `check` or `--check` found problems
+    Validation(String),
+    /// Some or all files of a directory conversion failed
+    Batch { failed: usize, total: usize },
 }
 
 impl fmt::Display for Pdf2MdError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::InvalidInput(msg) => write!(f, "Invalid input: {}", msg),
-            Self::PdfError(e) => write!(f, "PDF error: {}", e),
-            Self::MarkdownError(e) => write!(f, "Markdown error: {}", e),
-            Self::Io(e) => write!(f, "I/O error: {}", e),
+            Self::InvalidInput(msg) => write!(f, "{}: {}", tr(Message::InvalidInput), msg),
+            Self::PdfError(e) => write!(f, "{}: {}", tr(Message::PdfError), e),
+            Self::MarkdownError(e) => write!(f, "{}: {}", tr(Message::MarkdownError), e),
+            Self::Io(e) => write!(f, "{}: {}", tr(Message::IoError), e),
+            Self::Validation(msg) => write!(f, "{}: {}", tr(Message::ValidationFailed), msg),
+            Self::Batch { failed, total } => write!(
+                f,
+                "{}",
+                trf(
+                    Message::BatchFailed,
+                    &[("failed", failed), ("total", total)]
+                )
+            ),
         }
     }
 }
@@ -62,5 +76,9 @@ pub fn error_to_exit_code(error: &Pdf2MdError) -> i32 {
         Pdf2MdError::PdfError(_) => 4,
         Pdf2MdError::MarkdownError(_) => 3,
         Pdf2MdError::Io(_) => 2,
+        Pdf2MdError::Validation(_) => 5,
+        // Whether retrying the whole batch or inspecting a few files is called for
+        Pdf2MdError::Batch { failed, total } if failed < total => 6,
+        Pdf2MdError::Batch { .. } => 7,
     }
 }
@@ -0,0 +1,59 @@
+use crate::{Pdf2MdError, Result};
+use std::path::Path;
+
+/// Read a glyph map: a TOML table from a character (`"U+F0B7"`) or literal
+/// string to the text replacing it
+pub fn load(path: &Path) -> Result<Vec<(String, String)>> {
+    let text = std::fs::read_to_string(path)?;
+    parse(&text)
+        .map_err(|e| Pdf2MdError::InvalidInput(format!("glyph map {}: {}", path.display(), e)))
+}
+
+fn parse(text: &str) -> std::result::Result<Vec<(String, String)>, String> {
+    let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.to_string())?;
+    table
+        .iter()
+        .map(|(key, value)| {
+            let replacement = value
+                .as_str()
+                .ok_or_else(|| format!("the replacement for `{}` must be a string", key))?;
+            Ok((glyph(key)?, replacement.to_string()))
+        })
+        .collect()
+}
+
+/// The text a key stands for: the character of a `U+XXXX` code point, or the
+/// key itself
+fn glyph(key: &str) -> std::result::Result<String, String> {
+    if key.is_empty() {
+        return Err("empty key".to_string());
+    }
+    match key.strip_prefix("U+").or_else(|| key.strip_prefix("u+")) {
+        Some(hex) => u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .map(String::from)
+            .ok_or_else(|| format!("`{}` is not a valid code point", key)),
+        None => Ok(key.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let map = parse("\"U+F0B7\" = \"\u{2022}\"\n\"\u{FB01}\" = \"fi\"\n").unwrap();
+        assert_eq!(
+            map,
+            [
+                ("\u{F0B7}".to_string(), "\u{2022}".to_string()),
+                ("\u{FB01}".to_string(), "fi".to_string()),
+            ]
+        );
+        assert!(parse("\"U+D800\" = \"x\"").is_err());
+        assert!(parse("\"U+zz\" = \"x\"").is_err());
+        assert!(parse("\"a\" = 1").is_err());
+    }
+}
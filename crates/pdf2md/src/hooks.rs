@@ -0,0 +1,70 @@
+use crate::Result;
+use crate::alt_text::shell;
+use log::info;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+/// Pipe text through a user's command and return what it prints.
+///
+/// The command runs through the platform shell with `PDF2MD_INPUT` set to the
+/// PDF being converted. It fails the conversion if it exits unsuccessfully or
+/// prints something other than UTF-8.
+pub fn run_hook(option: &str, command: &str, text: &str, input: &Path) -> Result<String> {
+    info!("Running {} `{}`", option, command);
+    let failed =
+        |reason: String| std::io::Error::other(format!("{} `{}` {}", option, command, reason));
+
+    let mut child = shell(command)
+        .env("PDF2MD_INPUT", input)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(format!("could not be started: {}", e)))?;
+
+    // Write from another thread so a command printing as it reads cannot
+    // block on a full pipe
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let text = text.to_string();
+    let writer = std::thread::spawn(move || {
+        // The command may not read its input at all; a closed pipe is fine
+        let _ = stdin.write_all(text.as_bytes());
+    });
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        return Err(failed(format!("exited with {}", output.status)).into());
+    }
+    String::from_utf8(output.stdout).map_err(|_| failed("printed invalid UTF-8".to_string()).into())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_hook() {
+        let input = Path::new("report.pdf");
+        assert_eq!(
+            run_hook(
+                "--post-hook",
+                "tr a-z A-Z; echo \"$PDF2MD_INPUT\"",
+                "text\n",
+                input
+            )
+            .unwrap(),
+            "TEXT\nreport.pdf\n"
+        );
+        // Larger than a pipe buffer, echoed back while it is being written
+        let long = "line\n".repeat(100_000);
+        assert_eq!(run_hook("--pre-hook", "cat", &long, input).unwrap(), long);
+
+        let error = run_hook("--pre-hook", "exit 3", "", input).unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("--pre-hook `exit 3` exited with")
+        );
+    }
+}
@@ -0,0 +1,671 @@
+//! Translations of the messages pdf2md prints for people: warnings, notes,
+//! the dry-run preview and the `validate` report.
+//!
+//! Log lines (`--verbose`), clap's usage errors and the details of errors
+//! from the PDF library stay in English.
+
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Environment variable choosing the language when `--lang` is not given
+pub const ENV_VAR: &str = "PDF2MD_LANG";
+
+/// Languages of the user-facing output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Language {
+    /// English
+    #[default]
+    En,
+    /// German (Deutsch)
+    De,
+    /// Spanish (Español)
+    Es,
+}
+
+static LANGUAGE: OnceLock<Language> = OnceLock::new();
+
+/// Choose the output language; only the first call has an effect
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+/// The output language: the one set, or else the one `PDF2MD_LANG` names
+pub fn language() -> Language {
+    *LANGUAGE.get_or_init(|| from_env().unwrap_or_default())
+}
+
+/// The language `PDF2MD_LANG` names (`de`, `es_ES.UTF-8`, ...), if any
+pub fn from_env() -> Option<Language> {
+    parse(&std::env::var(ENV_VAR).ok()?)
+}
+
+fn parse(value: &str) -> Option<Language> {
+    let code = value.split(['_', '-', '.']).next()?;
+    Language::from_str(code, true).ok()
+}
+
+/// Messages printed for people
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Message {
+    // Prefixes of the lines printed to stderr
+    Error,
+    Warning,
+    Note,
+    // Error kinds
+    InvalidInput,
+    PdfError,
+    MarkdownError,
+    IoError,
+    ValidationFailed,
+    // Conversion
+    Decrypted,
+    PasswordPrompt,
+    WrongPassword,
+    FailedPage,
+    FailedPages,
+    FailedPageNote,
+    UnappliedRedactions,
+    CoveredText,
+    PartialOutput,
+    StoppedNote,
+    Portfolio,
+    OnePdf,
+    Pdfs,
+    SanitizedStripped,
+    SanitizedReplaced,
+    InvisibleFound,
+    InvisibleStripped,
+    // Dry-run preview
+    PreviewStart,
+    PreviewEnd,
+    ConfirmConvert,
+    NotConverted,
+    PdfVersion,
+    PageCount,
+    Title,
+    Author,
+    HasText,
+    Tagged,
+    Conformance,
+    Yes,
+    No,
+    TextCoverage,
+    Features,
+    Signatures,
+    SignedBy,
+    SignedOn,
+    UnknownSigner,
+    ChangedAfterSigning,
+    DetectedSections,
+    HeadingOutline,
+    NoHeadings,
+    OnPage,
+    PagePreviews,
+    PageLine,
+    NoText,
+    NotExtracted,
+    NoImages,
+    Images,
+    NoFonts,
+    Fonts,
+    EmbeddedSubset,
+    Embedded,
+    NotEmbedded,
+    FontOnPage,
+    FontOnPages,
+    MayGarble,
+    Links,
+    NoAnnotations,
+    Annotations,
+    // `validate` report
+    Document,
+    Page,
+    SeverityError,
+    SeverityWarning,
+    NoPages,
+    CannotExtract,
+    NoTextLayer,
+    GarbledFont,
+    Ok,
+    OkWith,
+    FailedWith,
+    FailedWithBoth,
+    Checked,
+    OneError,
+    Errors,
+    OneWarning,
+    Warnings,
+    OnePage,
+    Pages,
+    // `check` report
+    Coverage,
+    NoGlyphs,
+    LowCoverage,
+    // `--check`
+    OutOfDate,
+    MissingOutput,
+    OneLine,
+    Lines,
+    // Directory conversion
+    BatchSummary,
+    Slowest,
+    BatchFailed,
+    // `--timings` report
+    Timings,
+    StageLoad,
+    StageExtraction,
+    StageCleanup,
+    StageFormatting,
+    StageWriting,
+    SlowestPages,
+    // `toc` and `extract-images`
+    NoBookmarks,
+    WroteImages,
+    OneImage,
+    ImageCount,
+}
+
+impl Message {
+    /// English, German and Spanish text, with `{name}` placeholders
+    fn texts(self) -> [&'static str; 3] {
+        use Message::*;
+        match self {
+            Error => ["Error", "Fehler", "Error"],
+            Warning => ["Warning", "Warnung", "Advertencia"],
+            Note => ["Note", "Hinweis", "Nota"],
+            InvalidInput => ["Invalid input", "Ungültige Eingabe", "Entrada no válida"],
+            PdfError => ["PDF error", "PDF-Fehler", "Error de PDF"],
+            MarkdownError => ["Markdown error", "Markdown-Fehler", "Error de Markdown"],
+            IoError => ["I/O error", "E/A-Fehler", "Error de E/S"],
+            ValidationFailed => [
+                "Validation failed",
+                "Prüfung fehlgeschlagen",
+                "La validación falló",
+            ],
+            Decrypted => [
+                "decrypted with the empty user password; owner-password restrictions were bypassed for text extraction",
+                "mit dem leeren Benutzerpasswort entschlüsselt; die Einschränkungen des Besitzerpassworts wurden für die Textextraktion umgangen",
+                "descifrado con la contraseña de usuario vacía; las restricciones de la contraseña de propietario se omitieron para extraer el texto",
+            ],
+            PasswordPrompt => [
+                "Password for {file}: ",
+                "Passwort für {file}: ",
+                "Contraseña de {file}: ",
+            ],
+            WrongPassword => [
+                "the password is incorrect; try again",
+                "das Passwort ist falsch; bitte erneut versuchen",
+                "la contraseña es incorrecta; inténtelo de nuevo",
+            ],
+            FailedPage => [
+                "1 page could not be extracted and was skipped: {pages}",
+                "1 Seite konnte nicht extrahiert werden und wurde übersprungen: {pages}",
+                "1 página no se pudo extraer y se omitió: {pages}",
+            ],
+            FailedPages => [
+                "{n} pages could not be extracted and were skipped: {pages}",
+                "{n} Seiten konnten nicht extrahiert werden und wurden übersprungen: {pages}",
+                "{n} páginas no se pudieron extraer y se omitieron: {pages}",
+            ],
+            FailedPageNote => [
+                "Page {page} could not be extracted",
+                "Seite {page} konnte nicht extrahiert werden",
+                "La página {page} no se pudo extraer",
+            ],
+            UnappliedRedactions => [
+                "redactions marked on page(s) {pages} were never applied: the text under them is still in the PDF, though left out of the output",
+                "Schwärzungen auf Seite(n) {pages} wurden nie angewendet: Der Text darunter steht noch in der PDF, fehlt aber in der Ausgabe",
+                "las redacciones marcadas en la(s) página(s) {pages} nunca se aplicaron: el texto debajo sigue en el PDF, aunque se omitió de la salida",
+            ],
+            CoveredText => [
+                "text on page(s) {pages} is painted over with a dark box but still in the PDF; it was left out of the output",
+                "Text auf Seite(n) {pages} ist mit einem dunklen Kasten übermalt, steht aber noch in der PDF; er fehlt in der Ausgabe",
+                "el texto de la(s) página(s) {pages} está tapado con un recuadro oscuro pero sigue en el PDF; se omitió de la salida",
+            ],
+            PartialOutput => [
+                "wrote the pages before page {page} only",
+                "nur die Seiten vor Seite {page} wurden geschrieben",
+                "solo se escribieron las páginas anteriores a la página {page}",
+            ],
+            StoppedNote => [
+                "Conversion stopped at page {page} of {total}; the rest of the document is missing",
+                "Die Konvertierung endete bei Seite {page} von {total}; der Rest des Dokuments fehlt",
+                "La conversión se detuvo en la página {page} de {total}; falta el resto del documento",
+            ],
+            Portfolio => [
+                "a portfolio of {pdfs}, each converted to its own file: {names}",
+                "ein Portfolio aus {pdfs}, jedes in eine eigene Datei konvertiert: {names}",
+                "una cartera de {pdfs}, cada uno convertido a su propio archivo: {names}",
+            ],
+            OnePdf => ["1 PDF", "1 PDF", "1 PDF"],
+            Pdfs => ["{n} PDFs", "{n} PDFs", "{n} PDF"],
+            SanitizedStripped => [
+                "control characters and undecodable text removed: {n}",
+                "Steuerzeichen und nicht dekodierbarer Text entfernt: {n}",
+                "caracteres de control y texto no decodificable eliminados: {n}",
+            ],
+            SanitizedReplaced => [
+                "control characters and undecodable text replaced with U+FFFD: {n}",
+                "Steuerzeichen und nicht dekodierbarer Text durch U+FFFD ersetzt: {n}",
+                "caracteres de control y texto no decodificable reemplazados por U+FFFD: {n}",
+            ],
+            InvisibleFound => [
+                "zero-width joiners and bidi controls in the text: {n} (remove them with --invisible-chars strip)",
+                "Breitenlose Verbinder und Bidi-Steuerzeichen im Text: {n} (mit --invisible-chars strip entfernen)",
+                "uniones de ancho cero y controles bidi en el texto: {n} (elimínelos con --invisible-chars strip)",
+            ],
+            InvisibleStripped => [
+                "zero-width joiners and bidi controls removed: {n}",
+                "Breitenlose Verbinder und Bidi-Steuerzeichen entfernt: {n}",
+                "uniones de ancho cero y controles bidi eliminados: {n}",
+            ],
+            PreviewStart => [
+                "=== PDF Preview ===",
+                "=== PDF-Vorschau ===",
+                "=== Vista previa del PDF ===",
+            ],
+            PreviewEnd => [
+                "=== End Preview ===",
+                "=== Ende der Vorschau ===",
+                "=== Fin de la vista previa ===",
+            ],
+            ConfirmConvert => [
+                "Convert {pages} to {output}? [y/N] ",
+                "{pages} nach {output} konvertieren? [j/N] ",
+                "¿Convertir {pages} a {output}? [s/N] ",
+            ],
+            NotConverted => [
+                "not converted; nothing was written",
+                "nicht konvertiert; nichts wurde geschrieben",
+                "sin convertir; no se escribió nada",
+            ],
+            PdfVersion => [
+                "PDF version: {version}",
+                "PDF-Version: {version}",
+                "Versión de PDF: {version}",
+            ],
+            PageCount => ["Pages: {n}", "Seiten: {n}", "Páginas: {n}"],
+            Title => ["Title: {title}", "Titel: {title}", "Título: {title}"],
+            Author => ["Author: {author}", "Autor: {author}", "Autor: {author}"],
+            HasText => [
+                "Has extractable text: {answer}",
+                "Extrahierbarer Text: {answer}",
+                "Tiene texto extraíble: {answer}",
+            ],
+            Tagged => [
+                "Tagged (structure tree): {answer}",
+                "Getaggt (Strukturbaum): {answer}",
+                "Etiquetado (árbol de estructura): {answer}",
+            ],
+            Conformance => [
+                "Claimed conformance: {list}",
+                "Angegebene Konformität: {list}",
+                "Conformidad declarada: {list}",
+            ],
+            Yes => ["Yes", "Ja", "Sí"],
+            No => ["No", "Nein", "No"],
+            TextCoverage => [
+                "Text coverage: {percent}% of pages have a text layer ({with} of {total})",
+                "Textabdeckung: {percent} % der Seiten haben eine Textebene ({with} von {total})",
+                "Cobertura de texto: el {percent} % de las páginas tiene capa de texto ({with} de {total})",
+            ],
+            Features => [
+                "Features: {list}",
+                "Merkmale: {list}",
+                "Características: {list}",
+            ],
+            Signatures => ["Signatures:", "Signaturen:", "Firmas:"],
+            SignedBy => [
+                "{field}: signed by {signer}",
+                "{field}: signiert von {signer}",
+                "{field}: firmado por {signer}",
+            ],
+            SignedOn => [" on {time}", " am {time}", " el {time}"],
+            UnknownSigner => [
+                "an unknown signer",
+                "einer unbekannten Person",
+                "un firmante desconocido",
+            ],
+            ChangedAfterSigning => [
+                "the document was changed after signing",
+                "das Dokument wurde nach dem Signieren geändert",
+                "el documento se modificó después de la firma",
+            ],
+            DetectedSections => [
+                "Detected sections:",
+                "Erkannte Abschnitte:",
+                "Secciones detectadas:",
+            ],
+            HeadingOutline => [
+                "Heading outline:",
+                "Gliederung der Überschriften:",
+                "Esquema de encabezados:",
+            ],
+            NoHeadings => [
+                "Heading outline: no headings detected",
+                "Gliederung der Überschriften: keine Überschriften erkannt",
+                "Esquema de encabezados: no se detectaron encabezados",
+            ],
+            OnPage => [
+                "{text} (page {page})",
+                "{text} (Seite {page})",
+                "{text} (página {page})",
+            ],
+            PagePreviews => [
+                "Page previews:",
+                "Seitenvorschau:",
+                "Vista previa de las páginas:",
+            ],
+            PageLine => [
+                "Page {page}: {text}",
+                "Seite {page}: {text}",
+                "Página {page}: {text}",
+            ],
+            NoText => ["(no text)", "(kein Text)", "(sin texto)"],
+            NotExtracted => [
+                "(could not be extracted: {error})",
+                "(konnte nicht extrahiert werden: {error})",
+                "(no se pudo extraer: {error})",
+            ],
+            NoImages => ["Images: none", "Bilder: keine", "Imágenes: ninguna"],
+            Images => [
+                "Images: {n} ({formats}), {size}",
+                "Bilder: {n} ({formats}), {size}",
+                "Imágenes: {n} ({formats}), {size}",
+            ],
+            NoFonts => ["Fonts: none", "Schriften: keine", "Fuentes: ninguna"],
+            Fonts => ["Fonts: {n}", "Schriften: {n}", "Fuentes: {n}"],
+            EmbeddedSubset => [
+                "embedded subset",
+                "eingebettete Teilmenge",
+                "subconjunto incrustado",
+            ],
+            Embedded => ["embedded", "eingebettet", "incrustada"],
+            NotEmbedded => ["not embedded", "nicht eingebettet", "no incrustada"],
+            FontOnPage => ["page {pages}", "Seite {pages}", "página {pages}"],
+            FontOnPages => ["pages {pages}", "Seiten {pages}", "páginas {pages}"],
+            MayGarble => [
+                "(may extract as garbled text)",
+                "(Text wird eventuell verstümmelt extrahiert)",
+                "(el texto puede extraerse ilegible)",
+            ],
+            Links => [
+                "Links: {external} hyperlinks, {internal} internal links",
+                "Links: {external} Hyperlinks, {internal} interne Links",
+                "Enlaces: {external} hipervínculos, {internal} enlaces internos",
+            ],
+            NoAnnotations => [
+                "Annotations: none",
+                "Anmerkungen: keine",
+                "Anotaciones: ninguna",
+            ],
+            Annotations => [
+                "Annotations: {n} ({kinds})",
+                "Anmerkungen: {n} ({kinds})",
+                "Anotaciones: {n} ({kinds})",
+            ],
+            Document => ["Document", "Dokument", "Documento"],
+            Page => ["Page {page}", "Seite {page}", "Página {page}"],
+            SeverityError => ["error", "Fehler", "error"],
+            SeverityWarning => ["warning", "Warnung", "advertencia"],
+            NoPages => [
+                "document has no pages",
+                "das Dokument hat keine Seiten",
+                "el documento no tiene páginas",
+            ],
+            CannotExtract => [
+                "text cannot be extracted: {error}",
+                "Text kann nicht extrahiert werden: {error}",
+                "no se puede extraer el texto: {error}",
+            ],
+            NoTextLayer => [
+                "no text layer (scanned or image-only page)",
+                "keine Textebene (gescannte Seite oder nur Bilder)",
+                "sin capa de texto (página escaneada o solo con imágenes)",
+            ],
+            GarbledFont => [
+                "font {font} may extract as garbled text",
+                "Schrift {font} wird eventuell verstümmelt extrahiert",
+                "la fuente {font} puede extraerse como texto ilegible",
+            ],
+            Ok => ["OK", "OK", "OK"],
+            OkWith => [
+                "OK with {warnings}",
+                "OK mit {warnings}",
+                "OK con {warnings}",
+            ],
+            FailedWith => [
+                "FAILED with {errors}",
+                "FEHLGESCHLAGEN mit {errors}",
+                "FALLÓ con {errors}",
+            ],
+            FailedWithBoth => [
+                "FAILED with {errors} and {warnings}",
+                "FEHLGESCHLAGEN mit {errors} und {warnings}",
+                "FALLÓ con {errors} y {warnings}",
+            ],
+            Checked => [
+                "{verdict} ({pages} checked)",
+                "{verdict} ({pages} geprüft)",
+                "{verdict} ({pages} revisadas)",
+            ],
+            OneError => ["1 error", "1 Fehler", "1 error"],
+            Errors => ["{n} errors", "{n} Fehler", "{n} errores"],
+            OneWarning => ["1 warning", "1 Warnung", "1 advertencia"],
+            Warnings => ["{n} warnings", "{n} Warnungen", "{n} advertencias"],
+            OnePage => ["1 page", "1 Seite", "1 página"],
+            Pages => ["{n} pages", "{n} Seiten", "{n} páginas"],
+            Coverage => [
+                "{percent}% of {glyphs} glyphs extracted",
+                "{percent} % von {glyphs} Glyphen extrahiert",
+                "{percent} % de {glyphs} glifos extraídos",
+            ],
+            NoGlyphs => [
+                "no text in the content streams",
+                "kein Text in den Inhaltsströmen",
+                "no hay texto en los flujos de contenido",
+            ],
+            LowCoverage => [
+                "only {percent}% of the glyphs extracted, below --min-coverage {min}",
+                "nur {percent} % der Glyphen extrahiert, unter --min-coverage {min}",
+                "solo se extrajo el {percent} % de los glifos, por debajo de --min-coverage {min}",
+            ],
+            OutOfDate => [
+                "{output} is out of date with {input} from line {line} ({lines} changed)",
+                "{output} ist ab Zeile {line} nicht mehr aktuell zu {input} ({lines} geändert)",
+                "{output} no está al día con {input} desde la línea {line} ({lines} con cambios)",
+            ],
+            MissingOutput => [
+                "{output} does not exist; convert {input} without --check first",
+                "{output} existiert nicht; zuerst {input} ohne --check konvertieren",
+                "{output} no existe; convierta primero {input} sin --check",
+            ],
+            OneLine => ["1 line", "1 Zeile", "1 línea"],
+            Lines => ["{n} lines", "{n} Zeilen", "{n} líneas"],
+            BatchSummary => [
+                "Converted {ok} of {total} files in {seconds}s with {jobs} jobs",
+                "{ok} von {total} Dateien in {seconds} s mit {jobs} Jobs konvertiert",
+                "Se convirtieron {ok} de {total} archivos en {seconds} s con {jobs} trabajos",
+            ],
+            Slowest => [
+                "Slowest: {file} ({seconds}s)",
+                "Am langsamsten: {file} ({seconds} s)",
+                "El más lento: {file} ({seconds} s)",
+            ],
+            BatchFailed => [
+                "{failed} of {total} files failed",
+                "{failed} von {total} Dateien fehlgeschlagen",
+                "fallaron {failed} de {total} archivos",
+            ],
+            Timings => [
+                "Time spent ({seconds}s in total):",
+                "Zeitaufwand ({seconds} s insgesamt):",
+                "Tiempo empleado ({seconds} s en total):",
+            ],
+            StageLoad => ["loading", "Laden", "carga"],
+            StageExtraction => ["extraction", "Extraktion", "extracción"],
+            StageCleanup => ["cleanup", "Bereinigung", "limpieza"],
+            StageFormatting => ["formatting", "Formatierung", "formato"],
+            StageWriting => ["writing", "Schreiben", "escritura"],
+            SlowestPages => [
+                "Slowest pages:",
+                "Langsamste Seiten:",
+                "Páginas más lentas:",
+            ],
+            NoBookmarks => [
+                "the document has no bookmarks; listing the sections detected in its text",
+                "das Dokument hat keine Lesezeichen; aufgeführt sind die im Text erkannten Abschnitte",
+                "el documento no tiene marcadores; se muestran las secciones detectadas en su texto",
+            ],
+            WroteImages => [
+                "Wrote {images} and {manifest} to {dir}",
+                "{images} und {manifest} nach {dir} geschrieben",
+                "Se escribieron {images} y {manifest} en {dir}",
+            ],
+            OneImage => ["1 image", "1 Bild", "1 imagen"],
+            ImageCount => ["{n} images", "{n} Bilder", "{n} imágenes"],
+        }
+    }
+}
+
+/// A message in the output language
+pub fn tr(message: Message) -> &'static str {
+    let [en, de, es] = message.texts();
+    match language() {
+        Language::En => en,
+        Language::De => de,
+        Language::Es => es,
+    }
+}
+
+/// A message in the output language with its `{name}` placeholders filled in
+pub fn trf(message: Message, values: &[(&str, &dyn Display)]) -> String {
+    let mut text = String::new();
+    let mut rest = tr(message);
+    // One pass, so a value containing `{name}` is left alone
+    while let Some(start) = rest.find('{') {
+        text.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        let end = placeholder
+            .find('}')
+            .map_or(placeholder.len(), |end| end + 1);
+        let name = placeholder[1..end].trim_end_matches('}');
+        match values.iter().find(|(key, _)| *key == name) {
+            Some((_, value)) => text.push_str(&value.to_string()),
+            None => text.push_str(&placeholder[..end]),
+        }
+        rest = &placeholder[end..];
+    }
+    text.push_str(rest);
+    text
+}
+
+/// `one` for a count of 1, else `many` with `{n}` filled in
+pub fn count(n: usize, one: Message, many: Message) -> String {
+    if n == 1 {
+        tr(one).to_string()
+    } else {
+        trf(many, &[("n", &n)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Names of the `{name}` placeholders in a text
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text
+            .split('{')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_translations_keep_placeholders() {
+        use Message::*;
+        for message in [
+            PasswordPrompt,
+            FailedPage,
+            FailedPages,
+            FailedPageNote,
+            UnappliedRedactions,
+            CoveredText,
+            PartialOutput,
+            StoppedNote,
+            Portfolio,
+            Pdfs,
+            SanitizedStripped,
+            SanitizedReplaced,
+            InvisibleFound,
+            InvisibleStripped,
+            ConfirmConvert,
+            PdfVersion,
+            PageCount,
+            Title,
+            Author,
+            HasText,
+            Tagged,
+            Conformance,
+            TextCoverage,
+            Features,
+            SignedBy,
+            SignedOn,
+            OnPage,
+            PageLine,
+            NotExtracted,
+            Images,
+            Fonts,
+            FontOnPage,
+            FontOnPages,
+            Links,
+            Annotations,
+            Page,
+            CannotExtract,
+            GarbledFont,
+            OkWith,
+            FailedWith,
+            FailedWithBoth,
+            Checked,
+            Errors,
+            Warnings,
+            Pages,
+            Coverage,
+            LowCoverage,
+            OutOfDate,
+            MissingOutput,
+            Lines,
+            BatchSummary,
+            Slowest,
+            BatchFailed,
+            Timings,
+            WroteImages,
+            ImageCount,
+        ] {
+            let [en, de, es] = message.texts();
+            assert!(!placeholders(en).is_empty(), "{:?}", message);
+            assert_eq!(placeholders(en), placeholders(de), "{:?}", message);
+            assert_eq!(placeholders(en), placeholders(es), "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn test_trf_fills_once() {
+        assert_eq!(
+            trf(Message::OnPage, &[("text", &"{page}"), ("page", &3)]),
+            "{page} (page 3)"
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse("de"), Some(Language::De));
+        assert_eq!(parse("es_ES.UTF-8"), Some(Language::Es));
+        assert_eq!(parse("EN-us"), Some(Language::En));
+        assert_eq!(parse("fr"), None);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::Result;
+use crate::i18n::{self, Message, trf};
+use crate::json;
+use log::info;
+use pdf_extract::{ExtractOptions, ExtractedImage};
+use std::path::Path;
+
+/// File listing the extracted images, written next to them
+const MANIFEST: &str = "manifest.json";
+
+/// Write the images of a PDF, and a manifest of them, to `output_dir`
+pub fn run_extract_images(input: &Path, output_dir: &Path, options: &ExtractOptions) -> Result<()> {
+    pdf_extract::validate_pdf(input)?;
+    let doc = pdf_extract::PdfDocument::open(input)?;
+
+    let images = doc.extract_images(options);
+    info!(
+        "Writing {} images to {}",
+        images.len(),
+        output_dir.display()
+    );
+    for image in &images {
+        markdown_gen::write_binary_file(&image.data, &output_dir.join(&image.file_name))?;
+    }
+    markdown_gen::write_binary_file(manifest(&images).as_bytes(), &output_dir.join(MANIFEST))?;
+
+    let count = i18n::count(images.len(), Message::OneImage, Message::ImageCount);
+    println!(
+        "{}",
+        trf(
+            Message::WroteImages,
+            &[
+                ("images", &count),
+                ("manifest", &MANIFEST),
+                ("dir", &output_dir.display()),
+            ]
+        )
+    );
+    Ok(())
+}
+
+/// JSON array describing each image: file name, page, size in pixels
+/// (points for SVG drawings) and file size in bytes
+fn manifest(images: &[ExtractedImage]) -> String {
+    let items: Vec<String> = images
+        .iter()
+        .map(|image| {
+            format!(
+                "{{\"file\": {}, \"page\": {}, \"width\": {}, \"height\": {}, \"bytes\": {}}}",
+                json::string(&image.file_name),
+                image.page,
+                image.width,
+                image.height,
+                image.data.len()
+            )
+        })
+        .collect();
+    format!("{}\n", json::array(&items))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest() {
+        let image = ExtractedImage {
+            file_name: "page-2-image-1.png".to_string(),
+            page: 2,
+            width: 640,
+            height: 480,
+            data: vec![0; 1234],
+            alt_text: None,
+        };
+        assert_eq!(
+            manifest(&[image]),
+            "[\n  {\"file\": \"page-2-image-1.png\", \"page\": 2, \"width\": 640, \"height\": 480, \"bytes\": 1234}\n]\n"
+        );
+        assert_eq!(manifest(&[]), "[]\n");
+    }
+}
@@ -0,0 +1,178 @@
+//! `--check`: whether an output file is still what its PDF converts to, so
+//! CI can keep committed Markdown in step with the source documents
+
+use crate::i18n::{Message, count, trf};
+use crate::{Pdf2MdError, Result};
+use chrono::DateTime;
+use std::borrow::Cow;
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// Widest line quoted in the summary of a difference
+const QUOTE_CHARS: usize = 80;
+
+/// Compare a conversion made in memory with the output file, failing with a
+/// summary of the first difference.
+///
+/// `converted` is the conversion time of the fresh provenance record: the
+/// file's own time is kept in its place, as it only says when that file was
+/// written.
+pub(crate) fn compare(
+    output: &Path,
+    input: &Path,
+    fresh: &str,
+    converted: Option<&str>,
+) -> Result<()> {
+    let committed = match std::fs::read_to_string(output) {
+        Ok(committed) => committed,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(Pdf2MdError::Validation(trf(
+                Message::MissingOutput,
+                &[("output", &output.display()), ("input", &input.display())],
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let fresh = match converted {
+        Some(time) => with_committed_time(fresh, &committed, time),
+        None => Cow::Borrowed(fresh),
+    };
+    match difference(&committed, &fresh) {
+        None => Ok(()),
+        Some(difference) => Err(Pdf2MdError::Validation(difference.summary(output, input))),
+    }
+}
+
+/// The lines around which two texts differ, once their common start and end
+/// are set aside
+#[derive(Debug, PartialEq)]
+struct Difference<'a> {
+    /// First differing line, from 1
+    line: usize,
+    committed: Vec<&'a str>,
+    fresh: Vec<&'a str>,
+}
+
+impl Difference<'_> {
+    /// `report.md is out of date with report.pdf from line 7 (2 lines changed)`,
+    /// then the first line of each side
+    fn summary(&self, output: &Path, input: &Path) -> String {
+        let lines = self.committed.len().max(self.fresh.len());
+        let mut summary = trf(
+            Message::OutOfDate,
+            &[
+                ("output", &output.display()),
+                ("input", &input.display()),
+                ("lines", &count(lines, Message::OneLine, Message::Lines)),
+                ("line", &self.line),
+            ],
+        );
+        for (sign, side) in [('-', &self.committed), ('+', &self.fresh)] {
+            if let Some(line) = side.first() {
+                summary.push_str(&format!("\n  {} {}", sign, quote(line)));
+            }
+        }
+        summary
+    }
+}
+
+fn difference<'a>(committed: &'a str, fresh: &'a str) -> Option<Difference<'a>> {
+    if committed == fresh {
+        return None;
+    }
+    // Split on every newline, so a missing final one differs too
+    let committed: Vec<&str> = committed.split('\n').collect();
+    let fresh: Vec<&str> = fresh.split('\n').collect();
+    let start = committed
+        .iter()
+        .zip(&fresh)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let end = committed[start..]
+        .iter()
+        .rev()
+        .zip(fresh[start..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    Some(Difference {
+        line: start + 1,
+        committed: committed[start..committed.len() - end].to_vec(),
+        fresh: fresh[start..fresh.len() - end].to_vec(),
+    })
+}
+
+/// The fresh text with the conversion time the committed file has in the
+/// same place, when everything before it matches
+fn with_committed_time<'a>(fresh: &'a str, committed: &str, time: &str) -> Cow<'a, str> {
+    let Some(at) = fresh.find(time) else {
+        return Cow::Borrowed(fresh);
+    };
+    let end = at + time.len();
+    match committed.get(at..end) {
+        Some(old)
+            if committed.get(..at) == Some(&fresh[..at])
+                && DateTime::parse_from_rfc3339(old).is_ok() =>
+        {
+            Cow::Owned(format!("{}{}{}", &fresh[..at], old, &fresh[end..]))
+        }
+        _ => Cow::Borrowed(fresh),
+    }
+}
+
+/// A line cut to `QUOTE_CHARS` characters
+fn quote(line: &str) -> Cow<'_, str> {
+    match line.char_indices().nth(QUOTE_CHARS) {
+        Some((cut, _)) => Cow::Owned(format!("{}…", &line[..cut])),
+        None => Cow::Borrowed(line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difference() {
+        assert_eq!(difference("# A\n\nText\n", "# A\n\nText\n"), None);
+        assert_eq!(
+            difference("# A\n\nOld\nEnd\n", "# A\n\nNew\nMore\nEnd\n"),
+            Some(Difference {
+                line: 3,
+                committed: vec!["Old"],
+                fresh: vec!["New", "More"],
+            })
+        );
+        // Only the final newline is missing
+        assert_eq!(
+            difference("Text\n", "Text"),
+            Some(Difference {
+                line: 2,
+                committed: vec![""],
+                fresh: vec![],
+            })
+        );
+        let summary = difference("a\nb\n", "a\nc\n")
+            .unwrap()
+            .summary(Path::new("r.md"), Path::new("r.pdf"));
+        assert_eq!(
+            summary,
+            "r.md is out of date with r.pdf from line 2 (1 line changed)\n  - b\n  + c"
+        );
+    }
+
+    #[test]
+    fn test_with_committed_time() {
+        let fresh = "---\nconverted: 2026-10-15T09:00:00Z\n---\n\nText\n";
+        let committed = "---\nconverted: 2025-01-02T03:04:05Z\n---\n\nText\n";
+        assert_eq!(
+            with_committed_time(fresh, committed, "2026-10-15T09:00:00Z"),
+            committed
+        );
+        // Anything else differing before the time keeps the fresh one
+        let moved = "---\nsize: 1\nconverted: 2025-01-02T03:04:05Z\n---\n";
+        assert_eq!(
+            with_committed_time(fresh, moved, "2026-10-15T09:00:00Z"),
+            fresh
+        );
+    }
+}
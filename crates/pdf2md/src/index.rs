@@ -0,0 +1,260 @@
+//! `--index sqlite:<FILE>`: each converted document's text, metadata and
+//! page offsets added to an SQLite full-text (FTS5) database, through the
+//! `sqlite3` command-line shell
+
+use crate::config::Config;
+use crate::{Pdf2MdError, Result, sidecar};
+use chrono::{SecondsFormat, Utc};
+use log::info;
+use pdf_extract::{ExtractedContent, PdfDocument};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// How long a conversion waits for another one writing to the database, in ms
+const BUSY_TIMEOUT_MS: u32 = 60_000;
+
+/// Tables of the index. `pages` holds the Markdown of each page, from the
+/// line it starts on; search it with `pages MATCH '...'`
+const SCHEMA: &str = "\
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS documents (
+  id INTEGER PRIMARY KEY,
+  source TEXT NOT NULL UNIQUE,
+  output TEXT NOT NULL,
+  title TEXT,
+  author TEXT,
+  subject TEXT,
+  keywords TEXT,
+  created TEXT,
+  modified TEXT,
+  page_count INTEGER NOT NULL,
+  indexed TEXT NOT NULL
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS pages USING fts5(
+  text,
+  document UNINDEXED,
+  page UNINDEXED,
+  line UNINDEXED
+);
+";
+
+/// Where `--index` adds the converted documents
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexTarget {
+    /// An SQLite database file, created if missing
+    Sqlite(PathBuf),
+}
+
+impl std::str::FromStr for IndexTarget {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("sqlite", path)) if !path.is_empty() => Ok(IndexTarget::Sqlite(path.into())),
+            _ => Err(format!("unknown index '{}' (expected sqlite:<FILE>)", s)),
+        }
+    }
+}
+
+/// Add a converted document to the index, replacing an earlier entry for
+/// the same input
+pub fn add(
+    config: &Config,
+    target: &IndexTarget,
+    doc: &PdfDocument,
+    content: &ExtractedContent,
+    markdown: &str,
+) -> Result<()> {
+    let IndexTarget::Sqlite(database) = target;
+    let metadata = doc.extract_metadata()?;
+    info!("Indexing into {}", database.display());
+    execute(database, &statements(config, &metadata, content, markdown))
+}
+
+/// Run an SQL script on the database with the `sqlite3` shell, stopping at
+/// the first error
+fn execute(database: &Path, sql: &str) -> Result<()> {
+    let failed = |reason: String| {
+        std::io::Error::other(format!("--index {}: {}", database.display(), reason))
+    };
+    let mut child = Command::new("sqlite3")
+        .args(["-bail", "-batch"])
+        .arg(database)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(format!("sqlite3 could not be started: {}", e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    // sqlite3 stops reading at the first error; the error is on stderr
+    let _ = stdin.write_all(sql.as_bytes());
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(Pdf2MdError::Io(failed(message.trim().to_string())));
+    }
+    Ok(())
+}
+
+/// The SQL script adding the document, in one transaction
+fn statements(
+    config: &Config,
+    metadata: &pdf_extract::PdfMetadata,
+    content: &ExtractedContent,
+    markdown: &str,
+) -> String {
+    let source = quote(&config.input_path.display().to_string());
+    let mut sql = format!(".timeout {}\n{}BEGIN IMMEDIATE;\n", BUSY_TIMEOUT_MS, SCHEMA);
+    sql.push_str(&format!(
+        "DELETE FROM pages WHERE document IN (SELECT id FROM documents WHERE source = {0});\n\
+         DELETE FROM documents WHERE source = {0};\n",
+        source
+    ));
+    let optional = |value: &Option<String>| value.as_deref().map_or("NULL".to_string(), quote);
+    sql.push_str(&format!(
+        "INSERT INTO documents (source, output, title, author, subject, keywords, created, modified, page_count, indexed) \
+         VALUES ({}, {}, {}, {}, {}, {}, {}, {}, {}, {});\n",
+        source,
+        quote(&config.output_path.display().to_string()),
+        optional(&metadata.title),
+        optional(&metadata.author),
+        optional(&metadata.subject),
+        optional(&metadata.keywords),
+        optional(&metadata.creation_date),
+        optional(&metadata.modification_date),
+        content.page_count,
+        quote(&Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)),
+    ));
+    let document = format!("(SELECT id FROM documents WHERE source = {})", source);
+    for (page, line, text) in page_texts(content, markdown) {
+        sql.push_str(&format!(
+            "INSERT INTO pages (text, document, page, line) VALUES ({}, {}, {}, {});\n",
+            quote(text),
+            document,
+            page,
+            line
+        ));
+    }
+    sql.push_str("COMMIT;\n");
+    sql
+}
+
+/// The Markdown of each page located in it, with the page number and the
+/// line it starts on. Text before the first located page goes with that
+/// page, and pages not located stay with the one before them
+fn page_texts<'a>(content: &ExtractedContent, markdown: &'a str) -> Vec<(u32, usize, &'a str)> {
+    let starts: Vec<(u32, usize)> = sidecar::page_lines(content, markdown)
+        .into_iter()
+        .filter_map(|(page, line)| Some((page, line?)))
+        .collect();
+    if starts.is_empty() {
+        return if markdown.trim().is_empty() {
+            Vec::new()
+        } else {
+            vec![(1, 1, markdown)]
+        };
+    }
+
+    // Byte offset of each line start
+    let mut offsets = vec![0];
+    offsets.extend(
+        markdown
+            .match_indices('\n')
+            .map(|(i, _)| i + 1)
+            .filter(|&i| i < markdown.len()),
+    );
+    let offset = |line: usize| offsets.get(line - 1).copied().unwrap_or(markdown.len());
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &(page, line))| {
+            let start = if i == 0 { 0 } else { offset(line) };
+            let end = starts
+                .get(i + 1)
+                .map_or(markdown.len(), |&(_, next)| offset(next));
+            (page, line, markdown[start..end].trim())
+        })
+        .collect()
+}
+
+/// An SQL string literal
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\0', "").replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn content() -> ExtractedContent {
+        ExtractedContent {
+            page_count: 3,
+            page_starts: vec![
+                (1, "INTRODUCTION".to_string()),
+                (3, "It's done".to_string()),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_index_target() {
+        assert_eq!(
+            "sqlite:corpus.db".parse(),
+            Ok(IndexTarget::Sqlite("corpus.db".into()))
+        );
+        assert!("sqlite:".parse::<IndexTarget>().is_err());
+        assert!("postgres://db".parse::<IndexTarget>().is_err());
+    }
+
+    #[test]
+    fn test_page_texts() {
+        let markdown = "# Report\n\n## INTRODUCTION\n\nFirst page.\n\nIt's done.";
+        assert_eq!(
+            page_texts(&content(), markdown),
+            vec![
+                (1, 3, "# Report\n\n## INTRODUCTION\n\nFirst page."),
+                (3, 7, "It's done.")
+            ]
+        );
+        assert_eq!(page_texts(&ExtractedContent::default(), " \n"), vec![]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_add_to_sqlite() {
+        if Command::new("sqlite3").arg("-version").output().is_err() {
+            // Skip test if the sqlite3 shell isn't installed
+            return;
+        }
+        let dir = tempfile::tempdir().unwrap();
+        let database = dir.path().join("corpus.db");
+        let config = Config {
+            input_path: "report.pdf".into(),
+            output_path: "out/report.md".into(),
+            ..Default::default()
+        };
+        let metadata = pdf_extract::PdfMetadata {
+            title: Some("Q1 'Final'".to_string()),
+            ..Default::default()
+        };
+        let markdown = "## INTRODUCTION\n\nFirst page.\n\nIt's done.";
+        for _ in 0..2 {
+            execute(
+                &database,
+                &statements(&config, &metadata, &content(), markdown),
+            )
+            .unwrap();
+        }
+        let query = Command::new("sqlite3")
+            .arg(&database)
+            .arg("SELECT d.title, p.page, p.line FROM pages p JOIN documents d ON d.id = p.document WHERE pages MATCH 'done'")
+            .output()
+            .unwrap();
+        assert_eq!(String::from_utf8_lossy(&query.stdout), "Q1 'Final'|3|5\n");
+    }
+}
@@ -1,14 +1,56 @@
 pub mod cli;
 pub mod config;
+mod config_file;
 pub mod error;
+pub mod i18n;
+pub mod style;
 
+mod alt_text;
+mod batch;
+mod check;
 mod dry_run;
+mod glyph_map;
+mod hooks;
+mod images;
+mod in_sync;
+mod index;
+mod json;
 mod logging;
+mod mcp;
+mod metadata;
+mod normalize;
+mod notify;
+mod page_cache;
+mod pandoc;
+mod password;
+mod pipeline;
+mod portfolio;
+mod provenance;
+mod quarto;
+mod remote;
+pub mod replace;
+mod rpc;
+mod s3;
+mod sanitize;
+mod script;
+mod search;
+pub mod sections;
+mod sidecar;
+pub mod site;
+mod timings;
+mod toc;
+mod validate;
 
 pub use error::{Pdf2MdError, Result};
 
+use cli::{Command, OutputFormat};
 use config::Config;
+use i18n::{Message, count, tr, trf};
 use log::info;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 
 /// Main application entry point
 pub fn run(config: Config) -> Result<()> {
@@ -16,7 +58,67 @@ pub fn run(config: Config) -> Result<()> {
     logging::init_logging(config.verbose);
 
     info!("Starting pdf2md");
+    if let Some(path) = &config.config_file {
+        info!("Settings from {}", path.display());
+    }
+
+    if config.rpc {
+        return rpc::serve(&config);
+    }
+    let notify = notify::Notify::from_config(&config).filter(|_| !config.dry_run);
+    let started = std::time::Instant::now();
+    let result = convert_input(config);
+    if let Some(notify) = notify {
+        notify.send(&result, started.elapsed());
+    }
+    result.map(|_| ())
+}
+
+/// Convert the input file, S3 object, URL or directory, returning the number
+/// of PDFs converted
+fn convert_input(config: Config) -> Result<usize> {
+    if s3::is_s3(&config.input_path) || s3::is_s3(&config.output_path) {
+        return s3::run(config).map(|()| 1);
+    }
+    if remote::is_url(&config.input_path) {
+        return remote::run(config).map(|()| 1);
+    }
+    if config.input_path.is_dir() {
+        return batch::run_batch(&config);
+    }
+    if config.batch_report.is_some() {
+        return Err(Pdf2MdError::InvalidInput(
+            "--batch-report summarizes a directory conversion; pass a directory".to_string(),
+        ));
+    }
+    let search_index = config
+        .search_index
+        .clone()
+        .filter(|_| !config.dry_run && !config.check);
+    let output = config.output_path.clone();
+    convert(config)?;
+    if let Some(target) = &search_index {
+        let root = output.parent().unwrap_or(Path::new(""));
+        search::write(target, root, std::slice::from_ref(&output))?;
+    }
+    Ok(1)
+}
+
+/// What a conversion wrote, and the warnings it gave
+#[derive(Debug, Default)]
+pub(crate) struct Converted {
+    /// The output, then its images and sidecar
+    pub files: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+    pub pages: usize,
+    /// Words of the Markdown
+    pub words: usize,
+}
+
+/// Convert one PDF
+pub(crate) fn convert(config: Config) -> Result<Converted> {
     info!("Input: {}", config.input_path.display());
+    let timings = timings::Timings::start();
 
     // Validate configuration
     config.validate()?;
@@ -24,30 +126,502 @@ pub fn run(config: Config) -> Result<()> {
     // Validate PDF file
     pdf_extract::validate_pdf(&config.input_path)?;
 
-    // Open PDF
-    let doc = pdf_extract::PdfDocument::open(&config.input_path)?;
+    let doc = open(&config)?;
+    convert_document(config, doc, timings)
+}
+
+/// Files from this size up are read only for the pages `--pages` picks.
+/// Smaller ones parse quickly enough whole, which also reads the form,
+/// structure tree and embedded files a read of the pages alone skips.
+const PAGES_ONLY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Open the input PDF, with the pages `--pages` picks only
+fn open(config: &Config) -> Result<pdf_extract::PdfDocument> {
+    // The provenance record hashes the whole file
+    if let Some(pages) = config.pages.as_ref().filter(|_| !config.provenance)
+        && std::fs::metadata(&config.input_path)?.len() >= PAGES_ONLY_SIZE
+    {
+        match pdf_extract::PdfDocument::open_pages(&config.input_path, pages) {
+            Ok(doc) => return Ok(doc),
+            Err(e) => info!(
+                "Reading pages {} alone failed ({}); parsing the whole file",
+                pages, e
+            ),
+        }
+    }
+
+    // Without the image data it does not need in low-memory mode
+    let mut doc = if config.low_memory && !config.images && !config.dry_run {
+        pdf_extract::PdfDocument::open_without_images(&config.input_path)?
+    } else {
+        pdf_extract::PdfDocument::open(&config.input_path)?
+    };
+    if let Some(pages) = &config.pages {
+        doc.select_pages(pages)?;
+    }
+    Ok(doc)
+}
+
+/// Convert an opened PDF, with the configuration validated
+pub(crate) fn convert_document(
+    mut config: Config,
+    mut doc: pdf_extract::PdfDocument,
+    mut timings: timings::Timings,
+) -> Result<Converted> {
+    password::unlock(&mut doc, &config)?;
+    if !config.low_memory {
+        doc.cache_streams(config.stream_cache_mb * 1024 * 1024);
+    }
+
+    // Explain up front why a file may convert badly
+    let compatibility = doc.compatibility()?;
+    info!("PDF version {}", compatibility.version);
+    if compatibility.decrypted && !compatibility.with_password {
+        eprintln!(
+            "{}: {}",
+            style::note(tr(Message::Note)),
+            tr(Message::Decrypted)
+        );
+    }
+    for warning in compatibility.warnings() {
+        eprintln!("{}: {}", style::warning(tr(Message::Warning)), warning);
+    }
+
+    // A portfolio's documents are converted rather than its cover sheet
+    let portfolio = doc.portfolio_documents();
+    if !portfolio.is_empty() {
+        let names: Vec<&str> = portfolio
+            .iter()
+            .map(|document| document.name.as_str())
+            .collect();
+        eprintln!(
+            "{}: {}",
+            style::note(tr(Message::Note)),
+            trf(
+                Message::Portfolio,
+                &[
+                    (
+                        "pdfs",
+                        &count(portfolio.len(), Message::OnePdf, Message::Pdfs)
+                    ),
+                    ("names", &names.join(", ")),
+                ]
+            )
+        );
+    }
 
     // Handle dry-run mode
     if config.dry_run {
-        return dry_run::run_dry_run(&doc);
+        dry_run::run_dry_run(&doc, &config)?;
+        return Ok(Converted::default());
+    }
+    if config.confirm && !dry_run::confirm(&doc, &config)? {
+        eprintln!(
+            "{}: {}",
+            style::note(tr(Message::Note)),
+            tr(Message::NotConverted)
+        );
+        return Ok(Converted::default());
     }
+    if !portfolio.is_empty() {
+        return portfolio::convert(&config, &doc, &portfolio);
+    }
+
+    // Lay the output out for a static site generator
+    let site = match config.site {
+        Some(site) => Some(site::prepare(site, &mut config, &doc)?),
+        None => None,
+    };
 
     info!("Output: {}", config.output_path.display());
 
+    // Hash the input before converting, so the record matches what was read
+    let provenance = if config.provenance {
+        Some(provenance::Provenance::of(&config.input_path)?)
+    } else {
+        None
+    };
+
     // Extract content
-    let content = doc.extract_text()?;
-    info!("Extracted {} pages", content.page_count);
+    let mut options = config.extract_options();
+    if let Some(path) = &config.glyph_map {
+        options.glyph_map = glyph_map::load(path)?;
+    }
+    let script = match &config.script {
+        Some(path) => Some(script::Script::load(path)?),
+        None => None,
+    };
 
-    // Generate Markdown
-    let markdown = markdown_gen::format_content(&content.text);
+    timings.load = timings.lap();
+
+    // Write pages as they are extracted when no stage needs the whole document
+    if pipeline::can_stream(&config) {
+        let content = pipeline::convert(&config, &doc, &options, &mut timings)?;
+        warn_failed_pages(&content);
+        warn_redactions(&content);
+        if config.sidecar.is_some() || config.index.is_some() {
+            let markdown = std::fs::read_to_string(&config.output_path)?;
+            describe(&config, &doc, &content, &markdown)?;
+        }
+        // A conversion that stopped early fails, and may have written nothing
+        let words = match content.stopped_at {
+            None => words_in(&config.output_path)?,
+            Some(_) => 0,
+        };
+        let converted = converted(&config, &doc, &content, words)?;
+        finish(&config, content, timings)?;
+        return Ok(converted);
+    }
+
+    let content = doc.extract_text_with(&options)?;
+    timings.extracted(&content.timings);
+    warn_failed_pages(&content);
+    warn_redactions(&content);
+
+    let mut markdown = format_markdown(&config, &doc, &content, script.as_ref())?;
+    let pandoc = config.output_format == OutputFormat::PandocJson;
+    if site.is_none() && !pandoc && quarto::is_quarto_path(&config.output_path) {
+        markdown = quarto::to_quarto(&config, &doc.extract_metadata()?, &markdown);
+    }
+    if site.is_none()
+        && !pandoc
+        && let Some(provenance) = &provenance
+    {
+        markdown = provenance::add_to_front_matter(&markdown, provenance, false);
+    }
+    if let Some((page, _)) = &content.stopped_at {
+        markdown.push_str(&stopped_note(*page, content.page_count));
+    }
+
+    if let Some(command) = &config.post_hook {
+        markdown = hooks::run_hook("--post-hook", command, &markdown, &config.input_path)?;
+    }
+    let words = markdown.split_whitespace().count();
+    if pandoc {
+        markdown = pandoc::to_pandoc_json(
+            &config,
+            &doc.extract_metadata()?,
+            &markdown,
+            provenance.as_ref(),
+        );
+    }
+
+    timings.formatting = timings.lap();
+
+    // Compare with the existing output instead of writing it
+    if config.check {
+        if let Some((_, reason)) = content.stopped_at {
+            return Err(pdf_extract::PdfError::Processing(reason).into());
+        }
+        let converted = provenance.as_ref().map(|p| p.converted.as_str());
+        in_sync::compare(
+            &config.output_path,
+            &config.input_path,
+            &markdown,
+            converted,
+        )?;
+        return Ok(Converted {
+            files: vec![config.output_path.clone()],
+            warnings: sidecar::warnings(&doc, &content)?,
+            pages: content.page_count,
+            words,
+        });
+    }
 
     // Write output
-    markdown_gen::write_to_file(&markdown, &config.output_path)?;
+    match &site {
+        Some(site) => site.write(&config, &markdown, &content.images, provenance.as_ref())?,
+        None => markdown_gen::write_to_file(&markdown, &config.output_path)?,
+    }
+    describe(&config, &doc, &content, &markdown)?;
+    let converted = converted(&config, &doc, &content, words)?;
+    finish(&config, content, timings)?;
+    Ok(converted)
+}
+
+/// The files a conversion writes and the warnings it gave
+fn converted(
+    config: &Config,
+    doc: &pdf_extract::PdfDocument,
+    content: &pdf_extract::ExtractedContent,
+    words: usize,
+) -> Result<Converted> {
+    let mut files = vec![config.output_path.clone()];
+    let dir = config.image_output_dir();
+    files.extend(
+        content
+            .images
+            .iter()
+            .map(|image| dir.join(&image.file_name)),
+    );
+    if let Some(format) = config.sidecar {
+        files.push(sidecar::path(config, format));
+    }
+    Ok(Converted {
+        files,
+        warnings: sidecar::warnings(doc, content)?,
+        pages: content.page_count,
+        words,
+    })
+}
+
+/// Words of a written Markdown file, read a line at a time
+fn words_in(path: &Path) -> Result<usize> {
+    let mut words = 0;
+    for line in BufReader::new(File::open(path)?).lines() {
+        words += line?.split_whitespace().count();
+    }
+    Ok(words)
+}
+
+/// Write the sidecar and add the index entry of the Markdown written
+fn describe(
+    config: &Config,
+    doc: &pdf_extract::PdfDocument,
+    content: &pdf_extract::ExtractedContent,
+    markdown: &str,
+) -> Result<()> {
+    if let Some(format) = config.sidecar {
+        sidecar::write(config, format, doc, content, markdown)?;
+    }
+    if let Some(target) = &config.index {
+        index::add(config, target, doc, content, markdown)?;
+    }
+    Ok(())
+}
+
+/// Convert a PDF held in memory to Markdown, with the text and formatting
+/// options of `config`.
+///
+/// Nothing is written, so images and drawings are not extracted, and the
+/// site, Quarto and provenance options are left out.
+pub fn convert_bytes(bytes: &[u8], config: &Config) -> Result<String> {
+    let doc = pdf_extract::PdfDocument::from_bytes(bytes)?;
+    let mut options = config.extract_options();
+    options.images = false;
+    options.vector_graphics = false;
+    if let Some(path) = &config.glyph_map {
+        options.glyph_map = glyph_map::load(path)?;
+    }
+    let script = match &config.script {
+        Some(path) => Some(script::Script::load(path)?),
+        None => None,
+    };
+
+    let content = doc.extract_text_with(&options)?;
+    warn_failed_pages(&content);
+    warn_redactions(&content);
+    let mut markdown = format_markdown(config, &doc, &content, script.as_ref())?;
+    if let Some((page, _)) = &content.stopped_at {
+        markdown.push_str(&stopped_note(*page, content.page_count));
+    }
+    if let Some(command) = &config.post_hook {
+        markdown = hooks::run_hook("--post-hook", command, &markdown, &config.input_path)?;
+    }
+    if let Some((_, reason)) = content.stopped_at {
+        return Err(pdf_extract::PdfError::Processing(reason).into());
+    }
+    Ok(markdown)
+}
+
+/// Markdown of the extracted text, through the hooks and rewrites that do
+/// not depend on where it is written
+fn format_markdown(
+    config: &Config,
+    doc: &pdf_extract::PdfDocument,
+    content: &pdf_extract::ExtractedContent,
+    script: Option<&script::Script>,
+) -> Result<String> {
+    // Let the user's command clean up the text first
+    let text = match &config.pre_hook {
+        Some(command) => Cow::Owned(hooks::run_hook(
+            "--pre-hook",
+            command,
+            &content.text,
+            &config.input_path,
+        )?),
+        None => Cow::Borrowed(&content.text),
+    };
+
+    // Generate Markdown
+    let mut sanitizer =
+        sanitize::Sanitizer::new(config.sanitize, config.invisible_chars, config.normalize);
+    let text = sanitizer.clean(&text)?;
+    let mut markdown = markdown_gen::format_content_with(&text, &config.format);
+    if config.normalize_headings {
+        let title = site::document_title(config, &doc.extract_metadata()?);
+        rewrite(&mut markdown, |markdown| {
+            markdown_gen::normalize_headings(markdown, &title)
+        });
+    }
+    if config.number_headings {
+        rewrite(&mut markdown, markdown_gen::number_headings);
+    } else if config.strip_heading_numbers {
+        rewrite(&mut markdown, markdown_gen::strip_heading_numbers);
+    }
+    if let Some(style) = config.page_anchors {
+        markdown = provenance::add_page_anchors(content, &markdown, style);
+    }
+    if config.annotate_provenance {
+        markdown = provenance::annotate(content, &markdown);
+    }
+    if let Some(style) = config.footnotes {
+        let footnotes = pdf_extract::render_footnotes(style, &content.footnotes);
+        markdown.push_str(&sanitizer.clean(&footnotes)?);
+    }
+    sanitizer.finish();
+    if let Some(script) = script {
+        markdown = script.run(&markdown)?;
+    }
+    if config.chat {
+        markdown = markdown_gen::to_chat(&markdown, config.chat_section_limit);
+    }
+    if !config.replace_rules.is_empty() {
+        rewrite(&mut markdown, |markdown| {
+            replace::apply(markdown, &config.replace_rules)
+        });
+    }
+    Ok(markdown)
+}
+
+/// Warn about the pages left out of the output
+fn warn_failed_pages(content: &pdf_extract::ExtractedContent) {
+    info!("Extracted {} pages", content.page_count);
+    if content.failed_pages.is_empty() {
+        return;
+    }
+    let pages: Vec<String> = content.failed_pages.iter().map(u32::to_string).collect();
+    let pages = pages.join(", ");
+    let message = match content.failed_pages.len() {
+        1 => trf(Message::FailedPage, &[("pages", &pages)]),
+        n => trf(Message::FailedPages, &[("n", &n), ("pages", &pages)]),
+    };
+    eprintln!("{}: {}", style::warning(tr(Message::Warning)), message);
+}
+
+/// Warn about text left out because a redaction hides it, while the PDF
+/// still holds it
+fn warn_redactions(content: &pdf_extract::ExtractedContent) {
+    if content.redactions.applied > 0 {
+        info!("Found {} applied redactions", content.redactions.applied);
+    }
+    for warning in redaction_warnings(&content.redactions) {
+        eprintln!("{}: {}", style::warning(tr(Message::Warning)), warning);
+    }
+}
+
+/// The redactions whose text is still in the file, and on which pages
+pub(crate) fn redaction_warnings(redactions: &pdf_extract::Redactions) -> Vec<String> {
+    [
+        (&redactions.unapplied, Message::UnappliedRedactions),
+        (&redactions.covered, Message::CoveredText),
+    ]
+    .into_iter()
+    .filter(|(pages, _)| !pages.is_empty())
+    .map(|(pages, message)| {
+        let pages: Vec<String> = pages.iter().map(u32::to_string).collect();
+        trf(message, &[("pages", &pages.join(", "))])
+    })
+    .collect()
+}
+
+/// Note closing the output of a conversion that stopped at `page`
+pub(crate) fn stopped_note(page: u32, page_count: usize) -> String {
+    format!(
+        "\n\n> \u{26a0}\u{fe0f} {}",
+        trf(
+            Message::StoppedNote,
+            &[("page", &page), ("total", &page_count)]
+        )
+    )
+}
+
+/// Write the images once the Markdown is written, report the timings, and
+/// fail a conversion that stopped early
+fn finish(
+    config: &Config,
+    content: pdf_extract::ExtractedContent,
+    mut timings: timings::Timings,
+) -> Result<()> {
+    write_images(config, &content.images)?;
+    let writing = timings.lap();
+    timings.writing += writing;
+    if config.timings {
+        eprint!("{}", timings.report());
+    }
+
+    // Partial output is written, but the run still failed
+    if let Some((page, reason)) = content.stopped_at {
+        eprintln!(
+            "{}: {}",
+            style::warning(tr(Message::Warning)),
+            trf(Message::PartialOutput, &[("page", &page)])
+        );
+        return Err(pdf_extract::PdfError::Processing(reason).into());
+    }
 
     info!("Conversion complete");
     Ok(())
 }
 
+/// Entry point for the subcommands, which inspect a PDF without converting it
+pub fn run_command(command: Command) -> Result<()> {
+    logging::init_logging(false);
+    match command {
+        Command::Toc { input, format } => toc::run_toc(&input, format),
+        Command::Mcp => mcp::serve(&Config::command_line_defaults()),
+        Command::Metadata { input, format } => metadata::run_metadata(&input, format),
+        Command::Validate {
+            input,
+            deny_warnings,
+        } => validate::run_validate(&input, deny_warnings),
+        Command::Check {
+            input,
+            min_coverage,
+        } => check::run_check(&input, min_coverage),
+        Command::ExtractImages {
+            input,
+            output_dir,
+            image_format,
+            min_image_size,
+            skip_image_types,
+            vector_graphics,
+        } => {
+            let options = pdf_extract::ExtractOptions {
+                images: true,
+                vector_graphics,
+                image_format,
+                min_image_size,
+                skip_image_types,
+                ..Default::default()
+            };
+            images::run_extract_images(&input, &output_dir, &options)
+        }
+    }
+}
+
+/// Replace `markdown` with a rewrite of it, unless the rewrite returned it
+/// unchanged (borrowed)
+pub(crate) fn rewrite(markdown: &mut String, pass: impl for<'a> FnOnce(&'a str) -> Cow<'a, str>) {
+    if let Cow::Owned(rewritten) = pass(markdown) {
+        *markdown = rewritten;
+    }
+}
+
+/// Write extracted images next to the Markdown output
+pub(crate) fn write_images(config: &Config, images: &[pdf_extract::ExtractedImage]) -> Result<()> {
+    if images.is_empty() {
+        return Ok(());
+    }
+
+    let dir = config.image_output_dir();
+    info!("Writing {} images to {}", images.len(), dir.display());
+    for image in images {
+        markdown_gen::write_binary_file(&image.data, &dir.join(&image.file_name))?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -72,6 +646,7 @@ mod tests {
             output_path: output_path.clone(),
             verbose: false,
             dry_run: false,
+            ..Default::default()
         };
 
         let result = run(config);
@@ -83,6 +658,28 @@ mod tests {
         assert!(!content.is_empty());
     }
 
+    #[test]
+    fn test_convert_bytes_matches_file_conversion() {
+        let input_path = std::path::Path::new("tests/fixtures/sample.pdf");
+        if !input_path.exists() {
+            // Skip test if fixture doesn't exist
+            return;
+        }
+
+        let temp_dir = TempDir::new().unwrap();
+        let output_path = temp_dir.path().join("output.md");
+        let config = Config {
+            input_path: input_path.to_path_buf(),
+            output_path: output_path.clone(),
+            ..Default::default()
+        };
+        run(config.clone()).unwrap();
+
+        let markdown = convert_bytes(&fs::read(input_path).unwrap(), &Config::default()).unwrap();
+        assert_eq!(markdown, fs::read_to_string(&output_path).unwrap());
+        assert!(convert_bytes(b"not a PDF", &Config::default()).is_err());
+    }
+
     #[test]
     fn test_run_with_dry_run_mode() {
         let input_path = std::path::Path::new("tests/fixtures/sample.pdf");
@@ -96,6 +693,7 @@ mod tests {
             output_path: PathBuf::from("/tmp/output.md"),
             verbose: false,
             dry_run: true,
+            ..Default::default()
         };
 
         let result = run(config);
@@ -112,6 +710,7 @@ mod tests {
             output_path,
             verbose: false,
             dry_run: false,
+            ..Default::default()
         };
 
         let result = run(config);
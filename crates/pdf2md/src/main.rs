@@ -1,16 +1,25 @@
-use pdf2md::{cli::Args, config::Config, error::error_to_exit_code, run};
+use pdf2md::i18n::{self, Message, tr};
+use pdf2md::{cli::Args, config::Config, error::error_to_exit_code, run, run_command, style};
 use std::process;
 
 fn main() {
     // Parse command-line arguments
-    let args = Args::parse_args();
+    let mut args = Args::parse_args();
+    if let Some(language) = args.lang {
+        i18n::set_language(language);
+    }
+    if args.no_color {
+        style::disable();
+    }
 
-    // Create configuration
-    let config = Config::from_args(args);
+    // Run a subcommand, or convert with the configuration from the arguments
+    let result = match args.command.take() {
+        Some(command) => run_command(command),
+        None => run(Config::from_args(args)),
+    };
 
-    // Run application
-    if let Err(e) = run(config) {
-        eprintln!("Error: {}", e);
+    if let Err(e) = result {
+        eprintln!("{}: {}", style::error(tr(Message::Error)), e);
         let exit_code = error_to_exit_code(&e);
         process::exit(exit_code);
     }
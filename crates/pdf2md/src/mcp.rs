@@ -0,0 +1,280 @@
+//! `pdf2md mcp`: the converter as Model Context Protocol tools, served over
+//! stdin and stdout with the JSON-RPC loop of `--rpc`.
+
+use crate::config::Config;
+use crate::json::{self, Value};
+use crate::rpc::{self, Failure, INVALID_PARAMS, METHOD_NOT_FOUND, Methods, Server};
+use crate::{dry_run, metadata};
+use log::info;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+
+/// Protocol revisions understood, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Pages previewed by the `preview` tool unless asked for more or fewer
+const PREVIEW_PAGES: usize = 3;
+
+/// The tools, as listed by `tools/list`
+const TOOLS: &str = r#"[{"name":"convert","description":"Convert a PDF to Markdown. Returns the Markdown, or writes it (and any images) to `output` and returns a summary.","inputSchema":{"type":"object","properties":{"input":{"type":"string","description":"Path of the PDF file"},"output":{"type":"string","description":"Path of the Markdown file to write; leave out to get the Markdown back"}},"required":["input"]}},{"name":"preview","description":"Describe a PDF without converting it: version, pages, text coverage, images, fonts, annotations, the heading outline and the start of the first pages.","inputSchema":{"type":"object","properties":{"input":{"type":"string","description":"Path of the PDF file"},"pages":{"type":"integer","description":"Pages whose first 200 characters are shown (default 3)","minimum":0}},"required":["input"]}},{"name":"metadata","description":"Read a PDF's metadata: title, author, dates, producer, page count, PDF version, encryption and whether it has a text layer.","inputSchema":{"type":"object","properties":{"input":{"type":"string","description":"Path of the PDF file"}},"required":["input"]}}]"#;
+
+/// Serve the tools on stdin and stdout until stdin is closed
+pub fn serve(config: &Config) -> crate::Result<()> {
+    info!("Serving MCP tools on stdin and stdout");
+    rpc::serve_on(config, std::io::stdin().lock(), std::io::stdout(), &Mcp)
+}
+
+/// The methods of an MCP server offering tools
+struct Mcp;
+
+impl Methods for Mcp {
+    fn call(
+        &self,
+        server: &Server,
+        method: &str,
+        params: &Value,
+        cancelled: &AtomicBool,
+    ) -> Result<String, Failure> {
+        match method {
+            "initialize" => Ok(initialize(params)),
+            "ping" => Ok("{}".to_string()),
+            "tools/list" => Ok(format!("{{\"tools\":{}}}", TOOLS)),
+            "tools/call" => call_tool(server, params, cancelled),
+            other => Err(Failure::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method `{}`", other),
+            )),
+        }
+    }
+
+    fn cancels<'a>(&self, method: &str, params: &'a Value) -> Option<Result<&'a Value, Failure>> {
+        (method == "notifications/cancelled").then(|| {
+            params
+                .get("requestId")
+                .ok_or_else(|| Failure::new(INVALID_PARAMS, "missing `requestId`"))
+        })
+    }
+}
+
+/// The answer to `initialize`: the client's protocol revision when it is one
+/// of ours, else the newest
+fn initialize(params: &Value) -> String {
+    let version = params
+        .get("protocolVersion")
+        .and_then(Value::as_str)
+        .filter(|version| PROTOCOL_VERSIONS.contains(version))
+        .unwrap_or(PROTOCOL_VERSIONS[0]);
+    format!(
+        "{{\"protocolVersion\":{},\"capabilities\":{{\"tools\":{{}}}},\"serverInfo\":{{\"name\":\"pdf2md\",\"version\":{}}}}}",
+        json::string(version),
+        json::string(env!("CARGO_PKG_VERSION"))
+    )
+}
+
+/// Run a tool. Bad requests are protocol errors; a tool that fails answers
+/// with its error as text, flagged `isError`, so the agent can read it
+fn call_tool(server: &Server, params: &Value, cancelled: &AtomicBool) -> Result<String, Failure> {
+    let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+    let arguments = params.get("arguments").unwrap_or(&Value::Null);
+    let input = rpc::input(arguments)?;
+    let result = match name {
+        "convert" => {
+            let output = arguments
+                .get("output")
+                .and_then(Value::as_str)
+                .map(PathBuf::from);
+            server
+                .convert(&input, output, cancelled)
+                .map(|conversion| match &conversion.output {
+                    Some(path) => {
+                        let mut summary = format!(
+                            "Wrote {} ({} pages, {} images)",
+                            path.display(),
+                            conversion.pages,
+                            conversion.images
+                        );
+                        if !conversion.failed_pages.is_empty() {
+                            let pages: Vec<String> =
+                                conversion.failed_pages.iter().map(u32::to_string).collect();
+                            summary.push_str(&format!(
+                                "; pages {} could not be extracted",
+                                pages.join(", ")
+                            ));
+                        }
+                        text_result(&summary, None)
+                    }
+                    None => text_result(&conversion.markdown, None),
+                })
+        }
+        "preview" => {
+            let pages = match arguments.get("pages") {
+                Some(Value::Number(pages)) if *pages >= 0.0 => *pages as usize,
+                _ => PREVIEW_PAGES,
+            };
+            let config = Config {
+                preview_pages: pages,
+                ..server.config.clone()
+            };
+            server.open(&input).and_then(|doc| {
+                let report = dry_run::report(&doc, &config)?;
+                Ok(text_result(report.trim(), None))
+            })
+        }
+        "metadata" => server.open(&input).and_then(|doc| {
+            let fields = metadata::json_line(&input, &doc)?;
+            Ok(text_result(&fields, Some(&fields)))
+        }),
+        other => {
+            return Err(Failure::new(
+                INVALID_PARAMS,
+                format!(
+                    "unknown tool `{}` (expected convert, preview or metadata)",
+                    other
+                ),
+            ));
+        }
+    };
+    Ok(result.unwrap_or_else(|failure| error_result(&failure)))
+}
+
+/// A tool result of one text block, with the same content as an object
+/// for clients that read structured results
+fn text_result(text: &str, structured: Option<&str>) -> String {
+    let structured = structured
+        .map(|object| format!(",\"structuredContent\":{}", object))
+        .unwrap_or_default();
+    format!(
+        "{{\"content\":[{{\"type\":\"text\",\"text\":{}}}]{},\"isError\":false}}",
+        json::string(text),
+        structured
+    )
+}
+
+fn error_result(failure: &Failure) -> String {
+    format!(
+        "{{\"content\":[{{\"type\":\"text\",\"text\":{}}}],\"isError\":true}}",
+        json::string(&failure.message)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "tests/fixtures/sample.pdf";
+
+    /// The result of each request, in order of their ids
+    fn exchange(requests: &[(&str, String)]) -> Vec<Value> {
+        let lines: String = (1..)
+            .zip(requests)
+            .map(|(id, (method, params))| {
+                format!(
+                    "{{\"jsonrpc\":\"2.0\",\"id\":{},\"method\":\"{}\",\"params\":{}}}\n",
+                    id, method, params
+                )
+            })
+            .collect();
+        let mut output = Vec::new();
+        rpc::serve_on(&Config::default(), lines.as_bytes(), &mut output, &Mcp).unwrap();
+        let mut responses: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect();
+        responses.sort_by_key(|response| match response.get("id") {
+            Some(Value::Number(id)) => *id as u64,
+            _ => 0,
+        });
+        responses
+    }
+
+    fn text(result: &Value) -> &str {
+        match result.get("content") {
+            Some(Value::Array(blocks)) => blocks[0].get("text").and_then(Value::as_str).unwrap(),
+            _ => panic!("no content in {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_mcp_handshake() {
+        let responses = exchange(&[
+            (
+                "initialize",
+                r#"{"protocolVersion":"2025-03-26"}"#.to_string(),
+            ),
+            (
+                "initialize",
+                r#"{"protocolVersion":"1999-01-01"}"#.to_string(),
+            ),
+            ("tools/list", "{}".to_string()),
+            (
+                "tools/call",
+                r#"{"name":"print","arguments":{"input":"a.pdf"}}"#.to_string(),
+            ),
+        ]);
+        let version = |i: usize| {
+            responses[i]
+                .get("result")
+                .and_then(|result| result.get("protocolVersion"))
+                .and_then(Value::as_str)
+        };
+        assert_eq!(version(0), Some("2025-03-26"));
+        assert_eq!(version(1), Some(PROTOCOL_VERSIONS[0]));
+
+        let Some(Value::Array(tools)) = responses[2]
+            .get("result")
+            .and_then(|result| result.get("tools"))
+        else {
+            panic!("no tools");
+        };
+        let names: Vec<&str> = tools
+            .iter()
+            .filter_map(|tool| tool.get("name").and_then(Value::as_str))
+            .collect();
+        assert_eq!(names, ["convert", "preview", "metadata"]);
+        assert!(responses[3].get("error").is_some());
+    }
+
+    #[test]
+    fn test_mcp_tools() {
+        if !std::path::Path::new(SAMPLE).exists() {
+            // Skip test if fixture doesn't exist
+            return;
+        }
+        let call = |tool: &str| {
+            format!(
+                r#"{{"name":"{}","arguments":{{"input":"{}"}}}}"#,
+                tool, SAMPLE
+            )
+        };
+        let responses = exchange(&[
+            ("tools/call", call("convert")),
+            ("tools/call", call("preview")),
+            ("tools/call", call("metadata")),
+            (
+                "tools/call",
+                r#"{"name":"metadata","arguments":{"input":"missing.pdf"}}"#.to_string(),
+            ),
+        ]);
+        let results: Vec<&Value> = responses
+            .iter()
+            .map(|response| response.get("result").unwrap())
+            .collect();
+
+        let expected =
+            crate::convert_bytes(&std::fs::read(SAMPLE).unwrap(), &Config::default()).unwrap();
+        assert_eq!(text(results[0]), expected);
+        assert!(
+            text(results[1]).contains("Pages: 1"),
+            "{}",
+            text(results[1])
+        );
+        assert_eq!(
+            results[2]
+                .get("structuredContent")
+                .and_then(|fields| fields.get("pages")),
+            Some(&Value::Number(1.0))
+        );
+        assert_eq!(results[3].get("isError"), Some(&Value::Bool(true)));
+    }
+}
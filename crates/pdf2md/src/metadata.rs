@@ -0,0 +1,259 @@
+use crate::Result;
+use crate::cli::MetadataFormat;
+use crate::json;
+use std::path::Path;
+
+/// A value of the metadata report
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(Option<String>),
+    Number(u64),
+    Flag(bool),
+    /// Entries of their own, such as the signatures
+    Records(Vec<Vec<Field>>),
+}
+
+/// One entry of the metadata report: its key in JSON and YAML, its label in text
+#[derive(Debug, Clone, PartialEq)]
+struct Field {
+    key: &'static str,
+    label: &'static str,
+    value: Value,
+}
+
+/// Print the document information dictionary and basic facts about the file
+pub fn run_metadata(input: &Path, format: MetadataFormat) -> Result<()> {
+    pdf_extract::validate_pdf(input)?;
+    let doc = pdf_extract::PdfDocument::open(input)?;
+    let fields = report(input, &doc)?;
+
+    match format {
+        MetadataFormat::Text => print!("{}", as_text(&fields)),
+        MetadataFormat::Json => println!("{}", as_json(&fields)),
+        MetadataFormat::Yaml => print!("{}", as_yaml(&fields)),
+    }
+    Ok(())
+}
+
+/// The entries of the metadata report of an open document
+fn report(input: &Path, doc: &pdf_extract::PdfDocument) -> Result<Vec<Field>> {
+    let metadata = doc.extract_metadata()?;
+    let compatibility = doc.compatibility()?;
+
+    let text = |value: &Option<String>| Value::Text(value.clone());
+    let fields = [
+        (
+            "file",
+            "File",
+            Value::Text(Some(input.display().to_string())),
+        ),
+        (
+            "file_size",
+            "File size",
+            Value::Number(std::fs::metadata(input)?.len()),
+        ),
+        (
+            "pdf_version",
+            "PDF version",
+            Value::Text(Some(compatibility.version.clone())),
+        ),
+        ("pages", "Pages", Value::Number(metadata.page_count as u64)),
+        ("title", "Title", text(&metadata.title)),
+        ("author", "Author", text(&metadata.author)),
+        ("subject", "Subject", text(&metadata.subject)),
+        ("keywords", "Keywords", text(&metadata.keywords)),
+        ("creator", "Creator", text(&metadata.creator)),
+        ("producer", "Producer", text(&metadata.producer)),
+        ("created", "Created", text(&metadata.creation_date)),
+        ("modified", "Modified", text(&metadata.modification_date)),
+        ("encryption", "Encryption", text(&compatibility.encryption)),
+        ("pdfa", "PDF/A", text(&metadata.pdfa)),
+        ("pdfua", "PDF/UA", text(&metadata.pdfua)),
+        ("tagged", "Tagged", Value::Flag(metadata.tagged)),
+        (
+            "bookmarks",
+            "Bookmarks",
+            Value::Number(doc.outline().len() as u64),
+        ),
+        ("has_text", "Has text", Value::Flag(metadata.has_text)),
+        (
+            "signatures",
+            "Signatures",
+            Value::Records(metadata.signatures.iter().map(signature).collect()),
+        ),
+    ]
+    .map(|(key, label, value)| Field { key, label, value });
+    Ok(fields.into())
+}
+
+fn signature(signature: &pdf_extract::SignatureInfo) -> Vec<Field> {
+    [
+        ("field", "Field", Value::Text(Some(signature.field.clone()))),
+        ("signer", "Signer", Value::Text(signature.signer.clone())),
+        (
+            "signed",
+            "Signed",
+            Value::Text(signature.signing_time.clone()),
+        ),
+        (
+            "modified_after_signing",
+            "Modified after signing",
+            Value::Flag(signature.modified_after_signing),
+        ),
+    ]
+    .map(|(key, label, value)| Field { key, label, value })
+    .into()
+}
+
+/// The metadata report as one line of JSON, for `--rpc`
+pub fn json_line(input: &Path, doc: &pdf_extract::PdfDocument) -> Result<String> {
+    let members: Vec<String> = report(input, doc)?
+        .iter()
+        .map(|field| format!("{}:{}", json::string(field.key), scalar(&field.value)))
+        .collect();
+    Ok(format!("{{{}}}", members.join(",")))
+}
+
+/// `Label: value` lines, leaving out missing values; records follow their
+/// label as indented lists
+fn as_text(fields: &[Field]) -> String {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let value = match &field.value {
+                Value::Text(Some(text)) => text.clone(),
+                Value::Text(None) => return None,
+                Value::Number(number) => number.to_string(),
+                Value::Flag(flag) => if *flag { "yes" } else { "no" }.to_string(),
+                Value::Records(records) if records.is_empty() => return None,
+                Value::Records(records) => {
+                    let items: String = records
+                        .iter()
+                        .map(|record| {
+                            let lines = as_text(record).replace('\n', "\n    ");
+                            format!("\n  - {}", lines.trim_end())
+                        })
+                        .collect();
+                    return Some(format!("{}:{}\n", field.label, items));
+                }
+            };
+            Some(format!("{}: {}\n", field.label, value))
+        })
+        .collect()
+}
+
+/// One JSON object, with `null` for missing values
+fn as_json(fields: &[Field]) -> String {
+    let members: Vec<String> = fields
+        .iter()
+        .map(|field| format!("  {}: {}", json::string(field.key), scalar(&field.value)))
+        .collect();
+    format!("{{\n{}\n}}", members.join(",\n"))
+}
+
+/// A YAML mapping, with `null` for missing values
+fn as_yaml(fields: &[Field]) -> String {
+    fields
+        .iter()
+        .map(|field| format!("{}: {}\n", field.key, scalar(&field.value)))
+        .collect()
+}
+
+/// A value as JSON on one line, which is also valid YAML
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Text(Some(text)) => json::string(text),
+        Value::Text(None) => "null".to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Flag(flag) => flag.to_string(),
+        Value::Records(records) => {
+            let records: Vec<String> = records
+                .iter()
+                .map(|record| {
+                    let members: Vec<String> = record
+                        .iter()
+                        .map(|field| {
+                            format!("{}: {}", json::string(field.key), scalar(&field.value))
+                        })
+                        .collect();
+                    format!("{{{}}}", members.join(", "))
+                })
+                .collect();
+            format!("[{}]", records.join(", "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields() -> Vec<Field> {
+        vec![
+            Field {
+                key: "title",
+                label: "Title",
+                value: Value::Text(Some("Q3: \"Results\"".to_string())),
+            },
+            Field {
+                key: "author",
+                label: "Author",
+                value: Value::Text(None),
+            },
+            Field {
+                key: "pages",
+                label: "Pages",
+                value: Value::Number(12),
+            },
+            Field {
+                key: "has_text",
+                label: "Has text",
+                value: Value::Flag(true),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_formats() {
+        assert_eq!(
+            as_text(&fields()),
+            "Title: Q3: \"Results\"\nPages: 12\nHas text: yes\n"
+        );
+        assert_eq!(
+            as_json(&fields()),
+            "{\n  \"title\": \"Q3: \\\"Results\\\"\",\n  \"author\": null,\n  \"pages\": 12,\n  \"has_text\": true\n}"
+        );
+        assert_eq!(
+            as_yaml(&fields()),
+            "title: \"Q3: \\\"Results\\\"\"\nauthor: null\npages: 12\nhas_text: true\n"
+        );
+    }
+
+    #[test]
+    fn test_records() {
+        let signatures = vec![Field {
+            key: "signatures",
+            label: "Signatures",
+            value: Value::Records(vec![signature(&pdf_extract::SignatureInfo {
+                field: "Approval".to_string(),
+                signer: Some("Jane Doe".to_string()),
+                signing_time: None,
+                modified_after_signing: true,
+            })]),
+        }];
+        assert_eq!(
+            as_text(&signatures),
+            "Signatures:\n  - Field: Approval\n    Signer: Jane Doe\n    Modified after signing: yes\n"
+        );
+        assert_eq!(
+            as_yaml(&signatures),
+            "signatures: [{\"field\": \"Approval\", \"signer\": \"Jane Doe\", \"signed\": null, \"modified_after_signing\": true}]\n"
+        );
+        let none = vec![Field {
+            value: Value::Records(Vec::new()),
+            ..signatures[0].clone()
+        }];
+        assert_eq!(as_text(&none), "");
+        assert_eq!(as_json(&none), "{\n  \"signatures\": []\n}");
+    }
+}
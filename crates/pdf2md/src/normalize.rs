@@ -0,0 +1,189 @@
+//! `--normalize`: Unicode normalization of the extracted text (NFC or
+//! NFKC), so that strings that look the same compare equal downstream
+//!
+//! The character data is generated into `normalize/tables.rs` by
+//! `scripts/generate_unicode_tables.py`.
+
+mod tables;
+
+use crate::cli::Normalization;
+use std::borrow::Cow;
+use tables::{CANONICAL, COMBINING_CLASSES, COMPATIBILITY, COMPOSITIONS};
+
+// Hangul syllables are composed of jamo arithmetically
+const S_BASE: u32 = 0xAC00;
+const L_BASE: u32 = 0x1100;
+const V_BASE: u32 = 0x1161;
+const T_BASE: u32 = 0x11A7;
+const L_COUNT: u32 = 19;
+const V_COUNT: u32 = 21;
+const T_COUNT: u32 = 28;
+const N_COUNT: u32 = V_COUNT * T_COUNT;
+const S_COUNT: u32 = L_COUNT * N_COUNT;
+
+/// The text in the normalization form; ASCII text is returned as it is
+pub fn normalize(text: &str, form: Normalization) -> Cow<'_, str> {
+    let compatibility = match form {
+        Normalization::None => return Cow::Borrowed(text),
+        Normalization::Nfc => false,
+        Normalization::Nfkc => true,
+    };
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let composed: String = compose(decompose(text, compatibility))
+        .into_iter()
+        .collect();
+    if composed == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(composed)
+    }
+}
+
+/// Canonical combining class; 0 for starters
+fn combining_class(c: char) -> u8 {
+    COMBINING_CLASSES
+        .binary_search_by(|&(first, last, _)| {
+            if last < c {
+                std::cmp::Ordering::Less
+            } else if first > c {
+                std::cmp::Ordering::Greater
+            } else {
+                std::cmp::Ordering::Equal
+            }
+        })
+        .map_or(0, |i| COMBINING_CLASSES[i].2)
+}
+
+fn lookup(table: &'static [(char, &'static str)], c: char) -> Option<&'static str> {
+    table
+        .binary_search_by_key(&c, |&(from, _)| from)
+        .ok()
+        .map(|i| table[i].1)
+}
+
+/// Full decomposition, canonical or compatibility, in canonical order
+fn decompose(text: &str, compatibility: bool) -> Vec<char> {
+    let mut chars = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        let s = (c as u32).wrapping_sub(S_BASE);
+        if s < S_COUNT {
+            chars.push(char::from_u32(L_BASE + s / N_COUNT).expect("jamo"));
+            chars.push(char::from_u32(V_BASE + (s % N_COUNT) / T_COUNT).expect("jamo"));
+            if !s.is_multiple_of(T_COUNT) {
+                chars.push(char::from_u32(T_BASE + s % T_COUNT).expect("jamo"));
+            }
+            continue;
+        }
+        let mapping = compatibility
+            .then(|| lookup(COMPATIBILITY, c))
+            .flatten()
+            .or_else(|| lookup(CANONICAL, c));
+        match mapping {
+            Some(mapping) => chars.extend(mapping.chars()),
+            None => chars.push(c),
+        }
+    }
+
+    // Canonical ordering: each run of non-starters sorted by class, stably
+    let mut start = 0;
+    while start < chars.len() {
+        if combining_class(chars[start]) == 0 {
+            start += 1;
+            continue;
+        }
+        let end = chars[start..]
+            .iter()
+            .position(|&c| combining_class(c) == 0)
+            .map_or(chars.len(), |n| start + n);
+        chars[start..end].sort_by_key(|&c| combining_class(c));
+        start = end;
+    }
+    chars
+}
+
+/// Canonical composition of decomposed text
+fn compose(chars: Vec<char>) -> Vec<char> {
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    let mut starter: Option<usize> = None;
+    // Class of the last character since the starter, if any
+    let mut last_class: Option<u8> = None;
+    for c in chars {
+        let class = combining_class(c);
+        if let Some(at) = starter {
+            let blocked = last_class.is_some_and(|last| last == 0 || last >= class);
+            if !blocked && let Some(composite) = compose_pair(out[at], c) {
+                out[at] = composite;
+                continue;
+            }
+        }
+        if class == 0 {
+            starter = Some(out.len());
+            last_class = None;
+        } else {
+            last_class = Some(class);
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// The primary composite of two characters, if they have one
+fn compose_pair(first: char, second: char) -> Option<char> {
+    let (a, b) = (first as u32, second as u32);
+    // Leading and vowel jamo, then a syllable and trailing jamo
+    if (L_BASE..L_BASE + L_COUNT).contains(&a) && (V_BASE..V_BASE + V_COUNT).contains(&b) {
+        return char::from_u32(S_BASE + ((a - L_BASE) * V_COUNT + (b - V_BASE)) * T_COUNT);
+    }
+    if (S_BASE..S_BASE + S_COUNT).contains(&a)
+        && (a - S_BASE).is_multiple_of(T_COUNT)
+        && (T_BASE + 1..T_BASE + T_COUNT).contains(&b)
+    {
+        return char::from_u32(a + (b - T_BASE));
+    }
+    COMPOSITIONS
+        .binary_search_by_key(&(first, second), |&(a, b, _)| (a, b))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfc() {
+        let nfc = |text| normalize(text, Normalization::Nfc).into_owned();
+        // "café" decomposed, and precomposed
+        assert_eq!(nfc("cafe\u{301}"), "caf\u{e9}");
+        assert!(matches!(
+            normalize("caf\u{e9}", Normalization::Nfc),
+            Cow::Borrowed(_)
+        ));
+        // Marks put in canonical order before composing: dot below first
+        assert_eq!(nfc("q\u{307}\u{323}"), "q\u{323}\u{307}");
+        assert_eq!(nfc("s\u{307}\u{323}"), "\u{1e69}");
+        // Singletons and excluded composites stay decomposed
+        assert_eq!(nfc("\u{212b}"), "\u{c5}");
+        assert_eq!(nfc("\u{fb2c}"), "\u{5e9}\u{5bc}\u{5c1}");
+        // Hangul jamo compose into syllables
+        assert_eq!(nfc("\u{1100}\u{1161}\u{11a8}"), "\u{ac01}");
+        // Compatibility characters are kept
+        assert_eq!(nfc("\u{fb01}"), "\u{fb01}");
+    }
+
+    #[test]
+    fn test_nfkc() {
+        let nfkc = |text| normalize(text, Normalization::Nfkc).into_owned();
+        assert_eq!(nfkc("\u{fb01}nance"), "finance");
+        assert_eq!(nfkc("x\u{b2} \u{2460}"), "x2 1");
+        assert_eq!(nfkc("\u{ff21}\u{3131}"), "A\u{1100}");
+        assert_eq!(nfkc("\u{1e9b}\u{323}"), "\u{1e69}");
+    }
+
+    #[test]
+    fn test_none() {
+        assert_eq!(normalize("cafe\u{301}", Normalization::None), "cafe\u{301}");
+    }
+}
@@ -0,0 +1,218 @@
+//! `--notify-url` and `--notify-cmd`: tell a chat channel, queue or script
+//! that a conversion has finished, with a JSON summary of how it went
+
+use crate::alt_text::shell;
+use crate::config::Config;
+use crate::error::error_to_exit_code;
+use crate::i18n::{Message, tr};
+use crate::json::Value;
+use crate::s3::curl_quote;
+use crate::{Pdf2MdError, Result, style};
+use log::info;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Seconds curl may take to deliver a notification
+const TIMEOUT_SECONDS: u32 = 30;
+
+/// Where to send word of a finished conversion
+#[derive(Debug, Clone)]
+pub struct Notify {
+    url: Option<String>,
+    command: Option<String>,
+    input: PathBuf,
+    output: PathBuf,
+}
+
+impl Notify {
+    /// The notifications `config` asks for, if any
+    pub fn from_config(config: &Config) -> Option<Self> {
+        if config.notify_url.is_none() && config.notify_cmd.is_none() {
+            return None;
+        }
+        Some(Self {
+            url: config.notify_url.clone(),
+            command: config.notify_cmd.clone(),
+            input: config.input_path.clone(),
+            output: config.output_path.clone(),
+        })
+    }
+
+    /// Send the outcome of a run: the number of PDFs converted, or the error.
+    ///
+    /// A notification that cannot be delivered is only warned about: the
+    /// conversion itself is done either way.
+    pub fn send(&self, result: &Result<usize>, elapsed: Duration) {
+        let payload = self.payload(result, elapsed).to_json();
+        if let Some(url) = &self.url
+            && let Err(e) = post(url, &payload)
+        {
+            warn("--notify-url", &e);
+        }
+        if let Some(command) = &self.command
+            && let Err(e) = run_command(command, &payload, &self.input)
+        {
+            warn("--notify-cmd", &e);
+        }
+    }
+
+    fn payload(&self, result: &Result<usize>, elapsed: Duration) -> Value {
+        let path = |path: &Path| Value::String(path.display().to_string());
+        let (status, files, failed, error) = match result {
+            Ok(files) => ("succeeded", *files, 0, Value::Null),
+            Err(e) => {
+                let (files, failed) = match e {
+                    Pdf2MdError::Batch { failed, total } => (*total, *failed),
+                    _ => (1, 1),
+                };
+                ("failed", files, failed, Value::String(e.to_string()))
+            }
+        };
+        Value::Object(vec![
+            ("event".to_string(), Value::String("finished".to_string())),
+            ("status".to_string(), Value::String(status.to_string())),
+            (
+                "exit_code".to_string(),
+                Value::Number(result.as_ref().map_or_else(error_to_exit_code, |_| 0) as f64),
+            ),
+            ("input".to_string(), path(&self.input)),
+            ("output".to_string(), path(&self.output)),
+            ("files".to_string(), Value::Number(files as f64)),
+            ("failed".to_string(), Value::Number(failed as f64)),
+            (
+                "seconds".to_string(),
+                Value::Number((elapsed.as_secs_f64() * 1000.0).round() / 1000.0),
+            ),
+            ("error".to_string(), error),
+            (
+                "converter".to_string(),
+                Value::String(format!("pdf2md {}", env!("CARGO_PKG_VERSION"))),
+            ),
+        ])
+    }
+}
+
+fn warn(option: &str, error: &std::io::Error) {
+    eprintln!(
+        "{}: {} {}",
+        style::warning(tr(Message::Warning)),
+        option,
+        error
+    );
+}
+
+/// POST the payload with curl. The URL, which often carries a token, is
+/// passed on stdin with the rest of the configuration so it never appears in
+/// the process list.
+fn post(url: &str, payload: &str) -> std::io::Result<()> {
+    info!("Posting the notification");
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--fail", "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| std::io::Error::other(format!("curl could not be started: {}", e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(curl_config(url, payload).as_bytes())?;
+    drop(stdin);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr);
+        return Err(std::io::Error::other(
+            message.trim().trim_start_matches("curl: ").to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn curl_config(url: &str, payload: &str) -> String {
+    [
+        ("url", url),
+        ("header", "content-type: application/json"),
+        ("data-binary", payload),
+        ("max-time", &TIMEOUT_SECONDS.to_string()),
+    ]
+    .into_iter()
+    .map(|(name, value)| format!("{} = {}\n", name, curl_quote(value)))
+    .collect()
+}
+
+/// Run the user's command through the platform shell with the payload on
+/// stdin and `PDF2MD_INPUT` set, as for the hooks
+fn run_command(command: &str, payload: &str, input: &Path) -> std::io::Result<()> {
+    info!("Running --notify-cmd `{}`", command);
+    let mut child = shell(command)
+        .env("PDF2MD_INPUT", input)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| std::io::Error::other(format!("`{}` could not be started: {}", command, e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The command may not read the payload at all; a closed pipe is fine
+        let _ = stdin.write_all(payload.as_bytes());
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        return Err(std::io::Error::other(format!(
+            "`{}` exited with {}",
+            command, status
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notify() -> Notify {
+        Notify {
+            url: None,
+            command: None,
+            input: PathBuf::from("in"),
+            output: PathBuf::from("out"),
+        }
+    }
+
+    #[test]
+    fn test_payload() {
+        assert_eq!(
+            notify()
+                .payload(&Ok(3), Duration::from_millis(1500))
+                .to_json(),
+            format!(
+                "{{\"event\":\"finished\",\"status\":\"succeeded\",\"exit_code\":0,\
+                 \"input\":\"in\",\"output\":\"out\",\"files\":3,\"failed\":0,\
+                 \"seconds\":1.5,\"error\":null,\"converter\":\"pdf2md {}\"}}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        let failed = notify().payload(
+            &Err(Pdf2MdError::Batch {
+                failed: 3,
+                total: 3,
+            }),
+            Duration::ZERO,
+        );
+        assert_eq!(failed.get("exit_code"), Some(&Value::Number(7.0)));
+        assert_eq!(failed.get("files"), Some(&Value::Number(3.0)));
+        assert_eq!(
+            failed.get("error").and_then(Value::as_str),
+            Some("3 of 3 files failed")
+        );
+    }
+
+    #[test]
+    fn test_curl_config() {
+        assert_eq!(
+            curl_config("https://hooks.example.com/T0/B0", "{\"a\":\"b\"}"),
+            "url = \"https://hooks.example.com/T0/B0\"\n\
+             header = \"content-type: application/json\"\n\
+             data-binary = \"{\\\"a\\\":\\\"b\\\"}\"\n\
+             max-time = \"30\"\n"
+        );
+    }
+}
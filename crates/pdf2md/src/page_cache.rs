@@ -0,0 +1,60 @@
+use log::{debug, warn};
+use pdf_extract::PageCache;
+use std::path::{Path, PathBuf};
+
+/// Page text kept as one file per page in a directory.
+///
+/// Files are named after the page's key, so the directory can be shared by
+/// many documents and by runs with different options; entries nothing uses
+/// any more are left for the user to delete.
+#[derive(Debug)]
+pub struct DirPageCache {
+    dir: PathBuf,
+}
+
+impl DirPageCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.md", key))
+    }
+
+    fn write(&self, path: &Path, text: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        // Written beside the entry and renamed, so a reader never sees half a page
+        let partial = path.with_extension(format!("{}.tmp", std::process::id()));
+        std::fs::write(&partial, text)?;
+        std::fs::rename(&partial, path)
+    }
+}
+
+impl PageCache for DirPageCache {
+    fn get(&self, key: &str) -> Option<String> {
+        std::fs::read_to_string(self.path(key)).ok()
+    }
+
+    fn put(&self, key: &str, text: &str) {
+        let path = self.path(key);
+        match self.write(&path, text) {
+            Ok(()) => debug!("Cached page text in {}", path.display()),
+            Err(e) => warn!("Failed to cache page text in {}: {}", path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_page_cache_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = DirPageCache::new(dir.path().join("pages"));
+        assert_eq!(cache.get("abc"), None);
+        cache.put("abc", "Page text\n");
+        assert_eq!(cache.get("abc").as_deref(), Some("Page text\n"));
+        assert!(dir.path().join("pages/abc.md").is_file());
+    }
+}
@@ -0,0 +1,1246 @@
+//! `--format pandoc-json`: the document as Pandoc's JSON AST, read from the
+//! Markdown pdf2md writes so `pandoc -f json` can take it to any format
+
+use crate::config::Config;
+use crate::json;
+use crate::provenance::Provenance;
+use crate::site::document_title;
+use chrono::DateTime;
+use std::collections::HashMap;
+
+/// Version of the pandoc-types AST written (Pandoc 3.1.8 and later)
+const API_VERSION: &str = "[1,23,1]";
+
+/// The Markdown as a Pandoc document, with the title, author and date of the
+/// PDF (and the provenance, if recorded) as metadata
+pub fn to_pandoc_json(
+    config: &Config,
+    metadata: &pdf_extract::PdfMetadata,
+    markdown: &str,
+    provenance: Option<&Provenance>,
+) -> String {
+    let mut meta = vec![(
+        "title".to_string(),
+        MetaValue::Inlines(document_title(config, metadata)),
+    )];
+    if let Some(author) = metadata
+        .author
+        .as_deref()
+        .map(str::trim)
+        .filter(|author| !author.is_empty())
+    {
+        meta.push(("author".to_string(), MetaValue::Inlines(author.to_string())));
+    }
+    if let Some(date) = metadata
+        .creation_date
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+    {
+        let date = date.format("%Y-%m-%d").to_string();
+        meta.push(("date".to_string(), MetaValue::Inlines(date)));
+    }
+    if let Some(provenance) = provenance {
+        let field = |name: &str, value: String| (name.to_string(), MetaValue::String(value));
+        meta.push((
+            "provenance".to_string(),
+            MetaValue::Map(vec![
+                field("source", provenance.source.clone()),
+                field("size", provenance.size.to_string()),
+                field("sha256", provenance.sha256.clone()),
+                field("converter", provenance.converter.clone()),
+                field("converted", provenance.converted.clone()),
+            ]),
+        ));
+    }
+    document(markdown, meta)
+}
+
+/// A Pandoc document of `markdown`; scalar fields of a leading YAML front
+/// matter block join `meta`, without replacing its fields
+fn document(markdown: &str, mut meta: Vec<(String, MetaValue)>) -> String {
+    let (front_matter, body) = split_front_matter(markdown);
+    for (key, value) in front_matter {
+        if !meta.iter().any(|(name, _)| *name == key) {
+            meta.push((key, MetaValue::Inlines(value)));
+        }
+    }
+
+    let lines: Vec<&str> = body.lines().collect();
+    let notes = footnote_definitions(&lines);
+    let mut parser = Parser {
+        notes: &notes,
+        identifiers: HashMap::new(),
+        open: Vec::new(),
+    };
+    let blocks = parser.blocks(&lines);
+
+    let mut out = format!("{{\"pandoc-api-version\":{},\"meta\":{{", API_VERSION);
+    for (i, (key, value)) in meta.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&json::string(key));
+        out.push(':');
+        value.write(&mut out, &parser);
+    }
+    out.push_str("},\"blocks\":");
+    write_blocks(&mut out, &blocks);
+    out.push_str("}\n");
+    out
+}
+
+enum MetaValue {
+    /// Text read as Markdown
+    Inlines(String),
+    String(String),
+    Map(Vec<(String, MetaValue)>),
+}
+
+impl MetaValue {
+    fn write(&self, out: &mut String, parser: &Parser) {
+        match self {
+            MetaValue::Inlines(text) => {
+                out.push_str("{\"t\":\"MetaInlines\",\"c\":");
+                write_inlines(out, &parser.inlines(text));
+                out.push('}');
+            }
+            MetaValue::String(text) => {
+                out.push_str("{\"t\":\"MetaString\",\"c\":");
+                out.push_str(&json::string(text));
+                out.push('}');
+            }
+            MetaValue::Map(fields) => {
+                out.push_str("{\"t\":\"MetaMap\",\"c\":{");
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    out.push_str(&json::string(key));
+                    out.push(':');
+                    value.write(out, parser);
+                }
+                out.push_str("}}");
+            }
+        }
+    }
+}
+
+/// `key: value` lines of a leading `---` block, and the Markdown after it
+fn split_front_matter(markdown: &str) -> (Vec<(String, String)>, &str) {
+    let Some(rest) = markdown.strip_prefix("---\n") else {
+        return (Vec::new(), markdown);
+    };
+    let (yaml, body) = match rest.find("\n---\n") {
+        Some(end) => (&rest[..end], &rest[end + 5..]),
+        None => match rest.strip_suffix("\n---") {
+            Some(yaml) => (yaml, ""),
+            None => return (Vec::new(), markdown),
+        },
+    };
+    let fields = yaml
+        .lines()
+        .filter(|line| !line.starts_with([' ', '\t', '#']))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .map(|value| value.replace("\\\"", "\"").replace("\\\\", "\\"))
+                .unwrap_or_else(|| value.to_string());
+            (key.trim().to_string(), value)
+        })
+        .filter(|(_, value)| !value.is_empty())
+        .collect();
+    (fields, body)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Plain(Vec<Inline>),
+    Para(Vec<Inline>),
+    Header(usize, String, Vec<Inline>),
+    /// Code with its language
+    Code(String, String),
+    Quote(Vec<Block>),
+    BulletList(Vec<Vec<Block>>),
+    OrderedList(u64, Vec<Vec<Block>>),
+    DefinitionList(Vec<(Vec<Inline>, Vec<Vec<Block>>)>),
+    HorizontalRule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Inline {
+    Str(String),
+    Space,
+    SoftBreak,
+    LineBreak,
+    Emph(Vec<Inline>),
+    Strong(Vec<Inline>),
+    Strikeout(Vec<Inline>),
+    Underline(Vec<Inline>),
+    /// A `<span>` with its `style`
+    Span(String, Vec<Inline>),
+    Code(String),
+    Math {
+        display: bool,
+        tex: String,
+    },
+    Link(Vec<Inline>, String),
+    Image(Vec<Inline>, String),
+    Note(Vec<Block>),
+    RawHtml(String),
+}
+
+/// `[^label]: text` definitions, with their indented continuation lines
+fn footnote_definitions(lines: &[&str]) -> HashMap<String, String> {
+    let mut notes = HashMap::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let Some((label, text)) = footnote_definition(lines[i]) else {
+            i += 1;
+            continue;
+        };
+        let mut text = text.to_string();
+        i += 1;
+        while i < lines.len() && lines[i].starts_with("    ") {
+            text.push('\n');
+            text.push_str(lines[i].trim_start());
+            i += 1;
+        }
+        notes.insert(label.to_string(), text);
+    }
+    notes
+}
+
+fn footnote_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.strip_prefix("[^")?;
+    let (label, text) = rest.split_once("]:")?;
+    (!label.is_empty() && !label.contains(char::is_whitespace)).then_some((label, text.trim()))
+}
+
+struct Parser<'a> {
+    notes: &'a HashMap<String, String>,
+    /// Header identifiers given so far, with the count of each
+    identifiers: HashMap<String, usize>,
+    /// Labels of the notes being expanded; a reference back to one is kept
+    /// as text, since notes do not nest
+    open: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn blocks(&mut self, lines: &[&str]) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        let mut i = 0;
+        while i < lines.len() {
+            let line = lines[i];
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() {
+                i += 1;
+            } else if footnote_definition(line).is_some() {
+                i += 1;
+                while i < lines.len() && lines[i].starts_with("    ") {
+                    i += 1;
+                }
+            } else if let Some(fence) = fence(trimmed) {
+                let info = trimmed[fence.len()..].trim();
+                let language = info.split_whitespace().next().unwrap_or("");
+                let language = language.trim_matches(|c| c == '{' || c == '}');
+                let mut code = Vec::new();
+                i += 1;
+                while i < lines.len() && !lines[i].trim_start().starts_with(fence) {
+                    code.push(lines[i]);
+                    i += 1;
+                }
+                i += 1;
+                blocks.push(Block::Code(language.to_string(), code.join("\n")));
+            } else if let Some((level, text)) = atx_heading(trimmed) {
+                blocks.push(self.header(level, text));
+                i += 1;
+            } else if is_rule(trimmed) {
+                blocks.push(Block::HorizontalRule);
+                i += 1;
+            } else if trimmed.starts_with('>') {
+                let mut quoted = Vec::new();
+                while i < lines.len() && lines[i].trim_start().starts_with('>') {
+                    let text = &lines[i].trim_start()[1..];
+                    quoted.push(text.strip_prefix(' ').unwrap_or(text));
+                    i += 1;
+                }
+                blocks.push(Block::Quote(self.blocks(&quoted)));
+            } else if list_marker(line).is_some() {
+                i = self.list(lines, i, &mut blocks);
+            } else if lines.get(i + 1).is_some_and(|next| next.starts_with(": ")) {
+                i = self.definitions(lines, i, &mut blocks);
+            } else {
+                let start = i;
+                i += 1;
+                while i < lines.len() && !interrupts(lines[i]) {
+                    i += 1;
+                }
+                // A paragraph underlined with `===` or `---` is a heading
+                if i < lines.len()
+                    && let Some(level) = setext_level(lines[i])
+                {
+                    blocks.push(self.header(level, &lines[start..i].join(" ")));
+                    i += 1;
+                    continue;
+                }
+                blocks.push(Block::Para(self.inlines(&lines[start..i].join("\n"))));
+            }
+        }
+        blocks
+    }
+
+    fn header(&mut self, level: usize, text: &str) -> Block {
+        let inlines = self.inlines(text.trim());
+        let id = identifier(&stringify(&inlines));
+        let count = self.identifiers.entry(id.clone()).or_insert(0);
+        let id = if *count == 0 {
+            id
+        } else {
+            format!("{}-{}", id, count)
+        };
+        *count += 1;
+        Block::Header(level, id, inlines)
+    }
+
+    /// A list starting at `lines[start]`; returns the line after it
+    fn list(&mut self, lines: &[&str], start: usize, blocks: &mut Vec<Block>) -> usize {
+        let (first, _) = list_marker(lines[start]).expect("a list item");
+        let ordered = first.number;
+        let mut items: Vec<Vec<String>> = Vec::new();
+        let mut loose = false;
+        let mut i = start;
+        while i < lines.len() {
+            let line = lines[i];
+            if let Some((marker, text)) = list_marker(line) {
+                if marker.number.is_some() != ordered.is_some() {
+                    break;
+                }
+                items.push(vec![text.to_string()]);
+                i += 1;
+                continue;
+            }
+            let item = items.last_mut().expect("a list item");
+            if line.trim().is_empty() {
+                // A blank line ends the list unless an indented line or
+                // another item follows
+                let next = lines[i + 1..].iter().find(|line| !line.trim().is_empty());
+                match next {
+                    Some(next) if next.starts_with("  ") || next.starts_with('\t') => {
+                        item.push(String::new());
+                        loose = true;
+                    }
+                    Some(next)
+                        if list_marker(next).is_some_and(|(marker, _)| {
+                            marker.number.is_some() == ordered.is_some()
+                        }) =>
+                    {
+                        loose = true;
+                    }
+                    _ => break,
+                }
+            } else if line.starts_with("  ") || line.starts_with('\t') {
+                item.push(dedent(line).to_string());
+            } else if interrupts(line) {
+                break;
+            } else {
+                item.push(line.to_string());
+            }
+            i += 1;
+        }
+
+        let items = items
+            .iter()
+            .map(|item| {
+                let lines: Vec<&str> = item.iter().map(String::as_str).collect();
+                self.blocks(&lines)
+                    .into_iter()
+                    .map(|block| match block {
+                        Block::Para(inlines) if !loose => Block::Plain(inlines),
+                        block => block,
+                    })
+                    .collect()
+            })
+            .collect();
+        blocks.push(match ordered {
+            Some(number) => Block::OrderedList(number, items),
+            None => Block::BulletList(items),
+        });
+        i
+    }
+
+    /// Terms each followed by `: definition` lines; returns the line after them
+    fn definitions(&mut self, lines: &[&str], start: usize, blocks: &mut Vec<Block>) -> usize {
+        let mut items = Vec::new();
+        let mut i = start;
+        while i + 1 < lines.len() && lines[i + 1].starts_with(": ") {
+            let term = self.inlines(lines[i].trim());
+            let mut definitions = Vec::new();
+            i += 1;
+            while i < lines.len() && lines[i].starts_with(": ") {
+                let mut text = vec![&lines[i][2..]];
+                i += 1;
+                while i < lines.len() && !lines[i].trim().is_empty() && !lines[i].starts_with(": ")
+                {
+                    text.push(lines[i].trim_start());
+                    i += 1;
+                }
+                definitions.push(vec![Block::Plain(self.inlines(&text.join("\n")))]);
+            }
+            items.push((term, definitions));
+            while i < lines.len() && lines[i].trim().is_empty() {
+                i += 1;
+            }
+        }
+        blocks.push(Block::DefinitionList(items));
+        i
+    }
+
+    fn inlines(&self, text: &str) -> Vec<Inline> {
+        let mut inlines = Inlines::default();
+        let chars: Vec<char> = text.chars().collect();
+        self.parse_inlines(&chars, &mut inlines);
+        inlines.0
+    }
+
+    fn parse_inlines(&self, chars: &[char], out: &mut Inlines) {
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            let next = chars.get(i + 1).copied();
+            match c {
+                '\\' if next == Some('\n') => {
+                    out.push(Inline::LineBreak);
+                    i += 2;
+                }
+                '\\' if next.is_some_and(|next| next.is_ascii_punctuation()) => {
+                    out.push_char(chars[i + 1]);
+                    i += 2;
+                }
+                ' ' | '\t' => {
+                    let end = run_end(chars, i, |c| c == ' ' || c == '\t');
+                    if chars.get(end) == Some(&'\n') {
+                        out.push(if end - i >= 2 && c == ' ' {
+                            Inline::LineBreak
+                        } else {
+                            Inline::SoftBreak
+                        });
+                        i = end + 1;
+                    } else {
+                        out.push(Inline::Space);
+                        i = end;
+                    }
+                }
+                '\n' => {
+                    out.push(Inline::SoftBreak);
+                    i += 1;
+                }
+                '`' => {
+                    let end = run_end(chars, i, |c| c == '`');
+                    let ticks = end - i;
+                    match find_run(chars, end, '`', ticks) {
+                        Some(close) => {
+                            let code: String = chars[end..close].iter().collect();
+                            let code = code.replace('\n', " ");
+                            let code =
+                                match code.strip_prefix(' ').and_then(|c| c.strip_suffix(' ')) {
+                                    Some(inner) if !inner.trim().is_empty() => inner.to_string(),
+                                    _ => code,
+                                };
+                            out.push(Inline::Code(code));
+                            i = close + ticks;
+                        }
+                        None => {
+                            out.push_str(&chars[i..end].iter().collect::<String>());
+                            i = end;
+                        }
+                    }
+                }
+                '$' => match math(chars, i) {
+                    Some((inline, end)) => {
+                        out.push(inline);
+                        i = end;
+                    }
+                    None => {
+                        out.push_char('$');
+                        i += 1;
+                    }
+                },
+                '!' if next == Some('[') => match self.link(chars, i + 1) {
+                    Some((text, url, end)) => {
+                        out.push(Inline::Image(text, url));
+                        i = end;
+                    }
+                    None => {
+                        out.push_char('!');
+                        i += 1;
+                    }
+                },
+                '^' if next == Some('[') => match closing_bracket(chars, i + 1) {
+                    Some(close) => {
+                        let note = self.inlines(&chars[i + 2..close].iter().collect::<String>());
+                        out.push(Inline::Note(vec![Block::Para(note)]));
+                        i = close + 1;
+                    }
+                    None => {
+                        out.push_char('^');
+                        i += 1;
+                    }
+                },
+                '[' => {
+                    if next == Some('^')
+                        && let Some(close) = closing_bracket(chars, i)
+                    {
+                        let label: String = chars[i + 2..close].iter().collect();
+                        if let Some(text) = self.notes.get(&label)
+                            && !self.open.contains(&label)
+                        {
+                            let lines: Vec<&str> = text.lines().collect();
+                            let mut parser = Parser {
+                                notes: self.notes,
+                                identifiers: HashMap::new(),
+                                open: [self.open.as_slice(), &[label]].concat(),
+                            };
+                            out.push(Inline::Note(parser.blocks(&lines)));
+                            i = close + 1;
+                            continue;
+                        }
+                    }
+                    match self.link(chars, i) {
+                        Some((text, url, end)) => {
+                            out.push(Inline::Link(text, url));
+                            i = end;
+                        }
+                        None => {
+                            out.push_char('[');
+                            i += 1;
+                        }
+                    }
+                }
+                '<' => match self.html(chars, i) {
+                    Some((inline, end)) => {
+                        out.push(inline);
+                        i = end;
+                    }
+                    None => {
+                        out.push_char('<');
+                        i += 1;
+                    }
+                },
+                '*' | '_' | '~' => {
+                    let end = run_end(chars, i, |other| other == c);
+                    let run = end - i;
+                    let word_inside = c == '_' && i > 0 && chars[i - 1].is_alphanumeric();
+                    let opens = chars.get(end).is_some_and(|next| !next.is_whitespace())
+                        && !word_inside
+                        && (c != '~' || run == 2)
+                        && run <= 3;
+                    let close = opens.then(|| find_closing(chars, end, c, run)).flatten();
+                    match close {
+                        Some(close) => {
+                            let mut inner = Inlines::default();
+                            self.parse_inlines(&chars[end..close], &mut inner);
+                            out.push(match (c, run) {
+                                ('~', _) => Inline::Strikeout(inner.0),
+                                (_, 1) => Inline::Emph(inner.0),
+                                (_, 2) => Inline::Strong(inner.0),
+                                _ => Inline::Emph(vec![Inline::Strong(inner.0)]),
+                            });
+                            i = close + run;
+                        }
+                        None => {
+                            out.push_str(&chars[i..end].iter().collect::<String>());
+                            i = end;
+                        }
+                    }
+                }
+                c => {
+                    out.push_char(c);
+                    i += 1;
+                }
+            }
+        }
+    }
+
+    /// `[text](url)` starting at the bracket; the text, the URL and the
+    /// index after the link
+    fn link(&self, chars: &[char], open: usize) -> Option<(Vec<Inline>, String, usize)> {
+        let close = closing_bracket(chars, open)?;
+        if chars.get(close + 1) != Some(&'(') {
+            return None;
+        }
+        let end = (close + 2..chars.len()).find(|&j| chars[j] == ')')?;
+        let target: String = chars[close + 2..end].iter().collect();
+        let url = target.split_whitespace().next().unwrap_or("");
+        let url = url.trim_start_matches('<').trim_end_matches('>');
+        let text = self.inlines(&chars[open + 1..close].iter().collect::<String>());
+        Some((text, url.to_string(), end + 1))
+    }
+
+    /// An autolink, `<u>` or `<span>` element or other HTML tag at `<`
+    fn html(&self, chars: &[char], open: usize) -> Option<(Inline, usize)> {
+        let end = (open + 1..chars.len()).find(|&j| chars[j] == '>' || chars[j] == '\n')?;
+        if chars[end] != '>' {
+            return None;
+        }
+        let tag: String = chars[open + 1..end].iter().collect();
+        if tag.starts_with("http://") || tag.starts_with("https://") || tag.starts_with("mailto:") {
+            return Some((Inline::Link(vec![Inline::Str(tag.clone())], tag), end + 1));
+        }
+        let name: String = tag
+            .trim_start_matches('/')
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric())
+            .collect();
+        if name.is_empty() || !tag.starts_with(|c: char| c == '/' || c.is_ascii_alphabetic()) {
+            return None;
+        }
+        if name == "u" || name == "span" {
+            let closing: Vec<char> = format!("</{}>", name).chars().collect();
+            if let Some(close) = (end + 1..chars.len()).find(|&j| chars[j..].starts_with(&closing))
+            {
+                let inner = self.inlines(&chars[end + 1..close].iter().collect::<String>());
+                let inline = if name == "u" {
+                    Inline::Underline(inner)
+                } else {
+                    Inline::Span(attribute(&tag, "style").unwrap_or_default(), inner)
+                };
+                return Some((inline, close + closing.len()));
+            }
+        }
+        Some((Inline::RawHtml(format!("<{}>", tag)), end + 1))
+    }
+}
+
+/// Inlines with adjacent text merged into one `Str`
+#[derive(Default)]
+struct Inlines(Vec<Inline>);
+
+impl Inlines {
+    fn push(&mut self, inline: Inline) {
+        self.0.push(inline);
+    }
+
+    fn push_char(&mut self, c: char) {
+        match self.0.last_mut() {
+            Some(Inline::Str(text)) => text.push(c),
+            _ => self.0.push(Inline::Str(c.to_string())),
+        }
+    }
+
+    fn push_str(&mut self, s: &str) {
+        s.chars().for_each(|c| self.push_char(c));
+    }
+}
+
+/// The opening fence of a code block
+fn fence(line: &str) -> Option<&'static str> {
+    if line.starts_with("```") {
+        Some("```")
+    } else if line.starts_with("~~~") {
+        Some("~~~")
+    } else {
+        None
+    }
+}
+
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    let text = rest.trim();
+    let text = text.trim_end_matches('#');
+    Some((level, text.trim_end()))
+}
+
+/// `---`, `***` or `___`, spaces allowed between
+fn is_rule(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && ['-', '*', '_'].contains(&marks[0]) && marks.iter().all(|&c| c == marks[0])
+}
+
+fn setext_level(line: &str) -> Option<usize> {
+    let line = line.trim();
+    if line.is_empty() {
+        None
+    } else if line.chars().all(|c| c == '=') {
+        Some(1)
+    } else if line.chars().all(|c| c == '-') {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+struct ListMarker {
+    /// Start number of an ordered list item
+    number: Option<u64>,
+}
+
+fn list_marker(line: &str) -> Option<(ListMarker, &str)> {
+    if let Some(rest) = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+        && !is_rule(line)
+    {
+        return Some((ListMarker { number: None }, rest.trim_start()));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if !(1..=9).contains(&digits) {
+        return None;
+    }
+    let rest = line[digits..]
+        .strip_prefix(". ")
+        .or_else(|| line[digits..].strip_prefix(") "))?;
+    let number = line[..digits].parse().ok()?;
+    Some((
+        ListMarker {
+            number: Some(number),
+        },
+        rest.trim_start(),
+    ))
+}
+
+/// Whether a line ends the paragraph before it
+fn interrupts(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.is_empty()
+        || fence(trimmed).is_some()
+        || atx_heading(trimmed).is_some()
+        || trimmed.starts_with('>')
+        || setext_level(line).is_some()
+        || (is_rule(trimmed) && !trimmed.starts_with('-'))
+        || footnote_definition(line).is_some()
+        || list_marker(line).is_some_and(|(marker, _)| marker.number.is_none_or(|n| n == 1))
+}
+
+fn dedent(line: &str) -> &str {
+    line.strip_prefix('\t')
+        .or_else(|| line.strip_prefix("    "))
+        .or_else(|| line.strip_prefix("   "))
+        .or_else(|| line.strip_prefix("  "))
+        .unwrap_or(line)
+}
+
+fn run_end(chars: &[char], start: usize, in_run: impl Fn(char) -> bool) -> usize {
+    (start..chars.len())
+        .find(|&j| !in_run(chars[j]))
+        .unwrap_or(chars.len())
+}
+
+/// A run of exactly `len` `c`s at or after `from`
+fn find_run(chars: &[char], from: usize, c: char, len: usize) -> Option<usize> {
+    let mut j = from;
+    while j < chars.len() {
+        if chars[j] == c {
+            let end = run_end(chars, j, |other| other == c);
+            if end - j == len {
+                return Some(j);
+            }
+            j = end;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+/// The closing emphasis delimiter for an opening run of `len` `c`s: a run of
+/// the same length after text that is not whitespace, outside code spans
+fn find_closing(chars: &[char], from: usize, c: char, len: usize) -> Option<usize> {
+    let mut j = from;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 2,
+            '`' => {
+                let end = run_end(chars, j, |c| c == '`');
+                j = find_run(chars, end, '`', end - j).map_or(end, |close| close + end - j);
+            }
+            other if other == c => {
+                let end = run_end(chars, j, |other| other == c);
+                let word_follows = c == '_' && chars.get(end).is_some_and(|c| c.is_alphanumeric());
+                if end - j == len && j > from && !chars[j - 1].is_whitespace() && !word_follows {
+                    return Some(j);
+                }
+                j = end;
+            }
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// The `]` matching the `[` at `open`
+fn closing_bracket(chars: &[char], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut j = open;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 1,
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(j);
+                }
+            }
+            _ => {}
+        }
+        j += 1;
+    }
+    None
+}
+
+/// `$tex$` or `$$tex$$` at `start`, as Pandoc reads them: inline math may not
+/// start or end with a space, nor be followed by a digit
+fn math(chars: &[char], start: usize) -> Option<(Inline, usize)> {
+    if chars.get(start + 1) == Some(&'$') {
+        let from = start + 2;
+        let close = (from..chars.len().saturating_sub(1))
+            .find(|&j| chars[j] == '$' && chars[j + 1] == '$')?;
+        let tex: String = chars[from..close].iter().collect();
+        return Some((
+            Inline::Math {
+                display: true,
+                tex: tex.trim().to_string(),
+            },
+            close + 2,
+        ));
+    }
+    let from = start + 1;
+    if chars.get(from).is_none_or(|c| c.is_whitespace()) {
+        return None;
+    }
+    let mut j = from;
+    while j < chars.len() {
+        match chars[j] {
+            '\\' => j += 2,
+            // Half of a `$$` is not a closing `$`
+            '$' if chars.get(j + 1) == Some(&'$') => j += 2,
+            '$' if !chars[j - 1].is_whitespace()
+                && !chars.get(j + 1).is_some_and(char::is_ascii_digit) =>
+            {
+                let tex: String = chars[from..j].iter().collect();
+                return Some((
+                    Inline::Math {
+                        display: false,
+                        tex,
+                    },
+                    j + 1,
+                ));
+            }
+            _ => j += 1,
+        }
+    }
+    None
+}
+
+/// The value of `name="..."` in a tag
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let start = tag.find(&format!("{}=", name))? + name.len() + 1;
+    let rest = &tag[start..];
+    let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+    let value = &rest[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+/// The text of inlines, without notes, for header identifiers
+fn stringify(inlines: &[Inline]) -> String {
+    let mut text = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Str(s) | Inline::Code(s) | Inline::Math { tex: s, .. } => text.push_str(s),
+            Inline::Space | Inline::SoftBreak | Inline::LineBreak => text.push(' '),
+            Inline::Emph(inner)
+            | Inline::Strong(inner)
+            | Inline::Strikeout(inner)
+            | Inline::Underline(inner)
+            | Inline::Span(_, inner)
+            | Inline::Link(inner, _)
+            | Inline::Image(inner, _) => text.push_str(&stringify(inner)),
+            Inline::Note(_) | Inline::RawHtml(_) => {}
+        }
+    }
+    text
+}
+
+/// Pandoc's automatic header identifier: letters, digits, `_`, `-` and `.`,
+/// lowercased, with spaces as hyphens, from the first letter on
+fn identifier(text: &str) -> String {
+    let mut id = String::new();
+    for word in text.split_whitespace() {
+        let word: String = word
+            .chars()
+            .filter(|&c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.'))
+            .flat_map(char::to_lowercase)
+            .collect();
+        if word.is_empty() {
+            continue;
+        }
+        if !id.is_empty() {
+            id.push('-');
+        }
+        id.push_str(&word);
+    }
+    let id = id.trim_start_matches(|c: char| !c.is_alphabetic());
+    if id.is_empty() {
+        "section".to_string()
+    } else {
+        id.to_string()
+    }
+}
+
+/// Attributes with an identifier and nothing else
+fn attr(id: &str) -> String {
+    format!("[{},[],[]]", json::string(id))
+}
+
+fn write_blocks(out: &mut String, blocks: &[Block]) {
+    out.push('[');
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_block(out, block);
+    }
+    out.push(']');
+}
+
+fn write_items(out: &mut String, items: &[Vec<Block>]) {
+    out.push('[');
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_blocks(out, item);
+    }
+    out.push(']');
+}
+
+fn write_block(out: &mut String, block: &Block) {
+    match block {
+        Block::Plain(inlines) | Block::Para(inlines) => {
+            let tag = if matches!(block, Block::Plain(_)) {
+                "Plain"
+            } else {
+                "Para"
+            };
+            out.push_str(&format!("{{\"t\":\"{}\",\"c\":", tag));
+            write_inlines(out, inlines);
+        }
+        Block::Header(level, id, inlines) => {
+            out.push_str(&format!(
+                "{{\"t\":\"Header\",\"c\":[{},{},",
+                level,
+                attr(id)
+            ));
+            write_inlines(out, inlines);
+            out.push(']');
+        }
+        Block::Code(language, code) => {
+            let classes = if language.is_empty() {
+                String::new()
+            } else {
+                json::string(language)
+            };
+            out.push_str(&format!(
+                "{{\"t\":\"CodeBlock\",\"c\":[[\"\",[{}],[]],{}]",
+                classes,
+                json::string(code)
+            ));
+        }
+        Block::Quote(blocks) => {
+            out.push_str("{\"t\":\"BlockQuote\",\"c\":");
+            write_blocks(out, blocks);
+        }
+        Block::BulletList(items) => {
+            out.push_str("{\"t\":\"BulletList\",\"c\":");
+            write_items(out, items);
+        }
+        Block::OrderedList(start, items) => {
+            out.push_str(&format!(
+                "{{\"t\":\"OrderedList\",\"c\":[[{},{{\"t\":\"Decimal\"}},{{\"t\":\"Period\"}}],",
+                start
+            ));
+            write_items(out, items);
+            out.push(']');
+        }
+        Block::DefinitionList(items) => {
+            out.push_str("{\"t\":\"DefinitionList\",\"c\":[");
+            for (i, (term, definitions)) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push('[');
+                write_inlines(out, term);
+                out.push(',');
+                write_items(out, definitions);
+                out.push(']');
+            }
+            out.push(']');
+        }
+        Block::HorizontalRule => out.push_str("{\"t\":\"HorizontalRule\""),
+    }
+    out.push('}');
+}
+
+fn write_inlines(out: &mut String, inlines: &[Inline]) {
+    out.push('[');
+    for (i, inline) in inlines.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_inline(out, inline);
+    }
+    out.push(']');
+}
+
+fn write_inline(out: &mut String, inline: &Inline) {
+    let wrapped = |out: &mut String, tag: &str, inner: &[Inline]| {
+        out.push_str(&format!("{{\"t\":\"{}\",\"c\":", tag));
+        write_inlines(out, inner);
+    };
+    match inline {
+        Inline::Str(text) => out.push_str(&format!("{{\"t\":\"Str\",\"c\":{}", json::string(text))),
+        Inline::Space => out.push_str("{\"t\":\"Space\""),
+        Inline::SoftBreak => out.push_str("{\"t\":\"SoftBreak\""),
+        Inline::LineBreak => out.push_str("{\"t\":\"LineBreak\""),
+        Inline::Emph(inner) => wrapped(out, "Emph", inner),
+        Inline::Strong(inner) => wrapped(out, "Strong", inner),
+        Inline::Strikeout(inner) => wrapped(out, "Strikeout", inner),
+        Inline::Underline(inner) => wrapped(out, "Underline", inner),
+        Inline::Span(style, inner) => {
+            let attributes = if style.is_empty() {
+                String::new()
+            } else {
+                format!("[\"style\",{}]", json::string(style))
+            };
+            out.push_str(&format!(
+                "{{\"t\":\"Span\",\"c\":[[\"\",[],[{}]],",
+                attributes
+            ));
+            write_inlines(out, inner);
+            out.push(']');
+        }
+        Inline::Code(code) => out.push_str(&format!(
+            "{{\"t\":\"Code\",\"c\":[{},{}]",
+            attr(""),
+            json::string(code)
+        )),
+        Inline::Math { display, tex } => out.push_str(&format!(
+            "{{\"t\":\"Math\",\"c\":[{{\"t\":\"{}\"}},{}]",
+            if *display {
+                "DisplayMath"
+            } else {
+                "InlineMath"
+            },
+            json::string(tex)
+        )),
+        Inline::Link(text, url) | Inline::Image(text, url) => {
+            let tag = if matches!(inline, Inline::Link(..)) {
+                "Link"
+            } else {
+                "Image"
+            };
+            out.push_str(&format!("{{\"t\":\"{}\",\"c\":[{},", tag, attr("")));
+            write_inlines(out, text);
+            out.push_str(&format!(",[{},\"\"]]", json::string(url)));
+        }
+        Inline::Note(blocks) => {
+            out.push_str("{\"t\":\"Note\",\"c\":");
+            write_blocks(out, blocks);
+        }
+        Inline::RawHtml(html) => out.push_str(&format!(
+            "{{\"t\":\"RawInline\",\"c\":[\"html\",{}]",
+            json::string(html)
+        )),
+    }
+    out.push('}');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Value;
+
+    fn parse(markdown: &str) -> Vec<Block> {
+        let lines: Vec<&str> = markdown.lines().collect();
+        let notes = footnote_definitions(&lines);
+        Parser {
+            notes: &notes,
+            identifiers: HashMap::new(),
+            open: Vec::new(),
+        }
+        .blocks(&lines)
+    }
+
+    fn str(text: &str) -> Inline {
+        Inline::Str(text.to_string())
+    }
+
+    #[test]
+    fn test_blocks() {
+        let blocks = parse(
+            "## Intro\n\nSome text\nwrapped.\n\n---\n\n> quoted\n\n- one\n- two\n\nTerm\n: Meaning\n\n```python\nx = 1\n```\n\n## Intro",
+        );
+        assert_eq!(
+            blocks,
+            vec![
+                Block::Header(2, "intro".to_string(), vec![str("Intro")]),
+                Block::Para(vec![
+                    str("Some"),
+                    Inline::Space,
+                    str("text"),
+                    Inline::SoftBreak,
+                    str("wrapped.")
+                ]),
+                Block::HorizontalRule,
+                Block::Quote(vec![Block::Para(vec![str("quoted")])]),
+                Block::BulletList(vec![
+                    vec![Block::Plain(vec![str("one")])],
+                    vec![Block::Plain(vec![str("two")])]
+                ]),
+                Block::DefinitionList(vec![(
+                    vec![str("Term")],
+                    vec![vec![Block::Plain(vec![str("Meaning")])]]
+                )]),
+                Block::Code("python".to_string(), "x = 1".to_string()),
+                Block::Header(2, "intro-1".to_string(), vec![str("Intro")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inlines() {
+        let notes = HashMap::from([("1".to_string(), "A note.".to_string())]);
+        let parser = Parser {
+            notes: &notes,
+            identifiers: HashMap::new(),
+            open: Vec::new(),
+        };
+        assert_eq!(
+            parser.inlines("**bold** *it* ~~gone~~ <u>under</u> snake_case_name"),
+            vec![
+                Inline::Strong(vec![str("bold")]),
+                Inline::Space,
+                Inline::Emph(vec![str("it")]),
+                Inline::Space,
+                Inline::Strikeout(vec![str("gone")]),
+                Inline::Space,
+                Inline::Underline(vec![str("under")]),
+                Inline::Space,
+                str("snake_case_name"),
+            ]
+        );
+        assert_eq!(
+            parser.inlines("$E = mc^2$ costs $5 and $$x$$"),
+            vec![
+                Inline::Math {
+                    display: false,
+                    tex: "E = mc^2".to_string()
+                },
+                Inline::Space,
+                str("costs"),
+                Inline::Space,
+                str("$5"),
+                Inline::Space,
+                str("and"),
+                Inline::Space,
+                Inline::Math {
+                    display: true,
+                    tex: "x".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            parser.inlines("![Chart](images/p1.png)[^1]^[Inline] [^9]"),
+            vec![
+                Inline::Image(vec![str("Chart")], "images/p1.png".to_string()),
+                Inline::Note(vec![Block::Para(vec![
+                    str("A"),
+                    Inline::Space,
+                    str("note.")
+                ])]),
+                Inline::Note(vec![Block::Para(vec![str("Inline")])]),
+                Inline::Space,
+                str("[^9]"),
+            ]
+        );
+        assert_eq!(
+            parser.inlines("<span style=\"color:#ff0000\">red</span> `a*b*c`"),
+            vec![
+                Inline::Span("color:#ff0000".to_string(), vec![str("red")]),
+                Inline::Space,
+                Inline::Code("a*b*c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_identifier() {
+        assert_eq!(identifier("1.2 Getting Started!"), "getting-started");
+        assert_eq!(identifier("C++ and Rust"), "c-and-rust");
+        assert_eq!(identifier("2024"), "section");
+    }
+
+    #[test]
+    fn test_document() {
+        let json = document(
+            "---\nsubject: \"Q1 \\\"final\\\"\"\n---\n\n# Report\n\nText.[^1]\n\n[^1]: Note.\n",
+            vec![(
+                "title".to_string(),
+                MetaValue::Inlines("Report".to_string()),
+            )],
+        );
+        let value = json::parse(&json).unwrap();
+        assert!(json.starts_with("{\"pandoc-api-version\":[1,23,1],"));
+        let meta = value.get("meta").unwrap();
+        assert!(meta.get("title").is_some());
+        assert_eq!(
+            meta.get("subject").and_then(|subject| subject.get("c")),
+            Some(&Value::Array(vec![
+                Value::Object(vec![
+                    ("t".to_string(), Value::String("Str".to_string())),
+                    ("c".to_string(), Value::String("Q1".to_string()))
+                ]),
+                Value::Object(vec![("t".to_string(), Value::String("Space".to_string()))]),
+                Value::Object(vec![
+                    ("t".to_string(), Value::String("Str".to_string())),
+                    ("c".to_string(), Value::String("\"final\"".to_string()))
+                ]),
+            ]))
+        );
+        let Some(Value::Array(blocks)) = value.get("blocks") else {
+            panic!("no blocks in {}", json);
+        };
+        // The footnote definition is folded into its reference
+        assert_eq!(blocks.len(), 2);
+        assert!(json.contains(
+            "{\"t\":\"Note\",\"c\":[{\"t\":\"Para\",\"c\":[{\"t\":\"Str\",\"c\":\"Note.\"}]}]}"
+        ));
+    }
+
+    #[test]
+    fn test_notes_referring_to_notes() {
+        let note = |blocks: Vec<Block>| Block::Para(vec![str("Text"), Inline::Note(blocks)]);
+        // A note mentioning itself keeps the mention as text
+        assert_eq!(
+            parse("Text[^1]\n\n[^1]: See[^1]\n"),
+            vec![note(vec![Block::Para(vec![str("See[^1]")])])]
+        );
+        // A cycle is followed once around
+        assert_eq!(
+            parse("Text[^a]\n\n[^a]: A[^b]\n\n[^b]: B[^a]\n"),
+            vec![note(vec![Block::Para(vec![
+                str("A"),
+                Inline::Note(vec![Block::Para(vec![str("B[^a]")])]),
+            ])])]
+        );
+        let json = document("Text[^1]\n\n[^1]: See also[^1]\n", vec![]);
+        assert!(json::parse(&json).is_ok());
+    }
+}
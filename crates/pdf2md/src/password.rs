@@ -0,0 +1,138 @@
+//! Passwords of encrypted PDFs: from `--password`, `--password-file`, or
+//! else asked for on the terminal with echo turned off.
+
+use crate::config::Config;
+use crate::error::{Pdf2MdError, Result};
+use crate::i18n::{Message, tr, trf};
+use crate::style;
+use log::info;
+use pdf_extract::{PdfDocument, PdfError};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+
+/// Passwords asked for before giving up
+const ATTEMPTS: usize = 3;
+
+/// Held while asking, so the files of a directory conversion ask in turn
+static TERMINAL: Mutex<()> = Mutex::new(());
+
+/// Decrypt `doc` when the empty user password did not. Without a password
+/// given or a terminal to ask on, the document is left as it is, and
+/// converts with the warning that its text may be garbled.
+pub fn unlock(doc: &mut PdfDocument, config: &Config) -> Result<()> {
+    if !doc.needs_password() {
+        return Ok(());
+    }
+    let file = config.input_path.display().to_string();
+    if let Some(password) = given(config)? {
+        return doc.decrypt(&password).map_err(|e| incorrect(&file, e));
+    }
+
+    let _turn = TERMINAL.lock().unwrap_or_else(|e| e.into_inner());
+    let mut attempt = 1;
+    loop {
+        let prompt = trf(Message::PasswordPrompt, &[("file", &file)]);
+        let Some(password) = ask(&prompt)? else {
+            info!("No terminal to ask for the password of {} on", file);
+            return Ok(());
+        };
+        if password.is_empty() {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "{} is encrypted and no password was given",
+                file
+            )));
+        }
+        match doc.decrypt(&password) {
+            Ok(()) => return Ok(()),
+            Err(PdfError::InvalidInput(_)) if attempt < ATTEMPTS => eprintln!(
+                "{}: {}",
+                style::error(tr(Message::Error)),
+                tr(Message::WrongPassword)
+            ),
+            Err(e) => return Err(incorrect(&file, e)),
+        }
+        attempt += 1;
+    }
+}
+
+/// The password of `--password`, or the first line of `--password-file`
+fn given(config: &Config) -> Result<Option<String>> {
+    if let Some(password) = &config.password {
+        return Ok(Some(password.clone()));
+    }
+    let Some(path) = &config.password_file else {
+        return Ok(None);
+    };
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        Pdf2MdError::InvalidInput(format!(
+            "cannot read the password file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(Some(first_line(&text).to_string()))
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines().next().unwrap_or_default()
+}
+
+/// A wrong password as an error about the file, rather than about the PDF
+fn incorrect(file: &str, error: PdfError) -> Pdf2MdError {
+    match error {
+        PdfError::InvalidInput(message) => {
+            Pdf2MdError::InvalidInput(format!("{}: {}", file, message))
+        }
+        e => e.into(),
+    }
+}
+
+/// Ask on the controlling terminal, with echo off; `None` when there is no
+/// terminal, or its echo cannot be turned off
+fn ask(prompt: &str) -> Result<Option<String>> {
+    let Ok(mut tty) = OpenOptions::new().read(true).write(true).open("/dev/tty") else {
+        return Ok(None);
+    };
+    if !stty(&tty, "-echo") {
+        return Ok(None);
+    }
+    let answer = (|| {
+        write!(tty, "{}", prompt)?;
+        tty.flush()?;
+        let mut line = String::new();
+        BufReader::new(&tty).read_line(&mut line)?;
+        Ok::<_, std::io::Error>(line)
+    })();
+    stty(&tty, "echo");
+    // The Enter typed was not echoed either
+    let _ = writeln!(tty);
+    Ok(Some(answer?.trim_end_matches(['\r', '\n']).to_string()))
+}
+
+/// Change a setting of the terminal `tty` with `stty`
+fn stty(tty: &File, setting: &str) -> bool {
+    tty.try_clone().is_ok_and(|tty| {
+        Command::new("stty")
+            .arg(setting)
+            .stdin(tty)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_file_first_line() {
+        assert_eq!(first_line("s3cret\n"), "s3cret");
+        assert_eq!(first_line("s3cret\r\nnext"), "s3cret");
+        assert_eq!(first_line(" spaced \n"), " spaced ");
+        assert_eq!(first_line(""), "");
+    }
+}
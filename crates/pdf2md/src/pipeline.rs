@@ -0,0 +1,175 @@
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::sanitize::Sanitizer;
+use crate::timings::Timings;
+use crate::{Result, quarto};
+use log::info;
+use markdown_gen::{FormatOptions, StreamingFormatter};
+use pdf_extract::{ExtractOptions, ExtractedContent, PdfDocument};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Instant;
+
+/// Pieces of cleaned text the extraction may get ahead of the writer by
+/// before it waits
+const QUEUE_LENGTH: usize = 4;
+
+/// Whether every stage after extraction works a paragraph at a time, so
+/// pages can be written while later ones are still being extracted
+pub fn can_stream(config: &Config) -> bool {
+    whole_document_option(config).is_none()
+}
+
+/// The first option set that needs the whole document: heading rewrites,
+/// hooks, scripts, replace rules, chat output, front matter, page comments
+/// and anchors, site layouts, and `--check`
+pub fn whole_document_option(config: &Config) -> Option<&'static str> {
+    [
+        (config.site.is_some(), "--site"),
+        (config.check, "--check"),
+        (config.normalize_headings, "--normalize-headings"),
+        (config.number_headings, "--number-headings"),
+        (config.strip_heading_numbers, "--strip-heading-numbers"),
+        (config.pre_hook.is_some(), "--pre-hook"),
+        (config.post_hook.is_some(), "--post-hook"),
+        (config.script.is_some(), "--script"),
+        (!config.replace_rules.is_empty(), "a [[replace]] rule"),
+        (config.chat, "--chat"),
+        (config.provenance, "--provenance"),
+        (config.annotate_provenance, "--annotate-provenance"),
+        (config.page_anchors.is_some(), "--page-anchors"),
+        (
+            config.output_format == OutputFormat::PandocJson,
+            "--format pandoc-json",
+        ),
+        (
+            quarto::is_quarto_path(&config.output_path),
+            "Quarto output (.qmd, .Rmd)",
+        ),
+    ]
+    .into_iter()
+    .find_map(|(set, option)| set.then_some(option))
+}
+
+/// Convert with extraction and writing overlapped.
+///
+/// A thread extracts and cleans the pages and passes their text through a
+/// bounded channel to this one, which formats each paragraph as soon as it is
+/// complete and writes it out; the Markdown is the same as converting the
+/// whole text at once. Output goes to `<output>.partial` until the conversion
+/// succeeds, so a failed run leaves an earlier output in place. With
+/// `--low-memory` the images are written as each page is done; otherwise they
+/// are returned with the extracted content, without its text.
+pub fn convert(
+    config: &Config,
+    doc: &PdfDocument,
+    options: &ExtractOptions,
+    timings: &mut Timings,
+) -> Result<ExtractedContent> {
+    info!("Writing Markdown to: {}", config.output_path.display());
+    markdown_gen::create_parent_dirs(&config.output_path)?;
+    let partial = partial_path(&config.output_path);
+    let result = write(config, doc, options, &partial, timings).and_then(|content| {
+        fs::rename(&partial, &config.output_path)?;
+        Ok(content)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result
+}
+
+fn write(
+    config: &Config,
+    doc: &PdfDocument,
+    options: &ExtractOptions,
+    path: &Path,
+    timings: &mut Timings,
+) -> Result<ExtractedContent> {
+    let mut out = BufWriter::new(File::create(path)?);
+    let (sender, receiver) = mpsc::sync_channel::<String>(QUEUE_LENGTH);
+    let mut images = Vec::new();
+    let mut images_written = Ok(());
+    let mut sanitizer = Sanitizer::new(config.sanitize, config.invisible_chars, config.normalize);
+    let (content, written) = std::thread::scope(|scope| {
+        let extraction = scope.spawn(|| {
+            doc.extract_text_streaming(
+                options,
+                move |text| match sender.send(text) {
+                    Ok(()) => ControlFlow::Continue(()),
+                    // The writer gave up
+                    Err(_) => ControlFlow::Break(()),
+                },
+                |page_images| {
+                    if !config.low_memory {
+                        images.extend(page_images);
+                        return ControlFlow::Continue(());
+                    }
+                    images_written = crate::write_images(config, &page_images);
+                    match images_written {
+                        Ok(()) => ControlFlow::Continue(()),
+                        Err(_) => ControlFlow::Break(()),
+                    }
+                },
+            )
+        });
+        let written = write_markdown(receiver, &mut out, config.format, &mut sanitizer, timings);
+        let content = extraction.join().expect("extraction thread panicked");
+        (content, written)
+    });
+    let mut content = content?;
+    written?;
+    images_written?;
+    content.images = images;
+    timings.extracted(&content.timings);
+
+    if let Some(style) = config.footnotes {
+        let footnotes = pdf_extract::render_footnotes(style, &content.footnotes);
+        out.write_all(sanitizer.clean(&footnotes)?.as_bytes())?;
+    }
+    sanitizer.finish();
+    if let Some((page, _)) = &content.stopped_at {
+        out.write_all(crate::stopped_note(*page, content.page_count).as_bytes())?;
+    }
+    out.flush()?;
+    Ok(content)
+}
+
+/// Sanitize, format and write the text received until the extraction ends,
+/// adding the time spent on each to `timings`
+fn write_markdown(
+    receiver: mpsc::Receiver<String>,
+    out: &mut impl Write,
+    options: FormatOptions,
+    sanitizer: &mut Sanitizer,
+    timings: &mut Timings,
+) -> Result<()> {
+    let mut formatter = StreamingFormatter::new(options);
+    for text in receiver {
+        let started = Instant::now();
+        let markdown = formatter.push(&sanitizer.clean(&text)?);
+        timings.formatting += started.elapsed();
+        timed_write(out, &markdown, timings)?;
+    }
+    let started = Instant::now();
+    let markdown = formatter.finish();
+    timings.formatting += started.elapsed();
+    Ok(timed_write(out, &markdown, timings)?)
+}
+
+fn timed_write(out: &mut impl Write, markdown: &str, timings: &mut Timings) -> std::io::Result<()> {
+    let started = Instant::now();
+    out.write_all(markdown.as_bytes())?;
+    timings.writing += started.elapsed();
+    Ok(())
+}
+
+/// `out.md` becomes `out.md.partial`
+fn partial_path(output: &Path) -> PathBuf {
+    let mut path = output.as_os_str().to_owned();
+    path.push(".partial");
+    PathBuf::from(path)
+}
@@ -0,0 +1,200 @@
+//! PDF portfolios, whose only page is usually a cover sheet: each embedded
+//! PDF is converted to its own file, and the output lists them
+
+use crate::cli::OutputFormat;
+use crate::config::Config;
+use crate::i18n::{Message, tr};
+use crate::{Converted, Pdf2MdError, Result, in_sync, pandoc, site, style};
+use log::info;
+use pdf_extract::{EmbeddedDocument, PdfDocument};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Convert the PDFs of a portfolio into the directory named after the output
+/// (`report.md` -> `report/<name>.md`), then write to the output a list
+/// linking to each.
+///
+/// A document that fails does not stop the others; the conversion fails
+/// with the count, like a directory conversion.
+pub(crate) fn convert(
+    config: &Config,
+    doc: &PdfDocument,
+    documents: &[EmbeddedDocument],
+) -> Result<Converted> {
+    if config.site.is_some() {
+        return Err(Pdf2MdError::InvalidInput(format!(
+            "{} is a portfolio, and --site lays out a single document; extract its PDFs first",
+            config.input_path.display()
+        )));
+    }
+    let dir = config.output_path.with_extension("");
+    let staging = Staging::create()?;
+    let mut converted = Converted {
+        files: vec![config.output_path.clone()],
+        ..Converted::default()
+    };
+    let mut links = Vec::new();
+    let mut failed = 0;
+    for (document, stem) in documents.iter().zip(stems(documents)) {
+        let input = staging.dir.join(format!("{}.pdf", stem));
+        std::fs::write(&input, &document.data)?;
+        let mut member = config.clone();
+        member.input_path = input;
+        member.output_path = dir.join(format!("{}.{}", stem, config.output_format.extension()));
+        member.default_output = false;
+        // Documents written to the same directory must not share image names
+        member.image_dir = config.image_dir.join(&stem);
+        info!("Converting {} of the portfolio", document.name);
+        let output = member.output_path.clone();
+        match crate::convert(member) {
+            Ok(result) => {
+                converted.files.extend(result.files);
+                converted.warnings.extend(result.warnings);
+                converted.pages += result.pages;
+                converted.words += result.words;
+                links.push((document.name.as_str(), output));
+            }
+            Err(e) => {
+                eprintln!(
+                    "{}: {}: {}",
+                    document.name,
+                    style::error(tr(Message::Error)),
+                    e
+                );
+                failed += 1;
+            }
+        }
+    }
+
+    let metadata = doc.extract_metadata()?;
+    let title = site::document_title(config, &metadata);
+    let mut contents = list(&title, &links, &config.output_path);
+    if config.output_format == OutputFormat::PandocJson {
+        contents = pandoc::to_pandoc_json(config, &metadata, &contents, None);
+    }
+    if config.check {
+        in_sync::compare(&config.output_path, &config.input_path, &contents, None)?;
+    } else {
+        markdown_gen::write_to_file(&contents, &config.output_path)?;
+    }
+
+    if failed > 0 {
+        return Err(Pdf2MdError::Batch {
+            failed,
+            total: documents.len(),
+        });
+    }
+    Ok(converted)
+}
+
+/// The Markdown written to the output: the portfolio's title and a link to
+/// each document converted, relative to the output
+fn list(title: &str, links: &[(&str, PathBuf)], output: &Path) -> String {
+    let base = output.parent().unwrap_or(Path::new(""));
+    let mut list = format!("# {}\n", title);
+    if !links.is_empty() {
+        list.push('\n');
+    }
+    for (name, path) in links {
+        let target = path.strip_prefix(base).unwrap_or(path);
+        list.push_str(&format!(
+            "- [{}]({})\n",
+            name,
+            target
+                .to_string_lossy()
+                .replace('\\', "/")
+                .replace(' ', "%20")
+        ));
+    }
+    list
+}
+
+/// A distinct file stem for each document, from the name the portfolio gives
+/// it without any directories
+fn stems(documents: &[EmbeddedDocument]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    documents
+        .iter()
+        .enumerate()
+        .map(|(i, document)| {
+            let stem = Path::new(&document.name.replace('\\', "/"))
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().trim().to_string())
+                .filter(|stem| !stem.is_empty() && !stem.starts_with('.'))
+                .unwrap_or_else(|| format!("document-{}", i + 1));
+            let mut unique = stem.clone();
+            let mut n = 2;
+            while !taken.insert(unique.to_lowercase()) {
+                unique = format!("{}-{}", stem, n);
+                n += 1;
+            }
+            unique
+        })
+        .collect()
+}
+
+/// A private directory the embedded PDFs are written to, so each is
+/// converted like any other file
+struct Staging {
+    dir: PathBuf,
+}
+
+impl Staging {
+    fn create() -> Result<Self> {
+        // Files of a directory conversion may be portfolios converted at once
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "pdf2md-portfolio-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+}
+
+impl Drop for Staging {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stems() {
+        let document = |name: &str| EmbeddedDocument {
+            name: name.to_string(),
+            data: Vec::new(),
+        };
+        assert_eq!(
+            stems(&[
+                document("Budget 2024.pdf"),
+                document("archive/budget 2024.PDF"),
+                document("..\\..\\evil.pdf"),
+                document(""),
+            ]),
+            ["Budget 2024", "budget 2024-2", "evil", "document-4"]
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let links = [
+            (
+                "Budget 2024.pdf",
+                PathBuf::from("out/report/Budget 2024.md"),
+            ),
+            ("Minutes.pdf", PathBuf::from("out/report/Minutes.md")),
+        ];
+        assert_eq!(
+            list("Board pack", &links, Path::new("out/report.md")),
+            "# Board pack\n\n\
+             - [Budget 2024.pdf](report/Budget%202024.md)\n\
+             - [Minutes.pdf](report/Minutes.md)\n"
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use crate::cli::PageAnchors;
+use crate::error::Result;
+use crate::sidecar::page_offsets;
+use crate::site::yaml_escape;
+use chrono::{SecondsFormat, Utc};
+use pdf_extract::ExtractedContent;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Where a Markdown file came from, so an archived copy can be traced to its exact source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// File name of the input PDF
+    pub source: String,
+    /// Size of the input PDF in bytes
+    pub size: u64,
+    /// SHA-256 of the input PDF, as lowercase hex
+    pub sha256: String,
+    /// Tool name and version
+    pub converter: String,
+    /// Time of conversion, RFC 3339 in UTC
+    pub converted: String,
+}
+
+impl Provenance {
+    /// Provenance of a conversion of `path` run now
+    pub fn of(path: &Path) -> Result<Self> {
+        let (size, sha256) = hash(path)?;
+        Ok(Self {
+            source: path
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            size,
+            sha256,
+            converter: format!("pdf2md {}", env!("CARGO_PKG_VERSION")),
+            converted: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+        })
+    }
+
+    /// `provenance:` mapping, indented by `indent` spaces
+    fn yaml(&self, indent: usize) -> String {
+        let pad = " ".repeat(indent);
+        format!(
+            "{pad}provenance:\n{pad}  source: \"{}\"\n{pad}  size: {}\n{pad}  sha256: {}\n{pad}  converter: \"{}\"\n{pad}  converted: {}\n",
+            yaml_escape(&self.source),
+            self.size,
+            self.sha256,
+            self.converter,
+            self.converted
+        )
+    }
+
+    /// `[table]` holding the provenance fields
+    fn toml(&self, table: &str) -> String {
+        format!(
+            "\n[{}]\nsource = \"{}\"\nsize = {}\nsha256 = \"{}\"\nconverter = \"{}\"\nconverted = {}\n",
+            table,
+            yaml_escape(&self.source),
+            self.size,
+            self.sha256,
+            self.converter,
+            self.converted
+        )
+    }
+}
+
+/// Size in bytes and SHA-256, as lowercase hex, of a file
+pub fn hash(path: &Path) -> Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    let sha256 = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((size, sha256))
+}
+
+/// Add provenance to a page's YAML (`---`) or TOML (`+++`) front matter,
+/// starting a YAML block if the page has none.
+///
+/// With `under_extra` the fields go under `extra`, where Zola requires
+/// custom front matter keys.
+pub fn add_to_front_matter(page: &str, provenance: &Provenance, under_extra: bool) -> String {
+    for (delimiter, fields) in [
+        ("---\n", yaml_fields(provenance, under_extra)),
+        (
+            "+++\n",
+            provenance.toml(if under_extra {
+                "extra.provenance"
+            } else {
+                "provenance"
+            }),
+        ),
+    ] {
+        if let Some(body) = page.strip_prefix(delimiter)
+            && let Some(end) = body.find(&format!("\n{}", delimiter))
+        {
+            let end = delimiter.len() + end + 1;
+            return format!("{}{}{}", &page[..end], fields, &page[end..]);
+        }
+    }
+    format!(
+        "---\n{}---\n\n{}",
+        yaml_fields(provenance, under_extra),
+        page
+    )
+}
+
+/// The Markdown with an HTML comment naming the source page (`<!-- p.12 -->`)
+/// before each block
+pub fn annotate(content: &ExtractedContent, markdown: &str) -> String {
+    let (line_starts, offsets) = page_offsets(content, markdown);
+    let starts: Vec<(u32, usize)> = offsets
+        .into_iter()
+        .filter_map(|(page, offset)| Some((page, offset?)))
+        .collect();
+    let first_page = starts.first().map_or(1, |&(page, _)| page);
+
+    let mut annotated = String::with_capacity(markdown.len() + markdown.len() / 16);
+    let mut blocks = Blocks::default();
+    for (i, line) in markdown.lines().enumerate() {
+        if blocks.starts(line) {
+            // The page the block's first letter is on
+            let page = starts
+                .iter()
+                .take_while(|&&(_, offset)| offset <= line_starts[i])
+                .last()
+                .map_or(first_page, |&(page, _)| page);
+            annotated.push_str(&format!("<!-- p.{} -->\n", page));
+        }
+        annotated.push_str(line);
+        annotated.push('\n');
+    }
+    if !markdown.ends_with('\n') {
+        annotated.pop();
+    }
+    annotated
+}
+
+/// The Markdown with an anchor with the id `page-N` where each source page
+/// begins: before the block starting the page or, for a page beginning
+/// within a paragraph, as an HTML anchor on a line of the paragraph (heading
+/// anchors wait for the next block)
+pub fn add_page_anchors(content: &ExtractedContent, markdown: &str, style: PageAnchors) -> String {
+    let (line_starts, offsets) = page_offsets(content, markdown);
+    // Pages by the line they begin on
+    let mut pages = offsets
+        .into_iter()
+        .filter_map(|(page, offset)| {
+            let offset = offset?;
+            let line = line_starts.partition_point(|&start| start <= offset);
+            Some((line.saturating_sub(1), page))
+        })
+        .peekable();
+    let anchors = |pages: &[u32]| -> String {
+        match style {
+            PageAnchors::Html => pages
+                .iter()
+                .map(|page| format!("<a id=\"page-{}\"></a>", page))
+                .collect(),
+            PageAnchors::Heading => pages
+                .iter()
+                .map(|page| format!("###### Page {} {{#page-{}}}\n\n", page, page))
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+        }
+    };
+
+    let mut anchored = String::with_capacity(markdown.len() + markdown.len() / 16);
+    let mut blocks = Blocks::default();
+    let mut pending = Vec::new();
+    for (i, line) in markdown.lines().enumerate() {
+        while let Some((_, page)) = pages.next_if(|&(line, _)| line <= i) {
+            pending.push(page);
+        }
+        let starts = blocks.starts(line);
+        if !pending.is_empty() {
+            let inline = style == PageAnchors::Html && blocks.in_paragraph(line);
+            if starts {
+                anchored.push_str(&anchors(&pending));
+                anchored.push_str("\n\n");
+                pending.clear();
+            } else if inline {
+                anchored.push_str(&anchors(&pending));
+                anchored.push('\n');
+                pending.clear();
+            }
+        }
+        anchored.push_str(line);
+        anchored.push('\n');
+    }
+    pending.extend(pages.map(|(_, page)| page));
+    if !pending.is_empty() {
+        anchored.push_str(&format!("\n{}\n", anchors(&pending)));
+    }
+    if !markdown.ends_with('\n') {
+        anchored.pop();
+    }
+    anchored
+}
+
+/// Whether a line holds only the page marks of `annotate` or
+/// `add_page_anchors`, which are no part of the text
+pub(crate) fn is_page_mark(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("<!-- p.") && line.ends_with("-->"))
+        || (line.starts_with("<a id=\"page-") && line.ends_with("</a>"))
+        || (line.starts_with("###### Page ") && line.ends_with('}') && line.contains("{#page-"))
+}
+
+/// Follows where the blocks of Markdown start, line by line: each run of
+/// lines after a blank line, with fenced code kept whole; page marks are
+/// passed over
+#[derive(Default)]
+struct Blocks {
+    in_block: bool,
+    fence: Option<&'static str>,
+}
+
+impl Blocks {
+    /// Whether `line`, the next line, starts a block
+    fn starts(&mut self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            self.in_block = self.in_block && self.fence.is_some();
+            return false;
+        }
+        if self.fence.is_none() && is_page_mark(line) {
+            return false;
+        }
+        let starts = !self.in_block;
+        self.in_block = true;
+        self.fence = match self.fence {
+            Some(marker) if trimmed.starts_with(marker) => None,
+            Some(marker) => Some(marker),
+            None => ["```", "~~~"]
+                .into_iter()
+                .find(|marker| trimmed.starts_with(marker)),
+        };
+        starts
+    }
+
+    /// Whether the line just passed continues a paragraph, so inline HTML
+    /// may go before it: not in code, a table, a list or a quote
+    fn in_paragraph(&self, line: &str) -> bool {
+        let trimmed = line.trim_start();
+        self.fence.is_none()
+            && !trimmed.is_empty()
+            && !trimmed.starts_with("```")
+            && !trimmed.starts_with("~~~")
+            && !trimmed.starts_with(['|', '>', '-', '*', '+', '#'])
+            && !trimmed.split_once(". ").is_some_and(|(number, _)| {
+                !number.is_empty() && number.chars().all(|c| c.is_ascii_digit())
+            })
+    }
+}
+
+fn yaml_fields(provenance: &Provenance, under_extra: bool) -> String {
+    if under_extra {
+        format!("extra:\n{}", provenance.yaml(2))
+    } else {
+        provenance.yaml(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn provenance() -> Provenance {
+        Provenance {
+            source: "report.pdf".to_string(),
+            size: 3,
+            sha256: "ba7816bf".to_string(),
+            converter: "pdf2md 0.1.0".to_string(),
+            converted: "2026-10-15T09:30:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_provenance_of_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("abc.pdf");
+        fs::write(&path, "abc").unwrap();
+
+        let provenance = Provenance::of(&path).unwrap();
+        assert_eq!(provenance.source, "abc.pdf");
+        assert_eq!(provenance.size, 3);
+        assert_eq!(
+            provenance.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(provenance.converter.starts_with("pdf2md "));
+    }
+
+    #[test]
+    fn test_add_to_front_matter() {
+        let provenance = provenance();
+        let yaml = "provenance:\n  source: \"report.pdf\"\n  size: 3\n  sha256: ba7816bf\n  converter: \"pdf2md 0.1.0\"\n  converted: 2026-10-15T09:30:00Z\n";
+        assert_eq!(
+            add_to_front_matter("# Report", &provenance, false),
+            format!("---\n{}---\n\n# Report", yaml)
+        );
+        assert_eq!(
+            add_to_front_matter("---\ntitle: \"Report\"\n---\n\nText", &provenance, false),
+            format!("---\ntitle: \"Report\"\n{}---\n\nText", yaml)
+        );
+
+        let zola = add_to_front_matter("+++\ntitle = \"Report\"\n+++\n\nText", &provenance, true);
+        assert!(zola.starts_with(
+            "+++\ntitle = \"Report\"\n\n[extra.provenance]\nsource = \"report.pdf\"\n"
+        ));
+        assert!(zola.ends_with("converted = 2026-10-15T09:30:00Z\n+++\n\nText"));
+    }
+
+    #[test]
+    fn test_annotate() {
+        let content = ExtractedContent {
+            page_count: 3,
+            page_starts: vec![
+                (1, "Report The survey".to_string()),
+                (2, "mid-sentence, and more".to_string()),
+                (3, "Results are in".to_string()),
+            ],
+            ..Default::default()
+        };
+        let markdown = "# Report\n\nThe survey ran from\nmid-sentence, and more.\n\n```\nx = 1\n\ny = 2\n```\n\nResults are in.\n";
+        assert_eq!(
+            annotate(&content, markdown),
+            "<!-- p.1 -->\n# Report\n\n<!-- p.1 -->\nThe survey ran from\nmid-sentence, and more.\n\n\
+             <!-- p.2 -->\n```\nx = 1\n\ny = 2\n```\n\n<!-- p.3 -->\nResults are in.\n"
+        );
+    }
+
+    #[test]
+    fn test_add_page_anchors() {
+        let content = ExtractedContent {
+            page_count: 4,
+            page_starts: vec![
+                (1, "Report The survey".to_string()),
+                (2, "mid-sentence, and more".to_string()),
+                (3, "y = 2".to_string()),
+                (4, "Results are in".to_string()),
+            ],
+            ..Default::default()
+        };
+        let markdown = "# Report\n\nThe survey ran from\nmid-sentence, and more.\n\n```\nx = 1\ny = 2\n```\n\nResults are in.\n";
+        let html = add_page_anchors(&content, markdown, PageAnchors::Html);
+        assert_eq!(
+            html,
+            "<a id=\"page-1\"></a>\n\n# Report\n\nThe survey ran from\n<a id=\"page-2\"></a>\n\
+             mid-sentence, and more.\n\n```\nx = 1\ny = 2\n```\n\n\
+             <a id=\"page-3\"></a><a id=\"page-4\"></a>\n\nResults are in.\n"
+        );
+        assert_eq!(
+            add_page_anchors(&content, markdown, PageAnchors::Heading),
+            "###### Page 1 {#page-1}\n\n# Report\n\nThe survey ran from\nmid-sentence, and more.\n\n\
+             ###### Page 2 {#page-2}\n\n```\nx = 1\ny = 2\n```\n\n\
+             ###### Page 3 {#page-3}\n\n###### Page 4 {#page-4}\n\nResults are in.\n"
+        );
+        // The anchors are no part of the text when the pages are located again
+        let (_, offsets) = page_offsets(&content, &html);
+        assert!(offsets.iter().all(|(_, offset)| offset.is_some()));
+        assert!(
+            annotate(&content, &html)
+                .starts_with("<a id=\"page-1\"></a>\n\n<!-- p.1 -->\n# Report")
+        );
+    }
+}
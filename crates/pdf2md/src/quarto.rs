@@ -0,0 +1,110 @@
+use crate::config::Config;
+use crate::site::{document_title, yaml_escape};
+use chrono::DateTime;
+use std::path::Path;
+
+/// Whether the output path asks for a Quarto (`.qmd`) or R Markdown (`.Rmd`) document
+pub fn is_quarto_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("qmd") || ext.eq_ignore_ascii_case("rmd"))
+}
+
+/// Quarto document: YAML header followed by the Markdown with code blocks as chunks
+pub fn to_quarto(config: &Config, metadata: &pdf_extract::PdfMetadata, markdown: &str) -> String {
+    let title = document_title(config, metadata);
+    let date = metadata
+        .creation_date
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .map(|date| date.format("%Y-%m-%d").to_string());
+    quarto_header(&title, metadata.author.as_deref(), date.as_deref())
+        + code_chunks(markdown, config.execute_chunks).as_str()
+}
+
+/// YAML header for a Quarto document rendered to HTML
+fn quarto_header(title: &str, author: Option<&str>, date: Option<&str>) -> String {
+    let mut header = format!("---\ntitle: \"{}\"\n", yaml_escape(title));
+    if let Some(author) = author.map(str::trim).filter(|author| !author.is_empty()) {
+        header.push_str(&format!("author: \"{}\"\n", yaml_escape(author)));
+    }
+    if let Some(date) = date {
+        header.push_str(&format!("date: {}\n", date));
+    }
+    header.push_str("format: html\n---\n\n");
+    header
+}
+
+/// Turn fenced code blocks in a language Quarto can run into `{lang}` chunks.
+///
+/// Chunks get `#| eval: false` unless `execute` is set, so rendering the
+/// document never runs code recovered from a PDF by accident.
+fn code_chunks(markdown: &str, execute: bool) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+
+    for line in markdown.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if let Some(info) = trimmed.strip_prefix("```") {
+            if !in_fence && let Some(engine) = chunk_engine(info.trim()) {
+                let indent = &line[..line.len() - trimmed.len()];
+                out.push_str(&format!("{}```{{{}}}\n", indent, engine));
+                if !execute {
+                    out.push_str(&format!("{}#| eval: false\n", indent));
+                }
+                in_fence = true;
+                continue;
+            }
+            in_fence = !in_fence;
+        }
+        out.push_str(line);
+    }
+    out
+}
+
+/// Quarto engine for a fenced block's info string
+fn chunk_engine(info: &str) -> Option<&'static str> {
+    let lang = info.split_whitespace().next()?;
+    match lang.to_ascii_lowercase().as_str() {
+        "python" | "py" => Some("python"),
+        "r" => Some("r"),
+        "julia" | "jl" => Some("julia"),
+        "bash" | "sh" | "shell" => Some("bash"),
+        "sql" => Some("sql"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_quarto_path() {
+        assert!(is_quarto_path(Path::new("report.qmd")));
+        assert!(is_quarto_path(Path::new("analysis.Rmd")));
+        assert!(!is_quarto_path(Path::new("report.md")));
+    }
+
+    #[test]
+    fn test_quarto_header() {
+        assert_eq!(
+            quarto_header("Q3 \"Sales\"", Some("Ana"), Some("2024-05-01")),
+            "---\ntitle: \"Q3 \\\"Sales\\\"\"\nauthor: \"Ana\"\ndate: 2024-05-01\nformat: html\n---\n\n"
+        );
+        assert_eq!(
+            quarto_header("Q3", Some(" "), None),
+            "---\ntitle: \"Q3\"\nformat: html\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn test_code_chunks() {
+        let markdown = "Load it:\n\n```python\nimport pandas\n```\n\n```\nplain\n```\n\n```text\n```python\n```\n";
+        assert_eq!(
+            code_chunks(markdown, false),
+            "Load it:\n\n```{python}\n#| eval: false\nimport pandas\n```\n\n```\nplain\n```\n\n```text\n```python\n```\n"
+        );
+        assert!(code_chunks("```R\nsummary(x)\n```", true).starts_with("```{r}\nsummary(x)"));
+    }
+}
@@ -0,0 +1,259 @@
+//! `http://` and `https://` inputs, fetched with curl. With `--pages`, a
+//! linearized PDF on a server answering range requests is read a range at a
+//! time, so a few pages of a large archive cost a few requests; anything
+//! else is downloaded whole first.
+
+use crate::config::Config;
+use crate::s3::curl_quote;
+use crate::{Result, timings};
+use log::info;
+use pdf_extract::{PdfDocument, RangeSource};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Bytes of the first request, which shows whether the server answers range
+/// requests and whether the file is linearized
+const HEAD: u64 = 1024;
+
+/// Room for the response headers beyond the range asked for
+const HEADERS: u64 = 64 * 1024;
+
+/// Whether a command-line path is a URL
+pub fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| {
+        ["http://", "https://"].iter().any(|scheme| {
+            path.get(..scheme.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(scheme))
+        })
+    })
+}
+
+/// The last segment of a URL's path, without the query or fragment
+pub(crate) fn file_name(url: &Path) -> &str {
+    let url = url.to_str().unwrap_or_default();
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    path.split_once('/')
+        .and_then(|(_, path)| path.rsplit('/').next())
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download.pdf")
+}
+
+/// The URL as shown in messages: without the query, which often carries a token
+fn shown(url: &str) -> &str {
+    url.split('?').next().unwrap_or(url)
+}
+
+/// Convert the PDF at the URL `config.input_path` gives
+pub fn run(mut config: Config) -> Result<()> {
+    let url = config.input_path.to_string_lossy().into_owned();
+    // The provenance record hashes the whole file
+    if let Some(selection) = config.pages.clone().filter(|_| !config.provenance) {
+        config.validate()?;
+        let timings = timings::Timings::start();
+        if let Some(mut remote) = Remote::open(&url)? {
+            info!("Reading pages {} of {}", selection, shown(&url));
+            let doc = PdfDocument::from_ranges(&mut remote, &selection)?;
+            crate::convert_document(config, doc, timings)?;
+            return Ok(());
+        }
+    }
+
+    // A fresh directory of its own, which nothing else can have put there
+    let staging = tempfile::Builder::new()
+        .prefix("pdf2md-download-")
+        .tempdir()?;
+    let local = staging.path().join(file_name(&config.input_path));
+    info!("Downloading {}", shown(&url));
+    curl(&url, &[("output", &local.to_string_lossy())], Stdio::null())?.wait()?;
+    config.input_path = local;
+    crate::convert(config)?;
+    Ok(())
+}
+
+/// A file on a server answering range requests
+struct Remote {
+    url: String,
+    size: u64,
+}
+
+impl Remote {
+    /// The file at `url`, when its server answers range requests and the
+    /// file is linearized; `None` when it is better downloaded whole
+    fn open(url: &str) -> Result<Option<Self>> {
+        let Some((head, size)) = fetch(url, 0..HEAD)? else {
+            info!("The server does not answer range requests; downloading the whole file");
+            return Ok(None);
+        };
+        if !pdf_extract::is_linearized(&head) {
+            info!("The PDF is not linearized; downloading the whole file");
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            url: url.to_string(),
+            size,
+        }))
+    }
+}
+
+impl RangeSource for Remote {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&mut self, range: Range<u64>) -> std::io::Result<Vec<u8>> {
+        match fetch(&self.url, range)? {
+            Some((bytes, _)) => Ok(bytes),
+            None => Err(std::io::Error::other(format!(
+                "{}: the server stopped answering range requests",
+                shown(&self.url)
+            ))),
+        }
+    }
+}
+
+/// Bytes `range` of the file at `url` and the size of the file; `None` when
+/// the server sends anything but that range
+fn fetch(url: &str, range: Range<u64>) -> std::io::Result<Option<(Vec<u8>, u64)>> {
+    let bytes = format!("{}-{}", range.start, range.end - 1);
+    let mut child = curl(
+        url,
+        &[("range", &bytes), ("dump-header", "-")],
+        Stdio::piped(),
+    )?;
+    // A server ignoring the range sends the whole file, which is not waited for
+    let limit = range.end - range.start + HEADERS;
+    let mut data = Vec::new();
+    child
+        .child
+        .stdout
+        .take()
+        .expect("stdout is piped")
+        .take(limit + 1)
+        .read_to_end(&mut data)?;
+    if data.len() as u64 > limit {
+        let _ = child.child.kill();
+        let _ = child.child.wait();
+        return Ok(None);
+    }
+    child.wait()?;
+    Ok(partial(&data).filter(|(body, _)| body.len() as u64 == range.end - range.start))
+}
+
+/// The body of curl's output with the headers first, and the file size the
+/// `Content-Range` header gives, when the final response is `206 Partial
+/// Content`
+fn partial(data: &[u8]) -> Option<(Vec<u8>, u64)> {
+    let mut rest = data;
+    let mut headers;
+    loop {
+        let end = rest.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+        headers = std::str::from_utf8(&rest[..end]).ok()?;
+        rest = &rest[end..];
+        let status = headers.split_whitespace().nth(1)?;
+        // Redirects and interim responses come before the final one
+        if !(status.starts_with('3') || status.starts_with('1')) || !rest.starts_with(b"HTTP/") {
+            if status != "206" {
+                return None;
+            }
+            break;
+        }
+    }
+    let size = headers.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.trim().eq_ignore_ascii_case("content-range") {
+            return None;
+        }
+        value.trim().rsplit_once('/')?.1.parse().ok()
+    })?;
+    Some((rest.to_vec(), size))
+}
+
+/// A running curl fetching `url`, with the configuration on stdin so a
+/// token in the URL never appears in the process list
+fn curl(url: &str, options: &[(&str, &str)], stdout: Stdio) -> std::io::Result<Child> {
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--location",
+            "--config",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| std::io::Error::other(format!("curl could not be started: {}", e)))?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    stdin.write_all(curl_config(url, options).as_bytes())?;
+    drop(stdin);
+    Ok(Child {
+        child,
+        url: shown(url).to_string(),
+    })
+}
+
+fn curl_config(url: &str, options: &[(&str, &str)]) -> String {
+    [("url", url)]
+        .iter()
+        .chain(options)
+        .map(|(name, value)| format!("{} = {}\n", name, curl_quote(value)))
+        .collect()
+}
+
+/// curl, whose error is reported with the URL it failed on
+struct Child {
+    child: std::process::Child,
+    url: String,
+}
+
+impl Child {
+    fn wait(self) -> std::io::Result<()> {
+        let output = self.child.wait_with_output()?;
+        if !output.status.success() {
+            let message = String::from_utf8_lossy(&output.stderr);
+            return Err(std::io::Error::other(format!(
+                "{}: {}",
+                self.url,
+                message.trim().trim_start_matches("curl: ")
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_name() {
+        let name = |url: &str| file_name(Path::new(url)).to_string();
+        assert_eq!(
+            name("https://example.com/a/report.pdf?token=x"),
+            "report.pdf"
+        );
+        assert_eq!(name("http://example.com/report.pdf#page=2"), "report.pdf");
+        assert_eq!(name("https://example.com/"), "download.pdf");
+        assert_eq!(name("https://example.com"), "download.pdf");
+        assert!(is_url(Path::new("HTTPS://example.com/a.pdf")));
+        assert!(!is_url(Path::new("s3://bucket/a.pdf")));
+        assert!(!is_url(Path::new("http.pdf")));
+    }
+
+    #[test]
+    fn test_partial() {
+        let redirected = b"HTTP/1.1 302 Found\r\nLocation: /b.pdf\r\n\r\n\
+              HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-3/4096\r\n\r\n%PDF";
+        assert_eq!(partial(redirected), Some((b"%PDF".to_vec(), 4096)));
+        // The whole file instead of the range
+        assert_eq!(
+            partial(b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\n%PDF"),
+            None
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use regex::Regex;
+use std::borrow::Cow;
+
+/// A find/replace rule from the settings file, applied to the generated Markdown
+#[derive(Debug, Clone)]
+pub struct ReplaceRule {
+    pub pattern: Regex,
+    /// Replacement text; `$1` or `${name}` insert a captured group
+    pub replacement: String,
+}
+
+impl ReplaceRule {
+    /// Read a `[[replace]]` table: `pattern = "..."` and `replacement = "..."`
+    pub fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        let table = value
+            .as_table()
+            .ok_or("`replace` must be a list of tables ([[replace]])")?;
+        if let Some(key) = table
+            .keys()
+            .find(|key| !matches!(key.as_str(), "pattern" | "replacement"))
+        {
+            return Err(format!("unknown key `{}` in a [[replace]] rule", key));
+        }
+        let pattern = table
+            .get("pattern")
+            .and_then(toml::Value::as_str)
+            .ok_or("a [[replace]] rule needs a `pattern` string")?;
+        let replacement = table
+            .get("replacement")
+            .and_then(toml::Value::as_str)
+            .ok_or("a [[replace]] rule needs a `replacement` string")?;
+        let pattern = Regex::new(pattern)
+            .map_err(|e| format!("invalid [[replace]] pattern `{}`: {}", pattern, e))?;
+        Ok(Self {
+            pattern,
+            replacement: replacement.to_string(),
+        })
+    }
+}
+
+/// Apply the rules in order, each to the result of the one before; the
+/// Markdown is only copied once a rule matches
+pub fn apply<'a>(markdown: &'a str, rules: &[ReplaceRule]) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(markdown);
+    for rule in rules {
+        if let Cow::Owned(replaced) = rule.pattern.replace_all(&text, rule.replacement.as_str()) {
+            text = Cow::Owned(replaced);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml: &str) -> Result<ReplaceRule, String> {
+        ReplaceRule::from_toml(&toml::Value::Table(toml.parse().unwrap()))
+    }
+
+    #[test]
+    fn test_apply_in_order() {
+        let rules = [
+            rule(
+                r#"pattern = '(?m)^Page \d+ of \d+$\n?'
+replacement = """#,
+            )
+            .unwrap(),
+            rule(
+                r#"pattern = '(\w+)-\s+(\w+)'
+replacement = "$1$2""#,
+            )
+            .unwrap(),
+            rule(
+                r#"pattern = "ACME"
+replacement = "Acme""#,
+            )
+            .unwrap(),
+        ];
+        assert_eq!(
+            apply("ACME report on hyph- enation\nPage 3 of 9\nEnd", &rules),
+            "Acme report on hyphenation\nEnd"
+        );
+        assert!(matches!(
+            apply("Nothing to replace", &rules),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_bad_rules() {
+        let unclosed = "pattern = \"(\"\nreplacement = \"\"";
+        assert!(
+            rule(unclosed)
+                .unwrap_err()
+                .contains("invalid [[replace]] pattern")
+        );
+        assert!(rule(r#"pattern = "x""#).is_err());
+        assert!(rule("pattern = \"x\"\nreplacement = \"y\"\nflags = \"i\"").is_err());
+    }
+}
@@ -0,0 +1,504 @@
+//! `pdf2md --rpc`: JSON-RPC 2.0 over stdin and stdout, one message per line.
+//!
+//! Requests run on their own threads, so a `cancel` can reach a conversion
+//! in progress, and the documents opened last are kept for the next request
+//! on the same file. `pdf2md mcp` serves its tools over the same loop.
+
+use crate::config::Config;
+use crate::json::{self, Value};
+use crate::{Pdf2MdError, glyph_map, metadata, script};
+use log::{info, warn};
+use pdf_extract::PdfDocument;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// Documents kept open between requests
+const OPEN_DOCUMENTS: usize = 4;
+
+// Error codes of JSON-RPC 2.0, and the one LSP uses for cancelled requests
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+const CONVERSION_FAILED: i64 = -32000;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// The methods a server answers
+pub(crate) trait Methods: Sync {
+    /// Answer a request, on a thread of its own; the result is JSON
+    fn call(
+        &self,
+        server: &Server,
+        method: &str,
+        params: &Value,
+        cancelled: &AtomicBool,
+    ) -> Result<String, Failure>;
+
+    /// The id of the request a cancellation message names, if `method` is one
+    fn cancels<'a>(&self, method: &str, params: &'a Value) -> Option<Result<&'a Value, Failure>>;
+}
+
+/// The methods of `--rpc`: `convert`, `info` and `cancel`
+struct Rpc;
+
+impl Methods for Rpc {
+    fn call(
+        &self,
+        server: &Server,
+        method: &str,
+        params: &Value,
+        cancelled: &AtomicBool,
+    ) -> Result<String, Failure> {
+        match method {
+            "convert" => {
+                let output = match params.get("output") {
+                    None | Some(Value::Null) => None,
+                    Some(Value::String(path)) => Some(PathBuf::from(path)),
+                    Some(_) => return Err(Failure::new(INVALID_PARAMS, "`output` must be a path")),
+                };
+                Ok(server
+                    .convert(&input(params)?, output, cancelled)?
+                    .to_json())
+            }
+            "info" => {
+                let input = input(params)?;
+                let doc = server.open(&input)?;
+                Ok(metadata::json_line(&input, &doc)?)
+            }
+            other => Err(Failure::new(
+                METHOD_NOT_FOUND,
+                format!(
+                    "unknown method `{}` (expected convert, info or cancel)",
+                    other
+                ),
+            )),
+        }
+    }
+
+    fn cancels<'a>(&self, method: &str, params: &'a Value) -> Option<Result<&'a Value, Failure>> {
+        (method == "cancel").then(|| {
+            params
+                .get("id")
+                .ok_or_else(|| Failure::new(INVALID_PARAMS, "missing `id`"))
+        })
+    }
+}
+
+/// The `input` path of a request
+pub(crate) fn input(params: &Value) -> Result<PathBuf, Failure> {
+    params
+        .get("input")
+        .and_then(Value::as_str)
+        .map(PathBuf::from)
+        .ok_or_else(|| Failure::new(INVALID_PARAMS, "missing `input`"))
+}
+
+/// Serve requests from stdin until it is closed, converting with `config`
+pub fn serve(config: &Config) -> crate::Result<()> {
+    info!("Serving JSON-RPC on stdin and stdout");
+    serve_on(config, std::io::stdin().lock(), std::io::stdout(), &Rpc)
+}
+
+/// Serve the requests read from `input` with `methods`, writing responses to
+/// `output`; returns once the input ends and every request has been answered
+pub(crate) fn serve_on(
+    config: &Config,
+    input: impl BufRead,
+    output: impl Write + Send,
+    methods: &dyn Methods,
+) -> crate::Result<()> {
+    let server = Server {
+        config,
+        output: Mutex::new(Box::new(output)),
+        running: Mutex::new(HashMap::new()),
+        documents: Mutex::new(Vec::new()),
+    };
+    std::thread::scope(|scope| {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let request = match json::parse(&line) {
+                Ok(request) => request,
+                Err(e) => {
+                    server.respond(&Value::Null, Err(Failure::new(PARSE_ERROR, e)));
+                    continue;
+                }
+            };
+            let id = request.get("id").cloned();
+            let Some(method) = request.get("method").and_then(Value::as_str) else {
+                let failure = Failure::new(INVALID_REQUEST, "missing method");
+                server.respond(&id.unwrap_or(Value::Null), Err(failure));
+                continue;
+            };
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            let method = method.to_string();
+
+            // Cancelling is answered at once; everything else in the background
+            if let Some(target) = methods.cancels(&method, &params) {
+                let result = target.map(|target| server.cancel(target));
+                if let Some(id) = &id {
+                    server.respond(id, result);
+                }
+                continue;
+            }
+            let cancelled = Arc::new(AtomicBool::new(false));
+            if let Some(id) = &id {
+                server.running(id, Some(cancelled.clone()));
+            }
+            let server = &server;
+            scope.spawn(move || {
+                let result = methods.call(server, &method, &params, &cancelled);
+                if let Some(id) = &id {
+                    server.running(id, None);
+                    server.respond(id, result);
+                }
+            });
+        }
+        Ok(())
+    })
+}
+
+/// Why a request failed: a JSON-RPC error code and message
+#[derive(Debug)]
+pub(crate) struct Failure {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+}
+
+impl Failure {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    fn cancelled() -> Self {
+        Self::new(REQUEST_CANCELLED, "request cancelled")
+    }
+}
+
+impl From<Pdf2MdError> for Failure {
+    fn from(error: Pdf2MdError) -> Self {
+        Self::new(CONVERSION_FAILED, error.to_string())
+    }
+}
+
+impl From<pdf_extract::PdfError> for Failure {
+    fn from(error: pdf_extract::PdfError) -> Self {
+        Pdf2MdError::from(error).into()
+    }
+}
+
+impl From<markdown_gen::MarkdownError> for Failure {
+    fn from(error: markdown_gen::MarkdownError) -> Self {
+        Pdf2MdError::from(error).into()
+    }
+}
+
+impl From<std::io::Error> for Failure {
+    fn from(error: std::io::Error) -> Self {
+        Pdf2MdError::from(error).into()
+    }
+}
+
+/// A document opened for an earlier request, with the file state it was read in
+struct OpenDocument {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    len: u64,
+    document: Arc<PdfDocument>,
+}
+
+/// What a `convert` request produced
+pub(crate) struct Conversion {
+    pub(crate) markdown: String,
+    /// Where the Markdown was written, if anywhere
+    pub(crate) output: Option<PathBuf>,
+    /// Images written beside it
+    pub(crate) images: usize,
+    pub(crate) pages: usize,
+    pub(crate) failed_pages: Vec<u32>,
+}
+
+impl Conversion {
+    /// The result of `convert`: the Markdown, or the path it was written to
+    /// and the number of images, then the page counts
+    pub(crate) fn to_json(&self) -> String {
+        let failed: Vec<String> = self.failed_pages.iter().map(u32::to_string).collect();
+        let summary = format!(
+            "\"pages\":{},\"failed_pages\":[{}]",
+            self.pages,
+            failed.join(",")
+        );
+        match &self.output {
+            Some(path) => format!(
+                "{{\"output\":{},\"images\":{},{}}}",
+                json::string(&path.display().to_string()),
+                self.images,
+                summary
+            ),
+            None => format!(
+                "{{\"markdown\":{},{}}}",
+                json::string(&self.markdown),
+                summary
+            ),
+        }
+    }
+}
+
+pub(crate) struct Server<'a> {
+    pub(crate) config: &'a Config,
+    output: Mutex<Box<dyn Write + Send + 'a>>,
+    /// Cancellation flags of the requests in progress, by their JSON id
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+    /// Most recently used last
+    documents: Mutex<Vec<OpenDocument>>,
+}
+
+impl Server<'_> {
+    /// Write a response as one line
+    fn respond(&self, id: &Value, result: Result<String, Failure>) {
+        let body = match result {
+            Ok(result) => format!("\"result\":{}", result),
+            Err(failure) => format!(
+                "\"error\":{{\"code\":{},\"message\":{}}}",
+                failure.code,
+                json::string(&failure.message)
+            ),
+        };
+        let line = format!("{{\"jsonrpc\":\"2.0\",\"id\":{},{}}}\n", id.to_json(), body);
+        let mut output = self.output.lock().unwrap_or_else(|e| e.into_inner());
+        if let Err(e) = output
+            .write_all(line.as_bytes())
+            .and_then(|()| output.flush())
+        {
+            warn!("Failed to write response: {}", e);
+        }
+    }
+
+    /// Register the flag of a request in progress, or forget it once answered
+    fn running(&self, id: &Value, cancelled: Option<Arc<AtomicBool>>) {
+        let mut running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        match cancelled {
+            Some(flag) => running.insert(id.to_json(), flag),
+            None => running.remove(&id.to_json()),
+        };
+    }
+
+    /// Stop the request `id` if it is in progress, which then fails with
+    /// `REQUEST_CANCELLED`; the result says whether it was still running
+    fn cancel(&self, id: &Value) -> String {
+        let running = self.running.lock().unwrap_or_else(|e| e.into_inner());
+        let found = running.get(&id.to_json());
+        if let Some(flag) = found {
+            flag.store(true, Ordering::Relaxed);
+        }
+        format!("{{\"cancelled\":{}}}", found.is_some())
+    }
+
+    /// Convert `input`, writing the Markdown and images to `output` if given
+    pub(crate) fn convert(
+        &self,
+        input: &Path,
+        output: Option<PathBuf>,
+        cancelled: &AtomicBool,
+    ) -> Result<Conversion, Failure> {
+        let doc = self.open(input)?;
+        let mut config = self.config.clone();
+        config.input_path = input.to_path_buf();
+        let mut options = config.extract_options();
+        match &output {
+            Some(path) => config.output_path = path.clone(),
+            // Images have nowhere to go
+            None => {
+                options.images = false;
+                options.vector_graphics = false;
+            }
+        }
+        if let Some(path) = &config.glyph_map {
+            options.glyph_map = glyph_map::load(path)?;
+        }
+        let script = match &config.script {
+            Some(path) => Some(script::Script::load(path)?),
+            None => None,
+        };
+
+        let flow = || {
+            if cancelled.load(Ordering::Relaxed) {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        };
+        let mut text = String::new();
+        let mut images = Vec::new();
+        let mut content = doc.extract_text_streaming(
+            &options,
+            |piece| {
+                text.push_str(&piece);
+                flow()
+            },
+            |mut batch| {
+                images.append(&mut batch);
+                flow()
+            },
+        )?;
+        if flow().is_break() {
+            return Err(Failure::cancelled());
+        }
+        content.text = text;
+        content.images = images;
+
+        let mut markdown = crate::format_markdown(&config, &doc, &content, script.as_ref())?;
+        if let Some((page, _)) = &content.stopped_at {
+            markdown.push_str(&crate::stopped_note(*page, content.page_count));
+        }
+        if let Some(command) = &config.post_hook {
+            markdown = crate::hooks::run_hook("--post-hook", command, &markdown, input)?;
+        }
+        if let Some((_, reason)) = content.stopped_at {
+            return Err(Failure::new(CONVERSION_FAILED, reason));
+        }
+
+        if let Some(path) = &output {
+            markdown_gen::write_to_file(&markdown, path)?;
+            crate::write_images(&config, &content.images)?;
+        }
+        Ok(Conversion {
+            markdown,
+            output,
+            images: content.images.len(),
+            pages: content.page_count,
+            failed_pages: content.failed_pages,
+        })
+    }
+
+    /// The document at `path`, reused from an earlier request unless the
+    /// file changed since
+    pub(crate) fn open(&self, path: &Path) -> Result<Arc<PdfDocument>, Failure> {
+        let file = std::fs::metadata(path)?;
+        let (modified, len) = (file.modified().ok(), file.len());
+        {
+            let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(index) = documents.iter().position(|open| open.path == path) {
+                let open = documents.remove(index);
+                if open.modified == modified && open.len == len {
+                    let document = open.document.clone();
+                    documents.push(open);
+                    return Ok(document);
+                }
+            }
+        }
+
+        let mut document = PdfDocument::open(path)?;
+        document.cache_streams(self.config.stream_cache_mb * 1024 * 1024);
+        let document = Arc::new(document);
+        let mut documents = self.documents.lock().unwrap_or_else(|e| e.into_inner());
+        if documents.len() >= OPEN_DOCUMENTS {
+            documents.remove(0);
+        }
+        documents.push(OpenDocument {
+            path: path.to_path_buf(),
+            modified,
+            len,
+            document: document.clone(),
+        });
+        Ok(document)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "tests/fixtures/sample.pdf";
+
+    /// The responses to `requests`, one per line
+    fn exchange(requests: &str) -> Vec<Value> {
+        let mut output = Vec::new();
+        serve_on(&Config::default(), requests.as_bytes(), &mut output, &Rpc).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| json::parse(line).unwrap())
+            .collect()
+    }
+
+    fn by_id(responses: &[Value], id: f64) -> &Value {
+        responses
+            .iter()
+            .find(|response| response.get("id") == Some(&Value::Number(id)))
+            .unwrap()
+    }
+
+    fn error_code(response: &Value) -> Option<f64> {
+        match response.get("error").and_then(|error| error.get("code")) {
+            Some(Value::Number(code)) => Some(*code),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_rpc_errors() {
+        let responses = exchange(concat!(
+            "not json\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":1}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":2,\"method\":\"print\"}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":3,\"method\":\"convert\",\"params\":{}}\n",
+            "{\"jsonrpc\":\"2.0\",\"id\":4,\"method\":\"cancel\",\"params\":{\"id\":9}}\n",
+            "{\"jsonrpc\":\"2.0\",\"method\":\"info\",\"params\":{}}\n",
+        ));
+        assert_eq!(responses.len(), 5, "notifications get no response");
+        assert_eq!(error_code(&responses[0]), Some(PARSE_ERROR as f64));
+        assert_eq!(
+            error_code(by_id(&responses, 1.0)),
+            Some(INVALID_REQUEST as f64)
+        );
+        assert_eq!(
+            error_code(by_id(&responses, 2.0)),
+            Some(METHOD_NOT_FOUND as f64)
+        );
+        assert_eq!(
+            error_code(by_id(&responses, 3.0)),
+            Some(INVALID_PARAMS as f64)
+        );
+        assert_eq!(
+            by_id(&responses, 4.0).get("result").unwrap().to_json(),
+            "{\"cancelled\":false}"
+        );
+    }
+
+    #[test]
+    fn test_rpc_convert_and_info() {
+        if !Path::new(SAMPLE).exists() {
+            // Skip test if fixture doesn't exist
+            return;
+        }
+        let request = |id: u32, method: &str| {
+            format!(
+                "{{\"jsonrpc\":\"2.0\",\"id\":{},\"method\":\"{}\",\"params\":{{\"input\":\"{}\"}}}}\n",
+                id, method, SAMPLE
+            )
+        };
+        let responses = exchange(&format!("{}{}", request(1, "convert"), request(2, "info")));
+
+        let expected =
+            crate::convert_bytes(&std::fs::read(SAMPLE).unwrap(), &Config::default()).unwrap();
+        let result = by_id(&responses, 1.0).get("result").unwrap();
+        assert_eq!(
+            result.get("markdown").and_then(Value::as_str),
+            Some(&*expected)
+        );
+        assert_eq!(result.get("failed_pages"), Some(&Value::Array(Vec::new())));
+        let info = by_id(&responses, 2.0).get("result").unwrap();
+        assert_eq!(info.get("file").and_then(Value::as_str), Some(SAMPLE));
+    }
+}
@@ -0,0 +1,398 @@
+//! `s3://bucket/key` inputs and outputs, available when pdf2md is built with
+//! the `s3` feature
+
+use crate::config::Config;
+use std::path::Path;
+
+/// Whether a command-line path names an object in S3
+pub fn is_s3(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| path.starts_with("s3://"))
+}
+
+/// A value for curl's configuration file, in double quotes
+pub(crate) fn curl_quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(feature = "s3")]
+pub use transfer::run;
+
+/// Stand-in for builds without the `s3` feature: converting always fails
+#[cfg(not(feature = "s3"))]
+pub fn run(_config: Config) -> crate::Result<()> {
+    Err(crate::Pdf2MdError::InvalidInput(
+        "s3:// paths need pdf2md built with the `s3` feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "s3")]
+mod transfer {
+    use super::{Config, curl_quote, is_s3};
+    use crate::{Pdf2MdError, Result};
+    use log::info;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use std::process::{Command, Stdio};
+
+    /// Convert with the `s3://` input downloaded and the output uploaded.
+    ///
+    /// Both go through a staging directory that is removed afterwards. An
+    /// `s3://` output receives everything the conversion writes, images
+    /// included, under the prefix of its key.
+    pub fn run(mut config: Config) -> Result<()> {
+        let environment = Environment::from_env();
+        // A fresh directory of its own, which nothing else can have put there
+        let staging = tempfile::Builder::new().prefix("pdf2md-s3-").tempdir()?;
+
+        if is_s3(&config.input_path) {
+            let source = Location::parse(&config.input_path)?;
+            let local = staging.path().join("input").join(source.file_name());
+            info!("Downloading {}", source);
+            environment.transfer(&source, Transfer::Download(&local))?;
+            config.input_path = local;
+        }
+
+        let output_dir = staging.path().join("output");
+        let target = if is_s3(&config.output_path) {
+            let target = Location::parse(&config.output_path)?;
+            config.output_path = output_dir.join(target.file_name());
+            Some(target)
+        } else {
+            None
+        };
+
+        crate::convert(config)?;
+
+        if let Some(target) = target {
+            for file in files_under(&output_dir)? {
+                let relative = file.strip_prefix(&output_dir).expect("file is under dir");
+                let location = target.sibling(relative);
+                info!("Uploading {}", location);
+                environment.transfer(&location, Transfer::Upload(&file))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// An object named by `s3://bucket/key`
+    #[derive(Debug, Clone, PartialEq)]
+    struct Location {
+        bucket: String,
+        key: String,
+    }
+
+    impl Location {
+        fn parse(path: &Path) -> Result<Self> {
+            let invalid =
+                |reason: &str| Pdf2MdError::InvalidInput(format!("{}: {}", path.display(), reason));
+            let rest = path
+                .to_str()
+                .and_then(|path| path.strip_prefix("s3://"))
+                .ok_or_else(|| invalid("not an s3:// path"))?;
+            let (bucket, key) = rest
+                .split_once('/')
+                .ok_or_else(|| invalid("expected s3://bucket/key"))?;
+            if bucket.is_empty() || key.is_empty() {
+                return Err(invalid("expected s3://bucket/key"));
+            }
+            if key.ends_with('/') {
+                return Err(invalid("names a prefix; give the key of one object"));
+            }
+            Ok(Self {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+            })
+        }
+
+        fn file_name(&self) -> &str {
+            self.key.rsplit('/').next().unwrap_or(&self.key)
+        }
+
+        /// The object at `relative` beside this one
+        fn sibling(&self, relative: &Path) -> Self {
+            let prefix = match self.key.rfind('/') {
+                Some(slash) => &self.key[..=slash],
+                None => "",
+            };
+            let relative: Vec<_> = relative
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect();
+            Self {
+                bucket: self.bucket.clone(),
+                key: format!("{}{}", prefix, relative.join("/")),
+            }
+        }
+    }
+
+    impl std::fmt::Display for Location {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "s3://{}/{}", self.bucket, self.key)
+        }
+    }
+
+    enum Transfer<'a> {
+        Download(&'a Path),
+        Upload(&'a Path),
+    }
+
+    /// Where and as whom to reach S3, from the standard AWS variables
+    #[derive(Debug, Default)]
+    struct Environment {
+        region: String,
+        /// `AWS_ENDPOINT_URL`, for S3-compatible stores such as MinIO
+        endpoint: Option<String>,
+        access_key_id: Option<String>,
+        secret_access_key: Option<String>,
+        session_token: Option<String>,
+    }
+
+    impl Environment {
+        fn from_env() -> Self {
+            Self::from_vars(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+        }
+
+        fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+            Self {
+                region: var("AWS_REGION")
+                    .or_else(|| var("AWS_DEFAULT_REGION"))
+                    .unwrap_or_else(|| "us-east-1".to_string()),
+                endpoint: var("AWS_ENDPOINT_URL_S3").or_else(|| var("AWS_ENDPOINT_URL")),
+                access_key_id: var("AWS_ACCESS_KEY_ID"),
+                secret_access_key: var("AWS_SECRET_ACCESS_KEY"),
+                session_token: var("AWS_SESSION_TOKEN"),
+            }
+        }
+
+        fn url(&self, location: &Location) -> String {
+            let key = encode_key(&location.key);
+            match &self.endpoint {
+                Some(endpoint) => format!(
+                    "{}/{}/{}",
+                    endpoint.trim_end_matches('/'),
+                    location.bucket,
+                    key
+                ),
+                // Dotted bucket names do not match the wildcard certificate
+                None if location.bucket.contains('.') => format!(
+                    "https://s3.{}.amazonaws.com/{}/{}",
+                    self.region, location.bucket, key
+                ),
+                None => format!(
+                    "https://{}.s3.{}.amazonaws.com/{}",
+                    location.bucket, self.region, key
+                ),
+            }
+        }
+
+        /// The curl configuration for a transfer. It is passed on stdin so
+        /// the credentials never appear in the process list
+        fn curl_config(&self, location: &Location, transfer: &Transfer) -> String {
+            let mut lines = vec![("url", self.url(location))];
+            if let (Some(id), Some(secret)) = (&self.access_key_id, &self.secret_access_key) {
+                lines.push(("aws-sigv4", format!("aws:amz:{}:s3", self.region)));
+                lines.push(("user", format!("{}:{}", id, secret)));
+                lines.push((
+                    "header",
+                    "x-amz-content-sha256: UNSIGNED-PAYLOAD".to_string(),
+                ));
+                if let Some(token) = &self.session_token {
+                    lines.push(("header", format!("x-amz-security-token: {}", token)));
+                }
+            }
+            match transfer {
+                Transfer::Download(path) => {
+                    lines.push(("output", path.to_string_lossy().into_owned()));
+                }
+                Transfer::Upload(path) => {
+                    if let Some(content_type) = content_type(path) {
+                        lines.push(("header", format!("content-type: {}", content_type)));
+                    }
+                    lines.push(("upload-file", path.to_string_lossy().into_owned()));
+                }
+            }
+            lines
+                .into_iter()
+                .map(|(name, value)| format!("{} = {}\n", name, curl_quote(&value)))
+                .collect()
+        }
+
+        fn transfer(&self, location: &Location, transfer: Transfer) -> Result<()> {
+            if let Transfer::Download(path) = transfer
+                && let Some(parent) = path.parent()
+            {
+                std::fs::create_dir_all(parent)?;
+            }
+            let failed =
+                |reason: String| std::io::Error::other(format!("{}: {}", location, reason));
+
+            let mut child = Command::new("curl")
+                .args(["--silent", "--show-error", "--fail", "--config", "-"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| failed(format!("curl could not be started: {}", e)))?;
+            let mut stdin = child.stdin.take().expect("stdin is piped");
+            stdin.write_all(self.curl_config(location, &transfer).as_bytes())?;
+            drop(stdin);
+
+            let output = child.wait_with_output()?;
+            if !output.status.success() {
+                let message = String::from_utf8_lossy(&output.stderr);
+                return Err(failed(message.trim().trim_start_matches("curl: ").to_string()).into());
+            }
+            Ok(())
+        }
+    }
+
+    /// Percent-encode a key for the URL path, keeping its slashes
+    fn encode_key(key: &str) -> String {
+        let mut encoded = String::with_capacity(key.len());
+        for byte in key.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                    encoded.push(byte as char)
+                }
+                _ => encoded.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        encoded
+    }
+
+    fn content_type(path: &Path) -> Option<&'static str> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        Some(match extension.as_str() {
+            "md" | "qmd" => "text/markdown; charset=utf-8",
+            "json" => "application/json",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "svg" => "image/svg+xml",
+            _ => return None,
+        })
+    }
+
+    /// Every file under `dir`, in a stable order
+    fn files_under(dir: &Path) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        if !dir.exists() {
+            return Ok(files);
+        }
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in std::fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn location(path: &str) -> Location {
+            Location::parse(Path::new(path)).unwrap()
+        }
+
+        #[test]
+        fn test_location() {
+            let report = location("s3://lake/reports/2024/q1 summary.pdf");
+            assert_eq!(report.bucket, "lake");
+            assert_eq!(report.key, "reports/2024/q1 summary.pdf");
+            assert_eq!(report.file_name(), "q1 summary.pdf");
+            assert_eq!(
+                report.sibling(Path::new("images/img-1.png")).to_string(),
+                "s3://lake/reports/2024/images/img-1.png"
+            );
+            assert_eq!(
+                location("s3://lake/a.md").sibling(Path::new("a.md")).key,
+                "a.md"
+            );
+
+            for bad in ["s3://lake", "s3://lake/", "s3:///a.pdf", "s3://lake/docs/"] {
+                assert!(Location::parse(Path::new(bad)).is_err(), "{}", bad);
+            }
+        }
+
+        #[test]
+        fn test_url() {
+            let anonymous = Environment::from_vars(|_| None);
+            assert_eq!(
+                anonymous.url(&location("s3://lake/q1 report+.pdf")),
+                "https://lake.s3.us-east-1.amazonaws.com/q1%20report%2B.pdf"
+            );
+            assert_eq!(
+                anonymous.url(&location("s3://docs.example.com/a/b.pdf")),
+                "https://s3.us-east-1.amazonaws.com/docs.example.com/a/b.pdf"
+            );
+
+            let minio = Environment::from_vars(|name| match name {
+                "AWS_ENDPOINT_URL" => Some("http://localhost:9000/".to_string()),
+                "AWS_DEFAULT_REGION" => Some("eu-west-1".to_string()),
+                _ => None,
+            });
+            assert_eq!(minio.region, "eu-west-1");
+            assert_eq!(
+                minio.url(&location("s3://lake/a.pdf")),
+                "http://localhost:9000/lake/a.pdf"
+            );
+        }
+
+        #[test]
+        fn test_curl_config() {
+            let signed = Environment::from_vars(|name| {
+                Some(
+                    match name {
+                        "AWS_REGION" => "eu-central-1",
+                        "AWS_ACCESS_KEY_ID" => "AKID",
+                        "AWS_SECRET_ACCESS_KEY" => "se\"cret",
+                        "AWS_SESSION_TOKEN" => "token",
+                        _ => return None,
+                    }
+                    .to_string(),
+                )
+            });
+            let config = signed.curl_config(
+                &location("s3://lake/out/a.md"),
+                &Transfer::Upload(Path::new("/tmp/a.md")),
+            );
+            assert_eq!(
+                config,
+                "url = \"https://lake.s3.eu-central-1.amazonaws.com/out/a.md\"\n\
+                 aws-sigv4 = \"aws:amz:eu-central-1:s3\"\n\
+                 user = \"AKID:se\\\"cret\"\n\
+                 header = \"x-amz-content-sha256: UNSIGNED-PAYLOAD\"\n\
+                 header = \"x-amz-security-token: token\"\n\
+                 header = \"content-type: text/markdown; charset=utf-8\"\n\
+                 upload-file = \"/tmp/a.md\"\n"
+            );
+
+            let anonymous = Environment::from_vars(|_| None).curl_config(
+                &location("s3://lake/a.pdf"),
+                &Transfer::Download(Path::new("/tmp/a.pdf")),
+            );
+            assert!(!anonymous.contains("aws-sigv4"));
+            assert!(anonymous.ends_with("output = \"/tmp/a.pdf\"\n"));
+        }
+    }
+}
@@ -0,0 +1,211 @@
+//! `--sanitize`: control characters, Unicode noncharacters and the
+//! replacement characters left by undecodable text (invalid UTF-8, lone
+//! UTF-16 surrogates) taken out of the extracted text before it is
+//! formatted, along with the zero-width joiners and bidi controls of
+//! `--invisible-chars`, which is then put in the `--normalize` form
+
+use crate::Result;
+use crate::cli::{InvisibleChars, Normalization, Sanitize};
+use crate::i18n::{Message, tr, trf};
+use crate::normalize::normalize;
+use crate::style;
+use std::borrow::Cow;
+
+/// Cleans the pieces of one document's text, counting what it found
+#[derive(Debug)]
+pub struct Sanitizer {
+    policy: Sanitize,
+    invisible: InvisibleChars,
+    normalization: Normalization,
+    found: usize,
+    invisible_found: usize,
+}
+
+impl Sanitizer {
+    pub fn new(policy: Sanitize, invisible: InvisibleChars, normalization: Normalization) -> Self {
+        Self {
+            policy,
+            invisible,
+            normalization,
+            found: 0,
+            invisible_found: 0,
+        }
+    }
+
+    /// The text with each unwanted character stripped or replaced by U+FFFD
+    /// (with `--sanitize error`, an error naming the first one) and invisible
+    /// characters counted or stripped, normalized
+    pub fn clean<'a>(&mut self, text: &'a str) -> Result<Cow<'a, str>> {
+        let text = self.strip(text)?;
+        let text = then(text, |text| self.strip_invisible(text));
+        Ok(then(text, |text| normalize(text, self.normalization)))
+    }
+
+    fn strip_invisible<'a>(&mut self, text: &'a str) -> Cow<'a, str> {
+        if self.invisible == InvisibleChars::Keep || !text.contains(is_invisible) {
+            return Cow::Borrowed(text);
+        }
+        self.invisible_found += text.chars().filter(|&c| is_invisible(c)).count();
+        match self.invisible {
+            InvisibleChars::Strip => {
+                Cow::Owned(text.chars().filter(|&c| !is_invisible(c)).collect())
+            }
+            _ => Cow::Borrowed(text),
+        }
+    }
+
+    fn strip<'a>(&mut self, text: &'a str) -> Result<Cow<'a, str>> {
+        let Some(first) = text.find(is_unwanted) else {
+            return Ok(Cow::Borrowed(text));
+        };
+        let replacement = match self.policy {
+            Sanitize::Strip => None,
+            Sanitize::Replace => Some('\u{FFFD}'),
+            Sanitize::Error => {
+                let c = text[first..]
+                    .chars()
+                    .next()
+                    .expect("found at a char boundary");
+                return Err(pdf_extract::PdfError::Processing(format!(
+                    "the extracted text contains U+{:04X} ({}); convert with --sanitize strip or --sanitize replace",
+                    c as u32,
+                    describe(c)
+                ))
+                .into());
+            }
+        };
+
+        let mut cleaned = String::with_capacity(text.len());
+        cleaned.push_str(&text[..first]);
+        for c in text[first..].chars() {
+            if is_unwanted(c) {
+                self.found += 1;
+                cleaned.extend(replacement);
+            } else {
+                cleaned.push(c);
+            }
+        }
+        Ok(Cow::Owned(cleaned))
+    }
+
+    /// Warn about what was found, stripped or replaced, once the text is done
+    pub fn finish(self) {
+        if self.found > 0 {
+            let message = match self.policy {
+                Sanitize::Replace => Message::SanitizedReplaced,
+                _ => Message::SanitizedStripped,
+            };
+            warn(message, self.found);
+        }
+        if self.invisible_found > 0 {
+            let message = match self.invisible {
+                InvisibleChars::Strip => Message::InvisibleStripped,
+                _ => Message::InvisibleFound,
+            };
+            warn(message, self.invisible_found);
+        }
+    }
+}
+
+/// The text after a pass that may borrow from it
+fn then<'a>(text: Cow<'a, str>, pass: impl FnOnce(&str) -> Cow<'_, str>) -> Cow<'a, str> {
+    match text {
+        Cow::Borrowed(text) => pass(text),
+        Cow::Owned(text) => Cow::Owned(pass(&text).into_owned()),
+    }
+}
+
+fn warn(message: Message, n: usize) {
+    eprintln!(
+        "{}: {}",
+        style::warning(tr(Message::Warning)),
+        trf(message, &[("n", &n)])
+    );
+}
+
+/// Characters that corrupt a text file or stand for text that was lost:
+/// controls other than tab and line breaks, noncharacters, and U+FFFD
+fn is_unwanted(c: char) -> bool {
+    (c.is_control() && !matches!(c, '\t' | '\n' | '\r')) || is_noncharacter(c) || c == '\u{FFFD}'
+}
+
+/// Zero-width non-joiner and joiner, and the bidi marks, embeddings,
+/// overrides and isolates (U+200B and the byte order mark go in cleanup)
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200C}' | '\u{200D}' | '\u{200E}' | '\u{200F}' | '\u{061C}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2066}'..='\u{2069}'
+    )
+}
+
+/// U+FDD0 to U+FDEF, and the last two code points of every plane
+fn is_noncharacter(c: char) -> bool {
+    ('\u{FDD0}'..='\u{FDEF}').contains(&c) || (c as u32) & 0xFFFE == 0xFFFE
+}
+
+fn describe(c: char) -> &'static str {
+    if c.is_control() {
+        "a control character"
+    } else if c == '\u{FFFD}' {
+        "undecodable text"
+    } else {
+        "a noncharacter"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "Total\u{7}: 5\u{FFFD}0\u{FFFF}\tok\r\n\u{85}";
+
+    #[test]
+    fn test_strip() {
+        let mut sanitizer =
+            Sanitizer::new(Sanitize::Strip, InvisibleChars::Keep, Normalization::None);
+        assert_eq!(sanitizer.clean(TEXT).unwrap(), "Total: 50\tok\r\n");
+        assert!(matches!(
+            sanitizer.clean("clean text").unwrap(),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(sanitizer.found, 4);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut sanitizer =
+            Sanitizer::new(Sanitize::Replace, InvisibleChars::Keep, Normalization::None);
+        assert_eq!(
+            sanitizer.clean(TEXT).unwrap(),
+            "Total\u{FFFD}: 5\u{FFFD}0\u{FFFD}\tok\r\n\u{FFFD}"
+        );
+        assert_eq!(sanitizer.found, 4);
+    }
+
+    #[test]
+    fn test_error() {
+        let mut sanitizer =
+            Sanitizer::new(Sanitize::Error, InvisibleChars::Keep, Normalization::None);
+        let error = sanitizer.clean(TEXT).unwrap_err().to_string();
+        assert!(error.contains("U+0007 (a control character)"), "{}", error);
+        assert!(sanitizer.clean("clean\ttext\n").is_ok());
+    }
+
+    #[test]
+    fn test_invisible_chars() {
+        let text = "\u{202B}a\u{200D}b\u{200C}c\u{202C} \u{2067}d\u{2069}\u{200F}";
+        let mut strip = Sanitizer::new(Sanitize::Strip, InvisibleChars::Strip, Normalization::None);
+        assert_eq!(strip.clean(text).unwrap(), "abc d");
+        assert_eq!(strip.invisible_found, 7);
+
+        let mut warn = Sanitizer::new(Sanitize::Strip, InvisibleChars::Warn, Normalization::None);
+        assert!(matches!(warn.clean(text).unwrap(), Cow::Borrowed(_)));
+        assert_eq!(warn.invisible_found, 7);
+
+        let mut keep = Sanitizer::new(Sanitize::Strip, InvisibleChars::Keep, Normalization::None);
+        assert_eq!(keep.clean(text).unwrap(), text);
+        assert_eq!(keep.invisible_found, 0);
+    }
+}
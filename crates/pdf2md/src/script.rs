@@ -0,0 +1,200 @@
+//! Block transformation scripts (`--script`), available when pdf2md is built
+//! with the `scripting` feature
+
+#[cfg(feature = "scripting")]
+pub use engine::Script;
+
+/// Stand-in for builds without the `scripting` feature: loading always fails
+#[cfg(not(feature = "scripting"))]
+pub enum Script {}
+
+#[cfg(not(feature = "scripting"))]
+impl Script {
+    pub fn load(_path: &std::path::Path) -> crate::Result<Self> {
+        Err(crate::Pdf2MdError::InvalidInput(
+            "--script needs pdf2md built with the `scripting` feature".to_string(),
+        ))
+    }
+
+    pub fn run(&self, _markdown: &str) -> crate::Result<String> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use crate::{Pdf2MdError, Result};
+    use log::info;
+    use rhai::{AST, Array, Dynamic, Engine, Map, Scope};
+    use std::path::{Path, PathBuf};
+
+    /// A compiled Rhai script defining `fn transform(blocks)`.
+    ///
+    /// `blocks` is an array of maps, `#{kind: "heading", level: 2, text: "..."}`
+    /// or `#{kind: "text", text: "..."}`; the array `transform` returns becomes
+    /// the document, so blocks can be dropped, reordered, retitled or added.
+    pub struct Script {
+        path: PathBuf,
+        engine: Engine,
+        ast: AST,
+    }
+
+    impl Script {
+        /// Read and compile a script, so mistakes show up before extraction
+        pub fn load(path: &Path) -> Result<Self> {
+            let source = std::fs::read_to_string(path)?;
+            let engine = Engine::new();
+            let script =
+                |e: String| Pdf2MdError::InvalidInput(format!("script {}: {}", path.display(), e));
+            let ast = engine.compile(&source).map_err(|e| script(e.to_string()))?;
+            if !ast
+                .iter_functions()
+                .any(|f| f.name == "transform" && f.params.len() == 1)
+            {
+                return Err(script("no `fn transform(blocks)` defined".to_string()));
+            }
+            Ok(Self {
+                path: path.to_path_buf(),
+                engine,
+                ast,
+            })
+        }
+
+        /// Pass the Markdown's blocks through `transform` and render the result
+        pub fn run(&self, markdown: &str) -> Result<String> {
+            info!("Running script {}", self.path.display());
+            let failed = |e: String| {
+                Pdf2MdError::InvalidInput(format!("script {}: {}", self.path.display(), e))
+            };
+            let blocks: Array = split(markdown).into_iter().map(Dynamic::from).collect();
+            let result: Array = self
+                .engine
+                .call_fn(&mut Scope::new(), &self.ast, "transform", (blocks,))
+                .map_err(|e| failed(e.to_string()))?;
+            let blocks = result
+                .into_iter()
+                .enumerate()
+                .map(|(i, block)| render(block).map_err(|e| failed(format!("block {}: {}", i, e))))
+                .collect::<Result<Vec<_>>>()?;
+            Ok(blocks.join("\n\n"))
+        }
+    }
+
+    /// Blank-line separated blocks, keeping code fences whole
+    fn split(markdown: &str) -> Vec<Map> {
+        let mut blocks = Vec::new();
+        let mut current: Vec<&str> = Vec::new();
+        let mut in_fence = false;
+        for line in markdown.lines() {
+            if line.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if line.trim().is_empty() && !in_fence {
+                if !current.is_empty() {
+                    blocks.push(block(&current.join("\n")));
+                    current.clear();
+                }
+            } else {
+                current.push(line);
+            }
+        }
+        if !current.is_empty() {
+            blocks.push(block(&current.join("\n")));
+        }
+        blocks
+    }
+
+    fn block(text: &str) -> Map {
+        let mut map = Map::new();
+        let level = text.chars().take_while(|&c| c == '#').count();
+        match text[level..].strip_prefix(' ') {
+            Some(title) if (1..=6).contains(&level) && !text.contains('\n') => {
+                map.insert("kind".into(), "heading".into());
+                map.insert("level".into(), (level as i64).into());
+                map.insert("text".into(), title.trim().into());
+            }
+            _ => {
+                map.insert("kind".into(), "text".into());
+                map.insert("text".into(), text.into());
+            }
+        }
+        map
+    }
+
+    /// Markdown for a block returned by the script
+    fn render(block: Dynamic) -> std::result::Result<String, String> {
+        let type_name = block.type_name();
+        let map = block
+            .try_cast::<Map>()
+            .ok_or_else(|| format!("expected a map, got {}", type_name))?;
+        let field = |key: &str| map.get(key).cloned();
+        let text = field("text")
+            .and_then(|text| text.into_string().ok())
+            .ok_or("`text` must be a string")?;
+        let kind = field("kind")
+            .and_then(|kind| kind.into_string().ok())
+            .unwrap_or_else(|| "text".to_string());
+        match kind.as_str() {
+            "heading" => {
+                let level = field("level")
+                    .and_then(|level| level.as_int().ok())
+                    .filter(|level| (1..=6).contains(level))
+                    .ok_or("a heading needs a `level` from 1 to 6")?;
+                Ok(format!("{} {}", "#".repeat(level as usize), text))
+            }
+            "text" => Ok(text),
+            other => Err(format!("unknown kind `{}`", other)),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn script(source: &str) -> Script {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("test.rhai");
+            std::fs::write(&path, source).unwrap();
+            Script::load(&path).unwrap()
+        }
+
+        #[test]
+        fn test_split_keeps_fences_whole() {
+            let blocks = split("## Intro\n\nOne\ntwo\n\n```\na\n\nb\n```");
+            assert_eq!(blocks.len(), 3);
+            assert_eq!(blocks[0]["kind"].clone().into_string().unwrap(), "heading");
+            assert_eq!(blocks[0]["level"].as_int().unwrap(), 2);
+            assert_eq!(
+                blocks[2]["text"].clone().into_string().unwrap(),
+                "```\na\n\nb\n```"
+            );
+        }
+
+        #[test]
+        fn test_run() {
+            let script = script(
+                r#"fn transform(blocks) {
+                    let out = [];
+                    for b in blocks {
+                        if b.kind == "heading" { b.text.make_upper(); }
+                        if !b.text.starts_with("Confidential") { out.push(b); }
+                    }
+                    out
+                }"#,
+            );
+            let markdown = script.run("# Title\n\nConfidential draft\n\nBody").unwrap();
+            assert_eq!(markdown, "# TITLE\n\nBody");
+        }
+
+        #[test]
+        fn test_bad_block() {
+            let script = script("fn transform(blocks) { [#{kind: \"heading\", text: \"x\"}] }");
+            let err = script.run("Body").unwrap_err().to_string();
+            assert!(
+                err.contains("block 0: a heading needs a `level`"),
+                "{}",
+                err
+            );
+        }
+    }
+}
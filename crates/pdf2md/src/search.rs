@@ -0,0 +1,527 @@
+//! `--search-index`: a prebuilt client-side search index over the converted
+//! documents, in the serialized form `lunr.Index.load` or
+//! `elasticlunr.Index.load` reads
+//!
+//! Terms are split and trimmed the way both libraries do it, with only the
+//! `trimmer` in the saved pipeline, so queries are matched without stemming.
+
+use crate::Result;
+use crate::json::{self, Value};
+use log::info;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// The release whose serialized index format is written, per library
+const LUNR_VERSION: &str = "2.3.9";
+const ELASTICLUNR_VERSION: &str = "0.9.5";
+
+/// Fields of each document, in the order they are indexed
+const FIELDS: [&str; 2] = ["title", "body"];
+
+/// BM25 parameters lunr scores terms with
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Where `--search-index` writes, and for which library
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchIndex {
+    Lunr(PathBuf),
+    Elasticlunr(PathBuf),
+}
+
+impl SearchIndex {
+    pub fn path(&self) -> &Path {
+        match self {
+            SearchIndex::Lunr(path) | SearchIndex::Elasticlunr(path) => path,
+        }
+    }
+}
+
+impl std::str::FromStr for SearchIndex {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("lunr", path)) if !path.is_empty() => Ok(SearchIndex::Lunr(path.into())),
+            Some(("elasticlunr", path)) if !path.is_empty() => {
+                Ok(SearchIndex::Elasticlunr(path.into()))
+            }
+            _ => Err(format!(
+                "unknown search index '{}' (expected lunr:<FILE> or elasticlunr:<FILE>)",
+                s
+            )),
+        }
+    }
+}
+
+/// A converted document as it is indexed
+#[derive(Debug, PartialEq)]
+struct Document {
+    /// The output's path from the output directory, with `/` separators
+    reference: String,
+    title: String,
+    /// Terms of the title and of the body, in `FIELDS` order
+    terms: [Vec<String>; 2],
+}
+
+/// Index the Markdown files in `outputs`, referred to by their path from `root`
+pub fn write(target: &SearchIndex, root: &Path, outputs: &[PathBuf]) -> Result<()> {
+    let mut documents = Vec::with_capacity(outputs.len());
+    for output in outputs {
+        let markdown = std::fs::read_to_string(output)?;
+        let reference = output
+            .strip_prefix(root)
+            .unwrap_or(output)
+            .components()
+            .map(|part| part.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        documents.push(document(reference, &stem, &markdown));
+    }
+
+    let json = match target {
+        SearchIndex::Lunr(_) => lunr(&documents),
+        SearchIndex::Elasticlunr(_) => elasticlunr(&documents),
+    };
+    info!(
+        "Writing search index of {} documents to: {}",
+        documents.len(),
+        target.path().display()
+    );
+    markdown_gen::write_to_file(&json, target.path())?;
+    Ok(())
+}
+
+/// The document of one Markdown file: titled by its first top-level
+/// heading, or else by its file name
+fn document(reference: String, stem: &str, markdown: &str) -> Document {
+    let body = plain_text(markdown);
+    let outline = markdown_gen::outline(&body);
+    let title = outline
+        .iter()
+        .min_by_key(|(level, _)| *level)
+        .map_or_else(|| stem.to_string(), |(_, text)| text.clone());
+    Document {
+        reference,
+        terms: [terms(&title), terms(&body)],
+        title,
+    }
+}
+
+/// The Markdown without its front matter and link targets, which are not
+/// searched
+fn plain_text(markdown: &str) -> String {
+    let mut lines = markdown.lines().peekable();
+    if let Some(&fence @ ("---" | "+++")) = lines.peek() {
+        lines.next();
+        for line in lines.by_ref() {
+            if line == fence {
+                break;
+            }
+        }
+    }
+
+    let mut text = String::with_capacity(markdown.len());
+    for line in lines {
+        let mut rest = line;
+        while let Some(at) = rest.find("](") {
+            text.push_str(&rest[..at]);
+            rest = &rest[at + 2..];
+            rest = rest.find(')').map_or("", |end| &rest[end + 1..]);
+        }
+        text.push_str(rest);
+        text.push('\n');
+    }
+    text
+}
+
+/// The terms of `text`: lowercased, split on whitespace and hyphens, and
+/// trimmed of the non-word characters around them
+fn terms(text: &str) -> Vec<String> {
+    // `\W` of a JavaScript regular expression, which only knows ASCII words
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    text.to_lowercase()
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .map(|token| token.trim_matches(|c| !is_word(c)))
+        .filter(|term| !term.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// How often each term occurs in a field
+fn frequencies(terms: &[String]) -> BTreeMap<&str, usize> {
+    let mut counts = BTreeMap::new();
+    for term in terms {
+        *counts.entry(term.as_str()).or_default() += 1;
+    }
+    counts
+}
+
+/// The index `lunr.Index.load` reads, with the title of each document under
+/// `documents` for showing the results
+fn lunr(documents: &[Document]) -> String {
+    // Terms in the order of JavaScript's sort, which compares UTF-16 units
+    let mut terms: Vec<&str> = documents
+        .iter()
+        .flat_map(|document| document.terms.iter().flatten())
+        .map(String::as_str)
+        .collect();
+    terms.sort_by(|a, b| a.encode_utf16().cmp(b.encode_utf16()));
+    terms.dedup();
+    let term_index: HashMap<&str, usize> = terms.iter().enumerate().map(|(i, &t)| (t, i)).collect();
+
+    // Which documents each term occurs in, per field
+    let mut postings: Vec<[Vec<&str>; 2]> = vec![Default::default(); terms.len()];
+    let fields: Vec<Vec<BTreeMap<&str, usize>>> = documents
+        .iter()
+        .map(|document| {
+            document
+                .terms
+                .iter()
+                .map(|terms| frequencies(terms))
+                .collect()
+        })
+        .collect();
+    for (document, fields) in documents.iter().zip(&fields) {
+        for (field, counts) in fields.iter().enumerate() {
+            for term in counts.keys() {
+                postings[term_index[term]][field].push(&document.reference);
+            }
+        }
+    }
+
+    let count = documents.len() as f64;
+    let average_length: Vec<f64> = (0..FIELDS.len())
+        .map(|field| {
+            let total: usize = documents.iter().map(|d| d.terms[field].len()).sum();
+            total as f64 / count
+        })
+        .collect();
+    let idf = |posting: &[Vec<&str>; 2]| {
+        let with_term = posting.iter().map(Vec::len).sum::<usize>() as f64;
+        (1.0 + ((count - with_term + 0.5) / (with_term + 0.5)).abs()).ln()
+    };
+
+    let mut vectors = Vec::new();
+    for (document, fields) in documents.iter().zip(&fields) {
+        for (field, counts) in fields.iter().enumerate() {
+            let length = document.terms[field].len() as f64;
+            let mut elements: Vec<(usize, f64)> = counts
+                .iter()
+                .map(|(term, &tf)| {
+                    let i = term_index[term];
+                    let tf = tf as f64;
+                    let score = idf(&postings[i]) * ((K1 + 1.0) * tf)
+                        / (K1 * (1.0 - B + B * (length / average_length[field])) + tf);
+                    (i, (score * 1000.0).round() / 1000.0)
+                })
+                .collect();
+            elements.sort_by_key(|&(i, _)| i);
+            vectors.push(Value::Array(vec![
+                Value::String(format!("{}/{}", FIELDS[field], document.reference)),
+                Value::Array(
+                    elements
+                        .into_iter()
+                        .flat_map(|(i, score)| [Value::Number(i as f64), Value::Number(score)])
+                        .collect(),
+                ),
+            ]));
+        }
+    }
+
+    let inverted = terms
+        .iter()
+        .zip(&postings)
+        .enumerate()
+        .map(|(i, (term, posting))| {
+            let mut members = vec![("_index".to_string(), Value::Number(i as f64))];
+            for (field, references) in FIELDS.iter().zip(posting) {
+                let references = references
+                    .iter()
+                    .map(|reference| (reference.to_string(), Value::Object(Vec::new())))
+                    .collect();
+                members.push((field.to_string(), Value::Object(references)));
+            }
+            Value::Array(vec![
+                Value::String(term.to_string()),
+                Value::Object(members),
+            ])
+        })
+        .collect();
+
+    Value::Object(vec![
+        (
+            "version".to_string(),
+            Value::String(LUNR_VERSION.to_string()),
+        ),
+        ("fields".to_string(), fields_value()),
+        ("fieldVectors".to_string(), Value::Array(vectors)),
+        ("invertedIndex".to_string(), Value::Array(inverted)),
+        ("pipeline".to_string(), pipeline_value()),
+        (
+            "documents".to_string(),
+            Value::Object(
+                documents
+                    .iter()
+                    .map(|document| {
+                        (
+                            document.reference.clone(),
+                            Value::Object(vec![(
+                                "title".to_string(),
+                                Value::String(document.title.clone()),
+                            )]),
+                        )
+                    })
+                    .collect(),
+            ),
+        ),
+    ])
+    .to_json()
+}
+
+/// A node of an elasticlunr term trie, one level per UTF-16 unit of the term
+#[derive(Default)]
+struct TrieNode<'a> {
+    /// Square root of the term's count in each document it occurs in
+    documents: Vec<(&'a str, f64)>,
+    children: BTreeMap<u16, TrieNode<'a>>,
+}
+
+impl TrieNode<'_> {
+    fn to_json(&self) -> String {
+        let documents: Vec<String> = self
+            .documents
+            .iter()
+            .map(|(reference, tf)| format!("{}:{{\"tf\":{}}}", json::string(reference), tf))
+            .collect();
+        let mut out = format!(
+            "{{\"docs\":{{{}}},\"df\":{}",
+            documents.join(","),
+            self.documents.len()
+        );
+        for (unit, child) in &self.children {
+            // Half a surrogate pair is no `char`, so it is written escaped
+            let key = match char::from_u32(u32::from(*unit)) {
+                Some(c) => json::string(&c.to_string()),
+                None => format!("\"\\u{:04x}\"", unit),
+            };
+            out.push_str(&format!(",{}:{}", key, child.to_json()));
+        }
+        out.push('}');
+        out
+    }
+}
+
+/// The index `elasticlunr.Index.load` reads; the document store keeps the
+/// title of each document for showing the results
+fn elasticlunr(documents: &[Document]) -> String {
+    let mut roots: [TrieNode; 2] = Default::default();
+    for document in documents {
+        for (root, terms) in roots.iter_mut().zip(&document.terms) {
+            for (term, count) in frequencies(terms) {
+                let mut node = &mut *root;
+                for unit in term.encode_utf16() {
+                    node = node.children.entry(unit).or_default();
+                }
+                node.documents
+                    .push((&document.reference, (count as f64).sqrt()));
+            }
+        }
+    }
+
+    let number = |n: usize| Value::Number(n as f64);
+    let store = Value::Object(vec![
+        (
+            "docs".to_string(),
+            Value::Object(
+                documents
+                    .iter()
+                    .map(|document| {
+                        (
+                            document.reference.clone(),
+                            Value::Object(vec![
+                                ("id".to_string(), Value::String(document.reference.clone())),
+                                ("title".to_string(), Value::String(document.title.clone())),
+                            ]),
+                        )
+                    })
+                    .collect(),
+            ),
+        ),
+        (
+            "docInfo".to_string(),
+            Value::Object(
+                documents
+                    .iter()
+                    .map(|document| {
+                        (
+                            document.reference.clone(),
+                            Value::Object(
+                                FIELDS
+                                    .iter()
+                                    .zip(&document.terms)
+                                    .map(|(field, terms)| (field.to_string(), number(terms.len())))
+                                    .collect(),
+                            ),
+                        )
+                    })
+                    .collect(),
+            ),
+        ),
+        ("length".to_string(), number(documents.len())),
+        ("save".to_string(), Value::Bool(true)),
+    ]);
+
+    let index: Vec<String> = FIELDS
+        .iter()
+        .zip(&roots)
+        .map(|(field, root)| format!("{}:{{\"root\":{}}}", json::string(field), root.to_json()))
+        .collect();
+    format!(
+        "{{\"version\":{},\"fields\":{},\"ref\":\"id\",\"documentStore\":{},\"index\":{{{}}},\"pipeline\":{}}}",
+        json::string(ELASTICLUNR_VERSION),
+        fields_value().to_json(),
+        store.to_json(),
+        index.join(","),
+        pipeline_value().to_json()
+    )
+}
+
+fn fields_value() -> Value {
+    Value::Array(
+        FIELDS
+            .iter()
+            .map(|field| Value::String(field.to_string()))
+            .collect(),
+    )
+}
+
+/// The functions run on each query term, by their registered names
+fn pipeline_value() -> Value {
+    Value::Array(vec![Value::String("trimmer".to_string())])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn documents() -> Vec<Document> {
+        vec![
+            document(
+                "a.md".to_string(),
+                "a",
+                "---\ntitle: x\n---\n# Heat Engines\n\nEntropy, and [more entropy](b.md).",
+            ),
+            document("sub/b.md".to_string(), "b", "Work-energy theorem."),
+        ]
+    }
+
+    #[test]
+    fn test_search_index_target() {
+        assert_eq!(
+            "lunr:site/search.json".parse(),
+            Ok(SearchIndex::Lunr("site/search.json".into()))
+        );
+        assert_eq!(
+            "elasticlunr:s.json".parse(),
+            Ok(SearchIndex::Elasticlunr("s.json".into()))
+        );
+        assert!("lunr:".parse::<SearchIndex>().is_err());
+        assert!("pagefind:x".parse::<SearchIndex>().is_err());
+    }
+
+    #[test]
+    fn test_documents() {
+        let documents = documents();
+        assert_eq!(documents[0].title, "Heat Engines");
+        assert_eq!(
+            documents[0].terms[1],
+            ["heat", "engines", "entropy", "and", "more", "entropy"]
+        );
+        assert_eq!(documents[1].title, "b");
+        assert_eq!(documents[1].terms[1], ["work", "energy", "theorem"]);
+    }
+
+    #[test]
+    fn test_lunr_index() {
+        let index = json::parse(&lunr(&documents())).unwrap();
+        let Some(Value::Array(inverted)) = index.get("invertedIndex") else {
+            panic!("no inverted index");
+        };
+        let terms: Vec<&str> = inverted
+            .iter()
+            .map(|entry| match entry {
+                Value::Array(pair) => pair[0].as_str().unwrap(),
+                _ => panic!("bad entry"),
+            })
+            .collect();
+        assert_eq!(
+            terms,
+            [
+                "and", "b", "energy", "engines", "entropy", "heat", "more", "theorem", "work"
+            ]
+        );
+        let Value::Array(entropy) = &inverted[4] else {
+            panic!("bad entry");
+        };
+        assert_eq!(
+            entropy[1].to_json(),
+            r#"{"_index":4,"title":{},"body":{"a.md":{}}}"#
+        );
+
+        let Some(Value::Array(vectors)) = index.get("fieldVectors") else {
+            panic!("no field vectors");
+        };
+        let Value::Array(body) = &vectors[1] else {
+            panic!("bad vector");
+        };
+        assert_eq!(body[0].as_str(), Some("body/a.md"));
+        let Value::Array(elements) = &body[1] else {
+            panic!("bad vector");
+        };
+        let indexes: Vec<&Value> = elements.iter().step_by(2).collect();
+        assert_eq!(
+            indexes,
+            [0.0, 3.0, 4.0, 5.0, 6.0]
+                .map(Value::Number)
+                .iter()
+                .collect::<Vec<_>>()
+        );
+        // "entropy" twice in six terms, against an average of 4.5, in one
+        // of two documents
+        let idf = (1.0f64 + 1.5 / 1.5).ln();
+        let score = idf * 2.2 * 2.0 / (1.2 * (0.25 + 0.75 * 6.0 / 4.5) + 2.0);
+        assert_eq!(
+            elements[5],
+            Value::Number((score * 1000.0).round() / 1000.0)
+        );
+    }
+
+    #[test]
+    fn test_elasticlunr_index() {
+        let index = json::parse(&elasticlunr(&documents())).unwrap();
+        let body = index
+            .get("index")
+            .and_then(|index| index.get("body"))
+            .and_then(|body| body.get("root"))
+            .unwrap();
+        let node = "entropy"
+            .chars()
+            .try_fold(body, |node, c| node.get(&c.to_string()))
+            .unwrap();
+        assert_eq!(node.get("df"), Some(&Value::Number(1.0)));
+        assert_eq!(
+            node.get("docs").unwrap().to_json(),
+            format!(r#"{{"a.md":{{"tf":{}}}}}"#, 2f64.sqrt())
+        );
+        assert_eq!(
+            index
+                .get("documentStore")
+                .and_then(|store| store.get("docInfo"))
+                .unwrap()
+                .to_json(),
+            r#"{"a.md":{"title":2,"body":6},"sub/b.md":{"title":1,"body":3}}"#
+        );
+    }
+}
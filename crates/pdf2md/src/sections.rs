@@ -0,0 +1,144 @@
+use std::path::{Component, Path};
+
+/// A `[[section]]` rule from the settings file: where a chapter whose heading
+/// matches is written when a site layout splits the output
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectionRule {
+    /// Heading pattern; `*` matches any run of characters and `?` any one
+    pub heading: String,
+    /// File path relative to the docs directory; `{slug}` and `{n}` (the
+    /// chapter number, `01`, `02`, ...) are filled in
+    pub path: String,
+}
+
+impl SectionRule {
+    /// Read a `[[section]]` table: `heading = "..."` and `path = "..."`
+    pub fn from_toml(value: &toml::Value) -> Result<Self, String> {
+        let table = value
+            .as_table()
+            .ok_or("`section` must be a list of tables ([[section]])")?;
+        if let Some(key) = table
+            .keys()
+            .find(|key| !matches!(key.as_str(), "heading" | "path"))
+        {
+            return Err(format!("unknown key `{}` in a [[section]] rule", key));
+        }
+        let heading = table
+            .get("heading")
+            .and_then(toml::Value::as_str)
+            .ok_or("a [[section]] rule needs a `heading` string")?;
+        let path = table
+            .get("path")
+            .and_then(toml::Value::as_str)
+            .ok_or("a [[section]] rule needs a `path` string")?;
+
+        let filled = path.replace("{slug}", "").replace("{n}", "");
+        if let Some(start) = filled.find('{') {
+            let placeholder = filled[start..].split_inclusive('}').next().unwrap_or("{");
+            return Err(format!(
+                "unknown placeholder `{}` in [[section]] path `{}` (use {{slug}} or {{n}})",
+                placeholder, path
+            ));
+        }
+        if !Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            return Err(format!(
+                "[[section]] path `{}` must stay inside the docs directory",
+                path
+            ));
+        }
+        Ok(Self {
+            heading: heading.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
+/// File of chapter `number` titled `title`: the path of the first rule whose
+/// heading pattern matches, or `NN-<slug>.md`
+pub fn file_name(rules: &[SectionRule], title: &str, slug: &str, number: usize) -> String {
+    let n = format!("{:02}", number);
+    match rules.iter().find(|rule| glob_match(&rule.heading, title)) {
+        Some(rule) => rule.path.replace("{slug}", slug).replace("{n}", &n),
+        None => format!("{}-{}.md", n, slug),
+    }
+}
+
+/// Whether `text` matches the whole of `pattern`, with `*` and `?` wildcards
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` seen and the text position it resumes at
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((after, from)) => {
+                    p = after;
+                    t = from + 1;
+                    star = Some((after, from + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(toml: &str) -> Result<SectionRule, String> {
+        SectionRule::from_toml(&toml::Value::Table(toml.parse().unwrap()))
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("Appendix *", "Appendix A: Tables"));
+        assert!(glob_match("*Notes", "Release Notes"));
+        assert!(glob_match("Part ?", "Part 2"));
+        assert!(!glob_match("Part ?", "Part 12"));
+        assert!(!glob_match("Appendix *", "Appendices"));
+    }
+
+    #[test]
+    fn test_file_name() {
+        let rules = [
+            rule("heading = \"Appendix *\"\npath = \"appendices/{slug}.md\"").unwrap(),
+            rule("heading = \"*\"\npath = \"guide/{n}-{slug}.md\"").unwrap(),
+        ];
+        assert_eq!(
+            file_name(&rules, "Appendix B", "appendix-b", 7),
+            "appendices/appendix-b.md"
+        );
+        assert_eq!(file_name(&rules, "Setup", "setup", 2), "guide/02-setup.md");
+        assert_eq!(file_name(&[], "Setup", "setup", 2), "02-setup.md");
+    }
+
+    #[test]
+    fn test_bad_rules() {
+        assert!(rule("heading = \"*\"").is_err());
+        assert!(
+            rule("heading = \"*\"\npath = \"{title}.md\"")
+                .unwrap_err()
+                .contains("`{title}`")
+        );
+        assert!(
+            rule("heading = \"*\"\npath = \"../outside.md\"")
+                .unwrap_err()
+                .contains("inside the docs directory")
+        );
+    }
+}
@@ -0,0 +1,299 @@
+//! `--sidecar`: the metadata, statistics, warnings and page map of a
+//! conversion, written next to the Markdown as `<name>.meta.json` or
+//! `<name>.meta.yaml`
+
+use crate::cli::SidecarFormat;
+use crate::config::Config;
+use crate::i18n::{Message, trf};
+use crate::json::{self, Value};
+use crate::provenance::is_page_mark;
+use crate::{Result, metadata, redaction_warnings};
+use log::info;
+use pdf_extract::{ExtractedContent, PdfDocument};
+use std::path::PathBuf;
+
+/// Letters and digits of a page's opening words looked for in the Markdown
+const PAGE_START_CHARS: usize = 24;
+
+/// Where the sidecar of the output goes: `report.md` gets `report.meta.json`
+pub fn path(config: &Config, format: SidecarFormat) -> PathBuf {
+    config.output_path.with_extension(match format {
+        SidecarFormat::Json => "meta.json",
+        SidecarFormat::Yaml => "meta.yaml",
+    })
+}
+
+/// Write the sidecar of a conversion whose Markdown is `markdown`
+pub fn write(
+    config: &Config,
+    format: SidecarFormat,
+    doc: &PdfDocument,
+    content: &ExtractedContent,
+    markdown: &str,
+) -> Result<()> {
+    let report = report(config, doc, content, markdown)?;
+    let text = match format {
+        SidecarFormat::Json => report.to_pretty_json() + "\n",
+        SidecarFormat::Yaml => to_yaml(&report),
+    };
+    let path = path(config, format);
+    info!("Writing sidecar to: {}", path.display());
+    markdown_gen::write_to_file(&text, &path)?;
+    Ok(())
+}
+
+fn report(
+    config: &Config,
+    doc: &PdfDocument,
+    content: &ExtractedContent,
+    markdown: &str,
+) -> Result<Value> {
+    let metadata = json::parse(&metadata::json_line(&config.input_path, doc)?)
+        .expect("the metadata report is valid JSON");
+    let output = config
+        .output_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let warnings = warnings(doc, content)?
+        .into_iter()
+        .map(Value::String)
+        .collect();
+
+    let number = |n: usize| Value::Number(n as f64);
+    let stats = vec![
+        ("pages".to_string(), number(content.page_count)),
+        (
+            "failed_pages".to_string(),
+            Value::Array(
+                content
+                    .failed_pages
+                    .iter()
+                    .map(|&page| number(page as usize))
+                    .collect(),
+            ),
+        ),
+        (
+            "headings".to_string(),
+            number(markdown_gen::outline(markdown).len()),
+        ),
+        ("images".to_string(), number(markdown.matches("![").count())),
+        ("footnotes".to_string(), number(content.footnotes.len())),
+        (
+            "words".to_string(),
+            number(markdown.split_whitespace().count()),
+        ),
+        ("characters".to_string(), number(markdown.chars().count())),
+        ("lines".to_string(), number(markdown.lines().count())),
+    ];
+
+    let pages = page_lines(content, markdown)
+        .into_iter()
+        .map(|(page, line)| {
+            Value::Object(vec![
+                ("page".to_string(), number(page as usize)),
+                ("line".to_string(), line.map_or(Value::Null, number)),
+            ])
+        })
+        .collect();
+
+    Ok(Value::Object(vec![
+        ("output".to_string(), Value::String(output)),
+        (
+            "converter".to_string(),
+            Value::String(format!("pdf2md {}", env!("CARGO_PKG_VERSION"))),
+        ),
+        ("metadata".to_string(), metadata),
+        ("stats".to_string(), Value::Object(stats)),
+        ("warnings".to_string(), Value::Array(warnings)),
+        ("page_map".to_string(), Value::Array(pages)),
+    ]))
+}
+
+/// Compatibility warnings, pages that could not be extracted, text left out
+/// because a redaction hides it, and why a conversion stopped
+pub(crate) fn warnings(doc: &PdfDocument, content: &ExtractedContent) -> Result<Vec<String>> {
+    let mut warnings = doc.compatibility()?.warnings();
+    warnings.extend(
+        content
+            .failed_pages
+            .iter()
+            .map(|page| format!("page {} could not be extracted", page)),
+    );
+    warnings.extend(redaction_warnings(&content.redactions));
+    if let Some((_, reason)) = &content.stopped_at {
+        warnings.push(reason.clone());
+    }
+    Ok(warnings)
+}
+
+/// The line of the Markdown, from 1, on which each page begins; `None` for
+/// pages without text and pages whose opening words were rewritten past
+/// recognition
+pub(crate) fn page_lines(content: &ExtractedContent, markdown: &str) -> Vec<(u32, Option<usize>)> {
+    let (line_starts, offsets) = page_offsets(content, markdown);
+    offsets
+        .into_iter()
+        .map(|(page, offset)| {
+            // The last line starting at or before the page
+            let line = offset.map(|at| line_starts.partition_point(|&start| start <= at));
+            (page, line)
+        })
+        .collect()
+}
+
+/// Where each page begins in the letters and digits of the Markdown, as
+/// `page_lines` finds it, with where each line of the Markdown begins there
+pub(crate) fn page_offsets(
+    content: &ExtractedContent,
+    markdown: &str,
+) -> (Vec<usize>, Vec<(u32, Option<usize>)>) {
+    // The letters and digits of the Markdown, so markup, spacing and words
+    // split or joined by the clean-up do not matter, with where lines start
+    let mut letters = String::with_capacity(markdown.len());
+    let mut line_starts = Vec::new();
+    for line in markdown.lines() {
+        line_starts.push(letters.len());
+        if !is_page_mark(line) {
+            letters.extend(comparable(line));
+        }
+    }
+
+    let mut from = 0;
+    let mut offsets = Vec::new();
+    for page in 1..=content.page_count as u32 {
+        let offset = if content.failed_pages.contains(&page) {
+            let marker = trf(Message::FailedPageNote, &[("page", &page)]);
+            markdown
+                .lines()
+                .position(|line| line.starts_with('>') && line.contains(&marker))
+                .map(|i| line_starts[i])
+        } else {
+            content
+                .page_starts
+                .iter()
+                .find(|(number, _)| *number == page)
+                .and_then(|(_, start)| {
+                    let start: String = comparable(start).take(PAGE_START_CHARS).collect();
+                    if start.is_empty() {
+                        return None;
+                    }
+                    let at = from + letters[from..].find(&start)?;
+                    from = at + start.len();
+                    Some(at)
+                })
+        };
+        offsets.push((page, offset));
+    }
+    (line_starts, offsets)
+}
+
+/// The letters and digits of `text`, lowercased
+fn comparable(text: &str) -> impl Iterator<Item = char> + '_ {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+}
+
+/// The report as YAML: strings are quoted as in JSON, which YAML reads the same
+fn to_yaml(value: &Value) -> String {
+    let mut out = String::new();
+    write_yaml(&mut out, value, 0);
+    out
+}
+
+fn write_yaml(out: &mut String, value: &Value, indent: usize) {
+    let pad = " ".repeat(indent);
+    match value {
+        Value::Object(members) if !members.is_empty() => {
+            for (name, value) in members {
+                out.push_str(&format!("{}{}:", pad, name));
+                write_nested(out, value, indent);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for item in items {
+                out.push_str(&format!("{}-", pad));
+                match item {
+                    // The first member shares the dash's line
+                    Value::Object(members) if !members.is_empty() => {
+                        let mut nested = String::new();
+                        write_yaml(&mut nested, item, indent + 2);
+                        out.push_str(&nested[indent + 1..]);
+                    }
+                    item => write_nested(out, item, indent),
+                }
+            }
+        }
+        value => out.push_str(&format!("{}{}\n", pad, value.to_json())),
+    }
+}
+
+/// The value of a mapping entry or list item whose key or dash is written
+fn write_nested(out: &mut String, value: &Value, indent: usize) {
+    match value {
+        Value::Object(members) if !members.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent + 2);
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push('\n');
+            write_yaml(out, value, indent + 2);
+        }
+        value => out.push_str(&format!(" {}\n", value.to_json())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_lines() {
+        let content = ExtractedContent {
+            page_count: 4,
+            failed_pages: vec![3],
+            page_starts: vec![
+                (1, "INTRODUCTION This tool".to_string()),
+                (2, "con- verts documents.".to_string()),
+                (4, "Results are in".to_string()),
+            ],
+            ..Default::default()
+        };
+        let markdown = "## INTRODUCTION\n\nThis tool converts documents.\n\n> \u{26a0}\u{fe0f} Page 3 could not be extracted\n\n**Results** are in.";
+        assert_eq!(
+            page_lines(&content, markdown),
+            vec![(1, Some(1)), (2, Some(3)), (3, Some(5)), (4, Some(7))]
+        );
+    }
+
+    #[test]
+    fn test_to_yaml() {
+        let report = Value::Object(vec![
+            ("output".to_string(), Value::String("a.md".to_string())),
+            ("warnings".to_string(), Value::Array(Vec::new())),
+            (
+                "stats".to_string(),
+                Value::Object(vec![
+                    ("pages".to_string(), Value::Number(2.0)),
+                    (
+                        "failed_pages".to_string(),
+                        Value::Array(vec![Value::Number(2.0)]),
+                    ),
+                ]),
+            ),
+            (
+                "page_map".to_string(),
+                Value::Array(vec![Value::Object(vec![
+                    ("page".to_string(), Value::Number(1.0)),
+                    ("line".to_string(), Value::Null),
+                ])]),
+            ),
+        ]);
+        assert_eq!(
+            to_yaml(&report),
+            "output: \"a.md\"\nwarnings: []\nstats:\n  pages: 2\n  failed_pages:\n    - 2\npage_map:\n  - page: 1\n    line: null\n"
+        );
+    }
+}
@@ -0,0 +1,863 @@
+use crate::config::{Config, image_prefix};
+use crate::provenance::{self, Provenance};
+use crate::sections::{self, SectionRule};
+use crate::{Pdf2MdError, Result};
+use chrono::{DateTime, FixedOffset, Local, SecondsFormat};
+use clap::ValueEnum;
+use log::info;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Static site generators whose content layout can be written directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Site {
+    /// Page bundle: `content/<slug>/index.md` with images as page resources
+    Hugo,
+    /// `docs/index.md` plus one numbered file per chapter, images under `docs/`
+    Mkdocs,
+    /// Vault note named after the title, images as attachments embedded with `![[...]]`
+    Obsidian,
+    /// `_posts/YYYY-MM-DD-<slug>.md` (or a collection), images under `assets/<slug>/`
+    Jekyll,
+    /// `docs/<slug>/` with one page per chapter ordered in the sidebar, callouts as admonitions
+    Docusaurus,
+    /// Page bundle: `content/<slug>/index.md` with tags and categories as taxonomies
+    Zola,
+    /// `<Title>.md` in Notion's importable subset, plus `<Title>.zip` bundling it with its images
+    Notion,
+}
+
+impl Site {
+    /// Whether the generator reads `+++` TOML front matter
+    pub fn supports_toml(self) -> bool {
+        matches!(self, Site::Hugo | Site::Zola)
+    }
+}
+
+/// Syntax of the front matter block
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FrontMatterFormat {
+    /// `---` delimited YAML
+    #[default]
+    Yaml,
+    /// `+++` delimited TOML (Hugo, Zola)
+    Toml,
+}
+
+/// Output layout for one document in a site rooted at `-o`
+#[derive(Debug)]
+pub struct SiteLayout {
+    site: Site,
+    title: String,
+    /// PDF creation date, or the time of conversion
+    date: DateTime<FixedOffset>,
+    root: PathBuf,
+}
+
+/// Rewrite the output paths for a site layout rooted at `-o`
+pub fn prepare(
+    site: Site,
+    config: &mut Config,
+    doc: &pdf_extract::PdfDocument,
+) -> Result<SiteLayout> {
+    let metadata = doc.extract_metadata()?;
+    let title = document_title(config, &metadata);
+    let date = metadata
+        .creation_date
+        .as_deref()
+        .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+        .unwrap_or_else(|| Local::now().fixed_offset());
+    let root = config.output_path.clone();
+
+    match site {
+        Site::Hugo | Site::Zola => {
+            let bundle = root.join("content").join(slugify(&title));
+            config.output_path = bundle.join("index.md");
+            config.image_dir = PathBuf::new();
+        }
+        Site::Mkdocs => {
+            // Chapters sit next to index.md, so one image prefix serves every page
+            config.output_path = root.join("docs").join("index.md");
+        }
+        Site::Obsidian => {
+            // Attachments go to --image-dir inside the vault
+            config.output_path = root.join(format!("{}.md", note_name(&title)));
+        }
+        Site::Notion => {
+            config.output_path = root.join(format!("{}.md", note_name(&title)));
+        }
+        Site::Jekyll => {
+            let slug = slugify(&title);
+            config.output_path = match &config.jekyll_collection {
+                Some(collection) => root
+                    .join(format!("_{}", collection.trim_start_matches('_')))
+                    .join(format!("{}.md", slug)),
+                None => {
+                    root.join("_posts")
+                        .join(format!("{}-{}.md", date.format("%Y-%m-%d"), slug))
+                }
+            };
+            // Pages are served at their permalink, so images need a site-absolute URL
+            config.image_dir = PathBuf::from("..").join("assets").join(&slug);
+            config.image_url = Some(format!("/assets/{}/", slug));
+        }
+        Site::Docusaurus => {
+            config.output_path = root.join("docs").join(slugify(&title)).join("index.md");
+        }
+    }
+
+    Ok(SiteLayout {
+        site,
+        title,
+        date,
+        root,
+    })
+}
+
+impl SiteLayout {
+    /// Write the converted Markdown in the site's layout
+    pub fn write(
+        &self,
+        config: &Config,
+        markdown: &str,
+        images: &[pdf_extract::ExtractedImage],
+        provenance: Option<&Provenance>,
+    ) -> Result<()> {
+        // Notion shows front matter as text, so it gets no provenance
+        let stamp = |page: String| match provenance {
+            Some(provenance) if self.site != Site::Notion => {
+                provenance::add_to_front_matter(&page, provenance, self.site == Site::Zola)
+            }
+            _ => page,
+        };
+        match self.site {
+            Site::Hugo => {
+                let date = self.date.to_rfc3339_opts(SecondsFormat::Secs, false);
+                let page =
+                    hugo_front_matter(&self.title, &date, config.front_matter_format) + markdown;
+                markdown_gen::write_to_file(&stamp(page), &config.output_path)?;
+            }
+            Site::Zola => {
+                let date = self.date.to_rfc3339_opts(SecondsFormat::Secs, false);
+                let front_matter = zola_front_matter(
+                    &self.title,
+                    &date,
+                    &config.tags,
+                    &config.categories,
+                    config.front_matter_format,
+                );
+                markdown_gen::write_to_file(&stamp(front_matter + markdown), &config.output_path)?;
+            }
+            Site::Mkdocs => self.write_mkdocs(config, markdown, &stamp)?,
+            Site::Obsidian => {
+                let source = config
+                    .input_path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let note = obsidian_front_matter(&self.title, &source, &config.tags)
+                    + obsidian_embeds(markdown).as_str();
+                markdown_gen::write_to_file(&stamp(note), &config.output_path)?;
+            }
+            Site::Jekyll => {
+                let date = self.date.format("%Y-%m-%d %H:%M:%S %z").to_string();
+                let front_matter = jekyll_front_matter(
+                    &config.jekyll_layout,
+                    &self.title,
+                    &date,
+                    &config.categories,
+                );
+                markdown_gen::write_to_file(&stamp(front_matter + markdown), &config.output_path)?;
+            }
+            Site::Docusaurus => self.write_docusaurus(config, markdown, &stamp)?,
+            Site::Notion => {
+                let page = notion_markdown(markdown);
+                markdown_gen::write_to_file(&page, &config.output_path)?;
+
+                // Notion imports a zip of the page and the images it links to
+                let page_name = format!("{}.md", note_name(&self.title));
+                let prefix = image_prefix(&config.image_dir);
+                let mut entries = vec![(page_name, page.as_bytes())];
+                for image in images {
+                    entries.push((
+                        format!("{}{}", prefix, image.file_name),
+                        image.data.as_slice(),
+                    ));
+                }
+                let archive = config.output_path.with_extension("zip");
+                info!(
+                    "Bundling {} files into {}",
+                    entries.len(),
+                    archive.display()
+                );
+                markdown_gen::write_zip(&entries, &archive)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_docusaurus(
+        &self,
+        config: &Config,
+        markdown: &str,
+        stamp: &dyn Fn(String) -> String,
+    ) -> Result<()> {
+        let dir = config
+            .output_path
+            .parent()
+            .unwrap_or(&self.root)
+            .to_path_buf();
+        let (intro, chapters) = split_chapters(&admonitions(markdown), &config.section_rules)?;
+
+        let mut index = docusaurus_front_matter("index", &self.title, 1);
+        if !intro.is_empty() {
+            index.push_str(&intro);
+            index.push_str("\n\n");
+        }
+        for chapter in &chapters {
+            index.push_str(&format!("- [{}]({})\n", chapter.title, chapter.file_name));
+        }
+        markdown_gen::write_to_file(stamp(index).trim_end(), &config.output_path)?;
+
+        let prefix = image_prefix(&config.image_dir);
+        for (i, chapter) in chapters.iter().enumerate() {
+            // Docusaurus ids are file names, without the directories
+            let id = Path::new(&chapter.file_name)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy())
+                .unwrap_or_default();
+            let page = docusaurus_front_matter(&id, &chapter.title, i + 2)
+                + relocate_images(&chapter.body, &prefix, &chapter.file_name).as_str();
+            markdown_gen::write_to_file(stamp(page).trim_end(), &dir.join(&chapter.file_name))?;
+        }
+        Ok(())
+    }
+
+    fn write_mkdocs(
+        &self,
+        config: &Config,
+        markdown: &str,
+        stamp: &dyn Fn(String) -> String,
+    ) -> Result<()> {
+        let docs = self.root.join("docs");
+        let (intro, chapters) = split_chapters(markdown, &config.section_rules)?;
+
+        let mut index = format!("# {}\n\n", self.title);
+        if !intro.is_empty() {
+            index.push_str(&intro);
+            index.push_str("\n\n");
+        }
+        for chapter in &chapters {
+            index.push_str(&format!("- [{}]({})\n", chapter.title, chapter.file_name));
+        }
+        markdown_gen::write_to_file(stamp(index).trim_end(), &config.output_path)?;
+
+        let prefix = image_prefix(&config.image_dir);
+        for chapter in &chapters {
+            let body = relocate_images(&chapter.body, &prefix, &chapter.file_name);
+            let page = format!("# {}\n\n{}", chapter.title, body);
+            markdown_gen::write_to_file(stamp(page).trim_end(), &docs.join(&chapter.file_name))?;
+        }
+
+        if config.mkdocs_nav {
+            let nav = mkdocs_nav(&chapters);
+            let mkdocs_yml = self.root.join("mkdocs.yml");
+            if mkdocs_yml.exists() {
+                let snippet = self.root.join("mkdocs-nav.yml");
+                info!(
+                    "{} exists; writing nav to {}",
+                    mkdocs_yml.display(),
+                    snippet.display()
+                );
+                markdown_gen::write_to_file(&nav, &snippet)?;
+            } else {
+                let yml = format!("site_name: \"{}\"\n{}", yaml_escape(&self.title), nav);
+                markdown_gen::write_to_file(&yml, &mkdocs_yml)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A top-level section written to its own file
+#[derive(Debug, PartialEq)]
+struct Chapter {
+    title: String,
+    file_name: String,
+    body: String,
+}
+
+/// Split Markdown at its top-level (`## `) headings into the text before the
+/// first heading and numbered chapters, named by the `[[section]]` rules
+fn split_chapters(markdown: &str, rules: &[SectionRule]) -> Result<(String, Vec<Chapter>)> {
+    let mut intro = String::new();
+    let mut chapters: Vec<Chapter> = Vec::new();
+    let mut in_fence = false;
+
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && let Some(title) = line.strip_prefix("## ") {
+            let title = title.trim().to_string();
+            let file_name =
+                sections::file_name(rules, &title, &slugify(&title), chapters.len() + 1);
+            chapters.push(Chapter {
+                title,
+                file_name,
+                body: String::new(),
+            });
+            continue;
+        }
+        let target = match chapters.last_mut() {
+            Some(chapter) => &mut chapter.body,
+            None => &mut intro,
+        };
+        target.push_str(line);
+        target.push('\n');
+    }
+
+    let mut files: HashMap<&str, &str> = HashMap::from([("index.md", "the index")]);
+    for chapter in &chapters {
+        if let Some(other) = files.insert(&chapter.file_name, &chapter.title) {
+            return Err(Pdf2MdError::InvalidInput(format!(
+                "chapter \"{}\" and {} would both be written to {}; check the [[section]] rules",
+                chapter.title,
+                if other == "the index" {
+                    other.to_string()
+                } else {
+                    format!("\"{}\"", other)
+                },
+                chapter.file_name
+            )));
+        }
+    }
+
+    for chapter in &mut chapters {
+        chapter.body = chapter.body.trim().to_string();
+    }
+    Ok((intro.trim().to_string(), chapters))
+}
+
+/// Point the image links of a chapter written to a subdirectory (`file_name`
+/// with a `/`) back up to the images next to the index
+fn relocate_images(body: &str, prefix: &str, file_name: &str) -> String {
+    let depth = file_name.matches('/').count();
+    if depth == 0 || prefix.is_empty() || prefix.starts_with('/') || prefix.contains("://") {
+        return body.to_string();
+    }
+    body.replace(
+        &format!("]({}", prefix),
+        &format!("]({}{}", "../".repeat(depth), prefix),
+    )
+}
+
+/// `nav:` block for mkdocs.yml listing the index and every chapter
+fn mkdocs_nav(chapters: &[Chapter]) -> String {
+    let mut nav = String::from("nav:\n  - Home: index.md\n");
+    for chapter in chapters {
+        nav.push_str(&format!(
+            "  - \"{}\": {}\n",
+            yaml_escape(&chapter.title),
+            chapter.file_name
+        ));
+    }
+    nav
+}
+
+/// YAML front matter for a Docusaurus doc placed at `position` in the sidebar
+fn docusaurus_front_matter(id: &str, title: &str, position: usize) -> String {
+    format!(
+        "---\nid: {}\ntitle: \"{}\"\nsidebar_position: {}\n---\n\n",
+        id,
+        yaml_escape(title),
+        position
+    )
+}
+
+/// Turn paragraphs opening with `Note:`, `Warning:` and similar labels into
+/// Docusaurus admonitions (`:::note ... :::`)
+fn admonitions(markdown: &str) -> String {
+    rewrite_callouts(markdown, |kind, body| {
+        format!(":::{}\n\n{}\n\n:::", kind, body)
+    })
+}
+
+/// Rewrite every callout paragraph (outside code fences) with `render(kind, body)`
+fn rewrite_callouts(markdown: &str, render: impl Fn(&str, &str) -> String) -> String {
+    let mut in_fence = false;
+    let paragraphs: Vec<String> = markdown
+        .split("\n\n")
+        .map(|paragraph| {
+            let converted = if in_fence {
+                None
+            } else {
+                callout(paragraph).map(|(kind, body)| render(kind, body))
+            };
+            let fences = paragraph
+                .lines()
+                .filter(|line| line.trim_start().starts_with("```"))
+                .count();
+            if fences % 2 == 1 {
+                in_fence = !in_fence;
+            }
+            converted.unwrap_or_else(|| paragraph.to_string())
+        })
+        .collect();
+    paragraphs.join("\n\n")
+}
+
+/// Admonition type and remaining text of a paragraph such as `**Warning:** Hot surface`
+fn callout(paragraph: &str) -> Option<(&'static str, &str)> {
+    let text = paragraph.trim_start();
+    let text = text.strip_prefix("**").unwrap_or(text);
+    let (label, rest) = text.split_once(':')?;
+    let kind = match label.trim().to_ascii_lowercase().as_str() {
+        "note" => "note",
+        "tip" | "hint" => "tip",
+        "info" | "important" => "info",
+        "warning" | "caution" => "warning",
+        "danger" => "danger",
+        _ => return None,
+    };
+    let body = rest.strip_prefix("**").unwrap_or(rest).trim();
+    (!body.is_empty()).then_some((kind, body))
+}
+
+/// Restrict Markdown to what Notion's importer keeps: `---` dividers,
+/// `<aside>` callouts and tables only at the top level
+fn notion_markdown(markdown: &str) -> String {
+    let markdown = rewrite_callouts(markdown, |kind, body| {
+        let icon = match kind {
+            "warning" => "⚠️",
+            "danger" => "🚨",
+            "info" => "ℹ️",
+            _ => "💡",
+        };
+        format!("<aside>\n{} {}\n</aside>", icon, body)
+    });
+
+    let mut out = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if !in_fence && is_thematic_break(trimmed) {
+            out.push_str("---");
+        } else if !in_fence && let Some(row) = nested_table_row(line) {
+            // Notion drops tables inside quotes and lists, so lift them out
+            out.push_str(row);
+        } else {
+            out.push_str(line);
+        }
+        out.push('\n');
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// `***`, `___`, `- - -` and other thematic breaks
+fn is_thematic_break(line: &str) -> bool {
+    let marks: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&mark| marks.iter().all(|&c| c == mark))
+}
+
+/// Table row nested in a blockquote or list item, without its container markers
+fn nested_table_row(line: &str) -> Option<&str> {
+    let mut rest = line.trim_start();
+    let mut nested = line.len() - rest.len() >= 2;
+    loop {
+        let next = rest
+            .strip_prefix('>')
+            .or_else(|| rest.strip_prefix("- "))
+            .or_else(|| rest.strip_prefix("* "));
+        match next {
+            Some(next) => {
+                rest = next.trim_start();
+                nested = true;
+            }
+            None => break,
+        }
+    }
+    (nested && rest.starts_with('|')).then_some(rest)
+}
+
+/// YAML front matter for an Obsidian note, always tagged `pdf`
+fn obsidian_front_matter(title: &str, source: &str, tags: &[String]) -> String {
+    let mut front_matter = format!("---\ntitle: \"{}\"\ntags:\n  - pdf\n", yaml_escape(title));
+    for tag in tags {
+        // Obsidian tags cannot contain spaces
+        let tag = tag.trim().trim_start_matches('#').replace(' ', "-");
+        if !tag.is_empty() && tag != "pdf" {
+            front_matter.push_str(&format!("  - {}\n", tag));
+        }
+    }
+    front_matter.push_str(&format!("source: \"{}\"\n---\n\n", yaml_escape(source)));
+    front_matter
+}
+
+/// Rewrite Markdown image links `![alt](dir/name.png)` as Obsidian embeds `![[name.png|alt]]`
+fn obsidian_embeds(markdown: &str) -> String {
+    let mut out = String::with_capacity(markdown.len());
+    let mut rest = markdown;
+    while let Some(start) = rest.find("![") {
+        out.push_str(&rest[..start]);
+        let link = &rest[start..];
+        match parse_image_link(link) {
+            Some((alt, target, len)) => {
+                let file_name = target.rsplit('/').next().unwrap_or(target);
+                // Brackets and pipes would end the embed early
+                let alt = alt.replace(['\\', '[', ']'], "").replace('|', "-");
+                let alt = alt.trim();
+                if alt.is_empty() {
+                    out.push_str(&format!("![[{}]]", file_name));
+                } else {
+                    out.push_str(&format!("![[{}|{}]]", file_name, alt));
+                }
+                rest = &link[len..];
+            }
+            None => {
+                out.push_str("![");
+                rest = &link[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Parse `![alt](target)` at the start of `text`, honouring `\]` escapes in the alt text.
+///
+/// Returns the alt text, target and the length of the whole link.
+fn parse_image_link(text: &str) -> Option<(&str, &str, usize)> {
+    let body = text.strip_prefix("![")?;
+    let mut escaped = false;
+    let close = body.char_indices().find_map(|(i, c)| {
+        let found = c == ']' && !escaped;
+        escaped = c == '\\' && !escaped;
+        found.then_some(i)
+    })?;
+    let target_start = close + 1;
+    let after = body[target_start..].strip_prefix('(')?;
+    let target_len = after.find(')')?;
+    let target = &after[..target_len];
+    if target.is_empty() || target.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((
+        &body[..close],
+        target,
+        2 + target_start + 1 + target_len + 1,
+    ))
+}
+
+/// Title usable as a note file name: characters Obsidian forbids in links are replaced
+fn note_name(title: &str) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| match c {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    let name = name.trim_start_matches('.');
+    if name.is_empty() {
+        "document".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Title from the PDF metadata, falling back to the input file name
+pub(crate) fn document_title(config: &Config, metadata: &pdf_extract::PdfMetadata) -> String {
+    metadata
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            config
+                .input_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| "document".to_string())
+        })
+}
+
+/// Lowercase, hyphen-separated form of a title for directory names and URLs
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_alphanumeric() {
+            slug.extend(c.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "document".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Front matter for a Hugo page
+fn hugo_front_matter(title: &str, date: &str, format: FrontMatterFormat) -> String {
+    match format {
+        FrontMatterFormat::Yaml => format!(
+            "---\ntitle: \"{}\"\ndate: {}\ndraft: false\n---\n\n",
+            yaml_escape(title),
+            date
+        ),
+        FrontMatterFormat::Toml => format!(
+            "+++\ntitle = \"{}\"\ndate = {}\ndraft = false\n+++\n\n",
+            yaml_escape(title),
+            date
+        ),
+    }
+}
+
+/// Front matter for a Zola page, with non-empty taxonomies
+fn zola_front_matter(
+    title: &str,
+    date: &str,
+    tags: &[String],
+    categories: &[String],
+    format: FrontMatterFormat,
+) -> String {
+    let taxonomies: Vec<(&str, String)> = [("tags", tags), ("categories", categories)]
+        .into_iter()
+        .filter_map(|(name, terms)| {
+            let terms: Vec<String> = terms
+                .iter()
+                .map(|term| term.trim())
+                .filter(|term| !term.is_empty())
+                .map(|term| format!("\"{}\"", yaml_escape(term)))
+                .collect();
+            (!terms.is_empty()).then(|| (name, terms.join(", ")))
+        })
+        .collect();
+
+    match format {
+        FrontMatterFormat::Yaml => {
+            let mut front_matter =
+                format!("---\ntitle: \"{}\"\ndate: {}\n", yaml_escape(title), date);
+            if !taxonomies.is_empty() {
+                front_matter.push_str("taxonomies:\n");
+                for (name, terms) in &taxonomies {
+                    front_matter.push_str(&format!("  {}: [{}]\n", name, terms));
+                }
+            }
+            front_matter.push_str("---\n\n");
+            front_matter
+        }
+        FrontMatterFormat::Toml => {
+            let mut front_matter =
+                format!("+++\ntitle = \"{}\"\ndate = {}\n", yaml_escape(title), date);
+            if !taxonomies.is_empty() {
+                front_matter.push_str("\n[taxonomies]\n");
+                for (name, terms) in &taxonomies {
+                    front_matter.push_str(&format!("{} = [{}]\n", name, terms));
+                }
+            }
+            front_matter.push_str("+++\n\n");
+            front_matter
+        }
+    }
+}
+
+/// YAML front matter for a Jekyll post or collection document
+fn jekyll_front_matter(layout: &str, title: &str, date: &str, categories: &[String]) -> String {
+    let mut front_matter = format!(
+        "---\nlayout: {}\ntitle: \"{}\"\ndate: {}\n",
+        layout,
+        yaml_escape(title),
+        date
+    );
+    let categories: Vec<String> = categories
+        .iter()
+        .map(|category| category.trim())
+        .filter(|category| !category.is_empty())
+        .map(|category| format!("\"{}\"", yaml_escape(category)))
+        .collect();
+    if !categories.is_empty() {
+        front_matter.push_str(&format!("categories: [{}]\n", categories.join(", ")));
+    }
+    front_matter.push_str("---\n\n");
+    front_matter
+}
+
+/// Escape a value for a double-quoted YAML (or TOML basic) string
+pub(crate) fn yaml_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(
+            slugify("Annual Report 2024: Q3 Results!"),
+            "annual-report-2024-q3-results"
+        );
+        assert_eq!(slugify("  --  "), "document");
+        assert_eq!(slugify("Über Café"), "über-café");
+    }
+
+    #[test]
+    fn test_hugo_front_matter() {
+        let front_matter = hugo_front_matter(
+            "The \"Best\" Guide",
+            "2024-05-01T10:00:00+00:00",
+            FrontMatterFormat::Yaml,
+        );
+        assert_eq!(
+            front_matter,
+            "---\ntitle: \"The \\\"Best\\\" Guide\"\ndate: 2024-05-01T10:00:00+00:00\ndraft: false\n---\n\n"
+        );
+        assert_eq!(
+            hugo_front_matter(
+                "Guide",
+                "2024-05-01T10:00:00+00:00",
+                FrontMatterFormat::Toml
+            ),
+            "+++\ntitle = \"Guide\"\ndate = 2024-05-01T10:00:00+00:00\ndraft = false\n+++\n\n"
+        );
+    }
+
+    #[test]
+    fn test_zola_front_matter() {
+        let tags = vec!["rust".to_string(), "pdf".to_string()];
+        let date = "2024-05-01T10:00:00+02:00";
+        assert_eq!(
+            zola_front_matter("Notes", date, &tags, &[], FrontMatterFormat::Toml),
+            "+++\ntitle = \"Notes\"\ndate = 2024-05-01T10:00:00+02:00\n\n[taxonomies]\ntags = [\"rust\", \"pdf\"]\n+++\n\n"
+        );
+        assert_eq!(
+            zola_front_matter("Notes", date, &[], &tags[..1], FrontMatterFormat::Yaml),
+            "---\ntitle: \"Notes\"\ndate: 2024-05-01T10:00:00+02:00\ntaxonomies:\n  categories: [\"rust\"]\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn test_split_chapters() {
+        let markdown = "Preface text.\n\n## Getting Started\n\nInstall it.\n\n```\n## not a heading\n```\n\n## FAQ\n\nAsk.";
+        let (intro, chapters) = split_chapters(markdown, &[]).unwrap();
+        assert_eq!(intro, "Preface text.");
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].file_name, "01-getting-started.md");
+        assert!(chapters[0].body.contains("## not a heading"));
+        assert_eq!(chapters[1].title, "FAQ");
+        assert_eq!(chapters[1].body, "Ask.");
+    }
+
+    #[test]
+    fn test_split_chapters_with_rules() {
+        let rules: toml::Table = r#"
+            [[section]]
+            heading = "Appendix *"
+            path = "appendices/{slug}.md"
+        "#
+        .parse()
+        .unwrap();
+        let rules: Vec<SectionRule> = rules["section"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|rule| SectionRule::from_toml(rule).unwrap())
+            .collect();
+        let (_, chapters) =
+            split_chapters("## Usage\n\nRun.\n\n## Appendix A\n\nTables.", &rules).unwrap();
+        assert_eq!(chapters[0].file_name, "01-usage.md");
+        assert_eq!(chapters[1].file_name, "appendices/appendix-a.md");
+
+        let err = split_chapters("## Appendix A\n\n## Appendix A", &rules).unwrap_err();
+        assert!(err.to_string().contains("would both be written"), "{}", err);
+    }
+
+    #[test]
+    fn test_relocate_images() {
+        let body = "![Chart](images/page-9-image-1.png)";
+        assert_eq!(relocate_images(body, "images/", "01-intro.md"), body);
+        assert_eq!(
+            relocate_images(body, "images/", "appendices/a/b.md"),
+            "![Chart](../../images/page-9-image-1.png)"
+        );
+    }
+
+    #[test]
+    fn test_obsidian_embeds() {
+        let markdown = "Intro\n\n![Figure \\[1\\]: Setup](images/page-1-image-1.png)\n\n![](images/a.jpg) and ![not a link";
+        assert_eq!(
+            obsidian_embeds(markdown),
+            "Intro\n\n![[page-1-image-1.png|Figure 1: Setup]]\n\n![[a.jpg]] and ![not a link"
+        );
+    }
+
+    #[test]
+    fn test_obsidian_front_matter_and_note_name() {
+        let tags = vec!["#research".to_string(), "big data".to_string()];
+        assert_eq!(
+            obsidian_front_matter("Report", "report.pdf", &tags),
+            "---\ntitle: \"Report\"\ntags:\n  - pdf\n  - research\n  - big-data\nsource: \"report.pdf\"\n---\n\n"
+        );
+        assert_eq!(note_name("Q3: Results / Summary?"), "Q3 Results Summary");
+    }
+
+    #[test]
+    fn test_jekyll_front_matter() {
+        let categories = vec!["reports".to_string(), " ".to_string()];
+        assert_eq!(
+            jekyll_front_matter("post", "Q3", "2024-05-01 10:30:45 +0200", &categories),
+            "---\nlayout: post\ntitle: \"Q3\"\ndate: 2024-05-01 10:30:45 +0200\ncategories: [\"reports\"]\n---\n\n"
+        );
+        assert!(!jekyll_front_matter("page", "Q3", "2024-05-01", &[]).contains("categories"));
+    }
+
+    #[test]
+    fn test_admonitions() {
+        let markdown = "Intro.\n\n**Warning:** Do not open the case.\n\n```\ncode\n\nNote: in code\n```\n\nNote that this stays.\n\nTIP: Save often.";
+        assert_eq!(
+            admonitions(markdown),
+            "Intro.\n\n:::warning\n\nDo not open the case.\n\n:::\n\n```\ncode\n\nNote: in code\n```\n\nNote that this stays.\n\n:::tip\n\nSave often.\n\n:::"
+        );
+    }
+
+    #[test]
+    fn test_docusaurus_front_matter() {
+        assert_eq!(
+            docusaurus_front_matter("01-intro", "Intro", 2),
+            "---\nid: 01-intro\ntitle: \"Intro\"\nsidebar_position: 2\n---\n\n"
+        );
+    }
+
+    #[test]
+    fn test_notion_markdown() {
+        let markdown =
+            "Intro\n\n* * *\n\n> | a | b |\n> |---|---|\n\nNote: Read this.\n\n```\n***\n```";
+        assert_eq!(
+            notion_markdown(markdown),
+            "Intro\n\n---\n\n| a | b |\n|---|---|\n\n<aside>\n💡 Read this.\n</aside>\n\n```\n***\n```"
+        );
+    }
+
+    #[test]
+    fn test_mkdocs_nav() {
+        let (_, chapters) = split_chapters("## Intro\n\nText", &[]).unwrap();
+        assert_eq!(
+            mkdocs_nav(&chapters),
+            "nav:\n  - Home: index.md\n  - \"Intro\": 01-intro.md\n"
+        );
+    }
+}
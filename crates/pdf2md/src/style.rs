@@ -0,0 +1,106 @@
+//! Colour and emphasis in terminal output, with ANSI escapes: only where the
+//! stream is a terminal, the `NO_COLOR` environment variable is unset or
+//! empty, and `--no-color` was not given
+
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn colour off for the rest of the run (`--no-color`)
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// Where styled text is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    /// Whether text printed to the stream is styled
+    pub fn colored(self) -> bool {
+        let terminal = match self {
+            Stream::Stdout => std::io::stdout().is_terminal(),
+            Stream::Stderr => std::io::stderr().is_terminal(),
+        };
+        terminal
+            && !DISABLED.load(Ordering::Relaxed)
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    }
+}
+
+/// How a piece of text stands out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    /// Section heads
+    Bold,
+    /// Bold red
+    Error,
+    /// Bold yellow
+    Warning,
+    /// Bold cyan
+    Note,
+    /// Green
+    Success,
+}
+
+impl Style {
+    /// The SGR parameters of the style
+    fn code(self) -> &'static str {
+        match self {
+            Style::Bold => "1",
+            Style::Error => "1;31",
+            Style::Warning => "1;33",
+            Style::Note => "1;36",
+            Style::Success => "32",
+        }
+    }
+
+    /// `text` in the style when `colored`, else as it is
+    pub fn apply(self, text: &str, colored: bool) -> String {
+        if colored && !text.is_empty() {
+            format!("\x1b[{}m{}\x1b[0m", self.code(), text)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// `text` in the style, if the stream is styled
+pub fn paint(text: &str, style: Style, stream: Stream) -> String {
+    style.apply(text, stream.colored())
+}
+
+/// The label of an error message on stderr
+pub fn error(label: &str) -> String {
+    paint(label, Style::Error, Stream::Stderr)
+}
+
+/// The label of a warning on stderr
+pub fn warning(label: &str) -> String {
+    paint(label, Style::Warning, Stream::Stderr)
+}
+
+/// The label of a note on stderr
+pub fn note(label: &str) -> String {
+    paint(label, Style::Note, Stream::Stderr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(Style::Error.apply("error", true), "\x1b[1;31merror\x1b[0m");
+        assert_eq!(
+            Style::Bold.apply("Fonts: 2", true),
+            "\x1b[1mFonts: 2\x1b[0m"
+        );
+        assert_eq!(Style::Warning.apply("warning", false), "warning");
+        assert_eq!(Style::Note.apply("", true), "");
+    }
+}
@@ -0,0 +1,122 @@
+use crate::i18n::{Message, tr, trf};
+use pdf_extract::ExtractTimings;
+use std::time::{Duration, Instant};
+
+/// Pages listed in the report, slowest first
+const SLOWEST_PAGES: usize = 5;
+
+/// Where a conversion spent its time, for `--timings`
+#[derive(Debug)]
+pub struct Timings {
+    started: Instant,
+    /// End of the last stage recorded
+    mark: Instant,
+    pub load: Duration,
+    pub extraction: ExtractTimings,
+    pub formatting: Duration,
+    pub writing: Duration,
+}
+
+impl Timings {
+    pub fn start() -> Self {
+        let now = Instant::now();
+        Self {
+            started: now,
+            mark: now,
+            load: Duration::ZERO,
+            extraction: ExtractTimings::default(),
+            formatting: Duration::ZERO,
+            writing: Duration::ZERO,
+        }
+    }
+
+    /// Time since the end of the last stage recorded, which ends now
+    pub fn lap(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now - self.mark;
+        self.mark = now;
+        elapsed
+    }
+
+    /// Record the extraction, which ends now
+    pub fn extracted(&mut self, timings: &ExtractTimings) {
+        self.extraction = timings.clone();
+        self.mark = Instant::now();
+    }
+
+    /// Time per stage and the slowest pages, in the output language.
+    ///
+    /// When pages are written as they are extracted the stages overlap, so
+    /// they can add up to more than the total.
+    pub fn report(&self) -> String {
+        let stages = [
+            (Message::StageLoad, self.load),
+            (Message::StageExtraction, self.extraction.extraction),
+            (Message::StageCleanup, self.extraction.cleanup),
+            (Message::StageFormatting, self.formatting),
+            (Message::StageWriting, self.writing),
+        ];
+        let mut pages = self.extraction.pages.clone();
+        pages.sort_by_key(|&(page, elapsed)| (std::cmp::Reverse(elapsed), page));
+        pages.truncate(SLOWEST_PAGES);
+        let pages: Vec<(String, Duration)> = pages
+            .into_iter()
+            .map(|(page, elapsed)| (trf(Message::Page, &[("page", &page)]), elapsed))
+            .collect();
+        let width = stages
+            .iter()
+            .map(|(stage, _)| tr(*stage).chars().count())
+            .chain(pages.iter().map(|(page, _)| page.chars().count()))
+            .max()
+            .unwrap_or(0);
+        let line = |label: &str, elapsed: Duration| {
+            let pad = width - label.chars().count();
+            format!(
+                "  {}{} {:>8.3}s\n",
+                label,
+                " ".repeat(pad),
+                elapsed.as_secs_f64()
+            )
+        };
+
+        let total = format!("{:.2}", self.started.elapsed().as_secs_f64());
+        let mut report = format!("{}\n", trf(Message::Timings, &[("seconds", &total)]));
+        for (stage, elapsed) in stages {
+            report.push_str(&line(tr(stage), elapsed));
+        }
+        if !pages.is_empty() {
+            report.push_str(&format!("{}\n", tr(Message::SlowestPages)));
+            for (page, elapsed) in &pages {
+                report.push_str(&line(page, *elapsed));
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_lists_slowest_pages_first() {
+        let mut timings = Timings::start();
+        timings.extraction.pages = (1..=8)
+            .map(|page| {
+                (
+                    page,
+                    Duration::from_millis([5, 40, 1, 3, 40, 2, 9, 7][page as usize - 1]),
+                )
+            })
+            .collect();
+        let report = timings.report();
+        let pages: Vec<&str> = report
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("Page "))
+            .map(|line| line.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(pages, ["2", "5", "7", "8", "1"]);
+        assert!(report.contains("  extraction "));
+        assert!(report.contains("    0.040s"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Result;
+use crate::cli::TocFormat;
+use crate::config::Config;
+use crate::dry_run::heading_outline;
+use crate::i18n::{Message, tr, trf};
+use crate::json;
+use crate::style;
+use pdf_extract::OutlineEntry;
+use std::path::Path;
+
+/// Print the document's bookmarks, or the sections detected in its text when
+/// it has none
+pub fn run_toc(input: &Path, format: TocFormat) -> Result<()> {
+    pdf_extract::validate_pdf(input)?;
+    let doc = pdf_extract::PdfDocument::open(input)?;
+
+    let mut entries = doc.outline();
+    if entries.is_empty() {
+        let metadata = doc.extract_metadata()?;
+        let pages: Vec<pdf_extract::Result<String>> = (1..=metadata.page_count as u32)
+            .map(|page| doc.extract_page_text(page))
+            .collect();
+        let outline = heading_outline(&pages, &Config::default(), &metadata);
+        // Heading levels start at the top level found, as bookmarks do
+        let top = outline
+            .iter()
+            .map(|(level, _, _)| *level)
+            .min()
+            .unwrap_or(1);
+        entries = outline
+            .into_iter()
+            .map(|(level, title, page)| OutlineEntry {
+                level: level - top + 1,
+                title,
+                page: Some(page),
+            })
+            .collect();
+        if format == TocFormat::Text {
+            eprintln!(
+                "{}: {}",
+                style::note(tr(Message::Note)),
+                tr(Message::NoBookmarks)
+            );
+        }
+    }
+
+    match format {
+        TocFormat::Text => print!("{}", text(&entries)),
+        TocFormat::Json => println!("{}", as_json(&entries)),
+    }
+    Ok(())
+}
+
+/// Indented Markdown list, with the page after each title
+fn text(entries: &[OutlineEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let indent = "  ".repeat(entry.level - 1);
+            match entry.page {
+                Some(page) => format!(
+                    "{}- {}\n",
+                    indent,
+                    trf(Message::OnPage, &[("text", &entry.title), ("page", &page)])
+                ),
+                None => format!("{}- {}\n", indent, entry.title),
+            }
+        })
+        .collect()
+}
+
+/// JSON array of `{"level", "title", "page"}` objects, `page` being `null`
+/// for bookmarks that lead outside the document
+fn as_json(entries: &[OutlineEntry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "{{\"level\": {}, \"title\": {}, \"page\": {}}}",
+                entry.level,
+                json::string(&entry.title),
+                entry
+                    .page
+                    .map_or("null".to_string(), |page| page.to_string())
+            )
+        })
+        .collect();
+    json::array(&items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries() -> Vec<OutlineEntry> {
+        vec![
+            OutlineEntry {
+                level: 1,
+                title: "Part \"One\"".to_string(),
+                page: Some(1),
+            },
+            OutlineEntry {
+                level: 2,
+                title: "Notes\tand links".to_string(),
+                page: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn test_text() {
+        assert_eq!(
+            text(&entries()),
+            "- Part \"One\" (page 1)\n  - Notes\tand links\n"
+        );
+    }
+
+    #[test]
+    fn test_as_json() {
+        assert_eq!(
+            as_json(&entries()),
+            "[\n  {\"level\": 1, \"title\": \"Part \\\"One\\\"\", \"page\": 1},\n  {\"level\": 2, \"title\": \"Notes\\tand links\", \"page\": null}\n]"
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::i18n::{Message, count, tr, trf};
+use crate::{Pdf2MdError, Result};
+use std::path::Path;
+
+/// How serious a problem found by `validate` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    /// Content will be missing from the conversion
+    Error,
+    /// The conversion may be incomplete or garbled
+    Warning,
+}
+
+/// A problem with the whole document (`page` is `None`) or one page
+#[derive(Debug, Clone, PartialEq)]
+struct Problem {
+    page: Option<u32>,
+    severity: Severity,
+    message: String,
+}
+
+/// Check that a PDF parses and that every page can be extracted, printing
+/// each problem found.
+///
+/// Fails when a page cannot be extracted, or with `deny_warnings` on any
+/// problem; a file that cannot be opened at all fails with the PDF error.
+pub fn run_validate(input: &Path, deny_warnings: bool) -> Result<()> {
+    pdf_extract::validate_pdf(input)?;
+    let doc = pdf_extract::PdfDocument::open(input)?;
+    let page_count = doc.page_count();
+
+    let mut problems: Vec<Problem> = doc
+        .compatibility()?
+        .warnings()
+        .into_iter()
+        .map(|message| Problem {
+            page: None,
+            severity: Severity::Warning,
+            message,
+        })
+        .collect();
+    if page_count == 0 {
+        problems.push(Problem {
+            page: None,
+            severity: Severity::Error,
+            message: tr(Message::NoPages).to_string(),
+        });
+    }
+    for page in 1..=page_count as u32 {
+        match doc.extract_page_text(page) {
+            Ok(text) if text.trim().is_empty() => problems.push(Problem {
+                page: Some(page),
+                severity: Severity::Warning,
+                message: tr(Message::NoTextLayer).to_string(),
+            }),
+            Ok(_) => {}
+            Err(e) => problems.push(Problem {
+                page: Some(page),
+                severity: Severity::Error,
+                message: trf(Message::CannotExtract, &[("error", &e)]),
+            }),
+        }
+    }
+    for font in doc.font_inventory().iter().filter(|font| font.may_garble()) {
+        for &page in &font.pages {
+            problems.push(Problem {
+                page: Some(page),
+                severity: Severity::Warning,
+                message: trf(Message::GarbledFont, &[("font", &font.name)]),
+            });
+        }
+    }
+    problems.sort_by_key(|problem| (problem.page, problem.severity));
+
+    print!("{}", report(input, page_count, &problems));
+
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(Pdf2MdError::Validation(errors_count(errors)));
+    }
+    if deny_warnings && !problems.is_empty() {
+        return Err(Pdf2MdError::Validation(warnings_count(problems.len())));
+    }
+    Ok(())
+}
+
+/// One line per problem, then a summary
+fn report(input: &Path, page_count: usize, problems: &[Problem]) -> String {
+    let mut report = String::new();
+    for problem in problems {
+        let place = match problem.page {
+            Some(page) => trf(Message::Page, &[("page", &page)]),
+            None => tr(Message::Document).to_string(),
+        };
+        let severity = match problem.severity {
+            Severity::Error => tr(Message::SeverityError),
+            Severity::Warning => tr(Message::SeverityWarning),
+        };
+        report.push_str(&format!("{}: {}: {}\n", place, severity, problem.message));
+    }
+    let errors = problems
+        .iter()
+        .filter(|problem| problem.severity == Severity::Error)
+        .count();
+    let verdict = match (errors, problems.len() - errors) {
+        (0, 0) => tr(Message::Ok).to_string(),
+        (0, warnings) => trf(Message::OkWith, &[("warnings", &warnings_count(warnings))]),
+        (errors, 0) => trf(Message::FailedWith, &[("errors", &errors_count(errors))]),
+        (errors, warnings) => trf(
+            Message::FailedWithBoth,
+            &[
+                ("errors", &errors_count(errors)),
+                ("warnings", &warnings_count(warnings)),
+            ],
+        ),
+    };
+    let pages = count(page_count, Message::OnePage, Message::Pages);
+    report.push_str(&format!(
+        "{}: {}\n",
+        input.display(),
+        trf(
+            Message::Checked,
+            &[("verdict", &verdict), ("pages", &pages)]
+        )
+    ));
+    report
+}
+
+/// `1 error`, `2 errors`
+fn errors_count(n: usize) -> String {
+    count(n, Message::OneError, Message::Errors)
+}
+
+/// `1 warning`, `2 warnings`
+fn warnings_count(n: usize) -> String {
+    count(n, Message::OneWarning, Message::Warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let problems = [
+            Problem {
+                page: None,
+                severity: Severity::Warning,
+                message: "1 incremental update".to_string(),
+            },
+            Problem {
+                page: Some(2),
+                severity: Severity::Error,
+                message: "text cannot be extracted: bad font".to_string(),
+            },
+        ];
+        assert_eq!(
+            report(Path::new("a.pdf"), 3, &problems),
+            "Document: warning: 1 incremental update\nPage 2: error: text cannot be extracted: bad font\na.pdf: FAILED with 1 error and 1 warning (3 pages checked)\n"
+        );
+        assert_eq!(
+            report(Path::new("a.pdf"), 1, &[]),
+            "a.pdf: OK (1 page checked)\n"
+        );
+    }
+}
@@ -55,9 +55,369 @@ fn create_test_pdf(path: &std::path::Path) {
     doc.save(path).expect("Failed to save test PDF");
 }
 
+/// Helper to create a single-page PDF with a regular (F1) and a math (F2) font
+fn create_math_pdf(path: &std::path::Path) {
+    use lopdf::dictionary;
+
+    create_content_pdf_with(
+        path,
+        "BT\n/F1 12 Tf\n50 700 Td\n(Let ) Tj\n/F2 12 Tf\n(x) Tj\n/F1 12 Tf\n( be a variable) Tj\nET\n",
+        |doc| {
+            let mut font = |name: &str| {
+                doc.add_object(dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => name,
+                    "Encoding" => "WinAnsiEncoding",
+                })
+            };
+            dictionary! {
+                "Font" => dictionary! {
+                    "F1" => font("CMR10"),
+                    "F2" => font("ABCDEF+CMMI10"),
+                },
+            }
+        },
+    );
+}
+
+/// Helper to create a single-page PDF setting a Greek letter in Symbol and a
+/// check mark in ZapfDingbats, neither with an encoding of its own
+fn create_symbol_pdf(path: &std::path::Path) {
+    use lopdf::dictionary;
+
+    create_content_pdf_with(
+        path,
+        "BT\n/F1 12 Tf\n50 700 Td\n(Angle ) Tj\n/F2 12 Tf\n(q \\256 p) Tj\nET\n\
+         BT\n/F3 12 Tf\n50 680 Td\n(4) Tj\n/F1 12 Tf\n( Done) Tj\nET\n",
+        |doc| {
+            let mut font = |name: &str| {
+                doc.add_object(dictionary! {
+                    "Type" => "Font",
+                    "Subtype" => "Type1",
+                    "BaseFont" => name,
+                })
+            };
+            dictionary! {
+                "Font" => dictionary! {
+                    "F2" => font("Symbol"),
+                    "F3" => font("ZapfDingbats"),
+                },
+            }
+        },
+    );
+}
+
+/// Helper to create a single-page PDF with a caption line and a 2x2 grayscale image
+fn create_image_pdf(path: &std::path::Path) {
+    use lopdf::{Stream, dictionary};
+
+    create_content_pdf_with(
+        path,
+        "BT\n/F1 12 Tf\n50 700 Td\n(Overview of the system) Tj\nET\nq\n200 0 0 100 50 550 cm\n/Im1 Do\nQ\nBT\n/F1 10 Tf\n50 530 Td\n(Figure 1: System architecture) Tj\nET\n",
+        |doc| {
+            let image_id = doc.add_object(Stream::new(
+                dictionary! {
+                    "Type" => "XObject",
+                    "Subtype" => "Image",
+                    "Width" => 2,
+                    "Height" => 2,
+                    "ColorSpace" => "DeviceGray",
+                    "BitsPerComponent" => 8,
+                },
+                vec![0, 255, 255, 0],
+            ));
+            dictionary! { "XObject" => dictionary! { "Im1" => image_id } }
+        },
+    );
+}
+
+/// Helper to create a PDF with a small line chart drawn with vector paths
+fn create_chart_pdf(path: &std::path::Path) {
+    create_content_pdf(
+        path,
+        "BT\n/F1 12 Tf\n50 700 Td\n(Quarterly revenue) Tj\nET\n1 w\n0 0 0 RG\n100 400 m\n300 400 l\nS\n100 400 m\n100 550 l\nS\n1 0 0 RG\n100 420 m\n200 480 l\n290 540 l\nS\nBT\n/F1 8 Tf\n110 405 Td\n(Q1) Tj\nET\nBT\n/F1 10 Tf\n100 380 Td\n(Figure 2: Revenue growth) Tj\nET\n",
+    );
+}
+
+/// Helper to create a PDF with a raised footnote marker and its note at the page bottom
+fn create_footnote_pdf(path: &std::path::Path) {
+    create_content_pdf(
+        path,
+        "BT /F1 12 Tf 72 700 Td (The method is fast) Tj ET\n\
+         BT /F1 7 Tf 175 705 Td (1) Tj ET\n\
+         BT /F1 12 Tf 180 700 Td ( in practice.) Tj ET\n\
+         BT /F1 9 Tf 72 80 Td (1 Measured on a laptop.) Tj ET\n",
+    );
+}
+
+/// Helper to create a one-page PDF from a content stream using /F1 (Helvetica)
+/// and /F2 (Helvetica-Bold)
+fn create_content_pdf(path: &std::path::Path, content: &str) {
+    create_content_pdf_with(path, content, |_| lopdf::Dictionary::new());
+}
+
+/// Helper like `create_content_pdf` whose page also has the resources
+/// `resources` adds to the document; fonts named /F1 or /F2 replace
+/// Helvetica and Helvetica-Bold
+fn create_content_pdf_with(
+    path: &std::path::Path,
+    content: &str,
+    resources: impl FnOnce(&mut lopdf::Document) -> lopdf::Dictionary,
+) {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    let mut doc = LopdfDocument::with_version("1.4");
+    let pages_id = doc.new_object_id();
+
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let bold_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica-Bold",
+    });
+    let mut fonts = dictionary! { "F1" => font_id, "F2" => bold_id };
+    let mut page_resources = dictionary! {};
+    for (key, value) in resources(&mut doc) {
+        match value {
+            Object::Dictionary(extra) if key == b"Font" => {
+                for (name, font) in extra {
+                    fonts.set(name, font);
+                }
+            }
+            value => page_resources.set(key, value),
+        }
+    }
+    page_resources.set("Font", fonts);
+
+    let content_id = doc.add_object(Stream::new(dictionary! {}, content.as_bytes().to_vec()));
+    let page_id = doc.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+        "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+        "Resources" => page_resources,
+    });
+
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Count" => 1,
+        "Kids" => vec![page_id.into()],
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+
+    doc.trailer.set("Root", catalog_id);
+    doc.save(path).expect("Failed to save test PDF");
+}
+
+/// Helper to create a PDF with one line of Helvetica text per page
+fn create_pages_pdf(path: &std::path::Path, pages: &[&str]) {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    let mut doc = LopdfDocument::with_version("1.4");
+    let pages_id = doc.new_object_id();
+
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+
+    let mut kids = Vec::new();
+    for text in pages {
+        let content = format!("BT\n/F1 12 Tf\n50 700 Td\n({}) Tj\nET\n", text);
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary!{
+                "Font" => dictionary!{ "F1" => font_id },
+            },
+        });
+        kids.push(page_id.into());
+    }
+
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+
+    doc.trailer.set("Root", catalog_id);
+    doc.save(path).expect("Failed to save test PDF");
+}
+
+/// Three-page PDF whose second page sets a font without naming it
+fn create_broken_page_pdf(path: &std::path::Path) {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    create_pages_pdf(path, &["First page", "Second page", "Third page"]);
+    let mut doc = LopdfDocument::load(path).unwrap();
+    let page_id = doc.get_pages()[&2];
+    let content_id = doc.add_object(Stream::new(
+        dictionary! {},
+        b"BT\n12 Tf\n50 700 Td\n(Second page) Tj\nET\n".to_vec(),
+    ));
+    doc.get_dictionary_mut(page_id)
+        .unwrap()
+        .set("Contents", Object::Reference(content_id));
+    doc.save(path).expect("Failed to save test PDF");
+}
+
+/// One-page cover sheet made a portfolio of `documents`, each a (name, PDF
+/// file) pair, embedded in that order
+fn create_portfolio_pdf(path: &std::path::Path, documents: &[(&str, &std::path::Path)]) {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    create_pages_pdf(path, &["Open this portfolio in a PDF viewer"]);
+    let mut doc = LopdfDocument::load(path).unwrap();
+    let mut names = Vec::new();
+    for (i, (name, file)) in documents.iter().enumerate() {
+        let stream = doc.add_object(Stream::new(
+            dictionary! { "Type" => "EmbeddedFile" },
+            fs::read(file).unwrap(),
+        ));
+        let spec = doc.add_object(dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal(*name),
+            "UF" => Object::string_literal(*name),
+            "EF" => dictionary! { "F" => stream },
+        });
+        names.push(Object::string_literal(format!("{:03}", i)));
+        names.push(Object::Reference(spec));
+    }
+    let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    let catalog = doc.get_dictionary_mut(catalog_id).unwrap();
+    catalog.set(
+        "Names",
+        dictionary! { "EmbeddedFiles" => dictionary! { "Names" => names } },
+    );
+    catalog.set("Collection", dictionary! { "View" => "D" });
+    doc.save(path).expect("Failed to save test PDF");
+}
+
+/// PDF shaped like one saved for fast web view: a linearization dictionary
+/// first, then pages whose content streams are long drawings
+fn create_linearized_pdf(path: &std::path::Path, pages: &[&str]) {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    let mut doc = LopdfDocument::with_version("1.5");
+    // lopdf leaves linearization dictionaries out when saving, so the key is
+    // renamed, and named back in the file
+    doc.add_object(dictionary! { "Linearizes" => 1 });
+    let pages_id = doc.new_object_id();
+    let font_id = doc.add_object(dictionary! {
+        "Type" => "Font",
+        "Subtype" => "Type1",
+        "BaseFont" => "Helvetica",
+    });
+    let mut kids = Vec::new();
+    for text in pages {
+        let mut content = format!("BT\n/F1 12 Tf\n50 700 Td\n({}) Tj\nET\n", text);
+        for i in 0..4000 {
+            content.push_str(&format!("{} {} m {} 0 l S\n", i, i, i));
+        }
+        let content_id = doc.add_object(Stream::new(dictionary! {}, content.into_bytes()));
+        let page_id = doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+            "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+        });
+        kids.push(page_id.into());
+    }
+    let pages = dictionary! {
+        "Type" => "Pages",
+        "Count" => kids.len() as i64,
+        "Kids" => kids,
+    };
+    doc.objects.insert(pages_id, Object::Dictionary(pages));
+    let catalog_id = doc.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    doc.trailer.set("Root", catalog_id);
+    let mut bytes = Vec::new();
+    doc.save_to(&mut bytes).expect("Failed to save test PDF");
+    let key = bytes.windows(11).position(|w| w == b"/Linearizes").unwrap();
+    bytes[key..key + 11].copy_from_slice(b"/Linearized");
+    fs::write(path, bytes).unwrap();
+}
+
+/// Serve `file` over HTTP on a local port, answering range requests.
+/// Returns the base URL and the count of body bytes sent.
+fn serve(file: Vec<u8>) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let sent = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&sent);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut range = None;
+            for line in BufReader::new(&stream).lines() {
+                let line = line.unwrap_or_default();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(bytes) = line.to_ascii_lowercase().strip_prefix("range: bytes=") {
+                    let (start, end) = bytes.split_once('-').unwrap();
+                    let end = end.parse::<usize>().unwrap().min(file.len() - 1);
+                    range = Some((start.parse::<usize>().unwrap(), end));
+                }
+            }
+            let (head, body) = match range {
+                Some((start, end)) => (
+                    format!(
+                        "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes {}-{}/{}\r\n",
+                        start,
+                        end,
+                        file.len()
+                    ),
+                    &file[start..=end],
+                ),
+                None => ("HTTP/1.1 200 OK\r\n".to_string(), &file[..]),
+            };
+            counter.fetch_add(body.len(), Ordering::SeqCst);
+            let _ = write!(
+                stream,
+                "{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                head,
+                body.len()
+            );
+            let _ = stream.write_all(body);
+        }
+    });
+    (url, sent)
+}
+
 /// Helper to get the command for testing
 fn get_test_command() -> Command {
-    Command::new(env!("CARGO_BIN_EXE_pdf2md"))
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_pdf2md"));
+    cmd.env_remove("PDF2MD_LANG");
+    cmd
 }
 
 #[test]
@@ -93,13 +453,44 @@ fn test_missing_input_argument() {
 }
 
 #[test]
-fn test_missing_output_argument() {
+fn test_default_output_path() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_path = temp_dir.path().join("report.md");
+    create_pages_pdf(&input_path, &["Quarterly report"]);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i").arg(&input_path).assert().success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Quarterly report"
+    );
+
+    // The file written by an earlier run is kept unless --force is given
+    fs::write(&output_path, "Edited by hand").unwrap();
     let mut cmd = get_test_command();
     cmd.arg("-i")
-        .arg("input.pdf")
+        .arg(&input_path)
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("required"));
+        .code(1)
+        .stderr(predicate::str::contains("pass --force to overwrite it"));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Edited by hand");
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--dry-run")
+        .assert()
+        .success();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--force")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Quarterly report"
+    );
 }
 
 #[test]
@@ -179,74 +570,3223 @@ fn test_dry_run_flag() {
         .assert()
         .success()
         .stdout(predicate::str::contains("PDF Preview"))
-        .stdout(predicate::str::contains("Pages:"));
+        .stdout(predicate::str::contains("PDF version: 1.4"))
+        .stdout(predicate::str::contains("Pages:"))
+        .stdout(predicate::str::contains(
+            "Links: 0 hyperlinks, 0 internal links\nAnnotations: none\n",
+        ));
 
     // Output file should NOT be created in dry-run mode
     assert!(!output_path.exists());
 }
 
 #[test]
-fn test_short_flags() {
+fn test_dry_run_page_previews() {
     let temp_dir = TempDir::new().unwrap();
     let input_path = temp_dir.path().join("input.pdf");
     let output_path = temp_dir.path().join("output.md");
-
-    create_test_pdf(&input_path);
+    create_pages_pdf(&input_path, &["First page", "Second page", "Third page"]);
 
     let mut cmd = get_test_command();
     cmd.arg("-i")
         .arg(&input_path)
         .arg("-o")
         .arg(&output_path)
-        .arg("-v") // verbose
-        .arg("-n") // dry-run
+        .arg("--dry-run")
+        .arg("--preview-pages")
+        .arg("2")
         .assert()
-        .success();
-
-    // Should not create output in dry-run mode
+        .success()
+        .stdout(predicate::str::contains(
+            "Page previews:\n  Page 1: First page\n  Page 2: Second page\n",
+        ))
+        .stdout(predicate::str::contains(
+            "Text coverage: 100% of pages have a text layer (3 of 3)",
+        ))
+        .stdout(predicate::str::contains("Page 3:").not());
     assert!(!output_path.exists());
 }
 
 #[test]
-fn test_invalid_pdf_header() {
+fn test_dry_run_image_inventory() {
     let temp_dir = TempDir::new().unwrap();
     let input_path = temp_dir.path().join("input.pdf");
     let output_path = temp_dir.path().join("output.md");
+    create_image_pdf(&input_path);
 
-    // Create file with invalid PDF header
-    fs::write(&input_path, b"Not a PDF file").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Images: 1 (1 uncompressed), 4 B\n  Page 1: uncompressed 2×2 (4 B)\n",
+        ));
+}
+
+#[test]
+fn test_dry_run_font_inventory() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["One", "Two"]);
 
     let mut cmd = get_test_command();
     cmd.arg("-i")
         .arg(&input_path)
         .arg("-o")
         .arg(&output_path)
+        .arg("--dry-run")
         .assert()
-        .failure()
-        .code(4)
-        .stderr(predicate::str::contains("not a valid PDF"));
+        .success()
+        .stdout(predicate::str::contains(
+            "Fonts: 1\n  Helvetica: Type1, not embedded, ToUnicode: no, pages 1-2\n",
+        ));
 }
 
 #[test]
-fn test_output_with_nested_directories() {
+fn test_dry_run_heading_outline() {
     let temp_dir = TempDir::new().unwrap();
     let input_path = temp_dir.path().join("input.pdf");
-    let output_path = temp_dir
-        .path()
-        .join("subdir")
-        .join("nested")
-        .join("output.md");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Body text.", "RESULTS"]);
 
-    create_test_pdf(&input_path);
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Heading outline:\n  INTRODUCTION (page 1)\n  RESULTS (page 3)\n",
+        ));
 
+    // The outline follows the heading options
     let mut cmd = get_test_command();
     cmd.arg("-i")
         .arg(&input_path)
         .arg("-o")
         .arg(&output_path)
+        .arg("--dry-run")
+        .arg("--normalize-headings")
+        .arg("--number-headings")
         .assert()
-        .success();
+        .success()
+        .stdout(predicate::str::contains(
+            "Heading outline:\n  input (page 1)\n    1 INTRODUCTION (page 1)\n    2 RESULTS (page 3)\n",
+        ));
 
-    // Verify nested directories and file were created
+    // A scanned page has no text layer
+    create_pages_pdf(&input_path, &["INTRODUCTION", "", "", "RESULTS"]);
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Text coverage: 50% of pages have a text layer (2 of 4)",
+        ));
+}
+
+#[test]
+fn test_warns_about_degrading_features() {
+    use lopdf::{Document as LopdfDocument, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("form.pdf");
+    let output_path = temp_dir.path().join("form.md");
+
+    create_test_pdf(&input_path);
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(catalog_id).unwrap().set(
+        "AcroForm",
+        dictionary! { "XFA" => lopdf::Object::Array(vec![]) },
+    );
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Warning: document contains an XFA form",
+        ));
     assert!(output_path.exists());
 }
+
+/// Encrypt the test PDF with RC4 40-bit (revision 2), `user_password`
+/// opening it and copying disallowed
+fn create_encrypted_pdf(path: &std::path::Path, user_password: &str) {
+    use lopdf::{Document as LopdfDocument, Object, StringFormat, dictionary, encryption};
+
+    // Padding the password is extended with, from the PDF specification
+    const PAD: [u8; 32] = [
+        0x28, 0xbf, 0x4e, 0x5e, 0x4e, 0x75, 0x8a, 0x41, 0x64, 0x00, 0x4e, 0x56, 0xff, 0xfa, 0x01,
+        0x08, 0x2e, 0x2e, 0x00, 0xb6, 0xd0, 0x68, 0x3e, 0x80, 0x2f, 0x0c, 0xa9, 0xfe, 0x64, 0x53,
+        0x69, 0x7a,
+    ];
+    fn rc4(key: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut state: Vec<u8> = (0..=255).collect();
+        let mut j = 0u8;
+        for i in 0..256 {
+            j = j.wrapping_add(state[i]).wrapping_add(key[i % key.len()]);
+            state.swap(i, j as usize);
+        }
+        let (mut i, mut j) = (0u8, 0u8);
+        data.iter()
+            .map(|byte| {
+                i = i.wrapping_add(1);
+                j = j.wrapping_add(state[i as usize]);
+                state.swap(i as usize, j as usize);
+                let k = state[i as usize].wrapping_add(state[j as usize]);
+                byte ^ state[k as usize]
+            })
+            .collect()
+    }
+
+    create_test_pdf(path);
+    let mut doc = LopdfDocument::load(path).unwrap();
+    let id = Object::String(b"0123456789abcdef".to_vec(), StringFormat::Hexadecimal);
+    doc.trailer.set("ID", vec![id.clone(), id]);
+    let encrypt_id = doc.add_object(dictionary! {
+        "Filter" => "Standard",
+        "V" => 1,
+        "R" => 2,
+        "Length" => 40,
+        "O" => Object::String(vec![0x42; 32], StringFormat::Hexadecimal),
+        "P" => -20,
+    });
+    doc.trailer.set("Encrypt", encrypt_id);
+    let key = encryption::get_encryption_key(&doc, user_password, false).unwrap();
+    // What the user password is checked against
+    let check = Object::String(rc4(&key, &PAD), StringFormat::Hexadecimal);
+    doc.get_dictionary_mut(encrypt_id).unwrap().set("U", check);
+    for (&object_id, object) in doc.objects.iter_mut() {
+        if object_id == encrypt_id {
+            continue;
+        }
+        // RC4 encrypts and decrypts alike
+        if let Ok(encrypted) = encryption::decrypt_object(&key, object_id, object)
+            && let Object::Stream(stream) = object
+        {
+            stream.set_content(encrypted);
+        }
+    }
+    doc.save(path).unwrap();
+}
+
+#[test]
+fn test_decrypts_empty_user_password() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("protected.pdf");
+    let output_path = temp_dir.path().join("protected.md");
+    create_encrypted_pdf(&input_path, "");
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "owner-password restrictions were bypassed",
+        ))
+        .stderr(predicate::str::contains("Warning").not());
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Test PDF");
+}
+
+#[test]
+fn test_decrypts_with_password_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("locked.pdf");
+    let output_path = temp_dir.path().join("locked.md");
+    let password_path = temp_dir.path().join("password");
+    create_encrypted_pdf(&input_path, "s3cret");
+
+    fs::write(&password_path, "wrong\n").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--password-file")
+        .arg(&password_path)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("the password is incorrect"));
+    assert!(!output_path.exists());
+
+    fs::write(&password_path, "s3cret\n").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--password-file")
+        .arg(&password_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Warning").not())
+        .stderr(predicate::str::contains("bypassed").not());
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Test PDF");
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--dry-run")
+        .arg("--password")
+        .arg("s3cret")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("opened with the password given"));
+}
+
+#[test]
+fn test_recovers_truncated_pdf() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("mangled.pdf");
+    let output_path = temp_dir.path().join("mangled.md");
+
+    // Lose the cross-reference data at the end, as a mail gateway might
+    create_test_pdf(&input_path);
+    let bytes = fs::read(&input_path).unwrap();
+    let cut = bytes
+        .windows(10)
+        .position(|window| window == b"/Type/XRef")
+        .unwrap();
+    fs::write(&input_path, &bytes[..cut]).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("rebuilt it from 5 objects"));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Test PDF");
+}
+
+#[test]
+fn test_rejects_deeply_nested_pdf() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("nested.pdf");
+    let output_path = temp_dir.path().join("nested.md");
+
+    // Nesting like this would overflow the parser's stack
+    let mut bytes = b"%PDF-1.4\n1 0 obj\n".to_vec();
+    bytes.extend(std::iter::repeat_n(b'[', 100_000));
+    bytes.extend(std::iter::repeat_n(b']', 100_000));
+    bytes.extend_from_slice(b"\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n");
+    fs::write(&input_path, bytes).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("nested more than 256 levels deep"));
+}
+
+#[test]
+fn test_strict_and_lenient_modes() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("broken.pdf");
+    let output_path = temp_dir.path().join("broken.md");
+    create_broken_page_pdf(&input_path);
+
+    // Lenient, the default, skips the page and says so
+    for flags in [&[][..], &["--lenient"][..]] {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .args(flags)
+            .assert()
+            .success()
+            .stderr(predicate::str::contains(
+                "1 page could not be extracted and was skipped: 2",
+            ));
+        let markdown = fs::read_to_string(&output_path).unwrap();
+        assert_eq!(
+            markdown,
+            "First page\n\n> \u{26a0}\u{fe0f} Page 2 could not be extracted\n\nThird page"
+        );
+    }
+
+    fs::remove_file(&output_path).unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strict")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to extract text from page 2",
+        ));
+    assert!(!output_path.exists());
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strict")
+        .arg("--lenient")
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_keep_partial_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("broken.pdf");
+    let output_path = temp_dir.path().join("broken.md");
+    create_broken_page_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strict")
+        .arg("--keep-partial")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Failed to extract text from page 2",
+        ));
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "First page\n\n> \u{26a0}\u{fe0f} Conversion stopped at page 2 of 3; the rest of the document is missing"
+    );
+}
+
+#[test]
+fn test_provenance_front_matter() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_test_pdf(&input_path);
+    let size = fs::metadata(&input_path).unwrap().len();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--provenance")
+        .assert()
+        .success();
+
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    let expected = format!(
+        "---\nprovenance:\n  source: \"input.pdf\"\n  size: {}\n  sha256: ",
+        size
+    );
+    assert!(markdown.starts_with(&expected), "{}", markdown);
+    let sha256 = markdown
+        .lines()
+        .nth(4)
+        .unwrap()
+        .trim_start_matches("  sha256: ");
+    assert_eq!(sha256.len(), 64);
+    assert!(markdown.contains(&format!(
+        "  converter: \"pdf2md {}\"\n",
+        env!("CARGO_PKG_VERSION")
+    )));
+    assert!(markdown.ends_with("---\n\nTest PDF"));
+}
+
+#[test]
+fn test_annotate_provenance() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(
+        &input_path,
+        &["The survey ran for a month.", "Results follow below."],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--annotate-provenance")
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "<!-- p.1 -->\nThe survey ran for a month.\n\n<!-- p.2 -->\nResults follow below."
+    );
+}
+
+#[test]
+fn test_page_anchors() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(
+        &input_path,
+        &["The survey ran for a month.", "Results follow below."],
+    );
+
+    for (style, expected) in [
+        (
+            None,
+            "<a id=\"page-1\"></a>\n\nThe survey ran for a month.\n\n<a id=\"page-2\"></a>\n\nResults follow below.",
+        ),
+        (
+            Some("heading"),
+            "###### Page 1 {#page-1}\n\nThe survey ran for a month.\n\n###### Page 2 {#page-2}\n\nResults follow below.",
+        ),
+    ] {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--page-anchors")
+            .args(style)
+            .assert()
+            .success();
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_short_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("-v") // verbose
+        .arg("-n") // dry-run
+        .assert()
+        .success();
+
+    // Should not create output in dry-run mode
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_invalid_pdf_header() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+
+    // Create file with invalid PDF header
+    fs::write(&input_path, b"Not a PDF file").unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .failure()
+        .code(4)
+        .stderr(predicate::str::contains("not a valid PDF"));
+}
+
+#[test]
+fn test_input_without_pdf_extension() {
+    let temp_dir = TempDir::new().unwrap();
+    // Content-addressed blob: a valid PDF named by its hash
+    let input_path = temp_dir.path().join("3f2a9c");
+    let output_path = temp_dir.path().join("output.md");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    assert!(output_path.exists());
+}
+
+#[test]
+fn test_output_with_nested_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir
+        .path()
+        .join("subdir")
+        .join("nested")
+        .join("output.md");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+
+    // Verify nested directories and file were created
+    assert!(output_path.exists());
+}
+
+#[test]
+fn test_math_flag_emits_latex() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("math.pdf");
+    let output_path = temp_dir.path().join("math.md");
+
+    create_math_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--math")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("Let $x$ be a variable"));
+}
+
+#[test]
+fn test_math_flag_writes_tagged_formulas() {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("tagged.pdf");
+    let output_path = temp_dir.path().join("tagged.md");
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (The area is ) Tj\n\
+         /Formula <</MCID 0>> BDC (A = pi r2) Tj EMC\n\
+         ( for a circle.) Tj ET\n\
+         BT /F1 12 Tf 72 680 Td /Formula <</MCID 1>> BDC (E = mc2) Tj EMC ET\n",
+    );
+    // Tag both as formulas; only the first has MathML
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let page_id = doc.page_iter().next().unwrap();
+    let root_id = doc.new_object_id();
+    let mathml_id = doc.add_object(Stream::new(
+        dictionary! { "Type" => "EmbeddedFile", "Subtype" => "application/mathml+xml" },
+        "<math><mi>A</mi><mo>=</mo><mi>π</mi><msup><mi>r</mi><mn>2</mn></msup></math>"
+            .as_bytes()
+            .to_vec(),
+    ));
+    let area_id = doc.add_object(dictionary! {
+        "S" => "Formula",
+        "P" => root_id,
+        "AF" => vec![dictionary! {
+            "Type" => "Filespec",
+            "F" => Object::string_literal("area.mml"),
+            "EF" => dictionary! { "F" => mathml_id },
+        }
+        .into()],
+    });
+    let energy_id = doc.add_object(dictionary! { "S" => "Formula", "P" => root_id });
+    doc.objects.insert(
+        root_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "StructTreeRoot",
+            "K" => vec![area_id.into(), energy_id.into()],
+            "ParentTree" => dictionary! {
+                "Nums" => vec![0.into(), vec![area_id.into(), energy_id.into()].into()],
+            },
+        }),
+    );
+    doc.get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("StructParents", 0);
+    doc.catalog_mut().unwrap().set("StructTreeRoot", root_id);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--math")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(
+        content.contains("The area is $A=\\pi r^{2}$ for a circle."),
+        "{}",
+        content
+    );
+    assert!(content.contains("$$E = mc2$$"), "{}", content);
+}
+
+#[test]
+fn test_symbol_fonts_map_to_unicode() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("symbols.pdf");
+    let output_path = temp_dir.path().join("symbols.md");
+
+    create_symbol_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("Angle θ → π"), "{}", content);
+    assert!(content.contains("✔ Done"), "{}", content);
+}
+
+#[test]
+fn test_images_flag_writes_and_references_images() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let output_path = temp_dir.path().join("figure.md");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--images")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("![Figure 1: System architecture](images/page-1-image-1.png)"));
+    assert!(content.contains("*Figure 1: System architecture*"));
+    assert!(temp_dir.path().join("images/page-1-image-1.png").exists());
+}
+
+#[test]
+fn test_vector_graphics_flag_writes_svg() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("chart.pdf");
+    let output_path = temp_dir.path().join("chart.md");
+
+    create_chart_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--vector-graphics")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("![Figure 2: Revenue growth](images/page-1-figure-1.svg)"));
+    assert!(content.contains("Quarterly revenue"));
+    // Axis labels live inside the SVG, not the running text
+    assert!(!content.contains("Q1"));
+
+    let svg = fs::read_to_string(temp_dir.path().join("images/page-1-figure-1.svg")).unwrap();
+    assert!(svg.starts_with("<svg"));
+    assert!(svg.contains("stroke=\"#ff0000\""));
+    assert!(svg.contains(">Q1</text>"));
+}
+
+#[test]
+fn test_min_image_size_skips_small_images() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let output_path = temp_dir.path().join("figure.md");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--images")
+        .arg("--min-image-size")
+        .arg("8")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(!content.contains("!["));
+    assert!(content.contains("Figure 1: System architecture"));
+    assert!(!temp_dir.path().join("images").exists());
+}
+
+#[test]
+fn test_site_hugo_writes_page_bundle() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("System Overview.pdf");
+    let site_root = temp_dir.path().join("site");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("hugo")
+        .arg("--images")
+        .assert()
+        .success();
+
+    let bundle = site_root.join("content/system-overview");
+    let content = fs::read_to_string(bundle.join("index.md")).unwrap();
+    assert!(content.starts_with("---\ntitle: \"System Overview\"\ndate: "));
+    assert!(content.contains("\ndraft: false\n---\n"));
+    assert!(content.contains("](page-1-image-1.png)"));
+    assert!(bundle.join("page-1-image-1.png").exists());
+}
+
+#[test]
+fn test_site_mkdocs_writes_chapters_and_nav() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("guide.pdf");
+    let site_root = temp_dir.path().join("site");
+
+    create_pages_pdf(
+        &input_path,
+        &[
+            "Welcome to the guide.",
+            "INTRODUCTION",
+            "This tool converts documents.",
+            "USAGE",
+            "Run it from the shell.",
+        ],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("mkdocs")
+        .arg("--mkdocs-nav")
+        .assert()
+        .success();
+
+    let docs = site_root.join("docs");
+    let index = fs::read_to_string(docs.join("index.md")).unwrap();
+    assert!(index.starts_with("# guide\n\nWelcome to the guide."));
+    assert!(index.contains("- [INTRODUCTION](01-introduction.md)"));
+    let usage = fs::read_to_string(docs.join("02-usage.md")).unwrap();
+    assert_eq!(usage, "# USAGE\n\nRun it from the shell.");
+
+    let mkdocs_yml = fs::read_to_string(site_root.join("mkdocs.yml")).unwrap();
+    assert!(mkdocs_yml.contains("  - \"INTRODUCTION\": 01-introduction.md\n"));
+}
+
+#[test]
+fn test_site_obsidian_writes_note_with_embeds() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("overview.pdf");
+    let vault = temp_dir.path().join("vault");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&vault)
+        .arg("--site")
+        .arg("obsidian")
+        .arg("--images")
+        .arg("--image-dir")
+        .arg("attachments")
+        .arg("--tags")
+        .arg("architecture")
+        .assert()
+        .success();
+
+    let note = fs::read_to_string(vault.join("overview.md")).unwrap();
+    assert!(note.starts_with("---\ntitle: \"overview\"\ntags:\n  - pdf\n  - architecture\n"));
+    assert!(note.contains("![[page-1-image-1.png|Figure 1: System architecture]]"));
+    assert!(vault.join("attachments/page-1-image-1.png").exists());
+}
+
+#[test]
+fn test_site_jekyll_writes_dated_post() {
+    use lopdf::{Document as LopdfDocument, Object, StringFormat, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let site_root = temp_dir.path().join("blog");
+
+    create_image_pdf(&input_path);
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let info_id = doc.add_object(dictionary! {
+        "Title" => Object::String(b"Launch Notes".to_vec(), StringFormat::Literal),
+        "CreationDate" => Object::String(b"D:20240501103045+02'00'".to_vec(), StringFormat::Literal),
+    });
+    doc.trailer.set("Info", info_id);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("jekyll")
+        .arg("--images")
+        .arg("--categories")
+        .arg("news")
+        .assert()
+        .success();
+
+    let post = fs::read_to_string(site_root.join("_posts/2024-05-01-launch-notes.md")).unwrap();
+    assert!(post.starts_with(
+        "---\nlayout: post\ntitle: \"Launch Notes\"\ndate: 2024-05-01 10:30:45 +0200\ncategories: [\"news\"]\n---\n"
+    ));
+    assert!(post.contains("](/assets/launch-notes/page-1-image-1.png)"));
+    assert!(
+        site_root
+            .join("assets/launch-notes/page-1-image-1.png")
+            .exists()
+    );
+}
+
+#[test]
+fn test_qmd_output_has_quarto_header() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("analysis.pdf");
+    let output_path = temp_dir.path().join("analysis.qmd");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+
+    let document = fs::read_to_string(&output_path).unwrap();
+    assert!(document.starts_with("---\ntitle: \"analysis\"\n"));
+    assert!(document.contains("format: html\n---\n\n"));
+}
+
+#[test]
+fn test_pandoc_json_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("notes.pdf");
+    let output_path = temp_dir.path().join("notes.json");
+
+    create_pages_pdf(
+        &input_path,
+        &["INTRODUCTION", "This tool converts documents."],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--format")
+        .arg("pandoc-json")
+        .assert()
+        .success();
+
+    let document = fs::read_to_string(&output_path).unwrap();
+    assert!(document.starts_with(
+        "{\"pandoc-api-version\":[1,23,1],\"meta\":{\"title\":{\"t\":\"MetaInlines\",\"c\":[{\"t\":\"Str\",\"c\":\"notes\"}]}},\"blocks\":["
+    ));
+    assert!(document.contains(
+        "{\"t\":\"Header\",\"c\":[2,[\"introduction\",[],[]],[{\"t\":\"Str\",\"c\":\"INTRODUCTION\"}]]}"
+    ));
+    assert!(document.contains("{\"t\":\"Str\",\"c\":\"documents.\"}"));
+}
+
+#[test]
+fn test_sidecar_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("notes.pdf");
+    let output_path = temp_dir.path().join("notes.md");
+
+    create_pages_pdf(
+        &input_path,
+        &["INTRODUCTION", "This tool converts documents."],
+    );
+
+    for format in ["json", "yaml"] {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--sidecar")
+            .arg(format)
+            .assert()
+            .success();
+    }
+
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(!markdown.contains("notes.pdf"));
+    let json = fs::read_to_string(temp_dir.path().join("notes.meta.json")).unwrap();
+    assert!(
+        json.starts_with("{\n  \"output\": \"notes.md\",\n"),
+        "{}",
+        json
+    );
+    assert!(json.contains("\"pages\": 2,"));
+    assert!(json.contains("\"warnings\": [],"));
+    assert!(json.contains(
+        "\"page_map\": [\n    {\n      \"page\": 1,\n      \"line\": 1\n    },\n    {\n      \"page\": 2,\n      \"line\": 3\n    }\n  ]"
+    ));
+    let yaml = fs::read_to_string(temp_dir.path().join("notes.meta.yaml")).unwrap();
+    assert!(yaml.contains("\npage_map:\n  - page: 1\n    line: 1\n  - page: 2\n    line: 3\n"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sanitize_control_characters() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["Total due"]);
+
+    // The pre-hook's text is sanitized with the extracted text
+    let convert = |policy: &str| {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--pre-hook")
+            .arg("sed \"s/Total/Total$(printf '\\007')/\"")
+            .arg("--sanitize")
+            .arg(policy);
+        cmd
+    };
+
+    convert("strip")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "control characters and undecodable text removed: 1",
+        ));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Total due");
+
+    convert("replace").assert().success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Total\u{FFFD} due"
+    );
+
+    convert("error")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("U+0007 (a control character)"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_invisible_chars() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["Total due"]);
+
+    // A zero-width joiner after every letter, as some generators write them
+    let convert = |policy: &str| {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--pre-hook")
+            .arg("sed \"s/\\([a-z]\\)/\\1$(printf '\\342\\200\\215')/g\"")
+            .arg("--invisible-chars")
+            .arg(policy);
+        cmd
+    };
+
+    convert("warn")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "zero-width joiners and bidi controls in the text: 7",
+        ));
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "To\u{200D}t\u{200D}a\u{200D}l\u{200D} d\u{200D}u\u{200D}e\u{200D}"
+    );
+
+    convert("strip")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "zero-width joiners and bidi controls removed: 7",
+        ));
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Total due");
+
+    convert("keep")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("zero-width").not());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_normalize_unicode() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["cafe office"]);
+
+    // A decomposed é and an ffi ligature, from the pre-hook
+    let convert = |form: &str| {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--pre-hook")
+            .arg("sed \"s/cafe/cafe$(printf '\\314\\201')/; s/ffi/$(printf '\\357\\254\\203')/\"")
+            .arg("--normalize")
+            .arg(form);
+        cmd.assert().success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+
+    assert_eq!(convert("none"), "cafe\u{301} o\u{fb03}ce");
+    assert_eq!(convert("nfc"), "caf\u{e9} o\u{fb03}ce");
+    assert_eq!(convert("nfkc"), "caf\u{e9} office");
+}
+
+#[test]
+fn test_batch_manifest() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("papers");
+    let output_dir = temp_dir.path().join("out");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_pages_pdf(&input_dir.join("good.pdf"), &["Lenses bend light."]);
+    fs::write(input_dir.join("broken.pdf"), b"not a pdf").unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .assert()
+        .failure();
+
+    let manifest = fs::read_to_string(output_dir.join("manifest.json")).unwrap();
+    let broken = manifest.find("broken.pdf").unwrap();
+    let good = manifest.find("good.pdf").unwrap();
+    assert!(broken < good, "{}", manifest);
+    assert!(manifest.contains("\"status\": \"failed\""), "{}", manifest);
+    assert!(manifest.contains("\"status\": \"ok\""), "{}", manifest);
+    assert!(manifest.contains(&format!(
+        "\"outputs\": [\n        \"{}\"\n      ]",
+        output_dir.join("good.md").display()
+    )));
+    // SHA-256 of "not a pdf"
+    assert!(manifest.contains(
+        "\"sha256\": \"07bcbca5e5cff5eadb6a3578850bb9413f9bfd2406d9210b0c25b7f78449889a\""
+    ));
+}
+
+#[test]
+fn test_search_index_of_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("papers");
+    let output_dir = temp_dir.path().join("site");
+    fs::create_dir_all(input_dir.join("thermo")).unwrap();
+    create_pages_pdf(&input_dir.join("optics.pdf"), &["Lenses bend light."]);
+    create_pages_pdf(
+        &input_dir.join("thermo").join("heat.pdf"),
+        &["Entropy always grows."],
+    );
+
+    let index_path = output_dir.join("search.json");
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--recursive")
+        .arg("--search-index")
+        .arg(format!("lunr:{}", index_path.display()))
+        .assert()
+        .success();
+
+    let index = fs::read_to_string(&index_path).unwrap();
+    assert!(
+        index.starts_with("{\"version\":\"2.3.9\",\"fields\":[\"title\",\"body\"],"),
+        "{}",
+        index
+    );
+    assert!(index.contains("[\"entropy\",{\"_index\":"));
+    assert!(index.contains("\"body\":{\"thermo/heat.md\":{}}"));
+    assert!(index.contains("\"optics.md\":{\"title\":\"optics\"}"));
+}
+
+#[test]
+fn test_chat_mode_uses_bold_headings() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("notes.pdf");
+    let output_path = temp_dir.path().join("notes.md");
+
+    create_pages_pdf(
+        &input_path,
+        &["INTRODUCTION", "This tool converts documents."],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--chat")
+        .assert()
+        .success();
+
+    let chat = fs::read_to_string(&output_path).unwrap();
+    assert!(chat.starts_with("**INTRODUCTION**\n\nThis tool converts documents."));
+    assert!(!chat.contains("## "));
+}
+
+#[test]
+fn test_site_docusaurus_orders_chapters_in_sidebar() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("manual.pdf");
+    let site_root = temp_dir.path().join("website");
+
+    create_pages_pdf(
+        &input_path,
+        &[
+            "Welcome to the manual.",
+            "SETUP",
+            "Warning: Unplug the device first.",
+            "USAGE",
+            "Press the button.",
+        ],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("docusaurus")
+        .assert()
+        .success();
+
+    let docs = site_root.join("docs/manual");
+    let index = fs::read_to_string(docs.join("index.md")).unwrap();
+    assert!(index.starts_with("---\nid: index\ntitle: \"manual\"\nsidebar_position: 1\n---\n"));
+    let setup = fs::read_to_string(docs.join("01-setup.md")).unwrap();
+    assert!(setup.starts_with("---\nid: 01-setup\ntitle: \"SETUP\"\nsidebar_position: 2\n---\n"));
+    assert!(setup.contains(":::warning\n\nUnplug the device first.\n\n:::"));
+    let usage = fs::read_to_string(docs.join("02-usage.md")).unwrap();
+    assert!(usage.contains("sidebar_position: 3\n"));
+}
+
+#[test]
+fn test_site_zola_writes_toml_front_matter() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("notes.pdf");
+    let site_root = temp_dir.path().join("site");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("zola")
+        .arg("--front-matter-format")
+        .arg("toml")
+        .arg("--tags")
+        .arg("rust,pdf")
+        .assert()
+        .success();
+
+    let page = fs::read_to_string(site_root.join("content/notes/index.md")).unwrap();
+    assert!(page.starts_with("+++\ntitle = \"notes\"\ndate = "));
+    assert!(page.contains("\n[taxonomies]\ntags = [\"rust\", \"pdf\"]\n+++\n"));
+}
+
+#[test]
+fn test_toml_front_matter_rejected_for_yaml_sites() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("notes.pdf");
+
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(temp_dir.path().join("blog"))
+        .arg("--site")
+        .arg("jekyll")
+        .arg("--front-matter-format")
+        .arg("toml")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "requires --site hugo or --site zola",
+        ));
+}
+
+#[test]
+fn test_site_notion_zips_page_with_images() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let export_dir = temp_dir.path().join("notion");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&export_dir)
+        .arg("--site")
+        .arg("notion")
+        .arg("--images")
+        .assert()
+        .success();
+
+    let page = fs::read_to_string(export_dir.join("figure.md")).unwrap();
+    assert!(page.contains("](images/page-1-image-1.png)"));
+
+    let archive = fs::read(export_dir.join("figure.zip")).unwrap();
+    assert!(archive.starts_with(b"PK\x03\x04"));
+    let contains = |name: &[u8]| archive.windows(name.len()).any(|window| window == name);
+    assert!(contains(b"figure.md"));
+    assert!(contains(b"images/page-1-image-1.png"));
+}
+
+#[test]
+fn test_footnotes_reference_style() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("paper.pdf");
+    let output_path = temp_dir.path().join("paper.md");
+
+    create_footnote_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--footnotes")
+        .assert()
+        .success();
+
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("The method is fast[^1] in practice."));
+    assert!(markdown.ends_with("\n\n[^1]: Measured on a laptop."));
+    assert_eq!(markdown.matches("Measured on a laptop.").count(), 1);
+}
+
+#[test]
+fn test_footnotes_inline_and_notes_styles() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("paper.pdf");
+    create_footnote_pdf(&input_path);
+
+    let inline_path = temp_dir.path().join("inline.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&inline_path)
+        .arg("--footnotes")
+        .arg("inline")
+        .assert()
+        .success();
+    let inline = fs::read_to_string(&inline_path).unwrap();
+    assert!(inline.contains("fast^[Measured on a laptop.] in practice."));
+
+    let notes_path = temp_dir.path().join("notes.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&notes_path)
+        .arg("--footnotes=notes")
+        .assert()
+        .success();
+    let notes = fs::read_to_string(&notes_path).unwrap();
+    assert!(notes.contains("fast<sup>1</sup> in practice."));
+    assert!(notes.ends_with("## Notes\n\n1. Measured on a laptop."));
+}
+
+#[test]
+fn test_heading_numbering_flags() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    create_pages_pdf(
+        &input_path,
+        &[
+            "2. METHODS",
+            "We sampled widely.",
+            "3. RESULTS",
+            "It worked.",
+        ],
+    );
+
+    let stripped_path = temp_dir.path().join("stripped.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&stripped_path)
+        .arg("--strip-heading-numbers")
+        .assert()
+        .success();
+    let stripped = fs::read_to_string(&stripped_path).unwrap();
+    assert!(stripped.contains("## METHODS\n\nWe sampled widely.\n\n## RESULTS"));
+
+    let numbered_path = temp_dir.path().join("numbered.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&numbered_path)
+        .arg("--number-headings")
+        .assert()
+        .success();
+    let numbered = fs::read_to_string(&numbered_path).unwrap();
+    assert!(numbered.contains("## 1 METHODS\n\nWe sampled widely.\n\n## 2 RESULTS"));
+}
+
+#[test]
+fn test_normalize_headings_adds_title_h1() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("handbook.pdf");
+    let output_path = temp_dir.path().join("handbook.md");
+    create_pages_pdf(
+        &input_path,
+        &["METHODS", "We sampled widely.", "RESULTS", "It worked."],
+    );
+
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--normalize-headings")
+        .arg("--number-headings")
+        .assert()
+        .success();
+
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.starts_with("# handbook\n\n## 1 METHODS\n\n"));
+    assert!(markdown.contains("\n\n## 2 RESULTS\n\n"));
+    assert_eq!(markdown.matches("# ").count(), 3);
+}
+
+#[test]
+fn test_definition_lists() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("terms.pdf");
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 720 Td (Key measurements are defined below.) Tj ET\n\
+         BT /F2 12 Tf 72 700 Td (Latency) Tj ET\n\
+         BT /F1 12 Tf 100 686 Td (Time taken by one request.) Tj ET\n\
+         BT /F2 12 Tf 72 660 Td (Throughput) Tj ET\n\
+         BT /F1 12 Tf 100 646 Td (Requests served per second.) Tj ET\n",
+    );
+
+    let output_path = temp_dir.path().join("terms.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--definition-lists")
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains(
+        "Latency\n: Time taken by one request.\n\nThroughput\n: Requests served per second."
+    ));
+
+    // Without the flag the layout is left to the plain extractor
+    let plain_path = temp_dir.path().join("plain.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&plain_path)
+        .assert()
+        .success();
+    assert!(!fs::read_to_string(&plain_path).unwrap().contains("\n: "));
+}
+
+#[test]
+fn test_horizontal_rules_become_separators() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("sections.pdf");
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (The first section ends here.) Tj ET\n\
+         0.5 w 72 680 m 540 680 l S\n\
+         BT /F1 12 Tf 72 660 Td (The second section starts here.) Tj ET\n",
+    );
+
+    let output_path = temp_dir.path().join("sections.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--horizontal-rules")
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert_eq!(
+        markdown,
+        "The first section ends here.\n\n---\n\nThe second section starts here."
+    );
+}
+
+#[test]
+fn test_underline_style() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("underline.pdf");
+    // Without font widths each glyph advances half an em, so "manual" spans 126-162
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (Read the ) Tj (manual) Tj ( today.) Tj ET\n\
+         0.5 w 126 698 m 162 698 l S\n",
+    );
+
+    for (style, expected) in [
+        ("emphasis", "Read the *manual* today."),
+        ("html", "Read the <u>manual</u> today."),
+    ] {
+        let output_path = temp_dir.path().join(format!("{}.md", style));
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--underline")
+            .arg(style)
+            .assert()
+            .success();
+        assert_eq!(fs::read_to_string(&output_path).unwrap(), expected);
+    }
+
+    // The underline is not a section break
+    let output_path = temp_dir.path().join("rules.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--horizontal-rules")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Read the manual today."
+    );
+}
+
+#[test]
+fn test_strikethrough() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("redline.pdf");
+    // "30 days" spans 180-222 at half an em per glyph; the line crosses it at mid-height
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (Payment is due in ) Tj (30 days) Tj ( 14 days.) Tj ET\n\
+         0.5 w 180 704 m 222 704 l S\n",
+    );
+
+    let output_path = temp_dir.path().join("redline.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strikethrough")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Payment is due in ~~30 days~~ 14 days."
+    );
+}
+
+#[test]
+fn test_text_colors() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("spec.pdf");
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (The client ) Tj 0.8 0 0 rg (MUST) Tj 0 g ( retry.) Tj ET\n",
+    );
+
+    let output_path = temp_dir.path().join("spec.md");
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--text-colors")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "The client <span style=\"color:#cc0000\">MUST</span> retry."
+    );
+}
+
+#[test]
+fn test_drop_cap_joins_its_word() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("chapter.pdf");
+    create_content_pdf(
+        &input_path,
+        "BT /F2 18 Tf 72 740 Td (Chapter One) Tj ET\n\
+         BT /F1 40 Tf 72 672 Td (T) Tj ET\n\
+         BT /F1 12 Tf 100 700 Td (he night was dark, and the rain fell) Tj ET\n\
+         BT /F1 12 Tf 100 686 Td (in torrents on the old house.) Tj ET\n\
+         0.5 w 72 725 m 540 725 l S\n",
+    );
+
+    // Extracted the plain way, and from the page layout for the rule
+    for args in [&[][..], &["--horizontal-rules"]] {
+        let output_path = temp_dir.path().join("chapter.md");
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .args(args)
+            .assert()
+            .success();
+        let markdown = fs::read_to_string(&output_path).unwrap();
+        assert!(markdown.contains("The night was dark"), "{}", markdown);
+        assert!(!markdown.contains("\nT\n"), "{}", markdown);
+    }
+}
+
+#[test]
+fn test_image_format_rejects_unknown_value() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let output_path = temp_dir.path().join("figure.md");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--images")
+        .arg("--image-format")
+        .arg("gif")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unknown image format 'gif'"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_alt_text_cmd_supplies_image_alt_text() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    let output_path = temp_dir.path().join("figure.md");
+
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--images")
+        .arg("--alt-text-cmd")
+        .arg("echo A checkerboard diagram")
+        .assert()
+        .success();
+
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("![A checkerboard diagram](images/page-1-image-1.png)"));
+}
+
+#[test]
+fn test_toc_subcommand() {
+    use lopdf::{Document as LopdfDocument, Object, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("book.pdf");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Body text.", "RESULTS"]);
+
+    // Without bookmarks, the detected sections are listed
+    let mut cmd = get_test_command();
+    cmd.arg("toc")
+        .arg(&input_path)
+        .assert()
+        .success()
+        .stdout("- INTRODUCTION (page 1)\n- RESULTS (page 3)\n")
+        .stderr(predicate::str::contains("no bookmarks"));
+
+    // Bookmarks: Chapter 1 on page 1, with Section 1.1 on page 2 under it
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let pages = doc.get_pages();
+    let outlines_id = doc.new_object_id();
+    let chapter_id = doc.new_object_id();
+    let section_id = doc.add_object(dictionary! {
+        "Title" => Object::string_literal("Section 1.1"),
+        "Parent" => chapter_id,
+        "Dest" => vec![pages[&2].into(), "Fit".into()],
+    });
+    doc.objects.insert(
+        chapter_id,
+        Object::Dictionary(dictionary! {
+            "Title" => Object::string_literal("Chapter 1"),
+            "Parent" => outlines_id,
+            "First" => section_id,
+            "Last" => section_id,
+            "Dest" => vec![pages[&1].into(), "Fit".into()],
+        }),
+    );
+    doc.objects.insert(
+        outlines_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Outlines",
+            "First" => chapter_id,
+            "Last" => chapter_id,
+        }),
+    );
+    let catalog_id = doc.trailer.get(b"Root").unwrap().as_reference().unwrap();
+    doc.get_dictionary_mut(catalog_id)
+        .unwrap()
+        .set("Outlines", outlines_id);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("toc")
+        .arg(&input_path)
+        .assert()
+        .success()
+        .stdout("- Chapter 1 (page 1)\n  - Section 1.1 (page 2)\n");
+
+    let mut cmd = get_test_command();
+    cmd.arg("toc")
+        .arg(&input_path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .stdout(
+            "[\n  {\"level\": 1, \"title\": \"Chapter 1\", \"page\": 1},\n  {\"level\": 2, \"title\": \"Section 1.1\", \"page\": 2}\n]\n",
+        );
+
+    // Conversion still needs -i
+    let mut cmd = get_test_command();
+    cmd.arg("-o").arg(&input_path).assert().failure();
+}
+
+#[test]
+fn test_extract_images_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_dir = temp_dir.path().join("figures");
+    create_image_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("extract-images")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_dir)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 1 image and manifest.json"));
+
+    let image = fs::read(output_dir.join("page-1-image-1.png")).unwrap();
+    assert!(image.starts_with(b"\x89PNG"));
+    let manifest = fs::read_to_string(output_dir.join("manifest.json")).unwrap();
+    assert_eq!(
+        manifest,
+        format!(
+            "[\n  {{\"file\": \"page-1-image-1.png\", \"page\": 1, \"width\": 2, \"height\": 2, \"bytes\": {}}}\n]\n",
+            image.len()
+        )
+    );
+    // No Markdown is written
+    assert_eq!(fs::read_dir(&output_dir).unwrap().count(), 2);
+
+    // The size filter applies as in conversion
+    let filtered_dir = temp_dir.path().join("filtered");
+    let mut cmd = get_test_command();
+    cmd.arg("extract-images")
+        .arg(&input_path)
+        .arg("--output-dir")
+        .arg(&filtered_dir)
+        .arg("--min-image-size")
+        .arg("16")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Wrote 0 images"));
+    assert_eq!(
+        fs::read_to_string(filtered_dir.join("manifest.json")).unwrap(),
+        "[]\n"
+    );
+}
+
+#[test]
+fn test_metadata_subcommand() {
+    use lopdf::{Document as LopdfDocument, Object, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    create_pages_pdf(&input_path, &["First page", "Second page"]);
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let info_id = doc.add_object(dictionary! {
+        "Title" => Object::string_literal("Annual \"Report\""),
+        "Producer" => Object::string_literal("TestWriter 2.0"),
+        "CreationDate" => Object::string_literal("D:20240501103045+02'00'"),
+    });
+    doc.trailer.set("Info", info_id);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("metadata")
+        .arg(&input_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Pages: 2\nTitle: Annual \"Report\"\nProducer: TestWriter 2.0\nCreated: 2024-05-01T10:30:45+02:00\n",
+        ))
+        .stdout(predicate::str::contains("Author").not());
+
+    let mut cmd = get_test_command();
+    cmd.arg("metadata")
+        .arg(&input_path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("{\n"))
+        .stdout(predicate::str::contains(
+            "  \"title\": \"Annual \\\"Report\\\"\",\n  \"author\": null,\n",
+        ))
+        .stdout(predicate::str::contains(
+            "  \"has_text\": true,\n  \"signatures\": []\n}\n",
+        ));
+
+    let mut cmd = get_test_command();
+    cmd.arg("metadata")
+        .arg(&input_path)
+        .arg("--format")
+        .arg("yaml")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "pages: 2\ntitle: \"Annual \\\"Report\\\"\"\nauthor: null\n",
+        ));
+}
+
+#[test]
+fn test_conformance_is_reported() {
+    use lopdf::{Document as LopdfDocument, Object, Stream, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("archived.pdf");
+    create_test_pdf(&input_path);
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let xmp = "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF><rdf:Description rdf:about=\"\" \
+               pdfaid:part=\"2\" pdfaid:conformance=\"A\" pdfuaid:part=\"1\"/></rdf:RDF></x:xmpmeta>";
+    let metadata = doc.add_object(Stream::new(
+        dictionary! { "Type" => "Metadata", "Subtype" => "XML" },
+        xmp.as_bytes().to_vec(),
+    ));
+    let tree = doc.add_object(dictionary! { "Type" => "StructTreeRoot" });
+    let catalog = doc.catalog_mut().unwrap();
+    catalog.set("Metadata", metadata);
+    catalog.set(
+        "MarkInfo",
+        dictionary! { "Marked" => Object::Boolean(true) },
+    );
+    catalog.set("StructTreeRoot", tree);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("metadata")
+        .arg(&input_path)
+        .arg("--format")
+        .arg("json")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "  \"pdfa\": \"PDF/A-2a\",\n  \"pdfua\": \"PDF/UA-1\",\n  \"tagged\": true,\n",
+        ));
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Tagged (structure tree): Yes\nClaimed conformance: PDF/A-2a, PDF/UA-1\n",
+        ));
+}
+
+#[test]
+fn test_check_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+
+    create_pages_pdf(&input_path, &["First page", "Second page"]);
+    let mut cmd = get_test_command();
+    cmd.arg("check")
+        .arg(&input_path)
+        .assert()
+        .code(0)
+        .stdout(predicate::str::ends_with(
+            "100.0% of 19 glyphs extracted (2 pages checked)\n",
+        ));
+
+    // Text in a font missing from the page resources is shown but not extracted
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 720 Td (Kept) Tj /F9 12 Tf (Lost) Tj ET",
+    );
+    let mut cmd = get_test_command();
+    cmd.arg("check")
+        .arg(&input_path)
+        .assert()
+        .code(5)
+        .stdout(predicate::str::contains(
+            "Page 1: 50.0% of 8 glyphs extracted\n",
+        ))
+        .stderr(predicate::str::contains(
+            "only 50.0% of the glyphs extracted, below --min-coverage 90",
+        ));
+    let mut cmd = get_test_command();
+    cmd.arg("check")
+        .arg(&input_path)
+        .args(["--min-coverage", "50"])
+        .assert()
+        .code(0);
+}
+
+#[test]
+fn test_validate_subcommand() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+
+    create_pages_pdf(&input_path, &["First page", "Second page"]);
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .assert()
+        .code(0)
+        .stdout(predicate::str::ends_with("OK (2 pages checked)\n"));
+
+    // A page without text is a warning, failing only with --deny-warnings
+    create_pages_pdf(&input_path, &["First page", ""]);
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .assert()
+        .code(0)
+        .stdout(predicate::str::contains(
+            "Page 2: warning: no text layer (scanned or image-only page)\n",
+        ))
+        .stdout(predicate::str::ends_with(
+            "OK with 1 warning (2 pages checked)\n",
+        ));
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .arg("--deny-warnings")
+        .assert()
+        .code(5);
+
+    // A page that cannot be extracted is an error
+    create_broken_page_pdf(&input_path);
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .assert()
+        .code(5)
+        .stdout(predicate::str::contains(
+            "Page 2: error: text cannot be extracted",
+        ))
+        .stdout(predicate::str::ends_with(
+            "FAILED with 1 error (3 pages checked)\n",
+        ))
+        .stderr(predicate::str::contains("Validation failed: 1 error"));
+
+    // A file that does not parse fails as a PDF error
+    fs::write(&input_path, b"%PDF-1.4\nnot really a PDF").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("validate").arg(&input_path).assert().code(4);
+}
+
+#[test]
+fn test_project_config_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let docs_dir = temp_dir.path().join("docs");
+    fs::create_dir(&docs_dir).unwrap();
+    let input_path = docs_dir.join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Body text.", "RESULTS"]);
+    fs::write(
+        temp_dir.path().join(".pdf2md.toml"),
+        "number-headings = true\n",
+    )
+    .unwrap();
+
+    // Found above the input's directory
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("1 INTRODUCTION"));
+
+    // A conflicting command-line option wins over the file
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strip-heading-numbers")
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(!content.contains("1 INTRODUCTION"));
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--no-config")
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(!content.contains("1 INTRODUCTION"));
+
+    // Mistakes in the file are reported with its path
+    fs::write(temp_dir.path().join(".pdf2md.toml"), "colour = true\n").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            ".pdf2md.toml: unknown setting `colour`",
+        ));
+}
+
+#[test]
+fn test_config_profiles() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Body text.", "RESULTS"]);
+
+    // --profile without a settings file is an error
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--profile")
+        .arg("manual")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("defining [profile.manual]"));
+
+    fs::write(
+        temp_dir.path().join(".pdf2md.toml"),
+        "normalize-headings = true\n\n[profile.manual]\nnumber-headings = true\n",
+    )
+    .unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.starts_with("# input\n"));
+    assert!(!content.contains("1 INTRODUCTION"));
+
+    // The profile adds to the top-level settings
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--profile")
+        .arg("manual")
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.starts_with("# input\n"));
+    assert!(content.contains("1 INTRODUCTION"));
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--profile")
+        .arg("slides")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "no [profile.slides] section (profiles: manual)",
+        ));
+}
+
+#[test]
+fn test_heuristic_thresholds() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(
+        &input_path,
+        &["Rain today. Sun on Friday. Fog later.", "WEATHER OUTLOOK"],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("Rain today. Sun on Friday. Fog later."));
+    assert!(content.contains("## WEATHER OUTLOOK"));
+
+    // Short paragraphs for a newsletter; no headings longer than 10 characters
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--min-paragraph-chars")
+        .arg("8")
+        .arg("--max-heading-chars")
+        .arg("10")
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert!(content.contains("Rain today.\n\nSun on Friday.\n\nFog later."));
+    assert!(!content.contains("## WEATHER OUTLOOK"));
+}
+
+#[test]
+fn test_replace_rules_from_config() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["Copyright ACME Corp. Internal use"]);
+    fs::write(
+        temp_dir.path().join(".pdf2md.toml"),
+        r#"
+[[replace]]
+pattern = 'Copyright (\w+) Corp\.'
+replacement = "(c) $1"
+
+[[replace]]
+pattern = "ACME"
+replacement = "Acme"
+"#,
+    )
+    .unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    let content = fs::read_to_string(&output_path).unwrap();
+    assert_eq!(content, "(c) Acme Internal use");
+
+    fs::write(
+        temp_dir.path().join(".pdf2md.toml"),
+        "[[replace]]\npattern = \"(\"\nreplacement = \"\"\n",
+    )
+    .unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid [[replace]] pattern"));
+}
+
+#[test]
+fn test_glyph_map() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    let map_path = temp_dir.path().join("glyphs.toml");
+    create_pages_pdf(&input_path, &["Step one -- done"]);
+    fs::write(&map_path, "\"--\" = \"\u{2014}\"\n\"U+0064\" = \"D\"\n").unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--glyph-map")
+        .arg(&map_path)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Step one \u{2014} Done"
+    );
+
+    // A path in .pdf2md.toml is relative to that file
+    let project_dir = temp_dir.path().join("project");
+    fs::create_dir(&project_dir).unwrap();
+    fs::rename(&map_path, project_dir.join("glyphs.toml")).unwrap();
+    fs::write(
+        project_dir.join(".pdf2md.toml"),
+        "glyph-map = \"glyphs.toml\"\n",
+    )
+    .unwrap();
+    let project_input = project_dir.join("input.pdf");
+    fs::copy(&input_path, &project_input).unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&project_input)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Step one \u{2014} Done"
+    );
+
+    fs::write(project_dir.join("glyphs.toml"), "\"U+zz\" = \"x\"\n").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&project_input)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("`U+zz` is not a valid code point"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pre_and_post_hooks() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Draft text"]);
+
+    // The pre-hook sees the extracted text, so its changes are formatted
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--pre-hook")
+        .arg("sed 's/INTRODUCTION/PREFACE/'")
+        .arg("--post-hook")
+        .arg("sed 's/Draft/Final/'; printf '\\n<!-- %s -->' \"$(basename \"$PDF2MD_INPUT\")\"")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "## PREFACE\n\nFinal text\n<!-- input.pdf -->"
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--post-hook")
+        .arg("exit 7")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("--post-hook `exit 7` exited with"));
+}
+
+#[cfg(feature = "scripting")]
+#[test]
+fn test_script() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    let script_path = temp_dir.path().join("tidy.rhai");
+    create_pages_pdf(&input_path, &["INTRODUCTION", "Draft text"]);
+    fs::write(
+        &script_path,
+        r#"fn transform(blocks) {
+            for i in 0..blocks.len() {
+                if blocks[i].kind == "heading" { blocks[i].text = "Preface"; }
+            }
+            blocks
+        }"#,
+    )
+    .unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--script")
+        .arg(&script_path)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "## Preface\n\nDraft text"
+    );
+}
+
+#[cfg(not(feature = "scripting"))]
+#[test]
+fn test_script_needs_feature() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_test_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--script")
+        .arg(temp_dir.path().join("tidy.rhai"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("the `scripting` feature"));
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_section_rules_place_chapters() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("guide.pdf");
+    let site_root = temp_dir.path().join("site");
+    create_pages_pdf(
+        &input_path,
+        &[
+            "USAGE",
+            "Run it from the shell.",
+            "APPENDIX A",
+            "Exit codes.",
+        ],
+    );
+    fs::write(
+        temp_dir.path().join(".pdf2md.toml"),
+        "[[section]]\nheading = \"APPENDIX *\"\npath = \"appendices/{slug}.md\"\n",
+    )
+    .unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&site_root)
+        .arg("--site")
+        .arg("mkdocs")
+        .assert()
+        .success();
+
+    let docs = site_root.join("docs");
+    let index = fs::read_to_string(docs.join("index.md")).unwrap();
+    assert!(index.contains("- [USAGE](01-usage.md)"));
+    assert!(index.contains("- [APPENDIX A](appendices/appendix-a.md)"));
+    let appendix = fs::read_to_string(docs.join("appendices/appendix-a.md")).unwrap();
+    assert_eq!(appendix, "# APPENDIX A\n\nExit codes.");
+}
+
+#[test]
+fn test_localized_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_broken_page_pdf(&input_path);
+
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .arg("--lang")
+        .arg("de")
+        .assert()
+        .code(5)
+        .stdout(predicate::str::contains(
+            "Seite 2: Fehler: Text kann nicht extrahiert werden",
+        ))
+        .stdout(predicate::str::contains(
+            "FEHLGESCHLAGEN mit 1 Fehler (3 Seiten geprüft)",
+        ))
+        .stderr(predicate::str::starts_with(
+            "Fehler: Prüfung fehlgeschlagen: 1 Fehler",
+        ));
+
+    let mut cmd = get_test_command();
+    cmd.env("PDF2MD_LANG", "es_ES.UTF-8")
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--dry-run")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("=== Vista previa del PDF ==="))
+        .stdout(predicate::str::contains("Páginas: 3"));
+
+    // The notices left in the Markdown are translated too
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--lang")
+        .arg("de")
+        .assert()
+        .success();
+    assert!(
+        fs::read_to_string(&output_path)
+            .unwrap()
+            .contains("> \u{26a0}\u{fe0f} Seite 2 konnte nicht extrahiert werden")
+    );
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--lang")
+        .arg("de")
+        .arg("--strict")
+        .arg("--keep-partial")
+        .arg("--force")
+        .assert()
+        .failure();
+    assert!(
+        fs::read_to_string(&output_path)
+            .unwrap()
+            .ends_with("Die Konvertierung endete bei Seite 2 von 3; der Rest des Dokuments fehlt")
+    );
+}
+
+#[test]
+fn test_no_color() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    let output_path = temp_dir.path().join("output.md");
+    create_pages_pdf(&input_path, &["First page", ""]);
+
+    // Output to a pipe is never coloured; the flag is taken by every command
+    let mut cmd = get_test_command();
+    cmd.arg("validate")
+        .arg(&input_path)
+        .arg("--no-color")
+        .arg("--deny-warnings")
+        .assert()
+        .code(5)
+        .stderr(predicate::str::starts_with("Error: "));
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--dry-run")
+        .arg("--no-color")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\n=== PDF Preview ===\n"))
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn test_confirm_asks_after_preview() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_path = temp_dir.path().join("report.md");
+    create_pages_pdf(&input_path, &["Quarterly report", "Appendix"]);
+
+    // Anything but yes, including no answer at all, converts nothing
+    for answer in ["n\n", ""] {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--confirm")
+            .write_stdin(answer)
+            .assert()
+            .success()
+            .stdout(predicate::str::contains("=== PDF Preview ==="))
+            .stderr(predicate::str::contains(format!(
+                "Convert 2 pages to {}? [y/N] ",
+                output_path.display()
+            )))
+            .stderr(predicate::str::contains("nothing was written"));
+        assert!(!output_path.exists());
+    }
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--confirm")
+        .write_stdin("y\n")
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "Quarterly report\n\nAppendix"
+    );
+}
+
+#[test]
+fn test_portfolio_converts_each_document() {
+    let temp_dir = TempDir::new().unwrap();
+    let budget = temp_dir.path().join("budget.pdf");
+    let minutes = temp_dir.path().join("minutes.pdf");
+    create_pages_pdf(&budget, &["Budget 2024"]);
+    create_pages_pdf(&minutes, &["Minutes of the board"]);
+    let input_path = temp_dir.path().join("board.pdf");
+    let output_path = temp_dir.path().join("board.md");
+    create_portfolio_pdf(
+        &input_path,
+        &[("Budget 2024.pdf", &budget), ("Minutes.pdf", &minutes)],
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "Note: a portfolio of 2 PDFs, each converted to its own file: Budget 2024.pdf, Minutes.pdf",
+        ));
+    assert_eq!(
+        fs::read_to_string(&output_path).unwrap(),
+        "# board\n\n\
+         - [Budget 2024.pdf](board/Budget%202024.md)\n\
+         - [Minutes.pdf](board/Minutes.md)\n"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("board/Budget 2024.md")).unwrap(),
+        "Budget 2024"
+    );
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("board/Minutes.md")).unwrap(),
+        "Minutes of the board"
+    );
+
+    // A document that fails does not stop the others
+    create_broken_page_pdf(&minutes);
+    create_portfolio_pdf(
+        &input_path,
+        &[("Budget 2024.pdf", &budget), ("Minutes.pdf", &minutes)],
+    );
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strict")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("Minutes.pdf: Error:"))
+        .stderr(predicate::str::contains("Error: 1 of 2 files failed"));
+    assert!(
+        !fs::read_to_string(&output_path)
+            .unwrap()
+            .contains("Minutes")
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_notify_cmd_receives_summary() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_path = temp_dir.path().join("report.md");
+    let payload_path = temp_dir.path().join("payload.json");
+    create_test_pdf(&input_path);
+    let notify_cmd = format!("cat > '{}'", payload_path.display());
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--notify-cmd")
+        .arg(&notify_cmd)
+        .assert()
+        .success();
+    let payload = fs::read_to_string(&payload_path).unwrap();
+    assert!(
+        payload.starts_with("{\"event\":\"finished\",\"status\":\"succeeded\",\"exit_code\":0,"),
+        "{}",
+        payload
+    );
+    assert!(payload.contains("\"files\":1,\"failed\":0,"), "{}", payload);
+
+    // A failed conversion is reported too, and an undeliverable notification
+    // does not change the exit status
+    fs::write(&input_path, b"Not a PDF file").unwrap();
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--notify-cmd")
+        .arg(&notify_cmd)
+        .arg("--notify-url")
+        .arg("http://127.0.0.1:9/hook")
+        .assert()
+        .code(4)
+        .stderr(predicate::str::contains("Warning: --notify-url"));
+    let payload = fs::read_to_string(&payload_path).unwrap();
+    assert!(
+        payload.contains("\"status\":\"failed\",\"exit_code\":4,"),
+        "{}",
+        payload
+    );
+}
+
+#[test]
+fn test_check_compares_with_existing_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_path = temp_dir.path().join("report.md");
+    create_pages_pdf(&input_path, &["Quarterly report", "Appendix"]);
+
+    let check = || {
+        let mut cmd = get_test_command();
+        cmd.arg("-i")
+            .arg(&input_path)
+            .arg("--provenance")
+            .arg("--check")
+            .assert()
+    };
+    check().code(5).stderr(predicate::str::contains(format!(
+        "{} does not exist",
+        output_path.display()
+    )));
+    assert!(!output_path.exists());
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--provenance")
+        .assert()
+        .success();
+    // The time of the committed conversion is kept
+    let committed = fs::read_to_string(&output_path)
+        .unwrap()
+        .replace("converted: 20", "converted: 19");
+    fs::write(&output_path, &committed).unwrap();
+    check().success();
+
+    fs::write(&output_path, committed.replace("Appendix", "Annex")).unwrap();
+    check()
+        .code(5)
+        .stderr(predicate::str::contains(format!(
+            "{} is out of date with {} from line 12 (1 line changed)",
+            output_path.display(),
+            input_path.display()
+        )))
+        .stderr(predicate::str::contains("\n  - Annex\n  + Appendix"));
+    assert!(fs::read_to_string(&output_path).unwrap().ends_with("Annex"));
+}
+
+#[test]
+fn test_output_dir_names_output_after_input() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_dir = temp_dir.path().join("out");
+    create_pages_pdf(&input_path, &["Quarterly report"]);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .assert()
+        .success();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("report.md")).unwrap(),
+        "Quarterly report"
+    );
+
+    // It stands in for -o, and cannot be combined with it
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--output-dir")
+        .arg(&output_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("other.md"))
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("cannot be used with"));
+}
+
+#[test]
+fn test_batch_report() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("pdfs");
+    let output_dir = temp_dir.path().join("md");
+    fs::create_dir_all(&input_dir).unwrap();
+    create_pages_pdf(&input_dir.join("a.pdf"), &["Alpha text here", "More"]);
+    create_broken_page_pdf(&input_dir.join("broken.pdf"));
+
+    let csv_path = temp_dir.path().join("report.csv");
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--strict")
+        .arg("--batch-report")
+        .arg(&csv_path)
+        .assert()
+        .code(6);
+    let csv = fs::read_to_string(&csv_path).unwrap();
+    let rows: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        rows[0],
+        "input,output,status,pages,words,warnings,seconds,bytes,error"
+    );
+    let a: Vec<&str> = rows[1].split(',').collect();
+    assert!(a[0].ends_with("a.pdf"));
+    assert_eq!(a[2..6], ["ok", "2", "4", "0"]);
+    assert_eq!(a[7], "21");
+    let broken: Vec<&str> = rows[2].split(',').collect();
+    assert!(broken[0].ends_with("broken.pdf"));
+    assert_eq!(broken[2..6], ["failed", "", "", ""]);
+    assert!(broken[8].starts_with("PDF error: "));
+
+    let json_path = temp_dir.path().join("report.json");
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--batch-report")
+        .arg(&json_path)
+        .assert()
+        .success();
+    let json = fs::read_to_string(&json_path).unwrap();
+    assert!(json.contains("\"status\": \"warnings\""));
+    assert!(json.contains("\"words\": 4"));
+
+    // It needs a directory input
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(input_dir.join("a.pdf"))
+        .arg("-o")
+        .arg(temp_dir.path().join("a.md"))
+        .arg("--batch-report")
+        .arg(&json_path)
+        .assert()
+        .code(1);
+}
+
+#[test]
+fn test_directory_input_converts_each_pdf() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_dir = temp_dir.path().join("pdfs");
+    let output_dir = temp_dir.path().join("md");
+    fs::create_dir_all(input_dir.join("2024")).unwrap();
+    create_pages_pdf(&input_dir.join("a.pdf"), &["Alpha text"]);
+    create_pages_pdf(&input_dir.join("2024/b.pdf"), &["Beta text"]);
+    create_broken_page_pdf(&input_dir.join("2024/broken.pdf"));
+    fs::write(input_dir.join("notes.txt"), "not a PDF").unwrap();
+
+    // Without --recursive only the top level is converted
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--jobs")
+        .arg("2")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Converted 1 of 1 files"));
+    assert_eq!(
+        fs::read_to_string(output_dir.join("a.md")).unwrap(),
+        "Alpha text"
+    );
+    assert!(!output_dir.join("2024").exists());
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_dir)
+        .arg("-o")
+        .arg(&output_dir)
+        .arg("--recursive")
+        .arg("--strict")
+        .assert()
+        .code(6)
+        .stderr(predicate::str::contains("broken.pdf: Error:"))
+        .stderr(predicate::str::contains("Converted 2 of 3 files"))
+        .stderr(predicate::str::contains("Error: 1 of 3 files failed"));
+    assert_eq!(
+        fs::read_to_string(output_dir.join("2024/b.md")).unwrap(),
+        "Beta text"
+    );
+    assert!(!output_dir.join("2024/broken.md").exists());
+
+    // Every file failing is told apart from some failing
+    let broken_dir = temp_dir.path().join("broken");
+    fs::create_dir(&broken_dir).unwrap();
+    create_broken_page_pdf(&broken_dir.join("broken.pdf"));
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&broken_dir)
+        .arg("-o")
+        .arg(temp_dir.path().join("broken-out"))
+        .arg("--strict")
+        .assert()
+        .code(7)
+        .stderr(predicate::str::contains("Error: 1 of 1 files failed"));
+}
+
+#[test]
+fn test_stream_cache_does_not_change_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    create_test_pdf(&input_path);
+
+    let convert = |cache_mb: &str| {
+        let output_path = temp_dir.path().join(format!("cache-{}.md", cache_mb));
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .arg("--stream-cache-mb")
+            .arg(cache_mb)
+            .assert()
+            .success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+    let cached = convert("64");
+    assert!(cached.contains("Test PDF"));
+    assert_eq!(cached, convert("0"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_page_by_page_output_matches_whole_document() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("pages.pdf");
+    let pages: Vec<String> = (1..=12)
+        .map(|n| format!("SECTION {}", n))
+        .chain((1..=12).map(|n| format!("Body text of page {}.", n)))
+        .collect();
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    create_pages_pdf(&input_path, &pages);
+
+    // A post-hook needs the whole document, so the pages are not streamed
+    let convert = |name: &str, flags: &[&str]| {
+        let output_path = temp_dir.path().join(name);
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .args(flags)
+            .assert()
+            .success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+    let streamed = convert("streamed.md", &[]);
+    assert!(streamed.contains("## SECTION 12\n\nBody text of page 1."));
+    assert_eq!(streamed, convert("whole.md", &["--post-hook", "cat"]));
+    assert!(!temp_dir.path().join("streamed.md.partial").exists());
+}
+
+#[test]
+fn test_failed_conversion_keeps_earlier_output() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("broken.pdf");
+    let output_path = temp_dir.path().join("broken.md");
+    create_broken_page_pdf(&input_path);
+    fs::write(&output_path, "Earlier output").unwrap();
+
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--strict")
+        .assert()
+        .failure();
+    assert_eq!(fs::read_to_string(&output_path).unwrap(), "Earlier output");
+    assert!(!temp_dir.path().join("broken.md.partial").exists());
+}
+
+#[test]
+fn test_timings_report() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    create_test_pdf(&input_path);
+
+    // Page by page, and in one piece
+    for flags in [&[][..], &["--number-headings"][..]] {
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(temp_dir.path().join("output.md"))
+            .arg("--timings")
+            .args(flags)
+            .assert()
+            .success()
+            .stderr(predicate::str::contains("Time spent ("))
+            .stderr(predicate::str::contains("  extraction "))
+            .stderr(predicate::str::contains("  formatting "))
+            .stderr(predicate::str::contains("Slowest pages:\n  Page 1 "));
+    }
+
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.md"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Time spent").not());
+}
+
+#[test]
+fn test_low_memory_output_matches_default() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("figure.pdf");
+    create_image_pdf(&input_path);
+
+    let convert = |name: &str, flags: &[&str]| {
+        let output_path = temp_dir.path().join(name).join("figure.md");
+        get_test_command()
+            .arg("-i")
+            .arg(&input_path)
+            .arg("-o")
+            .arg(&output_path)
+            .args(flags)
+            .assert()
+            .success();
+        fs::read_to_string(&output_path).unwrap()
+    };
+    // Without --images the image data is not loaded at all
+    assert_eq!(convert("low", &["--low-memory"]), convert("default", &[]));
+    assert_eq!(
+        convert("low-images", &["--low-memory", "--images"]),
+        convert("default-images", &["--images"])
+    );
+    assert_eq!(
+        fs::read(temp_dir.path().join("low-images/images/page-1-image-1.png")).unwrap(),
+        fs::read(
+            temp_dir
+                .path()
+                .join("default-images/images/page-1-image-1.png")
+        )
+        .unwrap()
+    );
+}
+
+#[test]
+fn test_low_memory_rejects_whole_document_options() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    create_test_pdf(&input_path);
+
+    get_test_command()
+        .arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(temp_dir.path().join("output.md"))
+        .arg("--low-memory")
+        .arg("--chat")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(
+            "--low-memory writes pages as they are extracted, but --chat needs the whole document",
+        ));
+}
+
+#[test]
+fn test_rpc_mode() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("input.pdf");
+    create_pages_pdf(&input_path, &["First page", "Second page"]);
+    let output_path = temp_dir.path().join("out/output.md");
+    let request = |id: &str, method: &str, params: String| {
+        format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":{},\"method\":\"{}\",\"params\":{{{}}}}}\n",
+            id, method, params
+        )
+    };
+    let input = format!("\"input\":{:?}", input_path.display().to_string());
+    let requests = request("1", "info", input.clone())
+        + &request(
+            "\"two\"",
+            "convert",
+            format!(
+                "{},\"output\":{:?}",
+                input,
+                output_path.display().to_string()
+            ),
+        );
+
+    get_test_command()
+        .arg("--rpc")
+        .write_stdin(requests)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "{\"jsonrpc\":\"2.0\",\"id\":1,\"result\":{\"file\":",
+        ))
+        .stdout(predicate::str::contains("\"pages\":2,"))
+        .stdout(predicate::str::contains(
+            "\"id\":\"two\",\"result\":{\"output\":",
+        ))
+        .stdout(predicate::str::contains(
+            "\"images\":0,\"pages\":2,\"failed_pages\":[]}}",
+        ));
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("First page") && markdown.contains("Second page"));
+
+    // Nothing else may be given with --rpc
+    get_test_command()
+        .arg("--rpc")
+        .arg("-i")
+        .arg(&input_path)
+        .assert()
+        .failure();
+}
+
+#[test]
+fn test_pages_converts_only_those_pages() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("report.pdf");
+    let output_path = temp_dir.path().join("report.md");
+    create_pages_pdf(&input_path, &["First page", "Second page", "Third page"]);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--pages")
+        .arg("3,1")
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("First page"), "{}", markdown);
+    assert!(!markdown.contains("Second page"), "{}", markdown);
+    assert!(markdown.contains("Third page"), "{}", markdown);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--pages")
+        .arg("4-")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "pages 4- are not in the document, which has 3",
+        ));
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("--pages")
+        .arg("2-1")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("'2-1' ends before it starts"));
+}
+
+#[test]
+fn test_url_pages_are_read_a_range_at_a_time() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("archive.pdf");
+    let output_path = temp_dir.path().join("archive.md");
+    create_linearized_pdf(&input_path, &["First page", "Second page", "Third page"]);
+    let file = fs::read(&input_path).unwrap();
+    let size = file.len();
+    let (url, sent) = serve(file);
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(format!("{}/scans/archive.pdf", url))
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--pages")
+        .arg("2")
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("Second page"), "{}", markdown);
+    assert!(!markdown.contains("First page"), "{}", markdown);
+    let read = sent.swap(0, std::sync::atomic::Ordering::SeqCst);
+    assert!(read < size / 2, "read {} of {} bytes", read, size);
+
+    // Without --pages the file is downloaded, and named after the URL
+    let mut cmd = get_test_command();
+    cmd.current_dir(temp_dir.path())
+        .arg("-i")
+        .arg(format!("{}/scans/archive.pdf?token=secret", url))
+        .arg("--force")
+        .assert()
+        .success();
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("First page"), "{}", markdown);
+    assert!(sent.load(std::sync::atomic::Ordering::SeqCst) >= size);
+}
+
+#[test]
+fn test_leaves_out_redacted_text() {
+    use lopdf::{Document as LopdfDocument, Object, dictionary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("redacted.pdf");
+    let output_path = temp_dir.path().join("redacted.md");
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 700 Td (Patient:) Tj ET\n\
+         BT /F1 12 Tf 130 700 Td (John Smith) Tj ET\n\
+         0 g 128 696 72 16 re f\n\
+         BT /F1 12 Tf 72 680 Td (SSN 123-45-6789) Tj ET\n\
+         BT /F1 12 Tf 72 660 Td (Visit on Monday.) Tj ET\n",
+    );
+    let mut doc = LopdfDocument::load(&input_path).unwrap();
+    let page_id = doc.page_iter().next().unwrap();
+    let redact_id = doc.add_object(dictionary! {
+        "Type" => "Annot",
+        "Subtype" => "Redact",
+        "Rect" => vec![70.into(), 676.into(), 180.into(), 692.into()],
+    });
+    doc.get_object_mut(page_id)
+        .and_then(Object::as_dict_mut)
+        .unwrap()
+        .set("Annots", vec![redact_id.into()]);
+    doc.save(&input_path).unwrap();
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .arg("--sidecar")
+        .arg("yaml")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("page(s) 1 were never applied"))
+        .stderr(predicate::str::contains("painted over with a dark box"));
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("Patient:"));
+    assert!(markdown.contains("Visit on Monday."));
+    assert!(!markdown.contains("John Smith"));
+    assert!(!markdown.contains("123-45-6789"));
+    let sidecar = fs::read_to_string(temp_dir.path().join("redacted.meta.yaml")).unwrap();
+    assert!(sidecar.contains("redactions marked on page(s) 1 were never applied"));
+}
+
+#[test]
+fn test_leaves_out_text_under_a_drawn_box() {
+    let temp_dir = TempDir::new().unwrap();
+    let input_path = temp_dir.path().join("boxed.pdf");
+    let output_path = temp_dir.path().join("boxed.md");
+    // The box is a path, not a rectangle (`re`)
+    create_content_pdf(
+        &input_path,
+        "BT /F1 12 Tf 72 100 Td (Name: SECRETNAME) Tj ET\n\
+         0 g 110 96 m 210 96 l 210 112 l 110 112 l h f\n\
+         BT /F1 12 Tf 72 80 Td (Visible text.) Tj ET\n",
+    );
+
+    let mut cmd = get_test_command();
+    cmd.arg("-i")
+        .arg(&input_path)
+        .arg("-o")
+        .arg(&output_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("painted over with a dark box"));
+    let markdown = fs::read_to_string(&output_path).unwrap();
+    assert!(markdown.contains("Visible text."), "{}", markdown);
+    assert!(!markdown.contains("SECRETNAME"), "{}", markdown);
+}